@@ -7,11 +7,9 @@
 )]
 
 use embassy_executor::Spawner;
-use embassy_futures::select::select;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
-use embassy_time::Timer;
-use embedded_can::Frame;
+use embedded_can::{ExtendedId, Frame};
 use embedded_can::Id::{Extended, Standard};
 use esp_backtrace as _;
 use esp_hal::clock::CpuClock;
@@ -20,6 +18,7 @@ use esp_hal::timer::timg::TimerGroup;
 use esp_hal::twai::{EspTwaiFrame, StandardId, TwaiMode, TwaiRx, TwaiTx};
 use esp_hal::{twai, Async};
 use esp_println::logger;
+use zencan_node::async_node::AsyncNode;
 use zencan_node::Callbacks;
 use zencan_node::{common::NodeId, Node};
 
@@ -31,7 +30,6 @@ mod zencan {
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
 
-static CANOPEN_PROCESS_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 static CANOPEN_TX_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
 #[esp_hal_embassy::main]
@@ -65,7 +63,6 @@ async fn main(spawner: Spawner) {
     let serial = u32::from_be_bytes(last_mac_bytes);
 
     zencan::OBJECT1018.set_serial(serial);
-    zencan::NODE_MBOX.set_process_notify_callback(&notify_canopen_process_task);
     zencan::NODE_MBOX.set_transmit_notify_callback(&notify_canopen_tx_task);
 
     spawner.spawn(twai_rx_task(twai_rx)).unwrap();
@@ -73,10 +70,6 @@ async fn main(spawner: Spawner) {
     spawner.spawn(canopen_process_task()).unwrap();
 }
 
-fn notify_canopen_process_task() {
-    CANOPEN_PROCESS_SIGNAL.signal(());
-}
-
 fn notify_canopen_tx_task() {
     CANOPEN_TX_SIGNAL.signal(());
 }
@@ -85,9 +78,15 @@ fn notify_canopen_tx_task() {
 async fn twai_tx_task(mut twai_tx: TwaiTx<'static, Async>) {
     loop {
         while let Some(msg) = zencan::NODE_MBOX.next_transmit_message() {
-            let frame =
-                EspTwaiFrame::new(StandardId::new(msg.id.raw() as u16).unwrap(), msg.data())
-                    .unwrap();
+            let frame = match msg.id {
+                zencan_node::common::messages::CanId::Extended(id) => {
+                    EspTwaiFrame::new(ExtendedId::new(id).unwrap(), msg.data())
+                }
+                zencan_node::common::messages::CanId::Std(id) => {
+                    EspTwaiFrame::new(StandardId::new(id).unwrap(), msg.data())
+                }
+            }
+            .unwrap();
             if let Err(e) = twai_tx.transmit_async(&frame).await {
                 log::error!("Error sending CAN message: {e:?}");
             }
@@ -101,19 +100,14 @@ async fn twai_tx_task(mut twai_tx: TwaiTx<'static, Async>) {
 #[embassy_executor::task]
 async fn canopen_process_task() {
     let callbacks = Callbacks::default();
-    let mut node = Node::new(
+    let node = Node::new(
         NodeId::Unconfigured,
         callbacks,
         &zencan::NODE_MBOX,
         &zencan::NODE_STATE,
         &zencan::OD_TABLE,
     );
-    loop {
-        select(CANOPEN_PROCESS_SIGNAL.wait(), Timer::after_millis(10)).await;
-        let now_us = embassy_time::Instant::now().as_micros();
-
-        node.process(now_us);
-    }
+    AsyncNode::new(node, &zencan::NODE_MBOX).run().await
 }
 
 #[embassy_executor::task]
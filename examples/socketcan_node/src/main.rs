@@ -14,7 +14,7 @@ use zencan_node::{
         traits::{AsyncCanReceiver, AsyncCanSender},
         NodeId,
     },
-    Callbacks,
+    Callbacks, NotifyCallback,
 };
 
 #[cfg(target_os = "linux")]
@@ -113,12 +113,11 @@ async fn main() {
 
     let (mut tx, mut rx) = open_socketcan(&args.socket).unwrap();
 
-    // Node requires callbacks be static, so use Box::leak to make static ref from closure on heap
-    let process_notify = Box::leak(Box::new(tokio::sync::Notify::new()));
-    let process_notify_cb = Box::leak(Box::new(|| {
-        process_notify.notify_one();
+    let process_notify = Arc::new(tokio::sync::Notify::new());
+    let process_notify_clone = process_notify.clone();
+    zencan::NODE_MBOX.set_process_notify_callback(NotifyCallback::from_fn(move || {
+        process_notify_clone.notify_one();
     }));
-    zencan::NODE_MBOX.set_process_notify_callback(process_notify_cb);
 
     // Spawn a task to receive messages
     tokio::spawn(async move {
@@ -141,10 +140,9 @@ async fn main() {
     tokio::spawn(async move {
         let notify = Arc::new(tokio::sync::Notify::new());
         let notify_clone = notify.clone();
-        let transmit_notify_callback = Box::leak(Box::new(move || {
+        zencan::NODE_MBOX.set_transmit_notify_callback(NotifyCallback::from_fn(move || {
             notify_clone.notify_waiters();
         }));
-        zencan::NODE_MBOX.set_transmit_notify_callback(transmit_notify_callback);
         loop {
             notify.notified().await;
             while let Some(msg) = zencan::NODE_MBOX.next_transmit_message() {
@@ -30,9 +30,9 @@ use panic_probe as _;
 use rtt_target::{self as _, rtt_init, set_defmt_channel};
 
 use zencan_node::{
-    Callbacks, Node,
+    Callbacks, Node, NotifyCallback,
     common::NodeId,
-    object_dict::{ODEntry, ObjectAccess},
+    object_dict::ODEntry,
     restore_stored_comm_objects, restore_stored_objects,
 };
 
@@ -124,11 +124,6 @@ fn store_node_config(flash: &mut Stm32g0Flash, id: NodeId) {
     }
 }
 
-/// Callback to notify CAN task that there are messages to be processed
-fn notify_can_task() {
-    CAN_NOTIFY.notify();
-}
-
 /// Read the node ID from flash
 fn read_saved_node_id(flash: &mut Stm32g0Flash) -> NodeId {
     if let Some(sections) = persist::load_sections(&flash.unlock()) {
@@ -364,7 +359,9 @@ fn main() -> ! {
     );
 
     // Register handler for waking process task
-    zencan::NODE_MBOX.set_process_notify_callback(&notify_can_task);
+    // Notify CAN task when there are messages to be processed
+    zencan::NODE_MBOX
+        .set_process_notify_callback(NotifyCallback::with_context(Notify::notify, &CAN_NOTIFY));
 
     // Register handler for CAN frame transmit notice
     zencan::NODE_MBOX.set_transmit_notify_callback(&transmit_notify_handler);
@@ -439,32 +436,25 @@ async fn main_task() -> Infallible {
         let adc_values = [read_adc(0), read_adc(1), read_adc(2), read_adc(3)];
 
         // Store values to raw and scaled objects
+        //
+        // The `set_and_notify` setters also set the event flags on the updated objects. When the
+        // objects are mapped to TPDOs configured for async transmission, this triggers the
+        // transmission on next call to process().
         for i in 0..4 {
             let raw_value = adc_values[i];
-            OBJECT2000.set(i, adc_values[i]).unwrap();
+            OBJECT2000.set_and_notify(i, adc_values[i]).unwrap();
             let scale_num = zencan::OBJECT2200.get(i).unwrap() as i32;
             let scale_den = zencan::OBJECT2201.get(i).unwrap() as i32;
             let offset = zencan::OBJECT2202.get(i).unwrap() as i32;
             let scaled_value = ((raw_value as i32 + offset).saturating_mul(scale_num)) / scale_den;
 
-            OBJECT2001.set(i, scaled_value as i32).unwrap();
+            OBJECT2001.set_and_notify(i, scaled_value as i32).unwrap();
             OBJECT2002
-                .set(
+                .set_and_notify(
                     i,
                     scaled_value.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
                 )
                 .unwrap();
-
-            // For array objects, sub0 contains the size of the array, and the first array element
-            // is stored at index 1
-            let sub_idx = i as u8 + 1;
-
-            // Set the event flags on the updated objects. When the objects are mapped to TPDOs
-            // configured for async transmission, this triggers the transmission on next call to
-            // process().
-            OBJECT2000.set_event_flag(sub_idx).unwrap();
-            OBJECT2001.set_event_flag(sub_idx).unwrap();
-            OBJECT2002.set_event_flag(sub_idx).unwrap();
         }
 
         // Notify can task that there is something new to process
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zencan_common::{lss::LssIdentity, NodeId};
+use zencan_node::fuzz::LssSlaveHarness;
+
+fuzz_target!(|data: &[u8]| {
+    let identity = LssIdentity {
+        vendor_id: 1,
+        product_code: 2,
+        revision: 3,
+        serial: 4,
+    };
+    let mut harness = LssSlaveHarness::new(identity, NodeId::Unconfigured, true);
+
+    for chunk in data.chunks_exact(8) {
+        harness.feed(chunk).ok();
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zencan_common::NodeId;
+use zencan_node::fuzz::NmtHarness;
+
+fuzz_target!(|data: &[u8]| {
+    let mut harness = NmtHarness::new(NodeId::new(1).unwrap());
+
+    for chunk in data.chunks(2) {
+        harness.feed(chunk).ok();
+    }
+});
@@ -0,0 +1,97 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zencan_common::{
+    messages::CanId,
+    objects::{AccessType, DataType, ObjectCode, SubInfo},
+};
+use zencan_node::{
+    fuzz::SdoServerHarness,
+    object_dict::{
+        ByteField, ConstField, NullTermByteField, ODEntry, ProvidesSubObjects, ScalarField,
+        SubObjectAccess,
+    },
+    SDO_BUFFER_SIZE,
+};
+
+/// A record with a string larger than the SDO buffer, a short octet string, and a scalar, so that
+/// expedited, segmented, and block transfers are all reachable
+struct Object2000 {
+    sub1: NullTermByteField<1200>,
+    sub2: ByteField<20>,
+    sub3: ScalarField<u32>,
+}
+
+impl ProvidesSubObjects for Object2000 {
+    fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+        match sub {
+            0 => Some((
+                SubInfo::MAX_SUB_NUMBER,
+                const { &ConstField::new(3u8.to_le_bytes()) },
+            )),
+            1 => Some((
+                SubInfo {
+                    size: self.sub1.len(),
+                    data_type: DataType::VisibleString,
+                    access_type: AccessType::Rw,
+                    ..Default::default()
+                },
+                &self.sub1,
+            )),
+            2 => Some((
+                SubInfo {
+                    size: self.sub2.len(),
+                    data_type: DataType::OctetString,
+                    access_type: AccessType::Rw,
+                    ..Default::default()
+                },
+                &self.sub2,
+            )),
+            3 => Some((
+                SubInfo {
+                    size: 4,
+                    data_type: DataType::UInt32,
+                    access_type: AccessType::Rw,
+                    ..Default::default()
+                },
+                &self.sub3,
+            )),
+            _ => None,
+        }
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Record
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let object = Object2000 {
+        sub1: NullTermByteField::new([0; 1200]),
+        sub2: ByteField::new([0; 20]),
+        sub3: ScalarField::default(),
+    };
+    let od = [ODEntry {
+        index: 0x2000,
+        data: &object,
+    }];
+    let mut buffer = [0; SDO_BUFFER_SIZE];
+    let mut harness = SdoServerHarness::new(&mut buffer, &od);
+
+    for chunk in data.chunks_exact(10) {
+        // Mostly requests from one client, with an occasional interleaved second client
+        let source = if chunk[0] & 0x7 == 0 {
+            CanId::Std(0x602)
+        } else {
+            CanId::Std(0x601)
+        };
+        // Frames are usually handled in the same process call, but large jumps in time reach
+        // the timeouts
+        let elapsed_us = (chunk[1] as u32) << (chunk[0] >> 4);
+        harness.feed(source, &chunk[2..]).unwrap();
+        if chunk[0] & 0x8 == 0 {
+            harness.process(elapsed_us, &mut |_| {});
+        }
+        while harness.next_response().is_some() {}
+    }
+});
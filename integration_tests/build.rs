@@ -5,6 +5,13 @@ fn main() {
         eprintln!("Error building node from example1.toml: {}", e);
         std::process::exit(1);
     }
+    // The same config can be built more than once, to create independent nodes in one program
+    if let Err(e) =
+        zencan_build::build_node_from_device_config("EXAMPLE1_CH2", "device_configs/example1.toml")
+    {
+        eprintln!("Error building node from example1.toml: {}", e);
+        std::process::exit(1);
+    }
     if let Err(e) = zencan_build::build_node_from_device_config(
         "EXAMPLE2",
         "device_configs/example2_bootloader_app.toml",
@@ -22,4 +29,28 @@ fn main() {
         eprintln!("Error building node from example3_bootloader.toml: {}", e);
         std::process::exit(1);
     }
+    if let Err(e) = zencan_build::build_node_from_device_config(
+        "EXAMPLE4",
+        "device_configs/example4_dual_bank.toml",
+    ) {
+        eprintln!("Error building node from example4_dual_bank.toml: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = zencan_build::build_node_from_device_config(
+        "EXAMPLE5",
+        "device_configs/example5_connection_set.toml",
+    ) {
+        eprintln!(
+            "Error building node from example5_connection_set.toml: {}",
+            e
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = zencan_build::build_node_from_device_config(
+        "EXAMPLE6",
+        "device_configs/example6_compact.toml",
+    ) {
+        eprintln!("Error building node from example6_compact.toml: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -1,19 +1,34 @@
 pub mod object_dict1 {
     zencan_node::include_modules!(EXAMPLE1);
 }
+// A second instance of the example1 node, as used by a device with one node per CAN bus
+pub mod object_dict1_ch2 {
+    zencan_node::include_modules!(EXAMPLE1_CH2);
+}
 pub mod object_dict2 {
     zencan_node::include_modules!(EXAMPLE2);
 }
 pub mod object_dict3 {
     zencan_node::include_modules!(EXAMPLE3);
 }
-pub mod sim_bus;
+pub mod object_dict4 {
+    zencan_node::include_modules!(EXAMPLE4);
+}
+pub mod object_dict5 {
+    zencan_node::include_modules!(EXAMPLE5);
+}
+pub mod object_dict6 {
+    zencan_node::include_modules!(EXAMPLE6);
+}
 pub mod utils;
 
 pub mod prelude {
-    pub use super::sim_bus::{SimBus, SimBusReceiver, SimBusSender};
-    pub use super::utils::{get_sdo_client, test_with_background_process, BusLogger, TestContext};
+    pub use super::utils::get_sdo_client;
     pub use zencan_client::{RawAbortCode, SdoClientError};
     pub use zencan_common::{sdo::AbortCode, NodeId};
+    pub use zencan_node::test_util::{
+        test_with_background_buses, test_with_background_process, BusLogger, SimBus,
+        SimBusReceiver, SimBusSender, TestContext,
+    };
     pub use zencan_node::{Callbacks, Node};
 }
@@ -0,0 +1,149 @@
+//! Tests for backing up and restoring node parameters
+use std::sync::Arc;
+
+use integration_tests::{object_dict1::*, prelude::*};
+use serial_test::serial;
+use zencan_client::{
+    backup::{BackupError, ParameterBackup, RestoreOptions},
+    common::{device_config::DeviceConfig, messages::CanId},
+    nmt_master::NmtMaster,
+    ObjectInfo,
+};
+
+const NODE_ID: u8 = 1;
+
+#[serial]
+#[tokio::test]
+async fn test_backup_restore() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+    let info = ObjectInfo::from_device_config(&config);
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let backup = ParameterBackup::read(&mut client, &info).await.unwrap();
+        assert_eq!(
+            client.read_identity().await.unwrap(),
+            backup.identity.unwrap()
+        );
+        let params: Vec<_> = backup.parameters.iter().map(|p| (p.index, p.sub)).collect();
+        // Persisted parameters are included, and others are not
+        assert!(params.contains(&(0x2000, 1)));
+        assert!(params.contains(&(0x2002, 0)));
+        assert!(params.contains(&(0x1A01, 1)));
+        assert!(!params.contains(&(0x2003, 0)));
+        assert!(!params.contains(&(0x1018, 4)));
+
+        // The backup survives a round trip through the file format
+        let backup = ParameterBackup::from_toml(&backup.to_toml()).unwrap();
+
+        // Change the parameters, including the configuration of a PDO which is in use
+        let original_tpdo = client.read_tpdo_config(1).await.unwrap();
+        let mut tpdo = original_tpdo.clone();
+        tpdo.cob_id = CanId::std(0x250);
+        tpdo.mappings.truncate(0);
+        client.configure_tpdo(1, &tpdo).await.unwrap();
+        client.write_u32(0x2000, 1, 0x5555).await.unwrap();
+        client
+            .write_visible_string(0x2002, 0, "changed")
+            .await
+            .unwrap();
+        nmt.nmt_start(NODE_ID).await.unwrap();
+        ctx.wait_for_process(2).await;
+
+        let options = RestoreOptions {
+            save: false,
+            ..Default::default()
+        };
+        backup.restore(&mut client, &options).await.unwrap();
+        assert_eq!(original_tpdo, client.read_tpdo_config(1).await.unwrap());
+        assert_eq!(
+            backup
+                .parameters
+                .iter()
+                .find(|p| (p.index, p.sub) == (0x2000, 1))
+                .unwrap()
+                .data,
+            client.upload(0x2000, 1).await.unwrap()
+        );
+        assert_eq!(
+            "Some String",
+            client.read_visible_string(0x2002, 0).await.unwrap()
+        );
+
+        // A changed value fails verification
+        let mut modified = backup.clone();
+        modified
+            .parameters
+            .iter_mut()
+            .find(|p| (p.index, p.sub) == (0x2002, 0))
+            .unwrap()
+            .data = b"other".to_vec();
+        let Err(BackupError::VerifyFailed { mismatches }) = modified.verify(&mut client).await
+        else {
+            panic!("Expected verification to fail");
+        };
+        assert_eq!(vec![(0x2002, 0)], mismatches);
+
+        // A backup from a different product is not restored, unless the check is disabled
+        let mut other = backup.clone();
+        other.identity.as_mut().unwrap().product_code += 1;
+        assert!(matches!(
+            other.restore(&mut client, &options).await,
+            Err(BackupError::DeviceMismatch { .. })
+        ));
+        let options = RestoreOptions {
+            check_identity: false,
+            save: false,
+        };
+        other.restore(&mut client, &options).await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_backup_object_info() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+    client.set_object_info(Arc::new(ObjectInfo::from_device_config(&config)));
+
+    let test_task = move |_ctx| async move {
+        // A backup which does not match the object info is rejected before anything is written
+        let backup =
+            ParameterBackup::from_toml("[[parameter]]\nindex = 0x2000\nsub = 1\ndata = \"0102\"\n")
+                .unwrap();
+        assert!(matches!(
+            backup
+                .restore(&mut client, &RestoreOptions::default())
+                .await,
+            Err(BackupError::Write {
+                index: 0x2000,
+                sub: 1,
+                source: SdoClientError::DownloadSizeMismatch { .. }
+            })
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
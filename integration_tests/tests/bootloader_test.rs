@@ -6,13 +6,80 @@ use std::{
     },
 };
 
-use zencan_common::constants::values::{BOOTLOADER_ERASE_CMD, BOOTLOADER_RESET_CMD};
-use zencan_node::BootloaderSectionCallbacks;
+use zencan_common::{
+    constants::values::{BOOTLOADER_ERASE_CMD, BOOTLOADER_RESET_CMD, BOOTLOADER_SWAP_CMD},
+    crc32::crc32,
+};
+use zencan_node::{BootloaderBank, BootloaderSectionCallbacks};
 
-use integration_tests::{object_dict2, object_dict3, prelude::*};
+use integration_tests::{object_dict2, object_dict3, object_dict4, prelude::*};
 
 const BOOTLOADER_INFO_INDEX: u16 = 0x5500;
 const BOOTLOADER_SECTION0_INDEX: u16 = 0x5510;
+const BOOTLOADER_SECTION1_INDEX: u16 = 0x5511;
+
+fn is_abort(result: Result<(), SdoClientError>, code: AbortCode) -> bool {
+    matches!(
+        result,
+        Err(SdoClientError::ServerAbort {
+            abort_code: RawAbortCode::Valid(abort_code),
+            ..
+        }) if abort_code == code
+    )
+}
+
+/// Storage callbacks for a section, which record the programmed data
+struct BootloaderCallbacks {
+    erase_flag: AtomicBool,
+    data: Mutex<RefCell<Vec<u8>>>,
+    finalize_flag: AtomicBool,
+}
+
+impl BootloaderCallbacks {
+    fn new() -> &'static Self {
+        Box::leak(Box::new(Self {
+            erase_flag: AtomicBool::new(false),
+            data: Mutex::new(RefCell::new(Vec::new())),
+            finalize_flag: AtomicBool::new(false),
+        }))
+    }
+
+    fn erase_flag(&self) -> bool {
+        self.erase_flag.load(Ordering::Relaxed)
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().borrow_mut().clone()
+    }
+
+    fn finalize_flag(&self) -> bool {
+        self.finalize_flag.load(Ordering::Relaxed)
+    }
+}
+
+impl BootloaderSectionCallbacks for BootloaderCallbacks {
+    fn erase(&self) -> bool {
+        self.erase_flag.store(true, Ordering::Relaxed);
+        self.data.lock().unwrap().borrow_mut().clear();
+        true
+    }
+
+    fn write(&self, data: &[u8]) {
+        let write_buffer = self.data.lock().unwrap();
+        write_buffer.borrow_mut().extend_from_slice(data);
+    }
+
+    fn finalize(&self) -> bool {
+        self.finalize_flag.store(true, Ordering::Relaxed);
+        true
+    }
+
+    fn verify(&self, len: u32, crc: u32) -> bool {
+        // Check the stored data, as a flash driver would read it back
+        let data = self.data();
+        data.len() == len as usize && crc32(&data) == crc
+    }
+}
 
 #[serial_test::serial]
 #[tokio::test]
@@ -72,58 +139,7 @@ async fn test_program() {
     );
     let mut client = get_sdo_client(&mut bus, NODE_ID);
 
-    struct BootloaderCallbacks {
-        erase_flag: AtomicBool,
-        data: Mutex<RefCell<Vec<u8>>>,
-        finalize_flag: AtomicBool,
-    }
-
-    impl BootloaderCallbacks {
-        fn erase_flag(&self) -> bool {
-            self.erase_flag.load(Ordering::Relaxed)
-        }
-
-        fn data(&self) -> Vec<u8> {
-            self.data.lock().unwrap().borrow_mut().clone()
-        }
-
-        fn finalize_flag(&self) -> bool {
-            self.finalize_flag.load(Ordering::Relaxed)
-        }
-    }
-
-    impl BootloaderSectionCallbacks for BootloaderCallbacks {
-        fn erase(&self) -> bool {
-            self.erase_flag.store(true, Ordering::Relaxed);
-            true
-        }
-
-        /// Write a chunk of data
-        ///
-        /// Write will be called 1 or more times after an erase with a sequence of new data to write to
-        /// the section
-        fn write(&self, data: &[u8]) {
-            let write_buffer = self.data.lock().unwrap();
-            write_buffer.borrow_mut().extend_from_slice(data);
-        }
-
-        /// Finalize writing a section
-        ///
-        /// Will be called once after all data has been written to allow the storage driver to finalize
-        /// writing the data and return any errors.
-        ///
-        /// Returns true on successful write
-        fn finalize(&self) -> bool {
-            self.finalize_flag.store(true, Ordering::Relaxed);
-            true
-        }
-    }
-
-    let callbacks: &BootloaderCallbacks = Box::leak(Box::new(BootloaderCallbacks {
-        erase_flag: AtomicBool::new(false),
-        data: Mutex::new(RefCell::new(Vec::new())),
-        finalize_flag: AtomicBool::new(false),
-    }));
+    let callbacks = BootloaderCallbacks::new();
 
     object_dict3::BOOTLOADER_SECTION0.register_callbacks(callbacks);
 
@@ -158,7 +174,125 @@ async fn test_program() {
 
         assert!(callbacks.erase_flag());
         assert_eq!(download_data, callbacks.data());
-        assert!(callbacks.finalize_flag())
+        assert!(callbacks.finalize_flag());
+
+        // The section reports the CRC of the programmed data, and can be verified against it
+        let crc = crc32(&download_data);
+        assert_eq!(
+            crc,
+            client.read_u32(BOOTLOADER_SECTION0_INDEX, 5).await.unwrap()
+        );
+        assert!(is_abort(
+            client
+                .write_u32(BOOTLOADER_SECTION0_INDEX, 6, crc ^ 1)
+                .await,
+            AbortCode::InvalidValue
+        ));
+        assert!(!object_dict3::BOOTLOADER_SECTION0.is_verified());
+        client
+            .write_u32(BOOTLOADER_SECTION0_INDEX, 6, crc)
+            .await
+            .unwrap();
+        assert!(object_dict3::BOOTLOADER_SECTION0.is_verified());
+        // Mode bits: programmable and verified
+        assert_eq!(
+            3,
+            client.read_u8(BOOTLOADER_SECTION0_INDEX, 1).await.unwrap()
+        );
+        // Single bank device
+        assert_eq!(
+            0,
+            client.read_u8(BOOTLOADER_SECTION0_INDEX, 7).await.unwrap()
+        );
+
+        // Erasing the section clears the verification
+        client
+            .write_u32(BOOTLOADER_SECTION0_INDEX, 3, BOOTLOADER_ERASE_CMD)
+            .await
+            .unwrap();
+        assert_eq!(
+            1,
+            client.read_u8(BOOTLOADER_SECTION0_INDEX, 1).await.unwrap()
+        );
+        assert_eq!(
+            0,
+            client.read_u32(BOOTLOADER_SECTION0_INDEX, 5).await.unwrap()
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial_test::serial]
+#[tokio::test]
+async fn test_dual_bank_swap() {
+    use object_dict4::*;
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    BOOTLOADER_SECTION0.register_callbacks(BootloaderCallbacks::new());
+    BOOTLOADER_SECTION1.register_callbacks(BootloaderCallbacks::new());
+    BOOTLOADER_INFO.set_active_bank(BootloaderBank::A);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(5, client.read_u8(BOOTLOADER_INFO_INDEX, 0).await.unwrap());
+        // Active bank
+        assert_eq!(1, client.read_u8(BOOTLOADER_INFO_INDEX, 4).await.unwrap());
+        // Bank of each section
+        assert_eq!(
+            1,
+            client.read_u8(BOOTLOADER_SECTION0_INDEX, 7).await.unwrap()
+        );
+        assert_eq!(
+            2,
+            client.read_u8(BOOTLOADER_SECTION1_INDEX, 7).await.unwrap()
+        );
+
+        // The swap is refused until the inactive bank is verified
+        assert!(is_abort(
+            client
+                .write_u32(BOOTLOADER_INFO_INDEX, 5, BOOTLOADER_SWAP_CMD)
+                .await,
+            AbortCode::GeneralError
+        ));
+        assert!(!BOOTLOADER_INFO.swap_flag());
+
+        let image = Vec::from_iter((0..2000).map(|i| (i % 256) as u8));
+        client
+            .write_u32(BOOTLOADER_SECTION1_INDEX, 3, BOOTLOADER_ERASE_CMD)
+            .await
+            .unwrap();
+        client
+            .block_download(BOOTLOADER_SECTION1_INDEX, 4, &image)
+            .await
+            .unwrap();
+        client
+            .write_u32(BOOTLOADER_SECTION1_INDEX, 6, crc32(&image))
+            .await
+            .unwrap();
+
+        assert!(is_abort(
+            client.write_u32(BOOTLOADER_INFO_INDEX, 5, 0).await,
+            AbortCode::InvalidValue
+        ));
+        client
+            .write_u32(BOOTLOADER_INFO_INDEX, 5, BOOTLOADER_SWAP_CMD)
+            .await
+            .unwrap();
+        assert!(BOOTLOADER_INFO.swap_flag());
+        assert_eq!(Some(BootloaderBank::A), BOOTLOADER_INFO.active_bank());
     };
 
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
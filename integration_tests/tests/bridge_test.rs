@@ -0,0 +1,78 @@
+//! Test forwarding messages between two buses with a bridge
+//!
+
+use std::time::Duration;
+
+use integration_tests::prelude::*;
+use serial_test::serial;
+use tokio::time::timeout;
+use zencan_client::bridge::{Bridge, ForwardRule};
+use zencan_common::{
+    messages::{CanId, CanMessage},
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+#[serial]
+#[tokio::test]
+async fn test_bridge_forwarding() {
+    let mut bus_a = SimBus::new();
+    let mut bus_b = SimBus::new();
+    let _logger_a = BusLogger::new(bus_a.new_receiver());
+    let _logger_b = BusLogger::new(bus_b.new_receiver());
+
+    let mut bridge = Bridge::new();
+    bridge
+        .forward_a_to_b(ForwardRule::std(0x181..=0x1FF))
+        .forward_b_to_a(ForwardRule::std(0x201..=0x27F).remap_to(CanId::std(0x281)));
+
+    let bridge_task = bridge.run(
+        bus_a.new_sender(),
+        bus_a.new_receiver(),
+        bus_b.new_sender(),
+        bus_b.new_receiver(),
+    );
+
+    let mut a_sender = bus_a.new_sender();
+    let mut a_receiver = bus_a.new_receiver();
+    let mut b_sender = bus_b.new_sender();
+    let mut b_receiver = bus_b.new_receiver();
+
+    let test_task = async {
+        // Forwarded unchanged from A to B
+        let msg = CanMessage::new(CanId::std(0x185), &[1, 2, 3]);
+        a_sender.send(msg).await.unwrap();
+        // Not matched by any rule
+        a_sender
+            .send(CanMessage::new(CanId::std(0x605), &[4]))
+            .await
+            .unwrap();
+        // Forwarded from B to A with a new ID
+        b_sender
+            .send(CanMessage::new(CanId::std(0x202), &[5, 6]))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The receivers see messages sent on their own bus, followed by forwarded messages
+        let received_b: Vec<_> = std::iter::from_fn(|| b_receiver.try_recv()).collect();
+        assert_eq!(
+            vec![CanMessage::new(CanId::std(0x202), &[5, 6]), msg],
+            received_b
+        );
+        let received_a: Vec<_> = std::iter::from_fn(|| a_receiver.try_recv()).collect();
+        assert_eq!(
+            vec![
+                msg,
+                CanMessage::new(CanId::std(0x605), &[4]),
+                CanMessage::new(CanId::std(0x282), &[5, 6]),
+            ],
+            received_a
+        );
+    };
+
+    tokio::select! {
+        result = bridge_task => panic!("Bridge exited: {result:?}"),
+        result = timeout(Duration::from_secs(1), test_task) => result.unwrap(),
+    }
+}
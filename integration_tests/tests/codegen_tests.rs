@@ -1,7 +1,7 @@
 //! Tests that only validate the generated code
 //!
 
-use integration_tests::{object_dict1, object_dict2, object_dict3};
+use integration_tests::{object_dict1, object_dict2, object_dict3, object_dict6};
 use zencan_node::object_dict::find_object;
 
 #[test]
@@ -13,3 +13,112 @@ fn test_autostart_defaults() {
     // Example 3 should have no object 5000
     assert!(find_object(&object_dict3::OD_TABLE, 0x5000).is_none())
 }
+
+#[test]
+fn test_startup_config_defaults() {
+    assert_eq!(0, object_dict1::OBJECT5001.get_first_heartbeat_delay());
+    assert_eq!(0, object_dict1::OBJECT5001.get_bootup_retransmit_count());
+    assert_eq!(
+        100,
+        object_dict1::OBJECT5001.get_bootup_retransmit_interval()
+    );
+    // Example 2 configures startup in the startup section
+    assert_eq!(1, object_dict2::OBJECT5000.get_value());
+    assert_eq!(
+        50,
+        object_dict2::OBJECT5001.get_bootup_retransmit_interval()
+    );
+    // The startup config object is created even when autostart is unsupported
+    assert!(find_object(&object_dict3::OD_TABLE, 0x5001).is_some())
+}
+
+#[test]
+fn test_consts() {
+    use object_dict1::consts::{index, objects, IdentitySub, RecordExampleSub};
+
+    assert_eq!(0x2000, index::ARRAY_EXAMPLE);
+    assert_eq!(0x2001, index::RECORD_EXAMPLE);
+    assert_eq!(0x1018, index::IDENTITY);
+    assert_eq!((0x2002, 0), objects::PERSISTED_STRING_VAR);
+    assert_eq!((0x3000, 0), objects::U32_VAR);
+    // Record subs are named by their field name, or their sub index if they have none
+    assert_eq!((0x1018, 4), objects::IDENTITY_SERIAL);
+    assert_eq!((0x2001, 3), objects::RECORD_EXAMPLE_SUB3);
+
+    assert_eq!(0x1018, IdentitySub::INDEX);
+    assert_eq!(2, IdentitySub::ProductCode.sub());
+    assert_eq!((0x1018, 1), IdentitySub::VendorId.id());
+    assert_eq!(Ok(RecordExampleSub::Sub4), RecordExampleSub::try_from(4));
+    assert_eq!(Err(2), RecordExampleSub::try_from(2));
+    assert_eq!(1, u8::from(RecordExampleSub::Sub1));
+
+    // The constants address the objects in the table
+    assert!(find_object(&object_dict1::OD_TABLE, index::UNICODE_STRING_VAR).is_some());
+}
+
+#[test]
+fn test_sparse_record() {
+    use zencan_node::common::sdo::AbortCode;
+    use zencan_node::object_dict::ObjectAccess;
+
+    let obj = &object_dict1::OBJECT3012;
+    // Sub 0 reports the highest sub index, not the number of subs
+    assert_eq!(40, obj.get_sub0());
+    assert_eq!(40, obj.read_u8(0).unwrap());
+    assert_eq!(40, obj.max_sub_number());
+
+    for sub in [2, 5, 7, 39, 41] {
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), obj.sub_info(sub));
+    }
+    obj.write(6, &0x1234u16.to_le_bytes()).unwrap();
+    obj.write(40, &0xdeadbeefu32.to_le_bytes()).unwrap();
+    assert_eq!(0x1234, obj.get_sub6());
+    assert_eq!(0xdeadbeef, obj.get_far());
+}
+
+#[test]
+fn test_compact_objects() {
+    use object_dict6::consts::{index, RecordExampleSub};
+    use zencan_node::common::sdo::AbortCode;
+    use zencan_node::object_dict::ObjectAccess;
+
+    // Out of range array indices are aborts, rather than panics
+    let array = &object_dict6::OBJECT2000;
+    assert_eq!(Ok(123), array.get(0));
+    assert_eq!(Err(AbortCode::NoSuchSubIndex), array.get(2));
+    assert_eq!(Err(AbortCode::NoSuchSubIndex), array.set(2, 0));
+    array.set_and_notify(1, 7).unwrap();
+    assert_eq!(7, array.read_u32(2).unwrap());
+    assert_eq!(2, array.read_u8(0).unwrap());
+    assert_eq!(Err(AbortCode::NoSuchSubIndex), array.sub_info(3));
+
+    let resizable = &object_dict6::OBJECT3003;
+    resizable.set_len(2).unwrap();
+    assert_eq!(2, resizable.read_u16(2).unwrap());
+    assert_eq!(Err(AbortCode::NoSuchSubIndex), resizable.sub_info(3));
+    assert_eq!(Ok(3), resizable.get(2));
+
+    let record = &object_dict6::OBJECT2001;
+    assert_eq!(3, record.get_sub0());
+    assert_eq!(140, record.read_u32(1).unwrap());
+    assert_eq!(Err(AbortCode::NoSuchSubIndex), record.sub_info(2));
+    assert!(RecordExampleSub::try_from(3) == Ok(RecordExampleSub::Sub3));
+
+    object_dict6::OBJECT3000.set_value(5);
+    assert_eq!(5, object_dict6::OBJECT3000.read_u32(0).unwrap());
+    assert_eq!(
+        Err(AbortCode::NoSuchSubIndex),
+        object_dict6::OBJECT3001.sub_info(1)
+    );
+    assert_eq!(*b"compact\0", object_dict6::OBJECT3002.get_value());
+
+    // The table entries access the same objects
+    let entry = find_object(&object_dict6::OD_TABLE, index::ARRAY_EXAMPLE).unwrap();
+    assert_eq!(7, entry.read_u32(2).unwrap());
+    assert_eq!(
+        zencan_node::common::objects::ObjectCode::Array,
+        entry.object_code()
+    );
+    let entry = find_object(&object_dict6::OD_TABLE, index::STRING_VAR).unwrap();
+    assert_eq!(7, entry.current_size(0).unwrap());
+}
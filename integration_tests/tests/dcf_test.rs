@@ -0,0 +1,195 @@
+//! Tests for applying DCF files to nodes
+use integration_tests::{object_dict1::*, prelude::*};
+use serial_test::serial;
+use zencan_client::{
+    common::messages::CanId,
+    dcf::{ApplyOptions, ConciseDcf, Dcf, DcfError},
+};
+
+const NODE_ID: u8 = 1;
+
+const DCF: &str = "
+[DeviceComissioning]
+NodeID=1
+NodeName=Example
+
+[1800sub1]
+ParameterName=COB-ID used by TPDO
+DataType=0x0007
+AccessType=rw
+ParameterValue=$NODEID+0x280
+
+[1800sub2]
+ParameterName=Transmission type
+DataType=0x0005
+AccessType=rw
+ParameterValue=254
+
+[1A00sub0]
+ParameterName=Number of mapped objects
+DataType=0x0005
+AccessType=rw
+ParameterValue=1
+
+[1A00sub1]
+ParameterName=Mapping entry 1
+DataType=0x0007
+AccessType=rw
+ParameterValue=0x20000120
+
+[2000sub1]
+ParameterName=Array Example 1
+DataType=0x0007
+AccessType=rww
+ParameterValue=0x1234
+
+[2002]
+ParameterName=Persisted String Var
+DataType=0x0009
+AccessType=rw
+ParameterValue=from dcf
+
+[5555]
+ParameterName=Missing Object
+DataType=0x0005
+AccessType=rw
+ParameterValue=1
+";
+
+#[serial]
+#[tokio::test]
+async fn test_apply_dcf() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let dcf: Dcf = DCF.parse().unwrap();
+
+    let test_task = move |_ctx| async move {
+        let options = ApplyOptions {
+            save: false,
+            ..Default::default()
+        };
+        let mut progress = Vec::new();
+        let report = dcf.apply(&mut client, &options, |p| progress.push(p)).await;
+
+        // Every entry is attempted, and the missing object is reported
+        assert_eq!(7, progress.len());
+        assert!(progress.iter().all(|p| p.total == 7));
+        assert_eq!(6, report.written);
+        assert_eq!(1, report.failures.len());
+        assert!(matches!(
+            report.failures[0],
+            DcfError::Write { index: 0x5555, .. }
+        ));
+        assert!(!progress.iter().find(|p| p.index == 0x5555).unwrap().ok);
+
+        assert_eq!(0x1234, client.read_u32(0x2000, 1).await.unwrap());
+        assert_eq!(
+            "from dcf",
+            client.read_visible_string(0x2002, 0).await.unwrap()
+        );
+        let tpdo = client.read_tpdo_config(0).await.unwrap();
+        assert_eq!(CanId::std(0x281), tpdo.cob_id);
+        assert!(tpdo.enabled);
+        assert_eq!(254, tpdo.transmission_type);
+        assert_eq!(1, tpdo.mappings.len());
+        assert_eq!(0x2000, tpdo.mappings[0].index);
+        assert_eq!(1, tpdo.mappings[0].sub);
+
+        // Stopping at the first error leaves later entries unwritten. The transmission type is
+        // written first, and is out of range for a u8.
+        client.write_u32(0x2000, 1, 0).await.unwrap();
+        let mut dcf = dcf;
+        assert_eq!((0x1800, 2), (dcf.entries[1].index, dcf.entries[1].sub));
+        dcf.entries[1].value = "0x100".to_string();
+        let options = ApplyOptions {
+            stop_on_error: true,
+            ..options
+        };
+        let report = dcf.apply(&mut client, &options, |_| {}).await;
+        assert_eq!(1, report.failures.len());
+        assert_eq!(0, report.written);
+        assert_eq!(0, client.read_u32(0x2000, 1).await.unwrap());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_concise_dcf() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    // The missing object can't be included, as the configuration manager would stop there
+    let mut dcf: Dcf = DCF.parse().unwrap();
+    dcf.entries.retain(|e| e.index != 0x5555);
+
+    let test_task = move |_ctx| async move {
+        // The node acts as a configuration manager with storage for nodes 2 and 3
+        let concise = dcf.to_concise(Some(2)).unwrap();
+        concise.store(&mut client, 2).await.unwrap();
+        assert_eq!(
+            concise,
+            ConciseDcf::load_stored(&mut client, 2).await.unwrap()
+        );
+        assert_eq!(
+            ConciseDcf::new(),
+            ConciseDcf::load_stored(&mut client, 3).await.unwrap()
+        );
+        assert_eq!(concise.to_bytes().len(), CONCISE_DCF_STORES[0].len());
+
+        // Nodes without storage can't be configured
+        assert!(matches!(
+            concise.store(&mut client, 4).await,
+            Err(DcfError::Write {
+                index: 0x1F22,
+                sub: 4,
+                ..
+            })
+        ));
+
+        // Apply the stored configuration, here to the manager itself, as there is no other node
+        let stored = ConciseDcf::load_stored(&mut client, 2).await.unwrap();
+        assert_eq!(
+            stored.values.len(),
+            stored.apply(&mut client).await.unwrap()
+        );
+        assert_eq!(0x1234, client.read_u32(0x2000, 1).await.unwrap());
+        let tpdo = client.read_tpdo_config(0).await.unwrap();
+        assert_eq!(CanId::std(0x282), tpdo.cob_id);
+        assert!(tpdo.enabled);
+
+        // A concise DCF larger than the SDO buffer is written in parts
+        let mut large = ConciseDcf::new();
+        for i in 0..100u32 {
+            large.push(0x2000, 1, i.to_le_bytes());
+        }
+        assert!(large.to_bytes().len() > zencan_node::SDO_BUFFER_SIZE);
+        large.store(&mut client, 3).await.unwrap();
+        let stored = ConciseDcf::load_stored(&mut client, 3).await.unwrap();
+        assert_eq!(large, stored);
+        stored.apply(&mut client).await.unwrap();
+        assert_eq!(99, client.read_u32(0x2000, 1).await.unwrap());
+
+        // Writing empty data clears the stored configuration
+        client.download(0x1F22, 3, &[]).await.unwrap();
+        assert!(CONCISE_DCF_STORES[1].is_empty());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
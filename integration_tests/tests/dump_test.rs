@@ -0,0 +1,81 @@
+//! Tests for dumping the object dictionary of a node
+use integration_tests::{object_dict1::*, prelude::*};
+use serial_test::serial;
+use zencan_client::{common::objects::DataType, dump::EdsObjects};
+
+const NODE_ID: u8 = 1;
+
+const EDS: &str = "
+[2001sub1]
+ParameterName=Record u32
+DataType=0x0007
+AccessType=rw
+";
+
+#[serial]
+#[tokio::test]
+async fn test_dump_dictionary() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        let mut dump = client
+            .dump_dictionary(&[0x1008..=0x1008, 0x2000..=0x2003, 0x2FFE..=0x2FFF])
+            .await
+            .unwrap();
+        let find = |dump: &zencan_client::dump::DictionaryDump, index, sub| {
+            dump.entries
+                .iter()
+                .find(|e| (e.index, e.sub) == (index, sub))
+                .cloned()
+        };
+        let ids: Vec<_> = dump.entries.iter().map(|e| (e.index, e.sub)).collect();
+        assert_eq!(
+            vec![
+                (0x1008, 0),
+                (0x2000, 0),
+                (0x2000, 1),
+                (0x2000, 2),
+                (0x2001, 0),
+                (0x2001, 1),
+                (0x2001, 3),
+                (0x2001, 4),
+                (0x2002, 0),
+                (0x2003, 0),
+            ],
+            ids
+        );
+
+        let name = find(&dump, 0x1008, 0).unwrap();
+        assert_eq!(Some(DataType::VisibleString), name.data_type);
+        assert_eq!("\"Example 1\"", name.formatted_value());
+        let array = find(&dump, 0x2000, 1).unwrap();
+        assert_eq!(Some(DataType::UInt32), array.data_type);
+        assert_eq!("123 (0x7B)", array.formatted_value());
+        assert_eq!(
+            Some(DataType::UInt8),
+            find(&dump, 0x2001, 0).unwrap().data_type
+        );
+        // The node does not give the data types of record subs
+        assert_eq!(None, find(&dump, 0x2001, 1).unwrap().data_type);
+
+        dump.apply_eds(&EDS.parse::<EdsObjects>().unwrap());
+        let record = find(&dump, 0x2001, 1).unwrap();
+        assert_eq!(Some("Record u32".to_string()), record.name);
+        assert_eq!("140 (0x8C)", record.formatted_value());
+        assert!(dump
+            .to_csv()
+            .contains("0x2001,1,Record u32,UInt32,140 (0x8C),\n"));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
@@ -0,0 +1,239 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use zencan_client::{
+    flash::{flash, FlashError, FlashOptions, FlashStep},
+    Device,
+};
+use zencan_common::crc32::crc32;
+use zencan_node::{BootloaderBank, BootloaderSectionCallbacks};
+
+use integration_tests::{object_dict2, object_dict3, object_dict4, prelude::*};
+
+/// Storage callbacks for a section, which record the programmed data
+#[derive(Default)]
+struct SectionStorage {
+    data: Mutex<Vec<u8>>,
+}
+
+impl SectionStorage {
+    fn new() -> &'static Self {
+        Box::leak(Box::default())
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+}
+
+impl BootloaderSectionCallbacks for SectionStorage {
+    fn erase(&self) -> bool {
+        self.data.lock().unwrap().clear();
+        true
+    }
+
+    fn write(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+
+    fn finalize(&self) -> bool {
+        true
+    }
+
+    fn verify(&self, len: u32, crc: u32) -> bool {
+        let data = self.data();
+        data.len() == len as usize && crc32(&data) == crc
+    }
+}
+
+fn test_image(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[serial_test::serial]
+#[tokio::test]
+async fn test_flash_bootloader() {
+    use object_dict3::*;
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let storage = SectionStorage::new();
+    BOOTLOADER_SECTION0.register_callbacks(storage);
+
+    let mut device = Device::new(
+        NODE_ID,
+        bus.new_sender(),
+        bus.new_receiver(),
+        bus.new_receiver(),
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        let image = test_image(2500);
+        let options = FlashOptions {
+            chunk_size: 1000,
+            boot_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let section = flash(&mut device, &image, &options, |step| {
+            steps.lock().unwrap().push(step)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(0, section);
+        assert_eq!(image, storage.data());
+        assert!(BOOTLOADER_SECTION0.is_verified());
+        // Already running the bootloader, so no reset to bootloader is needed
+        assert_eq!(
+            vec![
+                FlashStep::Erasing { section: 0 },
+                FlashStep::Programming {
+                    written: 0,
+                    total: 2500
+                },
+                FlashStep::Programming {
+                    written: 1000,
+                    total: 2500
+                },
+                FlashStep::Programming {
+                    written: 2000,
+                    total: 2500
+                },
+                FlashStep::Programming {
+                    written: 2500,
+                    total: 2500
+                },
+                FlashStep::Verifying { crc: crc32(&image) },
+                FlashStep::Resetting,
+            ],
+            *steps.lock().unwrap()
+        );
+
+        assert!(matches!(
+            flash(
+                &mut device,
+                &image,
+                &FlashOptions {
+                    section: Some(1),
+                    ..options
+                },
+                |_| {}
+            )
+            .await,
+            Err(FlashError::NoSuchSection { section: 1 })
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial_test::serial]
+#[tokio::test]
+async fn test_flash_dual_bank() {
+    use object_dict4::*;
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let storage_a = SectionStorage::new();
+    let storage_b = SectionStorage::new();
+    BOOTLOADER_SECTION0.register_callbacks(storage_a);
+    BOOTLOADER_SECTION1.register_callbacks(storage_b);
+    BOOTLOADER_INFO.set_active_bank(BootloaderBank::B);
+
+    let mut device = Device::new(
+        NODE_ID,
+        bus.new_sender(),
+        bus.new_receiver(),
+        bus.new_receiver(),
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        let image = test_image(3000);
+        let options = FlashOptions {
+            boot_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        // The inactive bank is programmed without resetting to the bootloader
+        let section = flash(&mut device, &image, &options, |step| {
+            steps.lock().unwrap().push(step)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(0, section);
+        assert_eq!(image, storage_a.data());
+        assert!(storage_b.data().is_empty());
+        assert!(BOOTLOADER_INFO.swap_flag());
+        let steps = steps.lock().unwrap();
+        assert!(!steps.contains(&FlashStep::EnteringBootloader));
+        assert_eq!(
+            &[FlashStep::Swapping, FlashStep::Resetting],
+            &steps[steps.len() - 2..]
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial_test::serial]
+#[tokio::test]
+async fn test_flash_enter_bootloader_timeout() {
+    use object_dict2::*;
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let mut device = Device::new(
+        NODE_ID,
+        bus.new_sender(),
+        bus.new_receiver(),
+        bus.new_receiver(),
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        let options = FlashOptions {
+            boot_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        // The application is commanded to reset to its bootloader, but this test never resets it
+        let result = flash(&mut device, &test_image(100), &options, |_| {}).await;
+        assert!(BOOTLOADER_INFO.reset_flag());
+        assert!(matches!(result, Err(FlashError::BootTimeout)));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
@@ -0,0 +1,117 @@
+//! Test negotiation between redundant masters
+//!
+
+use std::time::Duration;
+
+use integration_tests::prelude::*;
+use serial_test::serial;
+use tokio::time::timeout;
+use zencan_client::{FlyingMaster, FlyingMasterConfig, MasterRole};
+use zencan_common::messages::MasterId;
+
+type Master<'a> = FlyingMaster<SimBusSender<'a>, SimBusReceiver>;
+
+fn fast_config(priority: u8, node_id: u8) -> FlyingMasterConfig {
+    FlyingMasterConfig {
+        announce_period: Duration::from_millis(10),
+        timeout: Duration::from_millis(50),
+        priority_time_slot: Duration::from_millis(20),
+        device_time_slot: Duration::from_millis(1),
+        ..FlyingMasterConfig::new(priority, node_id)
+    }
+}
+
+fn new_master<'a>(bus: &mut SimBus<'a>, priority: u8, node_id: u8) -> Master<'a> {
+    FlyingMaster::new(
+        fast_config(priority, node_id),
+        bus.new_sender(),
+        bus.new_receiver(),
+    )
+}
+
+/// Run a master's process loop for `duration`
+async fn run(master: &mut Master<'_>, duration: Duration) {
+    timeout(duration, async {
+        loop {
+            master.process().await.unwrap();
+        }
+    })
+    .await
+    .ok();
+}
+
+#[serial]
+#[tokio::test]
+async fn test_highest_priority_becomes_active() {
+    let mut bus = SimBus::new();
+    let _logger = BusLogger::new(bus.new_receiver());
+    // The lower priority master has the lower node ID, so priority must take precedence
+    let mut primary = new_master(&mut bus, 0, 5);
+    let mut secondary = new_master(&mut bus, 1, 1);
+
+    assert_eq!(MasterRole::Negotiating, primary.role());
+    assert_eq!(None, primary.active_master());
+
+    let duration = Duration::from_millis(200);
+    tokio::join!(run(&mut primary, duration), run(&mut secondary, duration));
+
+    let primary_id = MasterId {
+        priority: 0,
+        node_id: 5,
+    };
+    assert!(primary.is_active());
+    assert_eq!(Some(primary_id), primary.active_master());
+    assert_eq!(MasterRole::Standby { active: primary_id }, secondary.role());
+}
+
+#[serial]
+#[tokio::test]
+async fn test_failover_and_takeback() {
+    let mut bus = SimBus::new();
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut primary = new_master(&mut bus, 0, 1);
+    let mut secondary = new_master(&mut bus, 1, 2);
+
+    let duration = Duration::from_millis(200);
+    tokio::join!(run(&mut primary, duration), run(&mut secondary, duration));
+    assert!(primary.is_active());
+    assert!(!secondary.is_active());
+
+    // Stop running the primary, and the secondary should take over after its timeout
+    run(&mut secondary, Duration::from_millis(200)).await;
+    assert!(secondary.is_active());
+
+    // When the primary returns and forces a negotiation, it should win it
+    primary.force_negotiation().await.unwrap();
+    assert_eq!(MasterRole::Negotiating, primary.role());
+    tokio::join!(run(&mut primary, duration), run(&mut secondary, duration));
+    assert!(primary.is_active());
+    assert_eq!(
+        MasterRole::Standby {
+            active: MasterId {
+                priority: 0,
+                node_id: 1
+            }
+        },
+        secondary.role()
+    );
+}
+
+#[serial]
+#[tokio::test]
+async fn test_better_master_joining_takes_over() {
+    let mut bus = SimBus::new();
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut secondary = new_master(&mut bus, 1, 2);
+
+    run(&mut secondary, Duration::from_millis(100)).await;
+    assert!(secondary.is_active());
+
+    // A better master joining the bus wins its negotiation, and the active master steps down
+    // without a forced negotiation
+    let mut primary = new_master(&mut bus, 0, 1);
+    let duration = Duration::from_millis(200);
+    tokio::join!(run(&mut primary, duration), run(&mut secondary, duration));
+    assert!(primary.is_active());
+    assert!(!secondary.is_active());
+}
@@ -1,8 +1,8 @@
-use std::time::Duration;
+use std::{ops::ControlFlow, time::Duration};
 
 use assertables::assert_contains;
-use zencan_client::LssMaster;
-use zencan_common::{lss::LssIdentity, NodeId};
+use zencan_client::{FastScanProgress, LssError, LssMaster};
+use zencan_common::{lss::LssIdentity, node_id::ConfiguredNodeId, NodeId};
 use zencan_node::{Callbacks, Node};
 
 use serial_test::serial;
@@ -91,3 +91,197 @@ async fn test_fast_scan() {
     )
     .await;
 }
+
+#[serial]
+#[tokio::test]
+async fn test_fast_scan_progress_and_cancel() {
+    let (mbox, state, od) = {
+        (
+            &object_dict1::NODE_MBOX,
+            &object_dict1::NODE_STATE,
+            &object_dict1::OD_TABLE,
+        )
+    };
+    object_dict1::OBJECT1018.set_serial(1111);
+
+    let mut bus = SimBus::new();
+    bus.add_node(mbox);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(NodeId::new(255).unwrap(), callbacks, mbox, state, od);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    const TIMEOUT: Duration = Duration::from_millis(10);
+    let mut lss_master = LssMaster::new(bus.new_sender(), bus.new_receiver());
+
+    test_with_background_process(&mut [&mut node], &mut bus, move |_ctx| async move {
+        // Cancel part way through the vendor ID
+        let mut reports = Vec::new();
+        let result = lss_master
+            .fast_scan_with_progress(TIMEOUT, |p| {
+                reports.push(p);
+                if p.bits_resolved == 8 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(LssError::Cancelled)));
+        assert_eq!(9, reports.len());
+        assert_eq!(0, reports[0].bits_resolved);
+        assert_eq!(128, reports[0].bits_remaining());
+
+        // A new scan restarts from the beginning, and reports every bit
+        let mut reports: Vec<FastScanProgress> = Vec::new();
+        let found_id = lss_master
+            .fast_scan_with_progress(TIMEOUT, |p| {
+                reports.push(p);
+                ControlFlow::Continue(())
+            })
+            .await
+            .expect("Unexpected error")
+            .expect("No devices found by fastscan");
+        assert_eq!(1111, found_id.serial);
+        assert_eq!(129, reports.len());
+        for (i, p) in reports.iter().enumerate() {
+            assert_eq!(i as u8, p.bits_resolved);
+        }
+        assert_eq!(3, reports.last().unwrap().sub);
+        assert_eq!(0, reports.last().unwrap().bits_remaining());
+    })
+    .await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_fast_scan_all_and_assign() {
+    let (mbox1, state1, od1) = {
+        (
+            &object_dict1::NODE_MBOX,
+            &object_dict1::NODE_STATE,
+            &object_dict1::OD_TABLE,
+        )
+    };
+
+    let (mbox2, state2, od2) = {
+        (
+            &object_dict2::NODE_MBOX,
+            &object_dict2::NODE_STATE,
+            &object_dict2::OD_TABLE,
+        )
+    };
+    object_dict1::OBJECT1018.set_serial(2222);
+    object_dict2::OBJECT1018.set_serial(3333);
+
+    let mut bus = SimBus::new();
+    bus.add_node(mbox1);
+    bus.add_node(mbox2);
+    let callbacks1 = Callbacks::new();
+    let callbacks2 = Callbacks::new();
+    let mut node1 = Node::new(NodeId::new(255).unwrap(), callbacks1, mbox1, state1, od1);
+    let mut node2 = Node::new(NodeId::new(255).unwrap(), callbacks2, mbox2, state2, od2);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    const TIMEOUT: Duration = Duration::from_millis(25);
+    let mut lss_master = LssMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut client1 = get_sdo_client(&mut bus, 20);
+    let mut client2 = get_sdo_client(&mut bus, 21);
+
+    test_with_background_process(
+        &mut [&mut node1, &mut node2],
+        &mut bus,
+        move |_ctx| async move {
+            // Both nodes are found, and returned to the Waiting state, so they can be found again
+            for _ in 0..2 {
+                let mut serials: Vec<u32> = lss_master
+                    .fast_scan_all(TIMEOUT)
+                    .await
+                    .iter()
+                    .map(|id| id.serial)
+                    .collect();
+                serials.sort();
+                assert_eq!(vec![2222, 3333], serials);
+            }
+
+            let pool = (20..=30).map(|id| ConfiguredNodeId::new(id).unwrap());
+            let assigned = lss_master
+                .fast_scan_assign(TIMEOUT, pool, false)
+                .await
+                .unwrap();
+            assert_eq!(2, assigned.len());
+            assert_eq!(20, assigned[0].1.raw());
+            assert_eq!(21, assigned[1].1.raw());
+
+            // Each node responds on its assigned ID
+            assert_eq!(
+                assigned[0].0.serial,
+                client1.read_u32(0x1018, 4).await.unwrap()
+            );
+            assert_eq!(
+                assigned[1].0.serial,
+                client2.read_u32(0x1018, 4).await.unwrap()
+            );
+
+            // Configured nodes are no longer found by the scan
+            assert!(lss_master.fast_scan_all(TIMEOUT).await.is_empty());
+        },
+    )
+    .await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_fast_scan_assign_pool_exhausted() {
+    let (mbox1, state1, od1) = {
+        (
+            &object_dict1::NODE_MBOX,
+            &object_dict1::NODE_STATE,
+            &object_dict1::OD_TABLE,
+        )
+    };
+
+    let (mbox2, state2, od2) = {
+        (
+            &object_dict2::NODE_MBOX,
+            &object_dict2::NODE_STATE,
+            &object_dict2::OD_TABLE,
+        )
+    };
+    object_dict1::OBJECT1018.set_serial(4444);
+    object_dict2::OBJECT1018.set_serial(5555);
+
+    let mut bus = SimBus::new();
+    bus.add_node(mbox1);
+    bus.add_node(mbox2);
+    let callbacks1 = Callbacks::new();
+    let callbacks2 = Callbacks::new();
+    let mut node1 = Node::new(NodeId::new(255).unwrap(), callbacks1, mbox1, state1, od1);
+    let mut node2 = Node::new(NodeId::new(255).unwrap(), callbacks2, mbox2, state2, od2);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    const TIMEOUT: Duration = Duration::from_millis(25);
+    let mut lss_master = LssMaster::new(bus.new_sender(), bus.new_receiver());
+
+    test_with_background_process(
+        &mut [&mut node1, &mut node2],
+        &mut bus,
+        move |_ctx| async move {
+            let pool = [ConfiguredNodeId::new(40).unwrap()];
+            let assigned = lss_master
+                .fast_scan_assign(TIMEOUT, pool, false)
+                .await
+                .unwrap();
+            assert_eq!(1, assigned.len());
+            assert_eq!(40, assigned[0].1.raw());
+
+            // The other node is left unconfigured
+            let remaining = lss_master.fast_scan_all(TIMEOUT).await;
+            assert_eq!(1, remaining.len());
+            assert_ne!(assigned[0].0, remaining[0]);
+        },
+    )
+    .await;
+}
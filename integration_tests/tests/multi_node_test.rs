@@ -0,0 +1,73 @@
+//! Tests for running several nodes, each on its own bus, in one program
+use integration_tests::{object_dict1, object_dict1_ch2, prelude::*};
+use serial_test::serial;
+use zencan_client::nmt_master::NmtMaster;
+
+#[serial]
+#[tokio::test]
+async fn test_two_nodes_on_two_buses() {
+    // Both nodes use the same ID, which is allowed because they are on different buses
+    const NODE_ID: u8 = 1;
+
+    let original = object_dict1::OBJECT2000.get(0).unwrap();
+
+    let mut bus1 = SimBus::new();
+    let mut bus2 = SimBus::new();
+    bus1.add_node(&object_dict1::NODE_MBOX);
+    bus2.add_node(&object_dict1_ch2::NODE_MBOX);
+
+    let (op_tx1, op_rx1) = std::sync::mpsc::channel();
+    let (op_tx2, op_rx2) = std::sync::mpsc::channel();
+    let mut enter_operational1 = |_| op_tx1.send(()).unwrap();
+    let mut enter_operational2 = |_| op_tx2.send(()).unwrap();
+    let mut callbacks1 = Callbacks::new();
+    callbacks1.enter_operational = Some(&mut enter_operational1);
+    let mut callbacks2 = Callbacks::new();
+    callbacks2.enter_operational = Some(&mut enter_operational2);
+
+    let mut node1 = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks1,
+        &object_dict1::NODE_MBOX,
+        &object_dict1::NODE_STATE,
+        &object_dict1::OD_TABLE,
+    );
+    let mut node2 = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks2,
+        &object_dict1_ch2::NODE_MBOX,
+        &object_dict1_ch2::NODE_STATE,
+        &object_dict1_ch2::OD_TABLE,
+    );
+
+    let _logger1 = BusLogger::new(bus1.new_receiver());
+    let _logger2 = BusLogger::new(bus2.new_receiver());
+    let mut client1 = get_sdo_client(&mut bus1, NODE_ID);
+    let mut client2 = get_sdo_client(&mut bus2, NODE_ID);
+    let mut nmt_master1 = NmtMaster::new(bus1.new_sender(), bus1.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        // Each node has its own object dictionary
+        client1.write_u32(0x2000, 1, 100).await.unwrap();
+        client2.write_u32(0x2000, 1, 200).await.unwrap();
+        assert_eq!(100, client1.read_u32(0x2000, 1).await.unwrap());
+        assert_eq!(200, client2.read_u32(0x2000, 1).await.unwrap());
+
+        // An NMT command only reaches the node on the bus it is sent on
+        assert!(op_rx1.try_recv().is_err());
+        nmt_master1.nmt_start(0).await.unwrap();
+        ctx.wait_for_process(2).await;
+        assert!(op_rx1.try_recv().is_ok());
+        assert!(op_rx2.try_recv().is_err());
+    };
+
+    test_with_background_buses(
+        &mut [(&mut node1, &mut bus1), (&mut node2, &mut bus2)],
+        test_task,
+    )
+    .await;
+
+    assert_eq!(100, object_dict1::OBJECT2000.get(0).unwrap());
+    assert_eq!(200, object_dict1_ch2::OBJECT2000.get(0).unwrap());
+    object_dict1::OBJECT2000.set(0, original).unwrap();
+}
@@ -1,5 +1,10 @@
-use zencan_client::nmt_master::NmtMaster;
-use zencan_common::{nmt::NmtState, NodeId};
+use std::time::Duration;
+
+use zencan_client::{
+    nmt_master::NmtMaster, BusMultiplexer, Device, Endianness, EndiannessProfile, MessageFilter,
+    SdoClientError,
+};
+use zencan_common::{messages::CanId, nmt::NmtState, traits::AsyncCanReceiver, NodeId};
 use zencan_node::{Callbacks, Node};
 
 use integration_tests::prelude::*;
@@ -24,6 +29,7 @@ async fn test_nmt_init() {
     let sender = bus.new_sender();
     let receiver = bus.new_receiver();
     let mut master = NmtMaster::new(sender, receiver);
+    let mut raw_rx = bus.new_receiver();
 
     assert_eq!(NmtState::Bootup, node.nmt_state());
 
@@ -32,6 +38,11 @@ async fn test_nmt_init() {
 
     assert_eq!(NmtState::PreOperational, node.nmt_state());
 
+    // The boot-up message must carry a state value of 0
+    let bootup_msg = raw_rx.try_recv().unwrap();
+    assert_eq!(CanId::std(0x700 | NODE_ID as u16), bootup_msg.id());
+    assert_eq!(&[0], bootup_msg.data());
+
     // Master should have received a boot up message
     let nodes = master.get_nodes();
     assert_eq!(1, nodes.len());
@@ -56,3 +67,342 @@ async fn test_nmt_init() {
     assert_eq!(NmtState::Stopped, node.nmt_state());
     assert_eq!(2, node.rx_message_count());
 }
+
+#[serial]
+#[tokio::test]
+async fn test_device_handle() {
+    use integration_tests::object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut device = Device::new(
+        NODE_ID,
+        bus.new_sender(),
+        bus.new_receiver(),
+        bus.new_receiver(),
+    );
+
+    let test_task = move |_ctx| async move {
+        // The boot-up message was sent by the first process call
+        assert_eq!(
+            NmtState::PreOperational,
+            device
+                .wait_online(Duration::from_millis(100))
+                .await
+                .unwrap()
+        );
+
+        device.start().await.unwrap();
+        device
+            .write(0x2000, 1, &123u32.to_le_bytes())
+            .await
+            .unwrap();
+        assert_eq!(
+            123u32.to_le_bytes().to_vec(),
+            device.read(0x2000, 1).await.unwrap()
+        );
+
+        let identity = device.identity().await.unwrap();
+        assert_eq!(
+            identity.vendor_id,
+            device.sdo().read_u32(0x1018, 1).await.unwrap()
+        );
+
+        // After a reset, the node is not online again until its boot-up message is received
+        device.reset().await.unwrap();
+        assert_eq!(None, device.nmt_state());
+        assert_eq!(
+            NmtState::PreOperational,
+            device
+                .wait_online(Duration::from_millis(100))
+                .await
+                .unwrap()
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_device_endianness() {
+    use integration_tests::object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut device = Device::new(
+        NODE_ID,
+        bus.new_sender(),
+        bus.new_receiver(),
+        bus.new_receiver(),
+    );
+    device.set_endianness_profile(
+        EndiannessProfile::default()
+            .with_object(0x2000, Endianness::Big)
+            .with_sub(0x2000, 2, Endianness::WordSwapped),
+    );
+
+    let test_task = move |_ctx| async move {
+        device.write_u32(0x2000, 1, 0x11223344).await.unwrap();
+        assert_eq!(0x11223344, device.read_u32(0x2000, 1).await.unwrap());
+        assert_eq!(
+            vec![0x11, 0x22, 0x33, 0x44],
+            device.read(0x2000, 1).await.unwrap()
+        );
+        assert_eq!(
+            0x11223344,
+            device.sdo().read_u32_be(0x2000, 1).await.unwrap()
+        );
+
+        device.write_u32(0x2000, 2, 0x11223344).await.unwrap();
+        assert_eq!(
+            vec![0x22, 0x11, 0x44, 0x33],
+            device.read(0x2000, 2).await.unwrap()
+        );
+        assert_eq!(0x11223344, device.read_u32(0x2000, 2).await.unwrap());
+
+        // Objects not in the profile are little endian
+        device.write_u32(0x3000, 0, 0x11223344).await.unwrap();
+        assert_eq!(0x11223344, OBJECT3000.get_value());
+
+        // Restore the default values
+        device.sdo().write_u32(0x2000, 1, 123).await.unwrap();
+        device.sdo().write_u32(0x2000, 2, u32::MAX).await.unwrap();
+        device.write_u32(0x3000, 0, 0).await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_state_change_heartbeats() {
+    use integration_tests::object_dict1::*;
+    const NODE_ID: u8 = 1;
+    const HEARTBEAT_ID: CanId = CanId::std(0x700 | NODE_ID as u16);
+
+    // Enable heartbeat production with a period long enough that no periodic heartbeats are due
+    // during the test
+    OBJECT1017.set_value(1000);
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    OBJECT1017.set_value(0);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut master = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut raw_rx = bus.new_receiver();
+
+    let mut next_heartbeat = |bus: &mut SimBus| -> Option<u8> {
+        bus.flush_mailboxes();
+        while let Some(msg) = raw_rx.try_recv() {
+            if msg.id() == HEARTBEAT_ID {
+                return Some(msg.data()[0]);
+            }
+        }
+        None
+    };
+
+    // The boot-up message is followed immediately by the pre-operational heartbeat
+    node.process(0);
+    assert_eq!(Some(NmtState::Bootup as u8), next_heartbeat(&mut bus));
+    assert_eq!(
+        Some(NmtState::PreOperational as u8),
+        next_heartbeat(&mut bus)
+    );
+    assert_eq!(None, next_heartbeat(&mut bus));
+
+    // The heartbeat period restarts from the state change heartbeat
+    node.process(500_000);
+    assert_eq!(None, next_heartbeat(&mut bus));
+
+    master.nmt_start(NODE_ID).await.unwrap();
+    node.process(600_000);
+    assert_eq!(Some(NmtState::Operational as u8), next_heartbeat(&mut bus));
+    node.process(1_500_000);
+    assert_eq!(None, next_heartbeat(&mut bus));
+    node.process(1_600_000);
+    assert_eq!(Some(NmtState::Operational as u8), next_heartbeat(&mut bus));
+
+    // A command which does not change the state does not produce a heartbeat
+    master.nmt_start(NODE_ID).await.unwrap();
+    node.process(1_700_000);
+    assert_eq!(None, next_heartbeat(&mut bus));
+
+    master.nmt_stop(NODE_ID).await.unwrap();
+    node.process(1_800_000);
+    assert_eq!(Some(NmtState::Stopped as u8), next_heartbeat(&mut bus));
+
+    // An out-of-cycle heartbeat can be requested by the application
+    node.force_heartbeat();
+    node.process(1_900_000);
+    assert_eq!(Some(NmtState::Stopped as u8), next_heartbeat(&mut bus));
+    node.process(2_800_000);
+    assert_eq!(None, next_heartbeat(&mut bus));
+}
+
+#[serial]
+#[tokio::test]
+async fn test_sdo_disabled_when_stopped() {
+    use integration_tests::object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    client.set_timeout(Duration::from_millis(50));
+    let mut master = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(123, client.read_u32(0x2000, 1).await.unwrap());
+
+        // A stopped node ignores SDO requests
+        master.nmt_stop(NODE_ID).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(matches!(
+            client.read_u32(0x2000, 1).await,
+            Err(SdoClientError::NoResponse)
+        ));
+        assert!(matches!(
+            client.write_u32(0x2000, 1, 456).await,
+            Err(SdoClientError::NoResponse)
+        ));
+
+        // SDO resumes when the node is started
+        master.nmt_start(NODE_ID).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(123, client.read_u32(0x2000, 1).await.unwrap());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_sdo_in_stopped() {
+    use integration_tests::object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.set_sdo_in_stopped(true);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let mut master = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        master.nmt_stop(NODE_ID).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(123, client.read_u32(0x2000, 1).await.unwrap());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+    assert_eq!(NmtState::Stopped, node.nmt_state());
+}
+
+#[serial]
+#[tokio::test]
+async fn test_bus_multiplexer() {
+    const NODE_ID: u8 = 1;
+    let od = &integration_tests::object_dict1::OD_TABLE;
+    let state = &integration_tests::object_dict1::NODE_STATE;
+    let mbox = &integration_tests::object_dict1::NODE_MBOX;
+
+    let mut bus = SimBus::new();
+    bus.add_node(mbox);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        mbox,
+        state,
+        od,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    // All of the clients share one sender and receiver
+    let mux = BusMultiplexer::new(bus.new_sender(), bus.new_receiver());
+    let mut master = mux.nmt_master();
+    let mut client = mux.sdo_client(NODE_ID);
+    let mut all = mux.receiver(MessageFilter::All);
+    let mut heartbeats = mux.subscribe(CanId::std(0x700 + NODE_ID as u16));
+
+    let test_task = move |mut ctx: TestContext| async move {
+        // SDO transfers complete while the NMT master has the boot-up message queued
+        client.write_u32(0x2000, 1, 1234).await.unwrap();
+        assert_eq!(1234, client.read_u32(0x2000, 1).await.unwrap());
+
+        master.nmt_start(NODE_ID).await.unwrap();
+        ctx.wait_for_process(2).await;
+
+        // The master still sees the boot-up message
+        let nodes = master.get_nodes();
+        assert_eq!(1, nodes.len());
+        assert_eq!(NODE_ID, nodes[0].id);
+        assert_eq!(NmtState::PreOperational, nodes[0].state);
+
+        // And so does the subscription
+        let bootup = heartbeats.recv().await.unwrap();
+        assert_eq!(&[0], bootup.data());
+        assert_eq!(None, heartbeats.try_recv());
+
+        // The unfiltered receiver got all of the traffic
+        let mut ids = Vec::new();
+        while let Some(msg) = all.try_recv() {
+            ids.push(msg.id());
+        }
+        for id in [0x0, 0x581, 0x601, 0x701] {
+            assert!(ids.contains(&CanId::std(id)), "missing {id:x}");
+        }
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
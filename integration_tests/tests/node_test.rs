@@ -6,9 +6,15 @@ use std::{
 use integration_tests::{object_dict1, prelude::*};
 use rand::Rng as _;
 use serial_test::serial;
-use zencan_client::nmt_master::NmtMaster;
+use zencan_client::{nmt_master::NmtMaster, SdoClient};
 use zencan_common::{
-    messages::SyncObject, traits::AsyncCanSender, AtomicCell, TimeDifference, TimeOfDay,
+    messages::{
+        CanId, CanMessage, ConnectionSet, Heartbeat, NmtCommand, NmtCommandSpecifier, SyncObject,
+        TimeObject, VendorBroadcast,
+    },
+    nmt::NmtState,
+    traits::{AsyncCanReceiver as _, AsyncCanSender},
+    AtomicCell, TimeDifference, TimeOfDay,
 };
 
 #[serial]
@@ -524,9 +530,9 @@ async fn test_boolean_object_access() {
 
     let test_task = move |_ctx| async move {
         client.write_bool(0x300D, 0, true).await.unwrap();
-        assert_eq!(true, OBJECT300D.get_value());
+        assert!(OBJECT300D.get_value());
         client.write_bool(0x300D, 0, false).await.unwrap();
-        assert_eq!(false, OBJECT300D.get_value());
+        assert!(!OBJECT300D.get_value());
     };
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
 }
@@ -666,3 +672,607 @@ async fn test_numeric_access() {
     };
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
 }
+
+#[serial]
+#[tokio::test]
+async fn test_diagnostics_object() {
+    use object_dict1::*;
+    const DIAGNOSTICS_ID: u16 = 0x5F00;
+
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    // Counters are shared by all tests using the static mailbox
+    node.diagnostics().reset();
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(0, client.read_u32(DIAGNOSTICS_ID, 4).await.unwrap());
+
+        // Reading a non-existent object results in an abort
+        assert!(client.read_u32(0x4FFF, 0).await.is_err());
+        assert_eq!(1, client.read_u32(DIAGNOSTICS_ID, 4).await.unwrap());
+        assert_eq!(1, NODE_MBOX.diagnostics().sdo_aborts());
+
+        // Every request so far should have been received, and answered
+        let rx_count = client.read_u32(DIAGNOSTICS_ID, 1).await.unwrap();
+        let tx_count = client.read_u32(DIAGNOSTICS_ID, 2).await.unwrap();
+        assert!(rx_count >= 4);
+        assert!(tx_count >= 4);
+
+        // The diagnostics object is read-only
+        let result = client.write_u32(DIAGNOSTICS_ID, 1, 0).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::ReadOnly),
+                ..
+            })
+        ));
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[test]
+fn test_malformed_frames() {
+    use object_dict1::*;
+    use zencan_node::{diagnostics::MalformedFrame, object_dict::ObjectAccess as _};
+
+    let mut received = Vec::new();
+    let mut malformed_frame = |kind, msg| received.push((kind, msg));
+    let mut callbacks = Callbacks::new();
+    callbacks.malformed_frame = Some(&mut malformed_frame);
+    let mut node = Node::new(
+        NodeId::new(1).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.process(0);
+    node.diagnostics().reset();
+
+    // A short NMT command is rejected, rather than dropped when the node processes it
+    let short_nmt = CanMessage::new(zencan_common::messages::NMT_CMD_ID, &[1]);
+    assert_eq!(Err(short_nmt), NODE_MBOX.store_message(short_nmt));
+    node.process(1000);
+    assert_eq!(NmtState::PreOperational, node.nmt_state());
+    // Nothing is reported again on the next call
+    node.process(2000);
+
+    let short_sdo = CanMessage::new(CanId::std(0x601), &[0x40, 0x00, 0x10]);
+    assert_eq!(Err(short_sdo), NODE_MBOX.store_message(short_sdo));
+    assert_eq!(2, node.diagnostics().rx_malformed());
+    assert_eq!(2, DIAGNOSTICS_OBJECT.read_u32(9).unwrap());
+    node.process(3000);
+    drop(node);
+
+    assert_eq!(
+        vec![
+            (MalformedFrame::Nmt, short_nmt),
+            (MalformedFrame::Sdo, short_sdo)
+        ],
+        received
+    );
+}
+
+#[serial]
+#[test]
+fn test_configured_tx_queue_size() {
+    use object_dict1::*;
+    // example1.toml sets tx_queue_size = 8
+
+    // Drain anything left in the queue by previous tests
+    while NODE_MBOX.next_transmit_message().is_some() {}
+    assert_eq!(8, NODE_MBOX.tx_queue_free());
+
+    let msg = CanMessage::new(CanId::std(0x123), &[1, 2, 3]);
+    for _ in 0..8 {
+        NODE_MBOX.queue_transmit_message(msg).unwrap();
+    }
+    assert_eq!(0, NODE_MBOX.tx_queue_free());
+    assert_eq!(Err(msg), NODE_MBOX.queue_transmit_message(msg));
+
+    while NODE_MBOX.next_transmit_message().is_some() {}
+    assert_eq!(8, NODE_MBOX.tx_queue_free());
+}
+
+#[serial]
+#[test]
+fn test_generated_rx_queue() {
+    use object_dict1::*;
+    // example1.toml sets rx_queue_size = 4
+
+    let mut node = Node::new(
+        NodeId::new(1).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.process(0);
+    let (mut producer, mut consumer) = RX_QUEUE.split().unwrap();
+    assert!(RX_QUEUE.split().is_none());
+
+    let start: CanMessage = NmtCommand {
+        cs: NmtCommandSpecifier::Start,
+        node: 1,
+    }
+    .into();
+    let other = CanMessage::new(CanId::std(0x123), &[1, 2, 3]);
+    for _ in 0..3 {
+        producer.enqueue(other).unwrap();
+    }
+    producer.enqueue(start).unwrap();
+    assert_eq!(Err(other), producer.enqueue(other));
+
+    assert!(consumer.forward(&NODE_MBOX));
+    node.process(1000);
+    assert_eq!(NmtState::Operational, node.nmt_state());
+    assert!(!consumer.forward(&NODE_MBOX));
+}
+
+#[serial]
+#[tokio::test]
+async fn test_log_ring_object() {
+    use object_dict1::*;
+    const LOG_RING_ID: u16 = 0x5F01;
+
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    LOG_RING.clear();
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(
+            Vec::<u8>::new(),
+            client.upload(LOG_RING_ID, 0).await.unwrap()
+        );
+
+        writeln!(LOG_RING, "Starting up").unwrap();
+        writeln!(LOG_RING, "Sensor fault: {}", 42).unwrap();
+        let log = client.read_utf8(LOG_RING_ID, 0).await.unwrap();
+        assert_eq!("Starting up\nSensor fault: 42\n", log);
+
+        // Overfill the 128 byte ring. Only the most recent complete lines are returned.
+        for i in 0..20 {
+            writeln!(LOG_RING, "line {i}").unwrap();
+        }
+        let log = client.read_utf8(LOG_RING_ID, 0).await.unwrap();
+        assert!(log.len() <= 128);
+        assert!(log.starts_with("line "));
+        assert!(log.ends_with("line 18\nline 19\n"));
+        let block_log = client.block_upload(LOG_RING_ID, 0).await.unwrap();
+        assert_eq!(log.as_bytes(), block_log);
+
+        // The log is read-only
+        let result = client.download(LOG_RING_ID, 0, b"abc").await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::ReadOnly),
+                ..
+            })
+        ));
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// Test that commands on the vendor broadcast channel are delivered to the callback
+#[serial]
+#[tokio::test]
+async fn test_vendor_broadcast_callback() {
+    use object_dict1::*;
+
+    let _ = env_logger::try_init();
+
+    const NODE_ID: u8 = 1;
+    const BROADCAST_ID: u32 = 0x1ABC_0000;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    NODE_MBOX.set_vendor_broadcast_id(Some(BROADCAST_ID));
+
+    let received: Arc<RwLock<Vec<VendorBroadcast>>> = Arc::new(RwLock::new(Vec::new()));
+    let received_clone = received.clone();
+    let mut vendor_broadcast_cb = |cmd: VendorBroadcast| {
+        received_clone.write().unwrap().push(cmd);
+    };
+
+    let callbacks = Callbacks {
+        vendor_broadcast: Some(&mut vendor_broadcast_cb),
+        ..Default::default()
+    };
+
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let mut sender = bus.new_sender();
+
+    let test_task = |mut ctx: TestContext| async move {
+        let cmd = VendorBroadcast::new(0x42, &[1, 2, 3]).unwrap();
+        sender
+            .send(cmd.to_can_message(CanId::extended(BROADCAST_ID)))
+            .await
+            .unwrap();
+        // The same raw value as a standard ID is not on the channel
+        sender
+            .send(cmd.to_can_message(CanId::std(0x0AB)))
+            .await
+            .unwrap();
+        ctx.wait_for_process(2).await;
+
+        let received = received.read().unwrap().clone();
+        assert_eq!(vec![cmd], received);
+        assert_eq!(&[1, 2, 3], received[0].args());
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+
+    NODE_MBOX.set_vendor_broadcast_id(None);
+}
+
+/// Test that a heartbeat consumer configured over SDO detects a node's heartbeat stopping
+#[serial]
+#[tokio::test]
+async fn test_heartbeat_consumer() {
+    use object_dict1::*;
+    use zencan_node::heartbeat_consumer::HeartbeatStatus;
+
+    const NODE_ID: u8 = 1;
+    const PRODUCER_ID: u8 = 5;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+
+    let timeouts: Arc<RwLock<Vec<u8>>> = Arc::new(RwLock::new(Vec::new()));
+    let timeouts_clone = timeouts.clone();
+    let mut heartbeat_timeout_cb = |node: u8| {
+        timeouts_clone.write().unwrap().push(node);
+    };
+    let callbacks = Callbacks {
+        heartbeat_timeout: Some(&mut heartbeat_timeout_cb),
+        ..Default::default()
+    };
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let mut sender = bus.new_sender();
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = |mut ctx: TestContext| async move {
+        // example1.toml configures 2 consumers, with a default for node 100
+        assert_eq!(2, client.read_u8(0x1016, 0).await.unwrap());
+        assert_eq!(
+            (100 << 16) | 1000,
+            client.read_u32(0x1016, 1).await.unwrap()
+        );
+        assert_eq!(0, client.read_u32(0x1016, 2).await.unwrap());
+
+        // Monitoring the same node twice is not allowed
+        let result = client.write_u32(0x1016, 2, (100 << 16) | 500).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::IncompatibleParameter),
+                ..
+            })
+        ));
+
+        client
+            .write_u32(0x1016, 2, ((PRODUCER_ID as u32) << 16) | 100)
+            .await
+            .unwrap();
+        assert_eq!(HeartbeatStatus::Waiting, HEARTBEAT_CONSUMERS[1].status());
+
+        let heartbeat = Heartbeat {
+            node: PRODUCER_ID,
+            toggle: false,
+            state: NmtState::Operational,
+        };
+        sender.send(heartbeat.into()).await.unwrap();
+        ctx.wait_for_process(2).await;
+        assert_eq!(
+            HeartbeatStatus::Alive(NmtState::Operational),
+            HEARTBEAT_CONSUMERS[1].status()
+        );
+        assert!(timeouts.read().unwrap().is_empty());
+
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        ctx.wait_for_process(2).await;
+        assert_eq!(HeartbeatStatus::TimedOut, HEARTBEAT_CONSUMERS[1].status());
+        assert_eq!(vec![PRODUCER_ID], *timeouts.read().unwrap());
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// Test that the SDO server responds with the CiA 301 abort code for common access errors
+#[serial]
+#[tokio::test]
+async fn test_sdo_abort_codes() {
+    use object_dict1::*;
+
+    const NODE_ID: u8 = 1;
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    fn abort_code<T: std::fmt::Debug>(result: Result<T, SdoClientError>) -> AbortCode {
+        match result {
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(abort_code),
+                ..
+            }) => abort_code,
+            _ => panic!("Expected a server abort, got {result:?}"),
+        }
+    }
+
+    let test_task = move |_ctx| async move {
+        // Writing a read-only object
+        assert_eq!(
+            AbortCode::ReadOnly,
+            abort_code(client.write_u32(0x1018, 1, 0).await)
+        );
+        // Non-existent object vs non-existent sub index
+        assert_eq!(
+            AbortCode::NoSuchObject,
+            abort_code(client.read_u32(0x4FFF, 0).await)
+        );
+        assert_eq!(
+            AbortCode::NoSuchSubIndex,
+            abort_code(client.read_u32(0x1018, 9).await)
+        );
+        // Size mismatches
+        assert_eq!(
+            AbortCode::DataTypeMismatchLengthLow,
+            abort_code(client.download(0x2000, 1, &[0; 2]).await)
+        );
+        assert_eq!(
+            AbortCode::DataTypeMismatchLengthHigh,
+            abort_code(client.download(0x2000, 1, &[0; 6]).await)
+        );
+        assert_eq!(
+            AbortCode::DataTypeMismatchLengthLow,
+            abort_code(client.download(0x1010, 1, &[0; 2]).await)
+        );
+        // The sub0 of fixed size arrays is read-only
+        assert_eq!(
+            AbortCode::ReadOnly,
+            abort_code(client.write_u8(0x2000, 0, 1).await)
+        );
+        assert_eq!(
+            AbortCode::ReadOnly,
+            abort_code(client.write_u8(0x1010, 0, 1).await)
+        );
+
+        // The sub0 of special objects can be read like any other
+        assert_eq!(1, client.read_u8(0x1010, 0).await.unwrap());
+        assert_eq!(10, client.read_u8(0x5F00, 0).await.unwrap());
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_active_cob_ids() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 5;
+
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.process(0);
+
+    assert_eq!(
+        Some((CanId::std(0x605), CanId::std(0x585))),
+        node.sdo_cob_ids()
+    );
+    assert_eq!(
+        (Some(CanId::std(0x605)), Some(CanId::std(0x585))),
+        (NODE_MBOX.sdo_rx_cob_id(), NODE_MBOX.sdo_tx_cob_id())
+    );
+    assert_eq!(Some(CanId::std(0x705)), node.heartbeat_cob_id());
+    assert_eq!(OBJECT1017.get_value(), node.heartbeat_period_ms());
+
+    // PDOs enabled in the device config
+    assert_eq!(
+        vec![CanId::std(0x300)],
+        NODE_STATE.enabled_rpdo_cob_ids().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![CanId::std(0x205)],
+        NODE_STATE.enabled_tpdo_cob_ids().collect::<Vec<_>>()
+    );
+}
+
+#[serial]
+#[tokio::test]
+async fn test_network_time() {
+    use object_dict1::*;
+
+    let mut node = Node::new(
+        NodeId::new(1).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.process(0);
+    assert_eq!(None, node.network_time());
+
+    let time = TimeOfDay::from_ymd_hms_ms(2025, 6, 1, 12, 0, 0, 0).unwrap();
+    NODE_MBOX
+        .store_message(TimeObject::new(time).into())
+        .unwrap();
+    node.process(1_000_000);
+    assert_eq!(Some(time), node.network_time());
+    node.process(1_500_000);
+    assert_eq!(
+        time.total_millis() + 500,
+        node.network_time().unwrap().total_millis()
+    );
+
+    // With SYNC alignment, the time refers to the preceding SYNC
+    node.set_network_time_sync_alignment(true);
+    NODE_MBOX
+        .store_message(SyncObject::new(None).into())
+        .unwrap();
+    node.process(2_000_000);
+    let time = TimeOfDay::from_ymd_hms_ms(2025, 6, 1, 13, 0, 0, 0).unwrap();
+    NODE_MBOX
+        .store_message(TimeObject::new(time).into())
+        .unwrap();
+    node.process(2_300_000);
+    assert_eq!(
+        time.total_millis() + 300,
+        node.network_time().unwrap().total_millis()
+    );
+
+    // Short TIME messages are not handled
+    let msg = CanMessage::new(zencan_common::messages::TIME_ID, &[0; 4]);
+    assert_eq!(Err(msg), NODE_MBOX.store_message(msg));
+}
+
+#[serial]
+#[tokio::test]
+async fn test_connection_set() {
+    use integration_tests::object_dict5::*;
+    const NODE_ID: u8 = 3;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut raw_rx = bus.new_receiver();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    // The bases from the device config are used for the boot-up message and the SDO server
+    let config_set = ConnectionSet {
+        sdo_request_base: 0x480,
+        sdo_response_base: 0x400,
+        heartbeat_base: 0x500,
+        emcy_base: 0x80,
+    };
+    assert_eq!(config_set, node.connection_set());
+    node.process(0);
+    bus.flush_mailboxes();
+    let bootup = raw_rx.try_recv().unwrap();
+    assert_eq!(CanId::std(0x503), bootup.id());
+    assert_eq!([NmtState::Bootup as u8], bootup.data());
+    assert_eq!(Some(CanId::std(0x503)), node.heartbeat_cob_id());
+    assert_eq!(
+        Some((CanId::std(0x483), CanId::std(0x403))),
+        node.sdo_cob_ids()
+    );
+
+    // The bases can be changed at runtime, and take effect immediately
+    let runtime_set = ConnectionSet {
+        sdo_request_base: 0x380,
+        sdo_response_base: 0x300,
+        ..config_set
+    };
+    node.set_connection_set(runtime_set);
+    assert_eq!(
+        Some((CanId::std(0x383), CanId::std(0x303))),
+        node.sdo_cob_ids()
+    );
+
+    let mut client = SdoClient::new_with_connection_set(
+        NODE_ID,
+        &runtime_set,
+        bus.new_sender(),
+        bus.new_receiver(),
+    );
+    let mut std_client = get_sdo_client(&mut bus, NODE_ID);
+    let test_task = move |_ctx| async move {
+        assert_eq!(5000, client.read_u32(0x1018, 1).await.unwrap());
+        // Requests on the standard ID are ignored
+        assert!(matches!(
+            std_client.read_u32(0x1018, 1).await,
+            Err(SdoClientError::NoResponse)
+        ));
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+    node.set_connection_set(config_set);
+}
+
+#[serial]
+#[tokio::test]
+async fn test_concurrent_array_access() {
+    use object_dict1::*;
+    use zencan_node::object_dict::ObjectAccess as _;
+
+    let original = [OBJECT2000.get(0).unwrap(), OBJECT2000.get(1).unwrap()];
+
+    // The application updates the array from one thread while another reads it as the SDO server
+    // would. Every read must return one of the written values.
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            for i in 0..20000u32 {
+                let value = if i % 2 == 0 { 0 } else { u32::MAX };
+                OBJECT2000.set(0, value).unwrap();
+                OBJECT2000.write(2, &value.to_le_bytes()).unwrap();
+            }
+        });
+        scope.spawn(|| {
+            for _ in 0..20000 {
+                for sub in 1..=2 {
+                    let value = OBJECT2000.read_u32(sub).unwrap();
+                    assert!(value == 0 || value == u32::MAX || original.contains(&value));
+                }
+            }
+        });
+    });
+
+    OBJECT2000.set(0, original[0]).unwrap();
+    OBJECT2000.set(1, original[1]).unwrap();
+}
@@ -1,7 +1,11 @@
-use integration_tests::object_dict1::{NODE_STATE, OBJECT3008, OBJECT3009, OBJECT300A};
+use integration_tests::object_dict1::{
+    NODE_STATE, OBJECT3008, OBJECT3009, OBJECT300A, OBJECT300E, OBJECT300F, OBJECT3012,
+};
+use serial_test::serial;
 use zencan_node::object_dict::ObjectAccess;
 
 #[test]
+#[serial]
 fn test_event_flags() {
     fn test_event_flags(obj: &dyn ObjectAccess, n: u8) {
         // No flags set after toggle
@@ -34,4 +38,60 @@ fn test_event_flags() {
     test_event_flags(&OBJECT3008, 7);
     test_event_flags(&OBJECT3009, 8);
     test_event_flags(&OBJECT300A, 9);
+    test_event_flags(&OBJECT300E, 101);
+    test_event_flags(&OBJECT300F, 10);
+}
+
+/// Clear any flags left over from previous tests in both the A and B flag sets
+fn clear_both_banks(obj: &dyn ObjectAccess) {
+    for _ in 0..2 {
+        NODE_STATE.object_flag_sync().toggle();
+        obj.clear_events();
+    }
+}
+
+#[test]
+#[serial]
+fn test_next_event_flag() {
+    clear_both_banks(&OBJECT300E);
+    OBJECT300E.set_event_flag(3).unwrap();
+    OBJECT300E.set_event_flag(70).unwrap();
+    OBJECT300E.set_event_flag(100).unwrap();
+    NODE_STATE.object_flag_sync().toggle();
+
+    assert_eq!(Some(3), OBJECT300E.next_event_flag(0));
+    assert_eq!(Some(70), OBJECT300E.next_event_flag(4));
+    assert_eq!(Some(100), OBJECT300E.next_event_flag(71));
+    assert_eq!(None, OBJECT300E.next_event_flag(101));
+}
+
+#[test]
+#[serial]
+fn test_capped_event_flags() {
+    // The object caps its event flags at 10, which fits in a single 32-bit word. Setting a flag past
+    // the end of that word is accepted, but the flag is dropped and never reads back as set.
+    clear_both_banks(&OBJECT300F);
+    OBJECT300F.set_event_flag(31).unwrap();
+    OBJECT300F.set_event_flag(32).unwrap();
+    OBJECT300F.set_event_flag(100).unwrap();
+    NODE_STATE.object_flag_sync().toggle();
+
+    assert!(OBJECT300F.read_event_flag(31));
+    assert!(!OBJECT300F.read_event_flag(32));
+    assert!(!OBJECT300F.read_event_flag(100));
+    assert_eq!(None, OBJECT300F.next_event_flag(32));
+}
+
+#[test]
+#[serial]
+fn test_sparse_record_event_flags() {
+    // Flags are sized by the highest sub index of the record, not by the number of subs
+    clear_both_banks(&OBJECT3012);
+    OBJECT3012.set_sub6_and_notify(1);
+    OBJECT3012.set_far_and_notify(2);
+    NODE_STATE.object_flag_sync().toggle();
+
+    assert_eq!(Some(6), OBJECT3012.next_event_flag(0));
+    assert_eq!(Some(40), OBJECT3012.next_event_flag(7));
+    assert!(OBJECT3012.read_event_flag(40));
 }
@@ -3,19 +3,25 @@
 
 use std::time::Duration;
 
-use integration_tests::{object_dict1, prelude::*};
+use integration_tests::{object_dict1, object_dict6, prelude::*};
 use serial_test::serial;
 use tokio::time::timeout;
-use zencan_client::nmt_master::NmtMaster;
+use zencan_client::{nmt_master::NmtMaster, BusManager, SyncProducer};
 use zencan_common::{
     i24,
-    messages::{CanId, CanMessage, SyncObject},
+    messages::{CanId, CanMessage, NmtCommand, NmtCommandSpecifier, SyncObject, SYNC_ID},
+    nmt::NmtState,
     node_configuration::PdoConfig,
+    node_id::ConfiguredNodeId,
     pdo::PdoMapping,
+    sdo::AbortCode,
     traits::{AsyncCanReceiver, AsyncCanSender},
     u24, NodeId,
 };
-use zencan_node::object_dict::ObjectAccess as _;
+use zencan_node::{
+    object_dict::{find_object, ObjectAccess as _},
+    pdo::{InactiveEventPolicy, PdoKind},
+};
 
 #[serial]
 #[tokio::test]
@@ -50,6 +56,12 @@ async fn test_rpdo_assignment() {
         // Check initial value of RPDO1 cob_id
         assert_eq!(0x300, client.read_u32(0x1400, 1).await.unwrap());
 
+        // The RPDO is valid, so it must be invalidated before its COB-ID can be changed
+        client
+            .write_u32(0x1400, 1, 0x300 | (1 << 31))
+            .await
+            .unwrap();
+
         // Set COB-ID and readback
         // Invalid bit cleared, and ID == 0x201.
         let cob_id_word: u32 = 0x201;
@@ -89,7 +101,7 @@ async fn test_rpdo_assignment() {
         // Next 3 bytes are 0x300Csub12
         pdo_data[4..7].copy_from_slice(&u24::new(0x010203).to_le_bytes());
         pdo_sender
-            .send(CanMessage::new(CanId::Std(0x201), &pdo_data))
+            .send(CanMessage::new(CanId::Std(0x201), &pdo_data).with_timestamp(1_000_250))
             .await
             .unwrap();
 
@@ -101,6 +113,21 @@ async fn test_rpdo_assignment() {
             u24::new(0x010203),
             client.read_u24(0x300C, 12).await.unwrap()
         );
+
+        // Receive timestamps are available to the application
+        assert_eq!(Some(1_000_250), NODE_STATE.rpdos()[0].rx_timestamp());
+        let sync_msg: CanMessage = SyncObject::new(None).into();
+        pdo_sender
+            .send(sync_msg.with_timestamp(1_000_000))
+            .await
+            .unwrap();
+        assert_eq!(Some(1_000_000), NODE_MBOX.last_sync_timestamp());
+        // Messages without a timestamp clear it
+        pdo_sender
+            .send(CanMessage::new(CanId::Std(0x201), &pdo_data))
+            .await
+            .unwrap();
+        assert_eq!(None, NODE_STATE.rpdos()[0].rx_timestamp());
     };
 
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
@@ -338,6 +365,147 @@ async fn test_tpdo_event_flags() {
         assert!(rx.try_recv().is_none());
 
         assert_eq!(CanId::std(0x182), pdomsg.id);
+
+        // The generated notify setters update the value and set the event flag in one step
+        OBJECT2001.set_sub1_and_notify(334);
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx
+            .try_recv()
+            .expect("No message received after set_sub1_and_notify");
+        assert!(rx.try_recv().is_none());
+        assert_eq!(CanId::std(0x181), pdomsg.id);
+        assert_eq!(
+            334,
+            u32::from_le_bytes(pdomsg.data()[4..8].try_into().unwrap())
+        );
+
+        OBJECT2000.set_and_notify(0, 223).unwrap();
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx
+            .try_recv()
+            .expect("No message received after set_and_notify");
+        assert_eq!(CanId::std(0x181), pdomsg.id);
+        assert_eq!(
+            223,
+            u32::from_le_bytes(pdomsg.data()[0..4].try_into().unwrap())
+        );
+        assert_eq!(
+            Err(AbortCode::NoSuchSubIndex),
+            OBJECT2000.set_and_notify(2, 0)
+        );
+
+        OBJECT3000.set_value_and_notify(445);
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx
+            .try_recv()
+            .expect("No message received after set_value_and_notify");
+        assert_eq!(CanId::std(0x182), pdomsg.id);
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// An object mapped into two TPDOs must trigger both of them
+#[serial]
+#[tokio::test]
+async fn test_tpdo_mirrored_event_flags() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut rx = bus.new_receiver();
+
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        // TPDO0 and TPDO1 both carry 0x2000sub1, and TPDO1 also carries 0x3000
+        client
+            .configure_tpdo(
+                0,
+                &PdoConfig {
+                    cob_id: CanId::std(0x181),
+                    enabled: true,
+                    rtr_disabled: false,
+                    mappings: vec![PdoMapping {
+                        index: 0x2000,
+                        sub: 1,
+                        size: 32,
+                    }],
+                    transmission_type: 254,
+                },
+            )
+            .await
+            .unwrap();
+        client
+            .configure_tpdo(
+                1,
+                &PdoConfig {
+                    cob_id: CanId::std(0x182),
+                    enabled: true,
+                    rtr_disabled: false,
+                    mappings: vec![
+                        PdoMapping {
+                            index: 0x3000,
+                            sub: 0,
+                            size: 32,
+                        },
+                        PdoMapping {
+                            index: 0x2000,
+                            sub: 1,
+                            size: 32,
+                        },
+                    ],
+                    transmission_type: 254,
+                },
+            )
+            .await
+            .unwrap();
+
+        client.write_u32(0x2000, 1, 222).await.unwrap();
+        client.write_u32(0x3000, 0, 444).await.unwrap();
+
+        nmt.nmt_start(0).await.unwrap();
+        ctx.wait_for_process(1).await;
+        rx.flush();
+
+        // Each object records the TPDOs it is mapped into, including TPDO2 from its defaults
+        assert_eq!(0b011, OBJECT2000.tpdo_event_mask());
+        assert_eq!(0b110, OBJECT3000.tpdo_event_mask());
+
+        // An event on the shared object sends both PDOs
+        OBJECT2000.set_event_flag(1).unwrap();
+        ctx.wait_for_process(1).await;
+        let mut ids = vec![
+            rx.try_recv().expect("Missing first TPDO").id,
+            rx.try_recv().expect("Missing second TPDO").id,
+        ];
+        ids.sort_by_key(|id| id.raw());
+        assert_eq!(vec![CanId::std(0x181), CanId::std(0x182)], ids);
+        assert!(rx.try_recv().is_none());
+
+        // Nothing further is sent once the event has been handled
+        ctx.wait_for_process(2).await;
+        assert!(rx.try_recv().is_none());
+
+        // An event on an object only in TPDO1 sends only TPDO1
+        OBJECT3000.set_event_flag(0).unwrap();
+        ctx.wait_for_process(1).await;
+        let msg = rx.try_recv().expect("Missing TPDO1");
+        assert_eq!(CanId::std(0x182), msg.id);
+        assert!(rx.try_recv().is_none());
     };
 
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
@@ -381,7 +549,7 @@ async fn test_tpdo_sync_initiated_transmission() {
         rx: &mut SimBusReceiver,
         ctx: &mut TestContext,
     ) {
-        *sync_counter += 1_;
+        *sync_counter += 1;
         let sync_msg = SyncObject::new(Some(*sync_counter)).into();
         sender.send(sync_msg).await.unwrap();
         let msg = rx
@@ -632,6 +800,174 @@ async fn test_pdo_configuration() {
     }
 }
 
+/// Check that the application is notified when a PDO is reconfigured, and can read its mappings
+#[serial]
+#[tokio::test]
+async fn test_pdo_config_changed_callback() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let (changed_tx, changed_rx) = std::sync::mpsc::channel();
+    let mut pdo_config_changed = |kind, num| changed_tx.send((kind, num)).unwrap();
+    let mut callbacks = Callbacks::new();
+    callbacks.pdo_config_changed = Some(&mut pdo_config_changed);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        ctx.wait_for_process(2).await;
+        assert!(changed_rx.try_recv().is_err());
+
+        let mappings = vec![
+            PdoMapping {
+                index: 0x2000,
+                sub: 2,
+                size: 32,
+            },
+            PdoMapping {
+                index: 0x2001,
+                sub: 1,
+                size: 32,
+            },
+        ];
+        let config = PdoConfig {
+            cob_id: CanId::std(0x322),
+            enabled: true,
+            rtr_disabled: false,
+            mappings: mappings.clone(),
+            transmission_type: 254,
+        };
+        client.configure_rpdo(2, &config).await.unwrap();
+        ctx.wait_for_process(2).await;
+
+        let changes: Vec<_> = changed_rx.try_iter().collect();
+        assert!(!changes.is_empty());
+        assert!(changes.iter().all(|&change| change == (PdoKind::Rpdo, 2)));
+        assert_eq!(mappings, NODE_STATE.rpdos()[2].mappings().to_vec());
+
+        // Reading the configuration is not a change
+        client.read_rpdo_config(2).await.unwrap();
+        ctx.wait_for_process(2).await;
+        assert!(changed_rx.try_recv().is_err());
+
+        let tpdo1_count = client.read_u8(0x1A01, 0).await.unwrap();
+        client.write_u8(0x1A01, 0, 0).await.unwrap();
+        ctx.wait_for_process(2).await;
+        assert_eq!(
+            vec![(PdoKind::Tpdo, 1)],
+            changed_rx.try_iter().collect::<Vec<_>>()
+        );
+        assert!(NODE_STATE.tpdos()[1].mappings().is_empty());
+        client.write_u8(0x1A01, 0, tpdo1_count).await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// Check that a TPDO can be reconfigured while the node is operational
+#[serial]
+#[tokio::test]
+async fn test_tpdo_live_reconfiguration() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut rx = bus.new_receiver();
+
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let mut config = PdoConfig {
+            cob_id: CanId::std(0x181),
+            enabled: true,
+            rtr_disabled: false,
+            mappings: vec![PdoMapping {
+                index: 0x3000,
+                sub: 0,
+                size: 32,
+            }],
+            transmission_type: 254,
+        };
+        client.configure_tpdo(0, &config).await.unwrap();
+
+        nmt.nmt_start(0).await.unwrap();
+        ctx.wait_for_process(1).await;
+        rx.flush();
+
+        OBJECT3000.set_value_and_notify(1);
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx.try_recv().expect("No message received after TPDO event");
+        assert_eq!(CanId::std(0x181), pdomsg.id);
+
+        // The COB-ID of a valid PDO cannot be changed
+        let result = client.write_u32(0x1800, 1, 0x185).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::InvalidValue),
+                ..
+            })
+        ));
+        // Nor can its mapping while operational
+        assert!(client.write_u8(0x1A00, 0, 0).await.is_err());
+
+        // configure_tpdo disables the PDO while it is changed, so it can be used while operational
+        config.cob_id = CanId::std(0x185);
+        config.mappings.push(PdoMapping {
+            index: 0x2001,
+            sub: 1,
+            size: 32,
+        });
+        client.configure_tpdo(0, &config).await.unwrap();
+        assert_eq!(0x185, client.read_u32(0x1800, 1).await.unwrap());
+
+        // The new configuration is used immediately, without an NMT reset
+        rx.flush();
+        OBJECT3000.set_value_and_notify(2);
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx.try_recv().expect("No message received after TPDO event");
+        assert_eq!(CanId::std(0x185), pdomsg.id);
+        assert_eq!(8, pdomsg.data().len());
+        assert_eq!(
+            2,
+            u32::from_le_bytes(pdomsg.data()[0..4].try_into().unwrap())
+        );
+
+        // A disabled PDO is not sent
+        config.enabled = false;
+        client.configure_tpdo(0, &config).await.unwrap();
+        rx.flush();
+        OBJECT3000.set_value_and_notify(3);
+        ctx.wait_for_process(1).await;
+        assert!(rx.try_recv().is_none());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
 /// Check that the PDOs have the default values defined in example1.toml after node init
 #[serial]
 #[tokio::test]
@@ -655,7 +991,7 @@ async fn test_pdo_defaults() {
     let test_task = move |_ctx| async move {
         // Check that the initial value matches the one defined in example1.toml
         let tpdo1_cfg = client.read_tpdo_config(1).await.unwrap();
-        assert_eq!(true, tpdo1_cfg.enabled);
+        assert!(tpdo1_cfg.enabled);
         assert_eq!(CanId::std(0x201), tpdo1_cfg.cob_id);
         assert_eq!(254, tpdo1_cfg.transmission_type);
         assert_eq!(1, tpdo1_cfg.mappings.len());
@@ -664,7 +1000,7 @@ async fn test_pdo_defaults() {
         assert_eq!(32, tpdo1_cfg.mappings[0].size);
 
         let rpdo0_cfg = client.read_rpdo_config(0).await.unwrap();
-        assert_eq!(true, rpdo0_cfg.enabled);
+        assert!(rpdo0_cfg.enabled);
         assert_eq!(CanId::std(0x300), rpdo0_cfg.cob_id);
         assert_eq!(254, rpdo0_cfg.transmission_type);
         assert_eq!(2, rpdo0_cfg.mappings.len());
@@ -678,3 +1014,680 @@ async fn test_pdo_defaults() {
 
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
 }
+
+/// Test that the synchronous window length (0x1007) is enforced for synchronous PDOs
+#[serial]
+#[tokio::test]
+async fn test_sync_window_enforcement() {
+    use object_dict1::*;
+    use zencan_node::object_dict::find_object;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut sender = bus.new_sender();
+    let mut rx = bus.new_receiver();
+
+    // Make RPDO0 (COB ID 0x300) and TPDO1 (COB ID 0x201) synchronous
+    let rpdo0_comm = find_object(&OD_TABLE, 0x1400).unwrap();
+    rpdo0_comm.write(2, &[0]).unwrap();
+    let tpdo1_comm = find_object(&OD_TABLE, 0x1801).unwrap();
+    tpdo1_comm.write(2, &[1]).unwrap();
+    OBJECT1007.set_value(1000);
+
+    node.process(0);
+    nmt.nmt_start(0).await.unwrap();
+    node.process(0);
+    bus.flush_mailboxes();
+    while rx.try_recv().is_some() {}
+
+    let rpdo = |value: u32| CanMessage::new(CanId::std(0x300), &value.to_le_bytes());
+
+    // With receive timestamps, RPDOs are checked against the SYNC receive time
+    let sync: CanMessage = SyncObject::new(None).into();
+    sender.send(sync.with_timestamp(1_000_000)).await.unwrap();
+    sender
+        .send(rpdo(100).with_timestamp(1_000_500))
+        .await
+        .unwrap();
+    node.process(10);
+    assert_eq!(100, OBJECT2000.get(1).unwrap());
+    sender
+        .send(rpdo(200).with_timestamp(1_002_000))
+        .await
+        .unwrap();
+    node.process(20);
+    assert_eq!(100, OBJECT2000.get(1).unwrap());
+
+    // Without timestamps, the process time is used
+    sender.send(sync).await.unwrap();
+    node.process(1000);
+    sender.send(rpdo(300)).await.unwrap();
+    node.process(1500);
+    assert_eq!(300, OBJECT2000.get(1).unwrap());
+    sender.send(rpdo(400)).await.unwrap();
+    node.process(3000);
+    assert_eq!(300, OBJECT2000.get(1).unwrap());
+
+    // A synchronous TPDO which is not transmitted before the window closes is dropped
+    sender.send(sync).await.unwrap();
+    node.process(5000);
+    node.process(7000);
+    bus.flush_mailboxes();
+    while let Some(msg) = rx.try_recv() {
+        assert_ne!(CanId::std(0x201), msg.id());
+    }
+
+    // A TPDO transmitted within the window is sent
+    sender.send(sync).await.unwrap();
+    node.process(8000);
+    bus.flush_mailboxes();
+    assert_eq!(SYNC_ID, rx.try_recv().unwrap().id());
+    assert_eq!(CanId::std(0x201), rx.try_recv().unwrap().id());
+
+    OBJECT1007.set_value(0);
+}
+
+#[serial]
+#[tokio::test]
+async fn test_extended_tpdo_default() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut rx = bus.new_receiver();
+
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        // TPDO2 is configured in example1.toml with an extended ID, disabled by default
+        let cob_id = client.read_u32(0x1802, 1).await.unwrap();
+        assert_eq!(
+            (1 << 31) | (1 << 29) | (0x18000200 + NODE_ID as u32),
+            cob_id
+        );
+
+        client
+            .write_u32(0x1802, 1, cob_id & !(1 << 31))
+            .await
+            .unwrap();
+
+        nmt.nmt_start(0).await.unwrap();
+        ctx.wait_for_process(1).await;
+        rx.flush();
+
+        OBJECT3000.set_value_and_notify(0x1234);
+        ctx.wait_for_process(1).await;
+        let pdomsg = rx.try_recv().expect("No message received after TPDO event");
+        assert_eq!(CanId::extended(0x18000200 + NODE_ID as u32), pdomsg.id);
+        assert_eq!(0x1234u32.to_le_bytes(), pdomsg.data());
+
+        // Restore the default so later tests see a disabled PDO
+        client.write_u32(0x1802, 1, cob_id).await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_sync_producer_with_start_value() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+    const PERIOD: Duration = Duration::from_millis(2);
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut rx = bus.new_receiver();
+
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut producer = SyncProducer::new(bus.new_sender(), PERIOD);
+    producer.set_counter_overflow(4);
+
+    let test_task = move |mut ctx: TestContext| async move {
+        // Send on every 2nd SYNC, starting with the SYNC with counter 2
+        let config = PdoConfig {
+            cob_id: CanId::std(0x181),
+            enabled: true,
+            rtr_disabled: false,
+            mappings: vec![PdoMapping {
+                index: 0x3000,
+                sub: 0,
+                size: 32,
+            }],
+            transmission_type: 2,
+        };
+        client.configure_tpdo(0, &config).await.unwrap();
+        client.write_u8(0x1800, 6, 2).await.unwrap();
+
+        nmt.nmt_start(0).await.unwrap();
+        ctx.wait_for_process(1).await;
+        rx.flush();
+
+        let start = tokio::time::Instant::now();
+        let mut sent_after = Vec::new();
+        for _ in 0..8 {
+            producer.tick().await.unwrap();
+            ctx.wait_for_process(2).await;
+            let sync = SyncObject::from(rx.try_recv().expect("No SYNC received"));
+            if let Some(msg) = rx.try_recv() {
+                assert_eq!(CanId::std(0x181), msg.id());
+                sent_after.push(sync.count.unwrap());
+            }
+        }
+        assert!(start.elapsed() >= PERIOD * 7);
+        assert_eq!(vec![2, 4, 2, 4], sent_after);
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// Check that mappings which do not match the access type of the mapped object are rejected
+#[serial]
+#[tokio::test]
+async fn test_unmappable_objects() {
+    use object_dict1::*;
+    use std::sync::Arc;
+    use zencan_client::ObjectInfo;
+    use zencan_common::device_config::DeviceConfig;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let mut info_client = get_sdo_client(&mut bus, NODE_ID);
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+    info_client.set_object_info(Arc::new(ObjectInfo::from_device_config(&config)));
+
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let pdo_config = |index, size| PdoConfig {
+        cob_id: CanId::std(0x201),
+        enabled: true,
+        rtr_disabled: true,
+        mappings: vec![PdoMapping {
+            index,
+            sub: 0,
+            size,
+        }],
+        transmission_type: 254,
+    };
+
+    let test_task = move |_ctx| async move {
+        // 0x3004 is read-only, so it can be mapped to a TPDO but not to an RPDO
+        client
+            .configure_tpdo(0, &pdo_config(0x3004, 16))
+            .await
+            .unwrap();
+        assert_eq!(
+            Err(SdoClientError::ObjectNotMappable {
+                index: 0x3004,
+                sub: 0,
+                pdo: "RPDO",
+                reason: "the node does not allow it to be mapped",
+            }),
+            client.configure_rpdo(0, &pdo_config(0x3004, 16)).await
+        );
+
+        // With object metadata, mappings are checked before they are sent
+        assert_eq!(
+            Err(SdoClientError::ObjectNotMappable {
+                index: 0x3004,
+                sub: 0,
+                pdo: "RPDO",
+                reason: "the object is read-only",
+            }),
+            info_client.configure_rpdo(0, &pdo_config(0x3004, 16)).await
+        );
+        assert_eq!(
+            Err(SdoClientError::ObjectNotMappable {
+                index: 0x3000,
+                sub: 0,
+                pdo: "RPDO",
+                reason: "the object does not allow RPDO mapping",
+            }),
+            info_client.configure_rpdo(0, &pdo_config(0x3000, 32)).await
+        );
+        assert_eq!(
+            Err(SdoClientError::ObjectNotMappable {
+                index: 0x3000,
+                sub: 0,
+                pdo: "TPDO",
+                reason: "the mapping is larger than the object",
+            }),
+            info_client.configure_tpdo(0, &pdo_config(0x3000, 40)).await
+        );
+        info_client
+            .configure_tpdo(0, &pdo_config(0x3000, 32))
+            .await
+            .unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[serial]
+#[tokio::test]
+async fn test_node_id_change_rebinds_pdos() {
+    use object_dict1::*;
+
+    let mut node = Node::new(
+        NodeId::new(5).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.process(0);
+    let tpdos = NODE_STATE.tpdos();
+    assert_eq!(CanId::std(0x205), tpdos[1].cob_id());
+
+    // A COB ID written to a PDO does not follow the node ID
+    OD_TABLE
+        .iter()
+        .find(|entry| entry.index == 0x1802)
+        .unwrap()
+        .data
+        .write(1, &(0x8000_0250u32).to_le_bytes())
+        .unwrap();
+    assert_eq!(CanId::std(0x250), tpdos[2].cob_id());
+
+    node.set_node_id(NodeId::new(9).unwrap());
+    node.process(1000);
+    assert_eq!(9, node.node_id());
+    assert_eq!(CanId::std(0x209), tpdos[1].cob_id());
+    assert_eq!(CanId::std(0x250), tpdos[2].cob_id());
+    // RPDO0 does not add the node ID
+    assert_eq!(CanId::std(0x300), NODE_STATE.rpdos()[0].cob_id());
+
+    // When disabled, the COB IDs are updated on the next reset
+    node.set_rebind_pdo_cob_ids(false);
+    node.set_node_id(NodeId::new(10).unwrap());
+    node.process(2000);
+    assert_eq!(10, node.node_id());
+    assert_eq!(CanId::std(0x209), tpdos[1].cob_id());
+}
+
+#[serial]
+#[tokio::test]
+async fn test_pdos_across_nmt_states() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+    // TPDO1 maps 0x2000sub1, and RPDO0 maps 0x2000sub2 and 0x300Csub12 by default
+    const TPDO_ID: CanId = CanId::std(0x201);
+    const RPDO_ID: CanId = CanId::std(0x300);
+
+    let original = (OBJECT2000.get(0).unwrap(), OBJECT2000.get(1).unwrap());
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut pdo_sender = bus.new_sender();
+    let mut raw_rx = bus.new_receiver();
+
+    // Process the node, and return the values of the TPDOs it sent
+    let mut process = |node: &mut Node, bus: &mut SimBus, now_us: u64| -> Vec<u32> {
+        node.process(now_us);
+        bus.flush_mailboxes();
+        let mut values = Vec::new();
+        while let Some(msg) = raw_rx.try_recv() {
+            if msg.id() == TPDO_ID {
+                values.push(u32::from_le_bytes(msg.data()[0..4].try_into().unwrap()));
+            }
+        }
+        values
+    };
+    let raise_event = |value: u32| {
+        OBJECT2000.set(0, value).unwrap();
+        OBJECT2000.set_event_flag(1).unwrap();
+    };
+    let rpdo = |value: u32| {
+        let mut data = [0u8; 7];
+        data[0..4].copy_from_slice(&value.to_le_bytes());
+        CanMessage::new(RPDO_ID, &data)
+    };
+
+    // Boot into PreOperational
+    process(&mut node, &mut bus, 0);
+
+    // Nothing is exchanged while PreOperational
+    raise_event(1);
+    pdo_sender.send(rpdo(10)).await.unwrap();
+    assert!(process(&mut node, &mut bus, 1000).is_empty());
+    assert_eq!(original.1, OBJECT2000.get(1).unwrap());
+
+    // By default, the event is sent with the latest value on start, and the RPDO received while
+    // PreOperational is not applied
+    raise_event(2);
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    assert_eq!(vec![2], process(&mut node, &mut bus, 2000));
+    assert_eq!(original.1, OBJECT2000.get(1).unwrap());
+
+    // An RPDO received after the start command is applied, even if the command has not yet been
+    // processed
+    nmt.nmt_stop(NODE_ID).await.unwrap();
+    process(&mut node, &mut bus, 3000);
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    pdo_sender.send(rpdo(20)).await.unwrap();
+    process(&mut node, &mut bus, 4000);
+    assert_eq!(20, OBJECT2000.get(1).unwrap());
+
+    // An RPDO received before a stop command is dropped, and so is a TPDO queued before it
+    raise_event(3);
+    node.process(5000);
+    pdo_sender.send(rpdo(30)).await.unwrap();
+    nmt.nmt_stop(NODE_ID).await.unwrap();
+    assert!(process(&mut node, &mut bus, 6000).is_empty());
+    assert_eq!(NmtState::Stopped, node.nmt_state());
+    assert_eq!(20, OBJECT2000.get(1).unwrap());
+
+    // The dropped TPDO is sent on start
+    raise_event(4);
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    assert_eq!(vec![4], process(&mut node, &mut bus, 7000));
+
+    // With the discard policy, only events raised while Operational are sent
+    node.set_inactive_event_policy(InactiveEventPolicy::Discard);
+    let enter_preop = NmtCommand {
+        cs: NmtCommandSpecifier::EnterPreOp,
+        node: NODE_ID,
+    };
+    pdo_sender.send(enter_preop.into()).await.unwrap();
+    process(&mut node, &mut bus, 8000);
+    raise_event(5);
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    assert!(process(&mut node, &mut bus, 9000).is_empty());
+    raise_event(6);
+    assert_eq!(vec![6], process(&mut node, &mut bus, 10000));
+
+    OBJECT2000.set(0, original.0).unwrap();
+    OBJECT2000.set(1, original.1).unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn test_status_pdo() {
+    use object_dict6::*;
+    const NODE_ID: u8 = 3;
+    // example6 configures TPDO1 as the status TPDO, at 0x280 + node ID every 100ms
+    const STATUS_ID: CanId = CanId::std(0x283);
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut raw_rx = bus.new_receiver();
+
+    // Process the node, and return the status TPDOs it sent
+    let mut process = |node: &mut Node, bus: &mut SimBus, now_us: u64| -> Vec<CanMessage> {
+        node.process(now_us);
+        bus.flush_mailboxes();
+        let mut messages = Vec::new();
+        while let Some(msg) = raw_rx.try_recv() {
+            if msg.id() == STATUS_ID {
+                messages.push(msg);
+            }
+        }
+        messages
+    };
+
+    // The TPDO is configured from the device config
+    let comm = find_object(&OD_TABLE, 0x1801).unwrap();
+    assert_eq!(0x283, comm.read_u32(1).unwrap());
+    assert_eq!(254, comm.read_u8(2).unwrap());
+    assert_eq!(100, comm.read_u16(5).unwrap());
+
+    // No status is sent while PreOperational
+    for t in 0..5 {
+        assert!(process(&mut node, &mut bus, t * 100_000).is_empty());
+    }
+
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    assert!(process(&mut node, &mut bus, 1_000_000).is_empty());
+    let mut sent = Vec::new();
+    for t in 1..=10 {
+        sent.extend(process(&mut node, &mut bus, 1_000_000 + t * 50_000));
+    }
+    // One status per period
+    assert_eq!(5, sent.len());
+
+    let msg = sent.last().unwrap();
+    assert_eq!(8, msg.data().len());
+    let data = msg.data();
+    // Uptime in seconds
+    assert_eq!(1, u32::from_le_bytes([data[0], data[1], data[2], 0]));
+    // Error register
+    assert_eq!(0, data[3]);
+    // Message counters, as of when the status was sent
+    let diagnostics = node.diagnostics();
+    let rx_count = u16::from_le_bytes([data[4], data[5]]);
+    assert!(rx_count > 0 && rx_count as u32 <= diagnostics.rx_messages());
+    let tx_count = u16::from_le_bytes([data[6], data[7]]);
+    assert!(tx_count > 0 && tx_count as u32 <= diagnostics.tx_messages());
+    // The transmit count includes the previous status
+    assert_eq!(
+        u16::from_le_bytes([sent[0].data()[6], sent[0].data()[7]]) + 4,
+        tx_count
+    );
+
+    // The period can be changed at runtime, and restarts from the next process call
+    comm.write(5, &500u16.to_le_bytes()).unwrap();
+    let mut sent = Vec::new();
+    for t in 1..=30 {
+        let now_us = 1_500_000 + t * 50_000;
+        sent.extend(
+            process(&mut node, &mut bus, now_us)
+                .into_iter()
+                .map(|_| now_us),
+        );
+    }
+    assert_eq!(vec![2_050_000, 2_550_000], sent);
+
+    // And the status stops when the node is stopped
+    nmt.nmt_stop(NODE_ID).await.unwrap();
+    for t in 1..=10 {
+        assert!(process(&mut node, &mut bus, 3_000_000 + t * 100_000).is_empty());
+    }
+}
+
+#[serial]
+#[tokio::test]
+async fn test_tpdo_inhibit_time() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+    // TPDO1 maps 0x2000sub1 by default
+    const TPDO_ID: CanId = CanId::std(0x201);
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut nmt = NmtMaster::new(bus.new_sender(), bus.new_receiver());
+    let mut raw_rx = bus.new_receiver();
+
+    // Process the node, and return the values of the TPDOs it sent
+    let mut process = |node: &mut Node, bus: &mut SimBus, now_us: u64| -> Vec<u32> {
+        node.process(now_us);
+        bus.flush_mailboxes();
+        let mut values = Vec::new();
+        while let Some(msg) = raw_rx.try_recv() {
+            if msg.id() == TPDO_ID {
+                values.push(u32::from_le_bytes(msg.data()[0..4].try_into().unwrap()));
+            }
+        }
+        values
+    };
+    let raise_event = |value: u32| {
+        OBJECT2000.set(0, value).unwrap();
+        OBJECT2000.set_event_flag(1).unwrap();
+    };
+
+    process(&mut node, &mut bus, 0);
+
+    // Set a 10ms inhibit time
+    let comm = find_object(&OD_TABLE, 0x1801).unwrap();
+    assert_eq!(0, comm.read_u16(3).unwrap());
+    comm.write(3, &100u16.to_le_bytes()).unwrap();
+    assert_eq!(100, comm.read_u16(3).unwrap());
+
+    nmt.nmt_start(NODE_ID).await.unwrap();
+    process(&mut node, &mut bus, 1000);
+    assert_eq!(None, node.time_until_tpdo_us());
+
+    // The inhibit time can't be changed while the PDO is valid and the node is operational
+    assert!(comm.write(3, &0u16.to_le_bytes()).is_err());
+
+    // The first event is sent immediately
+    raise_event(1);
+    assert_eq!(vec![1], process(&mut node, &mut bus, 10_000));
+    assert_eq!(None, node.time_until_tpdo_us());
+
+    // Further events during the inhibit time are combined, and sent with the latest value exactly
+    // when it expires
+    raise_event(2);
+    assert!(process(&mut node, &mut bus, 12_000).is_empty());
+    assert_eq!(Some(8_000), node.time_until_tpdo_us());
+    raise_event(3);
+    assert!(process(&mut node, &mut bus, 15_000).is_empty());
+    assert_eq!(Some(5_000), node.time_until_tpdo_us());
+    assert!(process(&mut node, &mut bus, 19_999).is_empty());
+    assert_eq!(Some(1), node.time_until_tpdo_us());
+    assert_eq!(vec![3], process(&mut node, &mut bus, 20_000));
+    assert_eq!(None, node.time_until_tpdo_us());
+
+    // An event after the inhibit time has expired is sent immediately
+    assert!(process(&mut node, &mut bus, 35_000).is_empty());
+    raise_event(4);
+    assert_eq!(vec![4], process(&mut node, &mut bus, 35_500));
+
+    // No timers are reported when the node is not operational
+    raise_event(5);
+    process(&mut node, &mut bus, 36_000);
+    assert_eq!(Some(9_500), node.time_until_tpdo_us());
+    nmt.nmt_stop(NODE_ID).await.unwrap();
+    assert!(process(&mut node, &mut bus, 37_000).is_empty());
+    assert_eq!(None, node.time_until_tpdo_us());
+}
+
+#[serial]
+#[tokio::test]
+async fn test_bus_manager_tpdo_subscription() {
+    use futures::StreamExt;
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut manager = BusManager::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let node_id = ConfiguredNodeId::new(NODE_ID).unwrap();
+        // TPDO1 is sent on 0x200 + node ID by default
+        let mut tpdo = manager.subscribe_tpdo(node_id, 1).await.unwrap();
+        assert_eq!(CanId::std(0x201), tpdo.cob_id());
+        let mut other = manager.subscribe(CanId::std(0x202));
+
+        manager.nmt_start(NODE_ID).await;
+        ctx.wait_for_process(2).await;
+        // Discard a TPDO sent on start for an event left by another test
+        while tpdo.try_recv().is_some() {}
+
+        for value in 1..=5u32 {
+            OBJECT2000.set(0, value).unwrap();
+            OBJECT2000.set_event_flag(1).unwrap();
+            ctx.wait_for_process(2).await;
+            // SDO transfers on the same bus don't consume the TPDOs
+            let mut client = manager.sdo_client(NODE_ID);
+            assert_eq!(value, client.read_u32(0x2000, 1).await.unwrap());
+        }
+
+        let messages: Vec<CanMessage> =
+            timeout(Duration::from_secs(1), tpdo.by_ref().take(5).collect())
+                .await
+                .unwrap();
+        let values: Vec<u32> = messages
+            .iter()
+            .map(|msg| u32::from_le_bytes(msg.data()[0..4].try_into().unwrap()))
+            .collect();
+        assert_eq!(vec![1, 2, 3, 4, 5], values);
+        assert_eq!(None, tpdo.try_recv());
+        // Other subscriptions only get their own messages
+        assert_eq!(None, other.try_recv());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
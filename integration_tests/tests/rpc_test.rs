@@ -0,0 +1,177 @@
+//! Test access to a bus via the JSON-RPC server
+//!
+
+use integration_tests::{object_dict1::*, prelude::*};
+use serde_json::{json, Value};
+use serial_test::serial;
+use zencan_client::{
+    rpc::{error_codes, RpcServer},
+    BusManager,
+};
+use zencan_common::nmt::NmtState;
+
+const NODE_ID: u8 = 1;
+
+async fn call<S>(server: &mut RpcServer<S>, request: Value) -> Value
+where
+    S: zencan_common::traits::AsyncCanSender + Sync + Send,
+{
+    let response = server.handle_request(&request.to_string()).await.unwrap();
+    serde_json::from_str(&response).unwrap()
+}
+
+#[serial]
+#[tokio::test]
+async fn test_rpc_sdo_and_nmt() {
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    let mut server = RpcServer::new(BusManager::new(bus.new_sender(), bus.new_receiver()));
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "sdo.read",
+                   "params": {"node": NODE_ID, "index": 0x1008, "sub": 0}}),
+        )
+        .await;
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "id": 1, "result": {"data": b"Example 1".to_vec()}}),
+            response
+        );
+
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": "write", "method": "sdo.write",
+                   "params": {"node": NODE_ID, "index": 0x3000, "sub": 0, "data": [1, 2, 3, 4]}}),
+        )
+        .await;
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "id": "write", "result": null}),
+            response
+        );
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "sdo.read",
+                   "params": {"node": NODE_ID, "index": 0x3000, "sub": 0}}),
+        )
+        .await;
+        assert_eq!(json!({"data": [1, 2, 3, 4]}), response["result"]);
+
+        // Aborts are reported with the abort code
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "sdo.read",
+                   "params": {"node": NODE_ID, "index": 0x5FFF, "sub": 0}}),
+        )
+        .await;
+        assert_eq!(error_codes::SDO_ABORT, response["error"]["code"]);
+        assert_eq!(
+            json!({"index": 0x5FFF, "sub": 0, "abort_code": AbortCode::NoSuchObject as u32}),
+            response["error"]["data"]
+        );
+
+        // A node which does not exist does not respond
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "sdo.read",
+                   "params": {"node": 99, "index": 0x1008, "sub": 0}}),
+        )
+        .await;
+        assert_eq!(error_codes::NO_RESPONSE, response["error"]["code"]);
+
+        let response = call(
+            &mut server,
+            json!({"jsonrpc": "2.0", "id": 5, "method": "nmt.start", "params": {"node": 0}}),
+        )
+        .await;
+        assert_eq!(Value::Null, response["result"]);
+        ctx.wait_for_process(2).await;
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+    assert_eq!(NmtState::Operational, node.nmt_state());
+}
+
+#[serial]
+#[tokio::test]
+async fn test_rpc_request_errors() {
+    let mut bus = SimBus::new();
+    let mut server = RpcServer::new(BusManager::new(bus.new_sender(), bus.new_receiver()));
+
+    let response = server.handle_request("{not json").await.unwrap();
+    let response: Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(Value::Null, response["id"]);
+    assert_eq!(error_codes::PARSE_ERROR, response["error"]["code"]);
+
+    let response = call(&mut server, json!({"id": 1, "method": "nmt.start"})).await;
+    assert_eq!(error_codes::INVALID_REQUEST, response["error"]["code"]);
+
+    let response = call(
+        &mut server,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "sdo.explode"}),
+    )
+    .await;
+    assert_eq!(json!(1), response["id"]);
+    assert_eq!(error_codes::METHOD_NOT_FOUND, response["error"]["code"]);
+
+    // Missing params
+    let response = call(
+        &mut server,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "sdo.read"}),
+    )
+    .await;
+    assert_eq!(error_codes::INVALID_PARAMS, response["error"]["code"]);
+
+    // Out of range node ID
+    let response = call(
+        &mut server,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "sdo.read",
+               "params": {"node": 0, "index": 0x1000, "sub": 0}}),
+    )
+    .await;
+    assert_eq!(error_codes::INVALID_PARAMS, response["error"]["code"]);
+
+    // Notifications get no response
+    let request = json!({"jsonrpc": "2.0", "method": "sync", "params": {"count": 3}});
+    assert_eq!(None, server.handle_request(&request.to_string()).await);
+}
+
+#[serial]
+#[tokio::test]
+async fn test_rpc_serve() {
+    let mut bus = SimBus::new();
+    let mut server = RpcServer::new(BusManager::new(bus.new_sender(), bus.new_receiver()));
+
+    let input = concat!(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "nodes.list"}"#,
+        "\n\n",
+        r#"{"jsonrpc": "2.0", "method": "sync"}"#,
+        "\n",
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "sync", "params": {"count": 1}}"#,
+        "\n",
+    );
+    let mut output = Vec::new();
+    server.serve(input.as_bytes(), &mut output).await.unwrap();
+
+    let responses: Vec<Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        vec![
+            json!({"jsonrpc": "2.0", "id": 1, "result": []}),
+            json!({"jsonrpc": "2.0", "id": 2, "result": null}),
+        ],
+        responses
+    );
+}
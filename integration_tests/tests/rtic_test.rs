@@ -0,0 +1,116 @@
+//! Tests of the RX queue laid out the way an RTIC application uses it
+//!
+//! The queue is split in `init`, the producer is moved into the CAN receive interrupt task, which
+//! is run on its own thread here, and the consumer into the task which owns the node.
+use std::{sync::mpsc, thread};
+
+use integration_tests::object_dict1;
+use zencan_common::{
+    messages::{NmtCommand, NmtCommandSpecifier},
+    nmt::NmtState,
+    CanId, CanMessage, NodeId,
+};
+use zencan_node::{
+    priority_queue::PriorityQueue,
+    rtic::{RxConsumer, RxProducer, RxQueue},
+    Callbacks, Node, NodeMbox, SDO_BUFFER_SIZE,
+};
+
+/// RTIC moves local resources into the tasks which own them, so both halves must be Send
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<RxProducer<'static>>();
+    assert_send::<RxConsumer<'static>>();
+};
+
+/// The local resources of the CAN receive task
+struct CanRxLocal {
+    rx_producer: RxProducer<'static>,
+}
+
+/// The local resources of the process task
+struct ProcessLocal {
+    node: Node<'static>,
+    rx_consumer: RxConsumer<'static>,
+}
+
+/// The CAN receive interrupt handler, which queues each received frame
+fn can_rx(local: &mut CanRxLocal, received: &[CanMessage]) {
+    for msg in received {
+        local.rx_producer.enqueue(*msg).ok();
+    }
+}
+
+/// The process task, which forwards the queued frames and runs the node
+fn process(local: &mut ProcessLocal, now_us: u64) {
+    local.rx_consumer.forward(&object_dict1::NODE_MBOX);
+    local.node.process(now_us);
+}
+
+fn start_command() -> CanMessage {
+    NmtCommand {
+        cs: NmtCommandSpecifier::Start,
+        node: 1,
+    }
+    .into()
+}
+
+/// Run the receive task on another thread, as if it were a higher priority interrupt, and return
+/// once it has handled `received`
+fn run_can_rx(mut local: CanRxLocal, received: Vec<CanMessage>) {
+    let (done_tx, done_rx) = mpsc::channel();
+    thread::spawn(move || {
+        can_rx(&mut local, &received);
+        done_tx.send(()).unwrap();
+    });
+    done_rx.recv().unwrap();
+}
+
+#[test]
+fn test_generated_rx_queue_tasks() {
+    use object_dict1::*;
+
+    // init
+    let node = Node::new(
+        NodeId::new(1).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let (rx_producer, rx_consumer) = RX_QUEUE.split().unwrap();
+    let can_rx_local = CanRxLocal { rx_producer };
+    let mut process_local = ProcessLocal { node, rx_consumer };
+
+    process(&mut process_local, 0);
+    assert_eq!(NmtState::PreOperational, process_local.node.nmt_state());
+
+    let other = CanMessage::new(CanId::std(0x123), &[1, 2, 3]);
+    run_can_rx(can_rx_local, vec![other, start_command()]);
+
+    process(&mut process_local, 1000);
+    assert_eq!(NmtState::Operational, process_local.node.nmt_state());
+    assert!(NODE_MBOX.take_transmit_pending());
+}
+
+#[test]
+fn test_init_local_rx_queue() {
+    let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+    let sdo_buffer = Box::leak(Box::new([0u8; SDO_BUFFER_SIZE]));
+    let mbox = NodeMbox::new(&[], &[], tx_queue, sdo_buffer);
+
+    // An RTIC init local resource, e.g. `#[init(local = [rx_queue: RxQueue<4> = RxQueue::new()])]`,
+    // is provided as a `&'static mut`
+    let rx_queue: &'static mut RxQueue<4> = Box::leak(Box::new(RxQueue::new()));
+    let (rx_producer, mut rx_consumer) = rx_queue.split();
+
+    // The queue holds N - 1 messages
+    let other = CanMessage::new(CanId::std(0x123), &[1, 2, 3]);
+    let received = vec![other, other, start_command(), other];
+    run_can_rx(CanRxLocal { rx_producer }, received);
+
+    // Only the NMT command is handled by the node, and the last frame did not fit in the queue
+    assert!(rx_consumer.forward(&mbox));
+    assert_eq!(1, mbox.diagnostics().rx_messages());
+    assert!(!rx_consumer.forward(&mbox));
+}
@@ -2,13 +2,20 @@ use std::{
     cell::RefCell,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Mutex,
+        Arc, Mutex,
     },
 };
 
+use futures::FutureExt;
 use integration_tests::{object_dict1, prelude::*};
+use zencan_client::{ErrorHistoryEntry, ObjectInfo, SdoClient};
 use zencan_common::{
+    device_config::DeviceConfig,
+    messages::CanId,
     objects::{ObjectCode, SubInfo},
+    sdo::SdoResponse,
+    strings::truncate_utf8,
+    traits::AsyncCanReceiver,
     AtomicCell,
 };
 use zencan_node::object_dict::{ObjectAccess, SubObjectAccess};
@@ -63,6 +70,88 @@ async fn test_sdo_read() {
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn test_sdo_access_stats() {
+    use object_dict1::*;
+    use zencan_node::access_stats::{AccessStats, ObjectAccessCounter};
+    const NODE_ID: u8 = 1;
+
+    static COUNTERS: [ObjectAccessCounter; OD_TABLE_LEN] =
+        [const { ObjectAccessCounter::new() }; OD_TABLE_LEN];
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.set_access_counters(Some(&COUNTERS));
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        let stats = AccessStats::new(&OD_TABLE, &COUNTERS);
+        for _ in 0..3 {
+            client.upload(0x3000, 0).await.unwrap();
+        }
+        client.download(0x3000, 0, &[1, 2, 3, 4]).await.unwrap();
+        // Aborted transfers are counted too
+        client.upload(0x1018, 9).await.unwrap_err();
+
+        assert_eq!(3, stats.get(0x3000).unwrap().reads());
+        assert_eq!(1, stats.get(0x3000).unwrap().writes());
+        assert_eq!(1, stats.get(0x1018).unwrap().reads());
+        assert_eq!(
+            vec![0x1018, 0x3000],
+            stats
+                .iter()
+                .filter(|(_, c)| c.reads() + c.writes() > 0)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_sdo_extended_ids() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    node.set_sdo_extended_ids(true);
+    let mut client = SdoClient::new_extended(NODE_ID, bus.new_sender(), bus.new_receiver());
+    let mut std_client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        client.download(0x3000, 0, &[1, 2, 3, 4]).await.unwrap();
+        assert_eq!(vec![1, 2, 3, 4], client.upload(0x3000, 0).await.unwrap());
+
+        // Requests on the standard ID are ignored
+        assert!(matches!(
+            std_client.upload(0x3000, 0).await,
+            Err(SdoClientError::NoResponse)
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn test_block_download() {
@@ -179,6 +268,64 @@ impl SubObjectAccess for MockDomainData {
     }
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn test_sdo_access_callback() {
+    use object_dict1::*;
+    use zencan_node::{SdoAccess, SdoAccessKind};
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let (access_tx, access_rx) = std::sync::mpsc::channel();
+    let mut sdo_access = |access: &SdoAccess| access_tx.send(*access).unwrap();
+    let mut callbacks = Callbacks::new();
+    callbacks.sdo_access = Some(&mut sdo_access);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let _bus_logger = BusLogger::new(bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        let data = Vec::from_iter((0..1200).map(|i| i as u8));
+        client.block_download(0x3006, 0, &data).await.unwrap();
+        assert_eq!(data, client.block_upload(0x3006, 0).await.unwrap());
+        client.download(0x3000, 0, &[1, 2, 3, 4]).await.unwrap();
+        client.upload(0x3000, 1).await.unwrap_err();
+
+        let access = |kind, index, len, result| SdoAccess {
+            kind,
+            index,
+            sub: 0,
+            len,
+            result,
+        };
+        assert_eq!(
+            vec![
+                access(SdoAccessKind::Write, 0x3006, 1200, Ok(())),
+                access(SdoAccessKind::Read, 0x3006, 1200, Ok(())),
+                access(SdoAccessKind::Write, 0x3000, 4, Ok(())),
+                SdoAccess {
+                    sub: 1,
+                    ..access(
+                        SdoAccessKind::Read,
+                        0x3000,
+                        0,
+                        Err(AbortCode::NoSuchSubIndex)
+                    )
+                },
+            ],
+            access_rx.try_iter().collect::<Vec<_>>()
+        );
+    };
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn test_domain_access() {
@@ -337,3 +484,995 @@ async fn test_application_callbacks_unregistered() {
     };
     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
 }
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_cancelled_transfer() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let mut monitor = bus.new_receiver();
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let data = Vec::from_iter((0..1200).map(|i| i as u8));
+        client.block_download(0x3006, 0, &data).await.unwrap();
+        monitor.flush();
+
+        // Drop a segmented upload once the server has sent its first segment. The next transfer
+        // should abort it and succeed.
+        let first_segment = async {
+            loop {
+                let msg = monitor.recv().await.unwrap();
+                if matches!(
+                    SdoResponse::try_from(msg),
+                    Ok(SdoResponse::UploadSegment { .. })
+                ) {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = first_segment => (),
+            _ = client.upload(0x3006, 0) => panic!("Upload completed before it was dropped"),
+        }
+        assert!(client.transfer_in_progress());
+        assert_eq!(data, client.upload(0x3006, 0).await.unwrap());
+        assert!(!client.transfer_in_progress());
+
+        // Drop a block upload after it is initiated, and explicitly abort it
+        assert!(client.block_upload(0x3006, 0).now_or_never().is_none());
+        assert!(client.transfer_in_progress());
+        // Give the server time to start the transfer
+        ctx.wait_for_process(2).await;
+        client.abort_transfer().await.unwrap();
+        assert!(!client.transfer_in_progress());
+        assert_eq!(data, client.block_upload(0x3006, 0).await.unwrap());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// A domain which can only be read by streaming
+#[derive(Debug)]
+struct MockStreamData {
+    size: usize,
+    read_pos: AtomicUsize,
+    open_count: AtomicUsize,
+}
+
+impl MockStreamData {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            read_pos: AtomicUsize::new(0),
+            open_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn expected_data(&self) -> Vec<u8> {
+        Vec::from_iter((0..self.size).map(|i| (i % 251) as u8))
+    }
+}
+
+impl SubObjectAccess for MockStreamData {
+    fn read(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Err(AbortCode::UnsupportedAccess)
+    }
+
+    fn read_size(&self) -> usize {
+        self.size
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<(), AbortCode> {
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn begin_partial_read(&self) -> Result<(), AbortCode> {
+        self.read_pos.store(0, Ordering::Relaxed);
+        self.open_count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn read_partial(&self, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        let pos = self.read_pos.load(Ordering::Relaxed);
+        let read_len = buf.len().min(self.size - pos);
+        for (i, b) in buf[..read_len].iter_mut().enumerate() {
+            *b = ((pos + i) % 251) as u8;
+        }
+        self.read_pos.store(pos + read_len, Ordering::Relaxed);
+        Ok(read_len)
+    }
+
+    fn end_partial_read(&self) -> Result<(), AbortCode> {
+        self.open_count.fetch_sub(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_domain_streaming_read() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let domain: &MockStreamData = Box::leak(Box::new(MockStreamData::new(2000)));
+    OBJECT3007.value.register_handler(domain);
+
+    let test_task = move |mut ctx: TestContext| async move {
+        let expected = domain.expected_data();
+
+        assert_eq!(expected, client.upload(0x3007, 0).await.unwrap());
+        assert_eq!(0, domain.open_count.load(Ordering::Relaxed));
+
+        assert_eq!(expected, client.block_upload(0x3007, 0).await.unwrap());
+        assert_eq!(0, domain.open_count.load(Ordering::Relaxed));
+
+        // An interrupted read must still be finished
+        assert!(client.upload(0x3007, 0).now_or_never().is_none());
+        ctx.wait_for_process(2).await;
+        assert_eq!(1, domain.open_count.load(Ordering::Relaxed));
+        client.abort_transfer().await.unwrap();
+        ctx.wait_for_process(2).await;
+        assert_eq!(0, domain.open_count.load(Ordering::Relaxed));
+        assert_eq!(expected, client.upload(0x3007, 0).await.unwrap());
+        assert_eq!(0, domain.open_count.load(Ordering::Relaxed));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// A domain whose size changes at runtime, and which reports its size before it is read
+#[derive(Debug, Default)]
+struct MockDynamicData {
+    /// The number of bytes which can be read
+    size: AtomicUsize,
+    /// The size reported by `current_size`
+    reported_size: AtomicUsize,
+    read_pos: AtomicUsize,
+}
+
+impl MockDynamicData {
+    fn set_size(&self, size: usize, reported_size: usize) {
+        self.size.store(size, Ordering::Relaxed);
+        self.reported_size.store(reported_size, Ordering::Relaxed);
+    }
+
+    fn expected_data(&self) -> Vec<u8> {
+        Vec::from_iter((0..self.size.load(Ordering::Relaxed)).map(|i| (i % 253) as u8))
+    }
+}
+
+impl SubObjectAccess for MockDynamicData {
+    fn read(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Err(AbortCode::UnsupportedAccess)
+    }
+
+    fn read_size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    fn current_size(&self) -> Option<usize> {
+        Some(self.reported_size.load(Ordering::Relaxed))
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<(), AbortCode> {
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn begin_partial_read(&self) -> Result<(), AbortCode> {
+        self.read_pos.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn read_partial(&self, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        let pos = self.read_pos.load(Ordering::Relaxed);
+        let read_len = buf.len().min(self.read_size() - pos);
+        for (i, b) in buf[..read_len].iter_mut().enumerate() {
+            *b = ((pos + i) % 253) as u8;
+        }
+        self.read_pos.store(pos + read_len, Ordering::Relaxed);
+        Ok(read_len)
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_dynamic_size_upload() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut raw_rx = bus.new_receiver();
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let domain: &MockDynamicData = Box::leak(Box::default());
+    OBJECT3007.value.register_handler(domain);
+
+    let test_task = move |_ctx| async move {
+        // Sizes which fit in the SDO buffer, fill it exactly, and span several buffers
+        for size in [10, 889, 1000, 2000] {
+            domain.set_size(size, size);
+            assert_eq!(
+                domain.expected_data(),
+                client.upload(0x3007, 0).await.unwrap()
+            );
+
+            // The size must be indicated in the initiate response
+            let ack = std::iter::from_fn(|| raw_rx.try_recv())
+                .filter(|msg| msg.id() == CanId::std(0x580 + NODE_ID as u16))
+                .filter_map(|msg| SdoResponse::try_from(msg).ok())
+                .find_map(|resp| match resp {
+                    SdoResponse::ConfirmUpload { s, data, .. } => Some((s, data)),
+                    _ => None,
+                });
+            assert_eq!(Some((true, (size as u32).to_le_bytes())), ack);
+        }
+
+        // Only the reported number of bytes are transferred
+        domain.set_size(1000, 900);
+        let data = client.upload(0x3007, 0).await.unwrap();
+        assert_eq!(domain.expected_data()[..900], data);
+
+        // The upload is aborted if the object ends before its reported size
+        domain.set_size(1000, 1200);
+        assert!(matches!(
+            client.upload(0x3007, 0).await,
+            Err(SdoClientError::ServerAbort {
+                index: 0x3007,
+                sub: 0,
+                abort_code: RawAbortCode::Valid(AbortCode::GeneralError),
+            })
+        ));
+        domain.set_size(10, 20);
+        assert!(client.upload(0x3007, 0).await.is_err());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_download_object_info() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+    client.set_object_info(Arc::new(ObjectInfo::from_device_config(&config)));
+
+    let test_task = move |_ctx| async move {
+        let original = client.read_u32(0x2000, 1).await.unwrap();
+
+        assert!(matches!(
+            client.download(0x2000, 1, &[1, 2]).await,
+            Err(SdoClientError::DownloadSizeMismatch {
+                index: 0x2000,
+                sub: 1,
+                expected: 4,
+                actual: 2
+            })
+        ));
+        assert!(matches!(
+            client.download(0x1018, 1, &[0; 4]).await,
+            Err(SdoClientError::ObjectNotWritable {
+                index: 0x1018,
+                sub: 1
+            })
+        ));
+        assert!(matches!(
+            client.download(0x2002, 0, &[b'x'; 17]).await,
+            Err(SdoClientError::DownloadSizeMismatch { .. })
+        ));
+        // Strings may be shorter than the object
+        client.download(0x2002, 0, b"short").await.unwrap();
+        // The failed downloads never reached the node
+        assert_eq!(original, client.read_u32(0x2000, 1).await.unwrap());
+
+        // Values are converted to the object type
+        client.write_value(0x2000, 1, 1234i64).await.unwrap();
+        assert_eq!(1234, client.read_u32(0x2000, 1).await.unwrap());
+        client.write_value(0x2002, 0, "value").await.unwrap();
+        assert_eq!(
+            "value",
+            client.read_visible_string(0x2002, 0).await.unwrap()
+        );
+        assert!(matches!(
+            client.write_value(0x2000, 1, -1i64).await,
+            Err(SdoClientError::IncompatibleValue { .. })
+        ));
+        assert!(matches!(
+            client.write_value(0x2000, 1, "text").await,
+            Err(SdoClientError::IncompatibleValue { .. })
+        ));
+        // Objects without metadata can only be written with raw bytes
+        assert!(matches!(
+            client.write_value(0x6FFF, 0, 1i64).await,
+            Err(SdoClientError::IncompatibleValue {
+                data_type: None,
+                ..
+            })
+        ));
+
+        client.write_u32(0x2000, 1, original).await.unwrap();
+        client.download(0x2002, 0, b"Some String").await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_string_validation() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    fn is_invalid_value(result: Result<(), SdoClientError>) -> bool {
+        matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::InvalidValue),
+                ..
+            })
+        )
+    }
+
+    let test_task = move |_ctx| async move {
+        // VisibleString objects only accept visible ASCII, whether expedited or segmented
+        client
+            .write_visible_string(0x3005, 0, "hello")
+            .await
+            .unwrap();
+        assert!(is_invalid_value(
+            client.download(0x3005, 0, "25°C".as_bytes()).await
+        ));
+        assert!(is_invalid_value(client.download(0x3005, 0, b"a\tb").await));
+        assert!(matches!(
+            client.write_visible_string(0x3005, 0, "25°C").await,
+            Err(SdoClientError::IncompatibleValue { .. })
+        ));
+        assert_eq!(
+            "hello",
+            client.read_visible_string(0x3005, 0).await.unwrap()
+        );
+
+        // UnicodeString objects accept UTF-8
+        client
+            .write_unicode_string(0x3011, 0, "25°C")
+            .await
+            .unwrap();
+        assert_eq!("25°C", client.read_utf8(0x3011, 0).await.unwrap());
+        assert!(is_invalid_value(
+            client.download(0x3011, 0, b"\xff\xfe").await
+        ));
+        // A value which ends part way through a character is rejected
+        assert!(is_invalid_value(
+            client.download(0x3011, 0, &"°°°°".as_bytes()[..7]).await
+        ));
+        let truncated = truncate_utf8("°°°°°", 8);
+        client
+            .write_unicode_string(0x3011, 0, truncated)
+            .await
+            .unwrap();
+        assert_eq!("°°°°", client.read_utf8(0x3011, 0).await.unwrap());
+        OBJECT3011.value.set_str_truncated("25°C°°").unwrap();
+        assert_eq!("25°C°", client.read_utf8(0x3011, 0).await.unwrap());
+
+        // With metadata, strings are padded to the object size
+        let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+        client.set_object_info(Arc::new(ObjectInfo::from_device_config(&config)));
+        client.write_visible_string(0x3005, 0, "").await.unwrap();
+        assert_eq!("", client.read_visible_string(0x3005, 0).await.unwrap());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_read_unit_metadata() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+
+    let test_task = move |_ctx| async move {
+        let entries = client.read_unit_metadata().await.unwrap();
+        assert_eq!(config.unit_metadata_entries(), entries);
+
+        let mut info = ObjectInfo::new();
+        for (index, sub, meta) in entries {
+            info.insert_unit_metadata(index, sub, meta);
+        }
+        assert_eq!("mV", info.unit_metadata(0x2000, 2).unwrap().unit);
+        let temperature = info.unit_metadata(0x2001, 3).unwrap();
+        assert_eq!("°C", temperature.unit);
+        let raw = client.read_i16(0x2001, 3).await.unwrap();
+        assert_eq!(-36.8, temperature.to_engineering(raw as f64));
+        assert!(info.unit_metadata(0x2001, 1).is_none());
+
+        // The metadata object is read-only
+        assert!(matches!(
+            client.download(0x5F03, 0, &[1]).await,
+            Err(SdoClientError::ServerAbort { .. })
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_object_structure_sub() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        // Generated objects
+        assert_eq!(0x0708, client.read_u32(0x2000, 0xFF).await.unwrap());
+        assert_eq!(0x0009, client.read_u32(0x2001, 0xFF).await.unwrap());
+        assert_eq!(0x0907, client.read_u32(0x2002, 0xFF).await.unwrap());
+        // Objects implemented by the node
+        assert_eq!(0x0009, client.read_u32(0x1400, 0xFF).await.unwrap());
+        assert_eq!(0x0708, client.read_u32(0x1010, 0xFF).await.unwrap());
+
+        let result = client.download(0x2000, 0xFF, &[0; 4]).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::ReadOnly),
+                ..
+            })
+        ));
+        let result = client.upload(0x2000, 3).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::NoSuchSubIndex),
+                ..
+            })
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_resizable_array() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    fn is_abort<T>(result: Result<T, SdoClientError>, code: AbortCode) -> bool {
+        matches!(result, Err(SdoClientError::ServerAbort {
+            abort_code: RawAbortCode::Valid(c),
+            ..
+        }) if c == code)
+    }
+
+    let test_task = move |_ctx| async move {
+        // The array starts out full size
+        assert_eq!(6, client.read_u8(0x3013, 0).await.unwrap());
+        assert_eq!(6, client.read_u16(0x3013, 6).await.unwrap());
+
+        // Shrinking the array hides the elements beyond the new length
+        client.write_u8(0x3013, 0, 3).await.unwrap();
+        assert_eq!(3, OBJECT3013.get_len());
+        assert_eq!(3, client.read_u16(0x3013, 3).await.unwrap());
+        assert!(is_abort(
+            client.upload(0x3013, 4).await,
+            AbortCode::NoSuchSubIndex
+        ));
+        assert!(is_abort(
+            client.write_u16(0x3013, 4, 40).await,
+            AbortCode::NoSuchSubIndex
+        ));
+
+        // The length cannot exceed the size of the array
+        assert!(is_abort(
+            client.write_u8(0x3013, 0, 7).await,
+            AbortCode::ValueTooHigh
+        ));
+        assert_eq!(3, client.read_u8(0x3013, 0).await.unwrap());
+
+        // Growing the array again restores access to the elements
+        OBJECT3013.set_len(6).unwrap();
+        assert_eq!(6, client.read_u16(0x3013, 6).await.unwrap());
+
+        // Sub 0 is writable according to the object info
+        let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+        let info = ObjectInfo::from_device_config(&config);
+        assert_eq!(
+            zencan_common::objects::AccessType::Rw,
+            info.get(0x3013, 0).unwrap().access_type
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_delta_sync() {
+    use object_dict1::*;
+    use zencan_client::DeltaSync;
+    use zencan_common::objects::PersistGroup;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        let mut sync = DeltaSync::new([(0x1007, 0), (0x2000, 1), (0x2000, 2)]);
+
+        // The first sync reads everything
+        assert_eq!(
+            vec![PersistGroup::Communication, PersistGroup::Application],
+            sync.sync(&mut client).await.unwrap()
+        );
+        let original = client.read_u32(0x2000, 1).await.unwrap();
+        assert_eq!(Some(&original.to_le_bytes()[..]), sync.get(0x2000, 1));
+        let sync_window = client.read_u32(0x1007, 0).await.unwrap();
+        assert_eq!(Some(&sync_window.to_le_bytes()[..]), sync.get(0x1007, 0));
+
+        // Nothing has changed
+        assert!(sync.sync(&mut client).await.unwrap().is_empty());
+
+        // A download only causes its own group to be read
+        client.write_u32(0x2000, 1, 1234).await.unwrap();
+        assert_eq!(
+            vec![PersistGroup::Application],
+            sync.sync(&mut client).await.unwrap()
+        );
+        assert_eq!(Some(&1234u32.to_le_bytes()[..]), sync.get(0x2000, 1));
+
+        client.write_u32(0x1007, 0, sync_window).await.unwrap();
+        assert_eq!(
+            vec![PersistGroup::Communication],
+            sync.sync(&mut client).await.unwrap()
+        );
+
+        // Invalidating forces a full read
+        sync.invalidate();
+        assert_eq!(2, sync.sync(&mut client).await.unwrap().len());
+        assert_eq!(3, sync.values().count());
+
+        // A new boot epoch means the counters may repeat old values, so everything is read again
+        let boot_epoch = NODE_STATE.change_counters().boot_epoch();
+        NODE_STATE
+            .change_counters()
+            .set_boot_epoch(boot_epoch.wrapping_add(1));
+        assert_eq!(2, sync.sync(&mut client).await.unwrap().len());
+        assert!(sync.sync(&mut client).await.unwrap().is_empty());
+        NODE_STATE.change_counters().set_boot_epoch(boot_epoch);
+
+        client.write_u32(0x2000, 1, original).await.unwrap();
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+/// Objects covering expedited, segmented and aborted uploads, including a repeat
+const READ_MANY_OBJECTS: &[(u16, u8)] = &[
+    (0x1000, 0),
+    (0x2001, 1),
+    (0x2003, 0),
+    (0x3003, 0),
+    (0x5555, 0),
+    (0x3004, 0),
+    (0x2001, 3),
+    (0x2001, 1),
+    (0x2002, 0),
+    (0x1018, 1),
+];
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_read_many() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let _logger = BusLogger::new(bus.new_receiver());
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let test_task = move |_ctx| async move {
+        let mut expected = Vec::new();
+        for &(index, sub) in READ_MANY_OBJECTS {
+            expected.push(format!("{:?}", client.upload(index, sub).await));
+        }
+        assert!(expected[4].contains("NoSuchObject"));
+
+        for depth in [4, 3, 1] {
+            client.set_pipeline_depth(depth);
+            let results: Vec<_> = client
+                .read_many(READ_MANY_OBJECTS)
+                .await
+                .iter()
+                .map(|r| format!("{r:?}"))
+                .collect();
+            assert_eq!(expected, results, "pipeline depth {depth}");
+        }
+
+        // With object metadata, the strings are read sequentially without being requested in a
+        // pipeline first
+        let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+        client.set_object_info(Arc::new(ObjectInfo::from_device_config(&config)));
+        client.set_pipeline_depth(4);
+        let results: Vec<_> = client
+            .read_many(READ_MANY_OBJECTS)
+            .await
+            .iter()
+            .map(|r| format!("{r:?}"))
+            .collect();
+        assert_eq!(expected, results);
+
+        // The client is left ready for other transfers
+        assert!(!client.transfer_in_progress());
+        assert_eq!(
+            b"Some String".to_vec(),
+            client.upload(0x2003, 0).await.unwrap()
+        );
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_read_many_nodes() {
+    use integration_tests::object_dict1_ch2;
+    use zencan_client::BusManager;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&object_dict1::NODE_MBOX);
+    bus.add_node(&object_dict1_ch2::NODE_MBOX);
+    let mut node1 = Node::new(
+        NodeId::new(1).unwrap(),
+        Callbacks::new(),
+        &object_dict1::NODE_MBOX,
+        &object_dict1::NODE_STATE,
+        &object_dict1::OD_TABLE,
+    );
+    let mut node2 = Node::new(
+        NodeId::new(2).unwrap(),
+        Callbacks::new(),
+        &object_dict1_ch2::NODE_MBOX,
+        &object_dict1_ch2::NODE_STATE,
+        &object_dict1_ch2::OD_TABLE,
+    );
+    let mut client1 = get_sdo_client(&mut bus, 1);
+    let mut client2 = get_sdo_client(&mut bus, 2);
+    let manager = BusManager::new(bus.new_sender(), bus.new_receiver());
+
+    let test_task = move |_ctx| async move {
+        client1.write_u32(0x2000, 1, 1111).await.unwrap();
+        client2.write_u32(0x2000, 1, 2222).await.unwrap();
+
+        let objects: &[(u16, u8)] = &[(0x2000, 1), (0x2002, 0)];
+        let results = manager
+            .read_many(&[(2, objects), (1, objects), (3, &objects[..1])])
+            .await;
+        assert_eq!(3, results.len());
+        assert_eq!(
+            &2222u32.to_le_bytes(),
+            results[0][0].as_ref().unwrap().as_slice()
+        );
+        assert_eq!(
+            &1111u32.to_le_bytes(),
+            results[1][0].as_ref().unwrap().as_slice()
+        );
+        assert!(results[0][1].is_ok());
+        assert!(results[1][1].is_ok());
+        // There is no node 3
+        assert!(matches!(results[2][0], Err(SdoClientError::NoResponse)));
+
+        // Invalid and repeated node IDs fail without being read
+        let results = manager
+            .read_many(&[
+                (1, objects),
+                (0, objects),
+                (1, &objects[..1]),
+                (128, objects),
+            ])
+            .await;
+        assert_eq!(4, results.len());
+        assert_eq!(
+            &1111u32.to_le_bytes(),
+            results[0][0].as_ref().unwrap().as_slice()
+        );
+        assert!(matches!(
+            results[1][..],
+            [
+                Err(SdoClientError::InvalidNodeId { node_id: 0, .. }),
+                Err(SdoClientError::InvalidNodeId { node_id: 0, .. })
+            ]
+        ));
+        assert!(matches!(
+            results[2][..],
+            [Err(SdoClientError::InvalidNodeId { node_id: 1, .. })]
+        ));
+        assert_eq!(2, results[3].len());
+        assert!(results[3]
+            .iter()
+            .all(|r| matches!(r, Err(SdoClientError::InvalidNodeId { node_id: 128, .. }))));
+    };
+
+    test_with_background_process(&mut [&mut node1, &mut node2], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_complete_access() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    fn is_abort<T>(result: Result<T, SdoClientError>, code: AbortCode) -> bool {
+        matches!(result, Err(SdoClientError::ServerAbort {
+            abort_code: RawAbortCode::Valid(c),
+            ..
+        }) if c == code)
+    }
+
+    let test_task = move |_ctx| async move {
+        // Write the whole record, starting at sub 1. Sub 2 is not defined, and sub 3 is read-only,
+        // so its data is ignored.
+        let mut data = Vec::new();
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.extend_from_slice(&[0xff, 0xff]);
+        data.extend_from_slice(b"hello\0\0\0\0\0\0\0");
+        client.download_complete(0x2001, 1, &data).await.unwrap();
+        assert_eq!(1000, client.read_u32(0x2001, 1).await.unwrap());
+        assert_eq!(0x20, client.read_i16(0x2001, 3).await.unwrap());
+        assert_eq!(
+            "hello",
+            client.read_visible_string(0x2001, 4).await.unwrap()
+        );
+
+        // Read it back, starting at sub 0, which takes two bytes
+        let read = client.upload_complete(0x2001, 0).await.unwrap();
+        let mut expected = vec![4, 0];
+        expected.extend_from_slice(&1000u32.to_le_bytes());
+        expected.extend_from_slice(&0x20i16.to_le_bytes());
+        expected.extend_from_slice(b"hello\0\0\0\0\0\0\0");
+        assert_eq!(expected, read);
+
+        // Writing sub 0 of a resizable array changes its length
+        client
+            .download_complete(0x3013, 0, &[3, 0, 1, 0, 2, 0, 3, 0])
+            .await
+            .unwrap();
+        assert_eq!(3, OBJECT3013.get_len());
+        assert_eq!(
+            vec![1, 0, 2, 0, 3, 0],
+            client.upload_complete(0x3013, 1).await.unwrap()
+        );
+        OBJECT3013.set_len(6).unwrap();
+
+        // Data which doesn't match the object is rejected without writing anything
+        assert!(is_abort(
+            client.download_complete(0x2001, 1, &data[..10]).await,
+            AbortCode::DataTypeMismatchLengthLow
+        ));
+        assert_eq!(1000, client.read_u32(0x2001, 1).await.unwrap());
+
+        // Complete access must start at sub 0 or 1, and only applies to arrays and records
+        assert!(is_abort(
+            client.upload_complete(0x2001, 2).await,
+            AbortCode::NoSuchSubIndex
+        ));
+        assert!(is_abort(
+            client.upload_complete(0x2002, 0).await,
+            AbortCode::UnsupportedAccess
+        ));
+        // PDO configuration objects are implemented by the node, and don't support it
+        assert!(is_abort(
+            client.upload_complete(0x1800, 0).await,
+            AbortCode::UnsupportedAccess
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_config_signature() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let callbacks = Callbacks::new();
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        callbacks,
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+
+    let config = DeviceConfig::load("device_configs/example1.toml").unwrap();
+    let signature = config.signature();
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(signature, client.read_config_signature().await.unwrap());
+        client.verify_config_signature(signature).await.unwrap();
+
+        let result = client.verify_config_signature(signature ^ 1).await;
+        assert!(matches!(
+            result,
+            Err(SdoClientError::ConfigSignatureMismatch { expected, actual })
+                if expected == signature ^ 1 && actual == signature
+        ));
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_error_history() {
+    use object_dict1::*;
+    const NODE_ID: u8 = 1;
+
+    let mut bus = SimBus::new();
+    bus.add_node(&NODE_MBOX);
+    let mut node = Node::new(
+        NodeId::new(NODE_ID).unwrap(),
+        Callbacks::new(),
+        &NODE_MBOX,
+        &NODE_STATE,
+        &OD_TABLE,
+    );
+    let mut client = get_sdo_client(&mut bus, NODE_ID);
+    let _logger = BusLogger::new(bus.new_receiver());
+
+    // The application records two errors, most recent first
+    OBJECT1003.set(0, 0x1234_8130).unwrap();
+    OBJECT1003.set(1, 0x0000_3210).unwrap();
+    OBJECT1003.set_len(2).unwrap();
+
+    let test_task = move |_ctx| async move {
+        assert_eq!(
+            vec![
+                ErrorHistoryEntry {
+                    error_code: 0x8130,
+                    additional_info: 0x1234
+                },
+                ErrorHistoryEntry {
+                    error_code: 0x3210,
+                    additional_info: 0
+                },
+            ],
+            client.read_error_history().await.unwrap()
+        );
+
+        client.clear_error_history().await.unwrap();
+        assert_eq!(0, client.read_u8(0x1003, 0).await.unwrap());
+        assert!(client.read_error_history().await.unwrap().is_empty());
+    };
+
+    test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+    OBJECT1003.set_len(4).unwrap();
+}
@@ -0,0 +1,118 @@
+#!/usr/bin/env bash
+# Build and lint zencan-common and zencan-node under each supported feature combination, and
+# zencan-client and zencan-cli with their optional features
+#
+# The workspace build only exercises the default features, so an API which only compiles with `std`
+# will not be caught by it. Run this before submitting changes to either crate. It is also run in
+# CI.
+#
+# Usage: scripts/check-features.sh
+#
+# The no_std target check requires the thumbv7em-none-eabihf target to be installed
+# (`rustup target add thumbv7em-none-eabihf`). It is skipped if the target is not installed.
+#
+# Every check is run, even after one fails, and the failed checks are listed at the end. The exit
+# status is non-zero if any check failed.
+set -uo pipefail
+
+cd "$(dirname "$0")/.."
+
+FAILURES=()
+
+# Run a check, and record it as failed without stopping if it does not succeed
+#
+# Usage: check <description> <command...>
+check() {
+    local description="$1"
+    shift
+    echo "==> $description"
+    if ! "$@"; then
+        echo "==> FAILED: $description"
+        FAILURES+=("$description")
+    fi
+}
+
+# Exactly one of `log` or `defmt` must be enabled
+FEATURE_SETS=(
+    "log"
+    "defmt"
+    "std,log"
+    "std,defmt"
+    "socketcan,log"
+)
+
+NO_STD_TARGET=thumbv7em-none-eabihf
+
+# Fetch the optional dependencies up front, so that a fetch problem is reported on its own rather
+# than as a failure of whichever check first needs them
+check "fetch dependencies" cargo fetch
+
+for crate in zencan-common zencan-node; do
+    for features in "${FEATURE_SETS[@]}"; do
+        check "$crate: clippy --features $features" \
+            cargo clippy -p "$crate" --no-default-features --features "$features" -- -D warnings
+    done
+
+    # Unit tests without std. defmt is excluded, because it requires a global logger to link.
+    check "$crate: test --features log" \
+        cargo test -p "$crate" --no-default-features --features log
+done
+
+for features in embassy,log embassy,defmt log,log-verbose defmt,log-verbose log,unit-metadata embedded-storage,defmt embassy,log,process-timing; do
+    check "zencan-node: clippy --features $features" \
+        cargo clippy -p zencan-node --no-default-features --features "$features" -- -D warnings
+done
+check "zencan-node: test --features embassy,log" \
+    cargo test -p zencan-node --no-default-features --features embassy,log
+
+# Lint all targets and run the tests with each optional feature
+for features in std,log,tracing log,embedded-storage log,process-timing log,access-stats log,validate-strings log,strict-abort-codes log,fuzz; do
+    check "zencan-node: clippy --all-targets --features $features" \
+        cargo clippy -p zencan-node --all-targets --no-default-features --features "$features" -- -D warnings
+    check "zencan-node: test --features $features" \
+        cargo test -p zencan-node --no-default-features --features "$features"
+done
+
+check "zencan-node: clippy --all-targets --features log,test-util" \
+    cargo clippy -p zencan-node --all-targets --no-default-features --features log,test-util -- -D warnings
+
+for features in metrics tracing; do
+    check "zencan-client: clippy --features $features" \
+        cargo clippy -p zencan-client --all-targets --features "$features" -- -D warnings
+    check "zencan-client: test --features $features" \
+        cargo test -p zencan-client --features "$features"
+done
+
+check "zencan-cli: clippy --features eds" \
+    cargo clippy -p zencan-cli --all-targets --features eds -- -D warnings
+
+check "zencan-cli: clippy --features browser" \
+    cargo clippy -p zencan-cli --all-targets --features browser -- -D warnings
+check "zencan-cli: test --features browser" \
+    cargo test -p zencan-cli --features browser
+
+# The PCAN-Basic library is required to link, so the pcan feature is only linted
+check "zencan-client: clippy --features pcan" \
+    cargo clippy -p zencan-client --all-targets --features pcan -- -D warnings
+check "zencan-cli: clippy --features browser,pcan" \
+    cargo clippy -p zencan-cli --all-targets --features browser,pcan -- -D warnings
+
+if rustup target list --installed | grep -q "^$NO_STD_TARGET\$"; then
+    for features in log defmt defmt,log-verbose embassy,defmt defmt,embedded-storage defmt,process-timing defmt,access-stats defmt,fuzz; do
+        check "zencan-node: check --target $NO_STD_TARGET --features $features" \
+            cargo check -p zencan-node --target "$NO_STD_TARGET" --no-default-features --features "$features"
+    done
+else
+    echo "==> Skipping $NO_STD_TARGET check; target is not installed"
+fi
+
+if [ ${#FAILURES[@]} -ne 0 ]; then
+    echo
+    echo "${#FAILURES[@]} check(s) failed:"
+    for failure in "${FAILURES[@]}"; do
+        echo "  - $failure"
+    done
+    exit 1
+fi
+echo
+echo "All checks passed"
@@ -6,7 +6,7 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
-use zencan_build::device_config_to_string;
+use zencan_build::{device_config_to_c_header, device_config_to_string};
 use zencan_common::device_config::DeviceConfig;
 
 #[derive(Clone, Debug, Parser)]
@@ -14,6 +14,9 @@ struct Args {
     config: PathBuf,
     #[clap(short, long)]
     format: bool,
+    /// Generate a C header instead, with this prefix on every define
+    #[clap(long, value_name = "PREFIX")]
+    c_header: Option<String>,
 }
 
 fn main() {
@@ -34,7 +37,11 @@ fn main() {
         }
     };
 
-    let compiled = device_config_to_string(&config, args.format).expect("Failed to compile");
+    let compiled = match &args.c_header {
+        Some(prefix) => device_config_to_c_header(&config, prefix),
+        None => device_config_to_string(&config, args.format),
+    }
+    .expect("Failed to compile");
 
     println!("{}", compiled);
 }
@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use zencan_common::device_config::{DeviceConfig, Object, ObjectDefinition};
+use zencan_common::objects::DataType;
+
+use crate::codegen::const_names;
+use crate::errors::CompileError;
+
+/// Get the name used for a data type in the header, e.g. `UINT32`
+fn type_name(data_type: DataType) -> String {
+    match data_type {
+        DataType::Other(value) => format!("OTHER_{value:04X}"),
+        _ => format!("{data_type:?}").to_ascii_uppercase(),
+    }
+}
+
+/// Make a name safe to include in a C comment
+fn comment_text(name: &str) -> String {
+    name.replace("*/", "* /")
+}
+
+/// Check that a prefix can be used to start a C identifier
+fn validate_prefix(prefix: &str) -> Result<(), CompileError> {
+    let valid = prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !prefix.starts_with(|c: char| c.is_ascii_digit());
+    if valid {
+        Ok(())
+    } else {
+        Err(CompileError::InvalidPrefix {
+            prefix: prefix.to_string(),
+        })
+    }
+}
+
+/// Write the type and size defines for a sub object
+fn write_sub_defines(out: &mut String, prefix: &str, name: &str, obj: &ObjectDefinition, sub: u8) {
+    // Unwrap safety: the subs passed here always exist
+    let info = obj.sub_info(sub).unwrap();
+    writeln!(
+        out,
+        "#define {prefix}{name}_TYPE {prefix}TYPE_{}",
+        type_name(info.data_type)
+    )
+    .unwrap();
+    writeln!(out, "#define {prefix}{name}_SIZE {}", info.size).unwrap();
+}
+
+/// Generate a C header defining the object indices, sub indices and data types of a node
+///
+/// This allows C code in the same project -- e.g. a coprocessor, or legacy code -- to use the same
+/// object dictionary constants as the rust code generated from the device config. The names match
+/// those of the generated `consts` module, with `prefix` prepended to every define. For example,
+/// with prefix `OD_`:
+///
+/// ```c
+/// /* 0x2001: Record Example */
+/// #define OD_RECORD_EXAMPLE 0x2001
+/// /* 0x2001sub1: Record Example - Value */
+/// #define OD_RECORD_EXAMPLE_VALUE 1
+/// #define OD_RECORD_EXAMPLE_VALUE_TYPE OD_TYPE_UINT32
+/// #define OD_RECORD_EXAMPLE_VALUE_SIZE 4
+/// ```
+///
+/// Var objects get the same `_TYPE` and `_SIZE` defines, and array objects get them for their
+/// elements, along with a `_LEN` define giving the number of elements. Each data type used has a
+/// `TYPE_` define with its CiA 301 data type index.
+pub fn device_config_to_c_header(dev: &DeviceConfig, prefix: &str) -> Result<String, CompileError> {
+    validate_prefix(prefix)?;
+
+    let mut sorted_objects: Vec<&ObjectDefinition> = dev.objects.iter().collect();
+    sorted_objects.sort_by_key(|o| o.index);
+
+    let mut data_types = BTreeSet::new();
+    let mut objects = String::new();
+    for names in const_names(&sorted_objects) {
+        let obj = names.obj;
+        let name = names.name.to_string();
+        writeln!(objects).unwrap();
+        writeln!(
+            objects,
+            "/* 0x{:04X}: {} */",
+            obj.index,
+            comment_text(&obj.parameter_name)
+        )
+        .unwrap();
+        writeln!(objects, "#define {prefix}{name} 0x{:04X}", obj.index).unwrap();
+        match &obj.object {
+            Object::Var(_) => {
+                write_sub_defines(&mut objects, prefix, &name, obj, 0);
+                data_types.insert(obj.sub_info(0).unwrap().data_type);
+            }
+            Object::Array(def) => {
+                writeln!(objects, "#define {prefix}{name}_LEN {}", def.array_size).unwrap();
+                if def.array_size > 0 {
+                    write_sub_defines(&mut objects, prefix, &name, obj, 1);
+                    data_types.insert(obj.sub_info(1).unwrap().data_type);
+                }
+            }
+            Object::Record(_) => {
+                for (sub, sub_name, _) in names.subs {
+                    writeln!(
+                        objects,
+                        "/* 0x{:04X}sub{}: {} - {} */",
+                        obj.index,
+                        sub.sub_index,
+                        comment_text(&obj.parameter_name),
+                        comment_text(&sub.parameter_name)
+                    )
+                    .unwrap();
+                    writeln!(objects, "#define {prefix}{sub_name} {}", sub.sub_index).unwrap();
+                    write_sub_defines(
+                        &mut objects,
+                        prefix,
+                        &sub_name.to_string(),
+                        obj,
+                        sub.sub_index,
+                    );
+                    data_types.insert(obj.sub_info(sub.sub_index).unwrap().data_type);
+                }
+            }
+        }
+    }
+
+    let guard = format!("{prefix}ZENCAN_OD_H");
+    let mut out = String::new();
+    writeln!(
+        out,
+        "/* Object dictionary constants for {} */",
+        comment_text(&dev.device_name)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/* Generated by zencan-build from the device config. Do not edit. */"
+    )
+    .unwrap();
+    writeln!(out, "#ifndef {guard}").unwrap();
+    writeln!(out, "#define {guard}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/* Data types */").unwrap();
+    for data_type in data_types {
+        writeln!(
+            out,
+            "#define {prefix}TYPE_{} 0x{:04X}",
+            type_name(data_type),
+            u16::from(data_type)
+        )
+        .unwrap();
+    }
+    out.push_str(&objects);
+    writeln!(out).unwrap();
+    writeln!(out, "#endif /* {guard} */").unwrap();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        device_name = "test"
+
+        [identity]
+        vendor_id = 1
+        product_code = 2
+        revision_number = 3
+
+        [[objects]]
+        index = 0x2000
+        parameter_name = "Speed"
+        data_type = "UInt16"
+        access_type = "ro"
+        object_type = "var"
+
+        [[objects]]
+        index = 0x2001
+        parameter_name = "Currents"
+        data_type = "Int16"
+        access_type = "ro"
+        object_type = "array"
+        array_size = 4
+
+        [[objects]]
+        index = 0x2002
+        parameter_name = "Motor"
+        object_type = "record"
+        [[objects.subs]]
+        sub_index = 1
+        parameter_name = "Name"
+        data_type = "VisibleString(12)"
+        access_type = "rw"
+        [[objects.subs]]
+        sub_index = 3
+        field_name = "pole_pairs"
+        data_type = "UInt8"
+        access_type = "rw"
+    "#;
+
+    #[test]
+    fn test_c_header() {
+        let config = DeviceConfig::load_from_str(CONFIG).unwrap();
+        let header = device_config_to_c_header(&config, "OD_").unwrap();
+
+        for line in [
+            "#ifndef OD_ZENCAN_OD_H",
+            "#define OD_TYPE_UINT16 0x0006",
+            "#define OD_TYPE_VISIBLESTRING 0x0009",
+            "#define OD_SPEED 0x2000",
+            "#define OD_SPEED_TYPE OD_TYPE_UINT16",
+            "#define OD_SPEED_SIZE 2",
+            "#define OD_CURRENTS 0x2001",
+            "#define OD_CURRENTS_LEN 4",
+            "#define OD_CURRENTS_TYPE OD_TYPE_INT16",
+            "#define OD_MOTOR 0x2002",
+            "#define OD_MOTOR_NAME 1",
+            "#define OD_MOTOR_NAME_TYPE OD_TYPE_VISIBLESTRING",
+            "#define OD_MOTOR_NAME_SIZE 12",
+            "#define OD_MOTOR_POLE_PAIRS 3",
+            // Standard objects are included
+            "#define OD_IDENTITY 0x1018",
+            "#define OD_IDENTITY_VENDOR_ID 1",
+        ] {
+            assert!(
+                header.lines().any(|l| l == line),
+                "Missing '{line}' in:\n{header}"
+            );
+        }
+        // Types which are not used are not defined
+        assert!(!header.contains("OD_TYPE_REAL64"));
+        assert!(header.ends_with("#endif /* OD_ZENCAN_OD_H */\n"));
+
+        assert!(device_config_to_c_header(&config, "").is_ok());
+        assert!(matches!(
+            device_config_to_c_header(&config, "1OD"),
+            Err(CompileError::InvalidPrefix { .. })
+        ));
+        assert!(device_config_to_c_header(&config, "OD-").is_err());
+    }
+}
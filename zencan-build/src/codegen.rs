@@ -1,9 +1,11 @@
+use std::collections::HashSet;
+
 use crate::errors::CompileError;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use zencan_common::device_config::{
-    DataType as DCDataType, DefaultValue, DeviceConfig, Object, ObjectDefinition, PdoDefaultConfig,
-    SubDefinition,
+    BootloaderBank, ConnectionSetConfig, DataType as DCDataType, DefaultValue, DeviceConfig,
+    Object, ObjectDefinition, PdoDefaultConfig, SubDefinition,
 };
 use zencan_common::objects::{AccessType, ObjectCode, PdoMappable};
 
@@ -15,6 +17,9 @@ fn pdo_init_tokens(cfg: Option<&PdoDefaultConfig>) -> TokenStream {
         enabled,
         rtr_disabled,
         transmission_type,
+        sync_start,
+        event_timer,
+        inhibit_time,
         mappings,
     }) = cfg
     {
@@ -29,7 +34,10 @@ fn pdo_init_tokens(cfg: Option<&PdoDefaultConfig>) -> TokenStream {
                 #rtr_disabled,
                 #transmission_type,
                 &[#(#mappings),*]
-            ))
+            )
+            .with_sync_start(#sync_start)
+            .with_event_timer(#event_timer)
+            .with_inhibit_time(#inhibit_time))
         }
     } else {
         quote! { Pdo::new_with_defaults(&OD_TABLE, &NODE_STATE, &PdoDefaults::DEFAULT) }
@@ -175,6 +183,35 @@ fn object_supports_tpdo(obj: &ObjectDefinition) -> bool {
     }
 }
 
+/// Get the number of 32-bit words needed to store the event flags for an object
+///
+/// Returns 0 when the object supports no TPDO mapping, in which case no flags are generated at all.
+/// Otherwise, at least one word is always allocated.
+fn event_flag_words(obj: &ObjectDefinition) -> usize {
+    if !object_supports_tpdo(obj) {
+        return 0;
+    }
+    let count = match &obj.object {
+        Object::Var(_) => 1,
+        Object::Array(def) => {
+            let count = def.array_size + 1;
+            def.event_flags
+                .map(|n| n.min(count))
+                .unwrap_or(count)
+                .max(1)
+        }
+        Object::Record(def) => {
+            def.subs
+                .iter()
+                .map(|s| s.sub_index as usize)
+                .max()
+                .unwrap_or(0)
+                + 1
+        }
+    };
+    count.div_ceil(32)
+}
+
 fn string_to_byte_literal_tokens(s: &str, size: usize) -> Result<TokenStream, CompileError> {
     let b = s.as_bytes();
     if b.len() > size {
@@ -196,8 +233,6 @@ fn generate_object_definition(obj: &ObjectDefinition) -> Result<TokenStream, Com
     let struct_name: syn::Ident = syn::parse_str(&format!("Object{:X}", obj.index)).unwrap();
 
     let mut field_tokens = TokenStream::new();
-    let mut tpdo_mapping = false;
-    let mut highest_sub_index = 0;
     match &obj.object {
         Object::Record(def) => {
             for sub in &def.subs {
@@ -206,8 +241,6 @@ fn generate_object_definition(obj: &ObjectDefinition) -> Result<TokenStream, Com
                 field_tokens.extend(quote! {
                     pub #field_name: #field_type,
                 });
-                tpdo_mapping |= sub.pdo_mapping.supports_tpdo();
-                highest_sub_index = highest_sub_index.max(sub.sub_index);
             }
         }
         Object::Array(def) => {
@@ -216,21 +249,22 @@ fn generate_object_definition(obj: &ObjectDefinition) -> Result<TokenStream, Com
             field_tokens.extend(quote! {
                 pub array: [#field_type; #array_size],
             });
-            tpdo_mapping |= def.pdo_mapping.supports_tpdo();
-            highest_sub_index = array_size as u8;
+            if def.resizable {
+                field_tokens.extend(quote! {
+                    pub len: ArrayLengthField,
+                });
+            }
         }
         Object::Var(def) => {
             let field_type = get_storage_type(def.data_type);
             field_tokens.extend(quote! {
                 pub value: #field_type,
             });
-            tpdo_mapping |= def.pdo_mapping.supports_tpdo();
-            highest_sub_index = 0;
         }
     }
 
-    if tpdo_mapping {
-        let n = (highest_sub_index as usize + 1).div_ceil(8);
+    let n = event_flag_words(obj);
+    if n > 0 {
         field_tokens.extend(quote! {
             flags: ObjectFlags<#n>,
         });
@@ -295,10 +329,12 @@ fn get_default_tokens(
             }
             let byte_lit = string_to_byte_literal_tokens(s, data_type.size())?;
             // OctetStrings are always the exact length
-            if matches!(data_type, DCDataType::OctetString(_)) {
-                Ok(quote!(ByteField::new(#byte_lit)))
-            } else {
-                Ok(quote!(NullTermByteField::new(#byte_lit)))
+            match data_type {
+                DCDataType::OctetString(_) => Ok(quote!(ByteField::new(#byte_lit))),
+                DCDataType::UnicodeString(_) => Ok(quote!(NullTermByteField::new(#byte_lit)
+                    .with_encoding(zencan_node::common::strings::StringEncoding::Unicode))),
+                _ => Ok(quote!(NullTermByteField::new(#byte_lit)
+                    .with_encoding(zencan_node::common::strings::StringEncoding::Visible))),
             }
         }
         DefaultValue::Float(f) => match data_type {
@@ -347,11 +383,12 @@ fn get_default_tokens(
 fn get_object_impls(
     obj: &ObjectDefinition,
     struct_name: &syn::Ident,
+    complete_access: bool,
+    compact: bool,
 ) -> Result<TokenStream, CompileError> {
     let mut accessor_methods = TokenStream::new();
     let mut default_init_tokens = TokenStream::new();
     let mut get_sub_tokens = TokenStream::new();
-    let mut flag_number = 0usize;
     let object_code;
 
     match &obj.object {
@@ -374,10 +411,6 @@ fn get_object_impls(
                 #field_name: #default_value,
             });
 
-            if def.pdo_mapping.supports_tpdo() {
-                flag_number = 1;
-            }
-
             // Accessors are generated for all data types, except Domain
             if !matches!(def.data_type, DCDataType::Domain) {
                 accessor_methods.extend(quote! {
@@ -391,6 +424,17 @@ fn get_object_impls(
                         self.#field_name.load()
                     }
                 });
+                if def.pdo_mapping.supports_tpdo() {
+                    let notify_setter_name = format_ident!("set_{}_and_notify", field_name);
+                    accessor_methods.extend(quote! {
+                        /// Set the value, and set its event flag to trigger any TPDO it is mapped to
+                        #[allow(dead_code)]
+                        pub fn #notify_setter_name(&self, value: #field_type) {
+                            self.#field_name.store(value);
+                            self.flags.set_flag(0);
+                        }
+                    });
+                }
             }
 
             get_sub_tokens.extend(quote! {
@@ -431,52 +475,132 @@ fn get_object_impls(
                 .collect::<Result<Vec<_>, CompileError>>()?;
 
             if !matches!(def.data_type, DCDataType::Domain) {
-                accessor_methods.extend(quote! {
-                    #[allow(dead_code)]
-                    pub fn set(&self, idx: usize, value: #field_type) -> Result<(), AbortCode> {
-                        if idx >= #array_size {
-                            return Err(AbortCode::NoSuchSubIndex)
+                if compact {
+                    // Checked lookups leave no panicking index in the generated code
+                    accessor_methods.extend(quote! {
+                        #[allow(dead_code)]
+                        pub fn set(&self, idx: usize, value: #field_type) -> Result<(), AbortCode> {
+                            self.array.get(idx).ok_or(AbortCode::NoSuchSubIndex)?.store(value);
+                            Ok(())
                         }
-                        self.array[idx].store(value);
-                        Ok(())
-                    }
-                    #[allow(dead_code)]
-                    pub fn get(&self, idx: usize) -> Result<#field_type, AbortCode> {
-                        if idx >= #array_size {
-                            return Err(AbortCode::NoSuchSubIndex)
+                        #[allow(dead_code)]
+                        pub fn get(&self, idx: usize) -> Result<#field_type, AbortCode> {
+                            Ok(self.array.get(idx).ok_or(AbortCode::NoSuchSubIndex)?.load())
                         }
-                        Ok(self.array[idx].load())
-                    }
-                });
+                    });
+                } else {
+                    accessor_methods.extend(quote! {
+                        #[allow(dead_code)]
+                        pub fn set(&self, idx: usize, value: #field_type) -> Result<(), AbortCode> {
+                            if idx >= #array_size {
+                                return Err(AbortCode::NoSuchSubIndex)
+                            }
+                            self.array[idx].store(value);
+                            Ok(())
+                        }
+                        #[allow(dead_code)]
+                        pub fn get(&self, idx: usize) -> Result<#field_type, AbortCode> {
+                            if idx >= #array_size {
+                                return Err(AbortCode::NoSuchSubIndex)
+                            }
+                            Ok(self.array[idx].load())
+                        }
+                    });
+                }
+                if def.pdo_mapping.supports_tpdo() {
+                    accessor_methods.extend(quote! {
+                        /// Set an element, and set its event flag to trigger any TPDO it is mapped to
+                        ///
+                        /// `idx` is the array index, i.e. the sub index minus one
+                        #[allow(dead_code)]
+                        pub fn set_and_notify(&self, idx: usize, value: #field_type) -> Result<(), AbortCode> {
+                            self.set(idx, value)?;
+                            self.flags.set_flag(idx as u8 + 1);
+                            Ok(())
+                        }
+                    });
+                }
             }
 
             default_init_tokens.extend(quote! {
                 array: [#(#default_tokens),*],
             });
 
+            // For resizable arrays, sub 0 holds the current length of the array, and elements
+            // beyond it do not exist
+            let (sub0_tokens, out_of_range_tokens) = if def.resizable {
+                accessor_methods.extend(quote! {
+                    /// Get the number of elements currently in use
+                    #[allow(dead_code)]
+                    pub fn get_len(&self) -> u8 {
+                        self.len.load()
+                    }
+                    /// Set the number of elements currently in use
+                    ///
+                    /// Returns `AbortCode::ValueTooHigh` if `len` is larger than the array size
+                    #[allow(dead_code)]
+                    pub fn set_len(&self, len: u8) -> Result<(), AbortCode> {
+                        self.len.store(len)
+                    }
+                });
+                default_init_tokens.extend(quote! {
+                    len: ArrayLengthField::new(#array_size as u8, #array_size as u8),
+                });
+                (
+                    quote! {
+                        (
+                            SubInfo {
+                                access_type: zencan_node::common::objects::AccessType::Rw,
+                                data_type: zencan_node::common::objects::DataType::UInt8,
+                                size: 1,
+                                pdo_mapping: zencan_node::common::objects::PdoMappable::None,
+                                persist: #persist,
+                            },
+                            &self.len,
+                        )
+                    },
+                    quote!(sub > self.len.load()),
+                )
+            } else {
+                (
+                    quote! {
+                        (
+                            SubInfo::MAX_SUB_NUMBER,
+                            const { &ConstField::new((#array_size as u8).to_le_bytes()) },
+                        )
+                    },
+                    quote!(sub as usize > #array_size),
+                )
+            };
+
+            let sub_info = quote! {
+                SubInfo {
+                    access_type: #access_type,
+                    data_type: #data_type,
+                    size: #storage_size,
+                    pdo_mapping: #pdo_mapping,
+                    persist: #persist,
+                }
+            };
+            let element_tokens = if compact {
+                quote! {
+                    self.array
+                        .get(sub as usize - 1)
+                        .map(|element| (#sub_info, element as &dyn SubObjectAccess))
+                }
+            } else {
+                quote!(Some((#sub_info, &self.array[sub as usize - 1])))
+            };
             get_sub_tokens.extend(quote! {
                 if sub == 0 {
-                    Some((
-                        SubInfo::MAX_SUB_NUMBER,
-                        const { &ConstField::new((#array_size as u8).to_le_bytes()) },
-                    ))
-                } else if sub as usize > #array_size {
+                    Some(#sub0_tokens)
+                } else if #out_of_range_tokens {
                     None
                 } else {
-                    Some((SubInfo {
-                        access_type: #access_type,
-                        data_type: #data_type,
-                        size: #storage_size,
-                        pdo_mapping: #pdo_mapping,
-                        persist: #persist,
-                    }, &self.array[sub as usize - 1]))
+                    #element_tokens
                 }
             });
 
-            if def.pdo_mapping.supports_tpdo() {
-                flag_number = array_size + 1;
-            }
-
             object_code = quote!(zencan_node::common::objects::ObjectCode::Array);
         }
 
@@ -486,10 +610,6 @@ fn get_object_impls(
             // For records, sub0 gives the highest sub object support by the record
             let max_sub = def.subs.iter().map(|s| s.sub_index).max().unwrap_or(0);
 
-            if object_supports_tpdo(obj) {
-                flag_number = max_sub as usize + 1;
-            }
-
             accessor_methods.extend(quote! {
                 #[allow(dead_code)]
                 pub fn get_sub0(&self) -> u8 {
@@ -537,6 +657,17 @@ fn get_object_impls(
                             self.#field_name.load()
                         }
                     });
+                    if sub.pdo_mapping.supports_tpdo() {
+                        let notify_setter_name = format_ident!("set_{}_and_notify", field_name);
+                        accessor_methods.extend(quote! {
+                            /// Set the value, and set its event flag to trigger any TPDO it is mapped to
+                            #[allow(dead_code)]
+                            pub fn #notify_setter_name(&self, value: #field_type) {
+                                self.#field_name.store(value);
+                                self.flags.set_flag(#sub_index);
+                            }
+                        });
+                    }
                 }
                 match_statements.extend(quote! {
                     #sub_index => Some(
@@ -570,8 +701,8 @@ fn get_object_impls(
 
     let mut flag_method_tokens = TokenStream::new();
     let mut flag_default_tokens = TokenStream::new();
-    if flag_number > 0 {
-        let flag_size = (flag_number).div_ceil(8);
+    let flag_size = event_flag_words(obj);
+    if flag_size > 0 {
         flag_method_tokens.extend(quote! {
             fn flags(&self) -> Option<&dyn ObjectFlagAccess> {
                 Some(&self.flags)
@@ -582,6 +713,15 @@ fn get_object_impls(
         });
     }
 
+    let mut complete_access_tokens = TokenStream::new();
+    if complete_access && !matches!(obj.object, Object::Var(_)) {
+        complete_access_tokens.extend(quote! {
+            fn supports_complete_access(&self) -> bool {
+                true
+            }
+        });
+    }
+
     Ok(quote! {
         impl #struct_name {
             #accessor_methods
@@ -604,16 +744,24 @@ fn get_object_impls(
             fn object_code(&self) -> zencan_node::common::objects::ObjectCode {
                 #object_code
             }
+
+            #complete_access_tokens
         }
     })
 }
 
+/// Generate the struct and trait impls for an object
+///
+/// When `complete_access` is set, array and record objects allow SDO complete access. When
+/// `compact` is set, accessors are generated without panicking indexing.
 pub fn generate_object_code(
     obj: &ObjectDefinition,
     struct_name: &syn::Ident,
+    complete_access: bool,
+    compact: bool,
 ) -> Result<TokenStream, CompileError> {
     let struct_def = generate_object_definition(obj)?;
-    let impls = get_object_impls(obj, struct_name)?;
+    let impls = get_object_impls(obj, struct_name, complete_access, compact)?;
 
     Ok(quote! {
         #struct_def
@@ -630,21 +778,38 @@ pub fn generate_state_inst(dev: &DeviceConfig) -> TokenStream {
     if !dev.bootloader.sections.is_empty() {
         let num_sections = dev.bootloader.sections.len() as u8;
         let application = dev.bootloader.application;
-        tokens.extend(quote! {
-            pub static BOOTLOADER_INFO:
-                zencan_node::BootloaderInfo<#application, #num_sections> =
-                zencan_node::BootloaderInfo::new();
-        });
+        if dev.bootloader.dual_bank {
+            let n = dev.bootloader.sections.len();
+            let section_idents = (0..n).map(|i| format_ident!("BOOTLOADER_SECTION{i}"));
+            tokens.extend(quote! {
+                static BOOTLOADER_BANK_SECTIONS: [&zencan_node::BootloaderSection; #n] =
+                    [#(&#section_idents),*];
+                pub static BOOTLOADER_INFO:
+                    zencan_node::BootloaderInfo<#application, #num_sections> =
+                    zencan_node::BootloaderInfo::new().dual_bank(&BOOTLOADER_BANK_SECTIONS);
+            });
+        } else {
+            tokens.extend(quote! {
+                pub static BOOTLOADER_INFO:
+                    zencan_node::BootloaderInfo<#application, #num_sections> =
+                    zencan_node::BootloaderInfo::new();
+            });
+        }
         for (i, section) in dev.bootloader.sections.iter().enumerate() {
             let var_name = format_ident!("BOOTLOADER_SECTION{i}");
             let size: u32 = section.size;
             let section_name = &section.name;
+            let bank = match section.bank {
+                None => quote!(),
+                Some(BootloaderBank::A) => quote!(.with_bank(zencan_node::BootloaderBank::A)),
+                Some(BootloaderBank::B) => quote!(.with_bank(zencan_node::BootloaderBank::B)),
+            };
             tokens.extend(quote! {
                 pub static #var_name: zencan_node::BootloaderSection =
                     zencan_node::BootloaderSection::new(
                         #section_name,
                         #size
-                    );
+                    )#bank;
             })
         }
     }
@@ -653,13 +818,13 @@ pub fn generate_state_inst(dev: &DeviceConfig) -> TokenStream {
         let tpdo_numbers = 0..n_tpdo;
         tokens.extend(quote! {
             pub static TPDO_COMM_OBJECTS: [PdoCommObject; #n_tpdo] = [
-                #(PdoCommObject::new(&NODE_STATE.tpdos()[#tpdo_numbers])),*
+                #(PdoCommObject::new_tpdo(&NODE_STATE.tpdos()[#tpdo_numbers])),*
             ];
         });
         let tpdo_numbers = 0..n_tpdo;
         tokens.extend(quote! {
             pub static TPDO_MAPPING_OBJECTS: [PdoMappingObject; #n_tpdo] = [
-                #(PdoMappingObject::new(&NODE_STATE.tpdos()[#tpdo_numbers])),*
+                #(PdoMappingObject::new_tpdo(&NODE_STATE.tpdos()[#tpdo_numbers])),*
             ];
         });
     }
@@ -675,7 +840,7 @@ pub fn generate_state_inst(dev: &DeviceConfig) -> TokenStream {
         let rpdo_numbers = 0..n_rpdo;
         tokens.extend(quote! {
             pub static RPDO_MAPPING_OBJECTS: [PdoMappingObject; #n_rpdo] = [
-                #(PdoMappingObject::new(&NODE_STATE.rpdos()[#rpdo_numbers])),*
+                #(PdoMappingObject::new_rpdo(&NODE_STATE.rpdos()[#rpdo_numbers])),*
             ];
         });
     }
@@ -687,6 +852,121 @@ pub fn generate_state_inst(dev: &DeviceConfig) -> TokenStream {
         });
     }
 
+    if dev.diagnostics {
+        tokens.extend(quote! {
+            pub static DIAGNOSTICS_OBJECT: DiagnosticsObject =
+                DiagnosticsObject::new(NODE_MBOX.diagnostics());
+        });
+    }
+
+    if dev.status_pdo.is_some() {
+        tokens.extend(quote! {
+            pub static NODE_STATUS_OBJECT: NodeStatusObject =
+                NodeStatusObject::new(NODE_MBOX.diagnostics(), &OBJECT1001);
+        });
+    }
+
+    if dev.change_counters {
+        tokens.extend(quote! {
+            pub static CHANGE_COUNTERS_OBJECT: ChangeCountersObject =
+                ChangeCountersObject::new(NODE_STATE.change_counters());
+        });
+    }
+
+    if dev.unit_metadata {
+        let table = dev.unit_metadata_table();
+        let table_len = table.len();
+        tokens.extend(quote! {
+            static UNIT_METADATA_TABLE: [u8; #table_len] = [#(#table),*];
+            pub static UNIT_METADATA_OBJECT: zencan_node::unit_metadata::UnitMetadataObject =
+                zencan_node::unit_metadata::UnitMetadataObject::new(&UNIT_METADATA_TABLE);
+        });
+    }
+
+    if dev.log_ring_size > 0 {
+        let log_ring_size = dev.log_ring_size;
+        tokens.extend(quote! {
+            pub static LOG_RING: LogRing<#log_ring_size> = LogRing::new();
+        });
+    }
+
+    if dev.rx_queue_size > 0 {
+        // The queue holds one fewer message than its size
+        let rx_queue_size = dev.rx_queue_size + 1;
+        tokens.extend(quote! {
+            pub static RX_QUEUE: zencan_node::rtic::StaticRxQueue<#rx_queue_size> =
+                zencan_node::rtic::StaticRxQueue::new();
+        });
+    }
+
+    let manager = &dev.config_manager;
+    let node_ids = &manager.node_ids;
+    let n_stores = node_ids.len();
+    if manager.has_concise_dcf() {
+        let size = manager.concise_dcf_size;
+        tokens.extend(quote! {
+            pub static CONCISE_DCF_STORES: [DcfStore<#size>; #n_stores] = [
+                #(DcfStore::new(#node_ids)),*
+            ];
+            pub static CONCISE_DCF_OBJECT: DcfStoreObject<#size> =
+                DcfStoreObject::new(&CONCISE_DCF_STORES);
+        });
+    }
+    if manager.has_store_dcf() {
+        let size = manager.store_dcf_size;
+        tokens.extend(quote! {
+            pub static STORE_DCF_STORES: [DcfStore<#size>; #n_stores] = [
+                #(DcfStore::new(#node_ids)),*
+            ];
+            pub static STORE_DCF_OBJECT: DcfStoreObject<#size> =
+                DcfStoreObject::new(&STORE_DCF_STORES);
+        });
+    }
+
+    let n_consumers = dev.heartbeat_consumers.count as usize;
+    if n_consumers > 0 {
+        let consumer_initializers = (0..n_consumers).map(|i| {
+            let (node_id, time) = dev
+                .heartbeat_consumers
+                .defaults
+                .get(i)
+                .map(|d| (d.node_id, d.time))
+                .unwrap_or((0, 0));
+            quote! { HeartbeatConsumer::new(#node_id, #time) }
+        });
+        tokens.extend(quote! {
+            pub static HEARTBEAT_CONSUMERS: [HeartbeatConsumer; #n_consumers] = [
+                #(#consumer_initializers),*
+            ];
+            pub static HEARTBEAT_CONSUMER_OBJECT: HeartbeatConsumerObject =
+                HeartbeatConsumerObject::new(&HEARTBEAT_CONSUMERS);
+        });
+    }
+    let mbox_consumers = if n_consumers > 0 {
+        quote! { .with_heartbeat_consumers(&HEARTBEAT_CONSUMERS) }
+    } else {
+        quote! {}
+    };
+    let mbox_connection_set = if dev.connection_set != ConnectionSetConfig::default() {
+        let ConnectionSetConfig {
+            sdo_request_base,
+            sdo_response_base,
+            heartbeat_base,
+            emcy_base,
+        } = dev.connection_set;
+        quote! {
+            .with_connection_set(zencan_node::common::messages::ConnectionSet {
+                sdo_request_base: #sdo_request_base,
+                sdo_response_base: #sdo_response_base,
+                heartbeat_base: #heartbeat_base,
+                emcy_base: #emcy_base,
+            })
+        }
+    } else {
+        quote! {}
+    };
+
+    let tx_queue_size = dev.tx_queue_size;
     let rpdo_initializers = (0..n_rpdo).map(|i| pdo_init_tokens(dev.pdos.rpdo_defaults.get(&i)));
     let tpdo_initializers = (0..n_tpdo).map(|i| pdo_init_tokens(dev.pdos.tpdo_defaults.get(&i)));
 
@@ -699,20 +979,274 @@ pub fn generate_state_inst(dev: &DeviceConfig) -> TokenStream {
         ];
         #[allow(static_mut_refs)]
         static mut SDO_BUFFER: [u8; SDO_BUFFER_SIZE] = [0; SDO_BUFFER_SIZE];
-        static TX_MESSAGE_QUEUE: PriorityQueue<4, CanMessage> = PriorityQueue::new();
+        static TX_MESSAGE_QUEUE: PriorityQueue<#tx_queue_size, CanMessage> = PriorityQueue::new();
         pub static NODE_STATE: NodeState = NodeState::new(&RPDOS, &TPDOS);
         #[allow(static_mut_refs)]
-        pub static NODE_MBOX: NodeMbox = NodeMbox::new(NODE_STATE.rpdos(), NODE_STATE.tpdos(), &TX_MESSAGE_QUEUE, unsafe { &mut SDO_BUFFER });
+        pub static NODE_MBOX: NodeMbox = NodeMbox::new(NODE_STATE.rpdos(), NODE_STATE.tpdos(), &TX_MESSAGE_QUEUE, unsafe { &mut SDO_BUFFER })#mbox_consumers #mbox_connection_set;
     });
 
     tokens
 }
 
+/// Split a human readable name into its alphanumeric words
+fn name_words(name: &str) -> Vec<&str> {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Convert a name to an identifier, returning None if the result is not a valid identifier
+fn name_to_ident(name: String) -> Option<syn::Ident> {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    syn::parse_str(&name).ok()
+}
+
+/// Convert a name, e.g. "Persisted String Var", to a constant name, e.g. `PERSISTED_STRING_VAR`
+fn screaming_snake_ident(name: &str) -> Option<syn::Ident> {
+    name_to_ident(name_words(name).join("_").to_ascii_uppercase())
+}
+
+/// Convert a name, e.g. "Record Example", to a type name, e.g. `RecordExample`
+fn upper_camel_ident(name: &str) -> Option<syn::Ident> {
+    let name = name_words(name)
+        .iter()
+        .map(|w| {
+            let (first, rest) = w.split_at(1);
+            first.to_ascii_uppercase() + &rest.to_ascii_lowercase()
+        })
+        .collect();
+    name_to_ident(name)
+}
+
+/// Take `ident` if it is not already used, otherwise fall back to `alternate`
+fn unique_ident(
+    used: &mut HashSet<String>,
+    ident: Option<syn::Ident>,
+    alternate: syn::Ident,
+) -> syn::Ident {
+    let ident = match ident {
+        Some(ident) if !used.contains(&ident.to_string()) => ident,
+        _ => alternate,
+    };
+    used.insert(ident.to_string());
+    ident
+}
+
+/// Get the name used for a record sub in generated constants
+fn sub_const_name(sub: &SubDefinition) -> String {
+    match &sub.field_name {
+        Some(field_name) => field_name.clone(),
+        None if !name_words(&sub.parameter_name).is_empty() => sub.parameter_name.clone(),
+        None => format!("sub{}", sub.sub_index),
+    }
+}
+
+/// The names given to an object, and to its subs, in generated constants
+pub(crate) struct ConstNames<'a> {
+    pub obj: &'a ObjectDefinition,
+    /// The name of the object's index constant
+    pub name: syn::Ident,
+    /// For records, the name of the enum of the record's subs
+    pub enum_name: Option<syn::Ident>,
+    /// For records, each sub in order of sub index, with the names of its constant and its enum
+    /// variant
+    pub subs: Vec<(&'a SubDefinition, syn::Ident, syn::Ident)>,
+}
+
+/// Assign names to the objects, for use in generated constants
+///
+/// Constant names are derived from the parameter names of objects, and record subs are named by
+/// their field name if they have one. When a name cannot be used -- because it is empty, is not a
+/// valid identifier, or collides with another -- the name falls back to one based on the index,
+/// e.g. `OBJECT2001`.
+pub(crate) fn const_names<'a>(objects: &[&'a ObjectDefinition]) -> Vec<ConstNames<'a>> {
+    let mut used_consts = HashSet::new();
+    let mut used_enums = HashSet::new();
+
+    objects
+        .iter()
+        .map(|obj| {
+            let name = unique_ident(
+                &mut used_consts,
+                screaming_snake_ident(&obj.parameter_name),
+                format_ident!("OBJECT{:X}", obj.index),
+            );
+            let Object::Record(record) = &obj.object else {
+                return ConstNames {
+                    obj,
+                    name,
+                    enum_name: None,
+                    subs: Vec::new(),
+                };
+            };
+
+            let mut subs: Vec<&SubDefinition> = record.subs.iter().collect();
+            subs.sort_by_key(|s| s.sub_index);
+            let enum_name = unique_ident(
+                &mut used_enums,
+                upper_camel_ident(&obj.parameter_name).map(|n| format_ident!("{}Sub", n)),
+                format_ident!("Object{:X}Sub", obj.index),
+            );
+            let mut used_variants = HashSet::new();
+            let subs = subs
+                .into_iter()
+                .map(|sub| {
+                    let sub_name = sub_const_name(sub);
+                    let sub_const = unique_ident(
+                        &mut used_consts,
+                        screaming_snake_ident(&sub_name).map(|n| format_ident!("{}_{}", name, n)),
+                        format_ident!("{}_SUB{}", name, sub.sub_index),
+                    );
+                    let variant = unique_ident(
+                        &mut used_variants,
+                        upper_camel_ident(&sub_name),
+                        format_ident!("Sub{}", sub.sub_index),
+                    );
+                    (sub, sub_const, variant)
+                })
+                .collect();
+            ConstNames {
+                obj,
+                name,
+                enum_name: Some(enum_name),
+                subs,
+            }
+        })
+        .collect()
+}
+
+/// Generate the `consts` module, which provides names for the object indices and sub indices
+///
+/// See [`const_names`] for how the names are chosen. In compact mode, the sub index enums do not
+/// derive `Debug`.
+fn generate_consts(objects: &[&ObjectDefinition], compact: bool) -> TokenStream {
+    let mut index_consts = TokenStream::new();
+    let mut object_consts = TokenStream::new();
+    let mut sub_enums = TokenStream::new();
+    let derives = if compact {
+        quote!(#[derive(Clone, Copy, PartialEq, Eq, Hash)])
+    } else {
+        quote!(#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)])
+    };
+
+    for names in const_names(objects) {
+        let obj = names.obj;
+        let const_name = &names.name;
+        let index: syn::Lit = syn::parse_str(&format!("0x{:04X}", obj.index)).unwrap();
+        let doc = format!("0x{:04X}: {}", obj.index, obj.parameter_name);
+        index_consts.extend(quote! {
+            #[doc = #doc]
+            pub const #const_name: u16 = #index;
+        });
+
+        match &obj.object {
+            Object::Var(_) => {
+                object_consts.extend(quote! {
+                    #[doc = #doc]
+                    pub const #const_name: (u16, u8) = (#index, 0);
+                });
+            }
+            Object::Array(_) => (),
+            Object::Record(_) => {
+                let enum_name = names.enum_name.unwrap();
+                let mut variants = TokenStream::new();
+                let mut match_arms = TokenStream::new();
+                for (sub, sub_const, variant) in names.subs {
+                    let sub_index = proc_macro2::Literal::u8_unsuffixed(sub.sub_index);
+                    let sub_doc = format!(
+                        "0x{:04X}sub{}: {} - {}",
+                        obj.index, sub.sub_index, obj.parameter_name, sub.parameter_name
+                    );
+                    object_consts.extend(quote! {
+                        #[doc = #sub_doc]
+                        pub const #sub_const: (u16, u8) = (#index, #sub_index);
+                    });
+                    variants.extend(quote! {
+                        #[doc = #sub_doc]
+                        #variant = #sub_index,
+                    });
+                    match_arms.extend(quote! {
+                        #sub_index => Ok(Self::#variant),
+                    });
+                }
+
+                let enum_doc = format!("The sub objects of {}", doc);
+                sub_enums.extend(quote! {
+                    #[doc = #enum_doc]
+                    #derives
+                    #[repr(u8)]
+                    pub enum #enum_name {
+                        #variants
+                    }
+
+                    impl #enum_name {
+                        /// The index of the record object
+                        pub const INDEX: u16 = #index;
+
+                        /// Get the sub index
+                        pub const fn sub(self) -> u8 {
+                            self as u8
+                        }
+
+                        /// Get the index and sub index
+                        pub const fn id(self) -> (u16, u8) {
+                            (Self::INDEX, self as u8)
+                        }
+                    }
+
+                    impl From<#enum_name> for u8 {
+                        fn from(value: #enum_name) -> u8 {
+                            value as u8
+                        }
+                    }
+
+                    impl TryFrom<u8> for #enum_name {
+                        type Error = u8;
+
+                        /// Get the sub object with the given sub index, or return the sub index if
+                        /// there is no such sub object
+                        fn try_from(value: u8) -> Result<Self, u8> {
+                            match value {
+                                #match_arms
+                                _ => Err(value),
+                            }
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    quote! {
+        /// Named constants for the objects in the object dictionary
+        ///
+        /// Elements of array objects are addressed by the array index and the element's sub index,
+        /// e.g. `(index::ARRAY_EXAMPLE, 1)`.
+        #[allow(dead_code)]
+        pub mod consts {
+            /// The index of every object
+            pub mod index {
+                #index_consts
+            }
+
+            /// The index and sub index of every var object, and of every sub of record objects
+            pub mod objects {
+                #object_consts
+            }
+
+            #sub_enums
+        }
+    }
+}
+
 /// Generate code for a node from a [`DeviceConfig`] as a TokenStream
 pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, CompileError> {
     let mut object_defs = TokenStream::new();
     let mut object_instantiations = TokenStream::new();
     let mut table_entries = TokenStream::new();
+    let compact = dev.codegen.compact;
 
     let mut sorted_objects: Vec<&ObjectDefinition> = dev.objects.iter().collect();
     sorted_objects.sort_by_key(|o| o.index);
@@ -728,6 +1262,62 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
                     data: &STORAGE_COMMAND_OBJECT,
                 },
             });
+        } else if obj.index == 0x1016 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &HEARTBEAT_CONSUMER_OBJECT,
+                },
+            });
+        } else if obj.index == 0x1F20 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &STORE_DCF_OBJECT,
+                },
+            });
+        } else if obj.index == 0x1F22 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &CONCISE_DCF_OBJECT,
+                },
+            });
+        } else if obj.index == 0x5F00 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &DIAGNOSTICS_OBJECT,
+                },
+            });
+        } else if obj.index == 0x5F01 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &LOG_RING,
+                },
+            });
+        } else if obj.index == 0x5F02 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &CHANGE_COUNTERS_OBJECT,
+                },
+            });
+        } else if obj.index == 0x5F03 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &UNIT_METADATA_OBJECT,
+                },
+            });
+        } else if obj.index == 0x5F05 {
+            table_entries.extend(quote! {
+                ODEntry {
+                    index: #index,
+                    data: &NODE_STATUS_OBJECT,
+                },
+            });
         } else if obj.index == 0x5500 {
             // bootloader info object as usize
             table_entries.extend(quote! {
@@ -778,14 +1368,30 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
                 },
             })
         } else if !obj.application_callback {
-            object_defs.extend(generate_object_code(obj, &struct_name)?);
+            object_defs.extend(generate_object_code(
+                obj,
+                &struct_name,
+                dev.sdo_complete_access,
+                compact,
+            )?);
             object_instantiations.extend(quote! {
                 pub static #inst_name: #struct_name = #struct_name::default();
             });
+            // In compact mode, the table refers to objects through SharedObjectAccess, so that
+            // the ObjectAccess code is shared by all objects rather than generated for each
+            let entry_data = if compact {
+                let access_name = format_ident!("{}_ACCESS", inst_name);
+                object_instantiations.extend(quote! {
+                    static #access_name: SharedObjectAccess = SharedObjectAccess::new(&#inst_name);
+                });
+                access_name
+            } else {
+                inst_name
+            };
             table_entries.extend(quote! {
                 ODEntry {
                     index: #index,
-                    data: &#inst_name,
+                    data: &#entry_data,
                 },
             });
         } else {
@@ -804,6 +1410,8 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
 
     object_instantiations.extend(generate_state_inst(dev));
 
+    let consts = generate_consts(&sorted_objects, compact);
+
     let table_len = dev.objects.len();
     Ok(quote! {
         #[allow(unused_imports)]
@@ -829,9 +1437,11 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
             SubObjectAccess,
             ObjectFlagAccess,
             ScalarField,
+            SharedObjectAccess,
             ByteField,
             ConstField,
             NullTermByteField,
+            ArrayLengthField,
         };
         #[allow(unused_imports)]
         use zencan_node::common::{i24, u24, TimeOfDay, TimeDifference};
@@ -842,6 +1452,16 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
         #[allow(unused_imports)]
         use zencan_node::storage::StorageCommandObject;
         #[allow(unused_imports)]
+        use zencan_node::diagnostics::{DiagnosticsObject, NodeStatusObject};
+        #[allow(unused_imports)]
+        use zencan_node::change_counters::ChangeCountersObject;
+        #[allow(unused_imports)]
+        use zencan_node::log_ring::LogRing;
+        #[allow(unused_imports)]
+        use zencan_node::config_manager::{DcfStore, DcfStoreObject};
+        #[allow(unused_imports)]
+        use zencan_node::heartbeat_consumer::{HeartbeatConsumer, HeartbeatConsumerObject};
+        #[allow(unused_imports)]
         use zencan_node::NodeMbox;
         #[allow(unused_imports)]
         use zencan_node::NodeState;
@@ -849,9 +1469,12 @@ pub fn device_config_to_tokens(dev: &DeviceConfig) -> Result<TokenStream, Compil
         use zencan_node::priority_queue::PriorityQueue;
         #object_defs
         #object_instantiations
-        pub static OD_TABLE: [ODEntry; #table_len] = [
+        /// The number of objects in the object dictionary
+        pub const OD_TABLE_LEN: usize = #table_len;
+        pub static OD_TABLE: [ODEntry; OD_TABLE_LEN] = [
             #table_entries
         ];
+        #consts
     })
 }
 
@@ -16,6 +16,9 @@ pub enum CompileError {
     /// Default value does not match the object type
     #[snafu(display("DefaultValueTypeMismatch: {message}"))]
     DefaultValueTypeMismatch { message: String },
+    /// The prefix given for C header defines cannot start a C identifier
+    #[snafu(display("InvalidPrefix: {prefix} is not a valid C identifier prefix"))]
+    InvalidPrefix { prefix: String },
     /// Missing cargo env vars
     #[snafu(display("NotRunViaCargo: Missing expected cargo env variables"))]
     NotRunViaCargo,
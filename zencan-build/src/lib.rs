@@ -38,6 +38,29 @@
 //! }
 //! ```
 //!
+//! ### Multiple nodes
+//!
+//! A device with more than one CAN bus can run a separate node on each. Generate the code once for
+//! each node, under different names, and include each into its own module. The same device config
+//! may be used for more than one node, as every node gets its own statics.
+//!
+//! ```ignore
+//! // build.rs
+//! zencan_build::build_node_from_device_config("CH1", "device_config.toml").unwrap();
+//! zencan_build::build_node_from_device_config("CH2", "device_config.toml").unwrap();
+//!
+//! // main.rs
+//! mod ch1 {
+//!     zencan_node::include_modules!(CH1);
+//! }
+//! mod ch2 {
+//!     zencan_node::include_modules!(CH2);
+//! }
+//! ```
+//!
+//! Each node is then created from its own `NODE_MBOX`, `NODE_STATE` and `OD_TABLE`, and messages
+//! from each bus must be delivered only to the mailbox of the node on that bus.
+//!
 //! ## The generated code
 //!
 //! The generated code looks something like this:
@@ -48,7 +71,8 @@
 //! pub static OBJECT1008: Object1008 = Object1008::default();
 //! pub static NODE_STATE: NodeState<4usize, 4usize> = NodeState::new();
 //! pub static NODE_MBOX: NodeMbox = NodeMbox::new(NODE_STATE.rpdos());
-//! pub static OD_TABLE: [ODEntry; 31usize] = [
+//! pub const OD_TABLE_LEN: usize = 31usize;
+//! pub static OD_TABLE: [ODEntry; OD_TABLE_LEN] = [
 //!     ODEntry {
 //!         index: 0x1000,
 //!         data: ObjectData::Storage(&OBJECT1000),
@@ -69,6 +93,56 @@
 //! OD_TABLE. Additionally, a NODE_STATE and a NODE_MBOX are created, and these must be provided
 //! when instantiating node.
 //!
+//! A `consts` module is also generated, so that application code can refer to objects by name
+//! instead of by index:
+//!
+//! ```ignore
+//! pub mod consts {
+//!     pub mod index {
+//!         pub const DEVICE_TYPE: u16 = 0x1000;
+//!         pub const IDENTITY: u16 = 0x1018;
+//!     }
+//!     pub mod objects {
+//!         pub const DEVICE_TYPE: (u16, u8) = (0x1000, 0);
+//!         pub const IDENTITY_VENDOR_ID: (u16, u8) = (0x1018, 1);
+//!     }
+//!     #[repr(u8)]
+//!     pub enum IdentitySub {
+//!         VendorId = 1,
+//!         ProductCode = 2,
+//!     }
+//! }
+//! ```
+//!
+//! Names are derived from the `parameter_name` of each object, and record subs are named by their
+//! `field_name` when they have one. `index` has an entry for every object, `objects` has an entry
+//! for every var object and every record sub, and each record gets an enum of its subs.
+//!
+//! ### C header
+//!
+//! Projects which also contain C code, e.g. for a coprocessor, can generate a header with the
+//! same object indices, sub indices and data types from the device config, to keep both languages
+//! in sync. In build.rs, alongside the rust code:
+//!
+//! ```ignore
+//! zencan_build::compile_c_header("device_config.toml", "c/od.h", "OD_").unwrap();
+//! ```
+//!
+//! See [`device_config_to_c_header()`] for the contents of the header.
+//!
+//! ### Compact code
+//!
+//! For parts with little flash, the device config can select a size-optimized mode:
+//!
+//! ```toml
+//! [codegen]
+//! compact = true
+//! ```
+//!
+//! The generated objects have the same API, but the table entries refer to them through a
+//! `SharedObjectAccess`, so that the object access code is compiled once rather than for every
+//! object type. See `CodegenConfig` in `zencan_common::device_config` for details.
+//!
 //!
 #![warn(
     missing_docs,
@@ -80,9 +154,11 @@ use std::path::Path;
 
 use snafu::ResultExt;
 
+mod c_header;
 mod codegen;
 pub mod errors;
 
+pub use c_header::device_config_to_c_header;
 pub use codegen::device_config_to_string;
 pub use codegen::device_config_to_tokens;
 use zencan_common::device_config::DeviceConfig;
@@ -107,6 +183,28 @@ pub fn compile_device_config(
     Ok(())
 }
 
+/// Compile a device config TOML file into a C header of its object dictionary constants
+///
+/// See [`device_config_to_c_header()`].
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the device config TOML file
+/// * `out_path` - Path to write the header to
+/// * `prefix` - A prefix prepended to every define in the header, e.g. `"OD_"`
+pub fn compile_c_header(
+    config_path: impl AsRef<Path>,
+    out_path: impl AsRef<Path>,
+    prefix: &str,
+) -> Result<(), CompileError> {
+    let config = DeviceConfig::load(config_path.as_ref()).context(DeviceConfigSnafu)?;
+
+    let header = device_config_to_c_header(&config, prefix)?;
+
+    std::fs::write(out_path.as_ref(), header.as_bytes()).context(IoSnafu)?;
+    Ok(())
+}
+
 /// Generate a node for inclusion via `include_modules!` macro
 ///
 /// This is intended to be run in build.rs.
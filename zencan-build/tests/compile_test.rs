@@ -9,3 +9,36 @@ fn compile_test() {
     let _compiled =
         zencan_build::device_config_to_string(&config, false).expect("Failed to compile");
 }
+
+/// Check the properties of compact mode code which make it smaller
+///
+/// The generated source is about the same size in both modes. The savings come from the generic
+/// `ObjectAccess` implementation, which is compiled once for each object type placed directly in
+/// the object dictionary table, and only once for `SharedObjectAccess` in compact mode.
+///
+/// Flash size can't be measured here, so this only checks the generated source. The flash saved
+/// depends on the target, the optimization settings and the number of objects in the node.
+#[test]
+fn compact_test() {
+    const CONFIG: &str = include_str!("example_device_config.toml");
+
+    let mut config = DeviceConfig::load_from_str(CONFIG).expect("Failed to parse example config");
+    let normal = zencan_build::device_config_to_string(&config, true).expect("Failed to compile");
+    config.codegen.compact = true;
+    let compact = zencan_build::device_config_to_string(&config, true).expect("Failed to compile");
+
+    // Every generated object is in the table via SharedObjectAccess
+    let generated_objects = normal.matches("#[allow(dead_code)]\npub struct").count();
+    assert_eq!(12, generated_objects);
+    assert_eq!(0, normal.matches("SharedObjectAccess::new").count());
+    assert_eq!(
+        generated_objects,
+        compact.matches("SharedObjectAccess::new").count()
+    );
+
+    assert!(normal.contains("Debug"));
+    assert!(!compact.contains("Debug"));
+    assert!(normal.contains("self.array[idx]"));
+    assert!(!compact.contains("self.array[idx]"));
+    assert!(!compact.contains("self.array[sub as usize - 1]"));
+}
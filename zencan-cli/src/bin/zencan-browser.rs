@@ -0,0 +1,410 @@
+//! An interactive object browser for a single node
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::Parser;
+use clap_num::maybe_hex;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+use zencan_cli::browser::{format_value, parse_value, ObjectList};
+use zencan_client::{
+    common::{
+        nmt::NmtState, node_configuration::PdoConfig, node_id::ConfiguredNodeId,
+        traits::AsyncCanSender,
+    },
+    transport::open_can,
+    BusManager, PdoScanResult,
+};
+
+#[derive(Parser)]
+struct Args {
+    /// The CAN interface to connect to (e.g. 'can0', 'udp:192.168.1.10:20000' or
+    /// 'slcan:/dev/ttyACM0@500k')
+    socket: String,
+    /// The ID of the node to browse
+    #[arg(value_parser = maybe_hex::<u8>)]
+    node_id: u8,
+    /// An EDS file or device config TOML file describing the node's objects
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    file: PathBuf,
+    /// How often to re-read the values of the objects on screen, in milliseconds. 0 disables
+    /// automatic refresh.
+    #[arg(long, default_value_t = 1000)]
+    interval_ms: u64,
+}
+
+const HELP: &str = "↑/↓ select  enter edit  r refresh  p PDOs  \
+                    s start  x stop  a reset app  c reset comms  q quit";
+
+struct App {
+    node_id: ConfiguredNodeId,
+    socket: String,
+    objects: ObjectList,
+    /// The last value read from each entry, or the error from reading it
+    values: Vec<Option<Result<String, String>>>,
+    table_state: TableState,
+    /// The number of table rows which fit on screen, updated on each draw
+    page_size: usize,
+    /// The text being entered when editing a value
+    edit: Option<String>,
+    nmt_state: Option<NmtState>,
+    pdos: Option<Result<PdoScanResult, String>>,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    fn new(node_id: ConfiguredNodeId, socket: String, objects: ObjectList) -> Self {
+        let values = vec![None; objects.entries.len()];
+        Self {
+            node_id,
+            socket,
+            objects,
+            values,
+            table_state: TableState::default().with_selected(Some(0)),
+            page_size: 1,
+            edit: None,
+            nmt_state: None,
+            pdos: None,
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    fn selected(&self) -> usize {
+        self.table_state.selected().unwrap_or(0)
+    }
+
+    fn select(&mut self, row: isize) {
+        let last = self.objects.entries.len().saturating_sub(1) as isize;
+        self.table_state.select(Some(row.clamp(0, last) as usize));
+    }
+
+    /// Re-read the values of the entries on screen, and the node's NMT state
+    async fn refresh<S: AsyncCanSender + Sync + Send>(&mut self, manager: &mut BusManager<S>) {
+        self.nmt_state = manager
+            .node_list()
+            .await
+            .into_iter()
+            .find(|n| n.node_id == self.node_id.raw())
+            .and_then(|n| n.nmt_state);
+
+        let start = self.table_state.offset();
+        let end = (start + self.page_size).min(self.objects.entries.len());
+        let rows: Vec<usize> = (start..end)
+            .filter(|&i| self.objects.entries[i].access_type.is_readable())
+            .collect();
+        let objects: Vec<(u16, u8)> = rows
+            .iter()
+            .map(|&i| (self.objects.entries[i].index, self.objects.entries[i].sub))
+            .collect();
+        let results = manager
+            .sdo_client(self.node_id.raw())
+            .read_many(&objects)
+            .await;
+        for (i, result) in rows.into_iter().zip(results) {
+            let data_type = self.objects.entries[i].data_type;
+            self.values[i] = Some(
+                result
+                    .map(|data| format_value(data_type, &data))
+                    .map_err(|e| e.to_string()),
+            );
+        }
+    }
+
+    async fn read_pdos<S: AsyncCanSender + Sync + Send>(&mut self, manager: &mut BusManager<S>) {
+        self.pdos = Some(
+            manager
+                .read_pdo_config(self.node_id)
+                .await
+                .map_err(|e| e.to_string()),
+        );
+    }
+
+    async fn handle_key<S: AsyncCanSender + Sync + Send>(
+        &mut self,
+        key: KeyEvent,
+        manager: &mut BusManager<S>,
+    ) {
+        if let Some(text) = self.edit.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Esc => self.edit = None,
+                KeyCode::Enter => {
+                    let text = self.edit.take().unwrap();
+                    self.write_selected(&text, manager).await;
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        let row = self.selected() as isize;
+        let page = self.page_size as isize;
+        let node = self.node_id.raw();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.select(row - 1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(row + 1),
+            KeyCode::PageUp => self.select(row - page),
+            KeyCode::PageDown => self.select(row + page),
+            KeyCode::Home => self.select(0),
+            KeyCode::End => self.select(isize::MAX),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                let entry = &self.objects.entries[self.selected()];
+                if entry.access_type.is_writable() {
+                    self.edit = Some(String::new());
+                } else {
+                    self.status = format!("{} is not writable", entry.name);
+                }
+            }
+            KeyCode::Char('r') => {
+                self.refresh(manager).await;
+                self.status = "Refreshed".into();
+            }
+            KeyCode::Char('p') => {
+                if self.pdos.take().is_none() {
+                    self.read_pdos(manager).await;
+                }
+            }
+            KeyCode::Char('s') => {
+                manager.nmt_start(node).await;
+                self.status = format!("Sent NMT start to node {node}");
+            }
+            KeyCode::Char('x') => {
+                manager.nmt_stop(node).await;
+                self.status = format!("Sent NMT stop to node {node}");
+            }
+            KeyCode::Char('a') => {
+                manager.nmt_reset_app(node).await;
+                self.status = format!("Sent NMT reset app to node {node}");
+            }
+            KeyCode::Char('c') => {
+                manager.nmt_reset_comms(node).await;
+                self.status = format!("Sent NMT reset comms to node {node}");
+            }
+            _ => (),
+        }
+    }
+
+    async fn write_selected<S: AsyncCanSender + Sync + Send>(
+        &mut self,
+        text: &str,
+        manager: &mut BusManager<S>,
+    ) {
+        let i = self.selected();
+        let entry = &self.objects.entries[i];
+        let data = match parse_value(entry.data_type, text) {
+            Ok(data) => data,
+            Err(e) => {
+                self.status = e;
+                return;
+            }
+        };
+        let result = manager
+            .sdo_client(self.node_id.raw())
+            .download(entry.index, entry.sub, &data)
+            .await;
+        self.status = match result {
+            Ok(()) => format!("Wrote 0x{:04X}sub{}", entry.index, entry.sub),
+            Err(e) => format!("Error writing 0x{:04X}sub{}: {e}", entry.index, entry.sub),
+        };
+        if self.pdos.is_some() {
+            self.read_pdos(manager).await;
+        }
+        self.refresh(manager).await;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let nmt_state = self
+            .nmt_state
+            .map(|s| s.to_string())
+            .unwrap_or("Unknown".into());
+        frame.render_widget(
+            Line::from(format!(
+                "{} | node {} on {} | NMT: {nmt_state}",
+                self.objects.device_name, self.node_id, self.socket
+            ))
+            .bold(),
+            header,
+        );
+
+        let table_area = if let Some(pdos) = &self.pdos {
+            let [table_area, pdo_area] =
+                Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                    .areas(body);
+            frame.render_widget(pdo_panel(pdos), pdo_area);
+            table_area
+        } else {
+            body
+        };
+        self.draw_table(frame, table_area);
+
+        frame.render_widget(Line::from(self.status.as_str()), status);
+        frame.render_widget(Line::from(HELP).dim(), help);
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        // Borders and the header row take three lines
+        self.page_size = (area.height as usize).saturating_sub(3).max(1);
+        let selected = self.selected();
+        let rows = self
+            .objects
+            .entries
+            .iter()
+            .zip(&self.values)
+            .enumerate()
+            .map(|(i, (entry, value))| {
+                let value = match (&self.edit, value) {
+                    (Some(text), _) if i == selected => Cell::from(format!("> {text}_")).yellow(),
+                    _ if !entry.access_type.is_readable() => Cell::from("<write only>").dim(),
+                    (_, Some(Ok(value))) => Cell::from(value.as_str()),
+                    (_, Some(Err(e))) => Cell::from(e.as_str()).red(),
+                    (_, None) => Cell::from(""),
+                };
+                Row::new([
+                    Cell::from(format!("{:04X}sub{}", entry.index, entry.sub)),
+                    Cell::from(entry.name.as_str()),
+                    Cell::from(format!("{:?}", entry.data_type)),
+                    Cell::from(format!("{:?}", entry.access_type)),
+                    value,
+                ])
+            });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Fill(2),
+                Constraint::Length(13),
+                Constraint::Length(6),
+                Constraint::Fill(2),
+            ],
+        )
+        .header(Row::new(["Object", "Name", "Type", "Access", "Value"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title("Objects"));
+        frame.render_stateful_widget(table, area, &mut self.table_state);
+    }
+}
+
+fn pdo_lines(kind: &str, pdos: &[PdoConfig], lines: &mut Vec<Line<'static>>) {
+    for (i, pdo) in pdos.iter().enumerate() {
+        let line = Line::from(format!(
+            "{kind}{i}: COB={} type={}",
+            pdo.cob_id, pdo.transmission_type
+        ));
+        lines.push(if pdo.enabled { line } else { line.dim() });
+        for m in &pdo.mappings {
+            lines.push(Line::from(format!(
+                "  0x{:04X}sub{} ({} bits)",
+                m.index, m.sub, m.size
+            )));
+        }
+    }
+}
+
+fn pdo_panel(pdos: &Result<PdoScanResult, String>) -> Paragraph<'static> {
+    let block = Block::bordered().title("PDOs");
+    match pdos {
+        Ok(pdos) => {
+            let mut lines = Vec::new();
+            pdo_lines("TPDO", &pdos.tpdos, &mut lines);
+            pdo_lines("RPDO", &pdos.rpdos, &mut lines);
+            Paragraph::new(lines).block(block)
+        }
+        Err(e) => Paragraph::new(format!("Error reading PDO config: {e}"))
+            .red()
+            .block(block),
+    }
+}
+
+async fn run<S: AsyncCanSender + Sync + Send>(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    manager: &mut BusManager<S>,
+    interval: Option<Duration>,
+) -> std::io::Result<()> {
+    // Terminal events are read on a separate thread, so that they can be awaited alongside the
+    // refresh timer
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if event_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut refresh = tokio::time::interval(interval.unwrap_or(Duration::from_secs(1)));
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        tokio::select! {
+            event = event_rx.recv() => match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    app.handle_key(key, manager).await;
+                }
+                Some(_) => (),
+                None => return Ok(()),
+            },
+            _ = refresh.tick(), if interval.is_some() => app.refresh(manager).await,
+        }
+        if app.quit {
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let Ok(node_id) = ConfiguredNodeId::new(args.node_id) else {
+        eprintln!("{} is not a valid node ID", args.node_id);
+        return ExitCode::FAILURE;
+    };
+    let objects = match ObjectList::load(&args.file) {
+        Ok(objects) => objects,
+        Err(e) => {
+            eprintln!("Error loading {}: {e}", args.file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let (tx, rx) = match open_can(&args.socket).await {
+        Ok(bus) => bus,
+        Err(e) => {
+            eprintln!("Error opening {}: {e}", args.socket);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut manager = BusManager::new(tx, rx);
+    let mut app = App::new(node_id, args.socket, objects);
+    let interval = (args.interval_ms != 0).then(|| Duration::from_millis(args.interval_ms));
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app, &mut manager, interval).await;
+    ratatui::restore();
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Terminal error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,10 +1,11 @@
-#![cfg_attr(not(target_os = "linux"), allow(unused_imports, dead_code))]
-//! A REPL-style interactive shell for talking to CAN devices via socketcan
+//! A REPL-style interactive shell for talking to CAN devices
 use std::{
     array::TryFromSliceError,
     borrow::Cow,
     ffi::OsString,
+    io::Write,
     marker::PhantomData,
+    ops::ControlFlow,
     path::PathBuf,
     str::FromStr,
     sync::{Arc, Mutex},
@@ -18,24 +19,33 @@ use reedline::{
     Span,
 };
 use shlex::Shlex;
-use zencan_cli::command::{Cli, Commands, LssCommands, NmtAction, SdoDataType};
+use zencan_cli::command::{Cli, Commands, DumpFormat, LssCommands, NmtAction, SdoDataType};
 use zencan_client::{
+    backup::{ParameterBackup, RestoreOptions},
     common::{
-        lss::LssState, node_configuration::NodeConfig, node_id::ConfiguredNodeId,
-        traits::AsyncCanSender, NodeId,
+        device_config::DeviceConfig, lss::LssState, node_configuration::NodeConfig,
+        node_id::ConfiguredNodeId, traits::AsyncCanSender, NodeId,
     },
-    BusManager,
+    dcf::{ApplyOptions, Dcf},
+    dump::EdsObjects,
+    rpc::RpcServer,
+    transport::open_can,
+    BusManager, ObjectInfo,
 };
 
-#[cfg(target_os = "linux")]
-use zencan_client::open_socketcan;
-
 #[derive(Parser)]
 struct Args {
     /// Execute a single command
     #[arg(short, long)]
     command: Option<String>,
-    /// The CAN socket to connect to (e.g. 'can0' or 'vcan0')
+    /// Serve JSON-RPC requests on stdin/stdout instead of running the shell
+    #[arg(long, conflicts_with_all = ["command", "rpc_socket"])]
+    rpc: bool,
+    /// Serve JSON-RPC requests on a Unix socket at this path instead of running the shell
+    #[arg(long, conflicts_with = "command")]
+    rpc_socket: Option<PathBuf>,
+    /// The CAN interface to connect to (e.g. 'can0', 'udp:192.168.1.10:20000' or
+    /// 'slcan:/dev/ttyACM0@500k')
     socket: String,
 }
 
@@ -254,7 +264,23 @@ async fn run_command<S: AsyncCanSender + Sync + Send>(cmd: Commands, manager: &m
                     return;
                 }
             };
+            let expected_signature = match &args.device_config {
+                Some(path) => match DeviceConfig::load(path) {
+                    Ok(device_config) => Some(device_config.signature()),
+                    Err(e) => {
+                        println!("Error reading device config: {e}");
+                        return;
+                    }
+                },
+                None => config.config_signature(),
+            };
             let mut client = manager.sdo_client(args.node_id);
+            if let Some(signature) = expected_signature {
+                if let Err(e) = client.verify_config_signature(signature).await {
+                    println!("Not loading configuration: {e}");
+                    return;
+                }
+            }
             for (pdo_num, cfg) in config.tpdos() {
                 if let Err(e) = client.configure_tpdo(*pdo_num, cfg).await {
                     println!("Error configuring TPDO {pdo_num}:");
@@ -291,7 +317,17 @@ async fn run_command<S: AsyncCanSender + Sync + Send>(cmd: Commands, manager: &m
             }
             LssCommands::Fastscan { timeout } => {
                 let timeout = Duration::from_millis(timeout);
-                let ids = manager.lss_fastscan(timeout).await;
+                let ids = manager
+                    .lss_fastscan_with_progress(timeout, |found, progress| {
+                        print!(
+                            "\rScanning: {found} found, {:3.0}% of next identity",
+                            progress.fraction_complete() * 100.0
+                        );
+                        std::io::stdout().flush().ok();
+                        ControlFlow::Continue(())
+                    })
+                    .await;
+                println!();
                 println!("Found {} unconfigured nodes", ids.len());
                 for id in ids {
                     println!(
@@ -300,6 +336,33 @@ async fn run_command<S: AsyncCanSender + Sync + Send>(cmd: Commands, manager: &m
                     );
                 }
             }
+            LssCommands::Assign {
+                first,
+                last,
+                store,
+                timeout,
+            } => {
+                let Ok(pool) = (first..=last)
+                    .map(ConfiguredNodeId::new)
+                    .collect::<Result<Vec<_>, _>>()
+                else {
+                    println!("Invalid node ID range {first}..={last}");
+                    return;
+                };
+                let timeout = Duration::from_millis(timeout);
+                match manager.lss_fastscan_assign(timeout, pool, store).await {
+                    Ok(assigned) => {
+                        println!("Assigned {} nodes", assigned.len());
+                        for (id, node_id) in assigned {
+                            println!(
+                                "{node_id}: 0x{:x} 0x{:x} 0x{:x} 0x{:x}",
+                                id.vendor_id, id.product_code, id.revision, id.serial
+                            );
+                        }
+                    }
+                    Err(e) => println!("Error: {e}"),
+                }
+            }
             LssCommands::SetNodeId { node_id, identity } => {
                 let node_id = match NodeId::try_from(node_id) {
                     Ok(id) => id,
@@ -428,6 +491,139 @@ async fn run_command<S: AsyncCanSender + Sync + Send>(cmd: Commands, manager: &m
                 Err(e) => println!("Error: {e}"),
             }
         }
+        Commands::Backup(args) => {
+            let config = match DeviceConfig::load(&args.device_config) {
+                Ok(c) => c,
+                Err(e) => {
+                    println!("Error reading device config: {e}");
+                    return;
+                }
+            };
+            let info = ObjectInfo::from_device_config(&config);
+            let mut client = manager.sdo_client(args.node_id);
+            let backup = match ParameterBackup::read(&mut client, &info).await {
+                Ok(backup) => backup,
+                Err(e) => {
+                    println!("Error: {e}");
+                    return;
+                }
+            };
+            match backup.save(&args.path) {
+                Ok(()) => println!(
+                    "Saved {} parameters to {}",
+                    backup.parameters.len(),
+                    args.path.display()
+                ),
+                Err(e) => println!("Error: {e}"),
+            }
+        }
+        Commands::Restore(args) => {
+            let backup = match ParameterBackup::load(&args.path) {
+                Ok(backup) => backup,
+                Err(e) => {
+                    println!("Error reading backup: {e}");
+                    return;
+                }
+            };
+            let options = RestoreOptions {
+                check_identity: !args.force,
+                save: !args.no_save,
+            };
+            let mut client = manager.sdo_client(args.node_id);
+            match backup.restore(&mut client, &options).await {
+                Ok(()) => println!(
+                    "Restored and verified {} parameters",
+                    backup.parameters.len()
+                ),
+                Err(e) => println!("Error: {e}"),
+            }
+        }
+        Commands::ApplyDcf(args) => {
+            let dcf = match Dcf::load(&args.path) {
+                Ok(dcf) => dcf,
+                Err(e) => {
+                    println!("Error reading DCF: {e}");
+                    return;
+                }
+            };
+            // Values relative to $NODEID refer to the node being configured
+            let options = ApplyOptions {
+                node_id: Some(args.node_id),
+                stop_on_error: args.stop_on_error,
+                save: !args.no_save,
+            };
+            let mut client = manager.sdo_client(args.node_id);
+            let report = dcf
+                .apply(&mut client, &options, |p| {
+                    print!("\rWriting parameter {}/{}", p.completed, p.total);
+                    std::io::stdout().flush().ok();
+                })
+                .await;
+            println!();
+            for failure in &report.failures {
+                println!("Error: {failure}");
+            }
+            println!("{report}");
+        }
+        Commands::Dump(args) => {
+            let eds = match args.eds.as_ref().map(EdsObjects::load).transpose() {
+                Ok(eds) => eds,
+                Err(e) => {
+                    println!("Error reading EDS: {e}");
+                    return;
+                }
+            };
+            let ranges: Vec<_> = if args.range.is_empty() {
+                vec![0x1000..=0x1FFF, 0x2000..=0x5FFF]
+            } else {
+                args.range.iter().map(|r| r.0.clone()).collect()
+            };
+            let mut client = manager.sdo_client(args.node_id);
+            let mut dump = match client.dump_dictionary(&ranges).await {
+                Ok(dump) => dump,
+                Err(e) => {
+                    println!("Error: {e}");
+                    return;
+                }
+            };
+            if let Some(eds) = &eds {
+                dump.apply_eds(eds);
+            }
+            let output = match args.format {
+                DumpFormat::Csv => dump.to_csv(),
+                DumpFormat::Json => dump.to_json(),
+            };
+            match &args.path {
+                Some(path) => match std::fs::write(path, output) {
+                    Ok(()) => println!(
+                        "Saved {} sub objects to {}",
+                        dump.entries.len(),
+                        path.display()
+                    ),
+                    Err(e) => println!("Error: {e}"),
+                },
+                None => println!("{output}"),
+            }
+        }
+        Commands::StoreDcf(args) => {
+            let concise =
+                match Dcf::load(&args.path).and_then(|dcf| dcf.to_concise(Some(args.node_id))) {
+                    Ok(concise) => concise,
+                    Err(e) => {
+                        println!("Error reading DCF: {e}");
+                        return;
+                    }
+                };
+            let mut client = manager.sdo_client(args.manager_id);
+            match concise.store(&mut client, args.node_id).await {
+                Ok(()) => println!(
+                    "Stored {} values for node {}",
+                    concise.values.len(),
+                    args.node_id
+                ),
+                Err(e) => println!("Error: {e}"),
+            }
+        }
         Commands::ScanPdoConfig(args) => {
             let node_id = match ConfiguredNodeId::new(args.node_id) {
                 Ok(id) => id,
@@ -494,12 +690,6 @@ fn parse_command(line: &str) -> Result<Cli, clap::Error> {
     }
 }
 
-#[cfg(not(target_os = "linux"))]
-fn main() {
-    println!("zencan-cli uses socketcan, so currently only works on linux.");
-}
-
-#[cfg(target_os = "linux")]
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -508,10 +698,40 @@ async fn main() {
     let node_state = Arc::new(Mutex::new(0));
     let prompt = ZencanPrompt::new(&args.socket, node_state.clone());
 
-    #[cfg(target_os = "linux")]
-    let (tx, rx) = open_socketcan(&args.socket).expect("Failed to open bus socket");
+    let (tx, rx) = match open_can(&args.socket).await {
+        Ok(bus) => bus,
+        Err(e) => {
+            eprintln!("Error opening {}: {e}", args.socket);
+            return;
+        }
+    };
     let mut manager = BusManager::new(tx, rx);
 
+    if args.rpc {
+        let mut server = RpcServer::new(manager);
+        server
+            .serve_stdio()
+            .await
+            .expect("Error reading from stdin");
+        return;
+    }
+    if let Some(path) = args.rpc_socket {
+        #[cfg(unix)]
+        {
+            let mut server = RpcServer::new(manager);
+            server
+                .serve_unix(path)
+                .await
+                .expect("Error serving RPC socket");
+        }
+        #[cfg(not(unix))]
+        eprintln!(
+            "Cannot serve {}: Unix sockets are not supported",
+            path.display()
+        );
+        return;
+    }
+
     if let Some(cmd_string) = args.command {
         match parse_command(&cmd_string) {
             Ok(cmd) => run_command(cmd.command, &mut manager).await,
@@ -0,0 +1,61 @@
+//! Create an EDS file describing a node from its device config
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use zencan_client::common::device_config::DeviceConfig;
+use zencan_eds::ElectronicDataSheet;
+
+#[derive(Parser)]
+struct Args {
+    /// The device config TOML file of the node
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    config: PathBuf,
+    /// The EDS file to write. By default, the EDS is printed on stdout.
+    #[arg(short, long, value_hint = clap::ValueHint::FilePath)]
+    output: Option<PathBuf>,
+    /// A baud rate supported by the node, in kbit/s. May be given more than once.
+    #[arg(short, long = "baud-rate")]
+    baud_rates: Vec<u32>,
+    /// The vendor name to write in the EDS
+    #[arg(long)]
+    vendor_name: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let config = match DeviceConfig::load(&args.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading {}: {e}", args.config.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut eds = ElectronicDataSheet::from_device_config(&config);
+    for kbps in args.baud_rates {
+        if !eds.device_info.set_baud_rate_supported(kbps) {
+            eprintln!("Unsupported baud rate {kbps} kbit/s");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(vendor_name) = args.vendor_name {
+        eds.device_info.vendor_name = vendor_name;
+    }
+    let now = chrono::Local::now();
+    eds.file_info.creation_date = now.format("%m-%d-%Y").to_string();
+    eds.file_info.creation_time = now.format("%I:%M%p").to_string();
+    eds.file_info.modification_date = eds.file_info.creation_date.clone();
+    eds.file_info.modification_time = eds.file_info.creation_time.clone();
+
+    match args.output {
+        Some(path) => {
+            if let Err(e) = eds.write_to_file(&path) {
+                eprintln!("Error writing {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{eds}"),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,113 @@
+#![cfg_attr(not(target_os = "linux"), allow(unused_imports, dead_code))]
+//! Program a firmware image into a node over the bus
+use std::{io::Write, path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::Parser;
+use clap_num::maybe_hex;
+use zencan_client::{
+    flash::{flash, FirmwareImage, FlashOptions, FlashStep},
+    Device,
+};
+
+#[derive(Parser)]
+struct Args {
+    /// The CAN socket to connect to (e.g. 'can0' or 'vcan0')
+    socket: String,
+    /// The ID of the node to program
+    #[arg(value_parser = maybe_hex::<u8>)]
+    node_id: u8,
+    /// The image to program, as an ELF file or raw binary
+    file: PathBuf,
+    /// The bootloader section to program. By default, the first section in the inactive bank of a
+    /// dual-bank device, or section 0.
+    #[arg(short, long)]
+    section: Option<u8>,
+    /// Do not swap banks after programming a dual-bank device
+    #[arg(long)]
+    no_swap: bool,
+    /// Do not reset the node after programming
+    #[arg(long)]
+    no_reset: bool,
+    /// Time to wait for the node to boot after a reset, in milliseconds
+    #[arg(long, default_value_t = 5000)]
+    boot_timeout_ms: u64,
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("zencan-flash uses socketcan, so currently only works on linux.");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let image = match FirmwareImage::load(&args.file) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("Error loading {}: {e}", args.file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(address) = image.address {
+        println!(
+            "Loaded {} bytes at 0x{address:08X} from {}",
+            image.data.len(),
+            args.file.display()
+        );
+    } else {
+        println!(
+            "Loaded {} bytes from {}",
+            image.data.len(),
+            args.file.display()
+        );
+    }
+
+    // The heartbeat receiver must be separate from the SDO receiver, so open the socket twice
+    let sockets = zencan_client::open_socketcan(&args.socket)
+        .and_then(|(tx, rx)| Ok((tx, rx, zencan_client::open_socketcan(&args.socket)?.1)));
+    let (tx, rx, heartbeat_rx) = match sockets {
+        Ok(sockets) => sockets,
+        Err(e) => {
+            eprintln!("Error opening {}: {e}", args.socket);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut device = Device::new(args.node_id, tx, rx, heartbeat_rx);
+
+    let options = FlashOptions {
+        section: args.section,
+        swap: !args.no_swap,
+        reset: !args.no_reset,
+        boot_timeout: Duration::from_millis(args.boot_timeout_ms),
+        ..Default::default()
+    };
+    let result = flash(&mut device, &image.data, &options, |step| match step {
+        FlashStep::EnteringBootloader => println!("Resetting to bootloader"),
+        FlashStep::Erasing { section } => println!("Erasing section {section}"),
+        FlashStep::Programming { written, total } => {
+            print!("\rProgramming: {written}/{total} bytes");
+            if written == total {
+                println!();
+            }
+            std::io::stdout().flush().ok();
+        }
+        FlashStep::Verifying { crc } => println!("Verifying CRC 0x{crc:08X}"),
+        FlashStep::Swapping => println!("Swapping banks"),
+        FlashStep::Resetting => println!("Resetting node"),
+    })
+    .await;
+
+    match result {
+        Ok(section) => {
+            println!("Programmed section {section} of node {}", args.node_id);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,13 +1,17 @@
-#![cfg_attr(not(target_os = "linux"), allow(unused_imports))]
 use clap::Parser;
-use zencan_client::common::{
-    messages::{MessageError, ZencanMessage},
-    traits::AsyncCanReceiver,
-    CanMessage,
+use zencan_client::{
+    common::{
+        messages::{MessageError, ZencanMessage},
+        traits::AsyncCanReceiver,
+        CanMessage,
+    },
+    transport::open_can,
 };
 
 #[derive(Parser)]
 struct Args {
+    /// The CAN interface to connect to (e.g. 'can0', 'udp:192.168.1.10:20000' or
+    /// 'slcan:/dev/ttyACM0@500k')
     socket: String,
     #[clap(short, long)]
     verbose: bool,
@@ -32,16 +36,16 @@ impl From<CanMessage> for Message {
     }
 }
 
-#[cfg(not(target_os = "linux"))]
-fn main() {
-    println!("zencandump uses socketcan, so currently only works on linux.");
-}
-
-#[cfg(target_os = "linux")]
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let (_tx, mut rx) = zencan_client::open_socketcan(&args.socket).unwrap();
+    let (_tx, mut rx) = match open_can(&args.socket).await {
+        Ok(bus) => bus,
+        Err(e) => {
+            eprintln!("Error opening {}: {e}", args.socket);
+            return;
+        }
+    };
 
     loop {
         if let Ok(msg) = rx.recv().await {
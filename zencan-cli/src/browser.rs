@@ -0,0 +1,274 @@
+//! Object list and value conversions for the zencan-browser TUI
+//!
+//! The browser shows every sub object described by an EDS file or a device config. The
+//! [`ObjectList`] flattens either of them into a sorted list of entries, and [`format_value`] and
+//! [`parse_value`] convert between raw SDO data and the text shown and edited in the browser.
+use std::path::Path;
+
+use zencan_client::{
+    common::{
+        device_config::{DeviceConfig, Object},
+        objects::{AccessType, DataType, OBJECT_STRUCTURE_SUB},
+    },
+    SdoValue,
+};
+use zencan_eds::ElectronicDataSheet;
+
+pub use zencan_client::dump::format_value;
+
+/// A sub object shown in the browser
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrowserEntry {
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// A human readable name, including the object name for subs of arrays and records
+    pub name: String,
+    /// The data type of the sub object
+    pub data_type: DataType,
+    /// The access type of the sub object
+    pub access_type: AccessType,
+}
+
+/// The sub objects of a node, sorted by index and sub index
+#[derive(Clone, Debug, Default)]
+pub struct ObjectList {
+    /// The name of the device, as given in the EDS or device config
+    pub device_name: String,
+    /// The sub objects
+    pub entries: Vec<BrowserEntry>,
+}
+
+impl ObjectList {
+    /// Load an object list from a file
+    ///
+    /// Files with an `.eds` extension are read as an EDS, and all others as a device config.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let is_eds = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("eds"));
+        if is_eds {
+            let eds = ElectronicDataSheet::from_file(path).map_err(|e| format!("{e:?}"))?;
+            Ok(Self::from_eds(&eds))
+        } else {
+            let config = DeviceConfig::load(path).map_err(|e| e.to_string())?;
+            Ok(Self::from_device_config(&config))
+        }
+    }
+
+    /// Create an object list from a device config
+    pub fn from_device_config(config: &DeviceConfig) -> Self {
+        let mut entries = Vec::new();
+        for object in &config.objects {
+            for sub in 0..OBJECT_STRUCTURE_SUB {
+                let Some(info) = object.sub_info(sub) else {
+                    continue;
+                };
+                let name = match &object.object {
+                    Object::Var(_) => object.parameter_name.clone(),
+                    Object::Array(_) if sub == 0 => format!("{}: Length", object.parameter_name),
+                    Object::Array(_) => format!("{}[{sub}]", object.parameter_name),
+                    Object::Record(record) => {
+                        let sub_name = record
+                            .subs
+                            .iter()
+                            .find(|s| s.sub_index == sub)
+                            .map(|s| s.parameter_name.clone())
+                            .unwrap_or_else(|| "Highest sub index".into());
+                        format!("{}: {sub_name}", object.parameter_name)
+                    }
+                };
+                entries.push(BrowserEntry {
+                    index: object.index,
+                    sub,
+                    name,
+                    data_type: info.data_type,
+                    access_type: info.access_type,
+                });
+            }
+        }
+        entries.sort_by_key(|e| (e.index, e.sub));
+        Self {
+            device_name: config.device_name.clone(),
+            entries,
+        }
+    }
+
+    /// Create an object list from an EDS
+    pub fn from_eds(eds: &ElectronicDataSheet) -> Self {
+        let mut entries = Vec::new();
+        let objects = eds
+            .mandatory_objects
+            .iter()
+            .chain(&eds.optional_objects)
+            .chain(&eds.manufacturer_objects);
+        for object in objects {
+            for (sub, sub_object) in &object.subs {
+                let name = if object.subs.len() == 1 && *sub == 0 {
+                    object.parameter_name.clone()
+                } else {
+                    format!("{}: {}", object.parameter_name, sub_object.parameter_name)
+                };
+                entries.push(BrowserEntry {
+                    index: object.object_number,
+                    sub: *sub,
+                    name,
+                    data_type: sub_object.data_type,
+                    access_type: sub_object.access_type,
+                });
+            }
+        }
+        entries.sort_by_key(|e| (e.index, e.sub));
+        Self {
+            device_name: eds.device_info.product_name.clone(),
+            entries,
+        }
+    }
+}
+
+/// Parse text entered for a sub object into the raw data to write to it
+///
+/// Integers may be given in decimal, or in hex with a `0x` prefix. Strings are written as entered,
+/// and octet strings and other types are entered as hex bytes, optionally separated by spaces.
+pub fn parse_value(data_type: DataType, text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let invalid = || format!("'{text}' is not a valid {data_type:?} value");
+
+    let value = match data_type {
+        DataType::Boolean => match text {
+            "true" | "1" => SdoValue::Bool(true),
+            "false" | "0" => SdoValue::Bool(false),
+            _ => return Err(invalid()),
+        },
+        _ if data_type.int_format().is_some() => {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text),
+            };
+            let magnitude = match digits
+                .strip_prefix("0x")
+                .or_else(|| digits.strip_prefix("0X"))
+            {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => digits.parse::<i64>(),
+            }
+            .map_err(|_| invalid())?;
+            SdoValue::Integer(if negative { -magnitude } else { magnitude })
+        }
+        DataType::Real32 | DataType::Real64 => {
+            SdoValue::Float(text.parse().map_err(|_| invalid())?)
+        }
+        DataType::VisibleString | DataType::UnicodeString => SdoValue::String(text.into()),
+        _ => {
+            let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+            if !digits.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid())?;
+            SdoValue::Bytes(bytes)
+        }
+    };
+    value.encode(data_type).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        device_name = "browser-test"
+
+        [identity]
+        vendor_id = 1
+        product_code = 2
+        revision_number = 3
+
+        [[objects]]
+        index = 0x2000
+        parameter_name = "Speed"
+        object_type = "var"
+        data_type = "int16"
+        access_type = "rw"
+
+        [[objects]]
+        index = 0x2001
+        parameter_name = "Gains"
+        object_type = "array"
+        data_type = "uint32"
+        access_type = "rw"
+        array_size = 2
+
+        [[objects]]
+        index = 0x2002
+        parameter_name = "Limits"
+        object_type = "record"
+        [[objects.subs]]
+        sub_index = 1
+        parameter_name = "Min"
+        data_type = "real32"
+        access_type = "rw"
+    "#;
+
+    #[test]
+    fn test_object_list() {
+        let config = DeviceConfig::load_from_str(CONFIG).unwrap();
+        let list = ObjectList::from_device_config(&config);
+        assert_eq!("browser-test", list.device_name);
+        let find = |index, sub| {
+            list.entries
+                .iter()
+                .find(|e| (e.index, e.sub) == (index, sub))
+                .unwrap()
+        };
+        assert_eq!("Speed", find(0x2000, 0).name);
+        assert_eq!(DataType::Int16, find(0x2000, 0).data_type);
+        assert_eq!("Gains: Length", find(0x2001, 0).name);
+        assert_eq!("Gains[2]", find(0x2001, 2).name);
+        assert_eq!("Limits: Min", find(0x2002, 1).name);
+        assert_eq!(AccessType::Rw, find(0x2002, 1).access_type);
+        assert!(list
+            .entries
+            .windows(2)
+            .all(|w| (w[0].index, w[0].sub) < (w[1].index, w[1].sub)));
+
+        // An EDS of the same device describes the same sub objects
+        let eds = ElectronicDataSheet::from_device_config(&config);
+        let eds_list = ObjectList::from_eds(&eds);
+        let key = |e: &BrowserEntry| (e.index, e.sub, e.data_type);
+        assert_eq!(
+            list.entries.iter().map(key).collect::<Vec<_>>(),
+            eds_list.entries.iter().map(key).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(Ok(vec![0xfe, 0xff]), parse_value(DataType::Int16, "-2"));
+        assert_eq!(
+            Ok(vec![0x2c, 1, 0, 0]),
+            parse_value(DataType::UInt32, "0x12C")
+        );
+        assert!(parse_value(DataType::UInt8, "256").is_err());
+        assert!(parse_value(DataType::UInt8, "-1").is_err());
+        assert_eq!(Ok(vec![1]), parse_value(DataType::Boolean, "true"));
+        assert_eq!(
+            Ok(2.5f32.to_le_bytes().to_vec()),
+            parse_value(DataType::Real32, "2.5")
+        );
+        assert_eq!(
+            Ok(b"abc".to_vec()),
+            parse_value(DataType::VisibleString, "abc")
+        );
+        assert_eq!(
+            Ok(vec![1, 0xab]),
+            parse_value(DataType::OctetString, "01 AB")
+        );
+        assert!(parse_value(DataType::OctetString, "1").is_err());
+    }
+}
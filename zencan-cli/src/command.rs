@@ -1,6 +1,6 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_num::maybe_hex;
-use std::{path::PathBuf, str::FromStr};
+use std::{ops::RangeInclusive, path::PathBuf, str::FromStr};
 use zencan_client::common::lss::LssIdentity;
 
 #[derive(Debug, Parser)]
@@ -25,6 +25,16 @@ pub enum Commands {
     LoadConfig(LoadConfigArgs),
     /// Send command to save persistable objects
     SaveObjects(SaveObjectsArgs),
+    /// Save the configuration parameters of a node to a file
+    Backup(BackupArgs),
+    /// Restore configuration parameters from a backup file to a node
+    Restore(RestoreArgs),
+    /// Apply the parameter values in a DCF file to a node
+    ApplyDcf(ApplyDcfArgs),
+    /// Read every object in a range of indices from a node, and save it as CSV or JSON
+    Dump(DumpArgs),
+    /// Store a DCF file in a configuration manager as a concise DCF, to be applied to a node on boot
+    StoreDcf(StoreDcfArgs),
     /// NMT commands
     Nmt(NmtArgs),
     /// LSS commands
@@ -89,6 +99,113 @@ pub struct LoadConfigArgs {
     /// Path to a node config TOML file
     #[arg(value_hint=clap::ValueHint::FilePath)]
     pub path: PathBuf,
+    /// Path to the device config TOML file the node is expected to be built from
+    ///
+    /// The node's configuration signature is checked against the device config before loading.
+    /// Otherwise, it is checked against the `config_signature` in the node config file, if any.
+    #[arg(long, value_hint=clap::ValueHint::FilePath)]
+    pub device_config: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct BackupArgs {
+    /// The ID of the node to back up
+    pub node_id: u8,
+    /// Path to the node's device config TOML file, which describes its parameters
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub device_config: PathBuf,
+    /// Path of the backup file to write
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// The ID of the node to restore to
+    pub node_id: u8,
+    /// Path to a backup file
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub path: PathBuf,
+    /// Restore even if the node is a different product than the one backed up
+    #[arg(long)]
+    pub force: bool,
+    /// Do not command the node to save its objects after restoring
+    #[arg(long)]
+    pub no_save: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyDcfArgs {
+    /// The ID of the node to configure
+    pub node_id: u8,
+    /// Path to a DCF file
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub path: PathBuf,
+    /// Stop at the first parameter which fails to be written
+    #[arg(long)]
+    pub stop_on_error: bool,
+    /// Do not command the node to save its objects after applying
+    #[arg(long)]
+    pub no_save: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DumpArgs {
+    /// The ID of the node to read
+    pub node_id: u8,
+    /// Path of the file to write. The dump is printed if omitted.
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub path: Option<PathBuf>,
+    /// The output format
+    #[arg(long, value_enum, default_value_t = DumpFormat::Csv)]
+    pub format: DumpFormat,
+    /// Path to an EDS file for the node, which provides object names and data types
+    #[arg(long, value_hint=clap::ValueHint::FilePath)]
+    pub eds: Option<PathBuf>,
+    /// A range of indices to read, e.g. 0x1000-0x1FFF. May be given more than once. The
+    /// communication (0x1000-0x1FFF) and manufacturer (0x2000-0x5FFF) areas are read if omitted.
+    #[arg(long)]
+    pub range: Vec<IndexRange>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum DumpFormat {
+    Csv,
+    Json,
+}
+
+/// A range of object indices, given as `start-end`, or as a single index
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexRange(pub RangeInclusive<u16>);
+
+impl FromStr for IndexRange {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |s: &str| {
+            maybe_hex::<u16>(s.trim()).map_err(|_| format!("'{s}' is not a valid object index"))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if start > end {
+            return Err(format!(
+                "Range start 0x{start:X} is after its end 0x{end:X}"
+            ));
+        }
+        Ok(Self(start..=end))
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct StoreDcfArgs {
+    /// The ID of the configuration manager node
+    pub manager_id: u8,
+    /// The ID of the node the DCF configures
+    pub node_id: u8,
+    /// Path to a DCF file
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    pub path: PathBuf,
 }
 
 #[derive(Debug, Args)]
@@ -202,6 +319,19 @@ pub enum LssCommands {
         #[arg(default_value = "5")]
         timeout: u64,
     },
+    /// Find unconfigured nodes, and assign them sequential node IDs
+    Assign {
+        /// The first node ID to assign
+        first: u8,
+        /// The last node ID which may be assigned
+        last: u8,
+        /// Command each node to store its new node ID
+        #[arg(long)]
+        store: bool,
+        /// Timeout for waiting for fastscan response in milliseconds
+        #[arg(long, default_value = "5")]
+        timeout: u64,
+    },
     SetNodeId {
         /// The node ID to assign
         node_id: u8,
@@ -222,7 +352,7 @@ pub enum LssCommands {
 
 #[cfg(test)]
 mod tests {
-    use super::{Cli, Commands, SdoDataType};
+    use super::{Cli, Commands, DumpFormat, IndexRange, SdoDataType};
     use clap::Parser;
 
     #[test]
@@ -241,4 +371,35 @@ mod tests {
             other => panic!("expected write command, got {other:?}"),
         }
     }
+
+    #[test]
+    fn test_dump_args() {
+        let cli = Cli::try_parse_from([
+            "zencan-cli",
+            "dump",
+            "5",
+            "--format",
+            "json",
+            "--range",
+            "0x1000-0x1018",
+            "--range",
+            "0x2000",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Commands::Dump(args) => {
+                assert_eq!(args.node_id, 5);
+                assert_eq!(args.path, None);
+                assert_eq!(args.format, DumpFormat::Json);
+                assert_eq!(
+                    args.range,
+                    vec![IndexRange(0x1000..=0x1018), IndexRange(0x2000..=0x2000)]
+                );
+            }
+            other => panic!("expected dump command, got {other:?}"),
+        }
+        assert!("0x2000-0x1000".parse::<IndexRange>().is_err());
+        assert!("0x1000-".parse::<IndexRange>().is_err());
+    }
 }
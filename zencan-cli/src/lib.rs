@@ -1,6 +1,14 @@
 //! Command-line utilities for zencan
 //!
-//! Collection of tools for interacting with devices via a socketcan interface on linux.
+//! Collection of tools for interacting with devices on a CAN bus.
+//!
+//! Each tool takes the bus to use as its first argument. This is either the name of a socketcan
+//! interface, e.g. `can0`, or an interface URI such as `udp:192.168.1.10:20000` or
+//! `slcan:/dev/ttyACM0@500k`; see [`zencan_client::transport`] for the supported interfaces.
+//! `zencan-flash` only supports socketcan.
+//!
+//! Socketcan is only available on linux. With the `pcan` feature, the tools can also use PEAK PCAN
+//! adapters, e.g. `pcan:usb1@500k`.
 //!
 //! # zencandump
 //!
@@ -13,5 +21,20 @@
 //!
 //! A REPL-style interactive shell for controlling CAN devices.
 //!
+//! # zencan-browser
+//!
+//! A terminal UI for browsing and editing the objects of a node, described by an EDS or device
+//! config. Requires the `browser` feature.
+//!
+//! Usage example: `zencan-browser can0 5 device_config.toml`
+//!
+//! # zencan-eds-export
+//!
+//! Creates an EDS file describing a node from its device config. Requires the `eds` feature.
+//!
+//! Usage example: `zencan-eds-export device_config.toml --baud-rate 500 -o device.eds`
+//!
 
+#[cfg(feature = "browser")]
+pub mod browser;
 pub mod command;
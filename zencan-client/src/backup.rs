@@ -0,0 +1,488 @@
+//! Backup and restore of node parameters
+//!
+//! A [`ParameterBackup`] holds the values of a node's configuration parameters, which can be saved
+//! to a file and written back to the same node, or to a replacement for it, later. The parameters
+//! are the sub objects which can be both read and written, and which are persisted when the node
+//! saves its objects, as described by an [`ObjectInfo`] (usually created from the node's device
+//! config).
+//!
+//! ```ignore
+//! let info = ObjectInfo::from_device_config(&DeviceConfig::load("device.toml")?);
+//! let backup = ParameterBackup::read(&mut client, &info).await?;
+//! backup.save("node5.toml")?;
+//!
+//! // Later, after replacing the node
+//! let backup = ParameterBackup::load("node5.toml")?;
+//! backup.restore(&mut client, &RestoreOptions::default()).await?;
+//! ```
+//!
+//! # File format
+//!
+//! Backups are stored as TOML, with the identity of the node they were read from, and the raw
+//! value of each parameter as a hex string of its bytes, in the order they are transferred by SDO:
+//!
+//! ```toml
+//! [identity]
+//! vendor_id = 0x00000001
+//! product_code = 0x00000002
+//! revision = 0x00000003
+//! serial = 0x00000004
+//!
+//! [[parameter]]
+//! index = 0x2000
+//! sub = 1
+//! data = "7b000000"
+//! ```
+use std::{fmt::Write as _, path::Path};
+
+use serde::Deserialize;
+use snafu::{ResultExt, Snafu};
+use zencan_common::{
+    constants::object_ids::{RPDO_COMM_BASE, RPDO_MAP_BASE, TPDO_COMM_BASE, TPDO_MAP_BASE},
+    lss::LssIdentity,
+    sdo::AbortCode,
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+use crate::{ObjectInfo, RawAbortCode, SdoClient, SdoClientError};
+
+/// The bit in a PDO COB ID which disables the PDO
+pub(crate) const PDO_NOT_VALID: u32 = 1 << 31;
+
+/// Error returned when backing up or restoring parameters
+#[derive(Debug, Snafu)]
+pub enum BackupError {
+    /// A parameter could not be read from the node
+    #[snafu(display("Failed to read {index:04X}sub{sub}: {source}"))]
+    Read {
+        /// The object index
+        index: u16,
+        /// The sub index
+        sub: u8,
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// A parameter could not be written to the node
+    #[snafu(display("Failed to write {index:04X}sub{sub}: {source}"))]
+    Write {
+        /// The object index
+        index: u16,
+        /// The sub index
+        sub: u8,
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// The identity object could not be read from the node
+    #[snafu(display("Failed to read identity: {source}"))]
+    Identity {
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// The backup was read from a different type of device
+    #[snafu(display(
+        "Backup is for vendor 0x{:X} product 0x{:X}, but the node is vendor 0x{:X} product 0x{:X}",
+        expected.vendor_id,
+        expected.product_code,
+        actual.vendor_id,
+        actual.product_code,
+    ))]
+    DeviceMismatch {
+        /// The identity stored in the backup
+        expected: LssIdentity,
+        /// The identity of the node
+        actual: LssIdentity,
+    },
+    /// Some parameters read back after a restore do not have their restored value
+    #[snafu(display(
+        "{} parameters failed verification, starting with {:04X}sub{}",
+        mismatches.len(),
+        mismatches[0].0,
+        mismatches[0].1,
+    ))]
+    VerifyFailed {
+        /// The index and sub index of each parameter with the wrong value
+        mismatches: Vec<(u16, u8)>,
+    },
+    /// The save command failed after restoring the parameters
+    #[snafu(display("Failed to save objects: {source}"))]
+    Save {
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// The backup file could not be read or written
+    #[snafu(display("IO error: {source}"))]
+    Io {
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// The backup file is not valid
+    #[snafu(display("Invalid backup file: {message}"))]
+    InvalidFile {
+        /// Description of the problem
+        message: String,
+    },
+}
+
+/// The value of a single sub object in a [`ParameterBackup`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// The raw value of the sub object
+    pub data: Vec<u8>,
+}
+
+/// Options for [`ParameterBackup::restore()`]
+#[derive(Clone, Copy, Debug)]
+pub struct RestoreOptions {
+    /// Refuse to restore to a node with a different vendor ID or product code than the node the
+    /// backup was read from. Default: true
+    pub check_identity: bool,
+    /// Command the node to save its objects after the parameters are restored and verified, so
+    /// that they survive a reset. Default: true
+    pub save: bool,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            check_identity: true,
+            save: true,
+        }
+    }
+}
+
+/// A snapshot of the configuration parameters of a node
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterBackup {
+    /// The identity of the node the backup was read from, if known
+    pub identity: Option<LssIdentity>,
+    /// The parameter values, sorted by index and sub index
+    pub parameters: Vec<Parameter>,
+}
+
+impl ParameterBackup {
+    /// Read the parameters described by `info` from a node
+    ///
+    /// Every sub object in `info` which is readable, writable, and persisted is read, except for
+    /// PDO mappings past the number in use. Sub objects which the node reports do not exist are
+    /// skipped, and any other failed read fails the backup.
+    pub async fn read<S: AsyncCanSender, R: AsyncCanReceiver>(
+        client: &mut SdoClient<S, R>,
+        info: &ObjectInfo,
+    ) -> Result<Self, BackupError> {
+        let identity = client.read_identity().await.context(IdentitySnafu)?;
+
+        let objects: Vec<(u16, u8)> = info
+            .iter()
+            .filter(|(_, _, sub_info)| {
+                sub_info.persist
+                    && sub_info.access_type.is_readable()
+                    && sub_info.access_type.is_writable()
+            })
+            .map(|(index, sub, _)| (index, sub))
+            .collect();
+
+        let mut parameters = Vec::with_capacity(objects.len());
+        for (&(index, sub), result) in objects.iter().zip(client.read_many(&objects).await) {
+            let data = match result {
+                Ok(data) => data,
+                // The object info may describe sub objects which the node does not implement, e.g.
+                // more PDO mappings than it supports
+                Err(SdoClientError::ServerAbort {
+                    abort_code:
+                        RawAbortCode::Valid(AbortCode::NoSuchObject | AbortCode::NoSuchSubIndex),
+                    ..
+                }) => continue,
+                Err(source) => return Err(BackupError::Read { index, sub, source }),
+            };
+            parameters.push(Parameter { index, sub, data });
+        }
+
+        // Mappings past the number in use are not part of the PDO configuration, and unused
+        // mappings cannot be written back
+        let mapping_counts: Vec<(u16, u8)> = parameters
+            .iter()
+            .filter(|p| is_pdo_mapping_count(p.index, p.sub) && p.data.len() == 1)
+            .map(|p| (p.index, p.data[0]))
+            .collect();
+        parameters.retain(|p| {
+            !mapping_counts
+                .iter()
+                .any(|&(index, count)| p.index == index && p.sub > count)
+        });
+
+        Ok(Self {
+            identity: Some(identity),
+            parameters,
+        })
+    }
+
+    /// Write the parameters to a node, and read them back to verify them
+    ///
+    /// PDOs are disabled before their parameters are written, and their mapping counts and COB IDs
+    /// are written last, so that PDO configurations can be restored while the node is
+    /// operational, regardless of its current PDO configuration.
+    pub async fn restore<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        client: &mut SdoClient<S, R>,
+        options: &RestoreOptions,
+    ) -> Result<(), BackupError> {
+        if let (true, Some(expected)) = (options.check_identity, self.identity) {
+            let actual = client.read_identity().await.context(IdentitySnafu)?;
+            if expected.vendor_id != actual.vendor_id
+                || expected.product_code != actual.product_code
+            {
+                return DeviceMismatchSnafu { expected, actual }.fail();
+            }
+        }
+
+        let pdo_cob_ids: Vec<&Parameter> = self
+            .parameters
+            .iter()
+            .filter(|p| is_pdo_cob_id(p.index, p.sub))
+            .collect();
+        let pdo_mapping_counts: Vec<&Parameter> = self
+            .parameters
+            .iter()
+            .filter(|p| is_pdo_mapping_count(p.index, p.sub))
+            .collect();
+
+        for p in &pdo_cob_ids {
+            if let Ok(cob_id) = <[u8; 4]>::try_from(p.data.as_slice()) {
+                let disabled = u32::from_le_bytes(cob_id) | PDO_NOT_VALID;
+                write(client, p.index, p.sub, &disabled.to_le_bytes()).await?;
+            }
+        }
+        for p in self
+            .parameters
+            .iter()
+            .filter(|p| !is_pdo_cob_id(p.index, p.sub) && !is_pdo_mapping_count(p.index, p.sub))
+        {
+            write(client, p.index, p.sub, &p.data).await?;
+        }
+        for p in pdo_mapping_counts.iter().chain(&pdo_cob_ids) {
+            write(client, p.index, p.sub, &p.data).await?;
+        }
+
+        self.verify(client).await?;
+
+        if options.save {
+            client.save_objects().await.context(SaveSnafu)?;
+        }
+        Ok(())
+    }
+
+    /// Read the parameters from a node, and check that they match the backup
+    pub async fn verify<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        client: &mut SdoClient<S, R>,
+    ) -> Result<(), BackupError> {
+        let objects: Vec<(u16, u8)> = self.parameters.iter().map(|p| (p.index, p.sub)).collect();
+        let mut mismatches = Vec::new();
+        for (p, result) in self.parameters.iter().zip(client.read_many(&objects).await) {
+            let data = result.context(ReadSnafu {
+                index: p.index,
+                sub: p.sub,
+            })?;
+            if data != p.data {
+                mismatches.push((p.index, p.sub));
+            }
+        }
+        if !mismatches.is_empty() {
+            return VerifyFailedSnafu { mismatches }.fail();
+        }
+        Ok(())
+    }
+
+    /// Load a backup from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BackupError> {
+        let s = std::fs::read_to_string(path).context(IoSnafu)?;
+        Self::from_toml(&s)
+    }
+
+    /// Save the backup to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BackupError> {
+        std::fs::write(path, self.to_toml()).context(IoSnafu)
+    }
+
+    /// Parse a backup from a TOML string
+    pub fn from_toml(s: &str) -> Result<Self, BackupError> {
+        let file: BackupFile = toml::from_str(s).map_err(|e| BackupError::InvalidFile {
+            message: e.to_string(),
+        })?;
+
+        let mut parameters = Vec::with_capacity(file.parameter.len());
+        for p in file.parameter {
+            let data = parse_hex(&p.data).ok_or_else(|| BackupError::InvalidFile {
+                message: format!("invalid data for {:04X}sub{}", p.index, p.sub),
+            })?;
+            parameters.push(Parameter {
+                index: p.index,
+                sub: p.sub,
+                data,
+            });
+        }
+
+        Ok(Self {
+            identity: file.identity.map(|id| LssIdentity {
+                vendor_id: id.vendor_id,
+                product_code: id.product_code,
+                revision: id.revision,
+                serial: id.serial,
+            }),
+            parameters,
+        })
+    }
+
+    /// Format the backup as a TOML string
+    pub fn to_toml(&self) -> String {
+        let mut s = String::new();
+        if let Some(id) = &self.identity {
+            writeln!(s, "[identity]").unwrap();
+            writeln!(s, "vendor_id = 0x{:08X}", id.vendor_id).unwrap();
+            writeln!(s, "product_code = 0x{:08X}", id.product_code).unwrap();
+            writeln!(s, "revision = 0x{:08X}", id.revision).unwrap();
+            writeln!(s, "serial = 0x{:08X}", id.serial).unwrap();
+        }
+        for p in &self.parameters {
+            if !s.is_empty() {
+                writeln!(s).unwrap();
+            }
+            writeln!(s, "[[parameter]]").unwrap();
+            writeln!(s, "index = 0x{:04X}", p.index).unwrap();
+            writeln!(s, "sub = {}", p.sub).unwrap();
+            write!(s, "data = \"").unwrap();
+            for b in &p.data {
+                write!(s, "{b:02x}").unwrap();
+            }
+            writeln!(s, "\"").unwrap();
+        }
+        s
+    }
+}
+
+async fn write<S: AsyncCanSender, R: AsyncCanReceiver>(
+    client: &mut SdoClient<S, R>,
+    index: u16,
+    sub: u8,
+    data: &[u8],
+) -> Result<(), BackupError> {
+    client
+        .download(index, sub, data)
+        .await
+        .context(WriteSnafu { index, sub })
+}
+
+/// Returns true if the sub object is the COB ID of an RPDO or TPDO
+pub(crate) fn is_pdo_cob_id(index: u16, sub: u8) -> bool {
+    let comm_range = |base: u16| base..base + 0x200;
+    sub == 1
+        && (comm_range(RPDO_COMM_BASE).contains(&index)
+            || comm_range(TPDO_COMM_BASE).contains(&index))
+}
+
+/// Returns true if the sub object is the number of mappings of an RPDO or TPDO
+pub(crate) fn is_pdo_mapping_count(index: u16, sub: u8) -> bool {
+    let map_range = |base: u16| base..base + 0x200;
+    sub == 0
+        && (map_range(RPDO_MAP_BASE).contains(&index) || map_range(TPDO_MAP_BASE).contains(&index))
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackupFile {
+    identity: Option<IdentityEntry>,
+    #[serde(default)]
+    parameter: Vec<ParameterEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentityEntry {
+    vendor_id: u32,
+    product_code: u32,
+    revision: u32,
+    serial: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParameterEntry {
+    index: u16,
+    sub: u8,
+    data: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let backup = ParameterBackup {
+            identity: Some(LssIdentity::new(1, 2, 3, 0xdeadbeef)),
+            parameters: vec![
+                Parameter {
+                    index: 0x1400,
+                    sub: 1,
+                    data: vec![0x01, 0x02, 0x00, 0x80],
+                },
+                Parameter {
+                    index: 0x2002,
+                    sub: 0,
+                    data: b"Some String".to_vec(),
+                },
+                Parameter {
+                    index: 0x3000,
+                    sub: 0,
+                    data: vec![],
+                },
+            ],
+        };
+        let s = backup.to_toml();
+        assert!(s.contains("index = 0x2002"));
+        assert_eq!(backup, ParameterBackup::from_toml(&s).unwrap());
+
+        let empty = ParameterBackup::default();
+        assert_eq!(empty, ParameterBackup::from_toml(&empty.to_toml()).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        for s in [
+            "[[parameter]]\nindex = 0x2000\nsub = 1\ndata = \"123\"",
+            "[[parameter]]\nindex = 0x2000\nsub = 1\ndata = \"zz\"",
+            "[[parameter]]\nindex = 0x2000\nsub = 1",
+            "[[parameter]]\nindex = 0x12000\nsub = 1\ndata = \"\"",
+        ] {
+            assert!(matches!(
+                ParameterBackup::from_toml(s),
+                Err(BackupError::InvalidFile { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_pdo_parameters() {
+        assert!(is_pdo_cob_id(0x1400, 1));
+        assert!(is_pdo_cob_id(0x19FF, 1));
+        assert!(!is_pdo_cob_id(0x1400, 2));
+        assert!(!is_pdo_cob_id(0x1600, 1));
+        assert!(is_pdo_mapping_count(0x1A03, 0));
+        assert!(!is_pdo_mapping_count(0x1A03, 1));
+        assert!(!is_pdo_mapping_count(0x1C00, 0));
+    }
+}
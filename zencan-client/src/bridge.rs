@@ -0,0 +1,281 @@
+//! Forwarding of messages between two CAN interfaces
+//!
+//! A [`Bridge`] connects two CAN interfaces, called A and B, and forwards the messages which match
+//! its [`ForwardRule`]s from one to the other. This can be used to build a gateway which segments a
+//! large network, e.g. so that only the PDOs which are needed on the other side cross between the
+//! segments, or to connect a physical bus to a virtual one.
+//!
+//! Each rule matches a range of IDs, and can optionally move the range to a new base ID on the
+//! other interface. This allows e.g. the nodes on two segments to use the same node IDs, with the
+//! gateway translating their PDO IDs into separate ranges.
+//!
+//! ```ignore
+//! let (a_tx, a_rx) = open_socketcan("can0").unwrap();
+//! let (b_tx, b_rx) = open_socketcan("can1").unwrap();
+//! let mut bridge = Bridge::new();
+//! // Forward node TPDO1s from A to B unchanged
+//! bridge.forward_a_to_b(ForwardRule::std(0x181..=0x1FF));
+//! // Forward node TPDO1s from B to A, moved to the TPDO2 range
+//! bridge.forward_b_to_a(ForwardRule::std(0x181..=0x1FF).remap_to(CanId::std(0x281)));
+//! bridge.run(a_tx, a_rx, b_tx, b_rx).await?;
+//! ```
+//!
+//! The bridge does not keep track of the messages it has forwarded. If an interface's receiver
+//! also receives the messages sent by its own sender, then a message which is forwarded onto it
+//! and which matches a rule in the opposite direction will be forwarded back, so rules in opposite
+//! directions should not overlap after remapping. Socketcan sockets do not receive their own
+//! messages by default.
+use std::ops::RangeInclusive;
+
+use snafu::Snafu;
+use zencan_common::{
+    messages::CanId,
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+/// The highest standard (11-bit) CAN ID
+const MAX_STD_ID: u32 = 0x7FF;
+/// The highest extended (29-bit) CAN ID
+const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+/// Identifies one of the two interfaces connected by a [`Bridge`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BridgeInterface {
+    /// Interface A
+    A,
+    /// Interface B
+    B,
+}
+
+/// Error returned by [`Bridge::run`]
+#[derive(Clone, Debug, PartialEq, Snafu)]
+pub enum BridgeError {
+    /// Receiving from an interface failed
+    #[snafu(display("Error receiving from interface {interface:?}: {message}"))]
+    ReceiveFailed {
+        /// The interface which failed
+        interface: BridgeInterface,
+        /// A description of the receiver error
+        message: String,
+    },
+}
+
+/// Selects a range of CAN IDs to forward, and optionally remaps them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForwardRule {
+    ids: RangeInclusive<u32>,
+    extended: bool,
+    remap: Option<CanId>,
+}
+
+impl ForwardRule {
+    /// Create a rule which forwards a range of standard IDs
+    pub fn std(ids: RangeInclusive<u16>) -> Self {
+        Self {
+            ids: *ids.start() as u32..=*ids.end() as u32,
+            extended: false,
+            remap: None,
+        }
+    }
+
+    /// Create a rule which forwards a range of extended IDs
+    pub fn extended(ids: RangeInclusive<u32>) -> Self {
+        Self {
+            ids,
+            extended: true,
+            remap: None,
+        }
+    }
+
+    /// Move the forwarded range so that it starts at `base`
+    ///
+    /// A message with ID `start + n` is forwarded with the ID `base + n`, where `start` is the
+    /// first ID in the rule's range. The ID type of `base` is used, so this can also translate
+    /// between standard and extended IDs. A message whose new ID would be out of range for its ID
+    /// type is not forwarded.
+    pub fn remap_to(mut self, base: CanId) -> Self {
+        self.remap = Some(base);
+        self
+    }
+
+    /// Returns the ID with which a message should be forwarded, or None if the rule does not
+    /// match `id`
+    pub fn map(&self, id: CanId) -> Option<CanId> {
+        if id.is_extended() != self.extended || !self.ids.contains(&id.raw()) {
+            return None;
+        }
+        let Some(base) = self.remap else {
+            return Some(id);
+        };
+        let raw = base.raw().checked_add(id.raw() - self.ids.start())?;
+        match base {
+            CanId::Std(_) if raw <= MAX_STD_ID => Some(CanId::std(raw as u16)),
+            CanId::Extended(_) if raw <= MAX_EXTENDED_ID => Some(CanId::extended(raw)),
+            _ => None,
+        }
+    }
+}
+
+/// Forwards selected messages between two CAN interfaces
+///
+/// See the [module docs](self).
+#[derive(Clone, Debug, Default)]
+pub struct Bridge {
+    a_to_b: Vec<ForwardRule>,
+    b_to_a: Vec<ForwardRule>,
+}
+
+impl Bridge {
+    /// Create a bridge with no rules, which forwards nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule for forwarding messages received on interface A to interface B
+    ///
+    /// Rules are checked in the order they were added, and the first matching rule is used.
+    pub fn forward_a_to_b(&mut self, rule: ForwardRule) -> &mut Self {
+        self.a_to_b.push(rule);
+        self
+    }
+
+    /// Add a rule for forwarding messages received on interface B to interface A
+    ///
+    /// Rules are checked in the order they were added, and the first matching rule is used.
+    pub fn forward_b_to_a(&mut self, rule: ForwardRule) -> &mut Self {
+        self.b_to_a.push(rule);
+        self
+    }
+
+    /// Get the message to send to the other interface for a message received on `from`, or None if
+    /// it should not be forwarded
+    pub fn translate(&self, from: BridgeInterface, msg: &CanMessage) -> Option<CanMessage> {
+        let rules = match from {
+            BridgeInterface::A => &self.a_to_b,
+            BridgeInterface::B => &self.b_to_a,
+        };
+        let id = rules.iter().find_map(|rule| rule.map(msg.id()))?;
+        Some(if msg.is_rtr() {
+            CanMessage::new_rtr(id)
+        } else {
+            CanMessage::new(id, msg.data())
+        })
+    }
+
+    /// Forward messages between the interfaces until a receiver fails
+    ///
+    /// Messages which cannot be sent are logged and dropped, so that a fault on one interface does
+    /// not stop forwarding in the other direction.
+    pub async fn run<SA, RA, SB, RB>(
+        &self,
+        a_sender: SA,
+        a_receiver: RA,
+        b_sender: SB,
+        b_receiver: RB,
+    ) -> Result<(), BridgeError>
+    where
+        SA: AsyncCanSender,
+        RA: AsyncCanReceiver,
+        SB: AsyncCanSender,
+        RB: AsyncCanReceiver,
+    {
+        tokio::try_join!(
+            self.forward(BridgeInterface::A, a_receiver, b_sender),
+            self.forward(BridgeInterface::B, b_receiver, a_sender),
+        )?;
+        Ok(())
+    }
+
+    async fn forward<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        from: BridgeInterface,
+        mut receiver: R,
+        mut sender: S,
+    ) -> Result<(), BridgeError> {
+        loop {
+            let msg = receiver
+                .recv()
+                .await
+                .map_err(|e| BridgeError::ReceiveFailed {
+                    interface: from,
+                    message: format!("{e:?}"),
+                })?;
+            if let Some(forwarded) = self.translate(from, &msg) {
+                if let Err(e) = sender.send(forwarded).await {
+                    log::warn!(
+                        "Failed to forward message {} from interface {from:?}: {}",
+                        msg.id(),
+                        e.message()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matching() {
+        let rule = ForwardRule::std(0x181..=0x1FF);
+        assert_eq!(Some(CanId::std(0x181)), rule.map(CanId::std(0x181)));
+        assert_eq!(Some(CanId::std(0x1FF)), rule.map(CanId::std(0x1FF)));
+        assert_eq!(None, rule.map(CanId::std(0x180)));
+        assert_eq!(None, rule.map(CanId::std(0x200)));
+        // The ID type must match
+        assert_eq!(None, rule.map(CanId::extended(0x181)));
+
+        let rule = ForwardRule::extended(0x1000..=0x1FFF);
+        assert_eq!(
+            Some(CanId::extended(0x1234)),
+            rule.map(CanId::extended(0x1234))
+        );
+        assert_eq!(None, rule.map(CanId::std(0x100)));
+    }
+
+    #[test]
+    fn test_rule_remapping() {
+        let rule = ForwardRule::std(0x181..=0x1FF).remap_to(CanId::std(0x281));
+        assert_eq!(Some(CanId::std(0x281)), rule.map(CanId::std(0x181)));
+        assert_eq!(Some(CanId::std(0x285)), rule.map(CanId::std(0x185)));
+
+        // Translating between ID types
+        let rule = ForwardRule::std(0x180..=0x1FF).remap_to(CanId::extended(0x10000));
+        assert_eq!(Some(CanId::extended(0x10005)), rule.map(CanId::std(0x185)));
+
+        // IDs which would overflow the target ID type are not forwarded
+        let rule = ForwardRule::std(0x100..=0x1FF).remap_to(CanId::std(0x780));
+        assert_eq!(Some(CanId::std(0x7FF)), rule.map(CanId::std(0x17F)));
+        assert_eq!(None, rule.map(CanId::std(0x180)));
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut bridge = Bridge::new();
+        bridge
+            .forward_a_to_b(ForwardRule::std(0x100..=0x10F).remap_to(CanId::std(0x200)))
+            .forward_a_to_b(ForwardRule::std(0x100..=0x1FF));
+
+        let msg = CanMessage::new(CanId::std(0x101), &[1, 2, 3]).with_timestamp(1000);
+        let forwarded = bridge.translate(BridgeInterface::A, &msg).unwrap();
+        // The first matching rule is used
+        assert_eq!(CanMessage::new(CanId::std(0x201), &[1, 2, 3]), forwarded);
+        // Receive timestamps are not forwarded
+        assert_eq!(None, forwarded.timestamp());
+
+        let msg = CanMessage::new(CanId::std(0x120), &[4]);
+        assert_eq!(Some(msg), bridge.translate(BridgeInterface::A, &msg));
+
+        let rtr = CanMessage::new_rtr(CanId::std(0x102));
+        assert_eq!(
+            Some(CanMessage::new_rtr(CanId::std(0x202))),
+            bridge.translate(BridgeInterface::A, &rtr)
+        );
+
+        // There are no rules from B to A
+        assert_eq!(None, bridge.translate(BridgeInterface::B, &msg));
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Deref, DerefMut};
+use std::ops::{ControlFlow, Deref, DerefMut};
 use std::sync::Mutex;
 use std::time::Duration;
 use std::{collections::HashMap, sync::Arc, time::Instant};
@@ -9,7 +9,9 @@ use zencan_common::constants::object_ids::{
     RPDO_COMM_BASE, RPDO_MAP_BASE, TPDO_COMM_BASE, TPDO_MAP_BASE,
 };
 use zencan_common::lss::{LssIdentity, LssState};
-use zencan_common::messages::{NmtCommand, NmtCommandSpecifier, SyncObject, ZencanMessage};
+use zencan_common::messages::{
+    ConnectionSet, NmtCommand, NmtCommandSpecifier, SyncObject, VendorBroadcast, ZencanMessage,
+};
 use zencan_common::nmt::NmtState;
 use zencan_common::node_id::ConfiguredNodeId;
 use zencan_common::sdo::AbortCode;
@@ -20,11 +22,12 @@ use zencan_common::{
     CanId, NodeId,
 };
 
+use super::bus_multiplexer::BusMultiplexer;
 use super::shared_sender::SharedSender;
 use crate::sdo_client::{SdoClient, SdoClientError};
-use crate::{LssError, LssMaster, RawAbortCode};
+use crate::{telemetry, FastScanProgress, LssError, RawAbortCode};
 
-use super::shared_receiver::{SharedReceiver, SharedReceiverChannel};
+use super::shared_receiver::{MessageFilter, SharedReceiver, SharedReceiverChannel, Subscription};
 
 #[derive(Debug, Clone)]
 pub struct NodeInfo {
@@ -268,8 +271,7 @@ struct SdoClientMutex<S>
 where
     S: AsyncCanSender + Sync,
 {
-    sender: SharedSender<S>,
-    receiver: SharedReceiver,
+    mux: BusMultiplexer<S>,
     clients: HashMap<u8, Mutex<()>>,
 }
 
@@ -277,17 +279,13 @@ impl<S> SdoClientMutex<S>
 where
     S: AsyncCanSender + Sync,
 {
-    pub fn new(sender: SharedSender<S>, receiver: SharedReceiver) -> Self {
+    pub fn new(mux: BusMultiplexer<S>) -> Self {
         let mut clients = HashMap::new();
         for i in 0u8..128 {
             clients.insert(i, Mutex::new(()));
         }
 
-        Self {
-            sender,
-            receiver,
-            clients,
-        }
+        Self { mux, clients }
     }
 
     pub fn lock(&self, id: u8) -> SdoClientGuard<'_, SharedSender<S>, SharedReceiverChannel> {
@@ -295,7 +293,7 @@ where
             panic!("ID {} out of range", id);
         }
         let guard = self.clients.get(&id).unwrap().lock().unwrap();
-        let client = SdoClient::new_std(id, self.sender.clone(), self.receiver.create_rx());
+        let client = self.mux.sdo_client(id);
         SdoClientGuard {
             _guard: guard,
             client,
@@ -306,6 +304,7 @@ where
 /// Manage a zencan bus
 #[derive(Debug)]
 pub struct BusManager<S: AsyncCanSender + Sync + Send> {
+    mux: BusMultiplexer<S>,
     sender: SharedSender<S>,
     receiver: SharedReceiver,
     nodes: Arc<tokio::sync::Mutex<HashMap<u8, NodeInfo>>>,
@@ -324,16 +323,23 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
     ///
     /// When using socketcan, these can be created with [`crate::open_socketcan`]
     pub fn new(sender: S, receiver: impl AsyncCanReceiver + Sync + 'static) -> Self {
-        let receiver = SharedReceiver::new(receiver);
-        let sender = SharedSender::new(Arc::new(tokio::sync::Mutex::new(sender)));
-        let sdo_clients = SdoClientMutex::new(sender.clone(), receiver.clone());
-
-        let mut state_rx = receiver.create_rx();
+        let mux = BusMultiplexer::new(sender, receiver);
+        let sender = mux.sender();
+        let receiver = mux.shared_receiver().clone();
+        let sdo_clients = SdoClientMutex::new(mux.clone());
+
+        // The monitor only needs heartbeats
+        let mut state_rx = mux.receiver(MessageFilter::StdRange {
+            first: ConnectionSet::DEFAULT.heartbeat_id(1),
+            last: ConnectionSet::DEFAULT.heartbeat_id(127),
+        });
         let nodes = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
 
         let monitor_task = {
             let nodes = nodes.clone();
             tokio::spawn(async move {
+                // The time of the last heartbeat from each node, for measuring heartbeat gaps
+                let mut last_heartbeats = HashMap::new();
                 loop {
                     if let Ok(msg) = state_rx.recv().await {
                         if let Ok(ZencanMessage::Heartbeat(heartbeat)) =
@@ -341,6 +347,13 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
                         {
                             let id_num = heartbeat.node;
                             if let Ok(node_id) = NodeId::try_from(id_num) {
+                                let now = Instant::now();
+                                // A boot-up message starts a new sequence of heartbeats
+                                if heartbeat.state == NmtState::Bootup {
+                                    last_heartbeats.remove(&id_num);
+                                } else if let Some(previous) = last_heartbeats.insert(id_num, now) {
+                                    telemetry::heartbeat_gap(id_num, now - previous);
+                                }
                                 let mut nodes = nodes.lock().await;
                                 if let std::collections::hash_map::Entry::Vacant(e) =
                                     nodes.entry(id_num)
@@ -348,7 +361,12 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
                                     e.insert(NodeInfo::new(node_id.raw()));
                                 } else {
                                     let node = nodes.get_mut(&id_num).unwrap();
-                                    node.nmt_state = Some(heartbeat.state);
+                                    // A boot-up message indicates that the node has just
+                                    // entered PreOperational
+                                    node.nmt_state = Some(match heartbeat.state {
+                                        NmtState::Bootup => NmtState::PreOperational,
+                                        s => s,
+                                    });
                                     node.last_seen = Instant::now();
                                 }
                             } else {
@@ -361,6 +379,7 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
         };
 
         Self {
+            mux,
             sender,
             receiver,
             sdo_clients,
@@ -380,6 +399,42 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
         self.sdo_clients.lock(node_id)
     }
 
+    /// Read several sub objects from each of several nodes
+    ///
+    /// Each entry of `reads` gives a node ID and the objects to read from it. The nodes are read
+    /// in parallel, using [`SdoClient::read_many()`] for each, and the results are returned in the
+    /// same order as `reads`.
+    ///
+    /// If a node ID is out of range, or appears more than once, every read of that entry fails with
+    /// [`SdoClientError::InvalidNodeId`]. The first entry for a repeated node ID is read as usual.
+    pub async fn read_many(
+        &self,
+        reads: &[(u8, &[(u16, u8)])],
+    ) -> Vec<Vec<Result<Vec<u8>, SdoClientError>>> {
+        join_all(
+            reads
+                .iter()
+                .enumerate()
+                .map(|(i, &(node_id, objects))| async move {
+                    let reason = if !(1..=127).contains(&node_id) {
+                        Some("out of range")
+                    } else if reads[..i].iter().any(|&(id, _)| id == node_id) {
+                        Some("appears more than once")
+                    } else {
+                        None
+                    };
+                    match reason {
+                        Some(reason) => objects
+                            .iter()
+                            .map(|_| Err(SdoClientError::InvalidNodeId { node_id, reason }))
+                            .collect(),
+                        None => self.sdo_client(node_id).read_many(objects).await,
+                    }
+                }),
+        )
+        .await
+    }
+
     /// Get a list of known nodes
     pub async fn node_list(&self) -> Vec<NodeInfo> {
         let node_map = self.nodes.lock().await;
@@ -458,21 +513,36 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
     ///
     /// After devices are found, they are all put back into waiting state
     pub async fn lss_fastscan(&mut self, timeout: Duration) -> Vec<LssIdentity> {
-        let mut devices = Vec::new();
-        let mut lss = LssMaster::new(self.sender.clone(), self.receiver.create_rx());
-
-        // Put all nodes into Waiting state
-        lss.set_global_mode(LssState::Waiting).await;
-
-        // Each time a device is completely identified, it goes into Configuring mode and will not
-        // respond to further scans. Once all devices are identified, the scan will return None.
-        while let Some(id) = lss.fast_scan(timeout).await {
-            devices.push(id);
-        }
+        self.lss_fastscan_with_progress(timeout, |_, _| ControlFlow::Continue(()))
+            .await
+    }
 
-        lss.set_global_mode(LssState::Waiting).await;
+    /// Find all unconfigured devices on the bus, reporting progress as the scan proceeds
+    ///
+    /// Same as [`lss_fastscan`](Self::lss_fastscan), but `progress` is called with the number of
+    /// devices found so far, and the progress of the scan for the next device. Returning
+    /// [`ControlFlow::Break`] from `progress` cancels the scan, and the devices found before
+    /// cancellation are returned.
+    pub async fn lss_fastscan_with_progress(
+        &mut self,
+        timeout: Duration,
+        progress: impl FnMut(usize, FastScanProgress) -> ControlFlow<()>,
+    ) -> Vec<LssIdentity> {
+        let mut lss = self.mux.lss_master();
+        lss.fast_scan_all_with_progress(timeout, progress).await
+    }
 
-        devices
+    /// Find all unconfigured devices on the bus, and assign each a node ID from a pool
+    ///
+    /// See [`LssMaster::fast_scan_assign`](crate::LssMaster::fast_scan_assign).
+    pub async fn lss_fastscan_assign(
+        &mut self,
+        timeout: Duration,
+        node_ids: impl IntoIterator<Item = ConfiguredNodeId>,
+        store: bool,
+    ) -> Result<Vec<(LssIdentity, ConfiguredNodeId)>, LssError> {
+        let mut lss = self.mux.lss_master();
+        lss.fast_scan_assign(timeout, node_ids, store).await
     }
 
     /// Activate a single LSS slave by its identity
@@ -484,7 +554,7 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
     /// identify a device on the bus. If they are not known, they can be found using
     /// [`lss_fastscan()`](Self::lss_fastscan).
     pub async fn lss_activate(&mut self, ident: LssIdentity) -> Result<(), LssError> {
-        let mut lss = LssMaster::new(self.sender.clone(), self.receiver.create_rx());
+        let mut lss = self.mux.lss_master();
         lss.set_global_mode(LssState::Waiting).await;
         lss.enter_config_by_identity(
             ident.vendor_id,
@@ -500,7 +570,7 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
     /// It is required that one node has been put into Configuration mode already when this is
     /// called, e.g. using [`lss_activate`](Self::lss_activate)
     pub async fn lss_set_node_id(&mut self, node_id: NodeId) -> Result<(), LssError> {
-        let mut lss = LssMaster::new(self.sender.clone(), self.receiver.create_rx());
+        let mut lss = self.mux.lss_master();
         lss.set_node_id(node_id).await?;
         Ok(())
     }
@@ -510,13 +580,13 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
     /// It is required that one node has been put into Configuration mode already when this is
     /// called, e.g. using [`lss_activate`](Self::lss_activate)
     pub async fn lss_store_config(&mut self) -> Result<(), LssError> {
-        let mut lss = LssMaster::new(self.sender.clone(), self.receiver.create_rx());
+        let mut lss = self.mux.lss_master();
         lss.store_config().await
     }
 
     /// Send a command to put all devices into the specified LSS state
     pub async fn lss_set_global_mode(&mut self, mode: LssState) {
-        let mut lss = LssMaster::new(self.sender.clone(), self.receiver.create_rx());
+        let mut lss = self.mux.lss_master();
         lss.set_global_mode(mode).await;
     }
 
@@ -555,11 +625,49 @@ impl<S: AsyncCanSender + Sync + Send> BusManager<S> {
         self.sender.send(sync_obj.into()).await.ok();
     }
 
+    /// Send an application-defined command to all nodes on the vendor broadcast channel
+    ///
+    /// id - The 29-bit extended CAN ID which the nodes are configured to listen to
+    pub async fn vendor_broadcast(&mut self, id: u32, cmd: VendorBroadcast) {
+        self.sender
+            .send(cmd.to_can_message(CanId::extended(id)))
+            .await
+            .ok();
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "nmt_command", skip(self))
+    )]
     async fn send_nmt_cmd(&mut self, cmd: NmtCommandSpecifier, node: u8) {
         let message = NmtCommand { cs: cmd, node };
         self.sender.send(message.into()).await.ok();
     }
 
+    /// Subscribe to messages with a specific ID
+    ///
+    /// Returns a [`Stream`](futures::Stream) of the received messages with ID `cob_id`. See
+    /// [`SharedReceiver::subscribe`].
+    pub fn subscribe(&self, cob_id: CanId) -> Subscription {
+        self.receiver.subscribe(cob_id)
+    }
+
+    /// Subscribe to a TPDO of a node
+    ///
+    /// The COB ID of the TPDO is read from the node, so if the TPDO is later reconfigured to use
+    /// another COB ID, the subscription must be renewed.
+    ///
+    /// - `node`: The node ID
+    /// - `tpdo`: The TPDO number, starting from 0
+    pub async fn subscribe_tpdo(
+        &self,
+        node: ConfiguredNodeId,
+        tpdo: usize,
+    ) -> Result<Subscription, SdoClientError> {
+        let config = self.sdo_client(node.raw()).read_tpdo_config(tpdo).await?;
+        Ok(self.subscribe(config.cob_id))
+    }
+
     /// Read the RPDO and TPDO configuration for the specified node
     ///
     /// node - The node ID to read from
@@ -0,0 +1,126 @@
+//! Sharing a single CAN interface among several protocol clients
+use std::sync::Arc;
+
+use zencan_common::{
+    messages::{ConnectionSet, LSS_RESP_ID},
+    traits::{AsyncCanReceiver, AsyncCanSender},
+    CanId,
+};
+
+use super::shared_receiver::{MessageFilter, SharedReceiver, SharedReceiverChannel, Subscription};
+use super::shared_sender::SharedSender;
+use crate::{nmt_master::NmtMaster, LssMaster, SdoClient};
+
+/// Owns a CAN interface, and hands out virtual senders and receivers for it
+///
+/// Protocol clients such as [`SdoClient`], [`NmtMaster`] and [`LssMaster`] each expect to own a
+/// sender and receiver. Creating them from a `BusMultiplexer` lets them run at the same time on one
+/// interface, e.g. one socketcan socket, without stealing each other's messages. Each client's
+/// receiver is filtered to the messages for that client, so a client which is not being polled
+/// does not fill its queue with other traffic.
+///
+/// Application code can receive messages from the bus with [`receiver`](Self::receiver) or
+/// [`subscribe`](Self::subscribe), and send them with [`sender`](Self::sender).
+///
+/// The multiplexer must be created from within a tokio runtime. The interface is read by a
+/// background task, which runs until the multiplexer and all of the receivers created from it are
+/// dropped.
+///
+/// ```no_run
+/// # async fn example(sender: impl zencan_common::traits::AsyncCanSender + 'static,
+/// #     receiver: impl zencan_common::traits::AsyncCanReceiver + 'static) {
+/// use zencan_client::BusMultiplexer;
+///
+/// let mux = BusMultiplexer::new(sender, receiver);
+/// let mut nmt = mux.nmt_master();
+/// let mut client = mux.sdo_client(1);
+///
+/// nmt.nmt_start(0).await.unwrap();
+/// let device_type = client.read_u32(0x1000, 0).await.unwrap();
+/// let nodes = nmt.get_nodes();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BusMultiplexer<S: AsyncCanSender> {
+    sender: SharedSender<S>,
+    receiver: SharedReceiver,
+}
+
+impl<S: AsyncCanSender> Clone for BusMultiplexer<S> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+impl<S: AsyncCanSender> BusMultiplexer<S> {
+    /// Create a new multiplexer for a CAN interface
+    ///
+    /// # Arguments
+    /// - `sender`: An object which implements [`AsyncCanSender`] to be used for sending messages to
+    ///   the bus
+    /// - `receiver`: An object which implements [`AsyncCanReceiver`] to be used for receiving
+    ///   messages from the bus
+    pub fn new(sender: S, receiver: impl AsyncCanReceiver + 'static) -> Self {
+        Self {
+            sender: SharedSender::new(Arc::new(tokio::sync::Mutex::new(sender))),
+            receiver: SharedReceiver::new(receiver),
+        }
+    }
+
+    /// Get a sender for the interface
+    pub fn sender(&self) -> SharedSender<S> {
+        self.sender.clone()
+    }
+
+    /// Get a receiver for the messages which pass `filter`
+    pub fn receiver(&self, filter: MessageFilter) -> SharedReceiverChannel {
+        self.receiver.create_filtered_rx(filter)
+    }
+
+    /// Get the underlying shared receiver
+    pub fn shared_receiver(&self) -> &SharedReceiver {
+        &self.receiver
+    }
+
+    /// Subscribe to messages with a specific ID
+    ///
+    /// See [`SharedReceiver::subscribe`]
+    pub fn subscribe(&self, cob_id: CanId) -> Subscription {
+        self.receiver.subscribe(cob_id)
+    }
+
+    /// Create an SDO client for a node's default SDO server
+    ///
+    /// The client only receives the responses from the server.
+    pub fn sdo_client(&self, node_id: u8) -> SdoClient<SharedSender<S>, SharedReceiverChannel> {
+        let resp_cob_id = CanId::Std(ConnectionSet::DEFAULT.sdo_response_id(node_id));
+        SdoClient::new_std(
+            node_id,
+            self.sender(),
+            self.receiver(MessageFilter::Id(resp_cob_id)),
+        )
+    }
+
+    /// Create an NMT master
+    ///
+    /// The master only receives heartbeat and boot-up messages.
+    pub fn nmt_master(&self) -> NmtMaster<SharedSender<S>, SharedReceiverChannel> {
+        NmtMaster::new(
+            self.sender(),
+            self.receiver(MessageFilter::StdRange {
+                first: ConnectionSet::DEFAULT.heartbeat_id(1),
+                last: ConnectionSet::DEFAULT.heartbeat_id(127),
+            }),
+        )
+    }
+
+    /// Create an LSS master
+    ///
+    /// The master only receives LSS responses.
+    pub fn lss_master(&self) -> LssMaster<SharedSender<S>, SharedReceiverChannel> {
+        LssMaster::new(self.sender(), self.receiver(MessageFilter::Id(LSS_RESP_ID)))
+    }
+}
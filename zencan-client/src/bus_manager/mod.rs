@@ -1,4 +1,11 @@
 mod bus_manager;
+mod bus_multiplexer;
 mod shared_receiver;
 mod shared_sender;
-pub use bus_manager::BusManager;
+pub(crate) use bus_manager::NodeInfo;
+pub use bus_manager::{BusManager, PdoScanResult};
+pub use bus_multiplexer::BusMultiplexer;
+pub use shared_receiver::{
+    MessageFilter, NoMsgError, SharedReceiver, SharedReceiverChannel, Subscription,
+};
+pub use shared_sender::SharedSender;
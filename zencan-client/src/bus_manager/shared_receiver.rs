@@ -1,32 +1,118 @@
 //! Utility for sharing a single socket among tasks
 //!
 
+use std::pin::Pin;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::task::{Context, Poll};
 
 use tokio::select;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio_util::sync::CancellationToken;
 use tokio_util::sync::DropGuard;
-use zencan_common::{traits::AsyncCanReceiver, CanMessage};
+use zencan_common::{traits::AsyncCanReceiver, CanId, CanMessage};
 
+use crate::telemetry;
+
+/// Error returned by [`SharedReceiverChannel`] when the shared receiver has stopped
 #[derive(Clone, Copy, Debug)]
 pub struct NoMsgError;
 
+/// The number of messages which can be queued for each channel before messages are dropped
+const CHANNEL_CAPACITY: usize = 100;
+
+/// Selects which messages are delivered to a receiver channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageFilter {
+    /// Every message
+    All,
+    /// Messages with a single ID
+    Id(CanId),
+    /// Messages with any of the listed IDs
+    Ids(Vec<CanId>),
+    /// Messages with a standard ID in the inclusive range `first..=last`, e.g. the heartbeats of all
+    /// nodes
+    StdRange {
+        /// The first ID accepted
+        first: u16,
+        /// The last ID accepted
+        last: u16,
+    },
+}
+
+impl MessageFilter {
+    /// Returns true if a message with ID `id` passes the filter
+    pub fn matches(&self, id: CanId) -> bool {
+        match self {
+            MessageFilter::All => true,
+            MessageFilter::Id(filter_id) => *filter_id == id,
+            MessageFilter::Ids(ids) => ids.contains(&id),
+            MessageFilter::StdRange { first, last } => match id {
+                CanId::Std(id) => (*first..=*last).contains(&id),
+                CanId::Extended(_) => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    /// Only messages which pass the filter are sent to the channel
+    filter: MessageFilter,
+    sender: Sender<CanMessage>,
+}
+
 #[derive(Debug)]
 struct SharedRecieiverInner {
-    senders: Vec<Sender<CanMessage>>,
+    channels: Vec<Channel>,
 }
 
 impl SharedRecieiverInner {
-    pub fn create_rx(&mut self) -> Receiver<CanMessage> {
-        let (tx, rx) = channel(100);
-        self.senders.push(tx);
+    pub fn create_rx(&mut self, filter: MessageFilter) -> Receiver<CanMessage> {
+        let (sender, rx) = channel(CHANNEL_CAPACITY);
+        self.channels.push(Channel { filter, sender });
         rx
     }
 }
 
+/// Shares a single CAN receiver among multiple consumers
+///
+/// Every message received is copied to each consumer, so that consumers can not steal each other's
+/// messages. Consumers are created with [`create_rx`](Self::create_rx), which receives every
+/// message, e.g. for an [`SdoClient`](crate::SdoClient), with
+/// [`create_filtered_rx`](Self::create_filtered_rx), which receives only the messages passing a
+/// [`MessageFilter`], or with [`subscribe`](Self::subscribe), which receives only messages with a
+/// particular ID as a [`Stream`](futures::Stream).
+///
+/// [`BusMultiplexer`](crate::BusMultiplexer) combines a `SharedReceiver` with a shared sender, and
+/// creates protocol clients which receive only their own messages.
+///
+/// Each consumer has its own queue of up to 100 messages. When a consumer falls behind and its
+/// queue is full, messages are dropped for that consumer only.
+///
+/// The receiver is read by a background task, which runs until the `SharedReceiver` and all of its
+/// clones are dropped. It must be created from within a tokio runtime.
+///
+/// ```no_run
+/// # async fn example(sender: impl zencan_common::traits::AsyncCanSender,
+/// #     receiver: impl zencan_common::traits::AsyncCanReceiver + 'static) {
+/// use futures::StreamExt;
+/// use zencan_client::{SdoClient, SharedReceiver};
+/// use zencan_common::CanId;
+///
+/// let shared = SharedReceiver::new(receiver);
+/// let mut client = SdoClient::new_std(1, sender, shared.create_rx());
+/// let mut tpdos = shared.subscribe(CanId::std(0x181));
+///
+/// tokio::spawn(async move {
+///     while let Some(msg) = tpdos.next().await {
+///         println!("TPDO: {:?}", msg.data());
+///     }
+/// });
+/// let device_type = client.read_u32(0x1000, 0).await;
+/// # }
+/// ```
 #[derive(Clone, Debug)]
 pub struct SharedReceiver {
     _cancellation_guard: Arc<DropGuard>,
@@ -34,9 +120,10 @@ pub struct SharedReceiver {
 }
 
 impl SharedReceiver {
+    /// Create a new shared receiver, and start the task which reads `receiver`
     pub fn new<R: AsyncCanReceiver + Send + 'static>(mut receiver: R) -> Self {
         let inner = Arc::new(Mutex::new(SharedRecieiverInner {
-            senders: Vec::new(),
+            channels: Vec::new(),
         }));
         let cancellation = CancellationToken::new();
         let cancellation_guard = Arc::new(cancellation.clone().drop_guard());
@@ -46,12 +133,19 @@ impl SharedReceiver {
                 select! {
                     result = receiver.recv() => {
                         if let Ok(msg) = result {
+                            telemetry::frame_received(&msg);
                             let mut inner = inner_clone.lock().unwrap();
-                            inner.senders.retain(|sender| {
-                                if let Err(e) = sender.try_send(msg) {
+                            inner.channels.retain(|channel| {
+                                if !channel.filter.matches(msg.id()) {
+                                    // Check that the subscriber still exists, so that it is
+                                    // removed even if it never receives a message
+                                    return !channel.sender.is_closed();
+                                }
+                                if let Err(e) = channel.sender.try_send(msg) {
                                     return match e {
                                         TrySendError::Full(_) => {
                                             log::warn!("Dropped received message due to overflow");
+                                            telemetry::frame_dropped();
                                             true
                                         }
                                         TrySendError::Closed(_) => false,
@@ -75,36 +169,66 @@ impl SharedReceiver {
         }
     }
 
+    /// Create a new channel, which receives every message
     pub fn create_rx(&self) -> SharedReceiverChannel {
-        let rx = self.inner.lock().unwrap().create_rx();
+        self.create_filtered_rx(MessageFilter::All)
+    }
+
+    /// Create a new channel, which receives the messages which pass `filter`
+    pub fn create_filtered_rx(&self, filter: MessageFilter) -> SharedReceiverChannel {
+        let rx = self.inner.lock().unwrap().create_rx(filter.clone());
 
         SharedReceiverChannel {
             inner: self.inner.clone(),
+            filter,
+            receiver: rx,
+        }
+    }
+
+    /// Subscribe to messages with a specific ID
+    ///
+    /// Returns a [`Stream`](futures::Stream) of the messages received with ID `cob_id`, e.g. a
+    /// TPDO from a node. The subscription ends when it is dropped.
+    pub fn subscribe(&self, cob_id: CanId) -> Subscription {
+        let rx = self
+            .inner
+            .lock()
+            .unwrap()
+            .create_rx(MessageFilter::Id(cob_id));
+        Subscription {
+            cob_id,
             receiver: rx,
         }
     }
 
     /// Get the number of current receiver channels
     #[allow(dead_code)]
-    pub fn num_channels(&self) -> usize {
+    pub(crate) fn num_channels(&self) -> usize {
         let inner = self.inner.lock().unwrap();
-        inner.senders.len()
+        inner.channels.len()
     }
 }
 
+/// A channel from a [`SharedReceiver`]
+///
+/// Cloning the channel creates a new channel with the same filter, which receives messages from the
+/// time it is created.
 #[derive(Debug)]
 pub struct SharedReceiverChannel {
     /// Data shared with the multi consumer Rx
     inner: Arc<Mutex<SharedRecieiverInner>>,
+    /// The filter selecting the messages for this channel
+    filter: MessageFilter,
     /// Our receive channel
     receiver: Receiver<CanMessage>,
 }
 
 impl Clone for SharedReceiverChannel {
     fn clone(&self) -> Self {
-        let receiver = self.inner.lock().unwrap().create_rx();
+        let receiver = self.inner.lock().unwrap().create_rx(self.filter.clone());
         Self {
             inner: self.inner.clone(),
+            filter: self.filter.clone(),
             receiver,
         }
     }
@@ -112,16 +236,23 @@ impl Clone for SharedReceiverChannel {
 
 #[allow(dead_code)]
 impl SharedReceiverChannel {
+    /// Get the filter selecting the messages for this channel
+    pub fn filter(&self) -> &MessageFilter {
+        &self.filter
+    }
+
     /// Remove any pending messages from the queue
     pub fn flush(&mut self) {
         // Clear our queue
         while let Ok(_msg) = self.receiver.try_recv() {}
     }
 
+    /// Wait for the next message
     pub async fn recv(&mut self) -> Result<CanMessage, NoMsgError> {
         self.receiver.recv().await.ok_or(NoMsgError)
     }
 
+    /// Read the next message if one is queued
     pub fn try_recv(&mut self) -> Option<CanMessage> {
         self.receiver.try_recv().ok()
     }
@@ -139,6 +270,43 @@ impl AsyncCanReceiver for SharedReceiverChannel {
     }
 }
 
+/// A stream of the messages received with a particular ID
+///
+/// Created by [`SharedReceiver::subscribe`] or [`BusManager::subscribe`](crate::BusManager::subscribe).
+/// The stream ends if the shared receiver stops.
+#[derive(Debug)]
+pub struct Subscription {
+    cob_id: CanId,
+    receiver: Receiver<CanMessage>,
+}
+
+impl Subscription {
+    /// Get the ID of the subscribed messages
+    pub fn cob_id(&self) -> CanId {
+        self.cob_id
+    }
+
+    /// Wait for the next message
+    ///
+    /// Returns None if the shared receiver has stopped
+    pub async fn recv(&mut self) -> Option<CanMessage> {
+        self.receiver.recv().await
+    }
+
+    /// Read the next message if one is queued
+    pub fn try_recv(&mut self) -> Option<CanMessage> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl futures::Stream for Subscription {
+    type Item = CanMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -183,7 +351,7 @@ mod tests {
         let mut channel_b = shared_receiver.create_rx();
 
         let msg100 = CanMessage::new(CanId::std(100), &[0, 1, 2, 3]);
-        chan_tx.send(msg100.clone()).await.unwrap();
+        chan_tx.send(msg100).await.unwrap();
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         assert_eq!(Some(msg100), channel_a.try_recv());
@@ -194,10 +362,92 @@ mod tests {
         // Drop a channel, and make sure the num channels goes down after message is processed
         drop(channel_a);
 
-        chan_tx.send(msg100.clone()).await.unwrap();
+        chan_tx.send(msg100).await.unwrap();
         tokio::time::sleep(Duration::from_millis(100)).await;
         assert_eq!(msg100, channel_b.recv().await.unwrap());
 
         assert_eq!(1, shared_receiver.num_channels());
     }
+
+    #[test]
+    fn test_message_filter() {
+        let heartbeats = MessageFilter::StdRange {
+            first: 0x701,
+            last: 0x77F,
+        };
+        assert!(heartbeats.matches(CanId::std(0x701)));
+        assert!(heartbeats.matches(CanId::std(0x77F)));
+        assert!(!heartbeats.matches(CanId::std(0x700)));
+        assert!(!heartbeats.matches(CanId::std(0x780)));
+        assert!(!heartbeats.matches(CanId::extended(0x701)));
+
+        let ids = MessageFilter::Ids(vec![CanId::std(0x181), CanId::extended(0x181)]);
+        assert!(ids.matches(CanId::std(0x181)));
+        assert!(ids.matches(CanId::extended(0x181)));
+        assert!(!ids.matches(CanId::std(0x182)));
+
+        assert!(MessageFilter::All.matches(CanId::extended(0x1234)));
+        assert!(!MessageFilter::Id(CanId::std(1)).matches(CanId::std(2)));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_rx() {
+        let (chan_tx, chan_rx) = channel(8);
+        let shared_receiver = SharedReceiver::new(MockReceiver::new(chan_rx));
+
+        let mut sdo = shared_receiver.create_filtered_rx(MessageFilter::Id(CanId::std(0x581)));
+        // A clone has the same filter
+        let mut sdo_clone = sdo.clone();
+        assert_eq!(&MessageFilter::Id(CanId::std(0x581)), sdo_clone.filter());
+
+        let msg581 = CanMessage::new(CanId::std(0x581), &[1]);
+        let msg701 = CanMessage::new(CanId::std(0x701), &[0]);
+        for msg in [msg701, msg581, msg701] {
+            chan_tx.send(msg).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(Some(msg581), sdo.try_recv());
+        assert_eq!(None, sdo.try_recv());
+        assert_eq!(Some(msg581), sdo_clone.try_recv());
+        assert_eq!(None, sdo_clone.try_recv());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        use futures::StreamExt;
+
+        let (chan_tx, chan_rx) = channel(8);
+        let shared_receiver = SharedReceiver::new(MockReceiver::new(chan_rx));
+
+        let mut all = shared_receiver.create_rx();
+        let mut sub_181 = shared_receiver.subscribe(CanId::std(0x181));
+        let mut sub_281 = shared_receiver.subscribe(CanId::std(0x281));
+        assert_eq!(CanId::std(0x181), sub_181.cob_id());
+
+        let msg181 = CanMessage::new(CanId::std(0x181), &[1]);
+        let msg281 = CanMessage::new(CanId::std(0x281), &[2]);
+        let msg_ext = CanMessage::new(CanId::extended(0x181), &[3]);
+        for msg in [msg181, msg281, msg_ext, msg181] {
+            chan_tx.send(msg).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Subscribers each get their own messages, and the unfiltered channel gets all of them
+        assert_eq!(Some(msg181), sub_181.next().await);
+        assert_eq!(Some(msg181), sub_181.next().await);
+        assert_eq!(None, sub_181.try_recv());
+        assert_eq!(Some(msg281), sub_281.recv().await);
+        assert_eq!(None, sub_281.try_recv());
+        for msg in [msg181, msg281, msg_ext, msg181] {
+            assert_eq!(Some(msg), all.try_recv());
+        }
+
+        // A dropped subscription is removed on the next message, even if it doesn't match
+        drop(sub_281);
+        chan_tx.send(msg181).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(2, shared_receiver.num_channels());
+        assert_eq!(Some(msg181), sub_181.next().await);
+    }
 }
@@ -4,6 +4,11 @@ use tokio::sync::Mutex;
 
 use zencan_common::{traits::AsyncCanSender, CanMessage};
 
+use crate::telemetry;
+
+/// A CAN sender which can be cloned, so that several clients can send on one interface
+///
+/// Messages from each clone are sent in the order they are sent by that clone.
 #[derive(Debug)]
 pub struct SharedSender<S: AsyncCanSender> {
     inner: Arc<Mutex<S>>,
@@ -18,13 +23,16 @@ impl<S: AsyncCanSender> Clone for SharedSender<S> {
 }
 
 impl<S: AsyncCanSender> SharedSender<S> {
+    /// Create a new shared sender from a sender behind a mutex
     pub fn new(sender: Arc<Mutex<S>>) -> Self {
         Self { inner: sender }
     }
 
     async fn send(&mut self, msg: CanMessage) -> Result<(), S::Error> {
         let mut inner = self.inner.lock().await;
-        inner.send(msg).await
+        inner.send(msg).await?;
+        telemetry::frame_sent(&msg);
+        Ok(())
     }
 }
 
@@ -0,0 +1,545 @@
+//! CAN over UDP or TCP, compatible with cannelloni
+//!
+//! [cannelloni](https://github.com/mguentner/cannelloni) tunnels the frames of a socketcan interface
+//! over UDP or TCP. Running it on an embedded Linux gateway which is attached to a bus allows the
+//! client to operate on that bus from another machine, without first creating a local virtual CAN
+//! interface to forward it to.
+//!
+//! With UDP, both ends send to a fixed address. The gateway must be configured to send to the
+//! address which the client binds, e.g. for a gateway at 192.168.1.10 and a client at
+//! 192.168.1.20, both using the default port of 20000:
+//!
+//! ```text
+//! cannelloni -I can0 -R 192.168.1.20 -r 20000 -l 20000
+//! ```
+//!
+//! ```ignore
+//! let (tx, rx) = open_cannelloni_udp("0.0.0.0:20000", "192.168.1.10:20000").await?;
+//! let manager = BusManager::new(tx, rx);
+//! ```
+//!
+//! With TCP, the gateway acts as the server (`cannelloni -I can0 -C s -l 20000`), and the client
+//! connects to it with [`open_cannelloni_tcp`].
+//!
+//! Frames are received by a background task, so these functions must be called from within a tokio
+//! runtime. The task ends when the receiver is dropped. Error frames and CAN FD frames with more
+//! than 8 data bytes are discarded.
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use snafu::{ResultExt, Snafu};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{tcp::OwnedWriteHalf, TcpStream, ToSocketAddrs, UdpSocket},
+    select,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use zencan_common::{
+    messages::CanId,
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+/// The default port used by cannelloni
+pub const DEFAULT_PORT: u16 = 20000;
+
+/// The protocol version sent in the header of each UDP packet
+const UDP_VERSION: u8 = 2;
+/// The op code of a UDP packet carrying frames
+const OP_DATA: u8 = 0;
+/// The size of the header of a UDP packet
+const UDP_HEADER_LEN: usize = 5;
+/// The string exchanged by both ends when a TCP connection is established
+const TCP_HANDSHAKE: &[u8] = b"CANNELLONIv1";
+
+/// Flags which cannelloni sets in the ID field, as in the Linux `can_id`
+const EFF_FLAG: u32 = 0x8000_0000;
+const RTR_FLAG: u32 = 0x4000_0000;
+const ERR_FLAG: u32 = 0x2000_0000;
+/// Set in the length field of CAN FD frames, which are followed by a flags byte
+const FD_FRAME_FLAG: u8 = 0x80;
+/// The longest data field of a CAN FD frame
+const MAX_FD_LEN: usize = 64;
+
+/// The size of the fixed part of an encoded frame: the ID and the length
+const FRAME_HEADER_LEN: usize = 5;
+
+/// Size of the channel between the receive task and the receiver
+const RX_CHANNEL_SIZE: usize = 100;
+
+/// Error returned by a [`CannelloniReceiver`] or when opening a connection
+#[derive(Debug, Snafu)]
+pub enum CannelloniError {
+    /// An IO error on the socket
+    #[snafu(display("IO error: {source}"))]
+    Io {
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Received data could not be decoded
+    #[snafu(display("Invalid data received: {message}"))]
+    InvalidData {
+        /// A description of the problem
+        message: String,
+    },
+    /// The TCP server did not respond with the expected handshake
+    #[snafu(display("Invalid handshake received from server"))]
+    Handshake,
+    /// The connection has been closed, or the receive task has ended after an error
+    #[snafu(display("Connection closed"))]
+    Closed,
+}
+
+/// Error returned by [`CannelloniSender`]
+#[derive(Debug, Snafu)]
+#[snafu(display("Error sending {message:?}: {source}"))]
+pub struct CannelloniSendError {
+    source: std::io::Error,
+    message: CanMessage,
+}
+
+impl CanSendError for CannelloniSendError {
+    fn into_can_message(self) -> CanMessage {
+        self.message
+    }
+
+    fn message(&self) -> String {
+        self.source.to_string()
+    }
+}
+
+/// The decoded ID and length fields at the start of an encoded frame
+#[derive(Clone, Copy, Debug)]
+struct FrameHeader {
+    raw_id: u32,
+    len: u8,
+}
+
+impl FrameHeader {
+    fn parse(bytes: [u8; FRAME_HEADER_LEN]) -> Self {
+        Self {
+            raw_id: u32::from_be_bytes(bytes[0..4].try_into().unwrap()),
+            len: bytes[4],
+        }
+    }
+
+    fn is_fd(&self) -> bool {
+        self.len & FD_FRAME_FLAG != 0
+    }
+
+    fn data_len(&self) -> usize {
+        (self.len & !FD_FRAME_FLAG) as usize
+    }
+
+    /// The number of bytes following the header: the flags byte of an FD frame, and the data
+    ///
+    /// Remote frames have a length, but no data.
+    fn body_len(&self) -> Result<usize, CannelloniError> {
+        if self.is_fd() {
+            if self.data_len() > MAX_FD_LEN {
+                return InvalidDataSnafu {
+                    message: format!("FD frame length {} too long", self.data_len()),
+                }
+                .fail();
+            }
+            Ok(1 + self.data_len())
+        } else if self.raw_id & RTR_FLAG != 0 {
+            Ok(0)
+        } else {
+            // Classic frames may give a DLC up to 15, but never carry more than 8 bytes
+            Ok(self.data_len().min(8))
+        }
+    }
+
+    /// Convert the frame to a message
+    ///
+    /// Returns None for frames which can't be represented by a [`CanMessage`]
+    fn into_message(self, body: &[u8]) -> Option<CanMessage> {
+        if self.raw_id & ERR_FLAG != 0 {
+            return None;
+        }
+        let id = if self.raw_id & EFF_FLAG != 0 {
+            CanId::extended(self.raw_id & 0x1FFF_FFFF)
+        } else {
+            CanId::std((self.raw_id & 0x7FF) as u16)
+        };
+        let data = if self.is_fd() { &body[1..] } else { body };
+        if self.raw_id & RTR_FLAG != 0 {
+            Some(CanMessage::new_rtr(id))
+        } else if data.len() > 8 {
+            None
+        } else {
+            Some(CanMessage::new(id, data))
+        }
+    }
+}
+
+/// Append the encoding of a message to `buf`
+fn encode_frame(msg: &CanMessage, buf: &mut Vec<u8>) {
+    let mut raw_id = msg.id().raw();
+    if msg.id().is_extended() {
+        raw_id |= EFF_FLAG;
+    }
+    if msg.is_rtr() {
+        raw_id |= RTR_FLAG;
+    }
+    buf.extend_from_slice(&raw_id.to_be_bytes());
+    buf.push(msg.data().len() as u8);
+    if !msg.is_rtr() {
+        buf.extend_from_slice(msg.data());
+    }
+}
+
+/// Encode a UDP packet carrying a single message
+fn encode_packet(msg: &CanMessage, seq_no: u8) -> Vec<u8> {
+    let mut buf = vec![UDP_VERSION, OP_DATA, seq_no];
+    buf.extend_from_slice(&1u16.to_be_bytes());
+    encode_frame(msg, &mut buf);
+    buf
+}
+
+/// Decode the messages in a UDP packet
+///
+/// Packets which do not carry frames, e.g. the ACKs sent by some versions of cannelloni, contain
+/// no messages.
+fn decode_packet(packet: &[u8]) -> Result<Vec<CanMessage>, CannelloniError> {
+    if packet.len() < UDP_HEADER_LEN {
+        return InvalidDataSnafu {
+            message: format!("Packet of {} bytes is too short", packet.len()),
+        }
+        .fail();
+    }
+    if packet[0] != UDP_VERSION {
+        return InvalidDataSnafu {
+            message: format!("Unsupported version {}", packet[0]),
+        }
+        .fail();
+    }
+    if packet[1] != OP_DATA {
+        return Ok(Vec::new());
+    }
+    let count = u16::from_be_bytes([packet[3], packet[4]]);
+
+    let mut messages = Vec::with_capacity(count as usize);
+    let mut rest = &packet[UDP_HEADER_LEN..];
+    for _ in 0..count {
+        let truncated = || {
+            InvalidDataSnafu {
+                message: format!("Packet truncated, expected {count} frames"),
+            }
+            .build()
+        };
+        let (header, tail) = rest
+            .split_first_chunk::<FRAME_HEADER_LEN>()
+            .ok_or_else(truncated)?;
+        let header = FrameHeader::parse(*header);
+        let body_len = header.body_len()?;
+        if tail.len() < body_len {
+            return Err(truncated());
+        }
+        let (body, tail) = tail.split_at(body_len);
+        messages.extend(header.into_message(body));
+        rest = tail;
+    }
+    Ok(messages)
+}
+
+/// Read a single frame from a TCP stream
+///
+/// Returns None for frames which can't be represented by a [`CanMessage`]
+async fn read_frame(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<CanMessage>, CannelloniError> {
+    let mut header = [0; FRAME_HEADER_LEN];
+    stream.read_exact(&mut header).await.context(IoSnafu)?;
+    let header = FrameHeader::parse(header);
+    let mut body = [0; 1 + MAX_FD_LEN];
+    let body = &mut body[..header.body_len()?];
+    stream.read_exact(body).await.context(IoSnafu)?;
+    Ok(header.into_message(body))
+}
+
+#[derive(Debug)]
+enum Transport {
+    Udp {
+        socket: Arc<UdpSocket>,
+        seq_no: Arc<AtomicU8>,
+    },
+    Tcp(Arc<tokio::sync::Mutex<OwnedWriteHalf>>),
+}
+
+/// The sending half of a cannelloni connection, implementing [`AsyncCanSender`]
+#[derive(Debug, Clone)]
+pub struct CannelloniSender {
+    transport: Arc<Transport>,
+}
+
+impl AsyncCanSender for CannelloniSender {
+    type Error = CannelloniSendError;
+
+    async fn send(&mut self, msg: CanMessage) -> Result<(), Self::Error> {
+        match self.transport.as_ref() {
+            Transport::Udp { socket, seq_no } => {
+                let packet = encode_packet(&msg, seq_no.fetch_add(1, Ordering::Relaxed));
+                socket.send(&packet).await.map(|_| ())
+            }
+            Transport::Tcp(stream) => {
+                let mut buf = Vec::new();
+                encode_frame(&msg, &mut buf);
+                stream.lock().await.write_all(&buf).await
+            }
+        }
+        .context(CannelloniSendSnafu { message: msg })
+    }
+}
+
+/// The receiving half of a cannelloni connection, implementing [`AsyncCanReceiver`]
+#[derive(Debug)]
+pub struct CannelloniReceiver {
+    rx: Receiver<Result<CanMessage, CannelloniError>>,
+    _task_guard: DropGuard,
+}
+
+impl CannelloniReceiver {
+    /// Spawn a task running `receive` until the receiver is dropped
+    fn spawn<F>(receive: impl FnOnce(Sender<Result<CanMessage, CannelloniError>>) -> F) -> Self
+    where
+        F: core::future::Future<Output = ()> + Send + 'static,
+    {
+        let (tx, rx) = channel(RX_CHANNEL_SIZE);
+        let cancellation = CancellationToken::new();
+        let task = receive(tx);
+        let task_cancellation = cancellation.clone();
+        tokio::spawn(async move {
+            select! {
+                _ = task => (),
+                _ = task_cancellation.cancelled() => (),
+            }
+        });
+        Self {
+            rx,
+            _task_guard: cancellation.drop_guard(),
+        }
+    }
+}
+
+impl AsyncCanReceiver for CannelloniReceiver {
+    type Error = CannelloniError;
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Ok(msg)) => return Some(msg),
+                // Errors are only reported by recv
+                Ok(Err(_)) => (),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> Result<CanMessage, Self::Error> {
+        self.rx.recv().await.unwrap_or(Err(CannelloniError::Closed))
+    }
+}
+
+/// Open a UDP connection to a cannelloni instance
+///
+/// # Arguments
+/// * `local_addr` - The address to bind, which the remote instance sends to
+/// * `remote_addr` - The address of the remote instance
+///
+/// Only packets from `remote_addr` are received. Malformed packets are logged and ignored.
+pub async fn open_cannelloni_udp(
+    local_addr: impl ToSocketAddrs,
+    remote_addr: impl ToSocketAddrs,
+) -> Result<(CannelloniSender, CannelloniReceiver), CannelloniError> {
+    let socket = UdpSocket::bind(local_addr).await.context(IoSnafu)?;
+    socket.connect(remote_addr).await.context(IoSnafu)?;
+    let socket = Arc::new(socket);
+
+    let rx_socket = socket.clone();
+    let receiver = CannelloniReceiver::spawn(|tx| async move {
+        let mut buf = [0u8; 65536];
+        loop {
+            let messages = match rx_socket.recv(&mut buf).await {
+                Ok(len) => match decode_packet(&buf[..len]) {
+                    Ok(messages) => messages.into_iter().map(Ok).collect(),
+                    Err(e) => {
+                        log::warn!("Ignoring cannelloni packet: {e}");
+                        continue;
+                    }
+                },
+                // On Linux, an ICMP port unreachable reply to a sent packet is reported on the
+                // next receive. The remote instance may not be running yet, so keep going.
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => vec![Err(CannelloniError::Io { source: e })],
+            };
+            for msg in messages {
+                if tx.send(msg).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let sender = CannelloniSender {
+        transport: Arc::new(Transport::Udp {
+            socket,
+            seq_no: Arc::new(AtomicU8::new(0)),
+        }),
+    };
+    Ok((sender, receiver))
+}
+
+/// Open a TCP connection to a cannelloni instance running as a server
+///
+/// Returns an error if the server does not complete the handshake. Once the connection is closed,
+/// or fails, the receiver returns the error and then [`CannelloniError::Closed`].
+pub async fn open_cannelloni_tcp(
+    addr: impl ToSocketAddrs,
+) -> Result<(CannelloniSender, CannelloniReceiver), CannelloniError> {
+    let mut stream = TcpStream::connect(addr).await.context(IoSnafu)?;
+    stream.set_nodelay(true).context(IoSnafu)?;
+    stream.write_all(TCP_HANDSHAKE).await.context(IoSnafu)?;
+    let mut handshake = [0; TCP_HANDSHAKE.len()];
+    stream.read_exact(&mut handshake).await.context(IoSnafu)?;
+    if handshake != TCP_HANDSHAKE {
+        return HandshakeSnafu.fail();
+    }
+
+    let (mut read_half, write_half) = stream.into_split();
+    let receiver = CannelloniReceiver::spawn(|tx| async move {
+        loop {
+            let result = match read_frame(&mut read_half).await {
+                Ok(Some(msg)) => Ok(msg),
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            let end = result.is_err();
+            if tx.send(result).await.is_err() || end {
+                return;
+            }
+        }
+    });
+
+    let sender = CannelloniSender {
+        transport: Arc::new(Transport::Tcp(Arc::new(tokio::sync::Mutex::new(
+            write_half,
+        )))),
+    };
+    Ok((sender, receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let messages = [
+            CanMessage::new(CanId::std(0x601), &[0x40, 0x00, 0x10, 0x00]),
+            CanMessage::new(CanId::extended(0x18FE_F180), &[1, 2, 3, 4, 5, 6, 7, 8]),
+            CanMessage::new_rtr(CanId::std(0x701)),
+            CanMessage::new(CanId::std(0x80), &[]),
+        ];
+
+        let packet = encode_packet(&messages[1], 7);
+        assert_eq!(
+            vec![2, 0, 7, 0, 1, 0x98, 0xFE, 0xF1, 0x80, 8, 1, 2, 3, 4, 5, 6, 7, 8],
+            packet
+        );
+
+        let mut packet = vec![UDP_VERSION, OP_DATA, 0, 0, messages.len() as u8];
+        for msg in &messages {
+            encode_frame(msg, &mut packet);
+        }
+        assert_eq!(messages.to_vec(), decode_packet(&packet).unwrap());
+
+        // A truncated packet is rejected
+        assert!(decode_packet(&packet[..packet.len() - 1]).is_err());
+        // As is an unknown version
+        packet[0] = 1;
+        assert!(decode_packet(&packet).is_err());
+    }
+
+    #[test]
+    fn test_decode_skipped_frames() {
+        let mut packet = vec![UDP_VERSION, OP_DATA, 0, 0, 3];
+        // An error frame
+        packet.extend_from_slice(&[0x20, 0, 0, 0x04, 8, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // An FD frame with 12 bytes
+        packet.extend_from_slice(&[0, 0, 0x01, 0x23, 0x80 | 12, 0x01]);
+        packet.extend_from_slice(&[0xAA; 12]);
+        // An FD frame with 2 bytes
+        packet.extend_from_slice(&[0, 0, 0x01, 0x24, 0x80 | 2, 0x01, 0xBB, 0xCC]);
+        assert_eq!(
+            vec![CanMessage::new(CanId::std(0x124), &[0xBB, 0xCC])],
+            decode_packet(&packet).unwrap()
+        );
+
+        // Packets other than data are ignored
+        assert!(decode_packet(&[UDP_VERSION, 1, 0, 0, 0])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_udp() {
+        let gateway = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (mut tx, mut rx) = open_cannelloni_udp("127.0.0.1:0", gateway.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let msg = CanMessage::new(CanId::std(0x601), &[0x40, 0x00, 0x10, 0x00]);
+        tx.send(msg).await.unwrap();
+        tx.send(msg).await.unwrap();
+        let mut buf = [0; 100];
+        let (len, client_addr) = gateway.recv_from(&mut buf).await.unwrap();
+        assert_eq!(encode_packet(&msg, 0), buf[..len]);
+        let (len, _) = gateway.recv_from(&mut buf).await.unwrap();
+        assert_eq!(encode_packet(&msg, 1), buf[..len]);
+
+        let reply = CanMessage::new(CanId::std(0x581), &[0x4F, 0x00, 0x10, 0x00, 0x01]);
+        gateway
+            .send_to(&encode_packet(&reply, 0), client_addr)
+            .await
+            .unwrap();
+        assert_eq!(reply, rx.recv().await.unwrap());
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let msg = CanMessage::new(CanId::extended(0x1234_5678), &[1, 2, 3]);
+        let reply = CanMessage::new_rtr(CanId::std(0x705));
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut handshake = [0; TCP_HANDSHAKE.len()];
+            stream.read_exact(&mut handshake).await.unwrap();
+            assert_eq!(TCP_HANDSHAKE, handshake);
+            stream.write_all(TCP_HANDSHAKE).await.unwrap();
+
+            assert_eq!(Some(msg), read_frame(&mut stream).await.unwrap());
+            let mut buf = Vec::new();
+            encode_frame(&reply, &mut buf);
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let (mut tx, mut rx) = open_cannelloni_tcp(addr).await.unwrap();
+        tx.send(msg).await.unwrap();
+        assert_eq!(reply, rx.recv().await.unwrap());
+        server.await.unwrap();
+
+        // The server has closed the connection
+        assert!(matches!(rx.recv().await, Err(CannelloniError::Io { .. })));
+        assert!(matches!(rx.recv().await, Err(CannelloniError::Closed)));
+    }
+}
@@ -0,0 +1,220 @@
+//! Time source for protocol timeouts
+//!
+//! The [`SdoClient`](crate::SdoClient), [`Device`](crate::Device) and
+//! [`LssMaster`](crate::LssMaster) get the current time, and wait for timeouts and retry delays,
+//! through a [`Clock`]. By default this is the [`TokioClock`], which uses real time. Tests can
+//! substitute a [`SimClock`], which only advances when told to, so that timeout behavior can be
+//! tested quickly and deterministically.
+//!
+//! ```ignore
+//! let clock = SimClock::new();
+//! let mut client = SdoClient::new_std(1, sender, receiver);
+//! client.set_clock(Arc::new(clock.clone()));
+//!
+//! let (result, _) = tokio::join!(client.upload(0x1000, 0), async {
+//!     // Wait for the client to start waiting for the response, then time it out
+//!     while clock.sleeping() == 0 {
+//!         tokio::task::yield_now().await;
+//!     }
+//!     clock.advance(client_timeout);
+//! });
+//! assert!(matches!(result, Err(SdoClientError::NoResponse)));
+//! ```
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future::BoxFuture;
+use tokio::sync::watch;
+
+/// A source of time
+pub trait Clock: core::fmt::Debug + Send + Sync {
+    /// Get the current time
+    fn now(&self) -> Instant;
+
+    /// Wait until `deadline`
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+
+    /// Wait for `duration`
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleep_until(self.now() + duration)
+    }
+}
+
+/// Run a future until it completes, or until `deadline`
+///
+/// Returns None if the deadline passed first.
+pub(crate) async fn timeout_at<F: Future>(
+    clock: &dyn Clock,
+    deadline: Instant,
+    fut: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        biased;
+        output = fut => Some(output),
+        _ = clock.sleep_until(deadline) => None,
+    }
+}
+
+/// The default [`Clock`], using the tokio timer
+///
+/// Must be used from within a tokio runtime.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+#[derive(Debug)]
+struct SimClockShared {
+    now: watch::Sender<Instant>,
+    sleeping: AtomicUsize,
+}
+
+/// A simulated [`Clock`], which only advances when [`advance()`](Self::advance) is called
+///
+/// Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct SimClock {
+    shared: Arc<SimClockShared>,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimClock {
+    /// Create a clock, starting at the current real time
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(SimClockShared {
+                now: watch::Sender::new(Instant::now()),
+                sleeping: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Move the time forward, waking any sleeps whose deadline has been reached
+    ///
+    /// The woken tasks run the next time they are scheduled, e.g. after the caller yields.
+    pub fn advance(&self, duration: Duration) {
+        self.shared.now.send_modify(|now| *now += duration);
+    }
+
+    /// Get the number of sleeps currently waiting on the clock
+    ///
+    /// This can be used to advance the clock only once the code under test is waiting on it.
+    pub fn sleeping(&self) -> usize {
+        self.shared.sleeping.load(Ordering::Relaxed)
+    }
+}
+
+/// Decrements the sleep count when a sleep completes or is dropped
+struct SleepGuard<'a>(&'a AtomicUsize);
+
+impl Drop for SleepGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Instant {
+        *self.shared.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        let shared = self.shared.clone();
+        Box::pin(async move {
+            let mut now = shared.now.subscribe();
+            shared.sleeping.fetch_add(1, Ordering::Relaxed);
+            let _guard = SleepGuard(&shared.sleeping);
+            // The sender is held by `shared`, so the channel can't close
+            now.wait_for(|now| *now >= deadline).await.ok();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sim_clock() {
+        let clock = SimClock::new();
+        let start = clock.now();
+        let sleep = clock.sleep(Duration::from_secs(10));
+        let wait = timeout_at(
+            &clock,
+            start + Duration::from_secs(5),
+            std::future::pending::<()>(),
+        );
+
+        let (_, timed_out) = tokio::join!(sleep, async {
+            let result = tokio::join!(wait, async {
+                while clock.sleeping() < 2 {
+                    tokio::task::yield_now().await;
+                }
+                clock.advance(Duration::from_secs(5));
+            });
+            // The timeout has elapsed, but the sleep is still waiting
+            assert_eq!(1, clock.sleeping());
+            clock.advance(Duration::from_secs(5));
+            result.0
+        });
+        assert_eq!(None, timed_out);
+        assert_eq!(0, clock.sleeping());
+        assert_eq!(start + Duration::from_secs(10), clock.now());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_completes() {
+        let clock = SimClock::new();
+        let deadline = clock.now() + Duration::from_secs(1);
+        assert_eq!(Some(5), timeout_at(&clock, deadline, async { 5 }).await);
+        // A future which is ready wins even if the deadline has passed
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(Some(5), timeout_at(&clock, deadline, async { 5 }).await);
+        assert_eq!(0, clock.sleeping());
+    }
+
+    #[tokio::test]
+    async fn test_sdo_client_timeout() {
+        use crate::{transport::sim::open_sim, SdoClient, SdoClientError};
+
+        // There is no server on the bus, so the request is never answered
+        let (sender, receiver) = open_sim("test_sdo_client_timeout");
+        let mut client = SdoClient::new_std(1, sender, receiver);
+        let clock = SimClock::new();
+        client.set_clock(Arc::new(clock.clone()));
+        client.set_timeout(Duration::from_secs(3600));
+
+        let real_start = Instant::now();
+        let (result, _) = tokio::join!(client.upload(0x1000, 0), async {
+            while clock.sleeping() == 0 {
+                tokio::task::yield_now().await;
+            }
+            clock.advance(Duration::from_secs(3599));
+            tokio::task::yield_now().await;
+            // Still waiting for the response
+            assert_eq!(1, clock.sleeping());
+            clock.advance(Duration::from_secs(1));
+        });
+        assert!(matches!(result, Err(SdoClientError::NoResponse)));
+        assert!(real_start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,859 @@
+//! Applying device configuration files (DCF) to nodes
+//!
+//! A DCF is an EDS file describing a particular node, with a `ParameterValue` for each sub object
+//! which has been configured, as exported by many vendor configuration tools. A [`Dcf`] holds the
+//! configured values, which can be written to a node with [`Dcf::apply()`].
+//!
+//! ```ignore
+//! let dcf = Dcf::load("node5.dcf")?;
+//! let report = dcf
+//!     .apply(&mut client, &ApplyOptions::default(), |p| {
+//!         println!("{}/{} {:04X}sub{}", p.completed, p.total, p.index, p.sub)
+//!     })
+//!     .await;
+//! for failure in &report.failures {
+//!     println!("{failure}");
+//! }
+//! ```
+//!
+//! Values are parsed according to the `DataType` of their sub object, from decimal, hexadecimal
+//! (`0x` prefix) or octal (`0` prefix) integers, floats, or strings. Integer values may use the
+//! `$NODEID` variable, e.g. `$NODEID+0x180`. Octet strings are given as hex bytes. Domain values,
+//! and compact array objects, are not supported.
+//!
+//! Only sub objects which are writable according to their `AccessType` are written.
+//!
+//! A DCF can also be converted to a [`ConciseDcf`], to be stored in a CiA 302 configuration manager
+//! which configures the node when it boots.
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use ini::{Ini, Properties};
+use snafu::{ResultExt, Snafu};
+use zencan_common::{
+    concise_dcf::{self, ConciseDcfError},
+    constants::object_ids,
+    objects::DataType,
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+use crate::{
+    backup::{is_pdo_cob_id, is_pdo_mapping_count, PDO_NOT_VALID},
+    SdoClient, SdoClientError,
+};
+
+/// Error returned when loading or applying a DCF
+#[derive(Debug, Snafu)]
+pub enum DcfError {
+    /// The file could not be read
+    #[snafu(display("IO error: {source}"))]
+    Io {
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// The file is not a valid INI file
+    #[snafu(display("Invalid DCF file: {message}"))]
+    InvalidFile {
+        /// Description of the problem
+        message: String,
+    },
+    /// An object section is not valid
+    #[snafu(display("Invalid DCF section [{section}]: {message}"))]
+    InvalidSection {
+        /// The name of the section
+        section: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// A parameter value could not be converted to the data type of its sub object
+    #[snafu(display("Invalid value '{value}' for {index:04X}sub{sub}: {message}"))]
+    InvalidValue {
+        /// The object index
+        index: u16,
+        /// The sub index
+        sub: u8,
+        /// The value from the DCF
+        value: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// A parameter value could not be written to the node
+    #[snafu(display("Failed to write {index:04X}sub{sub}: {source}"))]
+    Write {
+        /// The object index
+        index: u16,
+        /// The sub index
+        sub: u8,
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// A value could not be read from the node
+    #[snafu(display("Failed to read {index:04X}sub{sub}: {source}"))]
+    Read {
+        /// The object index
+        index: u16,
+        /// The sub index
+        sub: u8,
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// A concise DCF could not be decoded
+    #[snafu(display("Invalid concise DCF: {source}"))]
+    InvalidConcise {
+        /// The decoding error
+        source: ConciseDcfError,
+    },
+    /// The save command failed after applying the parameter values
+    #[snafu(display("Failed to save objects: {source}"))]
+    Save {
+        /// The SDO client error
+        source: SdoClientError,
+    },
+}
+
+/// A configured value for a single sub object in a [`Dcf`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DcfEntry {
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// The name of the sub object
+    pub parameter_name: String,
+    /// The data type of the sub object
+    pub data_type: DataType,
+    /// The value, as written in the DCF
+    pub value: String,
+}
+
+impl DcfEntry {
+    /// Convert the value to the bytes to be written to the sub object
+    ///
+    /// `node_id` is substituted for `$NODEID` in integer values. If it is None, values which use
+    /// `$NODEID` are invalid.
+    pub fn encode(&self, node_id: Option<u8>) -> Result<Vec<u8>, DcfError> {
+        let invalid = |message: &str| DcfError::InvalidValue {
+            index: self.index,
+            sub: self.sub,
+            value: self.value.clone(),
+            message: message.to_string(),
+        };
+
+        let int_size = match self.data_type {
+            DataType::Boolean | DataType::Int8 | DataType::UInt8 => Some(1),
+            DataType::Int16 | DataType::UInt16 => Some(2),
+            DataType::Int24 | DataType::UInt24 => Some(3),
+            DataType::Int32 | DataType::UInt32 => Some(4),
+            DataType::Int64 | DataType::UInt64 => Some(8),
+            _ => None,
+        };
+        if let Some(size) = int_size {
+            let value = parse_integer(&self.value, node_id).map_err(invalid)?;
+            let bits = size as u32 * 8;
+            let signed = matches!(
+                self.data_type,
+                DataType::Int8
+                    | DataType::Int16
+                    | DataType::Int24
+                    | DataType::Int32
+                    | DataType::Int64
+            );
+            let (min, max) = match self.data_type {
+                DataType::Boolean => (0, 1),
+                _ if signed => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+                _ => (0, (1i128 << bits) - 1),
+            };
+            if value < min || value > max {
+                return Err(invalid("out of range for the data type"));
+            }
+            return Ok(value.to_le_bytes()[..size].to_vec());
+        }
+
+        match self.data_type {
+            DataType::Real32 => self
+                .value
+                .trim()
+                .parse::<f32>()
+                .map(|v| v.to_le_bytes().to_vec())
+                .map_err(|_| invalid("not a number")),
+            DataType::Real64 => self
+                .value
+                .trim()
+                .parse::<f64>()
+                .map(|v| v.to_le_bytes().to_vec())
+                .map_err(|_| invalid("not a number")),
+            DataType::VisibleString => Ok(self.value.as_bytes().to_vec()),
+            DataType::UnicodeString => Ok(self
+                .value
+                .encode_utf16()
+                .flat_map(|c| c.to_le_bytes())
+                .collect()),
+            DataType::OctetString => {
+                let hex: String = self.value.split_whitespace().collect();
+                let hex = hex.strip_prefix("0x").unwrap_or(&hex);
+                if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+                    return Err(invalid("octet strings must be hex bytes"));
+                }
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| invalid("octet strings must be hex bytes"))
+            }
+            _ => Err(invalid("the data type is not supported")),
+        }
+    }
+}
+
+/// Parse an integer value, which may be the sum of several terms including `$NODEID`
+pub(crate) fn parse_integer(s: &str, node_id: Option<u8>) -> Result<i128, &'static str> {
+    let mut total = 0i128;
+    for term in s.split('+') {
+        let term = term.trim();
+        let value = if term.eq_ignore_ascii_case("$NODEID") {
+            node_id.ok_or("$NODEID is used, but the node ID is not known")? as i128
+        } else {
+            let (negative, digits) = match term.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, term),
+            };
+            let value = if let Some(hex) = digits
+                .strip_prefix("0x")
+                .or_else(|| digits.strip_prefix("0X"))
+            {
+                i128::from_str_radix(hex, 16)
+            } else if digits.len() > 1 && digits.starts_with('0') {
+                i128::from_str_radix(&digits[1..], 8)
+            } else {
+                digits.parse::<i128>()
+            }
+            .map_err(|_| "not an integer")?;
+            if negative {
+                -value
+            } else {
+                value
+            }
+        };
+        total = total.checked_add(value).ok_or("not an integer")?;
+    }
+    Ok(total)
+}
+
+/// The configured values from a device configuration file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dcf {
+    /// The node ID from the `DeviceComissioning` section, if present
+    pub node_id: Option<u8>,
+    /// The node name from the `DeviceComissioning` section, if present
+    pub node_name: Option<String>,
+    /// The bit rate in kbit/s from the `DeviceComissioning` section, if present
+    pub baudrate: Option<u32>,
+    /// The sub objects with a `ParameterValue`, sorted by index and sub index
+    pub entries: Vec<DcfEntry>,
+}
+
+impl FromStr for Dcf {
+    type Err = DcfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ini = Ini::load_from_str(s).map_err(|e| DcfError::InvalidFile {
+            message: e.to_string(),
+        })?;
+        Self::from_ini(&ini)
+    }
+}
+
+/// Get a property, ignoring the case of its key
+pub(crate) fn get_property<'a>(properties: &'a Properties, key: &str) -> Option<&'a str> {
+    properties
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse an object section name, e.g. `1018` or `1018sub1`, into an index and sub index
+pub(crate) fn parse_section_name(name: &str) -> Option<(u16, u8)> {
+    let lower = name.to_ascii_lowercase();
+    let (index, sub) = match lower.split_once("sub") {
+        Some((index, sub)) => (index, Some(sub)),
+        None => (lower.as_str(), None),
+    };
+    let is_hex = |s: &str, max_len| {
+        !s.is_empty() && s.len() <= max_len && s.chars().all(|c| c.is_ascii_hexdigit())
+    };
+    if !is_hex(index, 4) {
+        return None;
+    }
+    let index = u16::from_str_radix(index, 16).ok()?;
+    let sub = match sub {
+        Some(sub) if is_hex(sub, 2) => u8::from_str_radix(sub, 16).ok()?,
+        Some(_) => return None,
+        None => 0,
+    };
+    Some((index, sub))
+}
+
+impl Dcf {
+    /// Load a DCF from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DcfError> {
+        let s = std::fs::read_to_string(path).context(IoSnafu)?;
+        s.parse()
+    }
+
+    fn from_ini(ini: &Ini) -> Result<Self, DcfError> {
+        let mut dcf = Dcf::default();
+
+        for (name, properties) in ini.iter() {
+            let Some(name) = name else {
+                continue;
+            };
+            let invalid = |message: String| DcfError::InvalidSection {
+                section: name.to_string(),
+                message,
+            };
+
+            // The standard spells it "Comissioning"
+            if name.eq_ignore_ascii_case("DeviceComissioning")
+                || name.eq_ignore_ascii_case("DeviceCommissioning")
+            {
+                if let Some(node_id) = get_property(properties, "NodeID") {
+                    let node_id = parse_integer(node_id, None)
+                        .ok()
+                        .and_then(|id| u8::try_from(id).ok())
+                        .ok_or_else(|| invalid(format!("invalid NodeID '{node_id}'")))?;
+                    dcf.node_id = Some(node_id);
+                }
+                dcf.node_name = get_property(properties, "NodeName").map(str::to_string);
+                if let Some(baudrate) = get_property(properties, "Baudrate") {
+                    let baudrate = parse_integer(baudrate, None)
+                        .ok()
+                        .and_then(|b| u32::try_from(b).ok())
+                        .ok_or_else(|| invalid(format!("invalid Baudrate '{baudrate}'")))?;
+                    dcf.baudrate = Some(baudrate);
+                }
+                continue;
+            }
+
+            let Some((index, sub)) = parse_section_name(name) else {
+                continue;
+            };
+            let Some(value) = get_property(properties, "ParameterValue") else {
+                continue;
+            };
+            let access_type = get_property(properties, "AccessType").unwrap_or("");
+            // "rww" and "rwr" are read-write objects which are mapped to RPDOs and TPDOs
+            let writable = matches!(
+                access_type.to_ascii_lowercase().as_str(),
+                "rw" | "wo" | "rww" | "rwr"
+            );
+            if !writable {
+                continue;
+            }
+            let data_type = get_property(properties, "DataType")
+                .and_then(|dt| parse_integer(dt, None).ok())
+                .and_then(|dt| u16::try_from(dt).ok())
+                .map(DataType::from)
+                .ok_or_else(|| invalid("missing or invalid DataType".to_string()))?;
+
+            dcf.entries.push(DcfEntry {
+                index,
+                sub,
+                parameter_name: get_property(properties, "ParameterName")
+                    .unwrap_or("")
+                    .to_string(),
+                data_type,
+                value: value.to_string(),
+            });
+        }
+
+        dcf.entries.sort_by_key(|e| (e.index, e.sub));
+        Ok(dcf)
+    }
+
+    /// Write the parameter values to a node
+    ///
+    /// Every entry is written, even if some fail, and the failures are returned in the
+    /// [`ApplyReport`], unless [`ApplyOptions::stop_on_error`] is set. `progress` is called after
+    /// each entry is written.
+    ///
+    /// PDOs with a COB ID in the DCF are disabled before their parameters are written. Their
+    /// mapping counts are set to zero before the mappings are written, and written, along with the
+    /// COB IDs, after all other values, so that PDOs can be reconfigured while the node is
+    /// operational.
+    pub async fn apply<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        client: &mut SdoClient<S, R>,
+        options: &ApplyOptions,
+        mut progress: impl FnMut(ApplyProgress),
+    ) -> ApplyReport {
+        let node_id = options.node_id.or(self.node_id);
+        let mut report = ApplyReport::default();
+
+        let (prepare, ordered) = self.write_order(node_id);
+        for (index, sub, data) in prepare {
+            if let Err(source) = client.download(index, sub, &data).await {
+                report.failures.push(DcfError::Write { index, sub, source });
+                if options.stop_on_error {
+                    return report;
+                }
+            }
+        }
+
+        let total = self.entries.len();
+        for (i, entry) in ordered.into_iter().enumerate() {
+            let result = match entry.encode(node_id) {
+                Ok(data) => client
+                    .download(entry.index, entry.sub, &data)
+                    .await
+                    .context(WriteSnafu {
+                        index: entry.index,
+                        sub: entry.sub,
+                    }),
+                Err(e) => Err(e),
+            };
+            progress(ApplyProgress {
+                index: entry.index,
+                sub: entry.sub,
+                completed: i + 1,
+                total,
+                ok: result.is_ok(),
+            });
+            match result {
+                Ok(()) => report.written += 1,
+                Err(e) => {
+                    report.failures.push(e);
+                    if options.stop_on_error {
+                        return report;
+                    }
+                }
+            }
+        }
+
+        if options.save && report.failures.is_empty() {
+            if let Err(e) = client.save_objects().await.context(SaveSnafu) {
+                report.failures.push(e);
+            }
+        }
+        report
+    }
+
+    /// Get the order in which the entries are written
+    ///
+    /// Returns the writes which disable the PDOs configured by the DCF and clear their mappings,
+    /// followed by the entries in the order they are written.
+    #[allow(clippy::type_complexity)]
+    fn write_order(&self, node_id: Option<u8>) -> (Vec<(u16, u8, Vec<u8>)>, Vec<&DcfEntry>) {
+        let is_cob_id = |e: &&DcfEntry| is_pdo_cob_id(e.index, e.sub);
+        let is_mapping_count = |e: &&DcfEntry| is_pdo_mapping_count(e.index, e.sub);
+        let cob_ids: Vec<&DcfEntry> = self.entries.iter().filter(is_cob_id).collect();
+        let mapping_counts: Vec<&DcfEntry> = self.entries.iter().filter(is_mapping_count).collect();
+
+        let mut prepare = Vec::new();
+        for e in &cob_ids {
+            if let Ok(data) = e.encode(node_id) {
+                if let Ok(cob_id) = <[u8; 4]>::try_from(data.as_slice()) {
+                    let disabled = u32::from_le_bytes(cob_id) | PDO_NOT_VALID;
+                    prepare.push((e.index, e.sub, disabled.to_le_bytes().to_vec()));
+                }
+            }
+        }
+        for e in &mapping_counts {
+            prepare.push((e.index, e.sub, vec![0]));
+        }
+
+        let ordered = self
+            .entries
+            .iter()
+            .filter(|e| !is_cob_id(e) && !is_mapping_count(e))
+            .chain(mapping_counts)
+            .chain(cob_ids)
+            .collect();
+        (prepare, ordered)
+    }
+
+    /// Convert the parameter values to a [`ConciseDcf`]
+    ///
+    /// The concise DCF writes the values in the same order as [`Dcf::apply()`], including the
+    /// writes which disable PDOs while they are configured. `node_id` is substituted for `$NODEID`
+    /// in values; if it is None, the node ID from the DCF is used.
+    pub fn to_concise(&self, node_id: Option<u8>) -> Result<ConciseDcf, DcfError> {
+        let node_id = node_id.or(self.node_id);
+        let (prepare, ordered) = self.write_order(node_id);
+        let mut concise = ConciseDcf::new();
+        for (index, sub, data) in prepare {
+            concise.push(index, sub, data);
+        }
+        for entry in ordered {
+            concise.push(entry.index, entry.sub, entry.encode(node_id)?);
+        }
+        Ok(concise)
+    }
+}
+
+/// A single value in a [`ConciseDcf`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConciseDcfValue {
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// The bytes to write to the sub object
+    pub data: Vec<u8>,
+}
+
+/// A concise DCF, a compact list of values to write to a node
+///
+/// A CiA 302 configuration manager stores a concise DCF for each node it manages in object 0x1F22,
+/// with the sub index equal to the node ID, and writes it to the node when the node boots. A
+/// concise DCF can be built from a [`Dcf`] with [`Dcf::to_concise()`], or value by value with
+/// [`ConciseDcf::push()`], and downloaded to a configuration manager with [`ConciseDcf::store()`].
+/// See [`zencan_common::concise_dcf`] for the encoding.
+///
+/// ```ignore
+/// // Build a configuration for node 5, and store it in the configuration manager
+/// let concise = Dcf::load("node5.dcf")?.to_concise(Some(5))?;
+/// concise.store(&mut manager_client, 5).await?;
+///
+/// // When node 5 boots, apply the stored configuration
+/// let concise = ConciseDcf::load_stored(&mut manager_client, 5).await?;
+/// concise.apply(&mut node5_client).await?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConciseDcf {
+    /// The values, in the order they are written
+    pub values: Vec<ConciseDcfValue>,
+}
+
+impl ConciseDcf {
+    /// Create an empty concise DCF
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value to write
+    pub fn push(&mut self, index: u16, sub: u8, data: impl Into<Vec<u8>>) -> &mut Self {
+        self.values.push(ConciseDcfValue {
+            index,
+            sub,
+            data: data.into(),
+        });
+        self
+    }
+
+    /// Encode the concise DCF, as stored in object 0x1F22
+    pub fn to_bytes(&self) -> Vec<u8> {
+        concise_dcf::encode(
+            self.values
+                .iter()
+                .map(|v| (v.index, v.sub, v.data.as_slice())),
+        )
+    }
+
+    /// Decode a concise DCF, as read from object 0x1F22
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ConciseDcfError> {
+        let mut concise = Self::new();
+        for entry in concise_dcf::entries(data)? {
+            let entry = entry?;
+            concise.push(entry.index, entry.sub, entry.data);
+        }
+        Ok(concise)
+    }
+
+    /// Write the values to a node, in order
+    ///
+    /// As done by a configuration manager, writing stops at the first value which fails. Returns
+    /// the number of values written.
+    pub async fn apply<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        client: &mut SdoClient<S, R>,
+    ) -> Result<usize, DcfError> {
+        for value in &self.values {
+            client
+                .download(value.index, value.sub, &value.data)
+                .await
+                .context(WriteSnafu {
+                    index: value.index,
+                    sub: value.sub,
+                })?;
+        }
+        Ok(self.values.len())
+    }
+
+    /// Store the concise DCF for node `node_id` in a configuration manager
+    ///
+    /// `client` must be a client for the configuration manager node, which must have storage for
+    /// `node_id` in its concise DCF object (0x1F22).
+    pub async fn store<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &self,
+        client: &mut SdoClient<S, R>,
+        node_id: u8,
+    ) -> Result<(), DcfError> {
+        client
+            .download(object_ids::CONCISE_DCF, node_id, &self.to_bytes())
+            .await
+            .context(WriteSnafu {
+                index: object_ids::CONCISE_DCF,
+                sub: node_id,
+            })
+    }
+
+    /// Read the concise DCF stored for node `node_id` in a configuration manager
+    ///
+    /// `client` must be a client for the configuration manager node. If no configuration is stored
+    /// for the node, an empty concise DCF is returned.
+    pub async fn load_stored<S: AsyncCanSender, R: AsyncCanReceiver>(
+        client: &mut SdoClient<S, R>,
+        node_id: u8,
+    ) -> Result<Self, DcfError> {
+        let data = client
+            .upload(object_ids::CONCISE_DCF, node_id)
+            .await
+            .context(ReadSnafu {
+                index: object_ids::CONCISE_DCF,
+                sub: node_id,
+            })?;
+        Self::from_bytes(&data).context(InvalidConciseSnafu)
+    }
+}
+
+/// Options for [`Dcf::apply()`]
+#[derive(Clone, Copy, Debug)]
+pub struct ApplyOptions {
+    /// The node ID substituted for `$NODEID` in values
+    ///
+    /// If None, the node ID from the DCF is used. Default: None
+    pub node_id: Option<u8>,
+    /// Stop at the first value which fails to be written. Default: false
+    pub stop_on_error: bool,
+    /// Command the node to save its objects after all values are written successfully, so that
+    /// they survive a reset. Default: true
+    pub save: bool,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            node_id: None,
+            stop_on_error: false,
+            save: true,
+        }
+    }
+}
+
+/// Progress of [`Dcf::apply()`], reported after each entry is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApplyProgress {
+    /// The index of the entry which was written
+    pub index: u16,
+    /// The sub index of the entry which was written
+    pub sub: u8,
+    /// The number of entries processed so far
+    pub completed: usize,
+    /// The total number of entries
+    pub total: usize,
+    /// Whether the entry was written successfully
+    pub ok: bool,
+}
+
+/// The outcome of [`Dcf::apply()`]
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    /// The number of entries written successfully
+    pub written: usize,
+    /// The errors for each entry which failed, and for any failed step in preparing the PDOs or
+    /// saving the objects
+    pub failures: Vec<DcfError>,
+}
+
+impl ApplyReport {
+    /// Returns true if every entry was written
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for ApplyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} values written, {} failures",
+            self.written,
+            self.failures.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DCF: &str = "
+[DeviceComissioning]
+NodeID=5
+NodeName=Motor
+Baudrate=500
+
+[1017]
+ParameterName=Producer Heartbeat Time
+DataType=0x0006
+AccessType=rw
+DefaultValue=0
+ParameterValue=1000
+
+[1018sub1]
+ParameterName=Vendor-ID
+DataType=0x0007
+AccessType=ro
+ParameterValue=0x1234
+
+[1800sub1]
+ParameterName=COB-ID used by TPDO
+DataType=0x0007
+AccessType=rw
+ParameterValue=$NODEID+0x180
+
+[1A00]
+ParameterName=TPDO mapping parameter
+ObjectType=0x9
+SubNumber=2
+
+[1a00sub0]
+ParameterName=Number of mapped objects
+DataType=0x0005
+AccessType=rw
+ParameterValue=1
+
+[1A00sub1]
+ParameterName=Mapping entry 1
+DataType=0x0007
+AccessType=rw
+ParameterValue=0x20000120
+
+[2000]
+ParameterName=Name
+DataType=0x0009
+AccessType=rw
+ParameterValue=hello world
+
+[2001]
+ParameterName=Not configured
+DataType=0x0007
+AccessType=rw
+DefaultValue=3
+";
+
+    #[test]
+    fn test_parse() {
+        let dcf: Dcf = DCF.parse().unwrap();
+        assert_eq!(Some(5), dcf.node_id);
+        assert_eq!(Some("Motor".to_string()), dcf.node_name);
+        assert_eq!(Some(500), dcf.baudrate);
+
+        // Read-only and unconfigured entries are not included
+        let objects: Vec<(u16, u8)> = dcf.entries.iter().map(|e| (e.index, e.sub)).collect();
+        assert_eq!(
+            vec![
+                (0x1017, 0),
+                (0x1800, 1),
+                (0x1A00, 0),
+                (0x1A00, 1),
+                (0x2000, 0)
+            ],
+            objects
+        );
+        assert_eq!(DataType::UInt16, dcf.entries[0].data_type);
+        assert_eq!("Producer Heartbeat Time", dcf.entries[0].parameter_name);
+    }
+
+    #[test]
+    fn test_to_concise() {
+        let dcf: Dcf = DCF.parse().unwrap();
+        let concise = dcf.to_concise(Some(6)).unwrap();
+        let writes: Vec<(u16, u8, &[u8])> = concise
+            .values
+            .iter()
+            .map(|v| (v.index, v.sub, v.data.as_slice()))
+            .collect();
+        assert_eq!(
+            vec![
+                // Disable the PDO and clear its mapping
+                (0x1800, 1, [0x86, 0x01, 0, 0x80].as_slice()),
+                (0x1A00, 0, &[0]),
+                (0x1017, 0, &[0xe8, 0x03]),
+                (0x1A00, 1, &[0x20, 0x01, 0x00, 0x20]),
+                (0x2000, 0, b"hello world"),
+                (0x1A00, 0, &[1]),
+                (0x1800, 1, &[0x86, 0x01, 0, 0]),
+            ],
+            writes
+        );
+
+        assert_eq!(
+            concise,
+            ConciseDcf::from_bytes(&concise.to_bytes()).unwrap()
+        );
+        assert_eq!(
+            Err(ConciseDcfError::Truncated),
+            ConciseDcf::from_bytes(&concise.to_bytes()[..20])
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let dcf: Dcf = DCF.parse().unwrap();
+        assert_eq!(vec![0xe8, 0x03], dcf.entries[0].encode(None).unwrap());
+        assert_eq!(
+            vec![0x85, 0x01, 0, 0],
+            dcf.entries[1].encode(Some(5)).unwrap()
+        );
+        assert!(matches!(
+            dcf.entries[1].encode(None),
+            Err(DcfError::InvalidValue { index: 0x1800, .. })
+        ));
+        assert_eq!(
+            b"hello world".to_vec(),
+            dcf.entries[4].encode(None).unwrap()
+        );
+
+        let entry = |data_type, value: &str| DcfEntry {
+            index: 0x2000,
+            sub: 0,
+            parameter_name: String::new(),
+            data_type,
+            value: value.to_string(),
+        };
+        assert_eq!(
+            vec![0xff],
+            entry(DataType::Int8, "-1").encode(None).unwrap()
+        );
+        assert_eq!(vec![8], entry(DataType::UInt8, "010").encode(None).unwrap());
+        assert_eq!(
+            vec![0x56, 0x34, 0x12],
+            entry(DataType::UInt24, "0x123456").encode(None).unwrap()
+        );
+        assert_eq!(
+            1.5f32.to_le_bytes().to_vec(),
+            entry(DataType::Real32, "1.5").encode(None).unwrap()
+        );
+        assert_eq!(
+            vec![0x0a, 0xbc],
+            entry(DataType::OctetString, "0A BC").encode(None).unwrap()
+        );
+        assert!(entry(DataType::UInt8, "256").encode(None).is_err());
+        assert!(entry(DataType::Int8, "-129").encode(None).is_err());
+        assert!(entry(DataType::Boolean, "2").encode(None).is_err());
+        assert!(entry(DataType::UInt16, "abc").encode(None).is_err());
+        assert!(entry(DataType::Domain, "00").encode(None).is_err());
+    }
+
+    #[test]
+    fn test_parse_section_name() {
+        assert_eq!(Some((0x1018, 0)), parse_section_name("1018"));
+        assert_eq!(Some((0x1018, 1)), parse_section_name("1018sub1"));
+        assert_eq!(Some((0x1a00, 0x1f)), parse_section_name("1A00SUB1F"));
+        assert_eq!(None, parse_section_name("FileInfo"));
+        assert_eq!(None, parse_section_name("1018sub"));
+        assert_eq!(None, parse_section_name("1018Value"));
+        assert_eq!(None, parse_section_name("12345"));
+    }
+}
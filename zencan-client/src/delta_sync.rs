@@ -0,0 +1,106 @@
+//! Incremental reading of a node's object values using the node's change counters
+use std::collections::{BTreeMap, HashMap};
+
+use zencan_common::{
+    constants::object_ids::{CHANGE_COUNTERS, CHANGE_COUNTERS_BOOT_EPOCH_SUB},
+    objects::PersistGroup,
+    sdo::AbortCode,
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+use crate::sdo_client::{RawAbortCode, SdoClient, SdoClientError};
+
+/// A cached copy of a set of sub object values from a node
+///
+/// Reading every configuration object on a large network over a slow link can take a long time.
+/// Nodes with the change counters object (0x5F02) enabled count modifications to each
+/// [`PersistGroup`], so on each [`sync()`](Self::sync), only the counters are read, and objects are
+/// uploaded only for groups whose counter has changed since the previous sync.
+///
+/// Counters do not survive a node reboot. The node's boot epoch is read on each sync, and all
+/// objects are re-read when it changes. For nodes whose application does not set a boot epoch, call
+/// [`invalidate()`](Self::invalidate) when a node is seen to reset.
+#[derive(Clone, Debug, Default)]
+pub struct DeltaSync {
+    objects: Vec<(u16, u8)>,
+    boot_epoch: Option<u32>,
+    counters: HashMap<PersistGroup, u32>,
+    values: BTreeMap<(u16, u8), Vec<u8>>,
+}
+
+impl DeltaSync {
+    /// Create a new DeltaSync which will read the given sub objects
+    pub fn new(objects: impl IntoIterator<Item = (u16, u8)>) -> Self {
+        Self {
+            objects: objects.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Update the cached values from the node
+    ///
+    /// Returns the groups which were re-read. On the first sync, after the node's boot epoch
+    /// changes, or after [`invalidate()`](Self::invalidate), all groups are read.
+    ///
+    /// If an upload fails, the error is returned and the group's cached counter is left unchanged,
+    /// so that the group will be read again on the next sync.
+    pub async fn sync<S: AsyncCanSender, R: AsyncCanReceiver>(
+        &mut self,
+        client: &mut SdoClient<S, R>,
+    ) -> Result<Vec<PersistGroup>, SdoClientError> {
+        // Nodes built before the boot epoch was added do not have it
+        let boot_epoch = match client
+            .read_u32(CHANGE_COUNTERS, CHANGE_COUNTERS_BOOT_EPOCH_SUB)
+            .await
+        {
+            Ok(epoch) => Some(epoch),
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::NoSuchSubIndex),
+                ..
+            }) => None,
+            Err(e) => return Err(e),
+        };
+        if boot_epoch != self.boot_epoch {
+            self.invalidate();
+            self.boot_epoch = boot_epoch;
+        }
+
+        let mut updated = Vec::new();
+        for group in PersistGroup::ALL {
+            // The counter is read before the objects, so that a change made while uploading will
+            // cause the group to be read again on the next sync
+            let counter = client.read_u32(CHANGE_COUNTERS, group.sub_index()).await?;
+            if self.counters.get(&group) == Some(&counter) {
+                continue;
+            }
+            self.counters.remove(&group);
+            for &(index, sub) in &self.objects {
+                if PersistGroup::from_index(index) != group {
+                    continue;
+                }
+                let value = client.upload(index, sub).await?;
+                self.values.insert((index, sub), value);
+            }
+            self.counters.insert(group, counter);
+            updated.push(group);
+        }
+        Ok(updated)
+    }
+
+    /// Discard the cached counters, so that all objects are read on the next sync
+    pub fn invalidate(&mut self) {
+        self.counters.clear();
+    }
+
+    /// Get the cached value of a sub object
+    ///
+    /// Returns None if the sub object has not been read
+    pub fn get(&self, index: u16, sub: u8) -> Option<&[u8]> {
+        self.values.get(&(index, sub)).map(|v| v.as_slice())
+    }
+
+    /// Iterate over all cached values, ordered by index and sub index
+    pub fn values(&self) -> impl Iterator<Item = ((u16, u8), &[u8])> {
+        self.values.iter().map(|(id, v)| (*id, v.as_slice()))
+    }
+}
@@ -0,0 +1,301 @@
+//! A handle for a single node combining SDO access, NMT control, and heartbeat monitoring
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use zencan_common::{
+    constants::values::BOOTLOADER_RESET_CMD,
+    i24,
+    lss::LssIdentity,
+    messages::{NmtCommand, NmtCommandSpecifier, ZencanMessage},
+    nmt::NmtState,
+    traits::{AsyncCanReceiver, AsyncCanSender, ReadSize},
+    u24, CanMessage,
+};
+
+use crate::{
+    clock::{timeout_at, Clock, TokioClock},
+    endianness::EndiannessProfile,
+    sdo_client::{SdoClient, SdoClientError},
+};
+
+type Result<T> = std::result::Result<T, SdoClientError>;
+
+/// Index of the bootloader info object
+const BOOTLOADER_INFO_INDEX: u16 = 0x5500;
+
+use paste::paste;
+macro_rules! access_methods {
+    ($type: ty) => {
+        paste! {
+            #[doc = concat!("Read a ", stringify!($type), " sub object from the node, in the byte order given by the device's endianness profile")]
+            pub async fn [<read_ $type>](&mut self, index: u16, sub: u8) -> Result<$type> {
+                let mut data = self.sdo.upload(index, sub).await?;
+                if data.len() != <$type as ReadSize>::READ_SIZE {
+                    return Err(SdoClientError::UnexpectedSize);
+                }
+                self.endianness.get(index, sub).swap_to_little(&mut data);
+                Ok($type::from_le_bytes(data.try_into().unwrap()))
+            }
+
+            #[doc = concat!("Write a ", stringify!($type), " sub object on the node, in the byte order given by the device's endianness profile")]
+            pub async fn [<write_ $type>](&mut self, index: u16, sub: u8, value: $type) -> Result<()> {
+                let mut data = value.to_le_bytes();
+                self.endianness.get(index, sub).swap_to_little(&mut data);
+                self.sdo.download(index, sub, &data).await
+            }
+        }
+    };
+}
+
+/// A handle for communicating with a single node
+///
+/// The device wraps an [`SdoClient`] for the node's default SDO server, sends NMT commands
+/// addressed to the node, and monitors the node's heartbeat messages, so that an application
+/// managing a node does not need to juggle separate SDO and NMT clients.
+///
+/// ```ignore
+/// let mut device = Device::new(5, sender, receiver, heartbeat_receiver);
+/// device.reset().await?;
+/// device.wait_online(Duration::from_secs(1)).await?;
+/// let identity = device.identity().await?;
+/// device.start().await?;
+/// ```
+///
+/// The NMT state is only known from the node's heartbeat messages. A node with no heartbeat
+/// producer configured only reports its state in the boot-up message sent after a reset.
+///
+/// The typed accessors, e.g. [`read_u32()`](Self::read_u32), convert values using the device's
+/// [`EndiannessProfile`], so that devices which store some values in a non-standard byte order can
+/// be accessed without byte swapping in application code. By default, all values are little
+/// endian.
+#[derive(Debug)]
+pub struct Device<S, R> {
+    node_id: u8,
+    sdo: SdoClient<S, R>,
+    heartbeat_receiver: R,
+    identity: Option<LssIdentity>,
+    nmt_state: Option<NmtState>,
+    last_heartbeat: Option<Instant>,
+    /// Set after a reset command is sent, so that heartbeats sent before the reset is handled are
+    /// ignored until the boot-up message is received
+    awaiting_bootup: bool,
+    endianness: EndiannessProfile,
+    clock: Arc<dyn Clock>,
+}
+
+impl<S: AsyncCanSender, R: AsyncCanReceiver> Device<S, R> {
+    /// Create a new Device
+    ///
+    /// # Arguments
+    /// - `node_id`: The ID of the node
+    /// - `sender`: Used for sending SDO requests and NMT commands to the node
+    /// - `receiver`: Used for receiving SDO responses from the node
+    /// - `heartbeat_receiver`: Used for receiving heartbeat messages from the node. This must be a
+    ///   separate receiver, because the SDO client discards any messages which are not SDO
+    ///   responses.
+    pub fn new(node_id: u8, sender: S, receiver: R, heartbeat_receiver: R) -> Self {
+        Self {
+            node_id,
+            sdo: SdoClient::new_std(node_id, sender, receiver),
+            heartbeat_receiver,
+            identity: None,
+            nmt_state: None,
+            last_heartbeat: None,
+            awaiting_bootup: false,
+            endianness: EndiannessProfile::default(),
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Set the clock used for timeouts and heartbeat times, by the device and its SDO client
+    ///
+    /// The default is the [`TokioClock`].
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.sdo.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Set the byte order used by the typed accessors for each object
+    pub fn set_endianness_profile(&mut self, profile: EndiannessProfile) {
+        self.endianness = profile;
+    }
+
+    /// Get the byte order used by the typed accessors for each object
+    pub fn endianness_profile(&self) -> &EndiannessProfile {
+        &self.endianness
+    }
+
+    /// Get the ID of the node
+    pub fn node_id(&self) -> u8 {
+        self.node_id
+    }
+
+    /// Access the SDO client for the node
+    pub fn sdo(&mut self) -> &mut SdoClient<S, R> {
+        &mut self.sdo
+    }
+
+    /// Read a sub object from the node
+    pub async fn read(&mut self, index: u16, sub: u8) -> Result<Vec<u8>> {
+        self.sdo.upload(index, sub).await
+    }
+
+    /// Write a sub object on the node
+    pub async fn write(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<()> {
+        self.sdo.download(index, sub, data).await
+    }
+
+    access_methods!(u64);
+    access_methods!(u32);
+    access_methods!(u24);
+    access_methods!(u16);
+    access_methods!(u8);
+    access_methods!(i64);
+    access_methods!(i32);
+    access_methods!(i24);
+    access_methods!(i16);
+    access_methods!(i8);
+    access_methods!(f64);
+    access_methods!(f32);
+
+    /// Get the node's identity
+    ///
+    /// The identity is read from the node on the first call, and cached for later calls
+    pub async fn identity(&mut self) -> Result<LssIdentity> {
+        if let Some(identity) = self.identity {
+            return Ok(identity);
+        }
+        let identity = self.sdo.read_identity().await?;
+        self.identity = Some(identity);
+        Ok(identity)
+    }
+
+    /// Command the node to enter the Operational state
+    pub async fn start(&mut self) -> Result<()> {
+        self.send_nmt_cmd(NmtCommandSpecifier::Start).await
+    }
+
+    /// Command the node to enter the Stopped state
+    pub async fn stop(&mut self) -> Result<()> {
+        self.send_nmt_cmd(NmtCommandSpecifier::Stop).await
+    }
+
+    /// Command the node to enter the PreOperational state
+    pub async fn enter_preoperational(&mut self) -> Result<()> {
+        self.send_nmt_cmd(NmtCommandSpecifier::EnterPreOp).await
+    }
+
+    /// Command the node to perform an application reset
+    ///
+    /// Use [`wait_online()`](Self::wait_online) to wait for the node to come back up.
+    pub async fn reset(&mut self) -> Result<()> {
+        self.begin_reset();
+        self.send_nmt_cmd(NmtCommandSpecifier::ResetApp).await
+    }
+
+    /// Command the node to reset into its bootloader
+    ///
+    /// Writes the reset command to the bootloader info object (0x5500). Use
+    /// [`wait_online()`](Self::wait_online) to wait for the bootloader to come up.
+    pub async fn reset_to_bootloader(&mut self) -> Result<()> {
+        self.begin_reset();
+        let result = self
+            .write_u32(BOOTLOADER_INFO_INDEX, 3, BOOTLOADER_RESET_CMD)
+            .await;
+        if result.is_err() {
+            // The node did not accept the command, so it will not reset
+            self.awaiting_bootup = false;
+        }
+        result
+    }
+
+    /// Command the node to perform a communications reset
+    ///
+    /// Use [`wait_online()`](Self::wait_online) to wait for the node to come back up.
+    pub async fn reset_comms(&mut self) -> Result<()> {
+        self.begin_reset();
+        self.send_nmt_cmd(NmtCommandSpecifier::ResetComm).await
+    }
+
+    /// Get the NMT state last reported by the node, or None if no heartbeat has been received
+    pub fn nmt_state(&mut self) -> Option<NmtState> {
+        self.process_rx();
+        self.nmt_state
+    }
+
+    /// Get the time the last heartbeat was received from the node
+    pub fn last_heartbeat(&mut self) -> Option<Instant> {
+        self.process_rx();
+        self.last_heartbeat
+    }
+
+    /// Wait until the node is online, and return its NMT state
+    ///
+    /// The node is online once a heartbeat has been received from it, or, after a reset, once its
+    /// boot-up message has been received. Returns [`SdoClientError::NoResponse`] if this does not
+    /// happen within `timeout`.
+    pub async fn wait_online(&mut self, timeout: Duration) -> Result<NmtState> {
+        let deadline = self.clock.now() + timeout;
+        loop {
+            if let Some(state) = self.nmt_state() {
+                return Ok(state);
+            }
+            match timeout_at(&*self.clock, deadline, self.heartbeat_receiver.recv()).await {
+                Some(Ok(msg)) => self.handle_message(msg),
+                Some(Err(_)) | None => return Err(SdoClientError::NoResponse),
+            }
+        }
+    }
+
+    fn begin_reset(&mut self) {
+        self.process_rx();
+        self.nmt_state = None;
+        self.awaiting_bootup = true;
+    }
+
+    fn process_rx(&mut self) {
+        while let Some(msg) = self.heartbeat_receiver.try_recv() {
+            self.handle_message(msg);
+        }
+    }
+
+    fn handle_message(&mut self, msg: CanMessage) {
+        let Ok(ZencanMessage::Heartbeat(heartbeat)) = msg.try_into() else {
+            return;
+        };
+        if heartbeat.node != self.node_id {
+            return;
+        }
+        let state = match heartbeat.state {
+            // A boot-up message indicates that the node has just entered PreOperational
+            NmtState::Bootup => {
+                self.awaiting_bootup = false;
+                NmtState::PreOperational
+            }
+            _ if self.awaiting_bootup => return,
+            s => s,
+        };
+        self.nmt_state = Some(state);
+        self.last_heartbeat = Some(self.clock.now());
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "nmt_command",
+            skip(self),
+            fields(node = self.node_id),
+            err(Display),
+        )
+    )]
+    async fn send_nmt_cmd(&mut self, cs: NmtCommandSpecifier) -> Result<()> {
+        let cmd = NmtCommand {
+            cs,
+            node: self.node_id,
+        };
+        self.sdo.send_message(cmd.into()).await
+    }
+}
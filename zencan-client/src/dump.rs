@@ -0,0 +1,467 @@
+//! Reading the object dictionary of a node, for diagnostics
+//!
+//! [`SdoClient::dump_dictionary()`] reads every sub object in a set of index ranges, and returns a
+//! [`DictionaryDump`], which can be rendered as CSV or JSON. The data types of the sub objects are
+//! read from the node where it supports the object structure sub (0xFF), and names and data types
+//! can be added from an EDS file:
+//!
+//! ```ignore
+//! let mut dump = client.dump_dictionary(&[0x1000..=0x1FFF, 0x2000..=0x5FFF]).await?;
+//! dump.apply_eds(&EdsObjects::load("node.eds")?);
+//! std::fs::write("node5.csv", dump.to_csv())?;
+//! ```
+//!
+//! Each index in the ranges is probed with an SDO read, so dumping large ranges takes some time.
+use std::{collections::HashMap, ops::RangeInclusive, path::Path, str::FromStr};
+
+use ini::Ini;
+use snafu::{ResultExt, Snafu};
+use zencan_common::{
+    objects::{DataType, ObjectCode, OBJECT_STRUCTURE_SUB},
+    sdo::AbortCode,
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+use crate::{
+    dcf::{get_property, parse_integer, parse_section_name},
+    RawAbortCode, SdoClient, SdoClientError,
+};
+
+/// Error returned when loading an EDS file
+#[derive(Debug, Snafu)]
+pub enum EdsError {
+    /// The file could not be read
+    #[snafu(display("IO error: {source}"))]
+    Io {
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// The file is not a valid INI file
+    #[snafu(display("Invalid EDS file: {message}"))]
+    InvalidFile {
+        /// Description of the problem
+        message: String,
+    },
+}
+
+/// The name and data type of a sub object, as described by an EDS file
+#[derive(Clone, Debug, PartialEq)]
+pub struct EdsSub {
+    /// The `ParameterName` of the sub object
+    pub name: String,
+    /// The `DataType` of the sub object
+    pub data_type: DataType,
+}
+
+/// The sub objects described by an EDS file
+///
+/// Only the names and data types are read. A DCF file may be loaded the same way.
+#[derive(Clone, Debug, Default)]
+pub struct EdsObjects {
+    subs: HashMap<(u16, u8), EdsSub>,
+}
+
+impl FromStr for EdsObjects {
+    type Err = EdsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ini = Ini::load_from_str(s).map_err(|e| EdsError::InvalidFile {
+            message: e.to_string(),
+        })?;
+        let mut objects = Self::default();
+        for (name, properties) in ini.iter() {
+            let Some((index, sub)) = name.and_then(parse_section_name) else {
+                continue;
+            };
+            // Array and record object sections have no data type, and describe the object rather
+            // than its sub 0
+            let Some(data_type) = get_property(properties, "DataType")
+                .and_then(|dt| parse_integer(dt, None).ok())
+                .and_then(|dt| u16::try_from(dt).ok())
+                .map(DataType::from)
+            else {
+                continue;
+            };
+            objects.subs.insert(
+                (index, sub),
+                EdsSub {
+                    name: get_property(properties, "ParameterName")
+                        .unwrap_or("")
+                        .to_string(),
+                    data_type,
+                },
+            );
+        }
+        Ok(objects)
+    }
+}
+
+impl EdsObjects {
+    /// Load an EDS file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, EdsError> {
+        let s = std::fs::read_to_string(path).context(IoSnafu)?;
+        s.parse()
+    }
+
+    /// Get the description of a sub object
+    pub fn get(&self, index: u16, sub: u8) -> Option<&EdsSub> {
+        self.subs.get(&(index, sub))
+    }
+}
+
+/// A sub object read by [`SdoClient::dump_dictionary()`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DumpEntry {
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// The name of the sub object, if known
+    pub name: Option<String>,
+    /// The data type of the sub object, if known
+    pub data_type: Option<DataType>,
+    /// The value read from the node
+    pub value: Vec<u8>,
+    /// A description of the error, if the sub object could not be read
+    pub error: Option<String>,
+}
+
+impl DumpEntry {
+    /// The value, formatted according to its data type if known, or as hex bytes otherwise
+    pub fn formatted_value(&self) -> String {
+        if self.error.is_some() {
+            return String::new();
+        }
+        match self.data_type {
+            Some(data_type) => format_value(data_type, &self.value),
+            None => hex_bytes(&self.value),
+        }
+    }
+}
+
+/// The sub objects read from a node by [`SdoClient::dump_dictionary()`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DictionaryDump {
+    /// The sub objects, sorted by index and sub index
+    pub entries: Vec<DumpEntry>,
+}
+
+/// Quote a CSV field if required
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl DictionaryDump {
+    /// Set the names and data types of the entries from an EDS
+    ///
+    /// Data types given by the EDS replace those read from the node.
+    pub fn apply_eds(&mut self, eds: &EdsObjects) {
+        for entry in &mut self.entries {
+            if let Some(sub) = eds.get(entry.index, entry.sub) {
+                entry.name = Some(sub.name.clone());
+                entry.data_type = Some(sub.data_type);
+            }
+        }
+    }
+
+    /// Render the dump as CSV, with a header row
+    ///
+    /// The columns are `index`, `sub`, `name`, `data_type`, `value`, and `error`. The value is
+    /// formatted according to its data type.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("index,sub,name,data_type,value,error\n");
+        for entry in &self.entries {
+            let fields = [
+                format!("0x{:04X}", entry.index),
+                entry.sub.to_string(),
+                csv_field(entry.name.as_deref().unwrap_or("")),
+                entry
+                    .data_type
+                    .map(|dt| format!("{dt:?}"))
+                    .unwrap_or_default(),
+                csv_field(&entry.formatted_value()),
+                csv_field(entry.error.as_deref().unwrap_or("")),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Render the dump as a JSON array, with an object for each entry
+    ///
+    /// Along with the formatted value, the raw value is given as hex bytes.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "index": format!("0x{:04X}", entry.index),
+                    "sub": entry.sub,
+                    "name": entry.name,
+                    "data_type": entry.data_type.map(|dt| format!("{dt:?}")),
+                    "value": entry.error.is_none().then(|| entry.formatted_value()),
+                    "raw": entry.error.is_none().then(|| hex_bytes(&entry.value)),
+                    "error": entry.error,
+                })
+            })
+            .collect();
+        // Unwrap safety: Serializing a Value cannot fail
+        serde_json::to_string_pretty(&entries).unwrap()
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Format raw data read from a sub object for display
+///
+/// Data which does not have the size expected for its data type, and data types with no textual
+/// form, are shown as hex bytes.
+pub fn format_value(data_type: DataType, bytes: &[u8]) -> String {
+    if let Some((size, signed)) = data_type.int_format() {
+        if bytes.len() != size {
+            return hex_bytes(bytes);
+        }
+        let mut buf = [0; 8];
+        buf[..size].copy_from_slice(bytes);
+        let value = u64::from_le_bytes(buf);
+        return if signed {
+            let shift = 64 - 8 * size as u32;
+            (((value << shift) as i64) >> shift).to_string()
+        } else {
+            format!("{value} (0x{value:X})")
+        };
+    }
+
+    match (data_type, bytes) {
+        (DataType::Boolean, [0]) => "false".into(),
+        (DataType::Boolean, [1]) => "true".into(),
+        (DataType::Real32, &[a, b, c, d]) => f32::from_le_bytes([a, b, c, d]).to_string(),
+        (DataType::Real64, bytes) if bytes.len() == 8 => {
+            f64::from_le_bytes(bytes.try_into().unwrap()).to_string()
+        }
+        (DataType::VisibleString | DataType::UnicodeString, bytes) => {
+            format!("{:?}", String::from_utf8_lossy(bytes))
+        }
+        _ => hex_bytes(bytes),
+    }
+}
+
+/// Returns true if the error is an abort indicating that the object or sub object does not exist
+fn is_missing(err: &SdoClientError) -> bool {
+    matches!(
+        err,
+        SdoClientError::ServerAbort {
+            abort_code: RawAbortCode::Valid(AbortCode::NoSuchObject | AbortCode::NoSuchSubIndex),
+            ..
+        }
+    )
+}
+
+/// Read the sub objects in `ranges`
+pub(crate) async fn dump_dictionary<S: AsyncCanSender, R: AsyncCanReceiver>(
+    client: &mut SdoClient<S, R>,
+    ranges: &[RangeInclusive<u16>],
+) -> Result<DictionaryDump, SdoClientError> {
+    let info = client.object_info().cloned();
+    let mut dump = DictionaryDump::default();
+    let mut indices: Vec<u16> = ranges.iter().cloned().flatten().collect();
+    indices.sort();
+    indices.dedup();
+
+    for index in indices {
+        // The object structure gives the object code and data type, where it is supported
+        let structure = match client.upload(index, OBJECT_STRUCTURE_SUB).await {
+            Ok(data) if data.len() == 4 => Some((data[0], data[1])),
+            Err(SdoClientError::ServerAbort {
+                abort_code: RawAbortCode::Valid(AbortCode::NoSuchObject),
+                ..
+            }) => continue,
+            Err(SdoClientError::NoResponse) => return Err(SdoClientError::NoResponse),
+            _ => None,
+        };
+        let object_code = structure.and_then(|(code, _)| ObjectCode::try_from(code).ok());
+        let structure_type = structure.map(|(_, dt)| DataType::from(dt as u16));
+
+        let sub0 = client.upload(index, 0).await;
+        let entry = |sub: u8, result: Result<Vec<u8>, SdoClientError>| {
+            let data_type = info
+                .as_ref()
+                .and_then(|info| info.get(index, sub))
+                .map(|info| info.data_type)
+                .or(match object_code {
+                    Some(ObjectCode::Var) => structure_type,
+                    Some(ObjectCode::Array | ObjectCode::Record) if sub == 0 => {
+                        Some(DataType::UInt8)
+                    }
+                    Some(ObjectCode::Array) => structure_type,
+                    _ => None,
+                });
+            let (value, error) = match result {
+                Ok(value) => (value, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            };
+            DumpEntry {
+                index,
+                sub,
+                name: None,
+                data_type,
+                value,
+                error,
+            }
+        };
+
+        let max_sub = match &sub0 {
+            Err(e) if is_missing(e) => continue,
+            Err(SdoClientError::NoResponse) => return Err(SdoClientError::NoResponse),
+            Ok(data) if data.len() == 1 => data[0],
+            _ => 0,
+        };
+        let has_subs = match object_code {
+            Some(ObjectCode::Array | ObjectCode::Record) => true,
+            Some(_) => false,
+            // Without the object structure, an object is taken to have subs if it has sub 1
+            None => max_sub > 0,
+        };
+        dump.entries.push(entry(0, sub0));
+        if !has_subs {
+            continue;
+        }
+        for sub in 1..=max_sub {
+            let result = client.upload(index, sub).await;
+            match result {
+                Err(SdoClientError::NoResponse) => return Err(SdoClientError::NoResponse),
+                // Records may have gaps in their sub indices
+                Err(e) if is_missing(&e) => {
+                    if object_code.is_none() && sub == 1 {
+                        break;
+                    }
+                }
+                result => dump.entries.push(entry(sub, result)),
+            }
+        }
+    }
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDS: &str = "
+[1008]
+ParameterName=Manufacturer device name
+DataType=0x0009
+AccessType=const
+
+[1018]
+ParameterName=Identity object
+ObjectType=0x9
+SubNumber=2
+
+[1018sub0]
+ParameterName=Highest sub-index supported
+DataType=0x0005
+AccessType=const
+
+[1018sub1]
+ParameterName=Vendor-ID
+DataType=0x0007
+AccessType=ro
+";
+
+    #[test]
+    fn test_eds_objects() {
+        let eds: EdsObjects = EDS.parse().unwrap();
+        assert_eq!(
+            Some(&EdsSub {
+                name: "Manufacturer device name".into(),
+                data_type: DataType::VisibleString
+            }),
+            eds.get(0x1008, 0)
+        );
+        assert_eq!(DataType::UInt8, eds.get(0x1018, 0).unwrap().data_type);
+        assert_eq!("Vendor-ID", eds.get(0x1018, 1).unwrap().name);
+        assert_eq!(None, eds.get(0x1018, 2));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut dump = DictionaryDump {
+            entries: vec![
+                DumpEntry {
+                    index: 0x1008,
+                    sub: 0,
+                    name: None,
+                    data_type: None,
+                    value: b"a,b".to_vec(),
+                    error: None,
+                },
+                DumpEntry {
+                    index: 0x1018,
+                    sub: 1,
+                    name: None,
+                    data_type: Some(DataType::UInt32),
+                    value: vec![0x2c, 1, 0, 0],
+                    error: None,
+                },
+                DumpEntry {
+                    index: 0x1018,
+                    sub: 2,
+                    name: None,
+                    data_type: None,
+                    value: vec![],
+                    error: Some("Write only".into()),
+                },
+            ],
+        };
+        assert_eq!(
+            "index,sub,name,data_type,value,error\n\
+             0x1008,0,,,61 2C 62,\n\
+             0x1018,1,,UInt32,300 (0x12C),\n\
+             0x1018,2,,,,Write only\n",
+            dump.to_csv()
+        );
+
+        dump.apply_eds(&EDS.parse().unwrap());
+        let csv = dump.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            "0x1008,0,Manufacturer device name,VisibleString,\"\"\"a,b\"\"\",",
+            lines[1]
+        );
+        assert_eq!("0x1018,1,Vendor-ID,UInt32,300 (0x12C),", lines[2]);
+
+        let json: serde_json::Value = serde_json::from_str(&dump.to_json()).unwrap();
+        assert_eq!("0x1018", json[1]["index"]);
+        assert_eq!("2C 01 00 00", json[1]["raw"]);
+        assert_eq!(serde_json::Value::Null, json[2]["value"]);
+        assert_eq!("Write only", json[2]["error"]);
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!("-2", format_value(DataType::Int16, &[0xfe, 0xff]));
+        assert_eq!("-1", format_value(DataType::Int24, &[0xff, 0xff, 0xff]));
+        assert_eq!(
+            "300 (0x12C)",
+            format_value(DataType::UInt32, &[0x2c, 1, 0, 0])
+        );
+        assert_eq!("2C 01", format_value(DataType::UInt32, &[0x2c, 1]));
+        assert_eq!("true", format_value(DataType::Boolean, &[1]));
+        assert_eq!("2.5", format_value(DataType::Real32, &2.5f32.to_le_bytes()));
+        assert_eq!("\"abc\"", format_value(DataType::VisibleString, b"abc"));
+        assert_eq!("01 02", format_value(DataType::OctetString, &[1, 2]));
+    }
+}
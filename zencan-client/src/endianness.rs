@@ -0,0 +1,131 @@
+//! Byte order handling for devices which do not store values little endian
+//!
+//! CiA 301 requires all numeric values to be transferred least significant byte first, but some
+//! third-party devices store values in manufacturer objects in a different order. An
+//! [`EndiannessProfile`] records which objects on a device use which [`Endianness`], so that a
+//! [`Device`](crate::Device) can convert values when they are read or written.
+use std::collections::HashMap;
+
+/// The order in which the bytes of a numeric value are stored in an object
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    /// Least significant byte first, as required by CiA 301
+    #[default]
+    Little,
+    /// Most significant byte first
+    Big,
+    /// Most significant 16-bit word first, with the bytes in each word stored least significant
+    /// first
+    ///
+    /// E.g. the u32 0x11223344 is stored as `[0x22, 0x11, 0x44, 0x33]`. Values which are not a
+    /// multiple of 4 bytes long are stored little endian.
+    WordSwapped,
+}
+
+impl Endianness {
+    /// Convert a value stored in this byte order to little endian, in place
+    ///
+    /// As each conversion is its own inverse, this also converts a little endian value to this
+    /// byte order.
+    pub fn swap_to_little(&self, data: &mut [u8]) {
+        match self {
+            Endianness::Little => (),
+            Endianness::Big => data.reverse(),
+            Endianness::WordSwapped if data.len().is_multiple_of(4) => {
+                // Reverse the byte order, then restore the order of bytes within each word
+                data.reverse();
+                data.chunks_mut(2).for_each(|word| word.reverse());
+            }
+            Endianness::WordSwapped => (),
+        }
+    }
+}
+
+/// The byte order used for each object on a device
+///
+/// Objects which are not given a byte order use the profile's default.
+///
+/// ```
+/// use zencan_client::{Endianness, EndiannessProfile};
+///
+/// // A device which is little endian, except for manufacturer object 0x2100
+/// let profile = EndiannessProfile::default().with_object(0x2100, Endianness::Big);
+/// assert_eq!(Endianness::Big, profile.get(0x2100, 1));
+/// assert_eq!(Endianness::Little, profile.get(0x2101, 0));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndiannessProfile {
+    default: Endianness,
+    objects: HashMap<u16, Endianness>,
+    subs: HashMap<(u16, u8), Endianness>,
+}
+
+impl EndiannessProfile {
+    /// Create a profile which uses `default` for all objects
+    pub fn new(default: Endianness) -> Self {
+        Self {
+            default,
+            ..Default::default()
+        }
+    }
+
+    /// Set the byte order for all sub objects of an object
+    pub fn with_object(mut self, index: u16, endianness: Endianness) -> Self {
+        self.objects.insert(index, endianness);
+        self
+    }
+
+    /// Set the byte order for a single sub object
+    ///
+    /// This takes precedence over a byte order set for the whole object.
+    pub fn with_sub(mut self, index: u16, sub: u8, endianness: Endianness) -> Self {
+        self.subs.insert((index, sub), endianness);
+        self
+    }
+
+    /// Get the byte order of a sub object
+    pub fn get(&self, index: u16, sub: u8) -> Endianness {
+        self.subs
+            .get(&(index, sub))
+            .or_else(|| self.objects.get(&index))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_to_little() {
+        let value = 0x11223344u32;
+        let mut data = value.to_be_bytes();
+        Endianness::Big.swap_to_little(&mut data);
+        assert_eq!(value.to_le_bytes(), data);
+
+        let mut data = [0x22, 0x11, 0x44, 0x33];
+        Endianness::WordSwapped.swap_to_little(&mut data);
+        assert_eq!(value.to_le_bytes(), data);
+        Endianness::WordSwapped.swap_to_little(&mut data);
+        assert_eq!([0x22, 0x11, 0x44, 0x33], data);
+
+        let mut data = 0x1122334455667788u64.to_le_bytes();
+        Endianness::WordSwapped.swap_to_little(&mut data);
+        assert_eq!([0x22, 0x11, 0x44, 0x33, 0x66, 0x55, 0x88, 0x77], data);
+
+        let mut data = [0x11, 0x22];
+        Endianness::WordSwapped.swap_to_little(&mut data);
+        assert_eq!([0x11, 0x22], data);
+    }
+
+    #[test]
+    fn test_profile() {
+        let profile = EndiannessProfile::new(Endianness::Big)
+            .with_object(0x2000, Endianness::Little)
+            .with_sub(0x2000, 2, Endianness::WordSwapped);
+        assert_eq!(Endianness::Big, profile.get(0x1000, 0));
+        assert_eq!(Endianness::Little, profile.get(0x2000, 1));
+        assert_eq!(Endianness::WordSwapped, profile.get(0x2000, 2));
+    }
+}
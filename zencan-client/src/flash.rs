@@ -0,0 +1,586 @@
+//! Firmware updates over the bus
+//!
+//! [`flash()`] programs a firmware image into a node which supports the zencan bootloader objects
+//! (see the `bootloader` module of `zencan-node`). It:
+//!
+//! 1. Resets the node into its bootloader via the bootloader info object (0x5500), if it is running
+//!    a single bank application,
+//! 2. Erases the section being programmed,
+//! 3. Streams the image to the section with block transfers,
+//! 4. Checks the CRC32 of the data received by the node, and writes it to the section's verify
+//!    command,
+//! 5. On a dual-bank device, commands a swap to the programmed bank,
+//! 6. Resets the node, and waits for it to boot.
+//!
+//! Images are loaded with [`FirmwareImage`], from either a raw binary or an ELF file.
+//!
+//! ```ignore
+//! let image = FirmwareImage::load("firmware.elf")?;
+//! let mut device = Device::new(node_id, sender, receiver, heartbeat_receiver);
+//! flash(&mut device, &image.data, &FlashOptions::default(), |step| println!("{step:?}")).await?;
+//! ```
+use std::{path::Path, time::Duration};
+
+use snafu::{ResultExt, Snafu};
+use zencan_common::{
+    constants::values::{BOOTLOADER_ERASE_CMD, BOOTLOADER_SWAP_CMD},
+    crc32::crc32,
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+use crate::{Device, SdoClientError};
+
+/// Index of the bootloader info object
+const BOOTLOADER_INFO_INDEX: u16 = 0x5500;
+/// Index of the first bootloader section object
+const BOOTLOADER_SECTION_BASE_INDEX: u16 = 0x5510;
+/// The maximum number of bootloader sections
+const MAX_SECTIONS: u8 = 16;
+
+/// Config bit indicating that the device supports the bootloader
+const CONFIG_SUPPORTED: u32 = 1 << 0;
+/// Config bit indicating that the device is running an application which can reset to bootloader
+const CONFIG_CAN_RESET: u32 = 1 << 1;
+
+/// Section mode bit indicating that the section has been verified
+const MODE_VERIFIED: u8 = 1 << 1;
+
+/// The largest image which will be created from an ELF file
+///
+/// Segments are combined into a single image, with any gaps between them filled. This limit
+/// catches ELF files with segments at widely separated addresses, e.g. in both flash and RAM, which
+/// would otherwise create a huge image.
+pub const MAX_ELF_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The value used to fill gaps between ELF segments, matching erased flash
+const FILL_BYTE: u8 = 0xFF;
+
+/// Error returned when loading a [`FirmwareImage`]
+#[derive(Debug, Snafu)]
+pub enum ImageError {
+    /// The image file could not be read
+    #[snafu(display("Failed to read image: {source}"))]
+    Io {
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// The ELF file could not be parsed
+    #[snafu(display("Invalid ELF file: {reason}"))]
+    InvalidElf {
+        /// Description of the problem
+        reason: &'static str,
+    },
+}
+
+/// A firmware image to be programmed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareImage {
+    /// The address of the first byte of the image, if known
+    ///
+    /// Only images loaded from an ELF file have an address.
+    pub address: Option<u64>,
+    /// The image data
+    pub data: Vec<u8>,
+}
+
+impl FirmwareImage {
+    /// Load an image from a file
+    ///
+    /// Files which begin with the ELF magic number are loaded with
+    /// [`from_elf()`](Self::from_elf), and all other files are treated as raw binary images.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        let bytes = std::fs::read(path).context(IoSnafu)?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Create an image from the contents of an ELF or raw binary file
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, ImageError> {
+        if bytes.starts_with(ELF_MAGIC) {
+            Self::from_elf(&bytes)
+        } else {
+            Ok(Self {
+                address: None,
+                data: bytes,
+            })
+        }
+    }
+
+    /// Create an image from the loadable segments of an ELF file
+    ///
+    /// The contents of each `PT_LOAD` segment are placed at its physical (load) address, so that
+    /// initialized data is included at its location in flash. Gaps between segments are filled
+    /// with 0xFF.
+    pub fn from_elf(bytes: &[u8]) -> Result<Self, ImageError> {
+        let elf = ElfReader::new(bytes)?;
+        let mut segments = elf.load_segments()?;
+        segments.sort_by_key(|s| s.address);
+
+        let Some(first) = segments.first() else {
+            return InvalidElfSnafu {
+                reason: "no loadable segments",
+            }
+            .fail();
+        };
+        let start = first.address;
+        let mut data = Vec::new();
+        for segment in segments {
+            let offset = (segment.address - start) as usize;
+            if offset < data.len() {
+                return InvalidElfSnafu {
+                    reason: "overlapping segments",
+                }
+                .fail();
+            }
+            if offset + segment.data.len() > MAX_ELF_IMAGE_SIZE {
+                return InvalidElfSnafu {
+                    reason: "segments span too large an address range",
+                }
+                .fail();
+            }
+            data.resize(offset, FILL_BYTE);
+            data.extend_from_slice(segment.data);
+        }
+
+        Ok(Self {
+            address: Some(start),
+            data,
+        })
+    }
+}
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// Program header type of a loadable segment
+const PT_LOAD: u32 = 1;
+
+struct ElfSegment<'a> {
+    address: u64,
+    data: &'a [u8],
+}
+
+/// Reads the fields needed to find the loadable segments of a 32 or 64-bit ELF file
+struct ElfReader<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl<'a> ElfReader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, ImageError> {
+        if bytes.len() < 16 || !bytes.starts_with(ELF_MAGIC) {
+            return InvalidElfSnafu {
+                reason: "missing ELF header",
+            }
+            .fail();
+        }
+        let is_64 = match bytes[4] {
+            1 => false,
+            2 => true,
+            _ => {
+                return InvalidElfSnafu {
+                    reason: "invalid class",
+                }
+                .fail()
+            }
+        };
+        let big_endian = match bytes[5] {
+            1 => false,
+            2 => true,
+            _ => {
+                return InvalidElfSnafu {
+                    reason: "invalid data encoding",
+                }
+                .fail()
+            }
+        };
+        Ok(Self {
+            bytes,
+            is_64,
+            big_endian,
+        })
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<&'a [u8], ImageError> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.bytes.get(start..start.checked_add(len)?))
+            .ok_or(ImageError::InvalidElf {
+                reason: "truncated file",
+            })
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, ImageError> {
+        let b = self.read(offset, 2)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, ImageError> {
+        let b = self.read(offset, 4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, ImageError> {
+        let b = self.read(offset, 8)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u64::from_be_bytes(b)
+        } else {
+            u64::from_le_bytes(b)
+        })
+    }
+
+    /// Read a field which is 4 bytes in a 32-bit file, and 8 bytes in a 64-bit file
+    fn word(&self, offset: u64) -> Result<u64, ImageError> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    fn load_segments(&self) -> Result<Vec<ElfSegment<'a>>, ImageError> {
+        let (phoff, phentsize, phnum) = if self.is_64 {
+            (self.u64(0x20)?, self.u16(0x36)?, self.u16(0x38)?)
+        } else {
+            (self.u32(0x1C)? as u64, self.u16(0x2A)?, self.u16(0x2C)?)
+        };
+
+        let mut segments = Vec::new();
+        for i in 0..phnum as u64 {
+            let ph = phoff + i * phentsize as u64;
+            if self.u32(ph)? != PT_LOAD {
+                continue;
+            }
+            let (offset, paddr, filesz) = if self.is_64 {
+                (
+                    self.word(ph + 0x08)?,
+                    self.word(ph + 0x18)?,
+                    self.word(ph + 0x20)?,
+                )
+            } else {
+                (
+                    self.word(ph + 0x04)?,
+                    self.word(ph + 0x0C)?,
+                    self.word(ph + 0x10)?,
+                )
+            };
+            // Segments with no file data, e.g. .bss, are not programmed
+            if filesz == 0 {
+                continue;
+            }
+            let len = usize::try_from(filesz).map_err(|_| ImageError::InvalidElf {
+                reason: "segment too large",
+            })?;
+            segments.push(ElfSegment {
+                address: paddr,
+                data: self.read(offset, len)?,
+            });
+        }
+        Ok(segments)
+    }
+}
+
+/// Error returned by [`flash()`]
+#[derive(Debug, Snafu)]
+pub enum FlashError {
+    /// An SDO transfer with the node failed
+    #[snafu(display("SDO error: {source}"), context(false))]
+    Sdo {
+        /// The SDO client error
+        source: SdoClientError,
+    },
+    /// The node does not support the bootloader
+    #[snafu(display("The node does not support the bootloader"))]
+    BootloaderNotSupported,
+    /// The node was commanded to reset to its bootloader, but did not do so
+    #[snafu(display("The node did not enter its bootloader"))]
+    BootloaderNotEntered,
+    /// The node did not send a boot-up message within the boot timeout after a reset
+    #[snafu(display("The node did not come back online after a reset"))]
+    BootTimeout,
+    /// The requested section does not exist
+    #[snafu(display("The node has no bootloader section {section}"))]
+    NoSuchSection {
+        /// The requested section
+        section: u8,
+    },
+    /// No section was found to program in the inactive bank of a dual-bank device
+    #[snafu(display("The node has no section in its inactive bank"))]
+    NoInactiveSection,
+    /// The CRC reported by the node does not match the image
+    #[snafu(display(
+        "Section CRC mismatch: expected 0x{expected:08X}, node reported 0x{actual:08X}"
+    ))]
+    CrcMismatch {
+        /// The CRC32 of the image
+        expected: u32,
+        /// The CRC32 reported by the node
+        actual: u32,
+    },
+    /// The node did not report the section as verified after the verify command
+    #[snafu(display("The node did not verify section {section}"))]
+    NotVerified {
+        /// The section being programmed
+        section: u8,
+    },
+}
+
+/// Options for [`flash()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlashOptions {
+    /// The section to program
+    ///
+    /// If None, the first section in the inactive bank is programmed on a dual-bank device, and
+    /// section 0 on any other device.
+    pub section: Option<u8>,
+    /// On a dual-bank device, command a swap to the programmed bank after it is verified
+    pub swap: bool,
+    /// Reset the node once it is programmed, and wait for it to boot
+    pub reset: bool,
+    /// The time to wait for the node to boot after a reset
+    pub boot_timeout: Duration,
+    /// The number of bytes written in each block transfer
+    ///
+    /// Each transfer appends to the section, and progress is reported after each one.
+    pub chunk_size: usize,
+}
+
+impl Default for FlashOptions {
+    fn default() -> Self {
+        Self {
+            section: None,
+            swap: true,
+            reset: true,
+            boot_timeout: Duration::from_secs(5),
+            chunk_size: 4096,
+        }
+    }
+}
+
+/// The steps of a firmware update, reported by [`flash()`] as they begin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlashStep {
+    /// The node is being reset into its bootloader
+    EnteringBootloader,
+    /// The section is being erased
+    Erasing {
+        /// The section being programmed
+        section: u8,
+    },
+    /// Image data is being written
+    Programming {
+        /// The number of bytes written so far
+        written: usize,
+        /// The size of the image
+        total: usize,
+    },
+    /// The section CRC is being checked
+    Verifying {
+        /// The CRC32 of the image
+        crc: u32,
+    },
+    /// The dual-bank device is being commanded to swap banks
+    Swapping,
+    /// The node is being reset to run the new image
+    Resetting,
+}
+
+/// Program a firmware image into a node
+///
+/// See the [module documentation](self) for the sequence of operations. `progress` is called as
+/// each step begins, and after each chunk of image data is written.
+///
+/// Returns the section which was programmed.
+pub async fn flash<S: AsyncCanSender, R: AsyncCanReceiver>(
+    device: &mut Device<S, R>,
+    image: &[u8],
+    options: &FlashOptions,
+    mut progress: impl FnMut(FlashStep),
+) -> Result<u8, FlashError> {
+    let config = device.read_u32(BOOTLOADER_INFO_INDEX, 1).await?;
+    if config & CONFIG_SUPPORTED == 0 {
+        return BootloaderNotSupportedSnafu.fail();
+    }
+    let dual_bank = device.read_u8(BOOTLOADER_INFO_INDEX, 0).await? >= 5;
+
+    // A dual-bank device programs its inactive bank while running the application
+    if config & CONFIG_CAN_RESET != 0 && !dual_bank {
+        progress(FlashStep::EnteringBootloader);
+        device.reset_to_bootloader().await?;
+        wait_boot(device, options.boot_timeout).await?;
+        let config = device.read_u32(BOOTLOADER_INFO_INDEX, 1).await?;
+        if config & CONFIG_CAN_RESET != 0 {
+            return BootloaderNotEnteredSnafu.fail();
+        }
+    }
+
+    let num_sections = device
+        .read_u8(BOOTLOADER_INFO_INDEX, 2)
+        .await?
+        .min(MAX_SECTIONS);
+    let section = match options.section {
+        Some(section) if section < num_sections => section,
+        Some(section) => return NoSuchSectionSnafu { section }.fail(),
+        None if dual_bank => {
+            let active = device.read_u8(BOOTLOADER_INFO_INDEX, 4).await?;
+            let mut inactive_section = None;
+            for section in 0..num_sections {
+                let bank = device.read_u8(section_index(section), 7).await?;
+                if bank != 0 && bank != active {
+                    inactive_section = Some(section);
+                    break;
+                }
+            }
+            inactive_section.ok_or(FlashError::NoInactiveSection)?
+        }
+        None if num_sections > 0 => 0,
+        None => return NoSuchSectionSnafu { section: 0 }.fail(),
+    };
+    let index = section_index(section);
+
+    progress(FlashStep::Erasing { section });
+    device.write_u32(index, 3, BOOTLOADER_ERASE_CMD).await?;
+
+    let total = image.len();
+    progress(FlashStep::Programming { written: 0, total });
+    let mut written = 0;
+    for chunk in image.chunks(options.chunk_size.max(1)) {
+        device.sdo().block_download(index, 4, chunk).await?;
+        written += chunk.len();
+        progress(FlashStep::Programming { written, total });
+    }
+
+    let crc = crc32(image);
+    progress(FlashStep::Verifying { crc });
+    let actual = device.read_u32(index, 5).await?;
+    if actual != crc {
+        return CrcMismatchSnafu {
+            expected: crc,
+            actual,
+        }
+        .fail();
+    }
+    device.write_u32(index, 6, crc).await?;
+    if device.read_u8(index, 1).await? & MODE_VERIFIED == 0 {
+        return NotVerifiedSnafu { section }.fail();
+    }
+
+    if dual_bank && options.swap {
+        progress(FlashStep::Swapping);
+        device
+            .write_u32(BOOTLOADER_INFO_INDEX, 5, BOOTLOADER_SWAP_CMD)
+            .await?;
+    }
+
+    if options.reset {
+        progress(FlashStep::Resetting);
+        device.reset().await?;
+        wait_boot(device, options.boot_timeout).await?;
+    }
+
+    Ok(section)
+}
+
+fn section_index(section: u8) -> u16 {
+    BOOTLOADER_SECTION_BASE_INDEX + section as u16
+}
+
+async fn wait_boot<S: AsyncCanSender, R: AsyncCanReceiver>(
+    device: &mut Device<S, R>,
+    timeout: Duration,
+) -> Result<(), FlashError> {
+    match device.wait_online(timeout).await {
+        Ok(_) => Ok(()),
+        Err(SdoClientError::NoResponse) => BootTimeoutSnafu.fail(),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a little endian ELF32 file with the given (paddr, data) PT_LOAD segments
+    fn build_elf32(segments: &[(u32, &[u8])]) -> Vec<u8> {
+        const EHDR_SIZE: usize = 52;
+        const PHDR_SIZE: usize = 32;
+        let mut elf = vec![0u8; EHDR_SIZE];
+        elf[..4].copy_from_slice(ELF_MAGIC);
+        elf[4] = 1; // ELFCLASS32
+        elf[5] = 1; // ELFDATA2LSB
+        elf[0x1C..0x20].copy_from_slice(&(EHDR_SIZE as u32).to_le_bytes());
+        elf[0x2A..0x2C].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+        elf[0x2C..0x2E].copy_from_slice(&(segments.len() as u16 + 1).to_le_bytes());
+
+        let mut data_offset = EHDR_SIZE + PHDR_SIZE * (segments.len() + 1);
+        for (paddr, data) in segments {
+            let mut ph = [0u8; PHDR_SIZE];
+            ph[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            ph[4..8].copy_from_slice(&(data_offset as u32).to_le_bytes());
+            // The virtual address differs from the physical address, as for initialized data
+            ph[8..12].copy_from_slice(&0x2000_0000u32.to_le_bytes());
+            ph[12..16].copy_from_slice(&paddr.to_le_bytes());
+            ph[16..20].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            ph[20..24].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            elf.extend_from_slice(&ph);
+            data_offset += data.len();
+        }
+        // A .bss segment, with no file data
+        let mut ph = [0u8; PHDR_SIZE];
+        ph[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        ph[12..16].copy_from_slice(&0x2000_1000u32.to_le_bytes());
+        ph[20..24].copy_from_slice(&0x100u32.to_le_bytes());
+        elf.extend_from_slice(&ph);
+
+        for (_, data) in segments {
+            elf.extend_from_slice(data);
+        }
+        elf
+    }
+
+    #[test]
+    fn test_load_elf() {
+        let elf = build_elf32(&[(0x0800_0010, &[5, 6]), (0x0800_0000, &[1, 2, 3, 4])]);
+        let image = FirmwareImage::from_bytes(elf).unwrap();
+        assert_eq!(Some(0x0800_0000), image.address);
+        let mut expected = vec![1, 2, 3, 4];
+        expected.resize(0x10, 0xFF);
+        expected.extend_from_slice(&[5, 6]);
+        assert_eq!(expected, image.data);
+    }
+
+    #[test]
+    fn test_load_bin() {
+        let image = FirmwareImage::from_bytes(vec![1, 2, 3]).unwrap();
+        assert_eq!(None, image.address);
+        assert_eq!(vec![1, 2, 3], image.data);
+    }
+
+    #[test]
+    fn test_invalid_elf() {
+        let mut elf = build_elf32(&[(0x0800_0000, &[1, 2, 3, 4])]);
+        elf.truncate(60);
+        assert!(matches!(
+            FirmwareImage::from_bytes(elf),
+            Err(ImageError::InvalidElf { .. })
+        ));
+
+        let overlapping = build_elf32(&[(0x0800_0000, &[1, 2, 3, 4]), (0x0800_0002, &[5])]);
+        assert!(matches!(
+            FirmwareImage::from_elf(&overlapping),
+            Err(ImageError::InvalidElf { .. })
+        ));
+
+        let empty = build_elf32(&[]);
+        assert!(matches!(
+            FirmwareImage::from_elf(&empty),
+            Err(ImageError::InvalidElf { .. })
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+//! Negotiation between redundant masters
+//!
+//! A network may include several controllers which are able to act as NMT master and SYNC
+//! producer, so that the network keeps running if one of them fails. [`FlyingMaster`] arbitrates
+//! which of them is active, and fails over automatically when the active master stops responding.
+//!
+//! This is a simplified version of the flying master mechanism described in CiA 302-2, and is not
+//! interoperable with other implementations of it. Each master is identified by a [`MasterId`],
+//! made up of a configured priority and its node ID; the master with the lowest ID is active.
+//!
+//! - When a master starts, or when negotiation is forced, it waits for a time slot derived from its
+//!   ID -- `priority * priority_time_slot + node_id * device_time_slot` -- and then, if no better
+//!   master has announced itself in that time, it becomes active and announces itself.
+//! - The active master announces itself every `announce_period`. If it receives an announcement
+//!   from a better master, it steps down. If it receives one from a worse master, it announces
+//!   itself immediately so that the other master steps down.
+//! - A standby master which has not received an announcement for `timeout` starts a new
+//!   negotiation. A standby master which sees a worse master become active also negotiates, so
+//!   that the best available master always ends up active.
+//!
+//! The application should check the role returned by [`FlyingMaster::process`], and only send NMT
+//! commands and SYNCs (e.g. with [`SyncProducer`](crate::SyncProducer)) while it is active.
+use std::time::Duration;
+
+use tokio::time::Instant;
+use zencan_common::{
+    messages::{FlyingMasterMessage, MasterId},
+    traits::{AsyncCanReceiver, AsyncCanSender},
+};
+
+/// Configuration for a [`FlyingMaster`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlyingMasterConfig {
+    /// The priority of this master. Lower values have higher priority.
+    pub priority: u8,
+    /// The node ID of this master, which breaks ties between masters with the same priority
+    pub node_id: u8,
+    /// The period at which the active master announces itself
+    pub announce_period: Duration,
+    /// The time without an announcement after which a standby master starts a new negotiation
+    ///
+    /// This should be several times `announce_period`.
+    pub timeout: Duration,
+    /// The negotiation delay added for each step of priority
+    ///
+    /// This should be longer than `node_id * device_time_slot` for the highest node ID in use, so
+    /// that priority always takes precedence over node ID.
+    pub priority_time_slot: Duration,
+    /// The negotiation delay added for each node ID
+    pub device_time_slot: Duration,
+}
+
+impl FlyingMasterConfig {
+    /// Create a config with the default timing
+    pub fn new(priority: u8, node_id: u8) -> Self {
+        Self {
+            priority,
+            node_id,
+            announce_period: Duration::from_millis(100),
+            timeout: Duration::from_millis(500),
+            priority_time_slot: Duration::from_millis(200),
+            device_time_slot: Duration::from_millis(1),
+        }
+    }
+
+    /// Get the ID of the master
+    pub fn id(&self) -> MasterId {
+        MasterId {
+            priority: self.priority,
+            node_id: self.node_id,
+        }
+    }
+
+    fn negotiation_delay(&self) -> Duration {
+        self.priority_time_slot * self.priority as u32 + self.device_time_slot * self.node_id as u32
+    }
+}
+
+/// The current role of a [`FlyingMaster`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MasterRole {
+    /// Waiting for the negotiation time slot
+    Negotiating,
+    /// This master is the active master
+    Active,
+    /// Another master is active
+    Standby {
+        /// The active master
+        active: MasterId,
+    },
+}
+
+/// Arbitrates with other masters on the bus to decide which one is active
+///
+/// See the [module docs](self).
+///
+/// ```ignore
+/// let mut master = FlyingMaster::new(FlyingMasterConfig::new(0, 1), sender, receiver);
+/// loop {
+///     if master.process().await? == MasterRole::Active {
+///         // Act as NMT master
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FlyingMaster<S, R> {
+    config: FlyingMasterConfig,
+    sender: S,
+    receiver: R,
+    role: MasterRole,
+    /// The time of the next negotiation slot, announcement, or timeout, depending on the role
+    deadline: Instant,
+}
+
+impl<S: AsyncCanSender, R: AsyncCanReceiver> FlyingMaster<S, R> {
+    /// Create a new FlyingMaster, which begins negotiating immediately
+    ///
+    /// The receiver is used only for negotiation messages; other messages are discarded.
+    pub fn new(config: FlyingMasterConfig, sender: S, receiver: R) -> Self {
+        Self {
+            config,
+            sender,
+            receiver,
+            role: MasterRole::Negotiating,
+            deadline: Instant::now() + config.negotiation_delay(),
+        }
+    }
+
+    /// Get the current role
+    pub fn role(&self) -> MasterRole {
+        self.role
+    }
+
+    /// Returns true if this master is the active master
+    pub fn is_active(&self) -> bool {
+        self.role == MasterRole::Active
+    }
+
+    /// Get the ID of the active master, if one is known
+    pub fn active_master(&self) -> Option<MasterId> {
+        match self.role {
+            MasterRole::Negotiating => None,
+            MasterRole::Active => Some(self.config.id()),
+            MasterRole::Standby { active } => Some(active),
+        }
+    }
+
+    /// Request that all masters, including this one, restart negotiation
+    pub async fn force_negotiation(&mut self) -> Result<(), S::Error> {
+        self.sender
+            .send(FlyingMasterMessage::ForceNegotiation.into())
+            .await?;
+        self.begin_negotiation();
+        Ok(())
+    }
+
+    /// Wait for the next negotiation message or timer event, handle it, and return the new role
+    ///
+    /// This should be called continuously, e.g. in a loop on a dedicated task.
+    pub async fn process(&mut self) -> Result<MasterRole, S::Error> {
+        tokio::select! {
+            msg = self.receiver.recv() => {
+                if let Ok(Ok(msg)) = msg.map(FlyingMasterMessage::try_from) {
+                    self.handle_message(msg).await?;
+                }
+            }
+            _ = tokio::time::sleep_until(self.deadline) => self.handle_deadline().await?,
+        }
+        Ok(self.role)
+    }
+
+    async fn handle_message(&mut self, msg: FlyingMasterMessage) -> Result<(), S::Error> {
+        let own_id = self.config.id();
+        let other = match msg {
+            FlyingMasterMessage::ForceNegotiation => {
+                self.begin_negotiation();
+                return Ok(());
+            }
+            // Our own announcement, echoed back by the bus
+            FlyingMasterMessage::ActiveMaster(other) if other == own_id => return Ok(()),
+            FlyingMasterMessage::ActiveMaster(other) => other,
+        };
+
+        match self.role {
+            _ if other < own_id => self.enter_standby(other),
+            // A worse master is active; negotiate so that this master takes over
+            MasterRole::Standby { .. } => self.begin_negotiation(),
+            // This master will win its time slot, and announce itself then
+            MasterRole::Negotiating => (),
+            MasterRole::Active => self.announce().await?,
+        }
+        Ok(())
+    }
+
+    async fn handle_deadline(&mut self) -> Result<(), S::Error> {
+        match self.role {
+            MasterRole::Negotiating | MasterRole::Active => {
+                self.role = MasterRole::Active;
+                self.announce().await?;
+            }
+            MasterRole::Standby { .. } => self.begin_negotiation(),
+        }
+        Ok(())
+    }
+
+    async fn announce(&mut self) -> Result<(), S::Error> {
+        self.deadline = Instant::now() + self.config.announce_period;
+        self.sender
+            .send(FlyingMasterMessage::ActiveMaster(self.config.id()).into())
+            .await
+    }
+
+    fn begin_negotiation(&mut self) {
+        self.role = MasterRole::Negotiating;
+        self.deadline = Instant::now() + self.config.negotiation_delay();
+    }
+
+    fn enter_standby(&mut self, active: MasterId) {
+        self.role = MasterRole::Standby { active };
+        self.deadline = Instant::now() + self.config.timeout;
+    }
+}
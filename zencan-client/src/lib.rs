@@ -3,7 +3,29 @@
 //! The crate provides utilities for communicating with nodes, including:
 //!
 //! - An [SDO client](SdoClient) for reading/writing a node's object dictionary via it's SDO server
+//! - A [Device] handle, which combines SDO access, NMT commands, and heartbeat monitoring for a
+//!   single node
 //! - An [LSS master](LssMaster) for discovering and configuring un-configured nodes with IDs
+//! - A [SYNC producer](SyncProducer) for driving synchronous PDOs at a fixed period
+//! - [Flying master](FlyingMaster) negotiation, for networks with redundant masters
+//! - [Firmware updates](flash) for nodes which support the zencan bootloader
+//! - [Backup and restore](backup) of node parameters, e.g. to configure a replacement node
+//! - [Applying DCF files](dcf) exported from vendor configuration tools to nodes
+//! - A [Bridge](bridge::Bridge) for forwarding messages between CAN interfaces, e.g. in a gateway
+//! - A [cannelloni](cannelloni) compatible UDP/TCP transport, for accessing a bus attached to a
+//!   remote gateway
+//! - An [slcan](slcan) transport, for serial line CAN adapters on platforms without socketcan
+//! - A PCAN-Basic backend for PEAK-System adapters on Windows, macOS and Linux, with the `pcan`
+//!   feature
+//! - A [simulated clock](clock::SimClock), for testing timeout behavior without waiting
+//! - [Opening any of the above by name](transport::open_can), e.g. `slcan:/dev/ttyACM0@500k`, so
+//!   that tools can select an interface at runtime
+//! - A [JSON-RPC server](rpc::RpcServer), which allows test automation written in other languages
+//!   to access a bus over stdio or a Unix socket
+//! - A [BusMultiplexer] for sharing one CAN interface among several protocol clients and
+//!   application code, e.g. an SDO client, an NMT master, and
+//!   [subscriptions](SharedReceiver::subscribe) to a node's TPDOs, without them stealing each
+//!   other's messages
 //! - A [BusManager] which is intended to be the engine behind an application, such as `zencan-cli`,
 //!   keeping track of nodes, and providing an API for managing them.
 //! - Defining a [NodeConfig](crate::common::node_configuration::NodeConfig) TOML file format, which allows for storing and loading node configuration (primarily
@@ -14,6 +36,44 @@
 //!
 //! This should be considered very alpha, with important missing features, and potentially frequent
 //! breaking API changes.
+//!
+//! # Metrics
+//!
+//! With the `metrics` feature enabled, the client records the following metrics via the
+//! [metrics](https://docs.rs/metrics) facade, so that a long-running service can export them with
+//! any `metrics` exporter, e.g. for Prometheus. Metrics are labelled with the `node` ID derived from
+//! the message COB ID, or "none" for messages which do not belong to a node.
+//!
+//! | Metric                              | Type      | Labels                   | Recorded by         |
+//! |-------------------------------------|-----------|--------------------------|---------------------|
+//! | `zencan_sdo_transfer_seconds`       | histogram | `node`, `op`, `result`   | [SdoClient]         |
+//! | `zencan_sdo_timeouts_total`         | counter   | `node`                   | [SdoClient]         |
+//! | `zencan_sdo_send_retries_total`     | counter   | `node`                   | [SdoClient]         |
+//! | `zencan_frames_sent_total`          | counter   | `node`                   | [BusManager]        |
+//! | `zencan_frames_received_total`      | counter   | `node`                   | [BusManager]        |
+//! | `zencan_frames_dropped_total`       | counter   |                          | [BusManager]        |
+//! | `zencan_heartbeat_interval_seconds` | histogram | `node`                   | [BusManager]        |
+//!
+//! The `op` label is one of `upload`, `download`, `block_upload` or `block_download`, and `result`
+//! is one of `ok`, `abort`, `timeout` or `error`.
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature enabled, the client creates [tracing](https://docs.rs/tracing) spans
+//! at the `DEBUG` level for each operation on the bus, so that their timing and outcome can be
+//! followed with any `tracing` subscriber.
+//!
+//! | Span                 | Fields                              | Created by                  |
+//! |----------------------|-------------------------------------|-----------------------------|
+//! | `sdo_upload`         | `server`, `index`, `sub`, `outcome` | [SdoClient]                 |
+//! | `sdo_download`       | `server`, `index`, `sub`, `outcome` | [SdoClient]                 |
+//! | `sdo_block_upload`   | `server`, `index`, `sub`, `outcome` | [SdoClient]                 |
+//! | `sdo_block_download` | `server`, `index`, `sub`, `outcome` | [SdoClient]                 |
+//! | `lss_*`              | The method arguments                | [LssMaster]                 |
+//! | `nmt_command`        | The command, and the `node` ID      | [BusManager], [Device], [NmtMaster](nmt_master::NmtMaster) |
+//!
+//! The `outcome` of an SDO transfer takes the same values as the `result` metric label. LSS and NMT
+//! spans record their result, or error, as an event when they complete.
 #![warn(
     missing_docs,
     missing_debug_implementations,
@@ -22,14 +82,43 @@
 #![allow(clippy::single_match)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod backup;
+pub mod bridge;
 mod bus_manager;
+pub mod cannelloni;
+pub mod clock;
+pub mod dcf;
+mod delta_sync;
+mod device;
+pub mod dump;
+mod endianness;
+pub mod flash;
+mod flying_master;
 mod lss_master;
 pub mod nmt_master;
+mod object_info;
+#[cfg(feature = "pcan")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pcan")))]
+pub mod pcan;
+pub mod rpc;
 mod sdo_client;
+pub mod slcan;
+mod sync_producer;
+mod telemetry;
+pub mod transport;
 pub use zencan_common as common;
 
-pub use bus_manager::BusManager;
+pub use bus_manager::{
+    BusManager, BusMultiplexer, MessageFilter, NoMsgError, PdoScanResult, SharedReceiver,
+    SharedReceiverChannel, SharedSender, Subscription,
+};
 #[cfg(all(feature = "socketcan", target_os = "linux"))]
 pub use common::open_socketcan;
-pub use lss_master::{LssError, LssMaster};
-pub use sdo_client::{RawAbortCode, SdoClient, SdoClientError};
+pub use delta_sync::DeltaSync;
+pub use device::Device;
+pub use endianness::{Endianness, EndiannessProfile};
+pub use flying_master::{FlyingMaster, FlyingMasterConfig, MasterRole};
+pub use lss_master::{FastScanProgress, LssError, LssMaster};
+pub use object_info::{ObjectInfo, SdoValue};
+pub use sdo_client::{ErrorHistoryEntry, RawAbortCode, SdoClient, SdoClientError};
+pub use sync_producer::SyncProducer;
@@ -1,20 +1,24 @@
 //! A
-use core::time::Duration;
+use core::{ops::ControlFlow, time::Duration};
+use std::sync::Arc;
 
-use tokio::time::timeout_at;
 use zencan_common::{
     lss::{LssIdentity, LssRequest, LssResponse, LssState, LSS_FASTSCAN_CONFIRM},
+    node_id::ConfiguredNodeId,
     traits::{AsyncCanReceiver, AsyncCanSender},
     NodeId,
 };
 
 use snafu::Snafu;
 
+use crate::clock::{timeout_at, Clock, TokioClock};
+
 #[derive(Debug)]
 /// Struct to interact with nodes using the LSS protocol
 pub struct LssMaster<S, R> {
     sender: S,
     receiver: R,
+    clock: Arc<dyn Clock>,
 }
 
 /// Error returned by [`LssMaster`]
@@ -74,6 +78,36 @@ pub enum LssError {
         /// Only supposed to be valid when error is 255
         spec_error: u8,
     },
+    /// The operation was cancelled by the caller
+    #[snafu(display("LSS operation cancelled"))]
+    Cancelled,
+}
+
+/// Progress of an LSS fast scan, reported by [`LssMaster::fast_scan_with_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FastScanProgress {
+    /// The identity sub-field currently being resolved (0 = vendor, 1 = product, 2 = revision, 3 =
+    /// serial)
+    pub sub: u8,
+    /// The number of identity bits resolved so far, out of 128
+    pub bits_resolved: u8,
+}
+
+impl FastScanProgress {
+    /// Total number of bits in an LSS identity
+    pub const TOTAL_BITS: u8 = 128;
+
+    /// The number of identity bits which remain to be resolved
+    ///
+    /// The number of candidate identities remaining is 2^`bits_remaining`.
+    pub fn bits_remaining(&self) -> u8 {
+        Self::TOTAL_BITS - self.bits_resolved
+    }
+
+    /// The fraction of the scan completed, from 0.0 to 1.0
+    pub fn fraction_complete(&self) -> f32 {
+        self.bits_resolved as f32 / Self::TOTAL_BITS as f32
+    }
 }
 
 impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
@@ -87,12 +121,35 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     ///
     /// When using socketcan, these can be created with [`crate::open_socketcan`].
     pub fn new(sender: S, receiver: R) -> Self {
-        Self { sender, receiver }
+        Self {
+            sender,
+            receiver,
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Set the clock used for response timeouts
+    ///
+    /// The default is the [`TokioClock`]. A [`SimClock`](crate::clock::SimClock) allows scans to
+    /// be tested without waiting.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
     }
 
     /// Configure an LSS slave with known identity
     ///
     /// If you know the 128-bit identity value for a node, you can configure it this way.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_configure_by_identity",
+            skip_all,
+            fields(identity = ?identity, node_id = node_id.raw()),
+            ret,
+            err(Display),
+        )
+    )]
     pub async fn configure_by_identity(
         &mut self,
         identity: LssIdentity,
@@ -117,6 +174,16 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     }
 
     /// Send a sequence of messages to put a single node into configuration mode based on its identity
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_enter_config",
+            skip(self),
+            ret,
+            err(Display)
+        )
+    )]
     pub async fn enter_config_by_identity(
         &mut self,
         vendor_id: u32,
@@ -160,6 +227,16 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     /// * `table` - The index of the table of baud rate settings to use (0 for the default CANOpen
     ///   table)
     /// * `index` - The index into the table of the baud rate setting to use
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_set_baud_rate",
+            skip(self),
+            ret,
+            err(Display)
+        )
+    )]
     pub async fn set_baud_rate(&mut self, table: u8, index: u8) -> Result<(), LssError> {
         const RESPONSE_TIMEOUT: Duration = Duration::from_millis(50);
         match self
@@ -186,6 +263,17 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     ///
     /// Returns Err(LssError::Timeout) if the node does not respond to the command, or
     /// Err(LssError::ConfigError) if the node responds with an error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_set_node_id",
+            skip_all,
+            fields(node_id = node_id.raw()),
+            ret,
+            err(Display),
+        )
+    )]
     pub async fn set_node_id(&mut self, node_id: NodeId) -> Result<(), LssError> {
         const RESPONSE_TIMEOUT: Duration = Duration::from_millis(50);
         match self
@@ -214,6 +302,16 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     ///
     /// Returns Err(LssError::Timeout) if the node does not respond to the command, or
     /// Err(LssError::ConfigError) if the node responds with an error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_store_config",
+            skip(self),
+            ret,
+            err(Display)
+        )
+    )]
     pub async fn store_config(&mut self) -> Result<(), LssError> {
         const RESPONSE_TIMEOUT: Duration = Duration::from_millis(50);
         match self
@@ -239,10 +337,47 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     ///   responsiveness of the slaves, and on the amount of bus traffic. If the timeout is set too
     ///   short, the scan may fail to find existing nodes.
     pub async fn fast_scan(&mut self, timeout: Duration) -> Option<LssIdentity> {
+        self.fast_scan_with_progress(timeout, |_| ControlFlow::Continue(()))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Perform a fast scan, reporting progress and allowing the scan to be cancelled
+    ///
+    /// A complete scan requires 132 request messages, each of which waits the full `timeout`, so on
+    /// a slow bus a scan can take several seconds. `progress` is called after each bit is
+    /// resolved. Returning [`ControlFlow::Break`] from it cancels the scan, in which case
+    /// `Err(LssError::Cancelled)` is returned. A cancelled scan leaves the slaves part way through
+    /// the fast scan sequence; they are reset by the next scan.
+    ///
+    /// Returns `Ok(None)` if no unconfigured node responds.
+    ///
+    /// # Arguments
+    /// * `timeout` - The duration of time to wait for responses after each message. See
+    ///   [`fast_scan`](Self::fast_scan).
+    /// * `progress` - Callback receiving a [`FastScanProgress`] as the scan proceeds
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_fast_scan",
+            skip_all,
+            fields(timeout = ?timeout),
+            ret,
+            err(Display),
+        )
+    )]
+    pub async fn fast_scan_with_progress(
+        &mut self,
+        timeout: Duration,
+        mut progress: impl FnMut(FastScanProgress) -> ControlFlow<()>,
+    ) -> Result<Option<LssIdentity>, LssError> {
         let mut id = [0, 0, 0, 0];
         let mut sub = 0;
         let mut next = 0;
         let mut bit_check;
+        let mut bits_resolved = 0;
 
         let mut send_fs = async |id: &[u32; 4], bit_check: u8, sub: u8, next: u8| -> bool {
             // Unlike send_and_receive, this function always waits the full timeout, because we don't know
@@ -260,13 +395,13 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
                 .await
                 .ok();
 
-            let wait_until = tokio::time::Instant::now() + timeout;
+            let wait_until = self.clock.now() + timeout;
             let mut resp_flag = false;
             loop {
-                match timeout_at(wait_until, self.receiver.recv()).await {
+                match timeout_at(&*self.clock, wait_until, self.receiver.recv()).await {
                     // timeout
-                    Err(_) => break,
-                    Ok(Ok(msg)) => {
+                    None => break,
+                    Some(Ok(msg)) => {
                         if let Ok(LssResponse::IdentifySlave) = LssResponse::try_from(msg) {
                             resp_flag = true;
                         }
@@ -280,32 +415,141 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
         // The first message resets the LSS state machines, and a response confirms that there is at
         // least one unconfigured slave to discover
         if !send_fs(&id, LSS_FASTSCAN_CONFIRM, sub, next).await {
-            return None;
+            return Ok(None);
         }
         while sub < 4 {
             bit_check = 32;
             while bit_check > 0 {
+                if progress(FastScanProgress { sub, bits_resolved }).is_break() {
+                    return Err(LssError::Cancelled);
+                }
                 bit_check -= 1;
                 if !send_fs(&id, bit_check, sub, next).await {
                     id[sub as usize] |= 1 << bit_check;
                 }
+                bits_resolved += 1;
             }
             next = (sub + 1) % 4;
             if !send_fs(&id, bit_check, sub, next).await {
-                return None;
+                return Ok(None);
             }
             sub += 1;
         }
 
-        Some(LssIdentity {
+        // A final report once all bits are resolved. The scan is complete, so a request to cancel
+        // is ignored.
+        let _ = progress(FastScanProgress {
+            sub: 3,
+            bits_resolved,
+        });
+
+        Ok(Some(LssIdentity {
             vendor_id: id[0],
             product_code: id[1],
             revision: id[2],
             serial: id[3],
-        })
+        }))
+    }
+
+    /// Find all unconfigured nodes on the bus
+    ///
+    /// The fast scan is repeated until no more nodes respond. Each node found enters the
+    /// Configuring state, in which it no longer responds to the scan, so each scan finds a new node.
+    /// Once all nodes are found, they are returned to the Waiting state.
+    ///
+    /// # Arguments
+    /// * `timeout` - The duration of time to wait for responses after each message. See
+    ///   [`fast_scan`](Self::fast_scan).
+    pub async fn fast_scan_all(&mut self, timeout: Duration) -> Vec<LssIdentity> {
+        self.fast_scan_all_with_progress(timeout, |_, _| ControlFlow::Continue(()))
+            .await
+    }
+
+    /// Find all unconfigured nodes on the bus, reporting progress as the scan proceeds
+    ///
+    /// Same as [`fast_scan_all`](Self::fast_scan_all), but `progress` is called with the number of
+    /// nodes found so far, and the progress of the scan for the next node. Returning
+    /// [`ControlFlow::Break`] from `progress` cancels the scan, and the nodes found before
+    /// cancellation are returned.
+    pub async fn fast_scan_all_with_progress(
+        &mut self,
+        timeout: Duration,
+        mut progress: impl FnMut(usize, FastScanProgress) -> ControlFlow<()>,
+    ) -> Vec<LssIdentity> {
+        let mut identities = Vec::new();
+        self.return_all_to_waiting(timeout).await;
+        loop {
+            let found = identities.len();
+            match self
+                .fast_scan_with_progress(timeout, |p| progress(found, p))
+                .await
+            {
+                Ok(Some(identity)) => identities.push(identity),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        self.set_global_mode(LssState::Waiting).await;
+        identities
+    }
+
+    /// Find all unconfigured nodes on the bus, and assign each a node ID from a pool
+    ///
+    /// Each node found by a fast scan is assigned the next ID from `node_ids`, and, if `store` is
+    /// true, commanded to store its configuration. It is then returned to the Waiting state. Once it
+    /// has applied its new ID, it is configured, and no longer responds to the scan. This simplifies
+    /// commissioning a set of identical devices, which can be given sequential IDs, e.g.:
+    ///
+    /// ```ignore
+    /// let pool = (10..=20).map(|id| ConfiguredNodeId::new(id).unwrap());
+    /// let assigned = lss_master.fast_scan_assign(timeout, pool, true).await?;
+    /// ```
+    ///
+    /// The scan stops when no more nodes respond, or when the pool is exhausted, in which case any
+    /// remaining nodes are left unconfigured. Returns the identity of each node found, with the ID
+    /// assigned to it.
+    ///
+    /// If a node fails to accept its ID or store its configuration, all nodes are returned to the
+    /// Waiting state and the error is returned. Nodes configured before the failure keep their new
+    /// IDs.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "lss_fast_scan_assign",
+            skip_all,
+            fields(timeout = ?timeout, store),
+            err(Display),
+        )
+    )]
+    pub async fn fast_scan_assign(
+        &mut self,
+        timeout: Duration,
+        node_ids: impl IntoIterator<Item = ConfiguredNodeId>,
+        store: bool,
+    ) -> Result<Vec<(LssIdentity, ConfiguredNodeId)>, LssError> {
+        let mut assigned = Vec::new();
+        self.return_all_to_waiting(timeout).await;
+        for node_id in node_ids {
+            let Some(identity) = self.fast_scan(timeout).await else {
+                break;
+            };
+            // The node just found is the only one in the Configuring state
+            let result = match self.set_node_id(NodeId::Configured(node_id)).await {
+                Ok(()) if store => self.store_config().await,
+                result => result,
+            };
+            self.return_all_to_waiting(timeout).await;
+            result?;
+            assigned.push((identity, node_id));
+        }
+        Ok(assigned)
     }
 
     /// Send command to the bus to set the LSS mode for all nodes
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "lss_set_global_mode", skip(self))
+    )]
     pub async fn set_global_mode(&mut self, mode: LssState) {
         // Send global mode to put all nodes into waiting state. No response expected.
         self.send_and_receive(
@@ -315,6 +559,16 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
         .await;
     }
 
+    /// Put all nodes into the Waiting state, before starting a fast scan
+    ///
+    /// A node handles one LSS request at a time, so the mode switch could be replaced by the first
+    /// scan request if it is sent immediately. Waiting `timeout` gives the nodes time to handle it,
+    /// and, for a node which was just assigned an ID, to apply its new ID.
+    async fn return_all_to_waiting(&mut self, timeout: Duration) {
+        self.set_global_mode(LssState::Waiting).await;
+        self.clock.sleep(timeout).await;
+    }
+
     async fn send_and_receive(
         &mut self,
         msg: LssRequest,
@@ -322,11 +576,11 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
     ) -> Option<LssResponse> {
         self.sender.send(msg.into()).await.ok()?;
 
-        let wait_until = tokio::time::Instant::now() + timeout;
+        let wait_until = self.clock.now() + timeout;
         loop {
-            match timeout_at(wait_until, self.receiver.recv()).await {
+            match timeout_at(&*self.clock, wait_until, self.receiver.recv()).await {
                 // Got a message
-                Ok(Ok(msg)) => {
+                Some(Ok(msg)) => {
                     match msg.try_into() {
                         Ok(lss_resp) => return Some(lss_resp),
                         // Failed to convert message into LSS response. Skip it.
@@ -334,12 +588,12 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> LssMaster<S, R> {
                     }
                 }
                 // `recv` returned without a message. Keep waiting.
-                Ok(Err(e)) => {
+                Some(Err(e)) => {
                     log::error!("Error reading can socket: {e:?}");
                     return None;
                 }
                 // Timeout waiting
-                Err(_) => return None,
+                None => return None,
             }
         }
     }
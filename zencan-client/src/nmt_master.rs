@@ -95,6 +95,11 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> NmtMaster<S, R> {
     }
 
     fn handle_heartbeat(&mut self, node: u8, state: NmtState, toggle: bool) {
+        // A boot-up message indicates that the node has just entered PreOperational
+        let state = match state {
+            NmtState::Bootup => NmtState::PreOperational,
+            s => s,
+        };
         // Find the node in the ordered list, inserting if needed.
         for i in 0..self.nodes.len() {
             let list_node = &mut self.nodes[i];
@@ -158,6 +163,10 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> NmtMaster<S, R> {
         self.send_nmt_cmd(NmtCommandSpecifier::Stop, node).await
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "nmt_command", skip(self), err(Debug))
+    )]
     async fn send_nmt_cmd(&mut self, cmd: NmtCommandSpecifier, node: u8) -> Result<()> {
         let message = NmtCommand { cs: cmd, node };
         self.sender.send(message.into()).await.map_err(|_| ())?;
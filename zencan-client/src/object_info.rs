@@ -0,0 +1,212 @@
+//! Object metadata used to check SDO downloads on the client side
+use std::collections::HashMap;
+
+use zencan_common::{
+    device_config::DeviceConfig,
+    objects::{DataType, SubInfo},
+    strings::is_visible_string,
+    unit_metadata::UnitMetadata,
+};
+
+/// A table of sub object metadata for a node
+///
+/// When an `ObjectInfo` is provided to an [`SdoClient`](crate::SdoClient), downloads to the
+/// sub objects it describes are checked before any message is sent, so that a mismatched size
+/// results in a descriptive client side error instead of a server abort. Sub objects which are not
+/// in the table are not checked.
+///
+/// It also holds the engineering unit metadata of sub objects, which can be used to display raw
+/// values as engineering values. This is loaded from a device config, or read from a node with
+/// [`SdoClient::read_unit_metadata`](crate::SdoClient::read_unit_metadata).
+#[derive(Clone, Debug, Default)]
+pub struct ObjectInfo {
+    subs: HashMap<(u16, u8), SubInfo>,
+    units: HashMap<(u16, u8), UnitMetadata>,
+}
+
+impl ObjectInfo {
+    /// Create an empty ObjectInfo
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an ObjectInfo describing all of the objects in a device config
+    pub fn from_device_config(config: &DeviceConfig) -> Self {
+        let mut info = Self::new();
+        for object in &config.objects {
+            for sub in 0..=255 {
+                if let Some(sub_info) = object.sub_info(sub) {
+                    info.insert(object.index, sub, sub_info);
+                }
+                if let Some(meta) = object.unit_metadata(sub) {
+                    info.insert_unit_metadata(object.index, sub, meta);
+                }
+            }
+        }
+        info
+    }
+
+    /// Add or replace the metadata for a sub object
+    pub fn insert(&mut self, index: u16, sub: u8, info: SubInfo) {
+        self.subs.insert((index, sub), info);
+    }
+
+    /// Get the metadata for a sub object
+    pub fn get(&self, index: u16, sub: u8) -> Option<&SubInfo> {
+        self.subs.get(&(index, sub))
+    }
+
+    /// Iterate over the metadata of all sub objects, sorted by index and sub index
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u8, &SubInfo)> {
+        let mut keys: Vec<_> = self.subs.keys().copied().collect();
+        keys.sort();
+        keys.into_iter()
+            .map(|(index, sub)| (index, sub, &self.subs[&(index, sub)]))
+    }
+
+    /// Add or replace the engineering unit metadata for a sub object
+    pub fn insert_unit_metadata(&mut self, index: u16, sub: u8, meta: UnitMetadata) {
+        self.units.insert((index, sub), meta);
+    }
+
+    /// Get the engineering unit metadata for a sub object
+    pub fn unit_metadata(&self, index: u16, sub: u8) -> Option<&UnitMetadata> {
+        self.units.get(&(index, sub))
+    }
+}
+
+/// A value to be written with [`SdoClient::write_value`](crate::SdoClient::write_value)
+///
+/// The value is converted to the data type of the target sub object, as given by the client's
+/// [`ObjectInfo`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SdoValue {
+    /// An integer value, which may be written to any integer, boolean, or real type which can
+    /// represent it
+    Integer(i64),
+    /// A floating point value, which may be written to a real type
+    Float(f64),
+    /// A boolean value, which may be written to a boolean or integer type
+    Bool(bool),
+    /// A string value, which may be written to a string type
+    String(String),
+    /// Raw bytes, which are written as-is
+    Bytes(Vec<u8>),
+}
+
+impl SdoValue {
+    /// Encode the value as the given data type
+    ///
+    /// Returns None if the value cannot be represented by the data type.
+    pub fn encode(&self, data_type: DataType) -> Option<Vec<u8>> {
+        fn int<T: TryFrom<i64>>(value: i64, to_le: impl Fn(T) -> Vec<u8>) -> Option<Vec<u8>> {
+            T::try_from(value).ok().map(to_le)
+        }
+
+        match (self, data_type) {
+            (SdoValue::Bytes(bytes), _) => Some(bytes.clone()),
+            (SdoValue::Bool(b), _) => SdoValue::Integer(*b as i64).encode(data_type),
+            (SdoValue::Integer(i), DataType::Boolean) => match i {
+                0 | 1 => Some(vec![*i as u8]),
+                _ => None,
+            },
+            (SdoValue::Integer(i), DataType::Int8) => int(*i, |v: i8| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::Int16) => int(*i, |v: i16| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::Int24) => (-(1 << 23)..(1 << 23))
+                .contains(i)
+                .then(|| i.to_le_bytes()[..3].to_vec()),
+            (SdoValue::Integer(i), DataType::Int32) => int(*i, |v: i32| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::Int64) => Some(i.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::UInt8) => int(*i, |v: u8| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::UInt16) => int(*i, |v: u16| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::UInt24) => (0..(1 << 24))
+                .contains(i)
+                .then(|| i.to_le_bytes()[..3].to_vec()),
+            (SdoValue::Integer(i), DataType::UInt32) => int(*i, |v: u32| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::UInt64) => int(*i, |v: u64| v.to_le_bytes().to_vec()),
+            (SdoValue::Integer(i), DataType::Real32 | DataType::Real64) => {
+                SdoValue::Float(*i as f64).encode(data_type)
+            }
+            (SdoValue::Float(f), DataType::Real32) => Some((*f as f32).to_le_bytes().to_vec()),
+            (SdoValue::Float(f), DataType::Real64) => Some(f.to_le_bytes().to_vec()),
+            (SdoValue::String(s), DataType::VisibleString) => {
+                is_visible_string(s.as_bytes()).then(|| s.as_bytes().to_vec())
+            }
+            (SdoValue::String(s), DataType::OctetString | DataType::UnicodeString) => {
+                Some(s.as_bytes().to_vec())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for SdoValue {
+    fn from(value: i64) -> Self {
+        SdoValue::Integer(value)
+    }
+}
+
+impl From<f64> for SdoValue {
+    fn from(value: f64) -> Self {
+        SdoValue::Float(value)
+    }
+}
+
+impl From<bool> for SdoValue {
+    fn from(value: bool) -> Self {
+        SdoValue::Bool(value)
+    }
+}
+
+impl From<&str> for SdoValue {
+    fn from(value: &str) -> Self {
+        SdoValue::String(value.to_string())
+    }
+}
+
+impl From<Vec<u8>> for SdoValue {
+    fn from(value: Vec<u8>) -> Self {
+        SdoValue::Bytes(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_value() {
+        assert_eq!(
+            Some(vec![0x34, 0x12]),
+            SdoValue::Integer(0x1234).encode(DataType::UInt16)
+        );
+        assert_eq!(None, SdoValue::Integer(0x10000).encode(DataType::UInt16));
+        assert_eq!(None, SdoValue::Integer(-1).encode(DataType::UInt32));
+        assert_eq!(
+            Some(vec![0xff, 0xff, 0xff]),
+            SdoValue::Integer(-1).encode(DataType::Int24)
+        );
+        assert_eq!(None, SdoValue::Integer(1 << 24).encode(DataType::UInt24));
+        assert_eq!(Some(vec![1]), SdoValue::Bool(true).encode(DataType::UInt8));
+        assert_eq!(None, SdoValue::Integer(2).encode(DataType::Boolean));
+        assert_eq!(
+            Some(2.5f32.to_le_bytes().to_vec()),
+            SdoValue::Float(2.5).encode(DataType::Real32)
+        );
+        assert_eq!(
+            Some(2.0f32.to_le_bytes().to_vec()),
+            SdoValue::Integer(2).encode(DataType::Real32)
+        );
+        assert_eq!(None, SdoValue::Float(2.5).encode(DataType::UInt32));
+        assert_eq!(None, SdoValue::from("abc").encode(DataType::UInt32));
+        assert_eq!(
+            Some(b"abc".to_vec()),
+            SdoValue::from("abc").encode(DataType::VisibleString)
+        );
+        assert_eq!(None, SdoValue::from("25°C").encode(DataType::VisibleString));
+        assert_eq!(
+            Some("25°C".as_bytes().to_vec()),
+            SdoValue::from("25°C").encode(DataType::UnicodeString)
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! PEAK-System PCAN adapters, via the PCAN-Basic library
+//!
+//! PCAN adapters, e.g. the PCAN-USB, are supported on Windows, macOS and Linux through PEAK's
+//! PCAN-Basic API, making them a good choice for tooling used on laptops which don't have
+//! socketcan. The library must be installed to build and run with the `pcan` feature:
+//!
+//! - Windows: `PCANBasic.dll`, installed with the PEAK device driver, and `PCANBasic.lib` from the
+//!   PCAN-Basic download
+//! - macOS: `libPCBUSB`, from the MacCAN project
+//! - Linux: `libpcanbasic`, which requires PEAK's `pcan` driver rather than the socketcan driver
+//!
+//! ```ignore
+//! let channel = PcanChannel::usb(1).unwrap();
+//! let (tx, rx) = open_pcan(channel, PcanBitrate::Kbps500)?;
+//! let manager = BusManager::new(tx, rx);
+//! ```
+//!
+//! Frames are received by polling the adapter from a background thread, which feeds a tokio
+//! channel. The thread ends when the receiver is dropped, and the channel is released once both
+//! the sender and receiver have been dropped.
+use std::{sync::Arc, thread, time::Duration};
+
+use snafu::Snafu;
+use tokio::sync::mpsc::{self, Receiver};
+use zencan_common::{
+    messages::CanId,
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+#[allow(non_snake_case)]
+mod ffi {
+    use core::ffi::c_char;
+
+    pub const PCAN_ERROR_OK: u32 = 0x00000;
+    pub const PCAN_ERROR_QRCVEMPTY: u32 = 0x00020;
+
+    pub const PCAN_MESSAGE_RTR: u8 = 0x01;
+    pub const PCAN_MESSAGE_EXTENDED: u8 = 0x02;
+    pub const PCAN_MESSAGE_FD: u8 = 0x04;
+    pub const PCAN_MESSAGE_ERRFRAME: u8 = 0x40;
+    pub const PCAN_MESSAGE_STATUS: u8 = 0x80;
+
+    /// Language code for English error text
+    pub const LANGUAGE_ENGLISH: u16 = 0x09;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    pub struct TPCANMsg {
+        pub ID: u32,
+        pub MSGTYPE: u8,
+        pub LEN: u8,
+        pub DATA: [u8; 8],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    pub struct TPCANTimestamp {
+        pub millis: u32,
+        pub millis_overflow: u16,
+        pub micros: u16,
+    }
+
+    #[cfg_attr(windows, link(name = "PCANBasic"))]
+    #[cfg_attr(target_os = "macos", link(name = "PCBUSB"))]
+    #[cfg_attr(all(unix, not(target_os = "macos")), link(name = "pcanbasic"))]
+    extern "system" {
+        pub fn CAN_Initialize(
+            Channel: u16,
+            Btr0Btr1: u16,
+            HwType: u8,
+            IOPort: u32,
+            Interrupt: u16,
+        ) -> u32;
+        pub fn CAN_Uninitialize(Channel: u16) -> u32;
+        pub fn CAN_Read(
+            Channel: u16,
+            MessageBuffer: *mut TPCANMsg,
+            TimestampBuffer: *mut TPCANTimestamp,
+        ) -> u32;
+        pub fn CAN_Write(Channel: u16, MessageBuffer: *mut TPCANMsg) -> u32;
+        pub fn CAN_GetErrorText(Error: u32, Language: u16, Buffer: *mut c_char) -> u32;
+    }
+}
+
+/// How long the receive thread sleeps when no message is available
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Size of the channel between the receive thread and the receiver
+const RX_CHANNEL_SIZE: usize = 100;
+
+/// A PCAN-Basic channel handle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcanChannel(u16);
+
+impl PcanChannel {
+    /// Get a PCAN-USB channel, numbered from 1 to 16
+    pub const fn usb(n: u8) -> Option<Self> {
+        match n {
+            1..=8 => Some(Self(0x50 + n as u16)),
+            9..=16 => Some(Self(0x500 + n as u16)),
+            _ => None,
+        }
+    }
+
+    /// Create a channel from a raw PCAN-Basic handle, e.g. for PCI or LAN adapters
+    pub const fn from_raw(handle: u16) -> Self {
+        Self(handle)
+    }
+
+    /// Get the raw PCAN-Basic handle
+    pub const fn raw(&self) -> u16 {
+        self.0
+    }
+}
+
+/// The CAN bitrates supported by [`open_pcan`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcanBitrate {
+    /// 10 kbit/s
+    Kbps10,
+    /// 20 kbit/s
+    Kbps20,
+    /// 50 kbit/s
+    Kbps50,
+    /// 100 kbit/s
+    Kbps100,
+    /// 125 kbit/s
+    Kbps125,
+    /// 250 kbit/s
+    Kbps250,
+    /// 500 kbit/s
+    Kbps500,
+    /// 800 kbit/s
+    Kbps800,
+    /// 1 Mbit/s
+    Mbps1,
+}
+
+impl PcanBitrate {
+    /// Get the bitrate for a rate in bits per second, if it is supported
+    pub fn from_bps(bps: u32) -> Option<Self> {
+        match bps {
+            10_000 => Some(Self::Kbps10),
+            20_000 => Some(Self::Kbps20),
+            50_000 => Some(Self::Kbps50),
+            100_000 => Some(Self::Kbps100),
+            125_000 => Some(Self::Kbps125),
+            250_000 => Some(Self::Kbps250),
+            500_000 => Some(Self::Kbps500),
+            800_000 => Some(Self::Kbps800),
+            1_000_000 => Some(Self::Mbps1),
+            _ => None,
+        }
+    }
+
+    /// The BTR0/BTR1 register values for the bitrate, as defined by PCAN-Basic
+    fn btr0btr1(&self) -> u16 {
+        match self {
+            Self::Kbps10 => 0x672F,
+            Self::Kbps20 => 0x532F,
+            Self::Kbps50 => 0x472F,
+            Self::Kbps100 => 0x432F,
+            Self::Kbps125 => 0x031C,
+            Self::Kbps250 => 0x011C,
+            Self::Kbps500 => 0x001C,
+            Self::Kbps800 => 0x0016,
+            Self::Mbps1 => 0x0014,
+        }
+    }
+}
+
+/// Error returned by a [`PcanReceiver`] or by [`open_pcan`]
+#[derive(Clone, Debug, PartialEq, Snafu)]
+pub enum PcanError {
+    /// A PCAN-Basic call returned an error status
+    #[snafu(display("PCAN error 0x{status:x}: {message}"))]
+    Status {
+        /// The status code
+        status: u32,
+        /// The description of the status from PCAN-Basic
+        message: String,
+    },
+    /// The receive thread has ended
+    #[snafu(display("Receive thread stopped"))]
+    Closed,
+}
+
+impl PcanError {
+    fn from_status(status: u32) -> Self {
+        let mut buf = [0 as core::ffi::c_char; 256];
+        // SAFETY: The buffer is the 256 bytes required by CAN_GetErrorText
+        let result =
+            unsafe { ffi::CAN_GetErrorText(status, ffi::LANGUAGE_ENGLISH, buf.as_mut_ptr()) };
+        let message = if result == ffi::PCAN_ERROR_OK {
+            // SAFETY: On success, the buffer holds a null terminated string
+            unsafe { core::ffi::CStr::from_ptr(buf.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            "Unknown error".into()
+        };
+        Self::Status { status, message }
+    }
+}
+
+/// Error returned by [`PcanSender`]
+#[derive(Debug, Snafu)]
+#[snafu(display("Error sending {message:?}: {source}"))]
+pub struct PcanSendError {
+    source: PcanError,
+    message: CanMessage,
+}
+
+impl CanSendError for PcanSendError {
+    fn into_can_message(self) -> CanMessage {
+        self.message
+    }
+
+    fn message(&self) -> String {
+        self.source.to_string()
+    }
+}
+
+/// An initialized channel, which is uninitialized when dropped
+#[derive(Debug)]
+struct ChannelHandle(PcanChannel);
+
+impl Drop for ChannelHandle {
+    fn drop(&mut self) {
+        // SAFETY: No other references to the channel remain
+        unsafe { ffi::CAN_Uninitialize(self.0.raw()) };
+    }
+}
+
+fn to_pcan_msg(msg: &CanMessage) -> ffi::TPCANMsg {
+    let mut pcan_msg = ffi::TPCANMsg {
+        ID: msg.id().raw(),
+        LEN: msg.data().len() as u8,
+        ..Default::default()
+    };
+    if msg.id().is_extended() {
+        pcan_msg.MSGTYPE |= ffi::PCAN_MESSAGE_EXTENDED;
+    }
+    if msg.is_rtr() {
+        pcan_msg.MSGTYPE |= ffi::PCAN_MESSAGE_RTR;
+    }
+    pcan_msg.DATA[..msg.data().len()].copy_from_slice(msg.data());
+    pcan_msg
+}
+
+/// Convert a received message
+///
+/// Returns None for status messages, error frames, and CAN FD frames
+fn from_pcan_msg(msg: &ffi::TPCANMsg, timestamp: &ffi::TPCANTimestamp) -> Option<CanMessage> {
+    const SKIPPED: u8 =
+        ffi::PCAN_MESSAGE_FD | ffi::PCAN_MESSAGE_ERRFRAME | ffi::PCAN_MESSAGE_STATUS;
+    if msg.MSGTYPE & SKIPPED != 0 {
+        return None;
+    }
+    let id = if msg.MSGTYPE & ffi::PCAN_MESSAGE_EXTENDED != 0 {
+        CanId::extended(msg.ID)
+    } else {
+        CanId::std(msg.ID as u16)
+    };
+    let msg = if msg.MSGTYPE & ffi::PCAN_MESSAGE_RTR != 0 {
+        CanMessage::new_rtr(id)
+    } else {
+        CanMessage::new(id, &msg.DATA[..(msg.LEN as usize).min(8)])
+    };
+    let millis = timestamp.millis as u64 + ((timestamp.millis_overflow as u64) << 32);
+    Some(msg.with_timestamp(millis * 1000 + timestamp.micros as u64))
+}
+
+/// The sending half of a PCAN channel, implementing [`AsyncCanSender`]
+#[derive(Debug, Clone)]
+pub struct PcanSender {
+    handle: Arc<ChannelHandle>,
+}
+
+impl AsyncCanSender for PcanSender {
+    type Error = PcanSendError;
+
+    async fn send(&mut self, msg: CanMessage) -> Result<(), Self::Error> {
+        let mut pcan_msg = to_pcan_msg(&msg);
+        // SAFETY: The channel is initialized, and the message is a valid TPCANMsg. CAN_Write only
+        // queues the message, so it does not block.
+        let status = unsafe { ffi::CAN_Write(self.handle.0.raw(), &mut pcan_msg) };
+        if status == ffi::PCAN_ERROR_OK {
+            Ok(())
+        } else {
+            Err(PcanSendError {
+                source: PcanError::from_status(status),
+                message: msg,
+            })
+        }
+    }
+}
+
+/// The receiving half of a PCAN channel, implementing [`AsyncCanReceiver`]
+#[derive(Debug)]
+pub struct PcanReceiver {
+    rx: Receiver<Result<CanMessage, PcanError>>,
+}
+
+impl AsyncCanReceiver for PcanReceiver {
+    type Error = PcanError;
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Ok(msg)) => return Some(msg),
+                // Errors are only reported by recv
+                Ok(Err(_)) => (),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> Result<CanMessage, Self::Error> {
+        self.rx.recv().await.unwrap_or(Err(PcanError::Closed))
+    }
+}
+
+/// Read messages from the channel until the receiver is dropped
+///
+/// Bus errors, e.g. bus off, are reported by PCAN-Basic as the status of reads. They are passed to
+/// the receiver once each time the status changes, but do not stop the thread.
+fn receive_thread(handle: Arc<ChannelHandle>, tx: mpsc::Sender<Result<CanMessage, PcanError>>) {
+    let mut last_status = ffi::PCAN_ERROR_OK;
+    while !tx.is_closed() {
+        let mut msg = ffi::TPCANMsg::default();
+        let mut timestamp = ffi::TPCANTimestamp::default();
+        // SAFETY: The channel is initialized, and the buffers are valid
+        let status = unsafe { ffi::CAN_Read(handle.0.raw(), &mut msg, &mut timestamp) };
+        let result = match status {
+            ffi::PCAN_ERROR_OK => from_pcan_msg(&msg, &timestamp).map(Ok),
+            ffi::PCAN_ERROR_QRCVEMPTY => {
+                thread::sleep(POLL_INTERVAL);
+                None
+            }
+            status if status != last_status => Some(Err(PcanError::from_status(status))),
+            _ => None,
+        };
+        if status != ffi::PCAN_ERROR_QRCVEMPTY {
+            last_status = status;
+        }
+        if let Some(result) = result {
+            if tx.blocking_send(result).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Open a PCAN channel
+///
+/// # Arguments
+/// * `channel` - The channel to open, e.g. `PcanChannel::usb(1)`
+/// * `bitrate` - The CAN bitrate
+///
+/// A channel can only be opened by one application at a time. Classic CAN frames are supported;
+/// CAN FD frames are discarded.
+pub fn open_pcan(
+    channel: PcanChannel,
+    bitrate: PcanBitrate,
+) -> Result<(PcanSender, PcanReceiver), PcanError> {
+    // SAFETY: The hardware type, IO port and interrupt are only used for non plug and play
+    // adapters, and are ignored otherwise
+    let status = unsafe { ffi::CAN_Initialize(channel.raw(), bitrate.btr0btr1(), 0, 0, 0) };
+    if status != ffi::PCAN_ERROR_OK {
+        return Err(PcanError::from_status(status));
+    }
+    let handle = Arc::new(ChannelHandle(channel));
+
+    let (tx, rx) = mpsc::channel(RX_CHANNEL_SIZE);
+    let thread_handle = handle.clone();
+    thread::Builder::new()
+        .name(format!("pcan-rx-{:x}", channel.raw()))
+        .spawn(move || receive_thread(thread_handle, tx))
+        .expect("Failed to spawn PCAN receive thread");
+
+    Ok((PcanSender { handle }, PcanReceiver { rx }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel() {
+        assert_eq!(0x51, PcanChannel::usb(1).unwrap().raw());
+        assert_eq!(0x58, PcanChannel::usb(8).unwrap().raw());
+        assert_eq!(0x509, PcanChannel::usb(9).unwrap().raw());
+        assert_eq!(0x510, PcanChannel::usb(16).unwrap().raw());
+        assert_eq!(None, PcanChannel::usb(0));
+        assert_eq!(None, PcanChannel::usb(17));
+    }
+
+    #[test]
+    fn test_message_conversion() {
+        let msg = CanMessage::new(CanId::extended(0x18FE_F180), &[1, 2, 3]);
+        let pcan_msg = to_pcan_msg(&msg);
+        assert_eq!(0x18FE_F180, pcan_msg.ID);
+        assert_eq!(ffi::PCAN_MESSAGE_EXTENDED, pcan_msg.MSGTYPE);
+        assert_eq!(3, pcan_msg.LEN);
+
+        let timestamp = ffi::TPCANTimestamp {
+            millis: 2,
+            millis_overflow: 1,
+            micros: 5,
+        };
+        let received = from_pcan_msg(&pcan_msg, &timestamp).unwrap();
+        assert_eq!(msg, received);
+        assert_eq!(Some(((1u64 << 32) + 2) * 1000 + 5), received.timestamp());
+
+        let rtr = to_pcan_msg(&CanMessage::new_rtr(CanId::std(0x705)));
+        assert_eq!(ffi::PCAN_MESSAGE_RTR, rtr.MSGTYPE);
+        assert!(from_pcan_msg(&rtr, &timestamp).unwrap().is_rtr());
+
+        let status = ffi::TPCANMsg {
+            MSGTYPE: ffi::PCAN_MESSAGE_STATUS,
+            ..Default::default()
+        };
+        assert!(from_pcan_msg(&status, &timestamp).is_none());
+    }
+}
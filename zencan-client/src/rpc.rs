@@ -0,0 +1,442 @@
+//! JSON-RPC access to a bus, for test automation
+//!
+//! [`RpcServer`] exposes the SDO, NMT and LSS operations of a [`BusManager`] as
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) methods, so that tools written in other
+//! languages -- e.g. a pytest suite -- can control a bus through a stable, machine-readable
+//! interface. Requests and responses are exchanged as single lines of JSON, over stdin/stdout or a
+//! Unix socket. Batch requests are not supported.
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "sdo.read", "params": {"node": 5, "index": 4104, "sub": 0}}
+//! <-- {"jsonrpc": "2.0", "id": 1, "result": {"data": [69, 120, 97, 109, 112, 108, 101]}}
+//! ```
+//!
+//! # Methods
+//!
+//! Parameters are passed by name. Object indices are plain numbers, since JSON has no hex syntax,
+//! and object data is an array of bytes.
+//!
+//! | Method                | Params                                           | Result                      |
+//! |-----------------------|--------------------------------------------------|-----------------------------|
+//! | `sdo.read`            | `node`, `index`, `sub`                           | `{"data": [u8]}`            |
+//! | `sdo.write`           | `node`, `index`, `sub`, `data`                   | `null`                      |
+//! | `nmt.start`           | `node` (0 for all nodes)                         | `null`                      |
+//! | `nmt.stop`            | `node`                                           | `null`                      |
+//! | `nmt.reset_app`       | `node`                                           | `null`                      |
+//! | `nmt.reset_comms`     | `node`                                           | `null`                      |
+//! | `nodes.list`          |                                                  | list of nodes               |
+//! | `nodes.scan`          |                                                  | list of nodes               |
+//! | `lss.fastscan`        | `timeout_ms` (optional, default 20)              | list of identities          |
+//! | `lss.activate`        | `vendor_id`, `product_code`, `revision`, `serial`| `null`                      |
+//! | `lss.set_node_id`     | `node_id`                                        | `null`                      |
+//! | `lss.store_config`    |                                                  | `null`                      |
+//! | `lss.set_global_mode` | `mode` (`"waiting"` or `"configuring"`)          | `null`                      |
+//! | `sync`                | `count` (optional)                               | `null`                      |
+//!
+//! A node is reported as an object with the fields `node_id`, `nmt_state`, `identity`,
+//! `device_name`, `software_version`, `hardware_version` and `last_seen_ms` (the time since the node
+//! was last seen). Unknown values are `null`.
+//!
+//! # Errors
+//!
+//! Errors are reported with the standard JSON-RPC error codes, or one of the application codes in
+//! [`error_codes`]. An SDO abort is reported with [`error_codes::SDO_ABORT`], and its `data` holds
+//! the `index`, `sub` and numeric `abort_code` of the aborted transfer.
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use zencan_common::{
+    lss::{LssIdentity, LssState},
+    traits::AsyncCanSender,
+    NodeId,
+};
+
+use crate::{bus_manager::NodeInfo, BusManager, LssError, RawAbortCode, SdoClientError};
+
+/// The error codes returned by [`RpcServer`]
+pub mod error_codes {
+    /// The request was not valid JSON
+    pub const PARSE_ERROR: i32 = -32700;
+    /// The request was not a valid JSON-RPC request object
+    pub const INVALID_REQUEST: i32 = -32600;
+    /// The method does not exist
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    /// The params were missing or invalid for the method
+    pub const INVALID_PARAMS: i32 = -32602;
+    /// A node did not respond in time
+    pub const NO_RESPONSE: i32 = -32000;
+    /// A node aborted an SDO transfer
+    pub const SDO_ABORT: i32 = -32001;
+    /// An SDO transfer failed for a reason other than a timeout or abort
+    pub const SDO_ERROR: i32 = -32002;
+    /// An LSS slave returned an error
+    pub const LSS_ERROR: i32 = -32003;
+}
+
+/// The default timeout for each step of an LSS fastscan
+const DEFAULT_FASTSCAN_TIMEOUT_MS: u64 = 20;
+
+/// A JSON-RPC error object
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RpcError {
+    /// The error code, from [`error_codes`]
+    pub code: i32,
+    /// A description of the error
+    pub message: String,
+    /// Additional structured information about the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INVALID_PARAMS, message)
+    }
+}
+
+impl From<SdoClientError> for RpcError {
+    fn from(e: SdoClientError) -> Self {
+        match e {
+            SdoClientError::NoResponse => Self::new(error_codes::NO_RESPONSE, e.to_string()),
+            SdoClientError::ServerAbort {
+                index,
+                sub,
+                abort_code,
+            } => {
+                let code = match abort_code {
+                    RawAbortCode::Valid(code) => code as u32,
+                    RawAbortCode::Unknown(code) => code,
+                };
+                Self {
+                    data: Some(json!({"index": index, "sub": sub, "abort_code": code})),
+                    ..Self::new(error_codes::SDO_ABORT, e.to_string())
+                }
+            }
+            _ => Self::new(error_codes::SDO_ERROR, e.to_string()),
+        }
+    }
+}
+
+impl From<LssError> for RpcError {
+    fn from(e: LssError) -> Self {
+        match e {
+            LssError::Timeout => Self::new(error_codes::NO_RESPONSE, e.to_string()),
+            _ => Self::new(error_codes::LSS_ERROR, e.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// The request ID. Requests without an ID are notifications, and get no response.
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeParams {
+    node: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SdoReadParams {
+    node: u8,
+    index: u16,
+    sub: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SdoWriteParams {
+    node: u8,
+    index: u16,
+    sub: u8,
+    data: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FastscanParams {
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentityParams {
+    vendor_id: u32,
+    product_code: u32,
+    revision: u32,
+    serial: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetNodeIdParams {
+    node_id: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum GlobalMode {
+    Waiting,
+    Configuring,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GlobalModeParams {
+    mode: GlobalMode,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SyncParams {
+    #[serde(default)]
+    count: Option<u8>,
+}
+
+/// Parse the params for a method, treating missing params as an empty object
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+fn sdo_node_id(node: u8) -> Result<u8, RpcError> {
+    if (1..=127).contains(&node) {
+        Ok(node)
+    } else {
+        Err(RpcError::invalid_params(format!(
+            "Invalid node ID {node}; must be 1 to 127"
+        )))
+    }
+}
+
+fn identity_to_json(id: &LssIdentity) -> Value {
+    json!({
+        "vendor_id": id.vendor_id,
+        "product_code": id.product_code,
+        "revision": id.revision,
+        "serial": id.serial,
+    })
+}
+
+fn node_to_json(node: &NodeInfo) -> Value {
+    json!({
+        "node_id": node.node_id,
+        "nmt_state": node.nmt_state.map(|s| s.to_string()),
+        "identity": node.identity.as_ref().map(identity_to_json),
+        "device_name": node.device_name,
+        "software_version": node.software_version,
+        "hardware_version": node.hardware_version,
+        "last_seen_ms": node.last_seen.elapsed().as_millis() as u64,
+    })
+}
+
+/// Serves JSON-RPC requests for operations on a bus
+///
+/// See the [module docs](self) for the available methods.
+///
+/// ```ignore
+/// let (tx, rx) = open_socketcan("can0").unwrap();
+/// let mut server = RpcServer::new(BusManager::new(tx, rx));
+/// server.serve_stdio().await?;
+/// ```
+#[derive(Debug)]
+pub struct RpcServer<S: AsyncCanSender + Sync + Send> {
+    bus: BusManager<S>,
+}
+
+impl<S: AsyncCanSender + Sync + Send> RpcServer<S> {
+    /// Create a server for operating on `bus`
+    pub fn new(bus: BusManager<S>) -> Self {
+        Self { bus }
+    }
+
+    /// Access the bus manager
+    pub fn bus(&mut self) -> &mut BusManager<S> {
+        &mut self.bus
+    }
+
+    /// Handle a single JSON-RPC request, and return the serialized response
+    ///
+    /// Returns None if the request was a notification, which gets no response.
+    pub async fn handle_request(&mut self, request: &str) -> Option<String> {
+        let (id, result) = match serde_json::from_str::<Value>(request) {
+            Err(e) => (
+                Value::Null,
+                Err(RpcError::new(error_codes::PARSE_ERROR, e.to_string())),
+            ),
+            Ok(value) => match serde_json::from_value::<Request>(value) {
+                Ok(request) if request.jsonrpc == "2.0" => {
+                    let result = self.call(&request.method, request.params).await;
+                    (request.id?, result)
+                }
+                Ok(request) => (
+                    request.id.unwrap_or(Value::Null),
+                    Err(RpcError::new(
+                        error_codes::INVALID_REQUEST,
+                        "Unsupported JSON-RPC version",
+                    )),
+                ),
+                Err(e) => (
+                    Value::Null,
+                    Err(RpcError::new(error_codes::INVALID_REQUEST, e.to_string())),
+                ),
+            },
+        };
+
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => json!({"jsonrpc": "2.0", "id": id, "error": error}),
+        };
+        Some(response.to_string())
+    }
+
+    /// Serve requests read line by line from `reader`, writing responses to `writer`, until the
+    /// reader is closed
+    pub async fn serve<R, W>(&mut self, reader: R, mut writer: W) -> std::io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_request(&line).await {
+                writer.write_all(response.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve requests on stdin, writing responses to stdout, until stdin is closed
+    pub async fn serve_stdio(&mut self) -> std::io::Result<()> {
+        self.serve(BufReader::new(tokio::io::stdin()), tokio::io::stdout())
+            .await
+    }
+
+    /// Listen for connections on a Unix socket at `path`, and serve requests from them
+    ///
+    /// Connections are served one at a time, in the order they are accepted. Any existing file at
+    /// `path` is removed first. This only returns if accepting a connection fails.
+    #[cfg(unix)]
+    pub async fn serve_unix(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let (reader, writer) = stream.into_split();
+            if let Err(e) = self.serve(BufReader::new(reader), writer).await {
+                log::warn!("RPC connection closed with error: {e}");
+            }
+        }
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "sdo.read" => {
+                let p: SdoReadParams = parse_params(params)?;
+                let mut client = self.bus.sdo_client(sdo_node_id(p.node)?);
+                let data = client.upload(p.index, p.sub).await?;
+                Ok(json!({ "data": data }))
+            }
+            "sdo.write" => {
+                let p: SdoWriteParams = parse_params(params)?;
+                let mut client = self.bus.sdo_client(sdo_node_id(p.node)?);
+                client.download(p.index, p.sub, &p.data).await?;
+                Ok(Value::Null)
+            }
+            "nmt.start" | "nmt.stop" | "nmt.reset_app" | "nmt.reset_comms" => {
+                let p: NodeParams = parse_params(params)?;
+                if p.node > 127 {
+                    return Err(RpcError::invalid_params(format!(
+                        "Invalid node ID {}; must be 0 to 127",
+                        p.node
+                    )));
+                }
+                match method {
+                    "nmt.start" => self.bus.nmt_start(p.node).await,
+                    "nmt.stop" => self.bus.nmt_stop(p.node).await,
+                    "nmt.reset_app" => self.bus.nmt_reset_app(p.node).await,
+                    _ => self.bus.nmt_reset_comms(p.node).await,
+                }
+                Ok(Value::Null)
+            }
+            "nodes.list" => {
+                let nodes = self.bus.node_list().await;
+                Ok(nodes.iter().map(node_to_json).collect())
+            }
+            "nodes.scan" => {
+                let nodes = self.bus.scan_nodes().await?;
+                Ok(nodes.iter().map(node_to_json).collect())
+            }
+            "lss.fastscan" => {
+                let p: FastscanParams = parse_params(params)?;
+                let timeout = p.timeout_ms.unwrap_or(DEFAULT_FASTSCAN_TIMEOUT_MS);
+                let devices = self.bus.lss_fastscan(Duration::from_millis(timeout)).await;
+                Ok(devices.iter().map(identity_to_json).collect())
+            }
+            "lss.activate" => {
+                let p: IdentityParams = parse_params(params)?;
+                let identity = LssIdentity::new(p.vendor_id, p.product_code, p.revision, p.serial);
+                self.bus.lss_activate(identity).await?;
+                Ok(Value::Null)
+            }
+            "lss.set_node_id" => {
+                let p: SetNodeIdParams = parse_params(params)?;
+                let node_id = NodeId::try_from(p.node_id).map_err(|_| {
+                    RpcError::invalid_params(format!("Invalid node ID {}", p.node_id))
+                })?;
+                self.bus.lss_set_node_id(node_id).await?;
+                Ok(Value::Null)
+            }
+            "lss.store_config" => {
+                self.bus.lss_store_config().await?;
+                Ok(Value::Null)
+            }
+            "lss.set_global_mode" => {
+                let p: GlobalModeParams = parse_params(params)?;
+                let mode = match p.mode {
+                    GlobalMode::Waiting => LssState::Waiting,
+                    GlobalMode::Configuring => LssState::Configuring,
+                };
+                self.bus.lss_set_global_mode(mode).await;
+                Ok(Value::Null)
+            }
+            "sync" => {
+                let p: SyncParams = parse_params(params)?;
+                self.bus.sync(p.count).await;
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(
+                error_codes::METHOD_NOT_FOUND,
+                format!("Unknown method '{method}'"),
+            )),
+        }
+    }
+}
@@ -1,19 +1,34 @@
-use std::time::Duration;
+use std::{ops::RangeInclusive, sync::Arc, time::Duration};
 
 use snafu::Snafu;
 use zencan_common::{
     constants::{object_ids, values::SAVE_CMD},
     i24,
     lss::LssIdentity,
-    messages::CanId,
+    messages::{CanId, ConnectionSet},
     node_configuration::PdoConfig,
+    objects::{AccessType, DataType},
     pdo::PdoMapping,
     sdo::{AbortCode, BlockSegment, SdoRequest, SdoResponse},
+    strings::is_visible_string,
     traits::{AsyncCanReceiver, AsyncCanSender, CanSendError as _, ReadSize},
-    u24, CanMessage, TimeDifference, TimeOfDay,
+    u24,
+    unit_metadata::{decode_table, DecodeError, UnitMetadata},
+    CanMessage, TimeDifference, TimeOfDay,
+};
+
+use crate::{
+    clock::{timeout_at, Clock, TokioClock},
+    dump::DictionaryDump,
+    object_info::{ObjectInfo, SdoValue},
+    telemetry,
 };
 
 const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_millis(150);
+/// The number of requests sent by [`SdoClient::read_many()`] before waiting for responses
+///
+/// This matches the depth of the request queue in a zencan node's SDO server.
+const DEFAULT_PIPELINE_DEPTH: usize = 4;
 
 /// A wrapper around the AbortCode enum to allow for unknown values
 ///
@@ -45,6 +60,30 @@ impl From<u32> for RawAbortCode {
     }
 }
 
+/// An entry of a node's error history, read from the pre-defined error field object (0x1003)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorHistoryEntry {
+    /// The error code, as sent in the EMCY message reporting the error
+    pub error_code: u16,
+    /// Manufacturer specific additional information
+    pub additional_info: u16,
+}
+
+impl From<u32> for ErrorHistoryEntry {
+    fn from(value: u32) -> Self {
+        Self {
+            error_code: value as u16,
+            additional_info: (value >> 16) as u16,
+        }
+    }
+}
+
+impl From<ErrorHistoryEntry> for u32 {
+    fn from(entry: ErrorHistoryEntry) -> Self {
+        entry.error_code as u32 | (entry.additional_info as u32) << 16
+    }
+}
+
 /// Error returned by [`SdoClient`] methods
 #[derive(Clone, Debug, PartialEq, Snafu)]
 pub enum SdoClientError {
@@ -97,10 +136,99 @@ pub enum SdoClientError {
     BlockSizeChangedTooSmall,
     /// The CRC on a block upload did not match
     CrcMismatch,
+    /// A download was not attempted because the object is not writable
+    #[snafu(display("Object 0x{index:X}sub{sub} is not writable"))]
+    ObjectNotWritable {
+        /// Index of the object
+        index: u16,
+        /// Sub index of the object
+        sub: u8,
+    },
+    /// A download was not attempted because the data size does not match the object
+    #[snafu(display(
+        "Cannot write {actual} bytes to object 0x{index:X}sub{sub}, which holds {expected} bytes"
+    ))]
+    DownloadSizeMismatch {
+        /// Index of the object
+        index: u16,
+        /// Sub index of the object
+        sub: u8,
+        /// The size of the object
+        expected: usize,
+        /// The size of the data provided
+        actual: usize,
+    },
+    /// A value could not be converted to the data type of the object
+    #[snafu(display(
+        "Cannot write {value:?} to object 0x{index:X}sub{sub} of type {data_type:?}"
+    ))]
+    IncompatibleValue {
+        /// Index of the object
+        index: u16,
+        /// Sub index of the object
+        sub: u8,
+        /// The value which was provided
+        value: SdoValue,
+        /// The data type of the object, if known
+        data_type: Option<DataType>,
+    },
+    /// A PDO mapping was rejected, either by the object metadata before anything was sent, or by
+    /// the node
+    #[snafu(display("Cannot map object 0x{index:X}sub{sub} to {pdo}: {reason}"))]
+    ObjectNotMappable {
+        /// Index of the object
+        index: u16,
+        /// Sub index of the object
+        sub: u8,
+        /// The type of PDO, "TPDO" or "RPDO"
+        pdo: &'static str,
+        /// Why the object cannot be mapped
+        reason: &'static str,
+    },
+    /// The unit metadata object read from the node could not be decoded
+    #[snafu(display("Invalid unit metadata: {source}"))]
+    InvalidUnitMetadata {
+        /// The decoding error
+        source: DecodeError,
+    },
+    /// The node's configuration signature does not match the expected one
+    #[snafu(display(
+        "Configuration signature mismatch: expected {expected:08X}, node has {actual:08X}"
+    ))]
+    ConfigSignatureMismatch {
+        /// The expected signature
+        expected: u32,
+        /// The signature read from the node
+        actual: u32,
+    },
+    /// A node ID passed to a request was out of range, or given more than once
+    #[snafu(display("Invalid node ID {node_id}: {reason}"))]
+    InvalidNodeId {
+        /// The node ID
+        node_id: u8,
+        /// Why the node ID is invalid
+        reason: &'static str,
+    },
 }
 
 type Result<T> = std::result::Result<T, SdoClientError>;
 
+/// The type of PDO being configured
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PdoKind {
+    Tpdo,
+    Rpdo,
+}
+
+impl PdoKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PdoKind::Tpdo => "TPDO",
+            PdoKind::Rpdo => "RPDO",
+        }
+    }
+}
+
 /// Convenience macro for expecting a particular variant of a response and erroring on abort of
 /// unexpected variant
 macro_rules! match_response  {
@@ -153,16 +281,59 @@ macro_rules! access_methods {
     };
 }
 
+/// Accessors for objects which store values most significant byte first, contrary to CiA 301
+macro_rules! be_access_methods {
+    ($type: ty) => {
+
+        paste! {
+            #[doc = concat!("Read a big endian ", stringify!($type), " sub object from the SDO server")]
+            pub async fn [<read_ $type _be>](&mut self, index: u16, sub: u8) -> Result<$type> {
+                let data = self.upload(index, sub).await?;
+                if data.len() != <$type as ReadSize>::READ_SIZE {
+                    return UnexpectedSizeSnafu.fail();
+                }
+                Ok($type::from_be_bytes(data.try_into().unwrap()))
+            }
+
+            #[doc = concat!("Write a big endian ", stringify!($type), " sub object to the SDO server")]
+            pub async fn [<write_ $type _be>](&mut self, index: u16, sub: u8, value: $type) -> Result<()> {
+                let data = value.to_be_bytes();
+                self.download(index, sub, &data).await
+            }
+        }
+    };
+}
+
 #[derive(Debug)]
 /// A client for accessing a node's SDO server
 ///
 /// A single server can talk to a single client at a time.
+///
+/// The client reads every message from its receiver while waiting for a response, and discards
+/// those which are not for it. To receive other messages from the same CAN interface, e.g. PDOs,
+/// create the client's receiver from a [`SharedReceiver`](crate::SharedReceiver), and
+/// [subscribe](crate::SharedReceiver::subscribe) to the other messages.
+///
+/// # Cancellation
+///
+/// All transfer methods are cancel-safe. If a transfer future is dropped before it completes (e.g.
+/// because it was wrapped in a `tokio::time::timeout`), the client remembers the interrupted
+/// transfer, and the next operation started on the client will first send an SDO abort to the
+/// server and discard any stale responses. The abort can also be sent immediately by calling
+/// [`abort_transfer()`](Self::abort_transfer).
 pub struct SdoClient<S, R> {
     req_cob_id: CanId,
     resp_cob_id: CanId,
     timeout: Duration,
     sender: S,
     receiver: R,
+    /// The object of a transfer which has been started but not completed
+    active_transfer: Option<(u16, u8)>,
+    /// Metadata used to check downloads before they are sent
+    object_info: Option<Arc<ObjectInfo>>,
+    /// The number of uploads requested at once by read_many
+    pipeline_depth: usize,
+    clock: Arc<dyn Clock>,
 }
 
 impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
@@ -174,8 +345,30 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     /// It is possible for nodes to have other SDO servers on other COB IDs, and clients for these
     /// can be created using [`Self::new()`]
     pub fn new_std(server_node_id: u8, sender: S, receiver: R) -> Self {
-        let req_cob_id = CanId::Std(0x600 + server_node_id as u16);
-        let resp_cob_id = CanId::Std(0x580 + server_node_id as u16);
+        Self::new_with_connection_set(server_node_id, &ConnectionSet::DEFAULT, sender, receiver)
+    }
+
+    /// Create a new SdoClient for a node's default SDO server, on a non-standard connection set
+    ///
+    /// This is the same as [`Self::new_std()`], for nodes configured with other SDO base IDs.
+    pub fn new_with_connection_set(
+        server_node_id: u8,
+        connection_set: &ConnectionSet,
+        sender: S,
+        receiver: R,
+    ) -> Self {
+        let req_cob_id = CanId::Std(connection_set.sdo_request_id(server_node_id));
+        let resp_cob_id = CanId::Std(connection_set.sdo_response_id(server_node_id));
+        Self::new(req_cob_id, resp_cob_id, sender, receiver)
+    }
+
+    /// Create a new SdoClient for a node's default SDO server, using extended CAN IDs
+    ///
+    /// This uses the same COB IDs as [`Self::new_std()`], as 29-bit IDs, for nodes whose default
+    /// SDO server is configured to use extended IDs.
+    pub fn new_extended(server_node_id: u8, sender: S, receiver: R) -> Self {
+        let req_cob_id = CanId::Extended(0x600 + server_node_id as u32);
+        let resp_cob_id = CanId::Extended(0x580 + server_node_id as u32);
         Self::new(req_cob_id, resp_cob_id, sender, receiver)
     }
 
@@ -187,9 +380,57 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
             timeout: DEFAULT_RESPONSE_TIMEOUT,
             sender,
             receiver,
+            active_transfer: None,
+            object_info: None,
+            pipeline_depth: DEFAULT_PIPELINE_DEPTH,
+            clock: Arc::new(TokioClock),
         }
     }
 
+    /// Set the object metadata for the server's node
+    ///
+    /// When set, downloads to sub objects described by the metadata are checked for size and
+    /// access type before anything is sent, and [`write_value()`](Self::write_value) can convert
+    /// values to the correct type.
+    pub fn set_object_info(&mut self, info: Arc<ObjectInfo>) {
+        self.object_info = Some(info);
+    }
+
+    /// Get the object metadata set with [`set_object_info()`](Self::set_object_info), if any
+    pub fn object_info(&self) -> Option<&Arc<ObjectInfo>> {
+        self.object_info.as_ref()
+    }
+
+    /// Check a download against the object metadata, if there is any for the sub object
+    fn validate_download(&self, index: u16, sub: u8, size: usize) -> Result<()> {
+        let Some(info) = self
+            .object_info
+            .as_ref()
+            .and_then(|info| info.get(index, sub))
+        else {
+            return Ok(());
+        };
+        if !info.access_type.is_writable() {
+            return ObjectNotWritableSnafu { index, sub }.fail();
+        }
+        // Mirrors the check made by the server: objects with no size are not checked, and strings
+        // and domains may be written with less data than their size
+        let size_ok = info.size == 0
+            || size == info.size
+            || (size < info.size
+                && (info.data_type.is_str() || info.data_type == DataType::Domain));
+        if !size_ok {
+            return DownloadSizeMismatchSnafu {
+                index,
+                sub,
+                expected: info.size,
+                actual: size,
+            }
+            .fail();
+        }
+        Ok(())
+    }
+
     /// Set the timeout for waiting on SDO server responses
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = timeout;
@@ -200,15 +441,55 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         self.timeout
     }
 
+    /// Set the clock used for response timeouts and send retry delays
+    ///
+    /// The default is the [`TokioClock`]. A [`SimClock`](crate::clock::SimClock) allows timeouts to
+    /// be tested without waiting.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Get the clock used for response timeouts and send retry delays
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Set the number of uploads [`read_many()`](Self::read_many) requests before waiting for
+    /// responses
+    ///
+    /// The server must be able to queue this many requests. The default of 4 suits zencan nodes. A
+    /// depth of 0 or 1 disables pipelining, so that every object is read with a separate
+    /// [`upload()`](Self::upload).
+    pub fn set_pipeline_depth(&mut self, depth: usize) {
+        self.pipeline_depth = depth;
+    }
+
+    /// Get the number of uploads [`read_many()`](Self::read_many) requests before waiting for
+    /// responses
+    pub fn get_pipeline_depth(&self) -> usize {
+        self.pipeline_depth
+    }
+
     async fn send(&mut self, data: [u8; 8]) -> Result<()> {
         let frame = CanMessage::new(self.req_cob_id, &data);
+        self.send_message(frame).await
+    }
+
+    /// Send a message using the client's sender, retrying on failure
+    ///
+    /// This allows other clients for the same node, e.g. [`Device`](crate::Device), to share the
+    /// sender.
+    pub(crate) async fn send_message(&mut self, frame: CanMessage) -> Result<()> {
         let mut tries = 3;
         loop {
             match self.sender.send(frame).await {
                 Ok(()) => return Ok(()),
                 Err(e) => {
                     tries -= 1;
-                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    if tries > 0 {
+                        telemetry::sdo_send_retry(self.resp_cob_id);
+                    }
+                    self.clock.sleep(Duration::from_millis(5)).await;
                     if tries == 0 {
                         return SocketSendFailedSnafu {
                             message: e.message(),
@@ -220,9 +501,128 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         }
     }
 
+    /// Returns true if a transfer was started but has not completed
+    ///
+    /// This can only be true after a transfer future has been dropped before completion.
+    pub fn transfer_in_progress(&self) -> bool {
+        self.active_transfer.is_some()
+    }
+
+    /// Abort an interrupted transfer
+    ///
+    /// If a previous transfer was dropped before completing, an SDO abort is sent to the server so
+    /// that it can return to idle without waiting for its timeout, and any stale responses in the
+    /// receiver are discarded. If there is no interrupted transfer, this does nothing.
+    pub async fn abort_transfer(&mut self) -> Result<()> {
+        if let Some((index, sub)) = self.active_transfer {
+            self.send(SdoRequest::abort(index, sub, AbortCode::GeneralError).to_bytes())
+                .await?;
+            self.active_transfer = None;
+            self.receiver.flush();
+        }
+        Ok(())
+    }
+
+    /// Prepare for a new transfer, aborting any previously interrupted one
+    async fn begin_transfer(&mut self, index: u16, sub: u8) -> Result<()> {
+        self.abort_transfer().await?;
+        self.active_transfer = Some((index, sub));
+        Ok(())
+    }
+
+    /// Mark the current transfer complete, notifying the server if it failed
+    async fn end_transfer<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let (Err(e), Some((index, sub))) = (&result, self.active_transfer) {
+            let abort_code = match e {
+                // The server has already given up on the transfer
+                SdoClientError::ServerAbort { .. } => None,
+                // No point trying to send on a failed socket
+                SdoClientError::SocketSendFailed { .. } => None,
+                SdoClientError::NoResponse => Some(AbortCode::SdoTimeout),
+                SdoClientError::MalformedResponse | SdoClientError::UnexpectedResponse { .. } => {
+                    Some(AbortCode::InvalidCommandSpecifier)
+                }
+                SdoClientError::ToggleNotAlternated => Some(AbortCode::ToggleNotAlternated),
+                SdoClientError::BlockSizeChangedTooSmall => Some(AbortCode::InvalidBlockSize),
+                SdoClientError::CrcMismatch => Some(AbortCode::CrcError),
+                SdoClientError::MismatchedObjectIndex { .. } | SdoClientError::UnexpectedSize => {
+                    Some(AbortCode::GeneralError)
+                }
+                // These are detected before a transfer is started
+                SdoClientError::ObjectNotWritable { .. }
+                | SdoClientError::DownloadSizeMismatch { .. }
+                | SdoClientError::IncompatibleValue { .. }
+                | SdoClientError::ObjectNotMappable { .. }
+                | SdoClientError::InvalidNodeId { .. } => None,
+                // Detected after the transfer is complete
+                SdoClientError::InvalidUnitMetadata { .. }
+                | SdoClientError::ConfigSignatureMismatch { .. } => None,
+            };
+            if let Some(abort_code) = abort_code {
+                // The original error is more useful to the caller than a failure to send the abort
+                self.send(SdoRequest::abort(index, sub, abort_code).to_bytes())
+                    .await
+                    .ok();
+            }
+        }
+        self.active_transfer = None;
+        result
+    }
+
     /// Write data to a sub-object on the SDO server
+    ///
+    /// If object metadata has been provided with [`set_object_info()`](Self::set_object_info), the
+    /// data is checked against it first, and an error is returned without contacting the server if
+    /// the object is not writable or the size is wrong.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "sdo_download",
+            skip_all,
+            fields(
+                server = %self.resp_cob_id,
+                index = %format_args!("{index:#06x}"),
+                sub = sub,
+                outcome = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn download(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<()> {
-        if data.len() <= 4 {
+        self.validate_download(index, sub, data.len())?;
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.download_inner(index, sub, data, false).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "download", start.elapsed(), &result);
+        result
+    }
+
+    /// Write all sub-objects of a record or array on the SDO server in one transfer, using
+    /// complete access
+    ///
+    /// `data` holds the value of each sub object from `sub`, which must be 0 or 1, to the highest
+    /// sub object, concatenated in order. When starting at sub 0, its value takes two bytes. The
+    /// server must support complete access for the object.
+    pub async fn download_complete(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<()> {
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.download_inner(index, sub, data, true).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "download", start.elapsed(), &result);
+        result
+    }
+
+    async fn download_inner(
+        &mut self,
+        index: u16,
+        sub: u8,
+        data: &[u8],
+        complete_access: bool,
+    ) -> Result<()> {
+        // An expedited transfer can't indicate a size of 0, so empty data is sent in a single empty
+        // segment
+        if !data.is_empty() && data.len() <= 4 && !complete_access {
             // Do an expedited transfer
             self.send(SdoRequest::expedited_download(index, sub, data).to_bytes())
                 .await?;
@@ -236,10 +636,12 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                 }
             )
         } else {
-            self.send(
-                SdoRequest::initiate_download(index, sub, Some(data.len() as u32)).to_bytes(),
-            )
-            .await?;
+            let req = if complete_access {
+                SdoRequest::initiate_complete_download(index, sub, data.len() as u32)
+            } else {
+                SdoRequest::initiate_download(index, sub, Some(data.len() as u32))
+            };
+            self.send(req.to_bytes()).await?;
 
             let resp = self.wait_for_response().await?;
             match_response!(
@@ -250,7 +652,7 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
 
             let mut toggle = false;
             // Send segments
-            let total_segments = data.len().div_ceil(7);
+            let total_segments = data.len().div_ceil(7).max(1);
             for n in 0..total_segments {
                 let last_segment = n == total_segments - 1;
                 let segment_size = (data.len() - n * 7).min(7);
@@ -267,11 +669,6 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                     SdoResponse::ConfirmDownloadSegment { t } => {
                         // Fail if toggle value doesn't match
                         if t != toggle {
-                            let abort_msg =
-                                SdoRequest::abort(index, sub, AbortCode::ToggleNotAlternated);
-
-                            self.send(abort_msg.to_bytes())
-                                .await?;
                             return ToggleNotAlternatedSnafu.fail();
                         }
                         // Otherwise, carry on
@@ -284,14 +681,63 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     }
 
     /// Read a sub-object on the SDO server
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "sdo_upload",
+            skip_all,
+            fields(
+                server = %self.resp_cob_id,
+                index = %format_args!("{index:#06x}"),
+                sub = sub,
+                outcome = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn upload(&mut self, index: u16, sub: u8) -> Result<Vec<u8>> {
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.upload_inner(index, sub, false).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "upload", start.elapsed(), &result);
+        result
+    }
+
+    /// Read all sub-objects of a record or array on the SDO server in one transfer, using complete
+    /// access
+    ///
+    /// Returns the value of each sub object from `sub`, which must be 0 or 1, to the highest sub
+    /// object, concatenated in order. When starting at sub 0, its value takes two bytes. The server
+    /// must support complete access for the object.
+    pub async fn upload_complete(&mut self, index: u16, sub: u8) -> Result<Vec<u8>> {
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.upload_inner(index, sub, true).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "upload", start.elapsed(), &result);
+        result
+    }
+
+    async fn upload_inner(
+        &mut self,
+        index: u16,
+        sub: u8,
+        complete_access: bool,
+    ) -> Result<Vec<u8>> {
         let mut read_buf = Vec::new();
 
-        self.send(SdoRequest::initiate_upload(index, sub).to_bytes())
-            .await?;
+        let req = if complete_access {
+            SdoRequest::initiate_complete_upload(index, sub)
+        } else {
+            SdoRequest::initiate_upload(index, sub)
+        };
+        self.send(req.to_bytes()).await?;
 
         let resp = self.wait_for_response().await?;
 
+        // The size of a segmented upload, if indicated by the server
+        let mut upload_size = None;
         let expedited = match_response!(
             resp,
             "ConfirmUpload",
@@ -309,6 +755,8 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                         len = 4 - n as usize;
                     }
                     read_buf.extend_from_slice(&data[0..len]);
+                } else if s {
+                    upload_size = Some(u32::from_le_bytes(data) as usize);
                 }
                 e
             }
@@ -327,11 +775,6 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                     "UploadSegment",
                     SdoResponse::UploadSegment { t, n, c, data } => {
                         if t != toggle {
-                            self.send(
-                                    SdoRequest::abort(index, sub, AbortCode::ToggleNotAlternated)
-                                        .to_bytes(),
-                                )
-                                .await?;
                             return ToggleNotAlternatedSnafu.fail();
                         }
                         read_buf.extend_from_slice(&data[0..7 - n as usize]);
@@ -343,15 +786,188 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                 );
                 toggle = !toggle;
             }
+            if upload_size.is_some_and(|size| size != read_buf.len()) {
+                return UnexpectedSizeSnafu.fail();
+            }
         }
         Ok(read_buf)
     }
 
+    /// Read several sub objects from the SDO server
+    ///
+    /// Returns the result of reading each of `objects`, in the same order.
+    ///
+    /// Objects which fit in an expedited transfer are requested in groups, without waiting for the
+    /// response to each request before sending the next, which is much faster than reading them
+    /// one at a time when there are many objects. Any object which turns out to need a segmented
+    /// transfer, or whose response is lost, is then read with [`upload()`](Self::upload). If
+    /// object metadata has been provided with [`set_object_info()`](Self::set_object_info),
+    /// objects known to be larger than 4 bytes skip straight to a sequential upload.
+    ///
+    /// See [`set_pipeline_depth()`](Self::set_pipeline_depth) to change the number of requests
+    /// sent at once.
+    pub async fn read_many(&mut self, objects: &[(u16, u8)]) -> Vec<Result<Vec<u8>>> {
+        let mut results: Vec<Option<Result<Vec<u8>>>> = objects.iter().map(|_| None).collect();
+
+        if self.pipeline_depth > 1 {
+            let expedited: Vec<usize> = (0..objects.len())
+                .filter(|&i| self.may_be_expedited(objects[i].0, objects[i].1))
+                .collect();
+            for batch in expedited.chunks(self.pipeline_depth) {
+                // On failure, the remaining objects are read sequentially, and will report the
+                // error if it persists
+                if self
+                    .read_pipelined(objects, batch, &mut results)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(objects.len());
+        for (&(index, sub), result) in objects.iter().zip(results) {
+            out.push(match result {
+                Some(result) => result,
+                None => self.upload(index, sub).await,
+            });
+        }
+        out
+    }
+
+    /// Returns false if the object metadata shows that a sub object cannot be read with an
+    /// expedited upload
+    fn may_be_expedited(&self, index: u16, sub: u8) -> bool {
+        match self
+            .object_info
+            .as_ref()
+            .and_then(|info| info.get(index, sub))
+        {
+            // Objects with no size have a size which is only known to the server
+            Some(info) => (1..=4).contains(&info.size),
+            None => true,
+        }
+    }
+
+    /// Request expedited uploads of a group of objects at once, and collect the responses
+    ///
+    /// `batch` holds the positions in `objects` to read, and the result for each one which is
+    /// read successfully or aborted by the server is stored in `results`. Results for objects
+    /// which need a segmented transfer, or which did not receive a response, are left empty.
+    async fn read_pipelined(
+        &mut self,
+        objects: &[(u16, u8)],
+        batch: &[usize],
+        results: &mut [Option<Result<Vec<u8>>>],
+    ) -> Result<()> {
+        let (first_index, first_sub) = objects[batch[0]];
+        self.begin_transfer(first_index, first_sub).await?;
+        for &i in batch {
+            let (index, sub) = objects[i];
+            self.send(SdoRequest::initiate_upload(index, sub).to_bytes())
+                .await?;
+        }
+
+        let mut pending = batch.to_vec();
+        // Objects for which the server started a segmented upload. When the server receives the
+        // next request, it aborts the segmented upload and drops the request.
+        let mut segmented = Vec::new();
+        while !pending.is_empty() {
+            let resp = match self.wait_for_response().await {
+                Ok(resp) => resp,
+                Err(SdoClientError::MalformedResponse) => continue,
+                // Whatever is still pending will be read sequentially
+                Err(_) => break,
+            };
+            let (index, sub, result) = match resp {
+                SdoResponse::ConfirmUpload {
+                    n,
+                    e: true,
+                    s,
+                    index,
+                    sub,
+                    data,
+                } => {
+                    let len = if s { 4 - n as usize } else { 0 };
+                    (index, sub, Some(Ok(data[0..len].to_vec())))
+                }
+                SdoResponse::ConfirmUpload {
+                    e: false,
+                    index,
+                    sub,
+                    ..
+                } => {
+                    segmented.push((index, sub));
+                    (index, sub, None)
+                }
+                SdoResponse::Abort {
+                    index,
+                    sub,
+                    abort_code,
+                } => {
+                    if segmented.contains(&(index, sub)) {
+                        continue;
+                    }
+                    let result = ServerAbortSnafu {
+                        index,
+                        sub,
+                        abort_code,
+                    }
+                    .fail();
+                    (index, sub, Some(result))
+                }
+                _ => continue,
+            };
+            if let Some(pos) = pending.iter().position(|&i| objects[i] == (index, sub)) {
+                results[pending.remove(pos)] = result;
+            }
+        }
+
+        if !pending.is_empty() || !segmented.is_empty() {
+            // The server may be part way through a segmented upload, or still have requests
+            // queued. An abort returns it to idle and clears its queue.
+            self.send(
+                SdoRequest::abort(first_index, first_sub, AbortCode::GeneralError).to_bytes(),
+            )
+            .await?;
+            self.receiver.flush();
+        }
+        self.active_transfer = None;
+        Ok(())
+    }
+
     /// Perform a block download to transfer data to an object
     ///
     /// Block downloads are more efficient for large amounts of data, but may not be supported by
     /// all devices.
+    ///
+    /// The data is checked against object metadata in the same way as [`download()`](Self::download).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "sdo_block_download",
+            skip_all,
+            fields(
+                server = %self.resp_cob_id,
+                index = %format_args!("{index:#06x}"),
+                sub = sub,
+                outcome = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn block_download(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<()> {
+        self.validate_download(index, sub, data.len())?;
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.block_download_inner(index, sub, data).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "block_download", start.elapsed(), &result);
+        result
+    }
+
+    async fn block_download_inner(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<()> {
         self.send(
             SdoRequest::InitiateBlockDownload {
                 cc: true, // CRC supported
@@ -468,7 +1084,30 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     }
 
     /// Perform a block upload of data from the node
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "sdo_block_upload",
+            skip_all,
+            fields(
+                server = %self.resp_cob_id,
+                index = %format_args!("{index:#06x}"),
+                sub = sub,
+                outcome = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn block_upload(&mut self, index: u16, sub: u8) -> Result<Vec<u8>> {
+        let start = self.clock.now();
+        self.begin_transfer(index, sub).await?;
+        let result = self.block_upload_inner(index, sub).await;
+        let result = self.end_transfer(result).await;
+        telemetry::sdo_transfer(self.resp_cob_id, "block_upload", start.elapsed(), &result);
+        result
+    }
+
+    async fn block_upload_inner(&mut self, index: u16, sub: u8) -> Result<Vec<u8>> {
         const CRC_SUPPORTED: bool = true;
         const BLKSIZE: u8 = 127;
         const PST: u8 = 0;
@@ -533,8 +1172,6 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         if server_supports_crc {
             let computed_crc = crc16::State::<crc16::XMODEM>::calculate(&rx_data);
             if crc != computed_crc {
-                self.send(SdoRequest::abort(index, sub, AbortCode::CrcError).to_bytes())
-                    .await?;
                 return Err(SdoClientError::CrcMismatch);
             }
         }
@@ -556,6 +1193,14 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     access_methods!(i24);
     access_methods!(i16);
     access_methods!(i8);
+    be_access_methods!(u64);
+    be_access_methods!(u32);
+    be_access_methods!(u24);
+    be_access_methods!(u16);
+    be_access_methods!(i64);
+    be_access_methods!(i32);
+    be_access_methods!(i24);
+    be_access_methods!(i16);
 
     /// Write to a TimeOfDay object on the SDO server
     pub async fn write_time_of_day(&mut self, index: u16, sub: u8, data: TimeOfDay) -> Result<()> {
@@ -608,6 +1253,53 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         Ok(String::from_utf8_lossy(&bytes).into())
     }
 
+    /// Write a string to a VisibleString object
+    ///
+    /// The string must contain only visible ASCII characters, or an
+    /// [`SdoClientError::IncompatibleValue`] error is returned without sending anything. If the size
+    /// of the object is known from the metadata provided with
+    /// [`set_object_info()`](Self::set_object_info), a shorter string is padded with nulls to fill
+    /// the object, so that no part of a previous, longer value remains.
+    pub async fn write_visible_string(&mut self, index: u16, sub: u8, value: &str) -> Result<()> {
+        if !is_visible_string(value.as_bytes()) {
+            return IncompatibleValueSnafu {
+                index,
+                sub,
+                value,
+                data_type: Some(DataType::VisibleString),
+            }
+            .fail();
+        }
+        let data = self.pad_string(index, sub, value);
+        self.download(index, sub, &data).await
+    }
+
+    /// Write a string to a UnicodeString object, encoded as UTF-8
+    ///
+    /// The string is padded in the same way as by
+    /// [`write_visible_string()`](Self::write_visible_string). Use
+    /// [`truncate_utf8`](zencan_common::strings::truncate_utf8) to shorten a string to fit an
+    /// object without splitting a character.
+    pub async fn write_unicode_string(&mut self, index: u16, sub: u8, value: &str) -> Result<()> {
+        let data = self.pad_string(index, sub, value);
+        self.download(index, sub, &data).await
+    }
+
+    /// Pad a string value with nulls to the size of the object, if known
+    fn pad_string(&self, index: u16, sub: u8, value: &str) -> Vec<u8> {
+        let mut data = value.as_bytes().to_vec();
+        let size = self
+            .object_info
+            .as_ref()
+            .and_then(|info| info.get(index, sub))
+            .map(|info| info.size)
+            .unwrap_or(0);
+        if data.len() < size {
+            data.resize(size, 0);
+        }
+        data
+    }
+
     /// Read an object as a boolean
     pub async fn read_bool(&mut self, index: u16, sub: u8) -> Result<bool> {
         let bytes = self.upload(index, sub).await?;
@@ -623,6 +1315,63 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         self.download(index, sub, &data).await
     }
 
+    /// Write a value to an object, converting it to the object's data type
+    ///
+    /// The data type is taken from the metadata provided with
+    /// [`set_object_info()`](Self::set_object_info). If the value cannot be represented by the
+    /// object's type -- e.g. an integer out of range, or a string written to an integer object --
+    /// an [`SdoClientError::IncompatibleValue`] error is returned. Without metadata for the object,
+    /// only [`SdoValue::Bytes`] can be written.
+    pub async fn write_value(
+        &mut self,
+        index: u16,
+        sub: u8,
+        value: impl Into<SdoValue>,
+    ) -> Result<()> {
+        let value = value.into();
+        let data_type = self
+            .object_info
+            .as_ref()
+            .and_then(|info| info.get(index, sub))
+            .map(|info| info.data_type);
+        let data = match (&value, data_type) {
+            (SdoValue::Bytes(bytes), None) => Some(bytes.clone()),
+            (_, Some(data_type)) => value.encode(data_type),
+            (_, None) => None,
+        };
+        let Some(data) = data else {
+            return IncompatibleValueSnafu {
+                index,
+                sub,
+                value,
+                data_type,
+            }
+            .fail();
+        };
+        self.download(index, sub, &data).await
+    }
+
+    /// Read every sub object in the given index ranges, for diagnostics
+    ///
+    /// Each index is probed by reading its object structure sub (0xFF), which gives the object
+    /// code and data type on nodes which support it, and then sub 0. Objects which do not exist are
+    /// skipped. For arrays and records, each sub up to the value of sub 0 is read. When the node
+    /// does not support the object structure sub, an object is taken to be an array or record if
+    /// it has a sub 1.
+    ///
+    /// Sub objects which cannot be read, e.g. because they are write only, are included in the dump
+    /// with the error. If the node stops responding, [`SdoClientError::NoResponse`] is returned.
+    ///
+    /// Data types are taken from the object info set with
+    /// [`set_object_info()`](Self::set_object_info) where available. Names and data types can be
+    /// added from an EDS with [`DictionaryDump::apply_eds()`].
+    pub async fn dump_dictionary(
+        &mut self,
+        ranges: &[RangeInclusive<u16>],
+    ) -> Result<DictionaryDump> {
+        crate::dump::dump_dictionary(self, ranges).await
+    }
+
     /// Read the identity object
     ///
     /// All nodes should implement this object
@@ -639,6 +1388,34 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
         ))
     }
 
+    /// Read the error history from the pre-defined error field object (0x1003)
+    ///
+    /// Returns the entries most recent first. Sub 0 gives the number of errors recorded, and each
+    /// of them is read.
+    pub async fn read_error_history(&mut self) -> Result<Vec<ErrorHistoryEntry>> {
+        let count = self.read_u8(object_ids::PRE_DEFINED_ERROR_FIELD, 0).await?;
+        let subs: Vec<_> = (1..=count)
+            .map(|sub| (object_ids::PRE_DEFINED_ERROR_FIELD, sub))
+            .collect();
+        self.read_many(&subs)
+            .await
+            .into_iter()
+            .map(|data| {
+                let data: [u8; 4] = data?
+                    .try_into()
+                    .map_err(|_| SdoClientError::UnexpectedSize)?;
+                Ok(u32::from_le_bytes(data).into())
+            })
+            .collect()
+    }
+
+    /// Clear the error history, by writing 0 to sub 0 of the pre-defined error field object
+    /// (0x1003)
+    pub async fn clear_error_history(&mut self) -> Result<()> {
+        self.write_u8(object_ids::PRE_DEFINED_ERROR_FIELD, 0, 0)
+            .await
+    }
+
     /// Write object 0x1010sub1 to command all objects be saved
     pub async fn save_objects(&mut self) -> Result<()> {
         self.write_u32(object_ids::SAVE_OBJECTS, 1, SAVE_CMD).await
@@ -667,49 +1444,164 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
             .await
     }
 
+    /// Read the engineering unit metadata of the node's objects
+    ///
+    /// Reads the unit metadata object (0x5F03), which is created on zencan nodes with
+    /// `unit_metadata` enabled in their device config. Returns a list of `(index, sub, metadata)`
+    /// entries, which can be added to an [`ObjectInfo`] with
+    /// [`ObjectInfo::insert_unit_metadata`].
+    pub async fn read_unit_metadata(&mut self) -> Result<Vec<(u16, u8, UnitMetadata)>> {
+        let data = self.upload(object_ids::UNIT_METADATA, 0).await?;
+        decode_table(&data).map_err(|source| SdoClientError::InvalidUnitMetadata { source })
+    }
+
+    /// Read the node's configuration signature
+    ///
+    /// Reads the configuration signature object (0x5F04), which is created on zencan nodes with
+    /// `config_signature` enabled in their device config.
+    pub async fn read_config_signature(&mut self) -> Result<u32> {
+        self.read_u32(object_ids::CONFIG_SIGNATURE, 0).await
+    }
+
+    /// Check that the node's configuration signature matches `expected`
+    ///
+    /// Returns [`SdoClientError::ConfigSignatureMismatch`] if it does not. This should be used
+    /// before writing a configuration to a node, to catch nodes running firmware with a different
+    /// object dictionary than the configuration was written for.
+    pub async fn verify_config_signature(&mut self, expected: u32) -> Result<()> {
+        let actual = self.read_config_signature().await?;
+        if actual != expected {
+            return ConfigSignatureMismatchSnafu { expected, actual }.fail();
+        }
+        Ok(())
+    }
+
     /// Configure a transmit PDO on the device
     ///
     /// This is a convenience function to write the PDO comm and mapping objects based on a
     /// [`PdoConfig`].
+    ///
+    /// If object metadata has been provided with [`set_object_info()`](Self::set_object_info), the
+    /// mapped objects are checked before anything is written, and an
+    /// [`SdoClientError::ObjectNotMappable`] error is returned if one is write-only, does not allow
+    /// TPDO mapping, or is smaller than its mapping. The same error is returned if the node rejects
+    /// a mapping.
     pub async fn configure_tpdo(&mut self, pdo_num: usize, cfg: &PdoConfig) -> Result<()> {
         let comm_index = 0x1800 + pdo_num as u16;
         let mapping_index = 0x1a00 + pdo_num as u16;
-        self.store_pdo(comm_index, mapping_index, cfg).await
+        self.store_pdo(PdoKind::Tpdo, comm_index, mapping_index, cfg)
+            .await
     }
 
     /// Configure a receive PDO on the device
     ///
     /// This is a convenience function to write the PDO comm and mapping objects based on a
     /// [`PdoConfig`].
+    ///
+    /// Mappings are checked in the same way as for [`configure_tpdo()`](Self::configure_tpdo),
+    /// except that read-only and const objects, rather than write-only objects, cannot be mapped.
     pub async fn configure_rpdo(&mut self, pdo_num: usize, cfg: &PdoConfig) -> Result<()> {
         let comm_index = 0x1400 + pdo_num as u16;
         let mapping_index = 0x1600 + pdo_num as u16;
-        self.store_pdo(comm_index, mapping_index, cfg).await
+        self.store_pdo(PdoKind::Rpdo, comm_index, mapping_index, cfg)
+            .await
+    }
+
+    /// Check PDO mappings against the object metadata, for the objects it describes
+    fn validate_pdo_mappings(&self, kind: PdoKind, mappings: &[PdoMapping]) -> Result<()> {
+        let Some(object_info) = self.object_info.as_ref() else {
+            return Ok(());
+        };
+        for mapping in mappings {
+            let Some(info) = object_info.get(mapping.index, mapping.sub) else {
+                continue;
+            };
+            let reason = match kind {
+                PdoKind::Tpdo if !info.access_type.is_readable() => {
+                    Some("the object is write-only")
+                }
+                PdoKind::Rpdo if info.access_type == AccessType::Const => {
+                    Some("the object is const")
+                }
+                PdoKind::Rpdo if !info.access_type.is_writable() => Some("the object is read-only"),
+                PdoKind::Tpdo if !info.pdo_mapping.supports_tpdo() => {
+                    Some("the object does not allow TPDO mapping")
+                }
+                PdoKind::Rpdo if !info.pdo_mapping.supports_rpdo() => {
+                    Some("the object does not allow RPDO mapping")
+                }
+                _ if mapping.size as usize > info.size * 8 => {
+                    Some("the mapping is larger than the object")
+                }
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                return ObjectNotMappableSnafu {
+                    index: mapping.index,
+                    sub: mapping.sub,
+                    pdo: kind.name(),
+                    reason,
+                }
+                .fail();
+            }
+        }
+        Ok(())
     }
 
     async fn store_pdo(
         &mut self,
+        kind: PdoKind,
         comm_index: u16,
         mapping_index: u16,
         cfg: &PdoConfig,
     ) -> Result<()> {
         assert!(cfg.mappings.len() < 0x40);
+        self.validate_pdo_mappings(kind, &cfg.mappings)?;
+        let mut cob_value = cfg.cob_id.raw() & 0x1FFFFFFF;
+        if cfg.cob_id.is_extended() {
+            cob_value |= 1 << 29;
+        }
+
+        // The PDO is invalidated first, so that it can be reconfigured even while the node is
+        // operational, and then enabled with the new configuration
+        self.write_u32(comm_index, 1, cob_value | (1 << 31)).await?;
+
         for (i, m) in cfg.mappings.iter().enumerate() {
             let mapping_value = m.to_object_value();
             self.write_u32(mapping_index, (i + 1) as u8, mapping_value)
-                .await?;
+                .await
+                .map_err(|e| {
+                    // Explain a rejected mapping in terms of the mapped object, rather than the
+                    // mapping object which was written
+                    let reason = match &e {
+                        SdoClientError::ServerAbort {
+                            abort_code: RawAbortCode::Valid(abort_code),
+                            ..
+                        } => match abort_code {
+                            AbortCode::UnnallowedPdo => "the node does not allow it to be mapped",
+                            AbortCode::NoSuchObject => "the object does not exist",
+                            AbortCode::IncompatibleParameter => {
+                                "the node does not support the mapping size"
+                            }
+                            _ => return e,
+                        },
+                        _ => return e,
+                    };
+                    SdoClientError::ObjectNotMappable {
+                        index: m.index,
+                        sub: m.sub,
+                        pdo: kind.name(),
+                        reason,
+                    }
+                })?;
         }
 
         let num_mappings = cfg.mappings.len() as u8;
         self.write_u8(mapping_index, 0, num_mappings).await?;
 
-        let mut cob_value = cfg.cob_id.raw() & 0x1FFFFFFF;
         if !cfg.enabled {
             cob_value |= 1 << 31;
         }
-        if cfg.cob_id.is_extended() {
-            cob_value |= 1 << 29;
-        }
         self.write_u8(comm_index, 2, cfg.transmission_type).await?;
         self.write_u32(comm_index, 1, cob_value).await?;
 
@@ -758,13 +1650,13 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     }
 
     async fn wait_for_block_segment(&mut self) -> Result<BlockSegment> {
-        let wait_until = tokio::time::Instant::now() + self.timeout;
+        let wait_until = self.clock.now() + self.timeout;
         loop {
-            match tokio::time::timeout_at(wait_until, self.receiver.recv()).await {
-                // Err indicates the timeout elapsed, so return
-                Err(_) => return NoResponseSnafu.fail(),
+            match timeout_at(&*self.clock, wait_until, self.receiver.recv()).await {
+                // None indicates the timeout elapsed, so return
+                None => return NoResponseSnafu.fail(),
                 // Message was recieved. If it is the resp, return. Otherwise, keep waiting
-                Ok(Ok(msg)) => {
+                Some(Ok(msg)) => {
                     if msg.id == self.resp_cob_id {
                         return msg
                             .data()
@@ -773,7 +1665,7 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
                     }
                 }
                 // Recv returned an error
-                Ok(Err(e)) => {
+                Some(Err(e)) => {
                     log::error!("Error reading from socket: {e:?}");
                     return NoResponseSnafu.fail();
                 }
@@ -782,19 +1674,19 @@ impl<S: AsyncCanSender, R: AsyncCanReceiver> SdoClient<S, R> {
     }
 
     async fn wait_for_response(&mut self) -> Result<SdoResponse> {
-        let wait_until = tokio::time::Instant::now() + self.timeout;
+        let wait_until = self.clock.now() + self.timeout;
         loop {
-            match tokio::time::timeout_at(wait_until, self.receiver.recv()).await {
-                // Err indicates the timeout elapsed, so return
-                Err(_) => return NoResponseSnafu.fail(),
+            match timeout_at(&*self.clock, wait_until, self.receiver.recv()).await {
+                // None indicates the timeout elapsed, so return
+                None => return NoResponseSnafu.fail(),
                 // Message was recieved. If it is the resp, return. Otherwise, keep waiting
-                Ok(Ok(msg)) => {
+                Some(Ok(msg)) => {
                     if msg.id == self.resp_cob_id {
                         return msg.try_into().map_err(|_| MalformedResponseSnafu.build());
                     }
                 }
                 // Recv returned an error
-                Ok(Err(e)) => {
+                Some(Err(e)) => {
                     log::error!("Error reading from socket: {e:?}");
                     return NoResponseSnafu.fail();
                 }
@@ -0,0 +1,518 @@
+//! CAN over a serial line, using the slcan (Lawicel) protocol
+//!
+//! Many inexpensive USB CAN adapters, e.g. the CANable or the Lawicel CANUSB, appear as a serial
+//! port and speak the slcan ASCII protocol. Unlike socketcan, they can be used on any platform,
+//! including Windows and macOS.
+//!
+//! The backend works with any port implementing tokio's `AsyncRead` and `AsyncWrite`, so the serial
+//! port is opened by the application, e.g. with the `tokio-serial` crate:
+//!
+//! ```ignore
+//! let port = tokio_serial::new("COM3", 115200).open_native_async()?;
+//! let (tx, rx) = open_slcan(port, SlcanBitrate::Kbps500).await?;
+//! let manager = BusManager::new(tx, rx);
+//! ```
+//!
+//! On unix, a [`TtyPort`] can be used instead, without any other crate. USB adapters ignore the
+//! baud rate, so it does not need to be configured.
+//!
+//! Frames are received by a background task, so [`open_slcan`] must be called from within a tokio
+//! runtime. The task ends when the receiver is dropped.
+use std::sync::Arc;
+#[cfg(unix)]
+use std::{
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use snafu::{ResultExt, Snafu};
+use tokio::{
+    io::{split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, WriteHalf},
+    select,
+    sync::mpsc::{channel, Receiver},
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use zencan_common::{
+    messages::CanId,
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+/// Terminates every command and response
+const CR: u8 = b'\r';
+/// Returned by the adapter in place of CR when a command fails
+const BELL: u8 = 0x07;
+/// The longest line the adapter sends: an extended frame with 8 bytes and a timestamp
+const MAX_LINE_LEN: usize = 1 + 8 + 1 + 16 + 4;
+
+/// Size of the channel between the receive task and the receiver
+const RX_CHANNEL_SIZE: usize = 100;
+
+/// The CAN bitrates supported by slcan adapters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlcanBitrate {
+    /// 10 kbit/s
+    Kbps10,
+    /// 20 kbit/s
+    Kbps20,
+    /// 50 kbit/s
+    Kbps50,
+    /// 100 kbit/s
+    Kbps100,
+    /// 125 kbit/s
+    Kbps125,
+    /// 250 kbit/s
+    Kbps250,
+    /// 500 kbit/s
+    Kbps500,
+    /// 800 kbit/s
+    Kbps800,
+    /// 1 Mbit/s
+    Mbps1,
+}
+
+impl SlcanBitrate {
+    /// Get the bitrate for a rate in bits per second, if it is supported
+    pub fn from_bps(bps: u32) -> Option<Self> {
+        match bps {
+            10_000 => Some(Self::Kbps10),
+            20_000 => Some(Self::Kbps20),
+            50_000 => Some(Self::Kbps50),
+            100_000 => Some(Self::Kbps100),
+            125_000 => Some(Self::Kbps125),
+            250_000 => Some(Self::Kbps250),
+            500_000 => Some(Self::Kbps500),
+            800_000 => Some(Self::Kbps800),
+            1_000_000 => Some(Self::Mbps1),
+            _ => None,
+        }
+    }
+
+    /// The `S` command which selects the bitrate
+    fn command(&self) -> [u8; 3] {
+        [b'S', b'0' + *self as u8, CR]
+    }
+}
+
+/// Error returned by an [`SlcanReceiver`] or by [`open_slcan`]
+#[derive(Debug, Snafu)]
+pub enum SlcanError {
+    /// An IO error on the serial port
+    #[snafu(display("IO error: {source}"))]
+    Io {
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The serial port has been closed, or the receive task has ended after an error
+    #[snafu(display("Serial port closed"))]
+    Closed,
+}
+
+/// Error returned by [`SlcanSender`]
+#[derive(Debug, Snafu)]
+#[snafu(display("Error sending {message:?}: {source}"))]
+pub struct SlcanSendError {
+    source: std::io::Error,
+    message: CanMessage,
+}
+
+impl CanSendError for SlcanSendError {
+    fn into_can_message(self) -> CanMessage {
+        self.message
+    }
+
+    fn message(&self) -> String {
+        self.source.to_string()
+    }
+}
+
+/// Encode the command which transmits a message
+fn encode_frame(msg: &CanMessage) -> Vec<u8> {
+    let mut line = match (msg.id(), msg.is_rtr()) {
+        (CanId::Std(id), false) => format!("t{id:03X}"),
+        (CanId::Std(id), true) => format!("r{id:03X}"),
+        (CanId::Extended(id), false) => format!("T{id:08X}"),
+        (CanId::Extended(id), true) => format!("R{id:08X}"),
+    };
+    line.push_str(&format!("{:X}", msg.data().len()));
+    if !msg.is_rtr() {
+        for b in msg.data() {
+            line.push_str(&format!("{b:02X}"));
+        }
+    }
+    line.push(CR as char);
+    line.into_bytes()
+}
+
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+    u32::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+/// Parse a line received from the adapter
+///
+/// Returns None for lines which are not a valid frame, e.g. the `z` and `Z` responses to a
+/// transmit command. Any characters following the frame, e.g. a timestamp, are ignored.
+fn parse_frame(line: &[u8]) -> Option<CanMessage> {
+    let (&kind, rest) = line.split_first()?;
+    let id_len = match kind {
+        b't' | b'r' => 3,
+        b'T' | b'R' => 8,
+        _ => return None,
+    };
+    let raw_id = parse_hex(rest.get(..id_len)?)?;
+    let id = if id_len == 3 {
+        CanId::std(u16::try_from(raw_id).ok().filter(|id| *id <= 0x7FF)?)
+    } else {
+        CanId::extended(Some(raw_id).filter(|id| *id <= 0x1FFF_FFFF)?)
+    };
+    let len = parse_hex(rest.get(id_len..id_len + 1)?)? as usize;
+    if len > 8 {
+        return None;
+    }
+    if kind == b'r' || kind == b'R' {
+        return Some(CanMessage::new_rtr(id));
+    }
+    let digits = rest.get(id_len + 1..id_len + 1 + 2 * len)?;
+    let mut data = [0; 8];
+    for (byte, pair) in data.iter_mut().zip(digits.chunks(2)) {
+        *byte = parse_hex(pair)? as u8;
+    }
+    Some(CanMessage::new(id, &data[..len]))
+}
+
+/// A serial port device on unix, opened in raw mode
+///
+/// The baud rate and other settings of the port are not changed, except that input and output
+/// processing is disabled so that the slcan protocol passes through unmodified.
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+#[derive(Debug)]
+pub struct TtyPort {
+    rx: tokio::net::unix::pipe::Receiver,
+    tx: tokio::net::unix::pipe::Sender,
+}
+
+#[cfg(unix)]
+impl TtyPort {
+    /// Open a serial port device, e.g. `/dev/ttyACM0`
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+        set_raw_mode(&file)?;
+        // The pipe types can wrap any pollable file, and a tty can be read and written like a pipe
+        let rx = tokio::net::unix::pipe::Receiver::from_file_unchecked(file.try_clone()?)?;
+        let tx = tokio::net::unix::pipe::Sender::from_file_unchecked(file)?;
+        Ok(Self { rx, tx })
+    }
+}
+
+#[cfg(unix)]
+fn set_raw_mode(file: &std::fs::File) -> std::io::Result<()> {
+    let fd = file.as_raw_fd();
+    // SAFETY: termios is plain data, and is filled in by tcgetattr before it is used
+    let mut termios: libc::termios = unsafe { core::mem::zeroed() };
+    // SAFETY: fd is an open file descriptor, and termios is a valid pointer
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: termios was initialized by tcgetattr
+    unsafe { libc::cfmakeraw(&mut termios) };
+    // SAFETY: fd is an open file descriptor, and termios is a valid pointer
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+impl AsyncRead for TtyPort {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.rx).poll_read(cx, buf)
+    }
+}
+
+#[cfg(unix)]
+impl AsyncWrite for TtyPort {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.tx).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.tx).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.tx).poll_shutdown(cx)
+    }
+}
+
+/// The sending half of an slcan adapter, implementing [`AsyncCanSender`]
+#[derive(Debug)]
+pub struct SlcanSender<T> {
+    port: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
+}
+
+// Derive would require T: Clone
+impl<T> Clone for SlcanSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            port: self.port.clone(),
+        }
+    }
+}
+
+impl<T: AsyncWrite + Send> AsyncCanSender for SlcanSender<T> {
+    type Error = SlcanSendError;
+
+    async fn send(&mut self, msg: CanMessage) -> Result<(), Self::Error> {
+        let mut port = self.port.lock().await;
+        port.write_all(&encode_frame(&msg))
+            .await
+            .context(SlcanSendSnafu { message: msg })
+    }
+}
+
+/// The receiving half of an slcan adapter, implementing [`AsyncCanReceiver`]
+#[derive(Debug)]
+pub struct SlcanReceiver {
+    rx: Receiver<Result<CanMessage, SlcanError>>,
+    _task_guard: DropGuard,
+}
+
+impl AsyncCanReceiver for SlcanReceiver {
+    type Error = SlcanError;
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Ok(msg)) => return Some(msg),
+                // Errors are only reported by recv
+                Ok(Err(_)) => (),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> Result<CanMessage, Self::Error> {
+        self.rx.recv().await.unwrap_or(Err(SlcanError::Closed))
+    }
+}
+
+/// Open the CAN channel of an slcan adapter
+///
+/// # Arguments
+/// * `port` - The serial port the adapter is attached to
+/// * `bitrate` - The CAN bitrate to configure
+///
+/// The channel is closed, configured with the bitrate, and opened. The adapter's responses to
+/// these commands are not checked, because some adapters report an error when closing a channel
+/// which is not open. Responses which are not frames are ignored by the receiver.
+pub async fn open_slcan<T>(
+    port: T,
+    bitrate: SlcanBitrate,
+) -> Result<(SlcanSender<T>, SlcanReceiver), SlcanError>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut read_half, mut write_half) = split(port);
+    for command in [b"C\r".as_slice(), &bitrate.command(), b"O\r"] {
+        write_half.write_all(command).await.context(IoSnafu)?;
+    }
+    write_half.flush().await.context(IoSnafu)?;
+
+    let (tx, rx) = channel(RX_CHANNEL_SIZE);
+    let cancellation = CancellationToken::new();
+    let task_cancellation = cancellation.clone();
+    let receive = async move {
+        let mut line = Vec::with_capacity(MAX_LINE_LEN);
+        let mut buf = [0u8; 64];
+        loop {
+            let len = match read_half.read(&mut buf).await {
+                Ok(0) => return,
+                Ok(len) => len,
+                Err(e) => {
+                    tx.send(Err(SlcanError::Io { source: e })).await.ok();
+                    return;
+                }
+            };
+            for &b in &buf[..len] {
+                match b {
+                    CR => {
+                        if let Some(msg) = parse_frame(&line) {
+                            if tx.send(Ok(msg)).await.is_err() {
+                                return;
+                            }
+                        }
+                        line.clear();
+                    }
+                    BELL => {
+                        log::warn!("slcan adapter reported an error");
+                        line.clear();
+                    }
+                    // Discard lines which are too long to be valid, rather than growing forever
+                    _ if line.len() < MAX_LINE_LEN => line.push(b),
+                    _ => (),
+                }
+            }
+        }
+    };
+    tokio::spawn(async move {
+        select! {
+            _ = receive => (),
+            _ = task_cancellation.cancelled() => (),
+        }
+    });
+
+    let sender = SlcanSender {
+        port: Arc::new(tokio::sync::Mutex::new(write_half)),
+    };
+    let receiver = SlcanReceiver {
+        rx,
+        _task_guard: cancellation.drop_guard(),
+    };
+    Ok((sender, receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_frame() {
+        assert_eq!(
+            b"t601444000100\r".to_vec(),
+            encode_frame(&CanMessage::new(CanId::std(0x601), &[0x44, 0, 1, 0]))
+        );
+        assert_eq!(
+            b"T18FEF1802ABCD\r".to_vec(),
+            encode_frame(&CanMessage::new(
+                CanId::extended(0x18FE_F180),
+                &[0xAB, 0xCD]
+            ))
+        );
+        assert_eq!(
+            b"r7050\r".to_vec(),
+            encode_frame(&CanMessage::new_rtr(CanId::std(0x705)))
+        );
+        assert_eq!(b"S6\r", &SlcanBitrate::Kbps500.command());
+        assert_eq!(Some(SlcanBitrate::Mbps1), SlcanBitrate::from_bps(1_000_000));
+        assert_eq!(None, SlcanBitrate::from_bps(1));
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        assert_eq!(
+            Some(CanMessage::new(CanId::std(0x581), &[0x60, 0, 0x10, 0])),
+            parse_frame(b"t581460001000")
+        );
+        // With a timestamp
+        assert_eq!(
+            Some(CanMessage::new(CanId::extended(0x0CF0_0400), &[1])),
+            parse_frame(b"T0CF00400101ABCD")
+        );
+        assert_eq!(
+            Some(CanMessage::new_rtr(CanId::extended(0x123))),
+            parse_frame(b"R000001230")
+        );
+        assert_eq!(
+            Some(CanMessage::new(CanId::std(0x80), &[])),
+            parse_frame(b"t0800")
+        );
+        // Truncated data
+        assert_eq!(None, parse_frame(b"t581460001"));
+        // Invalid length
+        assert_eq!(None, parse_frame(b"t5819"));
+        // Out of range ID
+        assert_eq!(None, parse_frame(b"t8000"));
+        // Transmit acknowledgements
+        assert_eq!(None, parse_frame(b"z"));
+        assert_eq!(None, parse_frame(b""));
+    }
+
+    #[tokio::test]
+    async fn test_open_slcan() {
+        let (port, mut adapter) = duplex(256);
+        let (mut tx, mut rx) = open_slcan(port, SlcanBitrate::Kbps250).await.unwrap();
+
+        let mut buf = [0; 7];
+        adapter.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"C\rS5\rO\r", &buf);
+
+        let msg = CanMessage::new(CanId::std(0x601), &[0x40, 0x00, 0x10, 0x00]);
+        tx.send(msg).await.unwrap();
+        let mut buf = [0; 14];
+        adapter.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"t601440001000\r", &buf);
+
+        // The acknowledgement and error are skipped
+        adapter
+            .write_all(b"z\r\x07t5814600010001234\r")
+            .await
+            .unwrap();
+        let reply = CanMessage::new(CanId::std(0x581), &[0x60, 0x00, 0x10, 0x00]);
+        assert_eq!(reply, rx.recv().await.unwrap());
+
+        // The receiver is closed along with the port
+        drop(adapter);
+        assert!(matches!(rx.recv().await, Err(SlcanError::Closed)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_tty_port() {
+        use std::{
+            ffi::CStr,
+            io::{Read, Write},
+            os::fd::FromRawFd,
+        };
+
+        // The test plays the adapter on the master side of a pseudo terminal
+        // SAFETY: The libc calls are used as documented, and each return value is checked
+        let (mut master, slave_path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(0, libc::grantpt(fd));
+            assert_eq!(0, libc::unlockpt(fd));
+            let mut name = [0 as libc::c_char; 64];
+            assert_eq!(0, libc::ptsname_r(fd, name.as_mut_ptr(), name.len()));
+            let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_owned();
+            (std::fs::File::from_raw_fd(fd), path)
+        };
+
+        let port = TtyPort::open(slave_path).unwrap();
+        let (mut tx, mut rx) = open_slcan(port, SlcanBitrate::Mbps1).await.unwrap();
+        let mut buf = [0; 7];
+        master.read_exact(&mut buf).unwrap();
+        assert_eq!(b"C\rS8\rO\r", &buf);
+
+        // CRs are not translated in either direction
+        tx.send(CanMessage::new_rtr(CanId::std(0x705)))
+            .await
+            .unwrap();
+        let mut buf = [0; 6];
+        master.read_exact(&mut buf).unwrap();
+        assert_eq!(b"r7050\r", &buf);
+        master.write_all(b"t1231AB\r").unwrap();
+        assert_eq!(
+            CanMessage::new(CanId::std(0x123), &[0xAB]),
+            rx.recv().await.unwrap()
+        );
+    }
+}
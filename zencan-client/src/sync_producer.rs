@@ -0,0 +1,155 @@
+//! Periodic SYNC transmission
+use std::time::Duration;
+
+use tokio::time::Instant;
+use zencan_common::{messages::SyncObject, traits::AsyncCanSender};
+
+/// The default time spent busy-waiting before each SYNC deadline
+const DEFAULT_SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Sends SYNC messages at a fixed period, for driving synchronous PDOs from a master
+///
+/// Deadlines are scheduled from the time of the first SYNC, rather than from the time the previous
+/// SYNC was sent, so delays in sending one SYNC do not accumulate as drift. If the producer falls
+/// more than a full period behind, e.g. because the task was not polled, the missed SYNCs are
+/// skipped rather than sent in a burst, and counted in [`missed_count`](Self::missed_count).
+///
+/// The tokio timer has a resolution of about a millisecond, so the producer sleeps until shortly
+/// before each deadline, and then busy-waits for the remaining time. The length of the busy-wait
+/// is set with [`set_spin_margin`](Self::set_spin_margin). The busy-wait blocks the executor
+/// thread, so the producer should be run on its own task in a multi-threaded runtime.
+///
+/// ```ignore
+/// let (sender, _) = open_socketcan("can0").unwrap();
+/// let mut producer = SyncProducer::new(sender, Duration::from_millis(10));
+/// producer.set_counter_overflow(4);
+/// tokio::spawn(async move { producer.run().await });
+/// ```
+#[derive(Debug)]
+pub struct SyncProducer<S> {
+    sender: S,
+    period: Duration,
+    counter_overflow: u8,
+    next_count: u8,
+    spin_margin: Duration,
+    next_deadline: Option<Instant>,
+    missed: u64,
+}
+
+impl<S: AsyncCanSender> SyncProducer<S> {
+    /// Create a new SyncProducer which sends SYNC messages without a counter every `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn new(sender: S, period: Duration) -> Self {
+        assert!(!period.is_zero(), "SYNC period must be non-zero");
+        Self {
+            sender,
+            period,
+            counter_overflow: 0,
+            next_count: 1,
+            spin_margin: DEFAULT_SPIN_MARGIN,
+            next_deadline: None,
+            missed: 0,
+        }
+    }
+
+    /// Get the SYNC period
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Set the SYNC counter overflow value
+    ///
+    /// When non-zero, each SYNC carries a counter which starts at 1, and is reset to 1 after it
+    /// reaches `overflow`. When zero, SYNCs are sent without a counter. This is the value of object
+    /// 0x1019 on a SYNC producer. Setting it restarts the counter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `overflow` is 1 or greater than 240, which are reserved values
+    pub fn set_counter_overflow(&mut self, overflow: u8) {
+        assert!(
+            overflow != 1 && overflow <= 240,
+            "SYNC counter overflow must be 0, or 2 to 240"
+        );
+        self.counter_overflow = overflow;
+        self.next_count = 1;
+    }
+
+    /// Set the time spent busy-waiting before each deadline
+    ///
+    /// A longer margin reduces jitter when the system is loaded, at the cost of CPU time. Zero
+    /// disables the busy-wait, and relies on the tokio timer alone. The default is 1ms.
+    pub fn set_spin_margin(&mut self, margin: Duration) {
+        self.spin_margin = margin;
+    }
+
+    /// Get the number of SYNCs which were skipped because the producer fell behind
+    pub fn missed_count(&self) -> u64 {
+        self.missed
+    }
+
+    /// Restart the schedule, so that the next call to [`tick`](Self::tick) sends a SYNC
+    /// immediately, and restart the counter
+    pub fn reset(&mut self) {
+        self.next_deadline = None;
+        self.next_count = 1;
+    }
+
+    /// Send a single SYNC message immediately, without affecting the schedule
+    pub async fn send_sync(&mut self) -> Result<(), S::Error> {
+        let count = if self.counter_overflow == 0 {
+            None
+        } else {
+            let count = self.next_count;
+            self.next_count = if count >= self.counter_overflow {
+                1
+            } else {
+                count + 1
+            };
+            Some(count)
+        };
+        self.sender.send(SyncObject::new(count).into()).await
+    }
+
+    /// Wait until the next SYNC is due, and send it
+    ///
+    /// The first call sends a SYNC immediately, and starts the schedule.
+    pub async fn tick(&mut self) -> Result<(), S::Error> {
+        let deadline = match self.next_deadline {
+            Some(deadline) => {
+                self.wait_until(deadline).await;
+                deadline
+            }
+            None => Instant::now(),
+        };
+        self.send_sync().await?;
+
+        let mut next = deadline + self.period;
+        let now = Instant::now();
+        if now > next {
+            let behind = (now - next).as_nanos() / self.period.as_nanos() + 1;
+            self.missed += behind as u64;
+            next += self.period * behind as u32;
+        }
+        self.next_deadline = Some(next);
+        Ok(())
+    }
+
+    /// Send SYNC messages until sending fails
+    pub async fn run(&mut self) -> Result<(), S::Error> {
+        loop {
+            self.tick().await?;
+        }
+    }
+
+    async fn wait_until(&self, deadline: Instant) {
+        let coarse = deadline.checked_sub(self.spin_margin).unwrap_or(deadline);
+        tokio::time::sleep_until(coarse).await;
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Recording of client metrics via the `metrics` facade, and of outcomes in `tracing` spans
+//!
+//! When the `metrics` and `tracing` features are disabled, these functions do nothing. See the
+//! crate docs for the list of metrics and spans.
+use std::time::Duration;
+
+use zencan_common::{messages::CanId, CanMessage};
+
+use crate::sdo_client::SdoClientError;
+
+/// Get the label identifying the node which a message belongs to, based on the predefined
+/// connection set
+///
+/// Messages which do not belong to a node, e.g. NMT commands, SYNC, and LSS, are labelled "none".
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+fn node_label(id: CanId) -> String {
+    match id {
+        CanId::Std(id) if (0x80..0x780).contains(&id) && id & 0x7F != 0 => (id & 0x7F).to_string(),
+        _ => "none".into(),
+    }
+}
+
+/// Record a completed SDO transfer
+///
+/// `resp_cob_id` is the COB ID on which the server responds, and `op` is the type of transfer. The
+/// outcome is also recorded in the `outcome` field of the current span.
+pub(crate) fn sdo_transfer<T>(
+    resp_cob_id: CanId,
+    op: &'static str,
+    elapsed: Duration,
+    result: &Result<T, SdoClientError>,
+) {
+    let outcome = match result {
+        Ok(_) => "ok",
+        Err(SdoClientError::ServerAbort { .. }) => "abort",
+        Err(SdoClientError::NoResponse) => "timeout",
+        Err(_) => "error",
+    };
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("outcome", outcome);
+    #[cfg(feature = "metrics")]
+    {
+        let node = node_label(resp_cob_id);
+        if matches!(result, Err(SdoClientError::NoResponse)) {
+            metrics::counter!("zencan_sdo_timeouts_total", "node" => node.clone()).increment(1);
+        }
+        metrics::histogram!(
+            "zencan_sdo_transfer_seconds",
+            "node" => node,
+            "op" => op,
+            "result" => outcome
+        )
+        .record(elapsed);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (resp_cob_id, op, elapsed, outcome);
+}
+
+/// Record a retry after a failure to send an SDO request
+pub(crate) fn sdo_send_retry(resp_cob_id: CanId) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("zencan_sdo_send_retries_total", "node" => node_label(resp_cob_id))
+        .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = resp_cob_id;
+}
+
+/// Record a frame sent by a [`BusManager`](crate::BusManager)
+pub(crate) fn frame_sent(msg: &CanMessage) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("zencan_frames_sent_total", "node" => node_label(msg.id())).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = msg;
+}
+
+/// Record a frame received by a [`BusManager`](crate::BusManager)
+pub(crate) fn frame_received(msg: &CanMessage) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("zencan_frames_received_total", "node" => node_label(msg.id())).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = msg;
+}
+
+/// Record a received frame which was dropped because a receiver channel was full
+pub(crate) fn frame_dropped() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("zencan_frames_dropped_total").increment(1);
+}
+
+/// Record the time between two consecutive heartbeats from a node
+pub(crate) fn heartbeat_gap(node: u8, gap: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("zencan_heartbeat_interval_seconds", "node" => node.to_string())
+        .record(gap);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (node, gap);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_label() {
+        assert_eq!("5", node_label(CanId::std(0x585)));
+        assert_eq!("127", node_label(CanId::std(0x77F)));
+        assert_eq!("1", node_label(CanId::std(0x181)));
+        // NMT, SYNC, LSS
+        assert_eq!("none", node_label(CanId::std(0x000)));
+        assert_eq!("none", node_label(CanId::std(0x080)));
+        assert_eq!("none", node_label(CanId::std(0x7E5)));
+        assert_eq!("none", node_label(CanId::extended(0x585)));
+    }
+}
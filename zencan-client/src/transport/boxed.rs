@@ -0,0 +1,139 @@
+//! Type erased senders and receivers
+use futures::future::{BoxFuture, LocalBoxFuture};
+use snafu::Snafu;
+use zencan_common::{
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+/// Object safe version of [`AsyncCanSender`]
+trait DynCanSender: Send + Sync {
+    fn send(&mut self, msg: CanMessage) -> LocalBoxFuture<'_, Result<(), BoxedSendError>>;
+}
+
+impl<T: AsyncCanSender + Sync> DynCanSender for T {
+    fn send(&mut self, msg: CanMessage) -> LocalBoxFuture<'_, Result<(), BoxedSendError>> {
+        Box::pin(async move {
+            AsyncCanSender::send(self, msg)
+                .await
+                .map_err(|e| BoxedSendError {
+                    description: e.message(),
+                    message: e.into_can_message(),
+                })
+        })
+    }
+}
+
+/// Object safe version of [`AsyncCanReceiver`]
+trait DynCanReceiver: Send + Sync {
+    fn try_recv(&mut self) -> Option<CanMessage>;
+    fn recv(&mut self) -> BoxFuture<'_, Result<CanMessage, BoxedReceiveError>>;
+    fn flush(&mut self);
+}
+
+impl<T: AsyncCanReceiver + Sync> DynCanReceiver for T {
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        AsyncCanReceiver::try_recv(self)
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<CanMessage, BoxedReceiveError>> {
+        Box::pin(async move {
+            AsyncCanReceiver::recv(self)
+                .await
+                .map_err(|e| BoxedReceiveError {
+                    message: format!("{e:?}"),
+                })
+        })
+    }
+
+    fn flush(&mut self) {
+        AsyncCanReceiver::flush(self)
+    }
+}
+
+/// Error returned by [`BoxedCanSender`]
+#[derive(Debug, Snafu)]
+#[snafu(display("Error sending {message:?}: {description}"))]
+pub struct BoxedSendError {
+    message: CanMessage,
+    description: String,
+}
+
+impl CanSendError for BoxedSendError {
+    fn into_can_message(self) -> CanMessage {
+        self.message
+    }
+
+    fn message(&self) -> String {
+        self.description.clone()
+    }
+}
+
+/// Error returned by [`BoxedCanReceiver`]
+#[derive(Clone, Debug, PartialEq, Eq, Snafu)]
+#[snafu(display("Error receiving: {message}"))]
+pub struct BoxedReceiveError {
+    /// The debug representation of the underlying receiver's error
+    pub message: String,
+}
+
+/// An [`AsyncCanSender`] wrapping any other sender
+///
+/// This allows the interface to be chosen at runtime. Note that, unlike the future returned by
+/// most senders, the future returned by `send` is not `Send`.
+pub struct BoxedCanSender(Box<dyn DynCanSender>);
+
+impl BoxedCanSender {
+    /// Wrap a sender
+    pub fn new<T: AsyncCanSender + Sync + 'static>(sender: T) -> Self {
+        Self(Box::new(sender))
+    }
+}
+
+impl core::fmt::Debug for BoxedCanSender {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BoxedCanSender").finish_non_exhaustive()
+    }
+}
+
+impl AsyncCanSender for BoxedCanSender {
+    type Error = BoxedSendError;
+
+    async fn send(&mut self, msg: CanMessage) -> Result<(), Self::Error> {
+        self.0.send(msg).await
+    }
+}
+
+/// An [`AsyncCanReceiver`] wrapping any other receiver
+pub struct BoxedCanReceiver(Box<dyn DynCanReceiver>);
+
+impl BoxedCanReceiver {
+    /// Wrap a receiver
+    pub fn new<T: AsyncCanReceiver + Sync + 'static>(receiver: T) -> Self {
+        Self(Box::new(receiver))
+    }
+}
+
+impl core::fmt::Debug for BoxedCanReceiver {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BoxedCanReceiver").finish_non_exhaustive()
+    }
+}
+
+impl AsyncCanReceiver for BoxedCanReceiver {
+    type Error = BoxedReceiveError;
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        self.0.try_recv()
+    }
+
+    fn recv(
+        &mut self,
+    ) -> impl core::future::Future<Output = Result<CanMessage, Self::Error>> + Send {
+        self.0.recv()
+    }
+
+    fn flush(&mut self) {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,371 @@
+//! Opening CAN interfaces by name
+//!
+//! [`open_can`] opens an interface given as a URI-style string, `<scheme>:<address>`, so that tools
+//! and tests can select it from a command line argument or config file. The sender and receiver
+//! are returned boxed, so that they have the same type for every kind of interface.
+//!
+//! | Scheme      | Example                   | Interface                                         |
+//! |-------------|---------------------------|---------------------------------------------------|
+//! | `socketcan` | `socketcan:can0`          | A socketcan interface (linux)                     |
+//! | `udp`       | `udp:192.168.1.10:20000`  | A [cannelloni](crate::cannelloni) gateway via UDP |
+//! | `tcp`       | `tcp:192.168.1.10:20000`  | A cannelloni gateway via TCP                      |
+//! | `slcan`     | `slcan:/dev/ttyACM0@1M`   | An [slcan](crate::slcan) adapter (unix)           |
+//! | `pcan`      | `pcan:usb1@500k`          | A PCAN-USB channel (`pcan` feature)               |
+//! | `sim`       | `sim:test`                | An in-process [virtual bus](sim)                  |
+//!
+//! A name without a scheme, e.g. `can0`, is opened as a socketcan interface. For UDP, the port of
+//! the gateway is also bound locally, so the gateway must be configured to send to it. Bitrates are
+//! given in bits per second, optionally with a `k` or `M` suffix.
+//!
+//! ```ignore
+//! let (tx, rx) = open_can("slcan:/dev/ttyACM0@500k").await?;
+//! let manager = BusManager::new(tx, rx);
+//! ```
+//!
+//! Other interfaces can be added by registering them with a [`TransportRegistry`]:
+//!
+//! ```ignore
+//! let mut registry = TransportRegistry::default();
+//! registry.register("mycan", |address: String| async move {
+//!     let (tx, rx) = open_my_can(&address).await.map_err(|e| TransportError::Open {
+//!         message: e.to_string(),
+//!     })?;
+//!     Ok((BoxedCanSender::new(tx), BoxedCanReceiver::new(rx)))
+//! });
+//! let (tx, rx) = registry.open("mycan:0").await?;
+//! ```
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use futures::future::BoxFuture;
+use snafu::Snafu;
+
+mod boxed;
+pub mod sim;
+
+pub use boxed::{BoxedCanReceiver, BoxedCanSender, BoxedReceiveError, BoxedSendError};
+
+/// Error returned when opening an interface
+#[derive(Clone, Debug, PartialEq, Eq, Snafu)]
+pub enum TransportError {
+    /// No transport is registered for the scheme
+    #[snafu(display("Unsupported interface type '{scheme}'"))]
+    UnknownScheme {
+        /// The scheme
+        scheme: String,
+    },
+    /// The address could not be parsed
+    #[snafu(display("Invalid address '{address}': {reason}"))]
+    InvalidAddress {
+        /// The address, without the scheme
+        address: String,
+        /// A description of the problem
+        reason: String,
+    },
+    /// The interface could not be opened
+    #[snafu(display("{message}"))]
+    Open {
+        /// A description of the error
+        message: String,
+    },
+}
+
+impl TransportError {
+    fn open(e: impl core::fmt::Display) -> Self {
+        Self::Open {
+            message: e.to_string(),
+        }
+    }
+}
+
+/// The boxed sender and receiver returned when an interface is opened
+pub type BoxedCanPair = (BoxedCanSender, BoxedCanReceiver);
+
+type Factory =
+    Arc<dyn Fn(String) -> BoxFuture<'static, Result<BoxedCanPair, TransportError>> + Send + Sync>;
+
+/// A set of transports, by URI scheme
+///
+/// The default registry contains all of the transports supported on the platform, with the enabled
+/// features.
+#[derive(Clone)]
+pub struct TransportRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl core::fmt::Debug for TransportRegistry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TransportRegistry")
+            .field("schemes", &self.schemes().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for TransportRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        #[cfg(all(feature = "socketcan", target_os = "linux"))]
+        registry.register("socketcan", builtin::socketcan);
+        registry.register("udp", builtin::cannelloni_udp);
+        registry.register("tcp", builtin::cannelloni_tcp);
+        #[cfg(unix)]
+        registry.register("slcan", builtin::slcan);
+        #[cfg(feature = "pcan")]
+        registry.register("pcan", builtin::pcan);
+        registry.register("sim", builtin::sim);
+        registry
+    }
+}
+
+impl TransportRegistry {
+    /// Create a registry with no transports
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register a transport, replacing any existing transport for the scheme
+    ///
+    /// The factory is called with the address following the scheme, and returns the opened
+    /// interface.
+    pub fn register<F, Fut>(&mut self, scheme: impl Into<String>, factory: F)
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<BoxedCanPair, TransportError>> + Send + 'static,
+    {
+        self.factories.insert(
+            scheme.into(),
+            Arc::new(move |address| Box::pin(factory(address))),
+        );
+    }
+
+    /// Get the registered schemes
+    pub fn schemes(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Open an interface, given as `<scheme>:<address>`
+    ///
+    /// A name without a scheme is opened with the `socketcan` transport.
+    pub async fn open(&self, uri: &str) -> Result<BoxedCanPair, TransportError> {
+        let (scheme, address) = uri.split_once(':').unwrap_or(("socketcan", uri));
+        let factory = self
+            .factories
+            .get(scheme)
+            .ok_or_else(|| TransportError::UnknownScheme {
+                scheme: scheme.to_owned(),
+            })?;
+        factory(address.to_owned()).await
+    }
+}
+
+/// Open an interface with the default [`TransportRegistry`]
+///
+/// See the [module docs](self) for the supported interfaces. Must be called from within a tokio
+/// runtime.
+pub async fn open_can(uri: &str) -> Result<BoxedCanPair, TransportError> {
+    TransportRegistry::default().open(uri).await
+}
+
+/// Parse a bitrate, e.g. `500000`, `500k` or `1M`
+fn parse_bitrate(s: &str) -> Option<u32> {
+    let (digits, multiplier) = if let Some(digits) = s.strip_suffix(['k', 'K']) {
+        (digits, 1_000)
+    } else if let Some(digits) = s.strip_suffix('M') {
+        (digits, 1_000_000)
+    } else {
+        (s, 1)
+    };
+    digits.parse::<u32>().ok()?.checked_mul(multiplier)
+}
+
+/// Split an address of the form `<device>@<bitrate>`
+#[cfg(any(unix, feature = "pcan"))]
+fn split_bitrate(address: &str) -> Result<(&str, u32), TransportError> {
+    let invalid = |reason: &str| TransportError::InvalidAddress {
+        address: address.to_owned(),
+        reason: reason.to_owned(),
+    };
+    let (device, bitrate) = address
+        .rsplit_once('@')
+        .ok_or_else(|| invalid("expected <device>@<bitrate>"))?;
+    let bitrate = parse_bitrate(bitrate).ok_or_else(|| invalid("invalid bitrate"))?;
+    Ok((device, bitrate))
+}
+
+mod builtin {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use super::*;
+    use crate::cannelloni::{open_cannelloni_tcp, open_cannelloni_udp};
+
+    fn boxed<S, R>((tx, rx): (S, R)) -> BoxedCanPair
+    where
+        S: zencan_common::traits::AsyncCanSender + Sync + 'static,
+        R: zencan_common::traits::AsyncCanReceiver + Sync + 'static,
+    {
+        (BoxedCanSender::new(tx), BoxedCanReceiver::new(rx))
+    }
+
+    async fn resolve(address: &str) -> Result<SocketAddr, TransportError> {
+        let invalid = |reason: String| TransportError::InvalidAddress {
+            address: address.to_owned(),
+            reason,
+        };
+        tokio::net::lookup_host(address)
+            .await
+            .map_err(|e| invalid(e.to_string()))?
+            .next()
+            .ok_or_else(|| invalid("no address found".into()))
+    }
+
+    #[cfg(all(feature = "socketcan", target_os = "linux"))]
+    pub async fn socketcan(address: String) -> Result<BoxedCanPair, TransportError> {
+        crate::open_socketcan(&address)
+            .map(boxed)
+            .map_err(TransportError::open)
+    }
+
+    pub async fn cannelloni_udp(address: String) -> Result<BoxedCanPair, TransportError> {
+        let remote = resolve(&address).await?;
+        let local = match remote {
+            SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), remote.port()),
+            SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), remote.port()),
+        };
+        open_cannelloni_udp(local, remote)
+            .await
+            .map(boxed)
+            .map_err(TransportError::open)
+    }
+
+    pub async fn cannelloni_tcp(address: String) -> Result<BoxedCanPair, TransportError> {
+        open_cannelloni_tcp(resolve(&address).await?)
+            .await
+            .map(boxed)
+            .map_err(TransportError::open)
+    }
+
+    #[cfg(unix)]
+    pub async fn slcan(address: String) -> Result<BoxedCanPair, TransportError> {
+        use crate::slcan::{open_slcan, SlcanBitrate, TtyPort};
+
+        let (path, bitrate) = split_bitrate(&address)?;
+        let bitrate =
+            SlcanBitrate::from_bps(bitrate).ok_or_else(|| TransportError::InvalidAddress {
+                address: address.clone(),
+                reason: format!("unsupported bitrate {bitrate}"),
+            })?;
+        let port = TtyPort::open(path).map_err(TransportError::open)?;
+        open_slcan(port, bitrate)
+            .await
+            .map(boxed)
+            .map_err(TransportError::open)
+    }
+
+    /// Parse a PCAN address, e.g. `usb1@500k`
+    #[cfg(feature = "pcan")]
+    pub fn parse_pcan(
+        address: &str,
+    ) -> Result<(crate::pcan::PcanChannel, crate::pcan::PcanBitrate), TransportError> {
+        use crate::pcan::{PcanBitrate, PcanChannel};
+
+        let invalid = |reason: String| TransportError::InvalidAddress {
+            address: address.to_owned(),
+            reason,
+        };
+        let (channel, bitrate) = split_bitrate(address)?;
+        let channel = channel
+            .strip_prefix("usb")
+            .and_then(|n| n.parse().ok())
+            .and_then(PcanChannel::usb)
+            .ok_or_else(|| invalid("expected a channel from usb1 to usb16".into()))?;
+        let bitrate = PcanBitrate::from_bps(bitrate)
+            .ok_or_else(|| invalid(format!("unsupported bitrate {bitrate}")))?;
+        Ok((channel, bitrate))
+    }
+
+    #[cfg(feature = "pcan")]
+    pub async fn pcan(address: String) -> Result<BoxedCanPair, TransportError> {
+        let (channel, bitrate) = parse_pcan(&address)?;
+        crate::pcan::open_pcan(channel, bitrate)
+            .map(boxed)
+            .map_err(TransportError::open)
+    }
+
+    pub async fn sim(address: String) -> Result<BoxedCanPair, TransportError> {
+        Ok(boxed(sim::open_sim(&address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::{
+        traits::{AsyncCanReceiver, AsyncCanSender},
+        CanId, CanMessage,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_parse_bitrate() {
+        assert_eq!(Some(500_000), parse_bitrate("500000"));
+        assert_eq!(Some(500_000), parse_bitrate("500k"));
+        assert_eq!(Some(1_000_000), parse_bitrate("1M"));
+        assert_eq!(None, parse_bitrate("1G"));
+        assert_eq!(None, parse_bitrate("5000M"));
+        assert_eq!(
+            Ok(("/dev/ttyACM0", 125_000)),
+            split_bitrate("/dev/ttyACM0@125K")
+        );
+        assert!(split_bitrate("/dev/ttyACM0").is_err());
+    }
+
+    #[cfg(feature = "pcan")]
+    #[test]
+    fn test_parse_pcan() {
+        use crate::pcan::{PcanBitrate, PcanChannel};
+
+        assert_eq!(
+            Ok((PcanChannel::usb(2).unwrap(), PcanBitrate::Kbps250)),
+            builtin::parse_pcan("usb2@250k")
+        );
+        assert!(builtin::parse_pcan("usb1").is_err());
+        assert!(builtin::parse_pcan("pci1@500k").is_err());
+        assert!(builtin::parse_pcan("usb1@12345").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_open_can() {
+        let (mut tx, _rx) = open_can("sim:test_open_can").await.unwrap();
+        let (_tx, mut rx) = open_can("sim:test_open_can").await.unwrap();
+        let msg = CanMessage::new(CanId::std(0x701), &[0x05]);
+        tx.send(msg).await.unwrap();
+        assert_eq!(msg, rx.recv().await.unwrap());
+
+        assert_eq!(
+            TransportError::UnknownScheme {
+                scheme: "foo".into()
+            },
+            open_can("foo:bar").await.unwrap_err()
+        );
+        assert!(matches!(
+            open_can("slcan:/dev/ttyACM0").await,
+            Err(TransportError::InvalidAddress { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let mut registry = TransportRegistry::new();
+        assert_eq!(0, registry.schemes().count());
+        // A transport which opens a sim bus with a prefixed name
+        registry.register("test", |address: String| async move {
+            builtin::sim(format!("test-{address}")).await
+        });
+        let (mut tx, _rx) = registry.open("test:a").await.unwrap();
+        let (_tx, mut rx) = open_can("sim:test-a").await.unwrap();
+        let msg = CanMessage::new(CanId::std(0x702), &[0x05]);
+        tx.send(msg).await.unwrap();
+        assert_eq!(Some(msg), rx.try_recv());
+    }
+}
@@ -0,0 +1,137 @@
+//! An in-process virtual bus
+//!
+//! Every sender and receiver pair opened with the same name is connected to the same bus. A message
+//! sent by a sender is received by every receiver on the bus except the one it was opened with,
+//! the same as a socketcan socket. A bus exists as long as any of its senders or receivers do.
+//!
+//! This allows tools and tests to be run against each other without any CAN hardware, by selecting
+//! e.g. `sim:test` with [`open_can`](super::open_can).
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use zencan_common::{
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+    CanMessage,
+};
+
+#[derive(Debug, Default)]
+struct SimBusShared {
+    /// The channel to each receiver, along with the ID of its endpoint
+    endpoints: Mutex<Vec<(usize, UnboundedSender<CanMessage>)>>,
+    next_endpoint: AtomicUsize,
+}
+
+/// The open buses, by name
+static BUSES: Mutex<Vec<(String, Weak<SimBusShared>)>> = Mutex::new(Vec::new());
+
+/// Error type of [`SimSender`], which can't fail
+#[derive(Clone, Copy, Debug)]
+pub struct SimSendError(Infallible);
+
+impl CanSendError for SimSendError {
+    fn into_can_message(self) -> CanMessage {
+        match self.0 {}
+    }
+
+    fn message(&self) -> String {
+        match self.0 {}
+    }
+}
+
+/// The sending half of a connection to a virtual bus
+#[derive(Debug, Clone)]
+pub struct SimSender {
+    bus: Arc<SimBusShared>,
+    endpoint: usize,
+}
+
+impl AsyncCanSender for SimSender {
+    type Error = SimSendError;
+
+    async fn send(&mut self, msg: CanMessage) -> Result<(), Self::Error> {
+        let mut endpoints = self.bus.endpoints.lock().unwrap();
+        // Receivers which have been dropped are removed
+        endpoints.retain(|(endpoint, tx)| *endpoint == self.endpoint || tx.send(msg).is_ok());
+        Ok(())
+    }
+}
+
+/// The receiving half of a connection to a virtual bus
+#[derive(Debug)]
+pub struct SimReceiver {
+    rx: UnboundedReceiver<CanMessage>,
+    _bus: Arc<SimBusShared>,
+}
+
+impl AsyncCanReceiver for SimReceiver {
+    type Error = Infallible;
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        self.rx.try_recv().ok()
+    }
+
+    async fn recv(&mut self) -> Result<CanMessage, Self::Error> {
+        // The bus holds the sending half of the channel as long as the receiver exists
+        Ok(self.rx.recv().await.expect("Sim bus channel closed"))
+    }
+}
+
+/// Open a connection to the virtual bus with the given name, creating it if it does not exist
+pub fn open_sim(name: &str) -> (SimSender, SimReceiver) {
+    let mut buses = BUSES.lock().unwrap();
+    buses.retain(|(_, bus)| bus.strong_count() > 0);
+    let bus = match buses.iter().find(|(n, _)| n == name) {
+        Some((_, bus)) => bus.upgrade().unwrap(),
+        None => {
+            let bus = Arc::new(SimBusShared::default());
+            buses.push((name.to_owned(), Arc::downgrade(&bus)));
+            bus
+        }
+    };
+
+    let (tx, rx) = unbounded_channel();
+    let endpoint = bus.next_endpoint.fetch_add(1, Ordering::Relaxed);
+    bus.endpoints.lock().unwrap().push((endpoint, tx));
+
+    let sender = SimSender {
+        bus: bus.clone(),
+        endpoint,
+    };
+    let receiver = SimReceiver { rx, _bus: bus };
+    (sender, receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::CanId;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sim_bus() {
+        let (mut tx_a, mut rx_a) = open_sim("test_sim_bus");
+        let (mut tx_b, mut rx_b) = open_sim("test_sim_bus");
+        let (_, mut rx_other) = open_sim("test_sim_bus_other");
+
+        let msg = CanMessage::new(CanId::std(0x123), &[1]);
+        tx_a.send(msg).await.unwrap();
+        assert_eq!(Some(msg), rx_b.try_recv());
+        // Not received by the sender's own receiver, or on another bus
+        assert_eq!(None, rx_a.try_recv());
+        assert_eq!(None, rx_other.try_recv());
+
+        // Dropped receivers are removed, and the bus stays open while any sender remains
+        drop(rx_a);
+        tx_b.send(msg).await.unwrap();
+        let (_, mut rx_c) = open_sim("test_sim_bus");
+        tx_b.send(msg).await.unwrap();
+        assert_eq!(Some(msg), rx_c.try_recv());
+        assert_eq!(None, rx_b.try_recv());
+    }
+}
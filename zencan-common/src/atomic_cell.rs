@@ -21,13 +21,6 @@ impl<T: Send + Copy> AtomicCell<T> {
         critical_section::with(|cs| self.inner.borrow(cs).get())
     }
 
-    /// Borrow a reference to the contained value
-    ///
-    /// A critical section must be obtained by the called and provided
-    pub fn borrow<'a>(&'a self, cs: critical_section::CriticalSection<'a>) -> &'a Cell<T> {
-        self.inner.borrow(cs)
-    }
-
     /// Perform atomic modification of the contained value
     ///
     /// This operation will be performed in a critical section, so it will block all IRQs until the
@@ -53,10 +46,22 @@ impl<T: Send> AtomicCell<T> {
         }
     }
 
+    /// Borrow a reference to the contained value
+    ///
+    /// A critical section must be obtained by the called and provided
+    pub fn borrow<'a>(&'a self, cs: critical_section::CriticalSection<'a>) -> &'a Cell<T> {
+        self.inner.borrow(cs)
+    }
+
     /// Replace the value of the AtomicCell
     pub fn store(&self, value: T) {
         critical_section::with(|cs| self.inner.borrow(cs).set(value));
     }
+
+    /// Replace the value of the AtomicCell, returning the previous value
+    pub fn replace(&self, value: T) -> T {
+        critical_section::with(|cs| self.inner.borrow(cs).replace(value))
+    }
 }
 
 impl<T: Send + Default> AtomicCell<T> {
@@ -0,0 +1,219 @@
+//! Concise DCF encoding
+//!
+//! A concise DCF is a compact binary list of object values to be written to a node, as defined by
+//! CiA 302. A configuration manager stores one for each node it configures in object 0x1F22, and
+//! writes its entries to the node over SDO, in order, when the node boots.
+//!
+//! # Encoding
+//!
+//! The data starts with the number of entries, followed by the entries. All values are little
+//! endian.
+//!
+//! | Field | Type         | Description |
+//! | ----- | ------------ | ----------- |
+//! | count | u32          | Number of entries |
+//!
+//! Each entry is:
+//!
+//! | Field | Type          | Description |
+//! | ----- | ------------- | ----------- |
+//! | index | u16           | Object index |
+//! | sub   | u8            | Sub index |
+//! | size  | u32           | Size of the value in bytes |
+//! | data  | \[u8; size\]  | The value to write |
+//!
+//! An empty buffer is accepted as a concise DCF with no entries, so that an empty 0x1F22 sub
+//! object means there is no configuration to apply.
+
+use snafu::Snafu;
+
+/// The size of the entry header, excluding the data
+const ENTRY_HEADER_SIZE: usize = 2 + 1 + 4;
+
+/// Error returned when a concise DCF cannot be decoded
+#[derive(Clone, Copy, Debug, PartialEq, Snafu)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConciseDcfError {
+    /// The data ends part way through the header or an entry
+    #[snafu(display("Concise DCF is truncated"))]
+    Truncated,
+    /// The data continues after the last entry
+    #[snafu(display("Concise DCF has {len} bytes after the last entry"))]
+    TrailingData {
+        /// The number of extra bytes
+        len: usize,
+    },
+}
+
+/// A single value in a concise DCF
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConciseDcfEntry<'a> {
+    /// The object index to write
+    pub index: u16,
+    /// The sub index to write
+    pub sub: u8,
+    /// The value to write
+    pub data: &'a [u8],
+}
+
+/// An iterator over the entries of a concise DCF
+///
+/// Created by [`entries`]. Once an error is returned, the iterator ends.
+#[derive(Clone, Debug)]
+pub struct ConciseDcfEntries<'a> {
+    data: &'a [u8],
+    remaining: u32,
+    failed: bool,
+}
+
+/// Iterate over the entries of a concise DCF
+///
+/// The header is checked here, and each entry is checked as it is reached. The iterator returns
+/// an error if the data is truncated, or if any data follows the last entry.
+pub fn entries(data: &[u8]) -> Result<ConciseDcfEntries<'_>, ConciseDcfError> {
+    if data.is_empty() {
+        return Ok(ConciseDcfEntries {
+            data,
+            remaining: 0,
+            failed: false,
+        });
+    }
+    let Some((count, data)) = data.split_first_chunk::<4>() else {
+        return TruncatedSnafu.fail();
+    };
+    Ok(ConciseDcfEntries {
+        data,
+        remaining: u32::from_le_bytes(*count),
+        failed: false,
+    })
+}
+
+impl<'a> ConciseDcfEntries<'a> {
+    fn next_entry(&mut self) -> Result<ConciseDcfEntry<'a>, ConciseDcfError> {
+        if self.data.len() < ENTRY_HEADER_SIZE {
+            return TruncatedSnafu.fail();
+        }
+        let (header, rest) = self.data.split_at(ENTRY_HEADER_SIZE);
+        let index = u16::from_le_bytes([header[0], header[1]]);
+        let sub = header[2];
+        let size = u32::from_le_bytes([header[3], header[4], header[5], header[6]]) as usize;
+        if rest.len() < size {
+            return TruncatedSnafu.fail();
+        }
+        let (data, rest) = rest.split_at(size);
+        self.data = rest;
+        Ok(ConciseDcfEntry { index, sub, data })
+    }
+}
+
+impl<'a> Iterator for ConciseDcfEntries<'a> {
+    type Item = Result<ConciseDcfEntry<'a>, ConciseDcfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = if self.remaining == 0 {
+            if self.data.is_empty() {
+                return None;
+            }
+            TrailingDataSnafu {
+                len: self.data.len(),
+            }
+            .fail()
+        } else {
+            self.remaining -= 1;
+            self.next_entry()
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Encode a list of `(index, sub, data)` values as a concise DCF
+///
+/// # Panics
+///
+/// Panics if there are more than `u32::MAX` entries, or a value is larger than `u32::MAX` bytes
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn encode<'a>(values: impl IntoIterator<Item = (u16, u8, &'a [u8])>) -> Vec<u8> {
+    let mut count = 0u32;
+    let mut out = vec![0; 4];
+    for (index, sub, data) in values {
+        let size: u32 = data
+            .len()
+            .try_into()
+            .expect("Concise DCF values must be at most u32::MAX bytes");
+        out.extend_from_slice(&index.to_le_bytes());
+        out.push(sub);
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(data);
+        count = count.checked_add(1).expect("Too many concise DCF entries");
+    }
+    out[..4].copy_from_slice(&count.to_le_bytes());
+    out
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = encode([
+            (0x1017, 0, [0xe8, 0x03].as_slice()),
+            (0x2000, 1, &[1, 2, 3, 4]),
+            (0x2002, 0, &[]),
+        ]);
+        assert_eq!(
+            [3, 0, 0, 0, 0x17, 0x10, 0, 2, 0, 0, 0, 0xe8, 0x03],
+            data[..13]
+        );
+        let decoded: Vec<_> = entries(&data).unwrap().map(Result::unwrap).collect();
+        assert_eq!(
+            vec![
+                ConciseDcfEntry {
+                    index: 0x1017,
+                    sub: 0,
+                    data: &[0xe8, 0x03]
+                },
+                ConciseDcfEntry {
+                    index: 0x2000,
+                    sub: 1,
+                    data: &[1, 2, 3, 4]
+                },
+                ConciseDcfEntry {
+                    index: 0x2002,
+                    sub: 0,
+                    data: &[]
+                },
+            ],
+            decoded
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(0, entries(&[]).unwrap().count());
+        assert_eq!(
+            Err(ConciseDcfError::Truncated),
+            entries(&[1, 0]).map(|_| ())
+        );
+
+        let data = encode([(0x2000, 1, [1, 2, 3, 4].as_slice())]);
+        let mut it = entries(&data[..data.len() - 1]).unwrap();
+        assert_eq!(Some(Err(ConciseDcfError::Truncated)), it.next());
+        assert_eq!(None, it.next());
+
+        let mut extended = data.clone();
+        extended.push(0);
+        let mut it = entries(&extended).unwrap();
+        assert!(it.next().unwrap().is_ok());
+        assert_eq!(
+            Some(Err(ConciseDcfError::TrailingData { len: 1 })),
+            it.next()
+        );
+    }
+}
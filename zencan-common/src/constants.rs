@@ -4,6 +4,10 @@
 
 /// Object indices for standard objects
 pub mod object_ids {
+    /// The pre-defined error field object index, holding the error history of the node
+    pub const PRE_DEFINED_ERROR_FIELD: u16 = 0x1003;
+    /// The synchronous window length object index
+    pub const SYNC_WINDOW_LENGTH: u16 = 0x1007;
     /// The Device Name object index
     pub const DEVICE_NAME: u16 = 0x1008;
     /// The hardware version object index
@@ -17,6 +21,13 @@ pub mod object_ids {
     /// The identity object index
     pub const IDENTITY: u16 = 0x1018;
 
+    /// The store DCF object index, holding a DCF file for each node managed by a configuration
+    /// manager
+    pub const STORE_DCF: u16 = 0x1F20;
+    /// The concise DCF object index, holding a concise DCF for each node managed by a
+    /// configuration manager
+    pub const CONCISE_DCF: u16 = 0x1F22;
+
     /// The first RPDO communication parameter index. RPDO comm can be stored from 0x1400 to 0x15FF.
     pub const RPDO_COMM_BASE: u16 = 0x1400;
     ///  The first RPDO mapping parameter index. RPDO mappings can be stored from 0x1600 to 0x17FF;
@@ -28,6 +39,18 @@ pub mod object_ids {
 
     /// The auto start object index
     pub const AUTO_START: u16 = 0x5000;
+    /// The startup config object index
+    pub const STARTUP_CONFIG: u16 = 0x5001;
+    /// The change counters object index
+    pub const CHANGE_COUNTERS: u16 = 0x5F02;
+    /// The sub index of the boot epoch in the change counters object
+    pub const CHANGE_COUNTERS_BOOT_EPOCH_SUB: u8 = 3;
+    /// The unit metadata object index
+    pub const UNIT_METADATA: u16 = 0x5F03;
+    /// The configuration signature object index
+    pub const CONFIG_SIGNATURE: u16 = 0x5F04;
+    /// The node status object index
+    pub const NODE_STATUS: u16 = 0x5F05;
 }
 
 /// Special values used to access standard objects
@@ -40,4 +63,7 @@ pub mod values {
 
     /// Magic value used to trigger bootloader section erase by writing objects 0x5510-0x551f
     pub const BOOTLOADER_ERASE_CMD: u32 = 0x53415245;
+
+    /// Magic value used to request a swap to the inactive bank by writing to object 0x5500
+    pub const BOOTLOADER_SWAP_CMD: u32 = 0x50415753;
 }
@@ -0,0 +1,75 @@
+//! CRC32 calculation
+//!
+//! Uses the IEEE 802.3 polynomial, as produced by e.g. zlib's `crc32` and `cksum -a crc32b`. It is
+//! used to verify the contents of bootloader sections.
+
+/// The reflected IEEE 802.3 polynomial
+const POLY: u32 = 0xEDB8_8320;
+
+/// An in-progress CRC32 calculation
+///
+/// ```
+/// use zencan_common::crc32::{crc32, Crc32};
+///
+/// let mut crc = Crc32::new();
+/// crc.update(b"1234");
+/// crc.update(b"56789");
+/// assert_eq!(0xCBF4_3926, crc.finish());
+/// assert_eq!(crc.finish(), crc32(b"123456789"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    /// Start a new calculation
+    pub const fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    /// Add data to the calculation
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.0;
+        for b in data {
+            crc ^= *b as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (POLY & mask);
+            }
+        }
+        self.0 = crc;
+    }
+
+    /// Get the CRC of the data added so far
+    pub const fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calculate the CRC32 of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0, crc32(&[]));
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(
+            0x414F_A339,
+            crc32(b"The quick brown fox jumps over the lazy dog")
+        );
+    }
+}
@@ -14,8 +14,27 @@
 //! # How frequently to send heartbeat messages (ms)
 //! heartbeat_period = 1000
 //!
-//! # Sets the default value of the Auto-start object
+//! # Number of messages which can be held in the transmit queue (default 4)
+//! tx_queue_size = 8
+//!
+//! # Generate an RX_QUEUE holding up to 16 received messages, for passing them from the CAN
+//! # interrupt to the task which owns the node (default 0, no queue)
+//! rx_queue_size = 16
+//!
+//! # Monitor the heartbeats of up to 2 other nodes, starting with node 3 which must send a
+//! # heartbeat at least every 1500ms
+//! [heartbeat_consumers]
+//! count = 2
+//! defaults = [
+//!     { node_id = 3, time = 1500 },
+//! ]
+//!
+//! # Configure startup behavior: enter Operational automatically, and repeat the boot-up message
+//! # twice, 50ms apart
+//! [startup]
 //! autostart = "enabled"
+//! bootup_retransmit_count = 2
+//! bootup_retransmit_interval = 50
 //!
 //! # Define 3 out of 4 device unique identifiers. These define the application/device, the fourth is
 //! # the serial number, which must be provided at run-time by the application.
@@ -49,6 +68,9 @@
 //! array_size = 4
 //! default_value = [0, 0, 0, 0]
 //! pdo_mapping = "tpdo"
+//! # Each count is 1mV
+//! unit = "V"
+//! scale = 0.001
 //! ```
 //!
 //! # Object Namespaces
@@ -58,6 +80,15 @@
 //!
 //! # Standard Objects
 //!
+//! ## 0x1007 - Synchronous Window Length
+//!
+//! A VAR object of type U32.
+//!
+//! The length of the window following a SYNC message, in microseconds, during which synchronous
+//! PDOs are exchanged. Synchronous RPDOs received outside the window are discarded, and synchronous
+//! TPDOs which have not been transmitted by the end of the window are dropped. A value of 0 disables
+//! the window. The default value is set by [DeviceConfig::sync_window_length].
+//!
 //! ## 0x1008 - Device Name
 //!
 //! A VAR object containing a string with a human readable device name. This value is set by
@@ -84,6 +115,15 @@
 //!
 //! To trigger a save, write a u32 with the [magic value](crate::constants::values::SAVE_CMD).
 //!
+//! ## 0x1016 - Consumer Heartbeat Time
+//!
+//! An array object of type U32, with one entry for each heartbeat consumer. It is only created when
+//! [HeartbeatConsumerConfig::count] is non-zero.
+//!
+//! Each entry holds the ID of a node to monitor in bits 16-23, and the time within which its
+//! heartbeat is expected in milliseconds in bits 0-15. Entries with a node ID or time of 0 are
+//! disabled. Default entries can be set by [HeartbeatConsumerConfig::defaults].
+//!
 //! ## 0x1017 - Heartbeat Producer Time
 //!
 //! A VAR object of type U16.
@@ -119,6 +159,18 @@
 //!
 //! One object for each TPDO supported by the node. This configures how the PDO is transmitted.
 //!
+//! | Sub Object | Type | Description |
+//! | ---------- | ---- | ----------- |
+//! | 1          | u32  | COB-ID |
+//! | 2          | u8   | Transmission type |
+//! | 3          | u16  | Inhibit time (100us) |
+//! | 5          | u16  | Event timer (ms) |
+//! | 6          | u8   | SYNC start value |
+//!
+//! The SYNC start value selects the SYNC counter value on which a TPDO with transmission type 1 -
+//! 240 is first sent, so that transmissions from different nodes can be staggered. The default is
+//! set by [PdoDefaultConfig::sync_start].
+//!
 //! ## 0x1A00 to 0x1A00 + N - TPDO Mapping Parameters
 //!
 //! One object for each TPDO supported by the node. This configures which sub objects the data in
@@ -129,17 +181,114 @@
 //!
 //! # Zencan Extensions
 //!
+//! ## 0x1F20 - Store DCF
+//!
+//! An array of domains holding a DCF file for each node managed by a CiA 302 configuration manager,
+//! with the sub index equal to the node ID. It is only created when
+//! [ConfigManagerConfig::store_dcf_size] is non-zero and [ConfigManagerConfig::node_ids] is not
+//! empty. Storage is only provided for the listed node IDs; the other sub objects are always empty.
+//!
+//! ## 0x1F22 - Concise DCF
+//!
+//! An array of domains holding a [concise DCF](crate::concise_dcf) for each node managed by a
+//! configuration manager, with the sub index equal to the node ID. It is only created when
+//! [ConfigManagerConfig::concise_dcf_size] is non-zero and [ConfigManagerConfig::node_ids] is not
+//! empty. Storage is only provided for the listed node IDs.
+//!
 //! ## 0x5000 - Auto Start
 //!
 //! Setting this to a non-zero value causes the node to immediately move into the Operational state
 //! after power-on, without receiving an NMT command to do so. Note that, if the device is later put
 //! into PreOperational via an NMT command, it will not auto-transition to Operational.
 //!
+//! ## 0x5001 - Startup Config
+//!
+//! A record object configuring the node's behavior after a reset. Defaults are set by
+//! [DeviceConfig::startup], and changes take effect on the next reset.
+//!
+//! | Sub Object | Type | Description |
+//! | ---------- | ---- | ----------- |
+//! | 0          | u8   | Max sub index - always 3 |
+//! | 1          | u16  | Delay in ms from the boot-up message to the first heartbeat |
+//! | 2          | u8   | Number of times the boot-up message is repeated |
+//! | 3          | u16  | Interval in ms between repeated boot-up messages |
+//!
+//! ## 0x5F00 - Node Diagnostics
+//!
+//! A read-only record object reporting communication statistics for remote health monitoring. It is
+//! only created when [DeviceConfig::diagnostics] is set.
+//!
+//! | Sub Object | Type | Description |
+//! | ---------- | ---- | ----------- |
+//! | 0          | u8   | Max sub index - always 10 |
+//! | 1          | u32  | Received message count |
+//! | 2          | u32  | Transmitted message count |
+//! | 3          | u32  | Receive overrun count |
+//! | 4          | u32  | SDO aborts sent |
+//! | 5          | u32  | TPDO events dropped |
+//! | 6          | u32  | Last internal error |
+//! | 7          | u32  | Transmit queue overflow count |
+//! | 8          | u32  | Operating time in seconds |
+//! | 9          | u32  | Malformed frames received |
+//! | 10         | u32  | Node messages dropped |
+//!
+//! ## 0x5F01 - Log Ring
+//!
+//! A read-only domain object containing the most recent text written to the node's RAM log ring.
+//! It is only created when [DeviceConfig::log_ring_size] is non-zero.
+//!
+//! ## 0x5F02 - Change Counters
+//!
+//! A read-only record object counting modifications to each
+//! [PersistGroup](crate::objects::PersistGroup). A client can read the counters and compare them to
+//! the values seen when it last read the node's objects, and skip re-reading groups which have not
+//! changed. It is only created when [DeviceConfig::change_counters] is set.
+//!
+//! | Sub Object | Type | Description |
+//! | ---------- | ---- | ----------- |
+//! | 0          | u8   | Max sub index - always 3 |
+//! | 1          | u32  | Communication objects change count |
+//! | 2          | u32  | Application objects change count |
+//! | 3          | u32  | Boot epoch, set by the application to a different value on each boot |
+//!
+//! ## 0x5F03 - Unit Metadata
+//!
+//! A read-only domain object containing the `unit`, `scale` and `offset` declared for var and array
+//! objects and record sub objects, so that a client can convert raw values to engineering values
+//! without access to the device config. See [unit_metadata](crate::unit_metadata) for the encoding.
+//! It is only created when [DeviceConfig::unit_metadata] is set. Generating code for it requires
+//! the `unit-metadata` feature of `zencan-node`.
+//!
+//! ## 0x5F04 - Configuration Signature
+//!
+//! A constant u32 holding the [signature](DeviceConfig::signature) of the object dictionary the node
+//! was built with. Commissioning tools can compare it to the signature of the device config they
+//! expect, before writing a configuration to the node. It is only created when
+//! [DeviceConfig::config_signature] is set.
+//!
+//! ## 0x5F05 - Node Status
+//!
+//! A read-only record object summarizing the health of the node, sized to fit in a single PDO. It
+//! is only created when [DeviceConfig::status_pdo] is set, and is mapped by the node status TPDO.
+//! The counters are the low bits of the node diagnostics counters, and wrap around.
+//!
+//! | Sub Object | Type | Description |
+//! | ---------- | ---- | ----------- |
+//! | 0          | u8   | Max sub index - always 4 |
+//! | 1          | u24  | Uptime in seconds |
+//! | 2          | u8   | Error register (0x1001) |
+//! | 3          | u16  | Received message count |
+//! | 4          | u16  | Transmitted message count |
+//!
 use std::collections::HashMap;
 
+use crate::constants::object_ids;
+use crate::crc32::Crc32;
+use crate::messages::{ConnectionSet, LSS_REQ_ID, LSS_RESP_ID, NMT_CMD_ID, SYNC_ID, TIME_ID};
 use crate::node_configuration::deserialize_pdo_map;
-use crate::objects::{AccessType, ObjectCode, PdoMappable};
+use crate::objects::{AccessType, ObjectCode, PdoMappable, SubInfo, OBJECT_STRUCTURE_SUB};
 use crate::pdo::PdoMapping;
+use crate::unit_metadata::{encode_table, UnitMetadata};
 use serde::{de::Error, Deserialize};
 
 use snafu::ResultExt as _;
@@ -174,8 +323,174 @@ pub enum LoadError {
         /// Duplicated sub index
         sub: u8,
     },
+    /// An array object has more elements than can be addressed by sub index
+    #[snafu(display(
+        "Array object 0x{index:x} has {size} elements, but at most {} are supported",
+        MAX_ARRAY_SIZE
+    ))]
+    ArrayTooLarge {
+        /// Index of the array object
+        index: u16,
+        /// The configured array size
+        size: usize,
+    },
+    /// A record defines a sub object on a reserved sub index
+    #[snafu(display("Sub index {sub} on object 0x{index:x} is reserved"))]
+    ReservedSubIndex {
+        /// Index of the record object
+        index: u16,
+        /// The reserved sub index
+        sub: u8,
+    },
+    /// The transmit queue size is invalid
+    #[snafu(display("Invalid tx_queue_size {size}: must be at least 1"))]
+    InvalidTxQueueSize {
+        /// The configured queue size
+        size: usize,
+    },
+    /// Too many heartbeat consumers are configured
+    #[snafu(display("Invalid heartbeat consumer count {count}: at most 127 are supported"))]
+    InvalidHeartbeatConsumerCount {
+        /// The configured count
+        count: u8,
+    },
+    /// More default heartbeat consumer entries are given than the configured count
+    #[snafu(display("{num_defaults} heartbeat consumer defaults given, but count is {count}"))]
+    TooManyHeartbeatConsumerDefaults {
+        /// The configured count
+        count: u8,
+        /// The number of default entries
+        num_defaults: usize,
+    },
+    /// A default heartbeat consumer entry has an invalid or repeated node ID
+    #[snafu(display(
+        "Invalid heartbeat consumer default for node {node_id}: node IDs must be unique and in 1-127"
+    ))]
+    InvalidHeartbeatConsumerNode {
+        /// The configured node ID
+        node_id: u8,
+    },
+    /// A configuration manager node ID is invalid or repeated
+    #[snafu(display(
+        "Invalid config_manager node ID {node_id}: node IDs must be unique and in 1-127"
+    ))]
+    InvalidConfigManagerNode {
+        /// The configured node ID
+        node_id: u8,
+    },
+    /// A PDO default COB ID does not fit in a CAN ID
+    #[snafu(display(
+        "Invalid COB ID 0x{cob_id:x} for {kind}{num}: must fit in 11 bits, or 29 bits when extended, after adding the node ID"
+    ))]
+    InvalidPdoCobId {
+        /// "TPDO" or "RPDO"
+        kind: &'static str,
+        /// The PDO number
+        num: usize,
+        /// The configured COB ID
+        cob_id: u32,
+    },
+    /// A PDO default COB ID falls in a range reserved by CiA 301
+    #[snafu(display(
+        "COB ID 0x{cob_id:x} for {kind}{num} overlaps the reserved CAN IDs 0x{first:x}-0x{last:x}"
+    ))]
+    ReservedPdoCobId {
+        /// "TPDO" or "RPDO"
+        kind: &'static str,
+        /// The PDO number
+        num: usize,
+        /// The configured COB ID
+        cob_id: u32,
+        /// The first ID of the reserved range
+        first: u32,
+        /// The last ID of the reserved range
+        last: u32,
+    },
+    /// A PDO default mapping is invalid
+    #[snafu(display("Invalid mapping of 0x{index:x}sub{sub} in {kind}{num}: {reason}"))]
+    InvalidPdoMapping {
+        /// "TPDO" or "RPDO"
+        kind: &'static str,
+        /// The PDO number
+        num: usize,
+        /// The mapped object index
+        index: u16,
+        /// The mapped sub index
+        sub: u8,
+        /// Description of the problem
+        reason: &'static str,
+    },
+    /// The default mappings of a PDO do not fit in a CAN message
+    #[snafu(display("Mappings for {kind}{num} total {bits} bits, but a PDO holds at most 64"))]
+    PdoTooLong {
+        /// "TPDO" or "RPDO"
+        kind: &'static str,
+        /// The PDO number
+        num: usize,
+        /// The total size of the mappings in bits
+        bits: usize,
+    },
+    /// A connection set base ID leaves no room for the node IDs
+    #[snafu(display(
+        "Invalid connection_set {service} 0x{base:x}: base + 127 must fit in 11 bits"
+    ))]
+    InvalidConnectionSetBase {
+        /// The name of the base ID
+        service: &'static str,
+        /// The configured base ID
+        base: u16,
+    },
+    /// The IDs derived from a connection set base ID overlap those of another service
+    #[snafu(display("Connection set {service} IDs overlap the {other} IDs"))]
+    OverlappingConnectionSet {
+        /// The name of the base ID
+        service: &'static str,
+        /// The service whose IDs are overlapped
+        other: &'static str,
+    },
+    /// The node status TPDO configuration is invalid
+    #[snafu(display("Invalid status_pdo using TPDO{tpdo}: {reason}"))]
+    InvalidStatusPdo {
+        /// The TPDO number
+        tpdo: usize,
+        /// Description of the problem
+        reason: &'static str,
+    },
+    /// The bootloader bank configuration is invalid
+    #[snafu(display("Invalid bootloader bank configuration: {reason}"))]
+    InvalidBootloaderBanks {
+        /// Description of the problem
+        reason: &'static str,
+    },
+    /// The unit metadata of a sub object is invalid
+    #[snafu(display("Invalid unit metadata for object 0x{index:x}sub{sub}: {reason}"))]
+    InvalidUnitMetadata {
+        /// Index of the object
+        index: u16,
+        /// Sub index of the sub object
+        sub: u8,
+        /// Description of the problem
+        reason: &'static str,
+    },
 }
 
+/// Standard CAN IDs which CiA 301 reserves for other services, and which may not be used by PDOs
+const RESERVED_COB_IDS: [(u32, u32); 7] = [
+    (0x000, 0x07F),
+    (0x101, 0x180),
+    (0x581, 0x5FF),
+    (0x601, 0x67F),
+    (0x6E0, 0x6FF),
+    (0x701, 0x77F),
+    (0x780, 0x7FF),
+];
+
+/// The largest supported array object
+///
+/// Sub 0 holds the array size, and sub 0xFF is reserved for the object structure, leaving subs 1-254
+/// for array elements.
+pub const MAX_ARRAY_SIZE: usize = 254;
+
 fn mandatory_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
     let mut objects = vec![
         ObjectDefinition {
@@ -202,6 +517,19 @@ fn mandatory_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
                 ..Default::default()
             }),
         },
+        ObjectDefinition {
+            index: 0x1007,
+            parameter_name: "Synchronous Window Length (us)".to_string(),
+            application_callback: false,
+            object: Object::Var(VarDefinition {
+                data_type: DataType::UInt32,
+                access_type: AccessType::Rw.into(),
+                default_value: Some(DefaultValue::Integer(config.sync_window_length as i64)),
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            }),
+        },
         ObjectDefinition {
             index: 0x1008,
             parameter_name: "Manufacturer Device Name".to_string(),
@@ -248,6 +576,7 @@ fn mandatory_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
                 default_value: Some(DefaultValue::Integer(config.heartbeat_period as i64)),
                 pdo_mapping: PdoMappable::None,
                 persist: false,
+                ..Default::default()
             }),
         },
         ObjectDefinition {
@@ -323,6 +652,7 @@ fn mandatory_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
                 default_value: Some(DefaultValue::Integer(default)),
                 pdo_mapping: PdoMappable::None,
                 persist: true,
+                ..Default::default()
             }),
         });
     }
@@ -330,6 +660,51 @@ fn mandatory_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
     objects
 }
 
+fn startup_objects(config: &DeviceConfig) -> Vec<ObjectDefinition> {
+    let startup = &config.startup;
+    let sub = |sub_index, name: &str, field_name: &str, data_type, default: u16| SubDefinition {
+        sub_index,
+        parameter_name: name.to_string(),
+        field_name: Some(field_name.into()),
+        data_type,
+        access_type: AccessType::Rw.into(),
+        default_value: Some(DefaultValue::Integer(default as i64)),
+        pdo_mapping: PdoMappable::None,
+        persist: true,
+        ..Default::default()
+    };
+    vec![ObjectDefinition {
+        index: 0x5001,
+        parameter_name: "Startup Config".to_string(),
+        application_callback: false,
+        object: Object::Record(RecordDefinition {
+            subs: vec![
+                sub(
+                    1,
+                    "First Heartbeat Delay",
+                    "first_heartbeat_delay",
+                    DataType::UInt16,
+                    startup.first_heartbeat_delay,
+                ),
+                sub(
+                    2,
+                    "Bootup Retransmit Count",
+                    "bootup_retransmit_count",
+                    DataType::UInt8,
+                    startup.bootup_retransmit_count as u16,
+                ),
+                sub(
+                    3,
+                    "Bootup Retransmit Interval",
+                    "bootup_retransmit_interval",
+                    DataType::UInt16,
+                    startup.bootup_retransmit_interval,
+                ),
+            ],
+        }),
+    }]
+}
+
 fn pdo_objects(num_rpdo: usize, num_tpdo: usize) -> Vec<ObjectDefinition> {
     let mut objects = Vec::new();
 
@@ -338,34 +713,71 @@ fn pdo_objects(num_rpdo: usize, num_tpdo: usize) -> Vec<ObjectDefinition> {
         let comm_index = if tx { 0x1800 } else { 0x1400 };
         let mapping_index = if tx { 0x1A00 } else { 0x1600 };
 
+        let mut comm_subs = vec![
+            SubDefinition {
+                sub_index: 1,
+                parameter_name: format!("COB-ID for {}{}", pdo_type, i),
+                field_name: None,
+                data_type: DataType::UInt32,
+                access_type: AccessType::Rw.into(),
+                default_value: None,
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            },
+            SubDefinition {
+                sub_index: 2,
+                parameter_name: format!("Transmission type for {}{}", pdo_type, i),
+                field_name: None,
+                data_type: DataType::UInt8,
+                access_type: AccessType::Rw.into(),
+                default_value: None,
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            },
+        ];
+        if tx {
+            comm_subs.push(SubDefinition {
+                sub_index: 3,
+                parameter_name: format!("Inhibit time for {}{}", pdo_type, i),
+                field_name: None,
+                data_type: DataType::UInt16,
+                access_type: AccessType::Rw.into(),
+                default_value: None,
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            });
+            comm_subs.push(SubDefinition {
+                sub_index: 5,
+                parameter_name: format!("Event timer for {}{}", pdo_type, i),
+                field_name: None,
+                data_type: DataType::UInt16,
+                access_type: AccessType::Rw.into(),
+                default_value: None,
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            });
+            comm_subs.push(SubDefinition {
+                sub_index: 6,
+                parameter_name: format!("SYNC start value for {}{}", pdo_type, i),
+                field_name: None,
+                data_type: DataType::UInt8,
+                access_type: AccessType::Rw.into(),
+                default_value: None,
+                pdo_mapping: PdoMappable::None,
+                persist: true,
+                ..Default::default()
+            });
+        }
+
         objects.push(ObjectDefinition {
             index: comm_index + i as u16,
             parameter_name: format!("{}{} Communication Parameter", pdo_type, i),
             application_callback: true,
-            object: Object::Record(RecordDefinition {
-                subs: vec![
-                    SubDefinition {
-                        sub_index: 1,
-                        parameter_name: format!("COB-ID for {}{}", pdo_type, i),
-                        field_name: None,
-                        data_type: DataType::UInt32,
-                        access_type: AccessType::Rw.into(),
-                        default_value: None,
-                        pdo_mapping: PdoMappable::None,
-                        persist: true,
-                    },
-                    SubDefinition {
-                        sub_index: 2,
-                        parameter_name: format!("Transmission type for {}{}", pdo_type, i),
-                        field_name: None,
-                        data_type: DataType::UInt8,
-                        access_type: AccessType::Rw.into(),
-                        default_value: None,
-                        pdo_mapping: PdoMappable::None,
-                        persist: true,
-                    },
-                ],
-            }),
+            object: Object::Record(RecordDefinition { subs: comm_subs }),
         });
 
         let mut mapping_subs = vec![SubDefinition {
@@ -377,6 +789,7 @@ fn pdo_objects(num_rpdo: usize, num_tpdo: usize) -> Vec<ObjectDefinition> {
             default_value: Some(DefaultValue::Integer(0)),
             pdo_mapping: PdoMappable::None,
             persist: true,
+            ..Default::default()
         }];
         for sub in 1..65 {
             mapping_subs.push(SubDefinition {
@@ -388,6 +801,7 @@ fn pdo_objects(num_rpdo: usize, num_tpdo: usize) -> Vec<ObjectDefinition> {
                 default_value: None,
                 pdo_mapping: PdoMappable::None,
                 persist: true,
+                ..Default::default()
             });
         }
 
@@ -413,47 +827,72 @@ fn bootloader_objects(cfg: &BootloaderConfig) -> Vec<ObjectDefinition> {
     if cfg.sections.is_empty() {
         return objects;
     }
+    let mut info_subs = vec![
+        SubDefinition {
+            sub_index: 1,
+            parameter_name: "Bootloader Config".into(),
+            field_name: Some("config".into()),
+            data_type: DataType::UInt32,
+            access_type: AccessType::Ro.into(),
+            default_value: Some(0.into()),
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+            ..Default::default()
+        },
+        SubDefinition {
+            sub_index: 2,
+            parameter_name: "Number of Section".into(),
+            field_name: Some("num_sections".into()),
+            data_type: DataType::UInt8,
+            access_type: AccessType::Ro.into(),
+            default_value: Some(cfg.sections.len().into()),
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+            ..Default::default()
+        },
+        SubDefinition {
+            sub_index: 3,
+            parameter_name: "Reset to Bootloader Command".into(),
+            field_name: None,
+            data_type: DataType::UInt32,
+            access_type: AccessType::Wo.into(),
+            default_value: None,
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+            ..Default::default()
+        },
+    ];
+    if cfg.dual_bank {
+        info_subs.extend([
+            SubDefinition {
+                sub_index: 4,
+                parameter_name: "Active Bank".into(),
+                data_type: DataType::UInt8,
+                access_type: AccessType::Ro.into(),
+                ..Default::default()
+            },
+            SubDefinition {
+                sub_index: 5,
+                parameter_name: "Swap Bank Command".into(),
+                data_type: DataType::UInt32,
+                access_type: AccessType::Wo.into(),
+                ..Default::default()
+            },
+        ]);
+    }
     objects.push(ObjectDefinition {
         index: 0x5500,
         parameter_name: "Bootloader Info".into(),
         application_callback: false,
-        object: Object::Record(RecordDefinition {
-            subs: vec![
-                SubDefinition {
-                    sub_index: 1,
-                    parameter_name: "Bootloader Config".into(),
-                    field_name: Some("config".into()),
-                    data_type: DataType::UInt32,
-                    access_type: AccessType::Ro.into(),
-                    default_value: Some(0.into()),
-                    pdo_mapping: PdoMappable::None,
-                    persist: false,
-                },
-                SubDefinition {
-                    sub_index: 2,
-                    parameter_name: "Number of Section".into(),
-                    field_name: Some("num_sections".into()),
-                    data_type: DataType::UInt8,
-                    access_type: AccessType::Ro.into(),
-                    default_value: Some(cfg.sections.len().into()),
-                    pdo_mapping: PdoMappable::None,
-                    persist: false,
-                },
-                SubDefinition {
-                    sub_index: 3,
-                    parameter_name: "Reset to Bootloader Command".into(),
-                    field_name: None,
-                    data_type: DataType::UInt32,
-                    access_type: AccessType::Wo.into(),
-                    default_value: None,
-                    pdo_mapping: PdoMappable::None,
-                    persist: false,
-                },
-            ],
-        }),
+        object: Object::Record(RecordDefinition { subs: info_subs }),
     });
 
     for (i, section) in cfg.sections.iter().enumerate() {
+        let bank = match section.bank {
+            None => 0,
+            Some(BootloaderBank::A) => 1,
+            Some(BootloaderBank::B) => 2,
+        };
         objects.push(ObjectDefinition {
             index: 0x5510 + i as u16,
             parameter_name: format!("Bootloader Section {i}"),
@@ -464,44 +903,57 @@ fn bootloader_objects(cfg: &BootloaderConfig) -> Vec<ObjectDefinition> {
                         sub_index: 1,
                         parameter_name: "Mode bits".into(),
                         data_type: DataType::UInt8,
-                        access_type: AccessType::Const.into(),
+                        access_type: AccessType::Ro.into(),
                         ..Default::default()
                     },
                     SubDefinition {
                         sub_index: 2,
                         parameter_name: "Section Name".into(),
-                        data_type: DataType::VisibleString(0),
+                        data_type: DataType::VisibleString(section.name.len()),
                         access_type: AccessType::Const.into(),
                         default_value: Some(section.name.as_str().into()),
                         ..Default::default()
                     },
                     SubDefinition {
                         sub_index: 3,
-                        parameter_name: "Section Size".into(),
+                        parameter_name: "Erase Command".into(),
                         data_type: DataType::UInt32,
-                        access_type: AccessType::Const.into(),
-                        default_value: Some((section.size as i64).into()),
+                        access_type: AccessType::Wo.into(),
                         ..Default::default()
                     },
                     SubDefinition {
                         sub_index: 4,
-                        parameter_name: "Erase Command".into(),
-                        data_type: DataType::UInt8,
-                        access_type: AccessType::Wo.into(),
+                        parameter_name: "Data".into(),
+                        data_type: DataType::Domain,
+                        access_type: AccessType::Rw.into(),
                         ..Default::default()
                     },
                     SubDefinition {
                         sub_index: 5,
-                        parameter_name: "Data".into(),
-                        data_type: DataType::Domain,
-                        access_type: AccessType::Rw.into(),
+                        parameter_name: "CRC32".into(),
+                        data_type: DataType::UInt32,
+                        access_type: AccessType::Ro.into(),
+                        ..Default::default()
+                    },
+                    SubDefinition {
+                        sub_index: 6,
+                        parameter_name: "Verify Command".into(),
+                        data_type: DataType::UInt32,
+                        access_type: AccessType::Wo.into(),
+                        ..Default::default()
+                    },
+                    SubDefinition {
+                        sub_index: 7,
+                        parameter_name: "Bank".into(),
+                        data_type: DataType::UInt8,
+                        access_type: AccessType::Const.into(),
+                        default_value: Some(bank.into()),
                         ..Default::default()
                     },
                 ],
             }),
         });
     }
-
     objects
 }
 
@@ -524,46 +976,297 @@ fn object_storage_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
     }
 }
 
-fn default_num_rpdo() -> u8 {
-    4
+fn diagnostics_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if !dev.diagnostics {
+        return vec![];
+    }
+
+    let sub_names = [
+        "Received Messages",
+        "Transmitted Messages",
+        "Receive Overruns",
+        "SDO Aborts Sent",
+        "TPDO Events Dropped",
+        "Last Internal Error",
+        "Transmit Queue Overflows",
+        "Operating Time",
+        "Malformed Frames Received",
+        "Node Messages Dropped",
+    ];
+    vec![ObjectDefinition {
+        index: 0x5F00,
+        parameter_name: "Node Diagnostics".to_string(),
+        application_callback: false,
+        object: Object::Record(RecordDefinition {
+            subs: sub_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| SubDefinition {
+                    sub_index: i as u8 + 1,
+                    parameter_name: name.to_string(),
+                    data_type: DataType::UInt32,
+                    access_type: AccessType::Ro.into(),
+                    ..Default::default()
+                })
+                .collect(),
+        }),
+    }]
 }
-fn default_num_tpdo() -> u8 {
-    4
+
+fn node_status_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if dev.status_pdo.is_none() {
+        return vec![];
+    }
+
+    let subs = [
+        ("Uptime", DataType::UInt24),
+        ("Error Register", DataType::UInt8),
+        ("Received Messages", DataType::UInt16),
+        ("Transmitted Messages", DataType::UInt16),
+    ];
+    vec![ObjectDefinition {
+        index: object_ids::NODE_STATUS,
+        parameter_name: "Node Status".to_string(),
+        application_callback: false,
+        object: Object::Record(RecordDefinition {
+            subs: subs
+                .iter()
+                .enumerate()
+                .map(|(i, (name, data_type))| SubDefinition {
+                    sub_index: i as u8 + 1,
+                    parameter_name: name.to_string(),
+                    data_type: *data_type,
+                    access_type: AccessType::Ro.into(),
+                    pdo_mapping: PdoMappable::Tpdo,
+                    ..Default::default()
+                })
+                .collect(),
+        }),
+    }]
 }
-fn default_true() -> bool {
-    true
+
+fn change_counter_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if !dev.change_counters {
+        return vec![];
+    }
+
+    let sub_names = ["Communication Objects", "Application Objects", "Boot Epoch"];
+    vec![ObjectDefinition {
+        index: 0x5F02,
+        parameter_name: "Change Counters".to_string(),
+        application_callback: false,
+        object: Object::Record(RecordDefinition {
+            subs: sub_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| SubDefinition {
+                    sub_index: i as u8 + 1,
+                    parameter_name: name.to_string(),
+                    data_type: DataType::UInt32,
+                    access_type: AccessType::Ro.into(),
+                    ..Default::default()
+                })
+                .collect(),
+        }),
+    }]
 }
 
-/// Options for Autostart config
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum AutoStartConfig {
-    /// Autostart is supported, but defaults to off
-    #[default]
-    Disabled,
-    /// Autostart defaults to enabled
-    Enabled,
-    /// Autostart is not supported -- no 0x5000 object will be created
-    Unsupported,
+fn log_ring_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if dev.log_ring_size == 0 {
+        return vec![];
+    }
+
+    vec![ObjectDefinition {
+        index: 0x5F01,
+        parameter_name: "Log Ring".to_string(),
+        application_callback: false,
+        object: Object::Var(VarDefinition {
+            data_type: DataType::Domain,
+            access_type: AccessType::Ro.into(),
+            pdo_mapping: PdoMappable::None,
+            ..Default::default()
+        }),
+    }]
 }
 
-/// Represents the configuration parameters for a single PDO
-#[derive(Clone, Debug, Deserialize, PartialEq)]
-#[serde(deny_unknown_fields)]
-pub struct PdoDefaultConfig {
-    /// The COB ID this PDO will use to send/receive
-    pub cob_id: u32,
-    /// The COB ID is an extended 29-bit ID
-    #[serde(default)]
-    pub extended: bool,
-    /// The node ID should be added to `cob_id`` at runtime
-    pub add_node_id: bool,
-    /// Indicates if this PDO is enabled
-    pub enabled: bool,
-    /// If set, this PDO will not respond to requests
-    #[serde(default)]
+fn heartbeat_consumer_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if dev.heartbeat_consumers.count == 0 {
+        return vec![];
+    }
+
+    vec![ObjectDefinition {
+        index: 0x1016,
+        parameter_name: "Consumer Heartbeat Time".to_string(),
+        application_callback: false,
+        object: Object::Array(ArrayDefinition {
+            data_type: DataType::UInt32,
+            access_type: AccessType::Rw.into(),
+            array_size: dev.heartbeat_consumers.count as usize,
+            persist: true,
+            ..Default::default()
+        }),
+    }]
+}
+
+fn config_manager_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    let cfg = &dev.config_manager;
+    let dcf_array = |index, parameter_name: &str| ObjectDefinition {
+        index,
+        parameter_name: parameter_name.to_string(),
+        application_callback: false,
+        object: Object::Array(ArrayDefinition {
+            data_type: DataType::Domain,
+            access_type: AccessType::Rw.into(),
+            array_size: 127,
+            persist: true,
+            ..Default::default()
+        }),
+    };
+
+    let mut objects = Vec::new();
+    if cfg.has_store_dcf() {
+        objects.push(dcf_array(object_ids::STORE_DCF, "Store DCF"));
+    }
+    if cfg.has_concise_dcf() {
+        objects.push(dcf_array(object_ids::CONCISE_DCF, "Concise DCF"));
+    }
+    objects
+}
+
+fn unit_metadata_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if !dev.unit_metadata {
+        return vec![];
+    }
+
+    vec![ObjectDefinition {
+        index: object_ids::UNIT_METADATA,
+        parameter_name: "Unit Metadata".to_string(),
+        application_callback: false,
+        object: Object::Var(VarDefinition {
+            data_type: DataType::Domain,
+            access_type: AccessType::Ro.into(),
+            pdo_mapping: PdoMappable::None,
+            ..Default::default()
+        }),
+    }]
+}
+
+fn config_signature_objects(dev: &DeviceConfig) -> Vec<ObjectDefinition> {
+    if !dev.config_signature {
+        return vec![];
+    }
+
+    vec![ObjectDefinition {
+        index: object_ids::CONFIG_SIGNATURE,
+        parameter_name: "Configuration Signature".to_string(),
+        application_callback: false,
+        object: Object::Var(VarDefinition {
+            data_type: DataType::UInt32,
+            access_type: AccessType::Const.into(),
+            default_value: Some(DefaultValue::Integer(dev.signature() as i64)),
+            pdo_mapping: PdoMappable::None,
+            ..Default::default()
+        }),
+    }]
+}
+
+fn default_num_rpdo() -> u8 {
+    4
+}
+fn default_num_tpdo() -> u8 {
+    4
+}
+fn default_true() -> bool {
+    true
+}
+fn default_tx_queue_size() -> usize {
+    4
+}
+
+/// Options for Autostart config
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoStartConfig {
+    /// Autostart is supported, but defaults to off
+    #[default]
+    Disabled,
+    /// Autostart defaults to enabled
+    Enabled,
+    /// Autostart is not supported -- no 0x5000 object will be created
+    Unsupported,
+}
+
+fn default_bootup_retransmit_interval() -> u16 {
+    100
+}
+
+/// Configuration of the node's behavior after a reset
+///
+/// Except for `autostart`, these set the defaults of the Startup Config object (0x5001), and can be
+/// changed at run-time. Changes take effect on the next reset.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StartupConfig {
+    /// Configures support for the AutoStart (0x5000) object and its default value
+    ///
+    /// When set, this overrides [DeviceConfig::autostart].
+    #[serde(default)]
+    pub autostart: Option<AutoStartConfig>,
+    /// Delay in milliseconds from the boot-up message to the first heartbeat
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub first_heartbeat_delay: u16,
+    /// Number of times the boot-up message is repeated after it is first sent
+    ///
+    /// Repeating the boot-up message helps a master which is not yet listening when the node
+    /// boots, at the cost of some bus traffic. Note that a repeated boot-up message cannot be told
+    /// apart from a reset, so tools which wait for a node to reset may be misled by them.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub bootup_retransmit_count: u8,
+    /// Interval in milliseconds between repeated boot-up messages
+    ///
+    /// Default: 100
+    #[serde(default = "default_bootup_retransmit_interval")]
+    pub bootup_retransmit_interval: u16,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            autostart: None,
+            first_heartbeat_delay: 0,
+            bootup_retransmit_count: 0,
+            bootup_retransmit_interval: default_bootup_retransmit_interval(),
+        }
+    }
+}
+
+/// Represents the configuration parameters for a single PDO
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PdoDefaultConfig {
+    /// The COB ID this PDO will use to send/receive
+    ///
+    /// Standard IDs may not overlap the CAN IDs reserved by CiA 301 for other services, e.g. SDO or
+    /// heartbeat messages, for any node ID when `add_node_id` is set.
+    pub cob_id: u32,
+    /// The COB ID is an extended 29-bit ID
+    #[serde(default)]
+    pub extended: bool,
+    /// The node ID should be added to `cob_id`` at runtime
+    pub add_node_id: bool,
+    /// Indicates if this PDO is enabled
+    pub enabled: bool,
+    /// If set, this PDO will not respond to requests
+    #[serde(default)]
     pub rtr_disabled: bool,
     /// List of mapping specifying what sub objects are mapped to this PDO
+    ///
+    /// Each mapped sub object must exist and be mappable to this type of PDO, and the sizes of the
+    /// mappings must total at most 64 bits.
     pub mappings: Vec<PdoMapping>,
     /// Specifies when a PDO is sent or latched
     ///
@@ -572,6 +1275,22 @@ pub struct PdoDefaultConfig {
     /// - 1 - 240: Sent in response to every Nth sync
     /// - 254: Event driven (application to send it whenever it wants)
     pub transmission_type: u8,
+    /// The SYNC counter value on which a TPDO with transmission type 1 - 240 is first sent
+    ///
+    /// 0 disables the start value. Not used for RPDOs.
+    #[serde(default)]
+    pub sync_start: u8,
+    /// The period in milliseconds at which an event driven TPDO is sent without events
+    ///
+    /// 0 disables the event timer. Not used for RPDOs.
+    #[serde(default)]
+    pub event_timer: u16,
+    /// The minimum time between transmissions of an event driven TPDO, in units of 100us
+    ///
+    /// Events raised during the inhibit time are combined into a single transmission when it
+    /// expires. 0 disables the inhibit time. Not used for RPDOs.
+    #[serde(default)]
+    pub inhibit_time: u16,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -658,6 +1377,224 @@ pub struct IdentityConfig {
     pub revision_number: u32,
 }
 
+/// Configuration of the node status TPDO
+///
+/// The node status TPDO publishes the node status object (0x5F05) -- the node's uptime, error
+/// register and message counters -- at a fixed rate, so that a monitor can track the health of
+/// every node on a bus without any application code. For example:
+///
+/// ```toml
+/// [status_pdo]
+/// tpdo = 3
+/// cob_id = 0x480
+/// period_ms = 5000
+/// ```
+///
+/// The TPDO is configured as an event driven TPDO with an event timer, and can be reconfigured
+/// at runtime like any other.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct StatusPdoConfig {
+    /// The number of the TPDO used. It must exist, and have no other default configuration.
+    pub tpdo: usize,
+    /// The COB ID of the TPDO
+    pub cob_id: u32,
+    /// The COB ID is an extended 29-bit ID
+    #[serde(default)]
+    pub extended: bool,
+    /// The node ID is added to `cob_id` at runtime
+    ///
+    /// Default: true
+    #[serde(default = "default_true")]
+    pub add_node_id: bool,
+    /// The period at which the TPDO is sent, in milliseconds
+    ///
+    /// Default: 1000
+    #[serde(default = "default_status_period")]
+    pub period_ms: u16,
+}
+
+fn default_status_period() -> u16 {
+    1000
+}
+
+impl StatusPdoConfig {
+    /// Get the default configuration of the TPDO
+    pub fn pdo_default_config(&self) -> PdoDefaultConfig {
+        let mapping = |sub, size| PdoMapping {
+            index: object_ids::NODE_STATUS,
+            sub,
+            size,
+        };
+        PdoDefaultConfig {
+            cob_id: self.cob_id,
+            extended: self.extended,
+            add_node_id: self.add_node_id,
+            enabled: true,
+            rtr_disabled: false,
+            mappings: vec![
+                mapping(1, 24),
+                mapping(2, 8),
+                mapping(3, 16),
+                mapping(4, 16),
+            ],
+            transmission_type: 254,
+            sync_start: 0,
+            event_timer: self.period_ms,
+            inhibit_time: 0,
+        }
+    }
+}
+
+/// The default configuration of a single heartbeat consumer
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HeartbeatConsumerDefault {
+    /// The ID of the node to monitor
+    pub node_id: u8,
+    /// The time within which a heartbeat is expected, in milliseconds
+    pub time: u16,
+}
+
+/// Configuration of the predefined connection set
+///
+/// The COB IDs of the default SDO server, heartbeat and EMCY are found by adding the node ID to
+/// these base IDs. The defaults are those of CiA 301, and only need to be changed to match a
+/// network which uses other IDs. Each base must leave room for node IDs 1-127, and the resulting
+/// ranges may not overlap each other, or the NMT, SYNC, TIME and LSS IDs.
+///
+/// Note that the node does not produce EMCY messages yet; `emcy_base` is reserved so that PDOs
+/// are not configured on the EMCY IDs.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionSetConfig {
+    /// Base ID on which the SDO server receives requests
+    ///
+    /// Default: 0x600
+    pub sdo_request_base: u16,
+    /// Base ID on which the SDO server sends responses
+    ///
+    /// Default: 0x580
+    pub sdo_response_base: u16,
+    /// Base ID on which heartbeats are sent, and on which heartbeat consumers listen
+    ///
+    /// Default: 0x700
+    pub heartbeat_base: u16,
+    /// Base ID on which EMCY messages are sent
+    ///
+    /// Default: 0x80
+    pub emcy_base: u16,
+}
+
+impl Default for ConnectionSetConfig {
+    fn default() -> Self {
+        ConnectionSet::DEFAULT.into()
+    }
+}
+
+impl From<ConnectionSet> for ConnectionSetConfig {
+    fn from(value: ConnectionSet) -> Self {
+        Self {
+            sdo_request_base: value.sdo_request_base,
+            sdo_response_base: value.sdo_response_base,
+            heartbeat_base: value.heartbeat_base,
+            emcy_base: value.emcy_base,
+        }
+    }
+}
+
+impl From<ConnectionSetConfig> for ConnectionSet {
+    fn from(value: ConnectionSetConfig) -> Self {
+        Self {
+            sdo_request_base: value.sdo_request_base,
+            sdo_response_base: value.sdo_response_base,
+            heartbeat_base: value.heartbeat_base,
+            emcy_base: value.emcy_base,
+        }
+    }
+}
+
+impl ConnectionSetConfig {
+    /// Get the name and range of IDs for each service, for node IDs 1-127
+    fn ranges(&self) -> [(&'static str, u32, u32); 4] {
+        [
+            ("sdo_request_base", self.sdo_request_base),
+            ("sdo_response_base", self.sdo_response_base),
+            ("heartbeat_base", self.heartbeat_base),
+            ("emcy_base", self.emcy_base),
+        ]
+        .map(|(service, base)| (service, base as u32 + 1, base as u32 + 127))
+    }
+}
+
+/// Configuration of the heartbeat consumers, used to monitor the heartbeats of other nodes
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HeartbeatConsumerConfig {
+    /// The number of nodes which can be monitored
+    ///
+    /// Each consumer is an entry in object 0x1016, and is statically allocated. When zero, no
+    /// 0x1016 object is created.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub count: u8,
+    /// Default configurations for the first consumers
+    ///
+    /// Remaining consumers are disabled by default.
+    #[serde(default)]
+    pub defaults: Vec<HeartbeatConsumerDefault>,
+}
+
+/// Configuration of the DCF storage of a CiA 302 configuration manager (objects 0x1F20 and 0x1F22)
+///
+/// Storage for each DCF is statically allocated, so the nodes which can be configured and the
+/// size of their DCFs must be set at build time.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigManagerConfig {
+    /// The IDs of the nodes whose configurations can be stored
+    ///
+    /// Default: empty
+    #[serde(default)]
+    pub node_ids: Vec<u8>,
+    /// The size in bytes of the concise DCF which can be stored for each node in object 0x1F22
+    ///
+    /// When zero, no 0x1F22 object is created.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub concise_dcf_size: usize,
+    /// The size in bytes of the DCF file which can be stored for each node in object 0x1F20
+    ///
+    /// When zero, no 0x1F20 object is created.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub store_dcf_size: usize,
+}
+
+impl ConfigManagerConfig {
+    /// Returns true if the concise DCF object (0x1F22) is created
+    pub fn has_concise_dcf(&self) -> bool {
+        self.concise_dcf_size > 0 && !self.node_ids.is_empty()
+    }
+
+    /// Returns true if the store DCF object (0x1F20) is created
+    pub fn has_store_dcf(&self) -> bool {
+        self.store_dcf_size > 0 && !self.node_ids.is_empty()
+    }
+}
+
+/// A bank of a dual-bank device
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum BootloaderBank {
+    /// Bank A
+    A,
+    /// Bank B
+    B,
+}
+
 /// Configuration object to define a programmable bootloader section
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -666,6 +1603,9 @@ pub struct BootloaderSection {
     pub name: String,
     /// Size of the section
     pub size: u32,
+    /// The bank containing the section, required when `dual_bank` is enabled
+    #[serde(default)]
+    pub bank: Option<BootloaderBank>,
 }
 
 /// Configuration of bootloader parameters
@@ -676,11 +1616,39 @@ pub struct BootloaderConfig {
     /// bootloader implementation
     #[serde(default)]
     pub application: bool,
+    /// If true, the device holds two copies of its image, in banks A and B, and supports swapping
+    /// between them
+    ///
+    /// Every section must be assigned to a bank, and each bank must contain at least one section.
+    #[serde(default)]
+    pub dual_bank: bool,
     /// List of programmable sections
     #[serde(default)]
     pub sections: Vec<BootloaderSection>,
 }
 
+/// Options controlling the code generated for the node
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CodegenConfig {
+    /// Generate size-optimized code, for parts with little flash
+    ///
+    /// In compact mode, the generated code:
+    /// - Places objects in the object dictionary through `SharedObjectAccess`, so that one copy of
+    ///   the object access code is shared by all objects, rather than one generated for each
+    /// - Uses checked lookups in array accessors, which return an abort code instead of containing
+    ///   a panicking index
+    /// - Does not derive `Debug` for the generated sub index enums
+    ///
+    /// Sharing the access code adds an indirect call to each object access. The flash saved grows
+    /// with the number of objects, and depends on the target and optimization settings.
+    ///
+    /// The API of the generated objects is the same in both modes.
+    ///
+    /// Default: false
+    pub compact: bool,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 /// Private struct for seserializing device config files
@@ -694,15 +1662,67 @@ pub struct DeviceConfig {
     /// - 'unsupported': No autostart object is created
     /// - 'disabled': An autostart object is created, and it defaults to disabled
     /// - 'enabled': An autostart object is created, and it defaults to enabled
+    ///
+    /// This may also be set as `autostart` in the `[startup]` section, which takes precedence. After
+    /// loading, this field holds the setting in effect.
     #[serde(default)]
     pub autostart: AutoStartConfig,
 
+    /// Configures the node's behavior after a reset
+    #[serde(default)]
+    pub startup: StartupConfig,
+
     /// Enables object storage commands (object 0x1010)
     ///
     /// Default: true
     #[serde(default = "default_true")]
     pub support_storage: bool,
 
+    /// Enables the node diagnostics object (0x5F00)
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub diagnostics: bool,
+
+    /// Enables the change counters object (0x5F02)
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub change_counters: bool,
+
+    /// Enables the unit metadata object (0x5F03)
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub unit_metadata: bool,
+
+    /// Enables SDO complete access to array and record objects
+    ///
+    /// Complete access reads or writes all sub objects of an object in a single SDO transfer, as
+    /// used by CANopen over EtherCAT tooling. It applies to the array and record objects generated
+    /// from the device config, excluding the PDO configuration objects and objects implemented by
+    /// application callbacks.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub sdo_complete_access: bool,
+
+    /// Enables the configuration signature object (0x5F04)
+    ///
+    /// See [DeviceConfig::signature].
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub config_signature: bool,
+
+    /// Size in bytes of the RAM log ring readable at object 0x5F01
+    ///
+    /// When zero, no log ring is created.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub log_ring_size: usize,
+
     /// A version describing the hardware
     #[serde(default)]
     pub hardware_version: String,
@@ -714,6 +1734,45 @@ pub struct DeviceConfig {
     #[serde(default)]
     pub heartbeat_period: u16,
 
+    /// The default length of the synchronous window following a SYNC, in microseconds
+    ///
+    /// See object 0x1007 in the [module docs](self). When zero, the window is not enforced.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub sync_window_length: u32,
+
+    /// The number of messages which can be held in the node's general transmit queue
+    ///
+    /// The queue holds messages other than TPDOs and SDO responses, e.g. heartbeats, LSS responses
+    /// and messages queued by the application. Each entry costs a few bytes of RAM.
+    ///
+    /// Default: 4
+    #[serde(default = "default_tx_queue_size")]
+    pub tx_queue_size: usize,
+
+    /// The number of received messages which can be held in the generated `RX_QUEUE`
+    ///
+    /// The queue is a `zencan_node::rtic::StaticRxQueue`, for passing received messages from an
+    /// interrupt to the task which owns the node without callbacks, e.g. in an RTIC application.
+    /// When zero, no queue is generated.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub rx_queue_size: usize,
+
+    /// Configures the base IDs of the predefined connection set
+    #[serde(default)]
+    pub connection_set: ConnectionSetConfig,
+
+    /// Configures heartbeat consumers for monitoring other nodes (object 0x1016)
+    #[serde(default)]
+    pub heartbeat_consumers: HeartbeatConsumerConfig,
+
+    /// Configures DCF storage for a configuration manager (objects 0x1F20 and 0x1F22)
+    #[serde(default)]
+    pub config_manager: ConfigManagerConfig,
+
     /// Configures the identity object on the device
     pub identity: IdentityConfig,
 
@@ -721,10 +1780,21 @@ pub struct DeviceConfig {
     #[serde(default)]
     pub pdos: DevicePdoConfig,
 
+    /// Configures the node status TPDO, and enables the node status object (0x5F05)
+    ///
+    /// When set, a TPDO is configured by default to publish the node's status periodically. See
+    /// [StatusPdoConfig].
+    #[serde(default)]
+    pub status_pdo: Option<StatusPdoConfig>,
+
     /// Configure bootloader options
     #[serde(default)]
     pub bootloader: BootloaderConfig,
 
+    /// Configure the generated code
+    #[serde(default)]
+    pub codegen: CodegenConfig,
+
     /// A list of application specific objects to define on the device
     #[serde(default)]
     pub objects: Vec<ObjectDefinition>,
@@ -735,6 +1805,12 @@ pub struct DeviceConfig {
 #[serde(deny_unknown_fields)]
 pub struct SubDefinition {
     /// Sub index for the sub-object being defined
+    ///
+    /// Sub index 0xFF is reserved for the object structure, and may not be used. Sub index 0 is
+    /// reserved for the highest sub index of the record, and may only be defined on records
+    /// implemented by an application callback.
+    ///
+    /// Sub indices do not need to be contiguous, e.g. a record may define only subs 1, 2 and 6.
     pub sub_index: u8,
     /// A human readable name for the value stored in this sub-object
     #[serde(default)]
@@ -759,6 +1835,15 @@ pub struct SubDefinition {
     /// Indicates if this sub object should be saved when the save command is sent
     #[serde(default)]
     pub persist: bool,
+    /// The unit of the engineering value, e.g. "mV"
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// The factor by which the raw value is multiplied to get the engineering value
+    #[serde(default)]
+    pub scale: Option<f64>,
+    /// The offset added to the scaled value to get the engineering value
+    #[serde(default)]
+    pub offset: Option<f64>,
 }
 
 /// An enum to represent object default values
@@ -831,6 +1916,15 @@ pub struct VarDefinition {
     /// Indicates that this object should be saved
     #[serde(default)]
     pub persist: bool,
+    /// The unit of the engineering value, e.g. "mV"
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// The factor by which the raw value is multiplied to get the engineering value
+    #[serde(default)]
+    pub scale: Option<f64>,
+    /// The offset added to the scaled value to get the engineering value
+    #[serde(default)]
+    pub offset: Option<f64>,
 }
 
 /// Descriptor for an array object
@@ -842,6 +1936,8 @@ pub struct ArrayDefinition {
     /// Access type for all array fields
     pub access_type: AccessTypeDeser,
     /// The number of elements in the array
+    ///
+    /// At most [`MAX_ARRAY_SIZE`] elements are supported
     pub array_size: usize,
     /// Default values for all array fields
     pub default_value: Option<Vec<DefaultValue>>,
@@ -851,6 +1947,30 @@ pub struct ArrayDefinition {
     #[serde(default)]
     /// Whether this array should be saved to flash on command
     pub persist: bool,
+    /// If true, sub 0 of the array is writable, and sets the number of elements in use
+    ///
+    /// Sub 0 may be written with any value from 0 up to `array_size`, and starts at `array_size`.
+    /// Elements beyond the current length cannot be accessed, and return a `NoSuchSubIndex` abort.
+    /// When the array is persisted, its length is persisted too.
+    #[serde(default)]
+    pub resizable: bool,
+    /// Limit the number of sub objects which can trigger TPDO events
+    ///
+    /// By default, event flags are allocated for every element of a TPDO mappable array. Large
+    /// arrays where only the first few elements need to trigger events can set this to reduce RAM
+    /// usage; setting the event flag on a sub index at or above this count has no effect. Flags
+    /// are stored in 32-bit words, so the count is effectively rounded up to a multiple of 32.
+    #[serde(default)]
+    pub event_flags: Option<usize>,
+    /// The unit of the engineering value of all array fields, e.g. "mV"
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// The factor by which raw values are multiplied to get engineering values
+    #[serde(default)]
+    pub scale: Option<f64>,
+    /// The offset added to scaled values to get engineering values
+    #[serde(default)]
+    pub offset: Option<f64>,
 }
 
 /// Descriptor for a record object
@@ -858,6 +1978,9 @@ pub struct ArrayDefinition {
 #[serde(deny_unknown_fields)]
 pub struct RecordDefinition {
     /// The sub object definitions for this record object
+    ///
+    /// Subs may be listed in any order, and there may be gaps between their sub indices. Sub 0
+    /// reports the highest sub index defined.
     #[serde(default)]
     pub subs: Vec<SubDefinition>,
 }
@@ -894,9 +2017,117 @@ impl ObjectDefinition {
             Object::Record(_) => ObjectCode::Record,
         }
     }
-}
 
-impl DeviceConfig {
+    /// Get the [`SubInfo`] describing a sub object, or None if the sub object does not exist
+    pub fn sub_info(&self, sub: u8) -> Option<SubInfo> {
+        fn make_info(
+            data_type: DataType,
+            access_type: AccessType,
+            pdo_mapping: PdoMappable,
+            persist: bool,
+        ) -> SubInfo {
+            SubInfo {
+                size: data_type.size(),
+                data_type: data_type.into(),
+                access_type,
+                pdo_mapping,
+                persist,
+            }
+        }
+
+        if sub == OBJECT_STRUCTURE_SUB {
+            return Some(SubInfo::OBJECT_STRUCTURE);
+        }
+        match &self.object {
+            Object::Var(var) => (sub == 0).then(|| {
+                make_info(
+                    var.data_type,
+                    var.access_type.0,
+                    var.pdo_mapping,
+                    var.persist,
+                )
+            }),
+            Object::Array(array) => match sub {
+                0 if array.resizable => Some(make_info(
+                    DataType::UInt8,
+                    AccessType::Rw,
+                    PdoMappable::None,
+                    array.persist,
+                )),
+                0 => Some(SubInfo::MAX_SUB_NUMBER),
+                _ if sub as usize <= array.array_size => Some(make_info(
+                    array.data_type,
+                    array.access_type.0,
+                    array.pdo_mapping,
+                    array.persist,
+                )),
+                _ => None,
+            },
+            Object::Record(record) => match record.subs.iter().find(|s| s.sub_index == sub) {
+                Some(s) => Some(make_info(
+                    s.data_type,
+                    s.access_type.0,
+                    s.pdo_mapping,
+                    s.persist,
+                )),
+                None if sub == 0 => Some(SubInfo::MAX_SUB_NUMBER),
+                None => None,
+            },
+        }
+    }
+
+    /// Get the engineering unit metadata declared for a sub object
+    ///
+    /// Returns None if the sub object does not exist, or none of `unit`, `scale` or `offset` are
+    /// set on it.
+    pub fn unit_metadata(&self, sub: u8) -> Option<UnitMetadata> {
+        fn make_metadata(
+            unit: &Option<String>,
+            scale: Option<f64>,
+            offset: Option<f64>,
+        ) -> Option<UnitMetadata> {
+            if unit.is_none() && scale.is_none() && offset.is_none() {
+                return None;
+            }
+            let default = UnitMetadata::default();
+            Some(UnitMetadata {
+                unit: unit.clone().unwrap_or(default.unit),
+                scale: scale.unwrap_or(default.scale),
+                offset: offset.unwrap_or(default.offset),
+            })
+        }
+
+        match &self.object {
+            Object::Var(var) if sub == 0 => make_metadata(&var.unit, var.scale, var.offset),
+            Object::Array(array) if sub != 0 && sub as usize <= array.array_size => {
+                make_metadata(&array.unit, array.scale, array.offset)
+            }
+            Object::Record(record) => record
+                .subs
+                .iter()
+                .find(|s| s.sub_index == sub)
+                .and_then(|s| make_metadata(&s.unit, s.scale, s.offset)),
+            _ => None,
+        }
+    }
+
+    /// Get the data type of a sub object, or None if the sub object does not exist
+    fn sub_data_type(&self, sub: u8) -> Option<DataType> {
+        match &self.object {
+            Object::Var(var) => (sub == 0).then_some(var.data_type),
+            Object::Array(array) => {
+                (sub != 0 && sub as usize <= array.array_size).then_some(array.data_type)
+            }
+            Object::Record(record) => record
+                .subs
+                .iter()
+                .find(|s| s.sub_index == sub)
+                .map(|s| s.data_type),
+        }
+    }
+}
+
+impl DeviceConfig {
     /// Try to read a device config from a file
     pub fn load(config_path: impl AsRef<std::path::Path>) -> Result<Self, LoadError> {
         let config_str = std::fs::read_to_string(&config_path).context(IoSnafu)?;
@@ -907,8 +2138,31 @@ impl DeviceConfig {
     pub fn load_from_str(config_str: &str) -> Result<Self, LoadError> {
         let mut config: DeviceConfig = toml::from_str(config_str).context(TomlParsingSnafu)?;
 
+        if config.tx_queue_size == 0 {
+            return InvalidTxQueueSizeSnafu {
+                size: config.tx_queue_size,
+            }
+            .fail();
+        }
+
+        if let Some(status_pdo) = &config.status_pdo {
+            Self::add_status_pdo(&mut config.pdos, status_pdo)?;
+        }
+
+        Self::validate_connection_set(&config.connection_set)?;
+        Self::validate_pdo_cob_ids(&config.pdos, &config.connection_set)?;
+        Self::validate_heartbeat_consumers(&config.heartbeat_consumers)?;
+        Self::validate_config_manager(&config.config_manager)?;
+        Self::validate_bootloader_banks(&config.bootloader)?;
+
+        // The autostart setting in the startup section takes precedence over the top-level one
+        if let Some(autostart) = config.startup.autostart {
+            config.autostart = autostart;
+        }
+
         // Add mandatory objects to the config
         config.objects.extend(mandatory_objects(&config));
+        config.objects.extend(startup_objects(&config));
         config
             .objects
             .extend(bootloader_objects(&config.bootloader));
@@ -917,12 +2171,363 @@ impl DeviceConfig {
             config.pdos.num_tpdo as usize,
         ));
         config.objects.extend(object_storage_objects(&config));
+        config.objects.extend(diagnostics_objects(&config));
+        config.objects.extend(log_ring_objects(&config));
+        config.objects.extend(change_counter_objects(&config));
+        config.objects.extend(node_status_objects(&config));
+        config.objects.extend(heartbeat_consumer_objects(&config));
+        config.objects.extend(config_manager_objects(&config));
+        config.objects.extend(unit_metadata_objects(&config));
+        // The signature covers all of the other objects, so must be added last
+        let signature_objects = config_signature_objects(&config);
+        config.objects.extend(signature_objects);
 
         Self::validate_unique_indices(&config.objects)?;
+        Self::validate_sub_indices(&config.objects)?;
+        Self::validate_unit_metadata(&config.objects)?;
+        Self::validate_pdo_mappings(&config)?;
 
         Ok(config)
     }
 
+    /// Get the signature of the object dictionary described by this config
+    ///
+    /// The signature is a CRC32 of the index, object code and the data type, access type and size
+    /// of every sub object, in index order, so it changes whenever the layout of the object
+    /// dictionary changes. Names, default values and other metadata are not included, nor is the
+    /// configuration signature object itself. When [DeviceConfig::config_signature] is set, the
+    /// signature is readable from object 0x5F04, so that tools can check that a node's firmware
+    /// matches the config they were written for.
+    pub fn signature(&self) -> u32 {
+        let mut objects: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|obj| obj.index != object_ids::CONFIG_SIGNATURE)
+            .collect();
+        objects.sort_by_key(|obj| obj.index);
+
+        let mut crc = Crc32::new();
+        for obj in objects {
+            crc.update(&obj.index.to_le_bytes());
+            crc.update(&[obj.object_code() as u8]);
+            for sub in 0..OBJECT_STRUCTURE_SUB {
+                let Some(info) = obj.sub_info(sub) else {
+                    continue;
+                };
+                let access_type = match info.access_type {
+                    AccessType::Ro => 0u8,
+                    AccessType::Wo => 1,
+                    AccessType::Rw => 2,
+                    AccessType::Const => 3,
+                };
+                crc.update(&[sub]);
+                crc.update(&u16::from(info.data_type).to_le_bytes());
+                crc.update(&[access_type]);
+                crc.update(&(info.size as u32).to_le_bytes());
+            }
+        }
+        crc.finish()
+    }
+
+    /// Get the unit metadata declared for every sub object, sorted by index and sub index
+    pub fn unit_metadata_entries(&self) -> Vec<(u16, u8, UnitMetadata)> {
+        let mut entries: Vec<_> = self
+            .objects
+            .iter()
+            .flat_map(|obj| {
+                (0..=255u8).filter_map(|sub| obj.unit_metadata(sub).map(|m| (obj.index, sub, m)))
+            })
+            .collect();
+        entries.sort_by_key(|(index, sub, _)| (*index, *sub));
+        entries
+    }
+
+    /// Get the contents of the unit metadata object (0x5F03)
+    pub fn unit_metadata_table(&self) -> Vec<u8> {
+        encode_table(
+            self.unit_metadata_entries()
+                .iter()
+                .map(|(index, sub, meta)| (*index, *sub, meta)),
+        )
+    }
+
+    /// Add the default configuration of the node status TPDO
+    fn add_status_pdo(pdos: &mut DevicePdoConfig, cfg: &StatusPdoConfig) -> Result<(), LoadError> {
+        let invalid = |reason| {
+            InvalidStatusPdoSnafu {
+                tpdo: cfg.tpdo,
+                reason,
+            }
+            .fail()
+        };
+        if cfg.tpdo >= pdos.num_tpdo as usize {
+            return invalid("the TPDO does not exist");
+        }
+        if pdos.tpdo_defaults.contains_key(&cfg.tpdo) {
+            return invalid("the TPDO also has a default configuration in the [pdos] section");
+        }
+        if cfg.period_ms == 0 {
+            return invalid("the period must be non-zero");
+        }
+        pdos.tpdo_defaults
+            .insert(cfg.tpdo, cfg.pdo_default_config());
+        Ok(())
+    }
+
+    fn validate_connection_set(cfg: &ConnectionSetConfig) -> Result<(), LoadError> {
+        let fixed = [
+            ("NMT", NMT_CMD_ID.raw(), NMT_CMD_ID.raw()),
+            ("SYNC", SYNC_ID.raw(), SYNC_ID.raw()),
+            ("TIME", TIME_ID.raw(), TIME_ID.raw()),
+            ("LSS", LSS_RESP_ID.raw(), LSS_REQ_ID.raw()),
+        ];
+        let ranges = cfg.ranges();
+        for (i, &(service, first, last)) in ranges.iter().enumerate() {
+            if last > 0x7FF {
+                return InvalidConnectionSetBaseSnafu {
+                    service,
+                    base: (first - 1) as u16,
+                }
+                .fail();
+            }
+            if let Some(&(other, _, _)) = ranges[..i]
+                .iter()
+                .chain(fixed.iter())
+                .find(|(_, other_first, other_last)| first <= *other_last && last >= *other_first)
+            {
+                return OverlappingConnectionSetSnafu { service, other }.fail();
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_pdo_cob_ids(
+        pdos: &DevicePdoConfig,
+        connection_set: &ConnectionSetConfig,
+    ) -> Result<(), LoadError> {
+        let reserved: Vec<(u32, u32)> = RESERVED_COB_IDS
+            .iter()
+            .copied()
+            .chain(
+                connection_set
+                    .ranges()
+                    .iter()
+                    .map(|&(_, first, last)| (first, last)),
+            )
+            .collect();
+        let defaults = pdos
+            .tpdo_defaults
+            .iter()
+            .map(|(num, cfg)| ("TPDO", num, cfg))
+            .chain(
+                pdos.rpdo_defaults
+                    .iter()
+                    .map(|(num, cfg)| ("RPDO", num, cfg)),
+            );
+        for (kind, num, cfg) in defaults {
+            let max_id = if cfg.extended { 0x1FFF_FFFF } else { 0x7FF };
+            let max_node_id = if cfg.add_node_id { 127 } else { 0 };
+            if cfg
+                .cob_id
+                .checked_add(max_node_id)
+                .is_none_or(|id| id > max_id)
+            {
+                return InvalidPdoCobIdSnafu {
+                    kind,
+                    num: *num,
+                    cob_id: cfg.cob_id,
+                }
+                .fail();
+            }
+            if cfg.extended {
+                continue;
+            }
+            // The node ID added at runtime is in 1-127
+            let (lowest, highest) = if cfg.add_node_id {
+                (cfg.cob_id + 1, cfg.cob_id + 127)
+            } else {
+                (cfg.cob_id, cfg.cob_id)
+            };
+            if let Some(&(first, last)) = reserved
+                .iter()
+                .find(|(first, last)| lowest <= *last && highest >= *first)
+            {
+                return ReservedPdoCobIdSnafu {
+                    kind,
+                    num: *num,
+                    cob_id: cfg.cob_id,
+                    first,
+                    last,
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the default PDO mappings can be applied by the node
+    fn validate_pdo_mappings(config: &DeviceConfig) -> Result<(), LoadError> {
+        let pdos = &config.pdos;
+        let defaults = pdos
+            .tpdo_defaults
+            .iter()
+            .map(|(num, cfg)| ("TPDO", num, cfg))
+            .chain(
+                pdos.rpdo_defaults
+                    .iter()
+                    .map(|(num, cfg)| ("RPDO", num, cfg)),
+            );
+        for (kind, num, cfg) in defaults {
+            let mut bits = 0;
+            for mapping in &cfg.mappings {
+                let invalid = |reason| {
+                    InvalidPdoMappingSnafu {
+                        kind,
+                        num: *num,
+                        index: mapping.index,
+                        sub: mapping.sub,
+                        reason,
+                    }
+                    .fail()
+                };
+                let Some(info) = config
+                    .objects
+                    .iter()
+                    .find(|obj| obj.index == mapping.index)
+                    .and_then(|obj| obj.sub_info(mapping.sub))
+                else {
+                    return invalid("the sub object does not exist");
+                };
+                let (mappable, access_ok) = if kind == "TPDO" {
+                    (
+                        info.pdo_mapping.supports_tpdo(),
+                        info.access_type.is_readable(),
+                    )
+                } else {
+                    (
+                        info.pdo_mapping.supports_rpdo(),
+                        info.access_type.is_writable(),
+                    )
+                };
+                if !mappable {
+                    return invalid("the sub object is not mappable to this type of PDO");
+                }
+                if !access_ok {
+                    return invalid("the access type of the sub object does not allow it");
+                }
+                if mapping.size % 8 != 0 {
+                    return invalid("the size must be a whole number of bytes");
+                }
+                if mapping.size as usize > info.size * 8 {
+                    return invalid("the size is larger than the sub object");
+                }
+                bits += mapping.size as usize;
+            }
+            if bits > 64 {
+                return PdoTooLongSnafu {
+                    kind,
+                    num: *num,
+                    bits,
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_heartbeat_consumers(cfg: &HeartbeatConsumerConfig) -> Result<(), LoadError> {
+        if cfg.count > 127 {
+            return InvalidHeartbeatConsumerCountSnafu { count: cfg.count }.fail();
+        }
+        if cfg.defaults.len() > cfg.count as usize {
+            return TooManyHeartbeatConsumerDefaultsSnafu {
+                count: cfg.count,
+                num_defaults: cfg.defaults.len(),
+            }
+            .fail();
+        }
+        for (i, default) in cfg.defaults.iter().enumerate() {
+            let repeated = cfg.defaults[..i]
+                .iter()
+                .any(|other| other.node_id == default.node_id);
+            if !(1..=127).contains(&default.node_id) || repeated {
+                return InvalidHeartbeatConsumerNodeSnafu {
+                    node_id: default.node_id,
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_config_manager(cfg: &ConfigManagerConfig) -> Result<(), LoadError> {
+        for (i, &node_id) in cfg.node_ids.iter().enumerate() {
+            let repeated = cfg.node_ids[..i].contains(&node_id);
+            if !(1..=127).contains(&node_id) || repeated {
+                return InvalidConfigManagerNodeSnafu { node_id }.fail();
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_bootloader_banks(cfg: &BootloaderConfig) -> Result<(), LoadError> {
+        let in_bank = |bank| cfg.sections.iter().any(|s| s.bank == Some(bank));
+        if !cfg.dual_bank {
+            if cfg.sections.iter().any(|s| s.bank.is_some()) {
+                return InvalidBootloaderBanksSnafu {
+                    reason: "sections may only be assigned a bank when dual_bank is enabled",
+                }
+                .fail();
+            }
+        } else if cfg.sections.iter().any(|s| s.bank.is_none()) {
+            return InvalidBootloaderBanksSnafu {
+                reason: "every section must be assigned a bank when dual_bank is enabled",
+            }
+            .fail();
+        } else if !in_bank(BootloaderBank::A) || !in_bank(BootloaderBank::B) {
+            return InvalidBootloaderBanksSnafu {
+                reason: "each bank must contain at least one section",
+            }
+            .fail();
+        }
+        Ok(())
+    }
+
+    fn validate_unit_metadata(objects: &[ObjectDefinition]) -> Result<(), LoadError> {
+        for obj in objects {
+            for sub in 0..=255u8 {
+                let Some(meta) = obj.unit_metadata(sub) else {
+                    continue;
+                };
+                let data_type = obj.sub_data_type(sub).unwrap_or_default();
+                let reason = if data_type.is_str()
+                    || matches!(
+                        data_type,
+                        DataType::Domain | DataType::TimeOfDay | DataType::TimeDifference
+                    ) {
+                    Some("only numeric types can have units")
+                } else if meta.unit.len() > u8::MAX as usize {
+                    Some("unit must be at most 255 bytes")
+                } else if !meta.scale.is_finite() || meta.scale == 0.0 {
+                    Some("scale must be finite and non-zero")
+                } else if !meta.offset.is_finite() {
+                    Some("offset must be finite")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return InvalidUnitMetadataSnafu {
+                        index: obj.index,
+                        sub,
+                        reason,
+                    }
+                    .fail();
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn validate_unique_indices(objects: &[ObjectDefinition]) -> Result<(), LoadError> {
         let mut found_indices = HashMap::new();
         for obj in objects {
@@ -948,6 +2553,36 @@ impl DeviceConfig {
 
         Ok(())
     }
+
+    fn validate_sub_indices(objects: &[ObjectDefinition]) -> Result<(), LoadError> {
+        for obj in objects {
+            match &obj.object {
+                Object::Array(array) if array.array_size > MAX_ARRAY_SIZE => {
+                    return ArrayTooLargeSnafu {
+                        index: obj.index,
+                        size: array.array_size,
+                    }
+                    .fail();
+                }
+                Object::Record(record) => {
+                    // Sub 0 of generated records always reports the highest sub index, so only
+                    // callback records may define it
+                    if let Some(sub) = record.subs.iter().find(|s| {
+                        s.sub_index == OBJECT_STRUCTURE_SUB
+                            || (s.sub_index == 0 && !obj.application_callback)
+                    }) {
+                        return ReservedSubIndexSnafu {
+                            index: obj.index,
+                            sub: sub.sub_index,
+                        }
+                        .fail();
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A newtype on AccessType to implement serialization
@@ -1005,6 +2640,33 @@ pub enum DataType {
     Domain,
 }
 
+impl From<DataType> for crate::objects::DataType {
+    fn from(dt: DataType) -> Self {
+        use crate::objects::DataType as ObjDataType;
+        match dt {
+            DataType::Boolean => ObjDataType::Boolean,
+            DataType::Int8 => ObjDataType::Int8,
+            DataType::Int16 => ObjDataType::Int16,
+            DataType::Int24 => ObjDataType::Int24,
+            DataType::Int32 => ObjDataType::Int32,
+            DataType::Int64 => ObjDataType::Int64,
+            DataType::UInt8 => ObjDataType::UInt8,
+            DataType::UInt16 => ObjDataType::UInt16,
+            DataType::UInt24 => ObjDataType::UInt24,
+            DataType::UInt32 => ObjDataType::UInt32,
+            DataType::UInt64 => ObjDataType::UInt64,
+            DataType::Real32 => ObjDataType::Real32,
+            DataType::Real64 => ObjDataType::Real64,
+            DataType::VisibleString(_) => ObjDataType::VisibleString,
+            DataType::OctetString(_) => ObjDataType::OctetString,
+            DataType::UnicodeString(_) => ObjDataType::UnicodeString,
+            DataType::TimeOfDay => ObjDataType::TimeOfDay,
+            DataType::TimeDifference => ObjDataType::TimeDifference,
+            DataType::Domain => ObjDataType::Domain,
+        }
+    }
+}
+
 impl DataType {
     /// Returns true if the type is one of the stringy types
     pub fn is_str(&self) -> bool {
@@ -1105,7 +2767,13 @@ impl<'de> serde::Deserialize<'de> for DataType {
 
 #[cfg(test)]
 mod tests {
-    use crate::device_config::{DeviceConfig, LoadError};
+    use crate::device_config::{
+        ArrayDefinition, ConnectionSetConfig, DefaultValue, DeviceConfig, HeartbeatConsumerDefault,
+        LoadError, Object, RecordDefinition,
+    };
+    use crate::messages::ConnectionSet;
+    use crate::objects::{AccessType, DataType, SubInfo};
+    use crate::unit_metadata::UnitMetadata;
     use assertables::assert_contains;
     #[test]
     fn test_duplicate_objects_errors() {
@@ -1182,4 +2850,709 @@ mod tests {
             err.to_string().as_str()
         );
     }
+
+    #[test]
+    fn test_reserved_sub_indices() {
+        const HEADER: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+        "#;
+
+        let array = format!(
+            r#"{HEADER}
+            [[objects]]
+            index = 0x2000
+            parameter_name = "Big Array"
+            object_type = "array"
+            data_type = "uint8"
+            access_type = "rw"
+            array_size = 255
+            "#
+        );
+        let err = DeviceConfig::load_from_str(&array).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::ArrayTooLarge {
+                index: 0x2000,
+                size: 255
+            }
+        ));
+        assert!(DeviceConfig::load_from_str(&array.replace("255", "254")).is_ok());
+
+        let record = format!(
+            r#"{HEADER}
+            [[objects]]
+            index = 0x2001
+            parameter_name = "Record"
+            object_type = "record"
+            [[objects.subs]]
+            sub_index = 255
+            parameter_name = "Reserved"
+            data_type = "uint8"
+            access_type = "rw"
+            "#
+        );
+        let err = DeviceConfig::load_from_str(&record).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::ReservedSubIndex {
+                index: 0x2001,
+                sub: 255
+            }
+        ));
+
+        let err = DeviceConfig::load_from_str(&record.replace("255", "0")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::ReservedSubIndex {
+                index: 0x2001,
+                sub: 0
+            }
+        ));
+        // A callback record implements sub 0 itself
+        let callback = record.replace("255", "0").replace(
+            r#"object_type = "record""#,
+            "object_type = \"record\"\n            application_callback = true",
+        );
+        assert!(DeviceConfig::load_from_str(&callback).is_ok());
+    }
+
+    #[test]
+    fn test_sparse_record() {
+        let config = DeviceConfig::load_from_str(
+            r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+
+            [[objects]]
+            index = 0x2001
+            parameter_name = "Sparse"
+            object_type = "record"
+            [[objects.subs]]
+            sub_index = 6
+            data_type = "uint16"
+            access_type = "rw"
+            [[objects.subs]]
+            sub_index = 1
+            data_type = "uint8"
+            access_type = "rw"
+            "#,
+        )
+        .unwrap();
+        let obj = config.objects.iter().find(|o| o.index == 0x2001).unwrap();
+        assert_eq!(Some(SubInfo::MAX_SUB_NUMBER), obj.sub_info(0));
+        assert_eq!(DataType::UInt8, obj.sub_info(1).unwrap().data_type);
+        assert_eq!(None, obj.sub_info(2));
+        assert_eq!(DataType::UInt16, obj.sub_info(6).unwrap().data_type);
+        assert_eq!(None, obj.sub_info(7));
+    }
+
+    #[test]
+    fn test_queue_sizes() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert_eq!(4, config.tx_queue_size);
+
+        let toml = format!("tx_queue_size = 16\n{TOML}");
+        let config = DeviceConfig::load_from_str(&toml).unwrap();
+        assert_eq!(16, config.tx_queue_size);
+
+        let toml = format!("tx_queue_size = 0\n{TOML}");
+        let err = DeviceConfig::load_from_str(&toml).unwrap_err();
+        assert!(matches!(err, LoadError::InvalidTxQueueSize { size: 0 }));
+
+        assert_eq!(0, config.rx_queue_size);
+        let toml = format!("rx_queue_size = 16\n{TOML}");
+        let config = DeviceConfig::load_from_str(&toml).unwrap();
+        assert_eq!(16, config.rx_queue_size);
+    }
+
+    #[test]
+    fn test_heartbeat_consumers() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert!(!config.objects.iter().any(|o| o.index == 0x1016));
+
+        let consumers = |cfg: &str| format!("{TOML}\n[heartbeat_consumers]\n{cfg}");
+        let config = DeviceConfig::load_from_str(&consumers(
+            "count = 3\ndefaults = [{ node_id = 4, time = 500 }]",
+        ))
+        .unwrap();
+        assert_eq!(3, config.heartbeat_consumers.count);
+        assert_eq!(
+            vec![HeartbeatConsumerDefault {
+                node_id: 4,
+                time: 500
+            }],
+            config.heartbeat_consumers.defaults
+        );
+        let object = config.objects.iter().find(|o| o.index == 0x1016).unwrap();
+        assert!(matches!(
+            &object.object,
+            Object::Array(ArrayDefinition { array_size: 3, .. })
+        ));
+
+        let err = DeviceConfig::load_from_str(&consumers("count = 128")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidHeartbeatConsumerCount { count: 128 }
+        ));
+        let err = DeviceConfig::load_from_str(&consumers(
+            "count = 1\ndefaults = [{ node_id = 4, time = 500 }, { node_id = 5, time = 500 }]",
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::TooManyHeartbeatConsumerDefaults {
+                count: 1,
+                num_defaults: 2
+            }
+        ));
+        let err = DeviceConfig::load_from_str(&consumers(
+            "count = 2\ndefaults = [{ node_id = 4, time = 500 }, { node_id = 4, time = 100 }]",
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidHeartbeatConsumerNode { node_id: 4 }
+        ));
+        let err = DeviceConfig::load_from_str(&consumers(
+            "count = 1\ndefaults = [{ node_id = 0, time = 500 }]",
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidHeartbeatConsumerNode { node_id: 0 }
+        ));
+    }
+
+    #[test]
+    fn test_config_manager() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert!(!config
+            .objects
+            .iter()
+            .any(|o| o.index == 0x1F20 || o.index == 0x1F22));
+
+        let manager = |cfg: &str| format!("{TOML}\n[config_manager]\n{cfg}");
+        let config =
+            DeviceConfig::load_from_str(&manager("node_ids = [2, 3]\nconcise_dcf_size = 128"))
+                .unwrap();
+        assert!(!config.objects.iter().any(|o| o.index == 0x1F20));
+        let object = config.objects.iter().find(|o| o.index == 0x1F22).unwrap();
+        let info = object.sub_info(127).unwrap();
+        assert_eq!(DataType::Domain, info.data_type);
+        assert_eq!(AccessType::Rw, info.access_type);
+        assert_eq!(None, object.sub_info(128));
+
+        // No storage is allocated without any node IDs
+        let config = DeviceConfig::load_from_str(&manager("concise_dcf_size = 128")).unwrap();
+        assert!(!config.objects.iter().any(|o| o.index == 0x1F22));
+
+        let err = DeviceConfig::load_from_str(&manager("node_ids = [2, 2]")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidConfigManagerNode { node_id: 2 }
+        ));
+        let err = DeviceConfig::load_from_str(&manager("node_ids = [128]")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidConfigManagerNode { node_id: 128 }
+        ));
+    }
+
+    #[test]
+    fn test_bootloader_banks() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+            [bootloader]
+            application = true
+        "#;
+        let bootloader = |cfg: &str| format!("{TOML}\n{cfg}");
+        let config = DeviceConfig::load_from_str(&bootloader(
+            r#"
+            dual_bank = true
+            sections = [
+                { name = "a", size = 16, bank = "A" },
+                { name = "b", size = 16, bank = "B" },
+            ]
+            "#,
+        ))
+        .unwrap();
+        let info = config.objects.iter().find(|o| o.index == 0x5500).unwrap();
+        assert!(matches!(
+            &info.object,
+            Object::Record(RecordDefinition { subs }) if subs.len() == 5
+        ));
+        let section = config.objects.iter().find(|o| o.index == 0x5511).unwrap();
+        let Object::Record(record) = &section.object else {
+            panic!("Section is not a record");
+        };
+        assert!(matches!(
+            record.subs[6].default_value,
+            Some(DefaultValue::Integer(2))
+        ));
+
+        for cfg in [
+            r#"sections = [{ name = "a", size = 16, bank = "A" }]"#,
+            "dual_bank = true\nsections = [{ name = \"a\", size = 16, bank = \"A\" }]",
+            "dual_bank = true\nsections = [{ name = \"a\", size = 16 }]",
+        ] {
+            let err = DeviceConfig::load_from_str(&bootloader(cfg)).unwrap_err();
+            assert!(matches!(err, LoadError::InvalidBootloaderBanks { .. }));
+        }
+    }
+
+    #[test]
+    fn test_pdo_cob_id_range() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+            [pdos]
+            num_rpdo = 1
+            num_tpdo = 1
+        "#;
+        let pdo = |cob_id: u32, extended: bool| {
+            format!(
+                r#"{TOML}
+                [pdos.tpdo.0]
+                enabled = true
+                cob_id = {cob_id}
+                extended = {extended}
+                add_node_id = true
+                transmission_type = 254
+                mappings = []
+                "#
+            )
+        };
+
+        let config = DeviceConfig::load_from_str(&pdo(0x18000200, true)).unwrap();
+        let tpdo = &config.pdos.tpdo_defaults[&0];
+        assert!(tpdo.extended);
+        assert_eq!(0x18000200, tpdo.cob_id);
+        assert!(DeviceConfig::load_from_str(&pdo(0x480, false)).is_ok());
+
+        let err = DeviceConfig::load_from_str(&pdo(0x790, false)).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidPdoCobId {
+                kind: "TPDO",
+                num: 0,
+                cob_id: 0x790
+            }
+        ));
+        assert!(DeviceConfig::load_from_str(&pdo(0x1FFFFFF0, true)).is_err());
+
+        // Node IDs added to 0x700 land in the heartbeat range
+        let err = DeviceConfig::load_from_str(&pdo(0x700, false)).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::ReservedPdoCobId {
+                cob_id: 0x700,
+                first: 0x701,
+                last: 0x77F,
+                ..
+            }
+        ));
+        // 0x580 + node ID is the SDO response range
+        assert!(DeviceConfig::load_from_str(&pdo(0x580, false)).is_err());
+        assert!(DeviceConfig::load_from_str(&pdo(0x100, false)).is_err());
+        // Extended IDs are not checked against the reserved ranges
+        assert!(DeviceConfig::load_from_str(&pdo(0x700, true)).is_ok());
+    }
+
+    #[test]
+    fn test_connection_set() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+            [pdos]
+            num_rpdo = 0
+            num_tpdo = 1
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert_eq!(ConnectionSetConfig::default(), config.connection_set);
+        assert_eq!(
+            ConnectionSet::DEFAULT,
+            ConnectionSet::from(config.connection_set)
+        );
+
+        let with_set = |set: &str| format!("{TOML}\n[connection_set]\n{set}");
+        let config = DeviceConfig::load_from_str(&with_set(
+            "sdo_request_base = 0x480\nsdo_response_base = 0x400",
+        ))
+        .unwrap();
+        let set = ConnectionSet::from(config.connection_set);
+        assert_eq!(0x485, set.sdo_request_id(5));
+        assert_eq!(0x405, set.sdo_response_id(5));
+        assert_eq!(0x705, set.heartbeat_id(5));
+
+        let err = DeviceConfig::load_from_str(&with_set("heartbeat_base = 0x790")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidConnectionSetBase {
+                service: "heartbeat_base",
+                base: 0x790
+            }
+        ));
+        let err = DeviceConfig::load_from_str(&with_set("sdo_request_base = 0x540")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::OverlappingConnectionSet {
+                service: "sdo_response_base",
+                other: "sdo_request_base"
+            }
+        ));
+        let err = DeviceConfig::load_from_str(&with_set("emcy_base = 0xC0")).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::OverlappingConnectionSet {
+                service: "emcy_base",
+                other: "TIME"
+            }
+        ));
+
+        // PDOs may not use the moved ranges
+        let pdo = |cob_id: u32| {
+            format!(
+                r#"{}
+                [pdos.tpdo.0]
+                enabled = true
+                cob_id = {cob_id}
+                add_node_id = true
+                transmission_type = 254
+                mappings = []
+                "#,
+                with_set("sdo_request_base = 0x480\nsdo_response_base = 0x400")
+            )
+        };
+        let err = DeviceConfig::load_from_str(&pdo(0x480)).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::ReservedPdoCobId {
+                first: 0x481,
+                last: 0x4FF,
+                ..
+            }
+        ));
+        assert!(DeviceConfig::load_from_str(&pdo(0x280)).is_ok());
+    }
+
+    #[test]
+    fn test_pdo_default_mappings() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+
+            [[objects]]
+            index = 0x2000
+            parameter_name = "Inputs"
+            object_type = "array"
+            data_type = "uint32"
+            access_type = "ro"
+            array_size = 4
+            pdo_mapping = "tpdo"
+
+            [[objects]]
+            index = 0x2001
+            parameter_name = "Output"
+            object_type = "var"
+            data_type = "uint16"
+            access_type = "rw"
+            pdo_mapping = "both"
+
+            [[objects]]
+            index = 0x2002
+            parameter_name = "Setting"
+            object_type = "var"
+            data_type = "uint8"
+            access_type = "rw"
+        "#;
+        let pdo = |kind: &str, mappings: &str| {
+            format!(
+                r#"{TOML}
+                [pdos.{kind}.1]
+                enabled = true
+                cob_id = 0x200
+                add_node_id = true
+                transmission_type = 254
+                mappings = [{mappings}]
+                "#
+            )
+        };
+        let load_err = |kind: &str, mappings: &str| {
+            DeviceConfig::load_from_str(&pdo(kind, mappings)).unwrap_err()
+        };
+
+        DeviceConfig::load_from_str(&pdo(
+            "tpdo",
+            "{ index=0x2000, sub=1, size=32 }, { index=0x2000, sub=2, size=16 }, { index=0x2001, sub=0, size=16 }",
+        ))
+        .unwrap();
+        DeviceConfig::load_from_str(&pdo("rpdo", "{ index=0x2001, sub=0, size=16 }")).unwrap();
+
+        let err = load_err(
+            "tpdo",
+            "{ index=0x2000, sub=1, size=32 }, { index=0x2000, sub=2, size=32 }, { index=0x2001, sub=0, size=16 }",
+        );
+        assert!(matches!(
+            err,
+            LoadError::PdoTooLong {
+                kind: "TPDO",
+                num: 1,
+                bits: 80
+            }
+        ));
+
+        for (kind, mapping, index, sub) in [
+            // Does not exist
+            ("tpdo", "{ index=0x2000, sub=5, size=32 }", 0x2000, 5),
+            ("tpdo", "{ index=0x2003, sub=0, size=8 }", 0x2003, 0),
+            // Not mappable
+            ("tpdo", "{ index=0x2002, sub=0, size=8 }", 0x2002, 0),
+            ("rpdo", "{ index=0x2000, sub=1, size=32 }", 0x2000, 1),
+            // Bad sizes
+            ("tpdo", "{ index=0x2001, sub=0, size=12 }", 0x2001, 0),
+            ("rpdo", "{ index=0x2001, sub=0, size=32 }", 0x2001, 0),
+        ] {
+            let err = load_err(kind, mapping);
+            let LoadError::InvalidPdoMapping {
+                index: err_index,
+                sub: err_sub,
+                ..
+            } = err
+            else {
+                panic!("Unexpected error for {mapping}: {err:?}");
+            };
+            assert_eq!((index, sub), (err_index, err_sub));
+        }
+    }
+
+    #[test]
+    fn test_unit_metadata() {
+        const TOML: &str = r#"
+            device_name = "test"
+            unit_metadata = true
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+
+            [[objects]]
+            index = 0x2000
+            object_type = "array"
+            data_type = "uint16"
+            access_type = "ro"
+            array_size = 2
+            unit = "V"
+            scale = 0.001
+
+            [[objects]]
+            index = 0x2001
+            object_type = "record"
+            [[objects.subs]]
+            sub_index = 1
+            data_type = "int16"
+            access_type = "ro"
+            offset = -40.0
+            [[objects.subs]]
+            sub_index = 2
+            data_type = "int16"
+            access_type = "ro"
+        "#;
+
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert!(config.objects.iter().any(|o| o.index == 0x5F03));
+        let volts = UnitMetadata {
+            unit: "V".into(),
+            scale: 0.001,
+            offset: 0.0,
+        };
+        let offset_only = UnitMetadata {
+            offset: -40.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                (0x2000, 1, volts.clone()),
+                (0x2000, 2, volts),
+                (0x2001, 1, offset_only),
+            ],
+            config.unit_metadata_entries()
+        );
+        assert_eq!(
+            Ok(config.unit_metadata_entries()),
+            crate::unit_metadata::decode_table(&config.unit_metadata_table())
+        );
+
+        let invalid = |object: &str| {
+            let toml = format!("{TOML}\n[[objects]]\nindex = 0x2002\n{object}");
+            DeviceConfig::load_from_str(&toml).unwrap_err()
+        };
+        let err = invalid(
+            r#"object_type = "var"
+            data_type = "VisibleString(4)"
+            access_type = "ro"
+            unit = "V""#,
+        );
+        assert!(matches!(
+            err,
+            LoadError::InvalidUnitMetadata {
+                index: 0x2002,
+                sub: 0,
+                reason: "only numeric types can have units"
+            }
+        ));
+        let err = invalid(
+            r#"object_type = "var"
+            data_type = "uint8"
+            access_type = "ro"
+            scale = 0.0"#,
+        );
+        assert!(matches!(
+            err,
+            LoadError::InvalidUnitMetadata {
+                reason: "scale must be finite and non-zero",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_config_signature() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+
+            [[objects]]
+            index = 0x2000
+            parameter_name = "Value"
+            object_type = "var"
+            data_type = "uint16"
+            access_type = "rw"
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert!(!config.objects.iter().any(|o| o.index == 0x5F04));
+
+        let toml = format!("config_signature = true\n{TOML}");
+        let signed = DeviceConfig::load_from_str(&toml).unwrap();
+        // The signature object is not included in the signature
+        assert_eq!(config.signature(), signed.signature());
+        let obj = signed.objects.iter().find(|o| o.index == 0x5F04).unwrap();
+        let Object::Var(def) = &obj.object else {
+            panic!("signature object is not a var");
+        };
+        assert!(matches!(
+            def.default_value,
+            Some(DefaultValue::Integer(v)) if v == config.signature() as i64
+        ));
+
+        // Changes to names and default values don't change the signature
+        let renamed = TOML.replace("\"Value\"", "\"Other\"").replace(
+            "access_type = \"rw\"",
+            "access_type = \"rw\"\ndefault_value = 5",
+        );
+        let renamed = DeviceConfig::load_from_str(&renamed).unwrap();
+        assert_eq!(config.signature(), renamed.signature());
+
+        // Changes to the layout do
+        let retyped = DeviceConfig::load_from_str(&TOML.replace("uint16", "uint32")).unwrap();
+        assert_ne!(config.signature(), retyped.signature());
+        let reaccessed = DeviceConfig::load_from_str(&TOML.replace("\"rw\"", "\"ro\"")).unwrap();
+        assert_ne!(config.signature(), reaccessed.signature());
+    }
+
+    #[test]
+    fn test_status_pdo() {
+        const TOML: &str = r#"
+            device_name = "test"
+            [identity]
+            vendor_id = 0
+            product_code = 1
+            revision_number = 2
+        "#;
+        let config = DeviceConfig::load_from_str(TOML).unwrap();
+        assert!(!config.objects.iter().any(|o| o.index == 0x5F05));
+
+        let toml = format!("{TOML}\n[status_pdo]\ntpdo = 3\ncob_id = 0x480\n");
+        let config = DeviceConfig::load_from_str(&toml).unwrap();
+        let pdo = config.pdos.tpdo_defaults.get(&3).unwrap();
+        assert!(pdo.enabled);
+        assert!(pdo.add_node_id);
+        assert_eq!(0x480, pdo.cob_id);
+        assert_eq!(254, pdo.transmission_type);
+        assert_eq!(1000, pdo.event_timer);
+        assert_eq!(
+            64,
+            pdo.mappings.iter().map(|m| m.size as usize).sum::<usize>()
+        );
+        let status = config.objects.iter().find(|o| o.index == 0x5F05).unwrap();
+        for m in &pdo.mappings {
+            let info = status.sub_info(m.sub).unwrap();
+            assert_eq!(m.size as usize, info.size * 8);
+            assert!(info.pdo_mapping.supports_tpdo());
+        }
+
+        // The TPDO must exist, and not be configured twice
+        let missing = format!("{TOML}\n[status_pdo]\ntpdo = 4\ncob_id = 0x480\n");
+        assert!(matches!(
+            DeviceConfig::load_from_str(&missing),
+            Err(LoadError::InvalidStatusPdo { tpdo: 4, .. })
+        ));
+        let conflict = format!(
+            "{TOML}\n[status_pdo]\ntpdo = 0\ncob_id = 0x480\n\n[pdos.tpdo.0]\nenabled = false\n\
+             cob_id = 0x200\nadd_node_id = true\ntransmission_type = 254\nmappings = []\n"
+        );
+        assert!(matches!(
+            DeviceConfig::load_from_str(&conflict),
+            Err(LoadError::InvalidStatusPdo { tpdo: 0, .. })
+        ));
+        // The COB ID is checked like any other PDO
+        let reserved = format!("{TOML}\n[status_pdo]\ntpdo = 0\ncob_id = 0x700\n");
+        assert!(matches!(
+            DeviceConfig::load_from_str(&reserved),
+            Err(LoadError::ReservedPdoCobId { .. })
+        ));
+    }
 }
@@ -0,0 +1,184 @@
+//! J1939 identifiers
+//!
+//! J1939 uses 29-bit extended CAN IDs, and is often found on the same bus as CANopen in vehicles.
+//! The ID is made up of a priority, a parameter group number (PGN), and the address of the sender:
+//!
+//! | Bits  | Field                      |
+//! |-------|----------------------------|
+//! | 26-28 | Priority                   |
+//! | 25    | Extended data page (EDP)   |
+//! | 24    | Data page (DP)             |
+//! | 16-23 | PDU format (PF)            |
+//! | 8-15  | PDU specific (PS)          |
+//! | 0-7   | Source address             |
+//!
+//! When the PDU format is below 240 (PDU1), the message is addressed to a single node, and the PDU
+//! specific byte holds the destination address. Otherwise (PDU2), it is broadcast, and the PDU
+//! specific byte is part of the PGN.
+//!
+//! ```
+//! use zencan_common::{j1939::J1939Id, CanId};
+//!
+//! // Electronic engine controller 1, broadcast by the engine at address 0
+//! let id = J1939Id::new(3, 0xF004, J1939Id::GLOBAL_ADDRESS, 0);
+//! assert_eq!(CanId::extended(0x0CF0_0400), id.into());
+//!
+//! // A request, addressed to node 0x21
+//! let id = J1939Id::try_from(CanId::extended(0x18EA_2117)).unwrap();
+//! assert_eq!(0xEA00, id.pgn());
+//! assert_eq!(Some(0x21), id.destination_address());
+//! assert_eq!(0x17, id.source_address());
+//! ```
+use crate::messages::CanId;
+
+/// The PDU format values at and above which a message is broadcast (PDU2)
+const PDU2_MIN_FORMAT: u8 = 240;
+
+/// A J1939 29-bit identifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct J1939Id(u32);
+
+impl J1939Id {
+    /// The destination address used to send a PDU1 message to all nodes
+    pub const GLOBAL_ADDRESS: u8 = 0xFF;
+
+    /// Create an ID
+    ///
+    /// `priority` is truncated to 3 bits, and `pgn` to 18 bits. `destination` is only used for
+    /// PDU1 PGNs, i.e. those with a PDU format below 240; for PDU1 PGNs, the PDU specific byte of
+    /// `pgn` is ignored.
+    pub const fn new(priority: u8, pgn: u32, destination: u8, source: u8) -> Self {
+        let pgn = pgn & 0x3FFFF;
+        let pgn = if ((pgn >> 8) as u8) < PDU2_MIN_FORMAT {
+            (pgn & !0xFF) | destination as u32
+        } else {
+            pgn
+        };
+        Self(((priority as u32 & 0x7) << 26) | (pgn << 8) | source as u32)
+    }
+
+    /// Create an ID from a raw 29-bit value
+    ///
+    /// Bits above bit 28 are ignored.
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw & 0x1FFF_FFFF)
+    }
+
+    /// Get the raw 29-bit ID
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Get the priority, from 0 (highest) to 7 (lowest)
+    pub const fn priority(&self) -> u8 {
+        (self.0 >> 26) as u8 & 0x7
+    }
+
+    /// Get the PDU format byte
+    pub const fn pdu_format(&self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    /// Returns true if the message is addressed to a single node (PDU1)
+    pub const fn is_pdu1(&self) -> bool {
+        self.pdu_format() < PDU2_MIN_FORMAT
+    }
+
+    /// Get the parameter group number
+    ///
+    /// For PDU1 messages, the destination address is not part of the PGN, and is returned as 0.
+    pub const fn pgn(&self) -> u32 {
+        let pgn = (self.0 >> 8) & 0x3FFFF;
+        if self.is_pdu1() {
+            pgn & !0xFF
+        } else {
+            pgn
+        }
+    }
+
+    /// Get the destination address of a PDU1 message, or None for a broadcast PDU2 message
+    pub const fn destination_address(&self) -> Option<u8> {
+        if self.is_pdu1() {
+            Some((self.0 >> 8) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Get the address of the node which sent the message
+    pub const fn source_address(&self) -> u8 {
+        self.0 as u8
+    }
+}
+
+impl From<J1939Id> for CanId {
+    fn from(value: J1939Id) -> Self {
+        CanId::Extended(value.0)
+    }
+}
+
+/// Error for converting a standard [`CanId`] to a [`J1939Id`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotExtendedIdError;
+
+impl core::fmt::Display for NotExtendedIdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "J1939 requires an extended ID")
+    }
+}
+impl core::error::Error for NotExtendedIdError {}
+
+impl TryFrom<CanId> for J1939Id {
+    type Error = NotExtendedIdError;
+
+    fn try_from(value: CanId) -> Result<Self, Self::Error> {
+        match value {
+            CanId::Extended(id) => Ok(Self::from_raw(id)),
+            CanId::Std(_) => Err(NotExtendedIdError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdu2() {
+        let id = J1939Id::new(6, 0xFEF1, 0x12, 0x80);
+        assert_eq!(0x18FE_F180, id.raw());
+        assert_eq!(6, id.priority());
+        assert_eq!(0xFE, id.pdu_format());
+        assert!(!id.is_pdu1());
+        assert_eq!(0xFEF1, id.pgn());
+        assert_eq!(None, id.destination_address());
+        assert_eq!(0x80, id.source_address());
+    }
+
+    #[test]
+    fn test_pdu1() {
+        let id = J1939Id::new(6, 0xEA55, 0x21, 0x17);
+        assert_eq!(0x18EA_2117, id.raw());
+        assert!(id.is_pdu1());
+        assert_eq!(0xEA00, id.pgn());
+        assert_eq!(Some(0x21), id.destination_address());
+
+        // The data page bits are part of the PGN
+        let id = J1939Id::new(7, 0x3_EF00, 0x05, 0x01);
+        assert_eq!(0x3_EF00, id.pgn());
+        assert_eq!(7, id.priority());
+    }
+
+    #[test]
+    fn test_can_id_conversion() {
+        let id = J1939Id::from_raw(0xFFFF_FFFF);
+        assert_eq!(0x1FFF_FFFF, id.raw());
+        assert_eq!(CanId::extended(0x1FFF_FFFF), CanId::from(id));
+        assert_eq!(Ok(id), J1939Id::try_from(CanId::extended(0x1FFF_FFFF)));
+        assert_eq!(
+            Err(NotExtendedIdError),
+            J1939Id::try_from(CanId::std(0x181))
+        );
+    }
+}
@@ -8,10 +8,13 @@
 
 mod atomic_cell;
 pub use atomic_cell::AtomicCell;
+pub mod concise_dcf;
 pub mod constants;
+pub mod crc32;
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 pub mod device_config;
+pub mod j1939;
 pub mod lss;
 pub mod messages;
 pub mod nmt;
@@ -22,14 +25,18 @@ pub mod node_id;
 pub mod objects;
 pub mod pdo;
 pub mod sdo;
+pub mod strings;
 mod time_types;
 pub mod traits;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod unit_metadata;
 
 #[cfg(all(feature = "socketcan", target_os = "linux"))]
 mod socketcan;
 
 #[cfg(all(feature = "socketcan", target_os = "linux"))]
-#[cfg_attr(docsrs, doc(all(feature = "socketcan", target_os = "linux")))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "socketcan", target_os = "linux"))))]
 pub use socketcan::{open_socketcan, SocketCanReceiver, SocketCanSender};
 
 pub use arbitrary_int::{i24, u24};
@@ -8,6 +8,7 @@ use crate::messages::{CanId, CanMessage, MessageError, LSS_REQ_ID, LSS_RESP_ID};
 
 /// Defines all possible values for the LSS command specifier field
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LssCommandSpecifier {
     /// Used to change the LSS mode for all nodes on the bus
     SwitchModeGlobal = 0x04,
@@ -51,6 +52,7 @@ pub enum LssCommandSpecifier {
 /// Represents the possible values of the error field returned in response to a ConfigureNodeId
 /// command
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum LssConfigureError {
     /// Success
@@ -572,6 +574,7 @@ impl LssResponse {
 
 /// The possible LSS states
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum LssState {
     /// The default state of a node.
@@ -601,6 +604,7 @@ impl LssState {
 /// register on the MCU, or by loading a previously programmed value from flash. It is important
 /// that each device on the bus have a unique identity.
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct LssIdentity {
     /// A number indicating the vendor of the device
     pub vendor_id: u32,
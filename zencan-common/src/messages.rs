@@ -6,12 +6,14 @@ use crate::{
     lss::{LssRequest, LssResponse},
     nmt::{InvalidNmtStateError, NmtState},
     sdo::{SdoRequest, SdoResponse},
+    TimeOfDay,
 };
 
 /// Yet another CanId enum
 ///
 /// TODO: Consider if this should use the CanId from embedded_can?
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CanId {
     /// An extended 28-bit identifier
     Extended(u32),
@@ -54,12 +56,27 @@ impl CanId {
             CanId::Std(_) => false,
         }
     }
+
+    /// Get a key which orders IDs by CAN bus arbitration priority
+    ///
+    /// Lower values win arbitration. A standard ID wins over an extended ID with the same 11-bit
+    /// base ID.
+    pub const fn arbitration_key(&self) -> u32 {
+        match self {
+            CanId::Std(id) => (*id as u32 & 0x7FF) << 19,
+            CanId::Extended(id) => ((*id >> 18) & 0x7FF) << 19 | 1 << 18 | (*id & 0x3FFFF),
+        }
+    }
 }
 
 const MAX_DATA_LENGTH: usize = 8;
 
 /// A struct to contain a CanMessage
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Messages are compared by their ID, data and RTR flag; the receive timestamp is not included in
+/// comparisons.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct CanMessage {
     /// The data payload of the message
     ///
@@ -71,8 +88,23 @@ pub struct CanMessage {
     pub rtr: bool,
     /// The id of this message
     pub id: CanId,
+    /// The time at which the message was received, in microseconds
+    ///
+    /// This is set by receivers which are able to capture a receive time, and is None otherwise.
+    /// The time base depends on the receiver; e.g. a socketcan receiver reports hardware clock time
+    /// when available, or system time otherwise, while an embedded node would typically use the
+    /// same microsecond clock it passes to `Node::process`.
+    pub timestamp: Option<u64>,
 }
 
+impl PartialEq for CanMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.rtr == other.rtr && self.data() == other.data()
+    }
+}
+
+impl Eq for CanMessage {}
+
 impl Default for CanMessage {
     fn default() -> Self {
         Self {
@@ -80,6 +112,7 @@ impl Default for CanMessage {
             dlc: 0,
             id: CanId::Std(0),
             rtr: false,
+            timestamp: None,
         }
     }
 }
@@ -103,6 +136,7 @@ impl CanMessage {
             dlc,
             data: buf,
             rtr,
+            timestamp: None,
         }
     }
 
@@ -131,6 +165,19 @@ impl CanMessage {
     pub fn is_rtr(&self) -> bool {
         self.rtr
     }
+
+    /// Get the receive timestamp of the message in microseconds, if one was captured
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Return a copy of the message with the receive timestamp set
+    pub fn with_timestamp(self, timestamp_us: u64) -> Self {
+        Self {
+            timestamp: Some(timestamp_us),
+            ..self
+        }
+    }
 }
 
 /// The error codes which can be delivered in a CAN frame
@@ -138,6 +185,7 @@ impl CanMessage {
 /// These are set by a receiver when it detects an error in a received frame, and received globally
 /// by all nodes on the bus
 #[derive(Clone, Copy, Debug, Snafu)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum CanError {
     /// The transmitter detected a different value on the bus than the value is was transmitting at
@@ -205,16 +253,89 @@ impl NmtCommandSpecifier {
 pub const NMT_CMD_ID: CanId = CanId::Std(0);
 /// The COB ID used for sending SYNC commands
 pub const SYNC_ID: CanId = CanId::Std(0x80);
+/// The COB ID used for sending TIME messages
+pub const TIME_ID: CanId = CanId::Std(0x100);
 /// The COB ID used for LSS slave responses
 pub const LSS_RESP_ID: CanId = CanId::Std(0x7E4);
 /// The COB ID used for LSS master requests
 pub const LSS_REQ_ID: CanId = CanId::Std(0x7E5);
 /// The COB ID used for heartbeat messages
 pub const HEARTBEAT_ID: u16 = 0x700;
+/// The COB ID used to force a new flying master negotiation
+pub const FLYING_MASTER_FORCE_ID: CanId = CanId::Std(0x71);
+/// The COB ID used by the active flying master to announce itself
+pub const FLYING_MASTER_ACTIVE_ID: CanId = CanId::Std(0x73);
 /// The default base ID for sending SDO requests (server node ID is added)
 pub const SDO_REQ_BASE: u16 = 0x600;
 /// The default base ID for sending SDO responses (server node ID is added)
 pub const SDO_RESP_BASE: u16 = 0x580;
+/// The default base ID for EMCY messages (producer node ID is added)
+pub const EMCY_BASE: u16 = 0x80;
+
+/// The base IDs of the predefined connection set
+///
+/// The COB IDs used by a node's default SDO server, heartbeat and EMCY are derived by adding the
+/// node ID to a base ID for each service. [`ConnectionSet::DEFAULT`] holds the bases defined by
+/// CiA 301, but some deployments move services to other ranges, and the node and client can be
+/// configured to match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConnectionSet {
+    /// Base ID on which SDO requests are sent to a server
+    pub sdo_request_base: u16,
+    /// Base ID on which SDO responses are sent by a server
+    pub sdo_response_base: u16,
+    /// Base ID on which heartbeats are sent
+    pub heartbeat_base: u16,
+    /// Base ID on which EMCY messages are sent
+    pub emcy_base: u16,
+}
+
+impl ConnectionSet {
+    /// The predefined connection set of CiA 301
+    pub const DEFAULT: Self = Self {
+        sdo_request_base: SDO_REQ_BASE,
+        sdo_response_base: SDO_RESP_BASE,
+        heartbeat_base: HEARTBEAT_ID,
+        emcy_base: EMCY_BASE,
+    };
+
+    /// Get the ID on which SDO requests are sent to the server on node `node_id`
+    pub const fn sdo_request_id(&self, node_id: u8) -> u16 {
+        self.sdo_request_base + node_id as u16
+    }
+
+    /// Get the ID on which the server on node `node_id` sends SDO responses
+    pub const fn sdo_response_id(&self, node_id: u8) -> u16 {
+        self.sdo_response_base + node_id as u16
+    }
+
+    /// Get the ID on which node `node_id` sends heartbeats
+    pub const fn heartbeat_id(&self, node_id: u8) -> u16 {
+        self.heartbeat_base + node_id as u16
+    }
+
+    /// Get the ID on which node `node_id` sends EMCY messages
+    pub const fn emcy_id(&self, node_id: u8) -> u16 {
+        self.emcy_base + node_id as u16
+    }
+
+    /// Get the node ID of the heartbeat producer for a standard ID
+    ///
+    /// Returns None if the ID is not in the heartbeat range, i.e. heartbeat base + 1-127
+    pub const fn heartbeat_node(&self, id: u16) -> Option<u8> {
+        match id.checked_sub(self.heartbeat_base) {
+            Some(node @ 1..=127) => Some(node as u8),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ConnectionSet {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
 
 /// An NmtCommand message
 #[derive(Clone, Copy, Debug)]
@@ -226,6 +347,13 @@ pub struct NmtCommand {
     pub node: u8,
 }
 
+impl NmtCommand {
+    /// Returns true if the command applies to the node with ID `node_id`
+    pub fn addresses(&self, node_id: u8) -> bool {
+        self.node == 0 || self.node == node_id
+    }
+}
+
 impl TryFrom<CanMessage> for NmtCommand {
     type Error = MessageError;
 
@@ -271,20 +399,27 @@ pub struct Heartbeat {
     pub state: NmtState,
 }
 
-impl From<Heartbeat> for CanMessage {
-    fn from(value: Heartbeat) -> Self {
+impl Heartbeat {
+    /// Create the heartbeat message, sent on the heartbeat ID of `connection_set`
+    pub fn to_message(&self, connection_set: &ConnectionSet) -> CanMessage {
         let mut msg = CanMessage {
-            id: CanId::Std(HEARTBEAT_ID | value.node as u16),
+            id: CanId::Std(connection_set.heartbeat_id(self.node)),
             dlc: 1,
             ..Default::default()
         };
-        msg.data[0] = value.state as u8;
-        if value.toggle {
+        msg.data[0] = self.state as u8;
+        if self.toggle {
             msg.data[0] |= 1 << 7;
         }
         msg
     }
 }
+
+impl From<Heartbeat> for CanMessage {
+    fn from(value: Heartbeat) -> Self {
+        value.to_message(&ConnectionSet::DEFAULT)
+    }
+}
 /// Represents a SYNC object/message
 ///
 /// A single CAN node can serve as the SYNC provider, sending a periodic sync object to all other
@@ -329,6 +464,125 @@ impl From<CanMessage> for SyncObject {
     }
 }
 
+/// An application-defined command sent on the vendor broadcast channel
+///
+/// The vendor broadcast channel is an extended CAN ID, chosen by the application, which all nodes
+/// listen to. It allows fleet-wide actions (e.g. entering a bootloader) to be triggered with a
+/// single message, rather than an SDO write to each node.
+///
+/// The first byte of the message holds the command, and the remaining bytes (up to 7) hold
+/// arguments. The meaning of both is defined by the application.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VendorBroadcast {
+    /// The application-defined command
+    pub command: u8,
+    args: [u8; 7],
+    len: u8,
+}
+
+impl VendorBroadcast {
+    /// The maximum number of argument bytes which can be sent with a command
+    pub const MAX_ARGS: usize = 7;
+
+    /// Create a new VendorBroadcast
+    ///
+    /// Returns [`MessageError::InvalidField`] if more than [`Self::MAX_ARGS`] argument bytes are
+    /// provided.
+    pub fn new(command: u8, args: &[u8]) -> Result<Self, MessageError> {
+        if args.len() > Self::MAX_ARGS {
+            return Err(MessageError::InvalidField);
+        }
+        let mut buf = [0; 7];
+        buf[..args.len()].copy_from_slice(args);
+        Ok(Self {
+            command,
+            args: buf,
+            len: args.len() as u8,
+        })
+    }
+
+    /// Get the argument bytes sent with the command
+    pub fn args(&self) -> &[u8] {
+        &self.args[..self.len as usize]
+    }
+
+    /// Create a CanMessage carrying this command on the broadcast channel `id`
+    pub fn to_can_message(&self, id: CanId) -> CanMessage {
+        let mut data = [0; 8];
+        data[0] = self.command;
+        data[1..1 + self.len as usize].copy_from_slice(self.args());
+        CanMessage::new(id, &data[..1 + self.len as usize])
+    }
+}
+
+impl TryFrom<&[u8]> for VendorBroadcast {
+    type Error = MessageError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        match data.split_first() {
+            Some((&command, args)) => Self::new(command, args),
+            None => Err(MessageError::MessageTooShort),
+        }
+    }
+}
+
+/// Identifies a master taking part in flying master negotiation
+///
+/// Masters are ordered by priority, and then by node ID. The lowest value wins the negotiation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MasterId {
+    /// The master's priority. Lower values have higher priority.
+    pub priority: u8,
+    /// The master's node ID, which breaks ties between masters with the same priority
+    pub node_id: u8,
+}
+
+/// Messages used to negotiate which of several redundant masters is active
+///
+/// See `zencan_client::FlyingMaster`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlyingMasterMessage {
+    /// Requests that all masters restart negotiation
+    ForceNegotiation,
+    /// Sent periodically by the active master, and by a master which has won negotiation
+    ActiveMaster(MasterId),
+}
+
+impl From<FlyingMasterMessage> for CanMessage {
+    fn from(value: FlyingMasterMessage) -> Self {
+        match value {
+            FlyingMasterMessage::ForceNegotiation => CanMessage::new(FLYING_MASTER_FORCE_ID, &[]),
+            FlyingMasterMessage::ActiveMaster(id) => {
+                CanMessage::new(FLYING_MASTER_ACTIVE_ID, &[id.priority, id.node_id])
+            }
+        }
+    }
+}
+
+impl TryFrom<CanMessage> for FlyingMasterMessage {
+    type Error = MessageError;
+
+    fn try_from(msg: CanMessage) -> Result<Self, Self::Error> {
+        let cob_id = msg.id();
+        if cob_id == FLYING_MASTER_FORCE_ID {
+            Ok(FlyingMasterMessage::ForceNegotiation)
+        } else if cob_id == FLYING_MASTER_ACTIVE_ID {
+            match msg.data() {
+                [priority, node_id, ..] => Ok(FlyingMasterMessage::ActiveMaster(MasterId {
+                    priority: *priority,
+                    node_id: *node_id,
+                })),
+                _ => Err(MessageError::MessageTooShort),
+            }
+        } else {
+            Err(MessageError::UnrecognizedId { cob_id })
+        }
+    }
+}
+
 impl TryFrom<CanMessage> for ZencanMessage {
     type Error = MessageError;
 
@@ -362,6 +616,8 @@ impl TryFrom<CanMessage> for ZencanMessage {
             Ok(ZencanMessage::SdoRequest(req))
         } else if cob_id == SYNC_ID {
             Ok(ZencanMessage::Sync(msg.into()))
+        } else if cob_id == TIME_ID {
+            Ok(ZencanMessage::Time(msg.try_into()?))
         } else if cob_id == LSS_REQ_ID {
             let req: LssRequest = msg
                 .data()
@@ -380,6 +636,48 @@ impl TryFrom<CanMessage> for ZencanMessage {
     }
 }
 
+/// Represents a TIME object/message
+///
+/// A single CAN node can serve as the TIME producer, broadcasting the network time of day to all
+/// other nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeObject {
+    /// The network time
+    pub time: TimeOfDay,
+}
+
+impl TimeObject {
+    /// Create a new TimeObject
+    pub fn new(time: TimeOfDay) -> Self {
+        Self { time }
+    }
+}
+
+impl From<TimeObject> for CanMessage {
+    fn from(value: TimeObject) -> Self {
+        CanMessage::new(TIME_ID, &value.time.to_le_bytes())
+    }
+}
+
+impl TryFrom<CanMessage> for TimeObject {
+    type Error = MessageError;
+
+    fn try_from(msg: CanMessage) -> Result<Self, Self::Error> {
+        if msg.id() != TIME_ID {
+            return Err(MessageError::UnexpectedId {
+                cob_id: msg.id(),
+                expected: TIME_ID,
+            });
+        }
+        let bytes = msg
+            .data()
+            .first_chunk::<{ TimeOfDay::SIZE }>()
+            .ok_or(MessageError::MessageTooShort)?;
+        Ok(TimeObject::new(TimeOfDay::from_le_bytes(*bytes)))
+    }
+}
+
 /// An enum representing all of the standard messages
 #[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -387,6 +685,7 @@ impl TryFrom<CanMessage> for ZencanMessage {
 pub enum ZencanMessage {
     NmtCommand(NmtCommand),
     Sync(SyncObject),
+    Time(TimeObject),
     Heartbeat(Heartbeat),
     SdoRequest(SdoRequest),
     SdoResponse(SdoResponse),
@@ -396,6 +695,7 @@ pub enum ZencanMessage {
 
 /// An error for problems converting CanMessages to zencan types
 #[derive(Debug, Clone, Copy, PartialEq, Snafu)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum MessageError {
     /// Not enough bytes were present in the message
     MessageTooShort,
@@ -98,6 +98,15 @@ impl NodeConfig {
         Ok(NodeConfig(raw_config))
     }
 
+    /// Get the configuration signature the node is expected to have, if one is specified
+    ///
+    /// The configuration should only be loaded into a node whose configuration signature object
+    /// (0x5F04) holds this value. See
+    /// [DeviceConfig::signature](crate::device_config::DeviceConfig::signature).
+    pub fn config_signature(&self) -> Option<u32> {
+        self.0.config_signature
+    }
+
     /// Get the transmit PDO configurations
     pub fn tpdos(&self) -> &HashMap<usize, PdoConfig> {
         &self.0.tpdo.0
@@ -131,6 +140,8 @@ impl From<PdoConfigMapSerializer> for HashMap<usize, PdoConfig> {
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct NodeConfigSerializer {
+    #[serde(default)]
+    pub config_signature: Option<u32>,
     #[serde(default)]
     pub tpdo: PdoConfigMapSerializer,
     #[serde(default)]
@@ -420,6 +431,7 @@ mod test {
         "#;
 
         let result = NodeConfig::load_from_str(str).unwrap();
+        assert_eq!(None, result.config_signature());
         assert_eq!(1, result.tpdos().len());
         let tpdo = result.tpdos().get(&0).unwrap();
         assert_eq!(CanId::extended(0x800), tpdo.cob_id);
@@ -428,6 +440,8 @@ mod test {
     #[test]
     fn test_node_config_parse() {
         let str = r#"
+        config_signature = 0xDEADBEEF
+
         [tpdo.0]
         enabled = true
         cob_id = 0x181
@@ -453,6 +467,7 @@ mod test {
         };
 
         println!("{config:?}");
+        assert_eq!(Some(0xDEADBEEF), config.config_signature());
         assert_eq!(1, config.tpdos().len());
         assert_eq!(1, config.stores().len());
     }
@@ -10,6 +10,40 @@ pub struct ObjectId {
     pub sub: u8,
 }
 
+/// Groups of objects for which a node counts changes
+///
+/// Communication objects (0x1000-0x1FFF) are in their own group, as they are also saved and restored
+/// separately from application objects. All other objects are in the application group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PersistGroup {
+    /// Communication objects (0x1000-0x1FFF)
+    Communication,
+    /// All objects outside of the communication range
+    Application,
+}
+
+impl PersistGroup {
+    /// All persist groups, ordered by their sub index in the change counters object
+    pub const ALL: [PersistGroup; 2] = [PersistGroup::Communication, PersistGroup::Application];
+
+    /// Get the group an object index belongs to
+    pub const fn from_index(index: u16) -> Self {
+        if index >= 0x1000 && index < 0x2000 {
+            Self::Communication
+        } else {
+            Self::Application
+        }
+    }
+
+    /// The sub index of the group's counter in the change counters object (0x5F02)
+    pub const fn sub_index(&self) -> u8 {
+        match self {
+            Self::Communication => 1,
+            Self::Application => 2,
+        }
+    }
+}
+
 /// Object Code value
 ///
 /// Defines the type of an object or sub object
@@ -190,6 +224,8 @@ impl From<u16> for DataType {
             9 => VisibleString,
             0xa => OctetString,
             0xb => UnicodeString,
+            0xc => TimeOfDay,
+            0xd => TimeDifference,
             0xf => Domain,
             0x10 => Int24,
             0x11 => Real64,
@@ -201,6 +237,34 @@ impl From<u16> for DataType {
     }
 }
 
+impl From<DataType> for u16 {
+    fn from(value: DataType) -> Self {
+        use DataType::*;
+        match value {
+            Boolean => 1,
+            Int8 => 2,
+            Int16 => 3,
+            Int32 => 4,
+            UInt8 => 5,
+            UInt16 => 6,
+            UInt32 => 7,
+            Real32 => 8,
+            VisibleString => 9,
+            OctetString => 0xa,
+            UnicodeString => 0xb,
+            TimeOfDay => 0xc,
+            TimeDifference => 0xd,
+            Domain => 0xf,
+            Int24 => 0x10,
+            Real64 => 0x11,
+            Int64 => 0x15,
+            UInt24 => 0x16,
+            UInt64 => 0x1b,
+            Other(value) => value,
+        }
+    }
+}
+
 impl DataType {
     /// Returns true if data type is one of the string types
     pub fn is_str(&self) -> bool {
@@ -209,6 +273,25 @@ impl DataType {
             Self::VisibleString | Self::OctetString | Self::UnicodeString
         )
     }
+
+    /// Get the size in bytes, and signedness, of an integer data type
+    ///
+    /// Returns None for types which are not integers
+    pub fn int_format(&self) -> Option<(usize, bool)> {
+        match self {
+            Self::Int8 => Some((1, true)),
+            Self::Int16 => Some((2, true)),
+            Self::Int24 => Some((3, true)),
+            Self::Int32 => Some((4, true)),
+            Self::Int64 => Some((8, true)),
+            Self::UInt8 => Some((1, false)),
+            Self::UInt16 => Some((2, false)),
+            Self::UInt24 => Some((3, false)),
+            Self::UInt32 => Some((4, false)),
+            Self::UInt64 => Some((8, false)),
+            _ => None,
+        }
+    }
 }
 
 /// Information about a sub object
@@ -226,6 +309,13 @@ pub struct SubInfo {
     pub persist: bool,
 }
 
+/// The sub index which reports the structure of an object
+///
+/// Per CiA 301, reading sub 0xFF of any object returns a u32, with the object code in bits 0-7 and
+/// the data type of the object's sub objects in bits 8-15. Because of this, sub 0xFF can never be
+/// used to store data.
+pub const OBJECT_STRUCTURE_SUB: u8 = 0xFF;
+
 impl SubInfo {
     /// A shorthand value for sub0 on record and array objects
     pub const MAX_SUB_NUMBER: SubInfo = SubInfo {
@@ -236,6 +326,15 @@ impl SubInfo {
         persist: false,
     };
 
+    /// The sub info for the object structure sub object (see [`OBJECT_STRUCTURE_SUB`])
+    pub const OBJECT_STRUCTURE: SubInfo = SubInfo {
+        size: 4,
+        data_type: DataType::UInt32,
+        access_type: AccessType::Const,
+        pdo_mapping: PdoMappable::None,
+        persist: false,
+    };
+
     /// Convenience function for creating a new sub-info by type
     pub const fn new_u32() -> Self {
         Self {
@@ -386,4 +485,10 @@ impl SubInfo {
         self.persist = value;
         self
     }
+
+    /// Convenience function to set the pdo_mapping value
+    pub const fn pdo_mapping(mut self, value: PdoMappable) -> Self {
+        self.pdo_mapping = value;
+        self
+    }
 }
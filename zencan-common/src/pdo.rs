@@ -4,6 +4,7 @@
 ///
 /// Each mapping specifies one sub-object to be included in the PDO data bytes.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(
     feature = "std",
     derive(serde::Deserialize),
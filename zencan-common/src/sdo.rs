@@ -38,6 +38,7 @@ impl TryFrom<u8> for ServerCommand {
 ///
 /// Defines the various reasons an SDO transfer can be aborted
 #[derive(Clone, Copy, Debug, PartialEq, IntEnum)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u32)]
 pub enum AbortCode {
     /// Toggle bit not alternated
@@ -132,6 +133,7 @@ impl TryFrom<u8> for ClientCommand {
 
 /// Represents the CAN message used to send a segment during a block upload or download
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct BlockSegment {
     /// Complete flag
     ///
@@ -176,6 +178,7 @@ impl BlockSegment {
             dlc: 8,
             rtr: false,
             id,
+            timestamp: None,
         }
     }
 }
@@ -280,6 +283,11 @@ pub enum SdoRequest {
         index: u16,
         /// Object sub-index
         sub: u8,
+        /// Complete access
+        ///
+        /// When set, all sub objects of the object are written in one transfer, starting at `sub`,
+        /// which must be 0 or 1. This is an extension from CANopen over EtherCAT.
+        ca: bool,
         /// data (value on expedited, size when e=1 and s=1)
         data: [u8; 4],
     },
@@ -300,6 +308,11 @@ pub enum SdoRequest {
         index: u16,
         /// The requested sub object
         sub: u8,
+        /// Complete access
+        ///
+        /// When set, all sub objects of the object are read in one transfer, starting at `sub`,
+        /// which must be 0 or 1. This is an extension from CANopen over EtherCAT.
+        ca: bool,
     },
     /// Request the next segment in an upload
     ReqUploadSegment {
@@ -387,10 +400,27 @@ impl SdoRequest {
             s: size.is_some(),
             index,
             sub,
+            ca: false,
             data,
         }
     }
 
+    /// Create an initiate download request using complete access
+    ///
+    /// The data is the value of each sub object from `sub`, which must be 0 or 1, to the highest
+    /// sub object, concatenated. See [`SdoRequest::InitiateDownload::ca`].
+    pub fn initiate_complete_download(index: u16, sub: u8, size: u32) -> Self {
+        SdoRequest::InitiateDownload {
+            n: 0,
+            e: false,
+            s: true,
+            index,
+            sub,
+            ca: true,
+            data: size.to_le_bytes(),
+        }
+    }
+
     /// Create an initiate block download request
     pub fn initiate_block_download(index: u16, sub: u8, crc_supported: bool, size: u32) -> Self {
         SdoRequest::InitiateBlockDownload {
@@ -435,13 +465,29 @@ impl SdoRequest {
             s: true,
             index,
             sub,
+            ca: false,
             data: msg_data,
         }
     }
 
     /// Creata an `InitiateUpload` request
     pub fn initiate_upload(index: u16, sub: u8) -> Self {
-        SdoRequest::InitiateUpload { index, sub }
+        SdoRequest::InitiateUpload {
+            index,
+            sub,
+            ca: false,
+        }
+    }
+
+    /// Create an `InitiateUpload` request using complete access
+    ///
+    /// All sub objects from `sub`, which must be 0 or 1, to the highest sub object are read.
+    pub fn initiate_complete_upload(index: u16, sub: u8) -> Self {
+        SdoRequest::InitiateUpload {
+            index,
+            sub,
+            ca: true,
+        }
     }
 
     /// Create an InitiateBlockUpload request
@@ -471,9 +517,11 @@ impl SdoRequest {
                 s,
                 index,
                 sub,
+                ca,
                 data,
             } => {
                 payload[0] = ((ClientCommand::InitiateDownload as u8) << 5)
+                    | ((ca as u8) << 4)
                     | (n << 2)
                     | ((e as u8) << 1)
                     | s as u8;
@@ -490,8 +538,8 @@ impl SdoRequest {
 
                 payload[1..8].copy_from_slice(&data);
             }
-            SdoRequest::InitiateUpload { index, sub } => {
-                payload[0] = (ClientCommand::InitiateUpload as u8) << 5;
+            SdoRequest::InitiateUpload { index, sub, ca } => {
+                payload[0] = ((ClientCommand::InitiateUpload as u8) << 5) | ((ca as u8) << 4);
                 payload[1] = (index & 0xff) as u8;
                 payload[2] = (index >> 8) as u8;
                 payload[3] = sub;
@@ -597,6 +645,7 @@ impl TryFrom<&[u8]> for SdoRequest {
                 let n = (value[0] >> 2) & 0x3;
                 let e = (value[0] & (1 << 1)) != 0;
                 let s = (value[0] & (1 << 0)) != 0;
+                let ca = (value[0] & (1 << 4)) != 0;
                 let index = value[1] as u16 | ((value[2] as u16) << 8);
                 let sub = value[3];
                 let data = value[4..8].try_into().unwrap();
@@ -606,13 +655,15 @@ impl TryFrom<&[u8]> for SdoRequest {
                     s,
                     index,
                     sub,
+                    ca,
                     data,
                 })
             }
             ClientCommand::InitiateUpload => {
                 let index = value[1] as u16 | ((value[2] as u16) << 8);
                 let sub = value[3];
-                Ok(SdoRequest::InitiateUpload { index, sub })
+                let ca = (value[0] & (1 << 4)) != 0;
+                Ok(SdoRequest::InitiateUpload { index, sub, ca })
             }
             ClientCommand::ReqUploadSegment => {
                 let t = (((value[0]) >> 4) & 1) != 0;
@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::UNIX_EPOCH};
 
 use crate::{
     messages::{CanError, CanId, CanMessage},
     traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
 };
 use snafu::{ResultExt, Snafu};
-use socketcan::{CanFrame, CanSocket, EmbeddedFrame, Frame, ShouldRetry, Socket};
+use socketcan::{
+    CanFrame, CanSocket, CanTimestamps, EmbeddedFrame, Frame, ShouldRetry, Socket, SocketOptions,
+    SOF_TIMESTAMPING_OPT_CMSG, SOF_TIMESTAMPING_RAW_HARDWARE, SOF_TIMESTAMPING_RX_HARDWARE,
+    SOF_TIMESTAMPING_RX_SOFTWARE, SOF_TIMESTAMPING_SOFTWARE,
+};
 use tokio::io::{unix::AsyncFd, Interest};
 
 fn socketcan_id_to_zencan_id(id: socketcan::CanId) -> CanId {
@@ -32,6 +36,20 @@ fn socketcan_frame_to_zencan_message(frame: socketcan::CanFrame) -> Result<CanMe
     }
 }
 
+/// Convert the timestamps captured by the socket to microseconds
+///
+/// The adapter's hardware timestamp is used when available. Otherwise, the kernel software
+/// timestamp is used, as microseconds since the unix epoch.
+fn timestamp_to_us(ts: &CanTimestamps) -> Option<u64> {
+    if let Some(hw) = ts.hw {
+        return Some(hw.as_micros() as u64);
+    }
+    ts.sw
+        .or(ts.socket)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_micros() as u64)
+}
+
 fn zencan_message_to_socket_frame(frame: CanMessage) -> socketcan::CanFrame {
     let id = zencan_id_to_socketcan_id(frame.id());
 
@@ -73,45 +91,86 @@ impl CanSendError for SendError {
 /// Create an Async socket around a socketcan CanSocket. This is just a reimplemenation of the tokio
 /// socket in the `socketcan` crate, but with support for `try_read_frame` and `try_write_frame`
 /// added.
+///
+/// Receive timestamps are enabled on the socket when supported, and received frames are returned
+/// along with their timestamp in microseconds.
 #[derive(Debug)]
-struct AsyncCanSocket(AsyncFd<CanSocket>);
+struct AsyncCanSocket {
+    fd: AsyncFd<CanSocket>,
+    timestamps: bool,
+}
 
 #[allow(dead_code)]
 impl AsyncCanSocket {
     pub fn new(inner: CanSocket) -> Result<Self, std::io::Error> {
         inner.set_nonblocking(true)?;
-        Ok(Self(AsyncFd::new(inner)?))
+        let timestamps = Self::enable_timestamps(&inner);
+        Ok(Self {
+            fd: AsyncFd::new(inner)?,
+            timestamps,
+        })
     }
 
     pub fn open(ifname: &str) -> Result<Self, std::io::Error> {
-        let socket = CanSocket::open(ifname)?;
-        socket.set_nonblocking(true)?;
-        Ok(Self(AsyncFd::new(socket)?))
+        Self::new(CanSocket::open(ifname)?)
     }
 
-    /// Attempt to read a CAN frame from the socket without blocking
+    /// Request receive timestamps from the kernel, using hardware timestamps if the interface
+    /// supports them
+    ///
+    /// Returns true if timestamps were enabled
+    fn enable_timestamps(socket: &CanSocket) -> bool {
+        let mut flags =
+            SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE | SOF_TIMESTAMPING_OPT_CMSG;
+        if socket.has_hw_timestamps() {
+            flags |= SOF_TIMESTAMPING_RX_HARDWARE | SOF_TIMESTAMPING_RAW_HARDWARE;
+        }
+        socket.set_timestamping(flags).is_ok()
+    }
+
+    fn read_frame_inner(
+        &self,
+        socket: &CanSocket,
+    ) -> Result<(CanFrame, Option<u64>), std::io::Error> {
+        if self.timestamps {
+            let (frame, ts) = socket.read_frame_with_timestamps()?;
+            Ok((frame, timestamp_to_us(&ts)))
+        } else {
+            Ok((socket.read_frame()?, None))
+        }
+    }
+
+    /// Attempt to read a CAN frame and its receive timestamp from the socket without blocking
     ///
     /// If no message is immediately available, a WouldBlock error is returned.
-    pub fn try_read_frame(&self) -> Result<CanFrame, std::io::Error> {
-        self.0.get_ref().read_frame()
+    pub fn try_read_frame(&self) -> Result<(CanFrame, Option<u64>), std::io::Error> {
+        self.read_frame_inner(self.fd.get_ref())
     }
 
-    /// Read a CAN frame from the socket asynchronously
-    pub async fn read_frame(&self) -> Result<CanFrame, std::io::Error> {
-        self.0
-            .async_io(Interest::READABLE, |inner| inner.read_frame())
+    /// Read a CAN frame and its receive timestamp from the socket asynchronously
+    pub async fn read_frame(&self) -> Result<(CanFrame, Option<u64>), std::io::Error> {
+        self.fd
+            .async_io(Interest::READABLE, |inner| self.read_frame_inner(inner))
             .await
     }
 
     pub async fn write_frame(&self, frame: &CanFrame) -> Result<(), std::io::Error> {
-        self.0
+        self.fd
             .async_io(Interest::WRITABLE, |inner| inner.write_frame(frame))
             .await
     }
 
     /// Attempt to write a CAN frame to the socket without blocking
     pub fn try_write_frame(&self, frame: CanFrame) -> Result<(), std::io::Error> {
-        self.0.get_ref().write_frame(&frame)
+        self.fd.get_ref().write_frame(&frame)
+    }
+}
+
+/// Attach a receive timestamp to a converted message
+fn with_timestamp(msg: CanMessage, timestamp: Option<u64>) -> CanMessage {
+    match timestamp {
+        Some(ts) => msg.with_timestamp(ts),
+        None => msg,
     }
 }
 
@@ -120,7 +179,10 @@ impl AsyncCanReceiver for SocketCanReceiver {
 
     fn try_recv(&mut self) -> Option<CanMessage> {
         match self.socket.try_read_frame() {
-            Ok(frame) => Some(socketcan_frame_to_zencan_message(frame).unwrap()),
+            Ok((frame, ts)) => Some(with_timestamp(
+                socketcan_frame_to_zencan_message(frame).unwrap(),
+                ts,
+            )),
             _ => None,
         }
     }
@@ -128,7 +190,11 @@ impl AsyncCanReceiver for SocketCanReceiver {
     async fn recv(&mut self) -> Result<CanMessage, ReceiveError> {
         loop {
             match self.socket.read_frame().await {
-                Ok(frame) => return socketcan_frame_to_zencan_message(frame).context(CanSnafu),
+                Ok((frame, ts)) => {
+                    return socketcan_frame_to_zencan_message(frame)
+                        .map(|msg| with_timestamp(msg, ts))
+                        .context(CanSnafu)
+                }
                 Err(e) => {
                     if !e.should_retry() {
                         return Err(ReceiveError::Io { source: e });
@@ -165,6 +231,9 @@ impl AsyncCanSender for SocketCanSender {
 ///
 /// A key benefit of this is that by creating both sender and receiver objects from a shared socket,
 /// the receiver will not receive messages sent by the sender.
+///
+/// Receive timestamps are requested from the kernel, and received messages carry them in
+/// [`CanMessage::timestamp`]. Hardware timestamps are used if the interface supports them.
 #[cfg_attr(docsrs, doc(cfg(feature = "socketcan")))]
 pub fn open_socketcan<S: AsRef<str>>(
     device: S,
@@ -0,0 +1,104 @@
+//! Validation and truncation of string object values
+//!
+//! CiA 301 restricts VISIBLE_STRING values to the visible ASCII characters (0x20-0x7E). zencan
+//! stores UNICODE_STRING values as UTF-8. String objects may be written with less data than their
+//! size, in which case the value ends at the first null byte, and any bytes after it are ignored.
+
+/// The character encoding of a string object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StringEncoding {
+    /// Visible ASCII characters, for VISIBLE_STRING objects
+    Visible,
+    /// UTF-8, for UNICODE_STRING objects
+    Unicode,
+}
+
+impl StringEncoding {
+    /// Check that a string object value is valid for this encoding
+    ///
+    /// `data` is the raw value written to the object; only the bytes before the first null are
+    /// checked. If `complete` is false, `data` is the first part of a value still being written,
+    /// and may end part way through a UTF-8 character.
+    pub fn is_valid(&self, data: &[u8], complete: bool) -> bool {
+        let value = str_value(data);
+        match self {
+            StringEncoding::Visible => is_visible_string(value),
+            StringEncoding::Unicode if complete => core::str::from_utf8(value).is_ok(),
+            StringEncoding::Unicode => is_utf8_prefix(value),
+        }
+    }
+}
+
+/// Get the value of a null terminated string, excluding the terminator
+///
+/// Returns all of `data` if it contains no null.
+pub fn str_value(data: &[u8]) -> &[u8] {
+    let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    &data[..len]
+}
+
+/// Returns true if `data` contains only visible ASCII characters
+pub fn is_visible_string(data: &[u8]) -> bool {
+    data.iter().all(|b| (0x20..=0x7E).contains(b))
+}
+
+/// Returns true if `data` is valid UTF-8, or the start of valid UTF-8 which ends part way through a
+/// character
+pub fn is_utf8_prefix(data: &[u8]) -> bool {
+    match core::str::from_utf8(data) {
+        Ok(_) => true,
+        // An error with no length is an incomplete character at the end of the input
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Truncate a string to at most `max_len` bytes, without splitting a character
+///
+/// ```
+/// use zencan_common::strings::truncate_utf8;
+///
+/// assert_eq!("25", truncate_utf8("25°C", 3));
+/// assert_eq!("25°", truncate_utf8("25°C", 4));
+/// ```
+pub fn truncate_utf8(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut len = max_len;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visible() {
+        assert!(StringEncoding::Visible.is_valid(b"Hello, world!", true));
+        assert!(StringEncoding::Visible.is_valid(b"abc\0\xff\x01", true));
+        assert!(!StringEncoding::Visible.is_valid(b"tab\there", true));
+        assert!(!StringEncoding::Visible.is_valid("25°C".as_bytes(), true));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let bytes = "25°C".as_bytes();
+        assert!(StringEncoding::Unicode.is_valid(bytes, true));
+        // Split in the middle of the degree sign
+        assert!(StringEncoding::Unicode.is_valid(&bytes[..3], false));
+        assert!(!StringEncoding::Unicode.is_valid(&bytes[..3], true));
+        assert!(!StringEncoding::Unicode.is_valid(b"\xff", false));
+        assert!(StringEncoding::Unicode.is_valid(b"ok\0\xff", true));
+    }
+
+    #[test]
+    fn test_truncate_utf8() {
+        assert_eq!("", truncate_utf8("°", 1));
+        assert_eq!("abc", truncate_utf8("abc", 10));
+        assert_eq!("ab", truncate_utf8("abc", 2));
+    }
+}
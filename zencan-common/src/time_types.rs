@@ -21,6 +21,7 @@ pub enum TimeCreateError {
 
 /// Represents a time in 48-bits, as stored in TimeOfDay objects
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TimeOfDay(TimeDifference);
 
 impl TimeOfDay {
@@ -68,6 +69,15 @@ impl TimeOfDay {
         }
     }
 
+    /// Create a TimeOfDay from the total number of milliseconds since 1984-01-01
+    pub fn from_total_millis(millis: u64) -> Result<Self, TimeCreateError> {
+        let days = millis / MILLIS_PER_DAY;
+        if days > u16::MAX as u64 {
+            return OutOfRangeSnafu.fail();
+        }
+        Ok(Self::new(days as u16, (millis % MILLIS_PER_DAY) as u32))
+    }
+
     /// Create a TimeOfDay from little endian bytes
     pub fn from_le_bytes(bytes: [u8; 6]) -> Self {
         Self(TimeDifference::from_le_bytes(bytes))
@@ -129,6 +139,7 @@ impl TimeOfDay {
 
 /// Represents a duration of time in 48-bits, as stored in TimeDifference objects
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TimeDifference {
     ms: u32,
     days: u16,
@@ -0,0 +1,194 @@
+//! Engineering unit metadata for object values
+//!
+//! Sub objects in a device config may declare a `unit`, `scale` and `offset`, which describe how to
+//! convert the raw value stored in the object into an engineering value:
+//!
+//! ```text
+//! engineering = raw * scale + offset
+//! ```
+//!
+//! When `unit_metadata` is enabled in the device config, the metadata is stored on the node in the
+//! unit metadata object (0x5F03), so that a generic tool can display engineering values without
+//! access to the device config. This module defines the encoding of that object.
+//!
+//! # Encoding
+//!
+//! The object contains a version byte (currently 1), followed by an entry for each sub object which
+//! has metadata. All values are little endian.
+//!
+//! | Field  | Type        | Description |
+//! | ------ | ----------- | ----------- |
+//! | index  | u16         | Object index |
+//! | sub    | u8          | Sub index |
+//! | scale  | f64         | Scale factor |
+//! | offset | f64         | Offset, added after scaling |
+//! | len    | u8          | Length of the unit string |
+//! | unit   | \[u8; len\] | UTF-8 unit string |
+
+use snafu::Snafu;
+
+/// The version of the encoding written by [`encode_table`]
+pub const UNIT_METADATA_VERSION: u8 = 1;
+
+/// The size of an encoded entry, excluding the unit string
+const ENTRY_HEADER_SIZE: usize = 2 + 1 + 8 + 8 + 1;
+
+/// The engineering unit, scale and offset of a sub object
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnitMetadata {
+    /// The unit of the engineering value, e.g. "mV"
+    pub unit: String,
+    /// The factor by which the raw value is multiplied
+    pub scale: f64,
+    /// The offset added to the scaled value
+    pub offset: f64,
+}
+
+impl Default for UnitMetadata {
+    fn default() -> Self {
+        Self {
+            unit: String::new(),
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl UnitMetadata {
+    /// Convert a raw object value to an engineering value
+    pub fn to_engineering(&self, raw: f64) -> f64 {
+        raw * self.scale + self.offset
+    }
+
+    /// Convert an engineering value to a raw object value
+    ///
+    /// The result is not rounded, so it must be rounded before being written to an integer object.
+    pub fn to_raw(&self, engineering: f64) -> f64 {
+        (engineering - self.offset) / self.scale
+    }
+}
+
+/// Error returned when a unit metadata table cannot be decoded
+#[derive(Clone, Copy, Debug, PartialEq, Snafu)]
+pub enum DecodeError {
+    /// The table was written with an unsupported encoding version
+    #[snafu(display("Unsupported unit metadata version {version}"))]
+    UnsupportedVersion {
+        /// The version byte read from the table
+        version: u8,
+    },
+    /// The table ends part way through an entry
+    #[snafu(display("Unit metadata table is truncated"))]
+    Truncated,
+    /// A unit string is not valid UTF-8
+    #[snafu(display("Invalid unit string for object 0x{index:X}sub{sub}"))]
+    InvalidUnit {
+        /// Object index of the entry
+        index: u16,
+        /// Sub index of the entry
+        sub: u8,
+    },
+}
+
+/// Encode a table of unit metadata, as stored in the unit metadata object
+///
+/// # Panics
+///
+/// Panics if a unit string is longer than 255 bytes
+pub fn encode_table<'a>(entries: impl IntoIterator<Item = (u16, u8, &'a UnitMetadata)>) -> Vec<u8> {
+    let mut table = vec![UNIT_METADATA_VERSION];
+    for (index, sub, meta) in entries {
+        let unit_len: u8 = meta
+            .unit
+            .len()
+            .try_into()
+            .expect("Unit string must be at most 255 bytes");
+        table.extend_from_slice(&index.to_le_bytes());
+        table.push(sub);
+        table.extend_from_slice(&meta.scale.to_le_bytes());
+        table.extend_from_slice(&meta.offset.to_le_bytes());
+        table.push(unit_len);
+        table.extend_from_slice(meta.unit.as_bytes());
+    }
+    table
+}
+
+/// Decode a table of unit metadata, read from the unit metadata object
+///
+/// Returns a list of `(index, sub, metadata)` entries. An empty table is accepted, and returns no
+/// entries.
+pub fn decode_table(data: &[u8]) -> Result<Vec<(u16, u8, UnitMetadata)>, DecodeError> {
+    let Some((&version, mut data)) = data.split_first() else {
+        return Ok(Vec::new());
+    };
+    if version != UNIT_METADATA_VERSION {
+        return UnsupportedVersionSnafu { version }.fail();
+    }
+
+    let mut entries = Vec::new();
+    while !data.is_empty() {
+        if data.len() < ENTRY_HEADER_SIZE {
+            return TruncatedSnafu.fail();
+        }
+        let index = u16::from_le_bytes(data[0..2].try_into().unwrap());
+        let sub = data[2];
+        let scale = f64::from_le_bytes(data[3..11].try_into().unwrap());
+        let offset = f64::from_le_bytes(data[11..19].try_into().unwrap());
+        let unit_len = data[19] as usize;
+        let unit = data
+            .get(ENTRY_HEADER_SIZE..ENTRY_HEADER_SIZE + unit_len)
+            .ok_or(DecodeError::Truncated)?;
+        let unit = core::str::from_utf8(unit)
+            .map_err(|_| DecodeError::InvalidUnit { index, sub })?
+            .to_string();
+        entries.push((
+            index,
+            sub,
+            UnitMetadata {
+                unit,
+                scale,
+                offset,
+            },
+        ));
+        data = &data[ENTRY_HEADER_SIZE + unit_len..];
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let millivolts = UnitMetadata {
+            unit: "mV".into(),
+            scale: 0.001,
+            offset: 0.0,
+        };
+        let celsius = UnitMetadata {
+            unit: "°C".into(),
+            scale: 0.1,
+            offset: -40.0,
+        };
+        let table = encode_table([(0x2000, 1, &millivolts), (0x3001, 0, &celsius)]);
+        assert_eq!(
+            Ok(vec![(0x2000, 1, millivolts), (0x3001, 0, celsius.clone())]),
+            decode_table(&table)
+        );
+
+        assert_eq!(Ok(vec![]), decode_table(&[]));
+        assert_eq!(Ok(vec![]), decode_table(&encode_table([])));
+        assert_eq!(
+            Err(DecodeError::Truncated),
+            decode_table(&table[..table.len() - 1])
+        );
+        assert_eq!(
+            Err(DecodeError::UnsupportedVersion { version: 2 }),
+            decode_table(&[2])
+        );
+
+        assert_eq!(210.0, celsius.to_engineering(2500.0));
+        assert_eq!(2500.0, celsius.to_raw(210.0));
+    }
+}
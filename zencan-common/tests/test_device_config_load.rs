@@ -34,6 +34,23 @@ fn test_system_defaults() {
             { index=0x2001, sub=1, size=8 },
             { index=0x2001, sub=2, size=8 },
         ]
+
+        [[objects]]
+        index = 0x2000
+        parameter_name = "Input"
+        object_type = "var"
+        data_type = "uint16"
+        access_type = "ro"
+        pdo_mapping = "tpdo"
+
+        [[objects]]
+        index = 0x2001
+        parameter_name = "Outputs"
+        object_type = "array"
+        data_type = "uint8"
+        access_type = "rw"
+        array_size = 2
+        pdo_mapping = "rpdo"
     "#;
 
     let cfg = DeviceConfig::load_from_str(DEVCFG).expect("Failed to parse device config");
@@ -51,7 +68,10 @@ fn test_system_defaults() {
                 sub: 0,
                 size: 16
             }],
-            transmission_type: 254
+            transmission_type: 254,
+            sync_start: 0,
+            event_timer: 0,
+            inhibit_time: 0,
         }
     );
 
@@ -75,7 +95,10 @@ fn test_system_defaults() {
                     size: 8
                 }
             ],
-            transmission_type: 0
+            transmission_type: 0,
+            sync_start: 0,
+            event_timer: 0,
+            inhibit_time: 0,
         }
     );
 }
@@ -0,0 +1,385 @@
+//! Creation of EDS files from zencan device configs, and serialization of EDS files
+use std::{collections::BTreeMap, path::Path};
+
+use ini::Ini;
+use zencan_common::{
+    device_config::{
+        ConnectionSetConfig, DefaultValue, DeviceConfig, HeartbeatConsumerDefault,
+        Object as DcObject, ObjectDefinition,
+    },
+    objects::{AccessType, DataType, ObjectCode, PdoMappable},
+};
+
+use crate::{Comments, DeviceInfo, DummyUsage, ElectronicDataSheet, FileInfo, Object, SubObject};
+
+fn format_default_value(value: &DefaultValue) -> String {
+    match value {
+        DefaultValue::Integer(i) if *i >= 0 => format!("0x{:X}", i),
+        DefaultValue::Integer(i) => i.to_string(),
+        DefaultValue::Float(f) => f.to_string(),
+        DefaultValue::String(s) => s.clone(),
+    }
+}
+
+fn format_access_type(access_type: AccessType) -> &'static str {
+    match access_type {
+        AccessType::Ro => "ro",
+        AccessType::Wo => "wo",
+        AccessType::Rw => "rw",
+        AccessType::Const => "const",
+    }
+}
+
+fn format_bool(value: bool) -> &'static str {
+    if value {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+/// Create the sub0 entry of an array or record, holding the highest sub index
+fn max_sub_subobject(max_sub: u8) -> SubObject {
+    SubObject {
+        parameter_name: "Highest sub-index supported".to_string(),
+        data_type: DataType::UInt8,
+        access_type: AccessType::Const,
+        default_value: Some(format!("0x{:X}", max_sub)),
+        pdo_mapping: Some(false),
+        ..Default::default()
+    }
+}
+
+fn convert_object(def: &ObjectDefinition) -> Object {
+    let mut subs = BTreeMap::new();
+    let object_code = match &def.object {
+        DcObject::Var(var) => {
+            subs.insert(
+                0,
+                SubObject {
+                    parameter_name: def.parameter_name.clone(),
+                    data_type: var.data_type.into(),
+                    access_type: var.access_type.0,
+                    default_value: var.default_value.as_ref().map(format_default_value),
+                    pdo_mapping: Some(var.pdo_mapping != PdoMappable::None),
+                    ..Default::default()
+                },
+            );
+            ObjectCode::Var
+        }
+        DcObject::Array(array) => {
+            let mut max_sub = max_sub_subobject(array.array_size as u8);
+            if array.resizable {
+                max_sub.access_type = AccessType::Rw;
+            }
+            subs.insert(0, max_sub);
+            for i in 0..array.array_size {
+                let default_value = array
+                    .default_value
+                    .as_ref()
+                    .and_then(|values| values.get(i))
+                    .map(format_default_value);
+                subs.insert(
+                    (i + 1) as u8,
+                    SubObject {
+                        parameter_name: format!("{} {}", def.parameter_name, i + 1),
+                        data_type: array.data_type.into(),
+                        access_type: array.access_type.0,
+                        default_value,
+                        pdo_mapping: Some(array.pdo_mapping != PdoMappable::None),
+                        ..Default::default()
+                    },
+                );
+            }
+            ObjectCode::Array
+        }
+        DcObject::Record(record) => {
+            let max_sub = record.subs.iter().map(|s| s.sub_index).max().unwrap_or(0);
+            subs.insert(0, max_sub_subobject(max_sub));
+            for sub in &record.subs {
+                subs.insert(
+                    sub.sub_index,
+                    SubObject {
+                        parameter_name: sub.parameter_name.clone(),
+                        data_type: sub.data_type.into(),
+                        access_type: sub.access_type.0,
+                        default_value: sub.default_value.as_ref().map(format_default_value),
+                        pdo_mapping: Some(sub.pdo_mapping != PdoMappable::None),
+                        ..Default::default()
+                    },
+                );
+            }
+            ObjectCode::Record
+        }
+    };
+
+    let sub_number = match object_code {
+        ObjectCode::Var => 0,
+        _ => subs.len() as u8,
+    };
+
+    Object {
+        parameter_name: def.parameter_name.clone(),
+        object_number: def.index,
+        object_code,
+        sub_number,
+        subs,
+    }
+}
+
+/// Fill in the default values of the Consumer Heartbeat Time object (0x1016)
+///
+/// The node implements this object itself, so the defaults are not part of its definition
+fn set_heartbeat_consumer_defaults(object: &mut Object, defaults: &[HeartbeatConsumerDefault]) {
+    for (sub_index, sub) in object.subs.iter_mut().filter(|(i, _)| **i != 0) {
+        let value = defaults
+            .get(*sub_index as usize - 1)
+            .map(|d| ((d.node_id as u32) << 16) | d.time as u32)
+            .unwrap_or(0);
+        sub.default_value = Some(format!("0x{:X}", value));
+    }
+}
+
+impl DeviceInfo {
+    /// Mark a baud rate, in kbit/s, as supported
+    ///
+    /// Returns false if the baud rate is not one of those listed in an EDS
+    pub fn set_baud_rate_supported(&mut self, kbps: u32) -> bool {
+        let flag = match kbps {
+            10 => &mut self.baudrate_10,
+            20 => &mut self.baudrate_20,
+            50 => &mut self.baudrate_50,
+            125 => &mut self.baudrate_125,
+            250 => &mut self.baudrate_250,
+            500 => &mut self.baudrate_500,
+            800 => &mut self.baudrate_800,
+            1000 => &mut self.baudrate_1000,
+            _ => return false,
+        };
+        *flag = true;
+        true
+    }
+}
+
+impl ElectronicDataSheet {
+    /// Create an EDS describing a node built from the given device config
+    ///
+    /// The device config should be loaded with [`DeviceConfig::load`], so that it includes the
+    /// standard objects which zencan creates on every node.
+    ///
+    /// The services of the node are described by the `[DeviceInfo]` section (boot-up slave, LSS
+    /// slave and the number of PDOs), and its timing parameters by the default values of the
+    /// objects which hold them, e.g. the heartbeat producer time (0x1017) and consumer heartbeat
+    /// times (0x1016). EDS has no entry for COB ID base values, so a connection set other than the
+    /// CiA 301 default is only noted in the comments.
+    ///
+    /// The supported baud rates depend on the hardware, and are left unset; they should be filled
+    /// in on the returned value before writing it out.
+    pub fn from_device_config(config: &DeviceConfig) -> Self {
+        let mut eds = ElectronicDataSheet {
+            file_info: FileInfo {
+                file_name: format!("{}.eds", config.device_name),
+                file_version: 1,
+                file_revision: 0,
+                eds_version: "4.0".to_string(),
+                description: config.device_name.clone(),
+                created_by: "zencan".to_string(),
+                modified_by: "zencan".to_string(),
+                ..Default::default()
+            },
+            device_info: DeviceInfo {
+                product_name: config.device_name.clone(),
+                vendor_number: Some(config.identity.vendor_id),
+                product_number: Some(config.identity.product_code),
+                revision_number: Some(config.identity.revision_number),
+                simple_boot_up_slave: true,
+                granularity: 8,
+                rpdo_count: config.pdos.num_rpdo as u16,
+                tpdo_count: config.pdos.num_tpdo as u16,
+                lss_supported: true,
+                ..Default::default()
+            },
+            dummy_usage: DummyUsage {
+                values: BTreeMap::from_iter((1..=7).map(|i| (DataType::from(i), false))),
+            },
+            comments: Comments {
+                lines: vec![
+                    format!("Software version: {}", config.software_version),
+                    format!("Hardware version: {}", config.hardware_version),
+                ],
+            },
+            ..Default::default()
+        };
+
+        if config.connection_set != ConnectionSetConfig::default() {
+            let set = &config.connection_set;
+            eds.comments.lines.push(format!(
+                "Connection set: SDO request 0x{:X}, SDO response 0x{:X}, heartbeat 0x{:X}, EMCY 0x{:X}",
+                set.sdo_request_base, set.sdo_response_base, set.heartbeat_base, set.emcy_base
+            ));
+        }
+
+        let mut objects = Vec::from_iter(config.objects.iter().map(convert_object));
+        objects.sort_by_key(|obj| obj.object_number);
+        if let Some(consumers) = objects.iter_mut().find(|obj| obj.object_number == 0x1016) {
+            set_heartbeat_consumer_defaults(consumers, &config.heartbeat_consumers.defaults);
+        }
+        for obj in objects {
+            match obj.object_number {
+                0x1000 | 0x1001 | 0x1018 => eds.mandatory_objects.push(obj),
+                0x2000..=0x5FFF => eds.manufacturer_objects.push(obj),
+                _ => eds.optional_objects.push(obj),
+            }
+        }
+        eds
+    }
+
+    /// Convert the EDS into its INI representation
+    pub fn to_ini(&self) -> Ini {
+        let mut ini = Ini::new();
+
+        let file_info = &self.file_info;
+        ini.with_section(Some("FileInfo"))
+            .set("FileName", &file_info.file_name)
+            .set("FileVersion", file_info.file_version.to_string())
+            .set("FileRevision", file_info.file_revision.to_string())
+            .set("EDSVersion", &file_info.eds_version)
+            .set("Description", &file_info.description)
+            .set("CreationTime", &file_info.creation_time)
+            .set("CreationDate", &file_info.creation_date)
+            .set("CreatedBy", &file_info.created_by)
+            .set("ModificationTime", &file_info.modification_time)
+            .set("ModificationDate", &file_info.modification_date)
+            .set("ModifiedBy", &file_info.modified_by);
+
+        let device_info = &self.device_info;
+        let format_opt_u32 = |v: Option<u32>| v.map(|v| format!("0x{:08X}", v)).unwrap_or_default();
+        ini.with_section(Some("DeviceInfo"))
+            .set("VendorName", &device_info.vendor_name)
+            .set("VendorNumber", format_opt_u32(device_info.vendor_number))
+            .set("ProductName", &device_info.product_name)
+            .set("ProductNumber", format_opt_u32(device_info.product_number))
+            .set(
+                "RevisionNumber",
+                format_opt_u32(device_info.revision_number),
+            )
+            .set("OrderCode", &device_info.order_code)
+            .set("BaudRate_10", format_bool(device_info.baudrate_10))
+            .set("BaudRate_20", format_bool(device_info.baudrate_20))
+            .set("BaudRate_50", format_bool(device_info.baudrate_50))
+            .set("BaudRate_125", format_bool(device_info.baudrate_125))
+            .set("BaudRate_250", format_bool(device_info.baudrate_250))
+            .set("BaudRate_500", format_bool(device_info.baudrate_500))
+            .set("BaudRate_800", format_bool(device_info.baudrate_800))
+            .set("BaudRate_1000", format_bool(device_info.baudrate_1000))
+            .set(
+                "SimpleBootUpMaster",
+                format_bool(device_info.simple_boot_up_master),
+            )
+            .set(
+                "SimpleBootUpSlave",
+                format_bool(device_info.simple_boot_up_slave),
+            )
+            .set("Granularity", device_info.granularity.to_string())
+            .set(
+                "DynamicChannelsSupported",
+                device_info.dynamic_channels_supported.to_string(),
+            )
+            .set("CompactPDO", "0")
+            .set("GroupMessaging", format_bool(device_info.group_messaging))
+            .set("NrOfRXPDO", device_info.rpdo_count.to_string())
+            .set("NrOfTXPDO", device_info.tpdo_count.to_string())
+            .set("LSS_Supported", format_bool(device_info.lss_supported));
+
+        let mut dummy_usage = ini.with_section(Some("DummyUsage"));
+        for (data_type, supported) in &self.dummy_usage.values {
+            dummy_usage.set(
+                format!("Dummy{:04X}", u16::from(*data_type)),
+                format_bool(*supported),
+            );
+        }
+
+        let mut comments = ini.with_section(Some("Comments"));
+        comments.set("Lines", self.comments.lines.len().to_string());
+        for (i, line) in self.comments.lines.iter().enumerate() {
+            comments.set(format!("Line{}", i + 1), line);
+        }
+
+        write_objects(&mut ini, "MandatoryObjects", &self.mandatory_objects);
+        write_objects(&mut ini, "OptionalObjects", &self.optional_objects);
+        write_objects(&mut ini, "ManufacturerObjects", &self.manufacturer_objects);
+
+        ini
+    }
+
+    /// Write the EDS to a file
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        self.to_ini().write_to_file(path)
+    }
+}
+
+impl std::fmt::Display for ElectronicDataSheet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Vec::new();
+        self.to_ini()
+            .write_to(&mut buf)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&buf))
+    }
+}
+
+fn write_objects(ini: &mut Ini, list_name: &str, objects: &[Object]) {
+    let mut list = ini.with_section(Some(list_name));
+    list.set("SupportedObjects", objects.len().to_string());
+    for (i, obj) in objects.iter().enumerate() {
+        list.set((i + 1).to_string(), format!("0x{:04X}", obj.object_number));
+    }
+
+    for obj in objects {
+        let section_name = format!("{:X}", obj.object_number);
+        match obj.object_code {
+            ObjectCode::Var => {
+                ini.with_section(Some(section_name.as_str()))
+                    .set("ParameterName", &obj.parameter_name)
+                    .set("ObjectType", "0x7");
+                if let Some(sub) = obj.subs.get(&0) {
+                    write_subobject(ini, &section_name, &obj.parameter_name, sub);
+                }
+            }
+            _ => {
+                ini.with_section(Some(section_name.as_str()))
+                    .set("ParameterName", &obj.parameter_name)
+                    .set("ObjectType", format!("0x{:X}", obj.object_code as u8))
+                    .set("SubNumber", obj.sub_number.to_string());
+                for (sub_index, sub) in &obj.subs {
+                    write_subobject(
+                        ini,
+                        &format!("{}sub{:X}", section_name, sub_index),
+                        &sub.parameter_name,
+                        sub,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn write_subobject(ini: &mut Ini, section_name: &str, parameter_name: &str, sub: &SubObject) {
+    let mut section = ini.with_section(Some(section_name));
+    section
+        .set("ParameterName", parameter_name)
+        .set("DataType", format!("0x{:04X}", u16::from(sub.data_type)))
+        .set("AccessType", format_access_type(sub.access_type));
+    if let Some(low_limit) = &sub.low_limit {
+        section.set("LowLimit", low_limit);
+    }
+    if let Some(high_limit) = &sub.high_limit {
+        section.set("HighLimit", high_limit);
+    }
+    if let Some(default_value) = &sub.default_value {
+        section.set("DefaultValue", default_value);
+    }
+    if let Some(pdo_mapping) = sub.pdo_mapping {
+        section.set("PDOMapping", format_bool(pdo_mapping));
+    }
+}
@@ -4,6 +4,8 @@ use std::{collections::BTreeMap, path::Path, str::FromStr};
 
 use zencan_common::objects::{AccessType, DataType, ObjectCode};
 
+mod export;
+
 #[derive(Debug, Snafu)]
 pub enum LoadError {
     IniFormatError {
@@ -185,7 +187,9 @@ impl Object {
                     }
                     .build(),
                 )?;
-                if *last_subindex != highest_subindex {
+                // A writable sub0 holds a count of valid entries (e.g. PDO mapping parameters)
+                // rather than the highest sub index, so it is only checked when read-only
+                if !first_subobj.access_type.is_writable() && *last_subindex != highest_subindex {
                     return EdsFormatSnafu {
                         message: format!(
                             "Invalid subindex for last subobject of '[{:X}]': expected highest subindex {}, found {}",
@@ -773,6 +777,191 @@ SupportedObjects=0
         println!("{:#?}", eds);
     }
 
+    #[test]
+    fn test_device_config_round_trip() {
+        let config = zencan_common::device_config::DeviceConfig::load_from_str(
+            r#"
+            device_name = "Test Device"
+            software_version = "v1.0"
+            hardware_version = "A"
+            heartbeat_period = 500
+
+            [identity]
+            vendor_id = 0x1234
+            product_code = 12
+            revision_number = 3
+
+            [pdos]
+            num_rpdo = 2
+            num_tpdo = 3
+
+            [[objects]]
+            index = 0x2000
+            parameter_name = "Measurement"
+            object_type = "var"
+            access_type = "ro"
+            data_type = "int16"
+            default_value = -5
+            pdo_mapping = "tpdo"
+
+            [[objects]]
+            index = 0x2001
+            parameter_name = "Array"
+            object_type = "array"
+            access_type = "rw"
+            data_type = "uint32"
+            array_size = 2
+            default_value = [1, 2]
+            resizable = true
+
+            [[objects]]
+            index = 0x2002
+            parameter_name = "Sparse Record"
+            object_type = "record"
+            [[objects.subs]]
+            sub_index = 1
+            parameter_name = "first"
+            data_type = "uint8"
+            access_type = "rw"
+            [[objects.subs]]
+            sub_index = 4
+            parameter_name = "fourth"
+            data_type = "visiblestring(8)"
+            access_type = "ro"
+            default_value = "abc"
+        "#,
+        )
+        .unwrap();
+
+        let eds = ElectronicDataSheet::from_device_config(&config);
+        let eds_str = eds.to_string();
+        println!("{}", eds_str);
+        let parsed = ElectronicDataSheet::from_str(&eds_str).unwrap();
+
+        assert_eq!(parsed.device_info.product_name, "Test Device");
+        assert_eq!(parsed.device_info.vendor_number, Some(0x1234));
+        assert_eq!(parsed.device_info.product_number, Some(12));
+        assert_eq!(parsed.device_info.revision_number, Some(3));
+        assert_eq!(parsed.device_info.rpdo_count, 2);
+        assert_eq!(parsed.device_info.tpdo_count, 3);
+        assert!(parsed.device_info.lss_supported);
+
+        let mandatory: Vec<u16> = parsed
+            .mandatory_objects
+            .iter()
+            .map(|o| o.object_number)
+            .collect();
+        assert_eq!(vec![0x1000, 0x1001, 0x1018], mandatory);
+        assert_eq!(eds.optional_objects.len(), parsed.optional_objects.len());
+        // Heartbeat producer time should carry the configured default
+        let heartbeat = parsed
+            .optional_objects
+            .iter()
+            .find(|o| o.object_number == 0x1017)
+            .unwrap();
+        assert_eq!(
+            500,
+            heartbeat.subs[&0]
+                .default_value
+                .as_ref()
+                .unwrap()
+                .parse_hex()
+                .unwrap()
+        );
+
+        let find_manufacturer = |index| {
+            parsed
+                .manufacturer_objects
+                .iter()
+                .find(|o| o.object_number == index)
+                .unwrap()
+        };
+
+        let var = find_manufacturer(0x2000);
+        assert_eq!(ObjectCode::Var, var.object_code);
+        assert_eq!(DataType::Int16, var.subs[&0].data_type);
+        assert_eq!(AccessType::Ro, var.subs[&0].access_type);
+        assert_eq!(Some("-5".to_string()), var.subs[&0].default_value);
+        assert_eq!(Some(true), var.subs[&0].pdo_mapping);
+
+        let array = find_manufacturer(0x2001);
+        assert_eq!(ObjectCode::Array, array.object_code);
+        assert_eq!(3, array.sub_number);
+        // The length of a resizable array is writable
+        assert_eq!(AccessType::Rw, array.subs[&0].access_type);
+        assert_eq!(DataType::UInt32, array.subs[&2].data_type);
+        assert_eq!(Some("0x2".to_string()), array.subs[&2].default_value);
+
+        let record = find_manufacturer(0x2002);
+        assert_eq!(ObjectCode::Record, record.object_code);
+        assert_eq!(3, record.sub_number);
+        assert_eq!(Some("0x4".to_string()), record.subs[&0].default_value);
+        assert_eq!(DataType::VisibleString, record.subs[&4].data_type);
+        assert_eq!(Some("abc".to_string()), record.subs[&4].default_value);
+    }
+
+    #[test]
+    fn test_device_config_timing() {
+        let config = zencan_common::device_config::DeviceConfig::load_from_str(
+            r#"
+            device_name = "Timing Device"
+            software_version = "v1.0"
+            hardware_version = "A"
+            heartbeat_period = 250
+
+            [identity]
+            vendor_id = 0x1234
+            product_code = 12
+            revision_number = 3
+
+            [pdos]
+            num_rpdo = 1
+            num_tpdo = 1
+
+            [heartbeat_consumers]
+            count = 2
+            defaults = [{ node_id = 3, time = 1500 }]
+
+            [connection_set]
+            heartbeat_base = 0x680
+        "#,
+        )
+        .unwrap();
+
+        let eds = ElectronicDataSheet::from_device_config(&config);
+        let parsed = ElectronicDataSheet::from_str(&eds.to_string()).unwrap();
+        assert!(parsed.device_info.simple_boot_up_slave);
+        assert!(parsed.device_info.lss_supported);
+
+        let find_optional = |index| {
+            parsed
+                .optional_objects
+                .iter()
+                .find(|o| o.object_number == index)
+                .unwrap()
+        };
+        let default = |obj: &Object, sub| {
+            obj.subs[&sub]
+                .default_value
+                .as_ref()
+                .unwrap()
+                .parse_hex()
+                .unwrap()
+        };
+        assert_eq!(250, default(find_optional(0x1017), 0));
+        // Consumer entries hold the node ID in bits 16-23 and the time in bits 0-15
+        let consumers = find_optional(0x1016);
+        assert_eq!(2, default(consumers, 0));
+        assert_eq!(3 << 16 | 1500, default(consumers, 1));
+        assert_eq!(0, default(consumers, 2));
+
+        assert!(parsed
+            .comments
+            .lines
+            .iter()
+            .any(|line| line.contains("heartbeat 0x680")));
+    }
+
     #[test]
     fn test_unsupported_obj() {
         let s = "
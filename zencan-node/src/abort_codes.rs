@@ -0,0 +1,60 @@
+//! Selection of SDO abort codes
+//!
+//! Some conditions have historically been reported with an approximate abort code, most often
+//! [`AbortCode::GeneralError`]. When the `strict-abort-codes` feature is enabled, the specific code
+//! required by CiA 301 is used instead. This is opt-in, because existing clients may rely on the
+//! approximate codes.
+//!
+//! Writes of the wrong length to fixed size sub objects are rejected with
+//! [`AbortCode::DataTypeMismatchLengthLow`] or [`AbortCode::DataTypeMismatchLengthHigh`], like
+//! writes to regular objects. The exceptions are the PDO mapping entries and the save command,
+//! which report [`AbortCode::DataTypeMismatch`] unless `strict-abort-codes` is enabled. Note that
+//! the one byte PDO config sub objects reject writes longer than one byte, where the extra bytes
+//! used to be ignored.
+use zencan_common::sdo::AbortCode;
+
+/// Returned when an object cannot be written in the current NMT state
+pub(crate) const DEVICE_STATE: AbortCode = if cfg!(feature = "strict-abort-codes") {
+    AbortCode::CantStoreDeviceState
+} else {
+    AbortCode::GeneralError
+};
+
+/// Returned when the application failed to complete an access, e.g. a flash erase
+pub(crate) const HARDWARE_ERROR: AbortCode = if cfg!(feature = "strict-abort-codes") {
+    AbortCode::HardwareError
+} else {
+    AbortCode::GeneralError
+};
+
+/// Returned when the signature written to the save command object (0x1010) is not recognized
+pub(crate) const WRONG_SIGNATURE: AbortCode = if cfg!(feature = "strict-abort-codes") {
+    AbortCode::CantStore
+} else {
+    AbortCode::IncompatibleParameter
+};
+
+/// Check that the length of written data matches the size of a fixed size sub object
+pub(crate) fn check_write_len(data: &[u8], size: usize) -> Result<(), AbortCode> {
+    if data.len() < size {
+        Err(AbortCode::DataTypeMismatchLengthLow)
+    } else if data.len() > size {
+        Err(AbortCode::DataTypeMismatchLengthHigh)
+    } else {
+        Ok(())
+    }
+}
+
+/// Check the length of data written to a sub object which has historically reported any length
+/// mismatch as [`AbortCode::DataTypeMismatch`]
+///
+/// With `strict-abort-codes`, this is the same as [`check_write_len`].
+pub(crate) fn check_legacy_write_len(data: &[u8], size: usize) -> Result<(), AbortCode> {
+    if cfg!(feature = "strict-abort-codes") {
+        check_write_len(data, size)
+    } else if data.len() != size {
+        Err(AbortCode::DataTypeMismatch)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+//! Per-object SDO access counters
+//!
+//! When profiling a system, it is useful to know which objects the masters on the bus read and
+//! write most frequently. Objects which are polled via SDO many times a second are good candidates
+//! to be moved into a PDO instead. With the `access-stats` feature, the node can count the SDO
+//! transfers made to each object in its object dictionary.
+//!
+//! The counters are statically allocated by the application, one per entry in the object
+//! dictionary, and registered with [`Node::set_access_counters`](crate::Node::set_access_counters).
+//! The generated `OD_TABLE_LEN` constant can be used to size them.
+//!
+//! ```ignore
+//! use zencan_node::access_stats::{AccessStats, ObjectAccessCounter};
+//!
+//! static ACCESS_COUNTERS: [ObjectAccessCounter; zencan::OD_TABLE_LEN] =
+//!     [const { ObjectAccessCounter::new() }; zencan::OD_TABLE_LEN];
+//!
+//! node.set_access_counters(Some(&ACCESS_COUNTERS));
+//!
+//! // Later, possibly from another task
+//! let stats = AccessStats::new(&zencan::OD_TABLE, &ACCESS_COUNTERS);
+//! for (index, counter) in stats.iter().filter(|(_, c)| c.reads() > 100) {
+//!     info!("Object {:x} was read {} times", index, counter.reads());
+//! }
+//! ```
+//!
+//! Every SDO transfer is counted once when it finishes, whether it succeeded or was aborted. The
+//! counters saturate at `u16::MAX`.
+
+use zencan_common::AtomicCell;
+
+use crate::{
+    object_dict::ODEntry,
+    sdo_server::{SdoAccess, SdoAccessKind},
+};
+
+/// Read and write counts for a single object
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct ObjectAccessCounter {
+    reads: AtomicCell<u16>,
+    writes: AtomicCell<u16>,
+}
+
+impl ObjectAccessCounter {
+    /// Create a new counter
+    pub const fn new() -> Self {
+        Self {
+            reads: AtomicCell::new(0),
+            writes: AtomicCell::new(0),
+        }
+    }
+
+    /// Get the number of SDO uploads of the object
+    pub fn reads(&self) -> u16 {
+        self.reads.load()
+    }
+
+    /// Get the number of SDO downloads to the object
+    pub fn writes(&self) -> u16 {
+        self.writes.load()
+    }
+
+    /// Reset both counts to zero
+    pub fn reset(&self) {
+        self.reads.store(0);
+        self.writes.store(0);
+    }
+
+    fn record(&self, kind: SdoAccessKind) {
+        let counter = match kind {
+            SdoAccessKind::Read => &self.reads,
+            SdoAccessKind::Write => &self.writes,
+        };
+        counter.fetch_update(|x| Some(x.saturating_add(1))).ok();
+    }
+}
+
+/// Access counters for each object of an object dictionary
+///
+/// The counter at each position in `counters` belongs to the object at the same position in `od`.
+/// Objects with no counter, because `counters` is shorter than `od`, are not counted.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct AccessStats<'a> {
+    od: &'a [ODEntry<'a>],
+    counters: &'a [ObjectAccessCounter],
+}
+
+impl<'a> AccessStats<'a> {
+    /// Create a view of the counters for an object dictionary
+    pub const fn new(od: &'a [ODEntry<'a>], counters: &'a [ObjectAccessCounter]) -> Self {
+        Self { od, counters }
+    }
+
+    /// Get the counter for an object
+    ///
+    /// Returns None if the object does not exist, or has no counter
+    pub fn get(&self, index: u16) -> Option<&'a ObjectAccessCounter> {
+        let pos = self.od.binary_search_by_key(&index, |e| e.index).ok()?;
+        self.counters.get(pos)
+    }
+
+    /// Iterate over the index and counter of every counted object
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &'a ObjectAccessCounter)> + 'a {
+        self.od
+            .iter()
+            .zip(self.counters)
+            .map(|(entry, counter)| (entry.index, counter))
+    }
+
+    /// Reset all counters to zero
+    pub fn reset(&self) {
+        for counter in self.counters {
+            counter.reset();
+        }
+    }
+
+    /// Count a finished SDO transfer
+    pub(crate) fn record(&self, access: &SdoAccess) {
+        if let Some(counter) = self.get(access.index) {
+            counter.record(access.kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::objects::{ObjectCode, SubInfo};
+
+    use super::*;
+    use crate::object_dict::{ProvidesSubObjects, ScalarField, SubObjectAccess};
+
+    struct TestObject {
+        value: ScalarField<u8>,
+    }
+
+    impl ProvidesSubObjects for TestObject {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                0 => Some((SubInfo::new_u8(), &self.value)),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Var
+        }
+    }
+
+    #[test]
+    fn test_access_stats() {
+        let object = Box::leak(Box::new(TestObject {
+            value: ScalarField::<u8>::new(0),
+        }));
+        let od = Box::leak(Box::new([
+            ODEntry {
+                index: 0x2000,
+                data: object,
+            },
+            ODEntry {
+                index: 0x2001,
+                data: object,
+            },
+            ODEntry {
+                index: 0x2002,
+                data: object,
+            },
+        ]));
+        // The last object has no counter
+        let counters = [const { ObjectAccessCounter::new() }; 2];
+        let stats = AccessStats::new(od, &counters);
+
+        let access = |kind, index| SdoAccess {
+            kind,
+            index,
+            sub: 0,
+            len: 1,
+            result: Ok(()),
+        };
+        stats.record(&access(SdoAccessKind::Read, 0x2000));
+        stats.record(&access(SdoAccessKind::Read, 0x2000));
+        stats.record(&access(SdoAccessKind::Write, 0x2001));
+        stats.record(&access(SdoAccessKind::Write, 0x2002));
+        stats.record(&access(SdoAccessKind::Write, 0x3000));
+
+        assert_eq!(2, stats.get(0x2000).unwrap().reads());
+        assert_eq!(0, stats.get(0x2000).unwrap().writes());
+        assert_eq!(1, stats.get(0x2001).unwrap().writes());
+        assert!(stats.get(0x2002).is_none());
+        assert_eq!(
+            vec![(0x2000, 2, 0), (0x2001, 0, 1)],
+            stats
+                .iter()
+                .map(|(index, c)| (index, c.reads(), c.writes()))
+                .collect::<Vec<_>>()
+        );
+
+        // Counts saturate
+        for _ in 0..u16::MAX {
+            stats.record(&access(SdoAccessKind::Read, 0x2000));
+        }
+        assert_eq!(u16::MAX, stats.get(0x2000).unwrap().reads());
+
+        stats.reset();
+        assert!(stats.iter().all(|(_, c)| c.reads() == 0 && c.writes() == 0));
+    }
+}
@@ -0,0 +1,251 @@
+//! Async wrapper for running a [`Node`] on the embassy executor
+//!
+//! [`AsyncNode`] owns the process loop: it waits until either the [`NodeMbox`] signals that a
+//! message needs processing, or the process period has elapsed, and then calls
+//! [`Node::process`]. Events produced by processing can optionally be delivered to the application
+//! through an embassy [`Channel`].
+//!
+//! ```ignore
+//! static NODE_EVENTS: NodeEventChannel<4> = Channel::new();
+//!
+//! #[embassy_executor::task]
+//! async fn canopen_process_task() {
+//!     let node = Node::new(
+//!         NodeId::Unconfigured,
+//!         Callbacks::default(),
+//!         &zencan::NODE_MBOX,
+//!         &zencan::NODE_STATE,
+//!         &zencan::OD_TABLE,
+//!     );
+//!     AsyncNode::new(node, &zencan::NODE_MBOX)
+//!         .with_events(NODE_EVENTS.sender().into())
+//!         .run()
+//!         .await
+//! }
+//!
+//! #[embassy_executor::task]
+//! async fn app_task() {
+//!     loop {
+//!         match NODE_EVENTS.receive().await {
+//!             NodeEvent::ObjectsUpdated => { /* Read new configuration */ }
+//!             NodeEvent::NmtStateChanged(state) => { /* Start or stop the application */ }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`AsyncNode::new`] delivers mailbox notifications through a single static signal, so it can only
+//! be used for one node in a program. Devices which run several nodes, e.g. one per CAN bus, should
+//! give each node its own [`ProcessSignal`] with [`AsyncNode::with_signal`]:
+//!
+//! ```ignore
+//! static CH1_SIGNAL: ProcessSignal = ProcessSignal::new();
+//! static CH2_SIGNAL: ProcessSignal = ProcessSignal::new();
+//!
+//! let ch1 = AsyncNode::with_signal(ch1_node, &ch1::NODE_MBOX, &CH1_SIGNAL);
+//! let ch2 = AsyncNode::with_signal(ch2_node, &ch2::NODE_MBOX, &CH2_SIGNAL);
+//! ```
+
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    channel::{Channel, DynamicSender},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer};
+use zencan_common::nmt::NmtState;
+
+use crate::{notify::NotifyCallback, Node, NodeMbox};
+
+/// The default maximum time between calls to [`Node::process`]
+pub const DEFAULT_PROCESS_PERIOD: Duration = Duration::from_millis(10);
+
+/// A signal used to wake an [`AsyncNode`] when its mailbox has messages to process
+pub type ProcessSignal = Signal<CriticalSectionRawMutex, ()>;
+
+/// The signal used by nodes created with [`AsyncNode::new`]
+static PROCESS_SIGNAL: ProcessSignal = Signal::new();
+
+fn notify_process(signal: &ProcessSignal) {
+    signal.signal(());
+}
+
+/// Events reported by an [`AsyncNode`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NodeEvent {
+    /// One or more objects were updated by an SDO download or a received RPDO
+    ObjectsUpdated,
+    /// The node's NMT state changed
+    NmtStateChanged(NmtState),
+}
+
+/// A channel which can be used to receive [`NodeEvent`]s from an [`AsyncNode`]
+pub type NodeEventChannel<const N: usize> = Channel<CriticalSectionRawMutex, NodeEvent, N>;
+
+/// Runs the process loop for a [`Node`] on the embassy executor
+///
+/// See the [module documentation](self) for an example.
+#[allow(missing_debug_implementations)]
+pub struct AsyncNode<'a> {
+    node: Node<'a>,
+    period: Duration,
+    events: Option<DynamicSender<'static, NodeEvent>>,
+    last_nmt_state: NmtState,
+    signal: &'static ProcessSignal,
+}
+
+impl<'a> AsyncNode<'a> {
+    /// Create a new AsyncNode
+    ///
+    /// This registers the process notify callback on `mbox`, replacing any callback previously set
+    /// with [`NodeMbox::set_process_notify_callback`].
+    ///
+    /// All nodes created with `new` share the same signal, so only one of them may be run. Use
+    /// [`with_signal`](Self::with_signal) to run more than one node.
+    pub fn new(node: Node<'a>, mbox: &'static NodeMbox) -> Self {
+        Self::with_signal(node, mbox, &PROCESS_SIGNAL)
+    }
+
+    /// Create a new AsyncNode which is woken by its own signal
+    ///
+    /// Each node in the program must be given a different signal. This registers the process notify
+    /// callback on `mbox`, replacing any callback previously set.
+    pub fn with_signal(
+        node: Node<'a>,
+        mbox: &'static NodeMbox,
+        signal: &'static ProcessSignal,
+    ) -> Self {
+        mbox.set_process_notify_callback(NotifyCallback::with_context(notify_process, signal));
+        #[cfg(feature = "process-timing")]
+        let node = {
+            let mut node = node;
+            node.set_timing_clock(Some(|| Instant::now().as_micros()));
+            node
+        };
+        let last_nmt_state = node.nmt_state();
+        Self {
+            node,
+            period: DEFAULT_PROCESS_PERIOD,
+            events: None,
+            last_nmt_state,
+            signal,
+        }
+    }
+
+    /// Set the maximum time between calls to [`Node::process`]
+    ///
+    /// Process is also called whenever a received message requires processing. The period bounds
+    /// the timing jitter of time-based actions, such as heartbeat transmission.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Deliver [`NodeEvent`]s to a channel
+    ///
+    /// Events are dropped if the channel is full.
+    pub fn with_events(mut self, sender: DynamicSender<'static, NodeEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Access the wrapped node
+    pub fn node(&mut self) -> &mut Node<'a> {
+        &mut self.node
+    }
+
+    /// Call process once, and publish any resulting events
+    ///
+    /// This is called by [`run()`](Self::run), but can be used directly by applications which need
+    /// to run their own loop.
+    pub fn process(&mut self) {
+        let now_us = Instant::now().as_micros();
+        let updated = self.node.process(now_us);
+        if updated {
+            self.send_event(NodeEvent::ObjectsUpdated);
+        }
+        let nmt_state = self.node.nmt_state();
+        if nmt_state != self.last_nmt_state {
+            self.last_nmt_state = nmt_state;
+            self.send_event(NodeEvent::NmtStateChanged(nmt_state));
+        }
+    }
+
+    /// Run the process loop forever
+    pub async fn run(mut self) -> ! {
+        loop {
+            self.process();
+            select(self.signal.wait(), Timer::after(self.period)).await;
+        }
+    }
+
+    fn send_event(&self, event: NodeEvent) {
+        if let Some(sender) = &self.events {
+            sender.try_send(event).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::{block_on, select::Either};
+    use zencan_common::{CanMessage, NodeId};
+
+    use super::*;
+    use crate::{priority_queue::PriorityQueue, Callbacks, NodeState};
+
+    fn make_node() -> (Node<'static>, &'static NodeMbox) {
+        let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
+        let mbox = Box::leak(Box::new(NodeMbox::new(&[], &[], tx_queue, sdo_buffer)));
+        let state = Box::leak(Box::new(NodeState::new(&[], &[])));
+        let od_table = Box::leak(Box::new([]));
+
+        let node = Node::new(
+            NodeId::new(1).unwrap(),
+            Callbacks::default(),
+            mbox,
+            state,
+            od_table,
+        );
+        (node, mbox)
+    }
+
+    #[test]
+    fn test_async_node_events() {
+        static EVENTS: NodeEventChannel<4> = Channel::new();
+        let (node, mbox) = make_node();
+        let async_node = AsyncNode::new(node, mbox)
+            .with_period(Duration::from_millis(1))
+            .with_events(EVENTS.sender().into());
+
+        // The node boots to pre-operational on the first process call
+        let event = block_on(async {
+            match select(async_node.run(), EVENTS.receive()).await {
+                Either::First(never) => never,
+                Either::Second(event) => event,
+            }
+        });
+        assert_eq!(NodeEvent::NmtStateChanged(NmtState::PreOperational), event);
+    }
+
+    #[test]
+    fn test_separate_signals() {
+        static SIGNAL1: ProcessSignal = ProcessSignal::new();
+        static SIGNAL2: ProcessSignal = ProcessSignal::new();
+        let (node1, mbox1) = make_node();
+        let (node2, mbox2) = make_node();
+        let _node1 = AsyncNode::with_signal(node1, mbox1, &SIGNAL1);
+        let _node2 = AsyncNode::with_signal(node2, mbox2, &SIGNAL2);
+
+        // A message for one node only wakes that node
+        let nmt_start = CanMessage::new(zencan_common::messages::NMT_CMD_ID, &[1, 1]);
+        mbox2.store_message(nmt_start).unwrap();
+        assert!(!SIGNAL1.signaled());
+        assert!(SIGNAL2.signaled());
+
+        mbox1.store_message(nmt_start).unwrap();
+        assert!(SIGNAL1.signaled());
+    }
+}
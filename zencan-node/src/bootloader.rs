@@ -1,81 +1,140 @@
 //! Bootloader objects
 //!
+//! A node which supports firmware updates over the bus includes the bootloader info object
+//! (0x5500), and a [`BootloaderSection`] object (0x5510-0x551F) for each programmable section.
+//! A section is programmed by writing the erase command, then the section data, and then the
+//! expected CRC32 of the data to the verify sub object.
 //!
+//! A dual-bank device holds two copies of its image in banks A and B, and runs from the active bank
+//! while the inactive bank is programmed. Once every section in the inactive bank has been
+//! verified, the client writes the swap command to the bootloader info object, and the application
+//! is notified with [`BootloaderInfo::swap_flag`] to switch banks.
 
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::object_dict::{
-    ConstByteRefField, ConstField, ObjectAccess, ProvidesSubObjects, SubObjectAccess,
-};
+use crate::abort_codes::{self, check_write_len};
+use crate::object_dict::{ConstByteRefField, ConstField, ObjectAccess, SubObjectAccess};
 use zencan_common::{
-    constants::values::BOOTLOADER_ERASE_CMD,
-    objects::{ObjectCode, SubInfo},
+    constants::values::{BOOTLOADER_ERASE_CMD, BOOTLOADER_RESET_CMD, BOOTLOADER_SWAP_CMD},
+    crc32::Crc32,
+    objects::{AccessType, DataType, ObjectCode, PdoMappable, SubInfo},
     sdo::AbortCode,
     AtomicCell,
 };
 
+/// A bank of a dual-bank device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum BootloaderBank {
+    /// Bank A
+    A = 1,
+    /// Bank B
+    B = 2,
+}
+
+impl BootloaderBank {
+    /// Get the other bank
+    pub const fn other(&self) -> Self {
+        match self {
+            BootloaderBank::A => BootloaderBank::B,
+            BootloaderBank::B => BootloaderBank::A,
+        }
+    }
+}
+
 /// Implements a Bootloader info (0x5500) object
-#[derive(Debug, Default)]
+#[allow(missing_debug_implementations)]
 pub struct BootloaderInfo<const APP: bool, const NUM_SECTIONS: u8> {
-    reset_flag: ResetField,
+    reset_flag: AtomicBool,
+    /// The sections of a dual-bank device, or None for a single bank device
+    bank_sections: Option<&'static [&'static BootloaderSection]>,
+    active_bank: AtomicCell<BootloaderBank>,
+    swap_flag: AtomicBool,
 }
 
 impl<const APP: bool, const NUM_SECTIONS: u8> BootloaderInfo<APP, NUM_SECTIONS> {
     /// Create new BootloaderInfo
     pub const fn new() -> Self {
         Self {
-            reset_flag: ResetField::new(),
+            reset_flag: AtomicBool::new(false),
+            bank_sections: None,
+            active_bank: AtomicCell::new(BootloaderBank::A),
+            swap_flag: AtomicBool::new(false),
         }
     }
 
+    /// Enable dual-bank support
+    ///
+    /// `sections` are the sections of the device. A swap is only accepted when every section in the
+    /// inactive bank has been verified.
+    pub const fn dual_bank(mut self, sections: &'static [&'static BootloaderSection]) -> Self {
+        self.bank_sections = Some(sections);
+        self
+    }
+
     /// Read the reset_flag
     ///
     /// The flag is set when a reset command is written to the object, and this function can be used
     /// by the application to determed when a reset to bootloader is commanded
     pub fn reset_flag(&self) -> bool {
-        self.reset_flag.load()
+        self.reset_flag.load(Ordering::Relaxed)
     }
-}
-
-#[derive(Debug, Default)]
-struct ResetField {
-    flag: AtomicBool,
-}
 
-impl ResetField {
-    pub const fn new() -> Self {
-        Self {
-            flag: AtomicBool::new(false),
-        }
+    /// Read the swap flag
+    ///
+    /// The flag is set when the swap command is accepted, and can be used by the application to
+    /// determine when to switch to the inactive bank. It is never set on a single bank device.
+    pub fn swap_flag(&self) -> bool {
+        self.swap_flag.load(Ordering::Relaxed)
     }
 
-    pub fn load(&self) -> bool {
-        self.flag.load(Ordering::Relaxed)
+    /// Get the bank the device is running from, or None for a single bank device
+    pub fn active_bank(&self) -> Option<BootloaderBank> {
+        self.bank_sections.map(|_| self.active_bank.load())
     }
-}
 
-impl SubObjectAccess for ResetField {
-    fn read(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, AbortCode> {
-        Err(AbortCode::WriteOnly)
+    /// Set the bank the device is running from
+    ///
+    /// This should be set by the application at startup. The default is [`BootloaderBank::A`].
+    pub fn set_active_bank(&self, bank: BootloaderBank) {
+        self.active_bank.store(bank);
     }
 
-    fn read_size(&self) -> usize {
-        0
+    fn max_sub(&self) -> u8 {
+        if self.bank_sections.is_some() {
+            5
+        } else {
+            3
+        }
     }
 
-    fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
-        if data.len() == 4 {
-            if data == [0x42, 0x4F, 0x4F, 0x54] {
-                self.flag.store(true, Ordering::Relaxed);
-                Ok(())
-            } else {
-                Err(AbortCode::InvalidValue)
-            }
-        } else if data.len() < 4 {
-            Err(AbortCode::DataTypeMismatchLengthLow)
-        } else {
-            Err(AbortCode::DataTypeMismatchLengthHigh)
+    fn write_swap_command(&self, data: &[u8]) -> Result<(), AbortCode> {
+        let Some(sections) = self.bank_sections else {
+            return Err(AbortCode::NoSuchSubIndex);
+        };
+        check_write_len(data, 4)?;
+        if data != BOOTLOADER_SWAP_CMD.to_le_bytes() {
+            return Err(AbortCode::InvalidValue);
+        }
+        let inactive = self.active_bank.load().other();
+        let mut inactive_sections = sections
+            .iter()
+            .filter(|section| section.bank() == Some(inactive))
+            .peekable();
+        let ready = inactive_sections.peek().is_some()
+            && inactive_sections.all(|section| section.is_verified());
+        if !ready {
+            return Err(abort_codes::DEVICE_STATE);
         }
+        self.swap_flag.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl<const APP: bool, const NUM_SECTIONS: u8> Default for BootloaderInfo<APP, NUM_SECTIONS> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -91,29 +150,61 @@ const fn get_config_value(app: bool) -> u32 {
     config
 }
 
-impl<const APP: bool, const NUM_SECTIONS: u8> ProvidesSubObjects
-    for BootloaderInfo<APP, NUM_SECTIONS>
-{
-    fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+impl<const APP: bool, const NUM_SECTIONS: u8> ObjectAccess for BootloaderInfo<APP, NUM_SECTIONS> {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
         match sub {
-            0 => Some((
-                SubInfo::MAX_SUB_NUMBER,
-                const { &ConstField::new(3u8.to_le_bytes()) },
-            )),
-            1 => Some((SubInfo::new_u32().ro_access(), {
-                const { &ConstField::new(get_config_value(APP).to_le_bytes()) }
-            })),
-            2 => Some((SubInfo::new_u8().ro_access(), {
-                const { &ConstField::new(NUM_SECTIONS.to_le_bytes()) }
-            })),
-            3 => Some((SubInfo::new_u32().wo_access(), &self.reset_flag)),
-            _ => None,
+            0 => ConstField::new([self.max_sub()]).read(offset, buf),
+            1 => ConstField::new(get_config_value(APP).to_le_bytes()).read(offset, buf),
+            2 => ConstField::new(NUM_SECTIONS.to_le_bytes()).read(offset, buf),
+            3 => Err(AbortCode::WriteOnly),
+            4 if self.bank_sections.is_some() => {
+                ConstField::new([self.active_bank.load() as u8]).read(offset, buf)
+            }
+            5 if self.bank_sections.is_some() => Err(AbortCode::WriteOnly),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        match self.sub_info(sub)? {
+            info if info.access_type.is_readable() => Ok(info.size),
+            _ => Ok(0),
+        }
+    }
+
+    fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
+        match sub {
+            0..=2 => Err(AbortCode::ReadOnly),
+            3 => {
+                check_write_len(data, 4)?;
+                if data == BOOTLOADER_RESET_CMD.to_le_bytes() {
+                    self.reset_flag.store(true, Ordering::Relaxed);
+                    Ok(())
+                } else {
+                    Err(AbortCode::InvalidValue)
+                }
+            }
+            4 if self.bank_sections.is_some() => Err(AbortCode::ReadOnly),
+            5 => self.write_swap_command(data),
+            _ => Err(AbortCode::NoSuchSubIndex),
         }
     }
 
     fn object_code(&self) -> ObjectCode {
         ObjectCode::Record
     }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        match sub {
+            0 => Ok(SubInfo::MAX_SUB_NUMBER),
+            1 => Ok(SubInfo::new_u32().ro_access()),
+            2 => Ok(SubInfo::new_u8().ro_access()),
+            3 => Ok(SubInfo::new_u32().wo_access()),
+            4 if self.bank_sections.is_some() => Ok(SubInfo::new_u8().ro_access()),
+            5 if self.bank_sections.is_some() => Ok(SubInfo::new_u32().wo_access()),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
 }
 
 /// A trait for applications to implement to provide a bootloader section access implementation
@@ -136,14 +227,48 @@ pub trait BootloaderSectionCallbacks: Sync {
     ///
     /// Returns true on successful write
     fn finalize(&self) -> bool;
+
+    /// Verify the programmed contents of the section
+    ///
+    /// Called when the client verifies the section, after the CRC of the data received since the
+    /// last erase has been checked against the expected value. Implementations may read back the
+    /// `len` bytes stored and check that their CRC32 is `crc`, to detect data which was received
+    /// correctly but not stored correctly.
+    ///
+    /// Returns true if the stored data is correct. The default implementation returns true.
+    fn verify(&self, len: u32, crc: u32) -> bool {
+        let _ = (len, crc);
+        true
+    }
 }
 
+/// Mode bit indicating that the section can be programmed
+const MODE_PROGRAMMABLE: u8 = 1 << 0;
+/// Mode bit indicating that the section has been verified since it was last programmed
+const MODE_VERIFIED: u8 = 1 << 1;
+
 /// Implements a bootloader section object in the object dictionary
+///
+/// | Sub | Type   | Access | Description |
+/// | --- | ------ | ------ | ----------- |
+/// | 1   | u8     | ro     | Mode bits. Bit 0: programmable, bit 1: verified |
+/// | 2   | str    | const  | Section name |
+/// | 3   | u32    | wo     | Erase command |
+/// | 4   | domain | rw     | Programming data |
+/// | 5   | u32    | ro     | CRC32 of the data programmed since the last erase |
+/// | 6   | u32    | wo     | Verify command: write the expected CRC32 of the section data |
+/// | 7   | u8     | const  | Bank: 0 for a single bank device, 1 for bank A, 2 for bank B |
 #[allow(missing_debug_implementations)]
 pub struct BootloaderSection {
     name: &'static str,
     size: u32,
+    bank: Option<BootloaderBank>,
     callbacks: AtomicCell<Option<&'static dyn BootloaderSectionCallbacks>>,
+    /// CRC of the data written since the last erase
+    crc: AtomicCell<Crc32>,
+    /// Number of bytes written since the last erase
+    written: AtomicCell<u32>,
+    verified: AtomicBool,
 }
 
 impl BootloaderSection {
@@ -152,24 +277,113 @@ impl BootloaderSection {
         Self {
             name,
             size,
+            bank: None,
             callbacks: AtomicCell::new(None),
+            crc: AtomicCell::new(Crc32::new()),
+            written: AtomicCell::new(0),
+            verified: AtomicBool::new(false),
         }
     }
 
+    /// Assign the section to a bank of a dual-bank device
+    pub const fn with_bank(mut self, bank: BootloaderBank) -> Self {
+        self.bank = Some(bank);
+        self
+    }
+
     /// Register the application callbacks which implement storage for this section
     pub fn register_callbacks(&self, callbacks: &'static dyn BootloaderSectionCallbacks) {
         self.callbacks.store(Some(callbacks));
     }
+
+    /// Get the bank the section belongs to, or None on a single bank device
+    pub fn bank(&self) -> Option<BootloaderBank> {
+        self.bank
+    }
+
+    /// Returns true if the section has been verified since it was last programmed
+    pub fn is_verified(&self) -> bool {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    /// Get the CRC32 of the data programmed since the last erase
+    pub fn crc(&self) -> u32 {
+        self.crc.load().finish()
+    }
+
+    fn mode_bits(&self) -> u8 {
+        let mut mode = MODE_PROGRAMMABLE;
+        if self.is_verified() {
+            mode |= MODE_VERIFIED;
+        }
+        mode
+    }
+
+    fn callbacks(&self) -> Result<&'static dyn BootloaderSectionCallbacks, AbortCode> {
+        self.callbacks.load().ok_or(AbortCode::ResourceNotAvailable)
+    }
+
+    fn erase(&self, data: &[u8]) -> Result<(), AbortCode> {
+        if data != BOOTLOADER_ERASE_CMD.to_le_bytes() {
+            return Err(AbortCode::InvalidValue);
+        }
+        let callbacks = self.callbacks()?;
+        self.verified.store(false, Ordering::Relaxed);
+        self.crc.store(Crc32::new());
+        self.written.store(0);
+        if callbacks.erase() {
+            Ok(())
+        } else {
+            Err(abort_codes::HARDWARE_ERROR)
+        }
+    }
+
+    /// Pass programming data to the storage callbacks
+    fn write_data(&self, data: &[u8]) -> Result<(), AbortCode> {
+        let callbacks = self.callbacks()?;
+        self.verified.store(false, Ordering::Relaxed);
+        let mut crc = self.crc.load();
+        crc.update(data);
+        self.crc.store(crc);
+        self.written.store(self.written.load() + data.len() as u32);
+        callbacks.write(data);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<(), AbortCode> {
+        if self.callbacks()?.finalize() {
+            Ok(())
+        } else {
+            Err(abort_codes::HARDWARE_ERROR)
+        }
+    }
+
+    fn verify(&self, data: &[u8]) -> Result<(), AbortCode> {
+        check_write_len(data, 4)?;
+        let callbacks = self.callbacks()?;
+        let expected = u32::from_le_bytes(data.try_into().unwrap());
+        if expected != self.crc() {
+            return Err(AbortCode::InvalidValue);
+        }
+        if !callbacks.verify(self.written.load(), expected) {
+            return Err(abort_codes::HARDWARE_ERROR);
+        }
+        self.verified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
 }
 
 impl ObjectAccess for BootloaderSection {
     fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
         match sub {
-            0 => ConstField::new(4u8.to_le_bytes()).read(offset, buf),
-            1 => ConstField::new(1u8.to_le_bytes()).read(offset, buf),
+            0 => ConstField::new(7u8.to_le_bytes()).read(offset, buf),
+            1 => ConstField::new([self.mode_bits()]).read(offset, buf),
             2 => ConstByteRefField::new(self.name.as_bytes()).read(offset, buf),
             3 => Err(AbortCode::WriteOnly),
             4 => Err(AbortCode::WriteOnly),
+            5 => ConstField::new(self.crc().to_le_bytes()).read(offset, buf),
+            6 => Err(AbortCode::WriteOnly),
+            7 => ConstField::new([self.bank.map(|b| b as u8).unwrap_or(0)]).read(offset, buf),
             _ => Err(AbortCode::NoSuchSubIndex),
         }
     }
@@ -181,47 +395,50 @@ impl ObjectAccess for BootloaderSection {
             2 => Ok(self.name.len()),
             3 => Ok(0),
             4 => Ok(0),
+            5 => Ok(4),
+            6 => Ok(0),
+            7 => Ok(1),
             _ => Err(AbortCode::NoSuchSubIndex),
         }
     }
 
     fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
         match sub {
-            0 => Err(AbortCode::ReadOnly),
-            1 => Err(AbortCode::ReadOnly),
-            2 => Err(AbortCode::ReadOnly),
-            3 => {
-                if data == BOOTLOADER_ERASE_CMD.to_le_bytes() {
-                    if let Some(cb) = self.callbacks.load() {
-                        if cb.erase() {
-                            Ok(())
-                        } else {
-                            Err(AbortCode::GeneralError)
-                        }
-                    } else {
-                        Err(AbortCode::ResourceNotAvailable)
-                    }
-                } else {
-                    Err(AbortCode::InvalidValue)
-                }
-            }
+            0 | 1 | 2 | 5 | 7 => Err(AbortCode::ReadOnly),
+            3 => self.erase(data),
             4 => {
-                if let Some(callbacks) = self.callbacks.load() {
-                    callbacks.write(data);
-                    if callbacks.finalize() {
-                        // success
-                        Ok(())
-                    } else {
-                        Err(AbortCode::GeneralError)
-                    }
-                } else {
-                    Err(AbortCode::ResourceNotAvailable)
-                }
+                self.write_data(data)?;
+                self.finalize()
             }
+            6 => self.verify(data),
             _ => Err(AbortCode::NoSuchSubIndex),
         }
     }
 
+    fn begin_partial(&self, sub: u8) -> Result<(), AbortCode> {
+        match sub {
+            // Programming data larger than the SDO buffer is streamed to the callbacks
+            4 => self.callbacks().map(|_| ()),
+            0 | 1 | 2 | 5 | 7 => Err(AbortCode::ReadOnly),
+            3 | 6 => Err(AbortCode::UnsupportedAccess),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+
+    fn write_partial(&self, sub: u8, buf: &[u8]) -> Result<(), AbortCode> {
+        match sub {
+            4 => self.write_data(buf),
+            _ => Err(AbortCode::UnsupportedAccess),
+        }
+    }
+
+    fn end_partial(&self, sub: u8) -> Result<(), AbortCode> {
+        match sub {
+            4 => self.finalize(),
+            _ => Err(AbortCode::UnsupportedAccess),
+        }
+    }
+
     fn object_code(&self) -> ObjectCode {
         ObjectCode::Record
     }
@@ -234,11 +451,14 @@ impl ObjectAccess for BootloaderSection {
             3 => Ok(SubInfo::new_u32().wo_access()),
             4 => Ok(SubInfo {
                 size: self.size as usize,
-                data_type: zencan_common::objects::DataType::Domain,
-                access_type: zencan_common::objects::AccessType::Rw,
-                pdo_mapping: zencan_common::objects::PdoMappable::None,
+                data_type: DataType::Domain,
+                access_type: AccessType::Rw,
+                pdo_mapping: PdoMappable::None,
                 persist: false,
             }),
+            5 => Ok(SubInfo::new_u32().ro_access()),
+            6 => Ok(SubInfo::new_u32().wo_access()),
+            7 => Ok(SubInfo::new_u8().ro_access()),
             _ => Err(AbortCode::NoSuchSubIndex),
         }
     }
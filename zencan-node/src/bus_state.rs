@@ -0,0 +1,62 @@
+//! CAN controller error states reported by the application
+//!
+//! The node has no access to the CAN controller, so the application reports changes to the
+//! controller's error state with [`Node::report_bus_state`](crate::Node::report_bus_state). The
+//! node then reacts according to its [`BusErrorPolicy`]:
+//!
+//! - TPDO transmission is paused while the bus is off, and optionally while the controller is
+//!   error passive. Events which occur while paused remain pending, so that event-driven TPDOs are
+//!   sent with their latest value once transmission resumes.
+//! - When the controller recovers from bus-off, the
+//!   [`Callbacks::bus_off_recovered`](crate::Callbacks::bus_off_recovered) callback is called.
+//!
+//! The node does not currently produce EMCY messages, so an application which needs to report bus
+//! errors to the network should do so from the `bus_off_recovered` callback.
+
+/// The error state of the CAN controller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusState {
+    /// The controller is operating normally
+    #[default]
+    ErrorActive,
+    /// The controller's error counters have exceeded the error passive limit
+    ErrorPassive,
+    /// The controller has entered the bus-off state, and cannot transmit
+    BusOff,
+}
+
+/// Configures how the node reacts to a [`BusState`] reported by the application
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BusErrorPolicy {
+    /// Pause TPDO transmission while the controller is error passive
+    pub pause_tpdos_when_error_passive: bool,
+    /// Pause TPDO transmission while the controller is bus-off
+    pub pause_tpdos_when_bus_off: bool,
+}
+
+impl Default for BusErrorPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BusErrorPolicy {
+    /// Create the default policy, which pauses TPDOs only while the bus is off
+    pub const fn new() -> Self {
+        Self {
+            pause_tpdos_when_error_passive: false,
+            pause_tpdos_when_bus_off: true,
+        }
+    }
+
+    /// Returns true if TPDO transmission should be paused in `state`
+    pub const fn pause_tpdos(&self, state: BusState) -> bool {
+        match state {
+            BusState::ErrorActive => false,
+            BusState::ErrorPassive => self.pause_tpdos_when_error_passive,
+            BusState::BusOff => self.pause_tpdos_when_bus_off,
+        }
+    }
+}
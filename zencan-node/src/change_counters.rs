@@ -0,0 +1,184 @@
+//! Change counters for detecting object dictionary modifications
+//!
+//! The node counts modifications to the objects in each [`PersistGroup`]. The [`ChangeCounters`] are
+//! owned by the [`NodeState`](crate::NodeState), and can be read remotely via the optional change
+//! counters object (0x5F02), which is created when `change_counters = true` is set in the device
+//! config.
+//!
+//! This allows a client to cheaply determine whether it needs to re-read a node's configuration:
+//! if a group's counter matches the value seen on the last read, none of the objects in the group
+//! have been modified via SDO since.
+//!
+//! Counters are incremented by the [`Node`](crate::Node) when an SDO download completes, and when
+//! an NMT reset occurs, as objects may be restored from storage at that time. Writes made by the
+//! application, or by RPDOs, are not counted. An application which modifies configuration objects
+//! itself can record the change with [`ChangeCounters::record_change`].
+//!
+//! The counters are held in RAM, and restart on boot, so the same count may be seen again after a
+//! reboot with different object values. To allow a client to detect this, the object also reports a
+//! boot epoch, which the application should set to a value which differs on every boot, e.g. a boot
+//! count kept in persistent storage, or a random number, using [`ChangeCounters::set_boot_epoch`].
+//! A client must consider its cached values invalid whenever the epoch changes. If the application
+//! does not set an epoch, it is zero, and a client can only detect a reboot by other means.
+
+use zencan_common::{
+    constants::object_ids::CHANGE_COUNTERS_BOOT_EPOCH_SUB,
+    objects::{ObjectCode, PersistGroup, SubInfo},
+    sdo::AbortCode,
+    AtomicCell,
+};
+
+use crate::object_dict::ObjectAccess;
+
+/// Counters tracking modifications to each [`PersistGroup`]
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct ChangeCounters {
+    communication: AtomicCell<u32>,
+    application: AtomicCell<u32>,
+    boot_epoch: AtomicCell<u32>,
+}
+
+impl ChangeCounters {
+    /// Create a new ChangeCounters with all counters, and the boot epoch, at zero
+    pub const fn new() -> Self {
+        Self {
+            communication: AtomicCell::new(0),
+            application: AtomicCell::new(0),
+            boot_epoch: AtomicCell::new(0),
+        }
+    }
+
+    fn counter(&self, group: PersistGroup) -> &AtomicCell<u32> {
+        match group {
+            PersistGroup::Communication => &self.communication,
+            PersistGroup::Application => &self.application,
+        }
+    }
+
+    /// Get the current change count for a group
+    pub fn get(&self, group: PersistGroup) -> u32 {
+        self.counter(group).load()
+    }
+
+    /// Record a modification to the object at `index`
+    pub fn record_change(&self, index: u16) {
+        self.record_group_change(PersistGroup::from_index(index));
+    }
+
+    /// Record a modification to any number of objects in `group`
+    pub fn record_group_change(&self, group: PersistGroup) {
+        self.counter(group)
+            .fetch_update(|x| Some(x.wrapping_add(1)))
+            .ok();
+    }
+
+    /// Get the boot epoch
+    pub fn boot_epoch(&self) -> u32 {
+        self.boot_epoch.load()
+    }
+
+    /// Set the boot epoch, which identifies the current boot to clients
+    ///
+    /// This should be called once at startup, with a value which differs from that of previous
+    /// boots. See the [module documentation](self).
+    pub fn set_boot_epoch(&self, epoch: u32) {
+        self.boot_epoch.store(epoch);
+    }
+}
+
+/// Implements the change counters object (0x5F02)
+///
+/// | Sub | Type | Description |
+/// | --- | ---- | ----------- |
+/// | 1   | u32  | Communication objects change count |
+/// | 2   | u32  | Application objects change count |
+/// | 3   | u32  | Boot epoch |
+#[allow(missing_debug_implementations)]
+pub struct ChangeCountersObject {
+    counters: &'static ChangeCounters,
+}
+
+impl ChangeCountersObject {
+    /// Create a new change counters object
+    pub const fn new(counters: &'static ChangeCounters) -> Self {
+        Self { counters }
+    }
+}
+
+impl ObjectAccess for ChangeCountersObject {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub == 0 {
+            if offset != 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = CHANGE_COUNTERS_BOOT_EPOCH_SUB;
+            return Ok(1);
+        }
+
+        let value = if sub == CHANGE_COUNTERS_BOOT_EPOCH_SUB {
+            self.counters.boot_epoch()
+        } else {
+            let group = PersistGroup::ALL
+                .iter()
+                .find(|g| g.sub_index() == sub)
+                .ok_or(AbortCode::NoSuchSubIndex)?;
+            self.counters.get(*group)
+        };
+        let value_bytes = value.to_le_bytes();
+        if offset < value_bytes.len() {
+            let read_len = buf.len().min(value_bytes.len() - offset);
+            buf[..read_len].copy_from_slice(&value_bytes[offset..offset + read_len]);
+            Ok(read_len)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        Ok(self.sub_info(sub)?.size)
+    }
+
+    fn write(&self, sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+        self.sub_info(sub)?;
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Record
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        match sub {
+            0 => Ok(SubInfo::MAX_SUB_NUMBER),
+            1..=CHANGE_COUNTERS_BOOT_EPOCH_SUB => Ok(SubInfo::new_u32()),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_counters_object() {
+        let counters = Box::leak(Box::new(ChangeCounters::new()));
+        let object = ChangeCountersObject::new(counters);
+
+        counters.record_change(0x1800);
+        counters.record_change(0x1A00);
+        counters.record_change(0x2000);
+        counters.record_change(0x5000);
+        counters.record_change(0x6000);
+
+        assert_eq!(3, object.read_u8(0).unwrap());
+        assert_eq!(2, object.read_u32(1).unwrap());
+        assert_eq!(3, object.read_u32(2).unwrap());
+        assert_eq!(0, object.read_u32(3).unwrap());
+        counters.set_boot_epoch(0x1234_5678);
+        assert_eq!(0x1234_5678, object.read_u32(3).unwrap());
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.read_u32(4));
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(1, &[0; 4]));
+    }
+}
@@ -0,0 +1,313 @@
+//! DCF storage for a CiA 302 configuration manager
+//!
+//! A configuration manager stores the configuration of the other nodes on the network, and writes
+//! it to each node when it boots. The configurations are stored in two array objects, with one
+//! domain sub object for each node ID from 1 to 127:
+//!
+//! - 0x1F20 (Store DCF) holds a complete DCF file for each node, so that a tool can retrieve it
+//! - 0x1F22 (Concise DCF) holds a [concise DCF](crate::common::concise_dcf) for each node, which
+//!   lists the values to be written to the node
+//!
+//! Storage is statically allocated, so it is only provided for the node IDs listed in the
+//! `[config_manager]` section of the device config. zencan-build creates a [`DcfStore`] for each of
+//! them, accessible by the application as `CONCISE_DCF_STORES` and `STORE_DCF_STORES`. The sub
+//! objects for other node IDs are always empty, and cannot be written. Stored values are persisted
+//! when objects are saved.
+//!
+//! The application is responsible for applying the stored configuration, e.g. by reading it with
+//! [`DcfStore::with_data`] and writing each of its entries to the node over SDO when the node's
+//! boot-up message is received.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use zencan_common::{
+    objects::{AccessType, DataType, ObjectCode, PdoMappable, SubInfo},
+    sdo::AbortCode,
+};
+
+use crate::object_dict::ObjectAccess;
+
+/// The highest node ID, and the highest sub index of the DCF storage objects
+const MAX_NODE_ID: u8 = 127;
+
+struct StoreState<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+/// Storage for the DCF of a single node
+///
+/// `N` is the size of the storage in bytes.
+#[allow(missing_debug_implementations)]
+pub struct DcfStore<const N: usize> {
+    node_id: u8,
+    state: Mutex<RefCell<StoreState<N>>>,
+}
+
+impl<const N: usize> DcfStore<N> {
+    /// Create a new, empty store for the node with ID `node_id`
+    pub const fn new(node_id: u8) -> Self {
+        Self {
+            node_id,
+            state: Mutex::new(RefCell::new(StoreState {
+                buf: [0; N],
+                len: 0,
+            })),
+        }
+    }
+
+    /// Get the ID of the node this store holds the DCF for
+    pub fn node_id(&self) -> u8 {
+        self.node_id
+    }
+
+    /// Get the size of the stored DCF in bytes
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.state.borrow_ref(cs).len)
+    }
+
+    /// Returns true if no DCF is stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove the stored DCF
+    pub fn clear(&self) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).len = 0);
+    }
+
+    /// Call `f` with the stored DCF
+    ///
+    /// `f` is called inside a critical section, so it should not do any slow work.
+    pub fn with_data<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        critical_section::with(|cs| {
+            let state = self.state.borrow_ref(cs);
+            f(&state.buf[..state.len])
+        })
+    }
+
+    /// Copy stored data into `buf`, starting at `offset`
+    ///
+    /// Returns the number of bytes copied
+    pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.with_data(|data| {
+            let data = data.get(offset..).unwrap_or_default();
+            let len = buf.len().min(data.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            len
+        })
+    }
+
+    /// Replace the stored DCF
+    fn store(&self, data: &[u8]) -> Result<(), AbortCode> {
+        if data.len() > N {
+            return Err(AbortCode::DataTypeMismatchLengthHigh);
+        }
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.buf[..data.len()].copy_from_slice(data);
+            state.len = data.len();
+        });
+        Ok(())
+    }
+
+    /// Append data to the stored DCF
+    fn append(&self, data: &[u8]) -> Result<(), AbortCode> {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            let start = state.len;
+            let Some(dest) = state.buf.get_mut(start..start + data.len()) else {
+                // Don't leave a truncated DCF behind
+                state.len = 0;
+                return Err(AbortCode::DataTypeMismatchLengthHigh);
+            };
+            dest.copy_from_slice(data);
+            state.len += data.len();
+            Ok(())
+        })
+    }
+}
+
+/// Implements a DCF storage object (0x1F20 or 0x1F22)
+///
+/// | Sub   | Type   | Description |
+/// | ----- | ------ | ----------- |
+/// | 0     | u8     | Highest sub index - always 127 |
+/// | 1-127 | Domain | The DCF for the node with ID equal to the sub index |
+#[allow(missing_debug_implementations)]
+pub struct DcfStoreObject<const N: usize> {
+    stores: &'static [DcfStore<N>],
+}
+
+impl<const N: usize> DcfStoreObject<N> {
+    /// Create a new DCF storage object, with storage for the nodes in `stores`
+    pub const fn new(stores: &'static [DcfStore<N>]) -> Self {
+        Self { stores }
+    }
+
+    /// Get the store for the node with ID `sub`, if there is one
+    fn store(&self, sub: u8) -> Result<Option<&DcfStore<N>>, AbortCode> {
+        if sub == 0 || sub > MAX_NODE_ID {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(self.stores.iter().find(|s| s.node_id == sub))
+    }
+
+    /// Get the store which can be written for the node with ID `sub`
+    fn writable_store(&self, sub: u8) -> Result<&DcfStore<N>, AbortCode> {
+        if sub == 0 {
+            return Err(AbortCode::ReadOnly);
+        }
+        self.store(sub)?.ok_or(AbortCode::OutOfMemory)
+    }
+}
+
+impl<const N: usize> ObjectAccess for DcfStoreObject<N> {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub == 0 {
+            if offset != 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = MAX_NODE_ID;
+            return Ok(1);
+        }
+        Ok(self
+            .store(sub)?
+            .map(|store| store.read_bytes(offset, buf))
+            .unwrap_or(0))
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        if sub == 0 {
+            return Ok(1);
+        }
+        Ok(self.store(sub)?.map(|store| store.len()).unwrap_or(0))
+    }
+
+    fn upload_size(&self, sub: u8) -> Option<usize> {
+        self.read_size(sub).ok()
+    }
+
+    fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
+        if data.is_empty() && sub != 0 {
+            // Clearing a sub object without storage is allowed, as it is already empty
+            return match self.store(sub)? {
+                Some(store) => store.store(data),
+                None => Ok(()),
+            };
+        }
+        self.writable_store(sub)?.store(data)
+    }
+
+    fn begin_partial(&self, sub: u8) -> Result<(), AbortCode> {
+        self.writable_store(sub)?.clear();
+        Ok(())
+    }
+
+    fn write_partial(&self, sub: u8, buf: &[u8]) -> Result<(), AbortCode> {
+        self.writable_store(sub)?.append(buf)
+    }
+
+    fn end_partial(&self, sub: u8) -> Result<(), AbortCode> {
+        self.writable_store(sub)?;
+        Ok(())
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Array
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        if sub == 0 {
+            return Ok(SubInfo::MAX_SUB_NUMBER);
+        }
+        let store = self.store(sub)?;
+        Ok(SubInfo {
+            // Sub objects without storage report a size of 0, like other domains with no fixed
+            // size
+            size: if store.is_some() { N } else { 0 },
+            data_type: DataType::Domain,
+            access_type: AccessType::Rw,
+            pdo_mapping: PdoMappable::None,
+            persist: store.is_some(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_object() -> DcfStoreObject<8> {
+        let stores = [DcfStore::new(2), DcfStore::new(5)];
+        DcfStoreObject::new(Box::leak(Box::new(stores)))
+    }
+
+    fn read_all(object: &DcfStoreObject<8>, sub: u8) -> Vec<u8> {
+        let mut buf = [0; 16];
+        let len = object.read(sub, 0, &mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn test_dcf_store_object() {
+        let object = make_object();
+        assert_eq!(127, object.read_u8(0).unwrap());
+        assert_eq!(127, object.max_sub_number());
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.sub_info(128));
+
+        object.write(5, &[1, 2, 3]).unwrap();
+        assert_eq!(vec![1, 2, 3], read_all(&object, 5));
+        assert_eq!(Some(3), object.upload_size(5));
+        assert!(read_all(&object, 2).is_empty());
+        assert_eq!(3, object.stores[1].len());
+        assert_eq!(6, object.stores[1].with_data(|d| d.iter().sum::<u8>()));
+
+        // Offset reads
+        let mut buf = [0; 4];
+        assert_eq!(1, object.read(5, 2, &mut buf).unwrap());
+        assert_eq!(3, buf[0]);
+
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            object.write(2, &[0; 9])
+        );
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(0, &[1]));
+
+        // Nodes without storage are always empty
+        let info = object.sub_info(3).unwrap();
+        assert_eq!(0, info.size);
+        assert!(!info.persist);
+        assert!(object.sub_info(2).unwrap().persist);
+        assert_eq!(Err(AbortCode::OutOfMemory), object.write(3, &[1]));
+        assert_eq!(Ok(()), object.write(3, &[]));
+        assert!(read_all(&object, 3).is_empty());
+
+        object.write(5, &[]).unwrap();
+        assert!(object.stores[1].is_empty());
+    }
+
+    #[test]
+    fn test_dcf_store_partial_write() {
+        let object = make_object();
+        object.write(2, &[9; 8]).unwrap();
+
+        object.begin_partial(2).unwrap();
+        object.write_partial(2, &[1, 2, 3]).unwrap();
+        object.write_partial(2, &[4, 5]).unwrap();
+        object.end_partial(2).unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 5], read_all(&object, 2));
+
+        // Overflowing the store leaves it empty
+        object.begin_partial(2).unwrap();
+        object.write_partial(2, &[1; 6]).unwrap();
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            object.write_partial(2, &[1; 6])
+        );
+        assert!(object.stores[0].is_empty());
+
+        assert_eq!(Err(AbortCode::OutOfMemory), object.begin_partial(4));
+    }
+}
@@ -0,0 +1,807 @@
+//! Node statistics for health monitoring
+//!
+//! The [`NodeDiagnostics`] counters are owned by the [`NodeMbox`](crate::NodeMbox), and are updated
+//! by the mailbox and the [`Node`](crate::Node) as messages are handled. They can be read locally
+//! via [`Node::diagnostics`](crate::Node::diagnostics), or remotely via the optional diagnostics
+//! object (0x5F00), which is created when `diagnostics = true` is set in the device config.
+//!
+//! A summary of the node's health can also be published periodically by the node status TPDO,
+//! which is created by the `[status_pdo]` section of the device config. It maps the node status
+//! object (0x5F05), see [`NodeStatusObject`].
+//!
+//! The counters are held in RAM, so by default they restart from zero on every boot. To accumulate
+//! lifetime statistics, an application can provide a
+//! [`Callbacks::store_diagnostics`](crate::Callbacks::store_diagnostics) callback and enable
+//! autosave with [`Node::set_diagnostics_autosave`](crate::Node::set_diagnostics_autosave). The node
+//! will then periodically pass a [`DiagnosticsSnapshot`] to the callback for storage, and the
+//! application restores it on the next boot with [`NodeDiagnostics::restore`].
+//!
+//! Checkpoints are coalesced to limit flash wear: the counters may change on every message, but a
+//! checkpoint is written at most once per [`DiagnosticsAutosave::interval_s`]. When nothing but the
+//! operating time and the message counts has changed, checkpoints are only written every
+//! [`DiagnosticsAutosave::idle_interval_s`]. The message counts are not treated as a change, since
+//! they grow constantly on a live bus, e.g. with heartbeats.
+
+use zencan_common::{
+    objects::{ObjectCode, PdoMappable, SubInfo},
+    sdo::AbortCode,
+    AtomicCell,
+};
+
+use crate::object_dict::ObjectAccess;
+
+/// Internal error conditions recorded by the node
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u32)]
+pub enum InternalError {
+    /// No error has occurred
+    #[default]
+    None = 0,
+    /// A received message overwrote a previous message which had not yet been processed
+    RxOverrun = 1,
+    /// A message could not be queued because the transmit queue was full
+    TxQueueFull = 2,
+    /// A TPDO was triggered again before its previous value was transmitted
+    PdoDropped = 3,
+    /// A received message had a recognized COB ID, but could not be parsed
+    RxMalformed = 4,
+    /// A message generated by the node was dropped because it could not be queued or held for
+    /// retry
+    TxDropped = 5,
+}
+
+/// The kinds of received frame which the node recognizes by COB ID, but may fail to parse
+///
+/// Malformed frames are rejected by [`NodeMbox::store_message`](crate::NodeMbox::store_message),
+/// counted in [`NodeDiagnostics::rx_malformed`], and passed to the
+/// [`Callbacks::malformed_frame`](crate::Callbacks::malformed_frame) callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MalformedFrame {
+    /// An NMT command shorter than 2 bytes, or with an unknown command specifier
+    Nmt,
+    /// An LSS request with an unknown command specifier, or too short for its command
+    Lss,
+    /// A TIME message shorter than 6 bytes
+    Time,
+    /// A request to the SDO server which is not 8 bytes long
+    Sdo,
+    /// A heartbeat from a monitored node with no data, or an invalid NMT state
+    Heartbeat,
+}
+
+/// Counters tracking the communication health of a node
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct NodeDiagnostics {
+    rx_messages: AtomicCell<u32>,
+    tx_messages: AtomicCell<u32>,
+    rx_overruns: AtomicCell<u32>,
+    sdo_aborts: AtomicCell<u32>,
+    pdo_events_dropped: AtomicCell<u32>,
+    tx_overflows: AtomicCell<u32>,
+    last_error: AtomicCell<InternalError>,
+    operating_time_s: AtomicCell<u32>,
+    rx_malformed: AtomicCell<u32>,
+    tx_dropped: AtomicCell<u32>,
+    uptime_s: AtomicCell<u32>,
+}
+
+fn increment(counter: &AtomicCell<u32>, value: u32) {
+    counter.fetch_update(|x| Some(x.wrapping_add(value))).ok();
+}
+
+impl NodeDiagnostics {
+    /// Create a new NodeDiagnostics with all counters at zero
+    pub const fn new() -> Self {
+        Self {
+            rx_messages: AtomicCell::new(0),
+            tx_messages: AtomicCell::new(0),
+            rx_overruns: AtomicCell::new(0),
+            sdo_aborts: AtomicCell::new(0),
+            pdo_events_dropped: AtomicCell::new(0),
+            tx_overflows: AtomicCell::new(0),
+            last_error: AtomicCell::new(InternalError::None),
+            operating_time_s: AtomicCell::new(0),
+            rx_malformed: AtomicCell::new(0),
+            tx_dropped: AtomicCell::new(0),
+            uptime_s: AtomicCell::new(0),
+        }
+    }
+
+    /// Number of messages received and handled by the node
+    pub fn rx_messages(&self) -> u32 {
+        self.rx_messages.load()
+    }
+
+    /// Number of messages handed off for transmission
+    pub fn tx_messages(&self) -> u32 {
+        self.tx_messages.load()
+    }
+
+    /// Number of received messages which overwrote a message not yet processed, or were dropped
+    /// because the SDO request queue was full
+    pub fn rx_overruns(&self) -> u32 {
+        self.rx_overruns.load()
+    }
+
+    /// Number of SDO abort responses sent by the SDO server
+    pub fn sdo_aborts(&self) -> u32 {
+        self.sdo_aborts.load()
+    }
+
+    /// Number of TPDO events which were replaced by a newer event before being transmitted
+    pub fn pdo_events_dropped(&self) -> u32 {
+        self.pdo_events_dropped.load()
+    }
+
+    /// Number of messages which could not be queued because the transmit queue was full
+    ///
+    /// This includes SDO responses dropped because the SDO response queue was full. Messages queued
+    /// by the application are dropped, while messages generated by the node are held and retried;
+    /// see [`Node::pending_tx_count`](crate::Node::pending_tx_count).
+    pub fn tx_overflows(&self) -> u32 {
+        self.tx_overflows.load()
+    }
+
+    /// Number of received messages with a recognized COB ID which could not be parsed
+    ///
+    /// See [`MalformedFrame`] for the frames which are checked
+    pub fn rx_malformed(&self) -> u32 {
+        self.rx_malformed.load()
+    }
+
+    /// Number of messages generated by the node which were dropped
+    ///
+    /// Messages generated by the node which do not fit in the transmit queue are held for retry,
+    /// see [`Node::pending_tx_count`](crate::Node::pending_tx_count). A message is only dropped when
+    /// the retry buffer is also full.
+    pub fn tx_dropped(&self) -> u32 {
+        self.tx_dropped.load()
+    }
+
+    /// The most recent internal error recorded
+    pub fn last_error(&self) -> InternalError {
+        self.last_error.load()
+    }
+
+    /// Total time the node has been running, in seconds
+    ///
+    /// This only accumulates across boots when the counters are restored from a stored
+    /// [`DiagnosticsSnapshot`]
+    pub fn operating_time_s(&self) -> u32 {
+        self.operating_time_s.load()
+    }
+
+    /// Time since the node booted, in seconds
+    ///
+    /// Unlike [`operating_time_s`](Self::operating_time_s), this is not part of the snapshot, and
+    /// always starts from zero on boot
+    pub fn uptime_s(&self) -> u32 {
+        self.uptime_s.load()
+    }
+
+    /// Get a copy of the current value of all counters
+    pub fn snapshot(&self) -> DiagnosticsSnapshot {
+        DiagnosticsSnapshot {
+            rx_messages: self.rx_messages(),
+            tx_messages: self.tx_messages(),
+            rx_overruns: self.rx_overruns(),
+            sdo_aborts: self.sdo_aborts(),
+            pdo_events_dropped: self.pdo_events_dropped(),
+            tx_overflows: self.tx_overflows(),
+            last_error: self.last_error(),
+            operating_time_s: self.operating_time_s(),
+            rx_malformed: self.rx_malformed(),
+            tx_dropped: self.tx_dropped(),
+        }
+    }
+
+    /// Restore all counters from a previously stored snapshot
+    ///
+    /// This should be called on boot, before the node begins processing messages
+    pub fn restore(&self, snapshot: &DiagnosticsSnapshot) {
+        self.rx_messages.store(snapshot.rx_messages);
+        self.tx_messages.store(snapshot.tx_messages);
+        self.rx_overruns.store(snapshot.rx_overruns);
+        self.sdo_aborts.store(snapshot.sdo_aborts);
+        self.pdo_events_dropped.store(snapshot.pdo_events_dropped);
+        self.tx_overflows.store(snapshot.tx_overflows);
+        self.last_error.store(snapshot.last_error);
+        self.operating_time_s.store(snapshot.operating_time_s);
+        self.rx_malformed.store(snapshot.rx_malformed);
+        self.tx_dropped.store(snapshot.tx_dropped);
+    }
+
+    /// Reset all counters to zero, and clear the last error
+    ///
+    /// The operating time is a lifetime statistic, and is not reset
+    pub fn reset(&self) {
+        self.rx_messages.store(0);
+        self.tx_messages.store(0);
+        self.rx_overruns.store(0);
+        self.sdo_aborts.store(0);
+        self.pdo_events_dropped.store(0);
+        self.tx_overflows.store(0);
+        self.rx_malformed.store(0);
+        self.tx_dropped.store(0);
+        self.last_error.store(InternalError::None);
+    }
+
+    pub(crate) fn record_rx(&self) {
+        increment(&self.rx_messages, 1);
+    }
+
+    pub(crate) fn record_tx(&self) {
+        increment(&self.tx_messages, 1);
+    }
+
+    pub(crate) fn record_rx_overrun(&self) {
+        self.record_rx_overruns(1);
+    }
+
+    pub(crate) fn record_rx_overruns(&self, count: u32) {
+        if count > 0 {
+            increment(&self.rx_overruns, count);
+            self.last_error.store(InternalError::RxOverrun);
+        }
+    }
+
+    pub(crate) fn record_sdo_aborts(&self, count: u32) {
+        increment(&self.sdo_aborts, count);
+    }
+
+    pub(crate) fn record_pdo_dropped(&self) {
+        increment(&self.pdo_events_dropped, 1);
+        self.last_error.store(InternalError::PdoDropped);
+    }
+
+    pub(crate) fn record_tx_overflow(&self) {
+        self.record_tx_overflows(1);
+    }
+
+    pub(crate) fn record_tx_overflows(&self, count: u32) {
+        if count > 0 {
+            increment(&self.tx_overflows, count);
+            self.last_error.store(InternalError::TxQueueFull);
+        }
+    }
+
+    pub(crate) fn record_rx_malformed(&self) {
+        increment(&self.rx_malformed, 1);
+        self.last_error.store(InternalError::RxMalformed);
+    }
+
+    pub(crate) fn record_tx_dropped(&self) {
+        increment(&self.tx_dropped, 1);
+        self.last_error.store(InternalError::TxDropped);
+    }
+
+    pub(crate) fn record_operating_time(&self, seconds: u32) {
+        increment(&self.operating_time_s, seconds);
+        increment(&self.uptime_s, seconds);
+    }
+
+    fn read_sub(&self, sub: u8) -> Result<u32, AbortCode> {
+        match sub {
+            1 => Ok(self.rx_messages()),
+            2 => Ok(self.tx_messages()),
+            3 => Ok(self.rx_overruns()),
+            4 => Ok(self.sdo_aborts()),
+            5 => Ok(self.pdo_events_dropped()),
+            6 => Ok(self.last_error() as u32),
+            7 => Ok(self.tx_overflows()),
+            8 => Ok(self.operating_time_s()),
+            9 => Ok(self.rx_malformed()),
+            10 => Ok(self.tx_dropped()),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+}
+
+impl InternalError {
+    fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::None),
+            1 => Some(Self::RxOverrun),
+            2 => Some(Self::TxQueueFull),
+            3 => Some(Self::PdoDropped),
+            4 => Some(Self::RxMalformed),
+            5 => Some(Self::TxDropped),
+            _ => None,
+        }
+    }
+}
+
+/// A copy of all [`NodeDiagnostics`] counters, for storing persistently
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiagnosticsSnapshot {
+    /// Received message count
+    pub rx_messages: u32,
+    /// Transmitted message count
+    pub tx_messages: u32,
+    /// Receive overrun count
+    pub rx_overruns: u32,
+    /// SDO aborts sent
+    pub sdo_aborts: u32,
+    /// TPDO events dropped
+    pub pdo_events_dropped: u32,
+    /// Transmit queue overflow count
+    pub tx_overflows: u32,
+    /// Last internal error
+    pub last_error: InternalError,
+    /// Operating time in seconds
+    pub operating_time_s: u32,
+    /// Malformed frames received
+    pub rx_malformed: u32,
+    /// Node messages dropped
+    pub tx_dropped: u32,
+}
+
+impl DiagnosticsSnapshot {
+    /// Format version stored in the first byte of the serialized snapshot
+    const VERSION: u8 = 3;
+
+    /// The number of bytes in a serialized snapshot
+    pub const SERIALIZED_SIZE: usize = 41;
+
+    /// The size of a version 1 snapshot, which did not include the malformed frame count
+    const V1_SERIALIZED_SIZE: usize = 33;
+
+    /// The size of a version 2 snapshot, which did not include the dropped message count
+    const V2_SERIALIZED_SIZE: usize = 37;
+
+    /// Serialize the snapshot for storage
+    pub fn to_bytes(&self) -> [u8; Self::SERIALIZED_SIZE] {
+        let values = [
+            self.rx_messages,
+            self.tx_messages,
+            self.rx_overruns,
+            self.sdo_aborts,
+            self.pdo_events_dropped,
+            self.tx_overflows,
+            self.last_error as u32,
+            self.operating_time_s,
+            self.rx_malformed,
+            self.tx_dropped,
+        ];
+        let mut bytes = [0; Self::SERIALIZED_SIZE];
+        bytes[0] = Self::VERSION;
+        for (chunk, value) in bytes[1..].chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize a snapshot created by [`DiagnosticsSnapshot::to_bytes`]
+    ///
+    /// Snapshots written by version 1, before the malformed frame count was added, and version 2,
+    /// before the dropped message count was added, are accepted, with the missing counts zero.
+    ///
+    /// Returns None if the data is not a valid snapshot, e.g. because it was written by an
+    /// incompatible version
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match (bytes.first(), bytes.len()) {
+            (Some(&Self::VERSION), Self::SERIALIZED_SIZE) => (),
+            (Some(1), Self::V1_SERIALIZED_SIZE) => (),
+            (Some(2), Self::V2_SERIALIZED_SIZE) => (),
+            _ => return None,
+        }
+        let mut values = [0u32; 10];
+        for (value, chunk) in values.iter_mut().zip(bytes[1..].chunks_exact(4)) {
+            *value = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Some(Self {
+            rx_messages: values[0],
+            tx_messages: values[1],
+            rx_overruns: values[2],
+            sdo_aborts: values[3],
+            pdo_events_dropped: values[4],
+            tx_overflows: values[5],
+            last_error: InternalError::from_u32(values[6])?,
+            operating_time_s: values[7],
+            rx_malformed: values[8],
+            tx_dropped: values[9],
+        })
+    }
+
+    /// Returns true if any counter other than the operating time and message counts differs
+    fn counters_differ(&self, other: &Self) -> bool {
+        Self {
+            rx_messages: other.rx_messages,
+            tx_messages: other.tx_messages,
+            operating_time_s: other.operating_time_s,
+            ..*self
+        } != *other
+    }
+}
+
+/// Policy for automatically checkpointing diagnostics counters to persistent storage
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiagnosticsAutosave {
+    /// Minimum time between checkpoints, in seconds
+    ///
+    /// Any number of counter changes within this interval are coalesced into a single write
+    pub interval_s: u32,
+    /// Time between checkpoints when only the operating time and message counts have changed, in
+    /// seconds
+    ///
+    /// This bounds how much operating time can be lost on power loss for an otherwise idle node
+    pub idle_interval_s: u32,
+}
+
+impl DiagnosticsAutosave {
+    /// Create a new autosave policy
+    pub const fn new(interval_s: u32, idle_interval_s: u32) -> Self {
+        Self {
+            interval_s,
+            idle_interval_s,
+        }
+    }
+
+    /// Determine if a new checkpoint should be written
+    ///
+    /// `saved` is the last stored snapshot, and `elapsed_us` the time since it was stored
+    pub(crate) fn checkpoint_due(
+        &self,
+        saved: &DiagnosticsSnapshot,
+        current: &DiagnosticsSnapshot,
+        elapsed_us: u64,
+    ) -> bool {
+        let interval_s = if current.counters_differ(saved) {
+            self.interval_s
+        } else if current != saved {
+            self.idle_interval_s.max(self.interval_s)
+        } else {
+            return false;
+        };
+        elapsed_us >= interval_s as u64 * 1_000_000
+    }
+}
+
+/// Implements the diagnostics object (0x5F00)
+///
+/// | Sub | Type | Description |
+/// | --- | ---- | ----------- |
+/// | 1   | u32  | Received message count |
+/// | 2   | u32  | Transmitted message count |
+/// | 3   | u32  | Receive overrun count |
+/// | 4   | u32  | SDO aborts sent |
+/// | 5   | u32  | TPDO events dropped |
+/// | 6   | u32  | Last internal error (see [`InternalError`]) |
+/// | 7   | u32  | Transmit queue overflow count |
+/// | 8   | u32  | Operating time in seconds |
+/// | 9   | u32  | Malformed frames received |
+/// | 10  | u32  | Node messages dropped |
+#[allow(missing_debug_implementations)]
+pub struct DiagnosticsObject {
+    diagnostics: &'static NodeDiagnostics,
+}
+
+impl DiagnosticsObject {
+    /// Create a new diagnostics object
+    pub const fn new(diagnostics: &'static NodeDiagnostics) -> Self {
+        Self { diagnostics }
+    }
+}
+
+impl ObjectAccess for DiagnosticsObject {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub == 0 {
+            if offset != 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = 10;
+            return Ok(1);
+        }
+
+        let value_bytes = self.diagnostics.read_sub(sub)?.to_le_bytes();
+        if offset < value_bytes.len() {
+            let read_len = buf.len().min(value_bytes.len() - offset);
+            buf[..read_len].copy_from_slice(&value_bytes[offset..offset + read_len]);
+            Ok(read_len)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        Ok(self.sub_info(sub)?.size)
+    }
+
+    fn write(&self, sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+        self.sub_info(sub)?;
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Record
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        match sub {
+            0 => Ok(SubInfo::MAX_SUB_NUMBER),
+            1..=10 => Ok(SubInfo::new_u32()),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+}
+
+/// Implements the node status object (0x5F05), mapped by the node status TPDO
+///
+/// The sub objects are sized so that all of them fit in a single PDO. The counters are the low bits
+/// of the [`NodeDiagnostics`] counters, so a monitor should compare successive values with wrapping
+/// arithmetic.
+///
+/// | Sub | Type | Description |
+/// | --- | ---- | ----------- |
+/// | 1   | u24  | Uptime in seconds, wrapping after about 194 days |
+/// | 2   | u8   | The error register (0x1001) |
+/// | 3   | u16  | Received message count |
+/// | 4   | u16  | Transmitted message count |
+#[allow(missing_debug_implementations)]
+pub struct NodeStatusObject {
+    diagnostics: &'static NodeDiagnostics,
+    error_register: &'static dyn ObjectAccess,
+}
+
+impl NodeStatusObject {
+    /// Create a new node status object
+    ///
+    /// `error_register` is the error register object (0x1001)
+    pub const fn new(
+        diagnostics: &'static NodeDiagnostics,
+        error_register: &'static dyn ObjectAccess,
+    ) -> Self {
+        Self {
+            diagnostics,
+            error_register,
+        }
+    }
+
+    fn read_sub(&self, sub: u8) -> Result<u32, AbortCode> {
+        match sub {
+            1 => Ok(self.diagnostics.uptime_s() & 0xFF_FFFF),
+            2 => Ok(self.error_register.read_u8(0)? as u32),
+            3 => Ok(self.diagnostics.rx_messages() & 0xFFFF),
+            4 => Ok(self.diagnostics.tx_messages() & 0xFFFF),
+            _ => Err(AbortCode::NoSuchSubIndex),
+        }
+    }
+}
+
+impl ObjectAccess for NodeStatusObject {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        let size = self.sub_info(sub)?.size;
+        let value_bytes = if sub == 0 {
+            [4, 0, 0, 0]
+        } else {
+            self.read_sub(sub)?.to_le_bytes()
+        };
+        if offset < size {
+            let read_len = buf.len().min(size - offset);
+            buf[..read_len].copy_from_slice(&value_bytes[offset..offset + read_len]);
+            Ok(read_len)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        Ok(self.sub_info(sub)?.size)
+    }
+
+    fn write(&self, sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+        self.sub_info(sub)?;
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Record
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        let info = match sub {
+            0 => return Ok(SubInfo::MAX_SUB_NUMBER),
+            1 => SubInfo::new_u24(),
+            2 => SubInfo::new_u8(),
+            3 | 4 => SubInfo::new_u16(),
+            _ => return Err(AbortCode::NoSuchSubIndex),
+        };
+        Ok(info.pdo_mapping(PdoMappable::Tpdo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_dict::{ProvidesSubObjects, ScalarField, SubObjectAccess};
+
+    #[test]
+    fn test_diagnostics_object() {
+        let diagnostics = Box::leak(Box::new(NodeDiagnostics::new()));
+        let object = DiagnosticsObject::new(diagnostics);
+
+        diagnostics.record_rx();
+        diagnostics.record_rx();
+        diagnostics.record_tx();
+        diagnostics.record_sdo_aborts(3);
+        diagnostics.record_pdo_dropped();
+        diagnostics.record_tx_overflow();
+        diagnostics.record_rx_malformed();
+
+        diagnostics.record_operating_time(5);
+
+        assert_eq!(10, object.read_u8(0).unwrap());
+        assert_eq!(2, object.read_u32(1).unwrap());
+        assert_eq!(1, object.read_u32(2).unwrap());
+        assert_eq!(0, object.read_u32(3).unwrap());
+        assert_eq!(3, object.read_u32(4).unwrap());
+        assert_eq!(1, object.read_u32(5).unwrap());
+        assert_eq!(
+            InternalError::RxMalformed as u32,
+            object.read_u32(6).unwrap()
+        );
+        assert_eq!(1, object.read_u32(7).unwrap());
+        assert_eq!(5, object.read_u32(8).unwrap());
+        assert_eq!(1, object.read_u32(9).unwrap());
+        assert_eq!(0, object.read_u32(10).unwrap());
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.read_u32(11));
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(1, &[0; 4]));
+
+        diagnostics.reset();
+        assert_eq!(0, object.read_u32(1).unwrap());
+        assert_eq!(0, object.read_u32(9).unwrap());
+        assert_eq!(InternalError::None, diagnostics.last_error());
+        // Operating time survives a reset
+        assert_eq!(5, object.read_u32(8).unwrap());
+    }
+
+    struct ErrorRegister {
+        value: ScalarField<u8>,
+    }
+
+    impl ProvidesSubObjects for ErrorRegister {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                0 => Some((SubInfo::new_u8(), &self.value)),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Var
+        }
+    }
+
+    #[test]
+    fn test_node_status_object() {
+        let diagnostics = Box::leak(Box::new(NodeDiagnostics::new()));
+        let error_register = Box::leak(Box::new(ErrorRegister {
+            value: ScalarField::<u8>::new(0x11),
+        }));
+        let object = NodeStatusObject::new(diagnostics, error_register);
+
+        for _ in 0..0x10002 {
+            diagnostics.record_rx();
+        }
+        diagnostics.record_tx();
+        diagnostics.record_operating_time(0x100_0005);
+
+        assert_eq!(4, object.read_u8(0).unwrap());
+        assert_eq!(3, object.read_size(1).unwrap());
+        assert_eq!(1, object.read_size(2).unwrap());
+        assert_eq!(2, object.read_size(3).unwrap());
+        assert_eq!(2, object.read_size(4).unwrap());
+
+        // Values wrap to the size of their sub object
+        let mut buf = [0; 4];
+        assert_eq!(3, object.read(1, 0, &mut buf).unwrap());
+        assert_eq!([5, 0, 0], buf[..3]);
+        assert_eq!(0x11, object.read_u8(2).unwrap());
+        assert_eq!(2, object.read_u16(3).unwrap());
+        assert_eq!(1, object.read_u16(4).unwrap());
+
+        // Uptime is not cleared by a counter reset
+        diagnostics.reset();
+        assert_eq!(0, object.read_u16(3).unwrap());
+        assert_eq!(3, object.read(1, 0, &mut buf).unwrap());
+        assert_eq!([5, 0, 0], buf[..3]);
+
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.read_size(5));
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(2, &[0]));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let diagnostics = NodeDiagnostics::new();
+        diagnostics.record_rx();
+        diagnostics.record_sdo_aborts(2);
+        diagnostics.record_pdo_dropped();
+        diagnostics.record_rx_malformed();
+        diagnostics.record_tx_dropped();
+        diagnostics.record_pdo_dropped();
+        diagnostics.record_operating_time(3600);
+
+        let snapshot = diagnostics.snapshot();
+        let bytes = snapshot.to_bytes();
+        assert_eq!(Some(snapshot), DiagnosticsSnapshot::from_bytes(&bytes));
+
+        let restored = NodeDiagnostics::new();
+        restored.restore(&DiagnosticsSnapshot::from_bytes(&bytes).unwrap());
+        assert_eq!(snapshot, restored.snapshot());
+        assert_eq!(InternalError::PdoDropped, restored.last_error());
+
+        // Erased flash or a different format is rejected
+        assert_eq!(
+            None,
+            DiagnosticsSnapshot::from_bytes(&[0xff; DiagnosticsSnapshot::SERIALIZED_SIZE])
+        );
+        assert_eq!(None, DiagnosticsSnapshot::from_bytes(&bytes[..10]));
+
+        // A version 1 snapshot has no malformed frame count
+        let mut v1 = [0; 33];
+        v1[0] = 1;
+        v1[1..33].copy_from_slice(&bytes[1..33]);
+        let restored = DiagnosticsSnapshot::from_bytes(&v1).unwrap();
+        assert_eq!(0, restored.rx_malformed);
+        assert_eq!(snapshot.rx_messages, restored.rx_messages);
+        assert_eq!(snapshot.operating_time_s, restored.operating_time_s);
+        assert_eq!(None, DiagnosticsSnapshot::from_bytes(&bytes[..33]));
+
+        // A version 2 snapshot has no dropped message count
+        let mut v2 = [0; 37];
+        v2[0] = 2;
+        v2[1..37].copy_from_slice(&bytes[1..37]);
+        let restored = DiagnosticsSnapshot::from_bytes(&v2).unwrap();
+        assert_eq!(0, restored.tx_dropped);
+        assert_eq!(snapshot.rx_malformed, restored.rx_malformed);
+        assert_eq!(None, DiagnosticsSnapshot::from_bytes(&bytes[..37]));
+    }
+
+    #[test]
+    fn test_autosave_policy() {
+        let policy = DiagnosticsAutosave::new(60, 3600);
+        let saved = DiagnosticsSnapshot::default();
+
+        // Nothing changed
+        assert!(!policy.checkpoint_due(&saved, &saved, 10_000_000_000));
+
+        // Counter changes are coalesced until the interval has elapsed
+        let counters = DiagnosticsSnapshot {
+            sdo_aborts: 1,
+            operating_time_s: 10,
+            ..saved
+        };
+        assert!(!policy.checkpoint_due(&saved, &counters, 59_999_999));
+        assert!(policy.checkpoint_due(&saved, &counters, 60_000_000));
+
+        // Operating time alone only triggers after the idle interval
+        let idle = DiagnosticsSnapshot {
+            operating_time_s: 600,
+            ..saved
+        };
+        assert!(!policy.checkpoint_due(&saved, &idle, 600_000_000));
+        assert!(policy.checkpoint_due(&saved, &idle, 3_600_000_000));
+
+        // Message traffic on an otherwise idle node also uses the idle interval
+        let traffic = DiagnosticsSnapshot {
+            rx_messages: 1200,
+            tx_messages: 600,
+            ..saved
+        };
+        assert!(!policy.checkpoint_due(&saved, &traffic, 600_000_000));
+        assert!(policy.checkpoint_due(&saved, &traffic, 3_600_000_000));
+        let traffic = DiagnosticsSnapshot {
+            operating_time_s: 600,
+            ..traffic
+        };
+        assert!(!policy.checkpoint_due(&saved, &traffic, 600_000_000));
+        assert!(policy.checkpoint_due(&saved, &traffic, 3_600_000_000));
+    }
+}
@@ -0,0 +1,611 @@
+//! Persistent storage of objects and node config on a NOR flash region
+//!
+//! [`FlashStorage`] implements the `store_node_config` and `store_objects` callbacks, and the
+//! matching restore operations, on top of any [`NorFlash`] implementation from the
+//! `embedded-storage` crate.
+//!
+//! The region is split into two banks, and each store writes a complete image -- the node ID and
+//! the serialized objects -- to the bank which is not currently in use. Each image has a sequence
+//! number and a CRC32, and the valid image with the highest sequence number is the current one. A
+//! store which is interrupted, e.g. by a power loss, leaves the previous image intact.
+//!
+//! ```ignore
+//! let storage = FlashStorage::new(flash).unwrap();
+//!
+//! let node_id = storage.node_id().unwrap_or(NodeId::Unconfigured);
+//! let mut store_node_config = storage.store_node_config_callback();
+//! let mut store_objects = storage.store_objects_callback();
+//! let callbacks = Callbacks {
+//!     store_node_config: Some(&mut store_node_config),
+//!     store_objects: Some(&mut store_objects),
+//!     ..Default::default()
+//! };
+//!
+//! // On RESET_APP, restore the saved objects
+//! let mut buf = [0; 512];
+//! storage.restore_objects(&zencan::OD_TABLE, &mut buf).ok();
+//! ```
+use core::{cell::RefCell, convert::Infallible, fmt};
+
+use defmt_or_log::warn;
+use embedded_storage::nor_flash::{NorFlash, NorFlashError, NorFlashErrorKind};
+use zencan_common::{crc32::Crc32, NodeId};
+
+use crate::{
+    object_dict::ODEntry,
+    persist::{restore_stored_comm_objects, restore_stored_objects},
+};
+
+/// Identifies the start of a stored image
+const MAGIC: u32 = 0x5A43_5346;
+/// Size of the image header: magic, sequence, node ID, reserved, and objects length
+const HEADER_SIZE: usize = 16;
+/// Size of the CRC following the objects data
+const CRC_SIZE: usize = 4;
+/// Size of the buffer used for reading and writing the flash
+///
+/// The read and write sizes of the flash must evenly divide it
+const CHUNK_SIZE: usize = 32;
+
+/// Errors returned by [`FlashStorage`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlashStorageError {
+    /// The flash driver returned an error
+    Flash(NorFlashErrorKind),
+    /// The flash region is too small to hold two banks, or its read or write size is not supported
+    InvalidRegion,
+    /// The data to store does not fit in a bank
+    OutOfSpace,
+    /// The buffer provided is too small for the stored objects
+    BufferTooSmall,
+    /// The reader ended before the expected number of bytes were read
+    UnexpectedEof,
+}
+
+impl fmt::Display for FlashStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flash(kind) => write!(f, "flash error: {kind}"),
+            Self::InvalidRegion => write!(f, "flash region is not usable for storage"),
+            Self::OutOfSpace => write!(f, "data does not fit in the flash region"),
+            Self::BufferTooSmall => write!(f, "buffer is too small for the stored objects"),
+            Self::UnexpectedEof => write!(f, "reader ended before all data was read"),
+        }
+    }
+}
+
+impl core::error::Error for FlashStorageError {}
+
+fn flash_err<E: NorFlashError>(e: E) -> FlashStorageError {
+    FlashStorageError::Flash(e.kind())
+}
+
+/// The header of a valid image
+#[derive(Clone, Copy, Debug)]
+struct ImageHeader {
+    bank: u32,
+    sequence: u32,
+    node_id: u8,
+    objects_len: usize,
+}
+
+/// Buffers writes to a bank so that the flash is always written in aligned chunks
+struct BankWriter {
+    offset: u32,
+    end: u32,
+    buf: [u8; CHUNK_SIZE],
+    fill: usize,
+    crc: Crc32,
+}
+
+impl BankWriter {
+    fn new(start: u32, end: u32) -> Self {
+        Self {
+            offset: start,
+            end,
+            buf: [0xFF; CHUNK_SIZE],
+            fill: 0,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// Write data which is covered by the CRC
+    fn write<F: NorFlash>(&mut self, flash: &mut F, data: &[u8]) -> Result<(), FlashStorageError> {
+        self.crc.update(data);
+        self.write_raw(flash, data)
+    }
+
+    fn write_raw<F: NorFlash>(
+        &mut self,
+        flash: &mut F,
+        mut data: &[u8],
+    ) -> Result<(), FlashStorageError> {
+        while !data.is_empty() {
+            let n = data.len().min(CHUNK_SIZE - self.fill);
+            self.buf[self.fill..self.fill + n].copy_from_slice(&data[..n]);
+            self.fill += n;
+            data = &data[n..];
+            if self.fill == CHUNK_SIZE {
+                self.flush(flash)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush<F: NorFlash>(&mut self, flash: &mut F) -> Result<(), FlashStorageError> {
+        if self.fill == 0 {
+            return Ok(());
+        }
+        let len = self.fill.next_multiple_of(F::WRITE_SIZE);
+        self.buf[self.fill..len].fill(0xFF);
+        if self.offset + len as u32 > self.end {
+            return Err(FlashStorageError::OutOfSpace);
+        }
+        flash
+            .write(self.offset, &self.buf[..len])
+            .map_err(flash_err)?;
+        self.offset += len as u32;
+        self.fill = 0;
+        Ok(())
+    }
+
+    /// Write the CRC of everything written so far, and flush the remaining data
+    fn finish<F: NorFlash>(mut self, flash: &mut F) -> Result<(), FlashStorageError> {
+        let crc = self.crc.finish();
+        self.write_raw(flash, &crc.to_le_bytes())?;
+        self.flush(flash)
+    }
+}
+
+/// Stores objects and node config in a NOR flash region
+///
+/// See the [module docs](self) for the storage format and usage.
+///
+/// The flash is held in a `RefCell`, so that the store callbacks -- which may not take a mutable
+/// reference -- can be created from a shared reference.
+#[allow(missing_debug_implementations)]
+pub struct FlashStorage<F> {
+    flash: RefCell<F>,
+    bank_size: u32,
+}
+
+impl<F: NorFlash> FlashStorage<F> {
+    /// Create a FlashStorage using the whole of `flash`
+    ///
+    /// The region is split into two banks, each a multiple of the erase size. Returns
+    /// [`FlashStorageError::InvalidRegion`] if the read or write size of the flash does not divide
+    /// 32 bytes, or if the region is smaller than two erase sectors.
+    pub fn new(flash: F) -> Result<Self, FlashStorageError> {
+        if CHUNK_SIZE % F::READ_SIZE != 0 || CHUNK_SIZE % F::WRITE_SIZE != 0 {
+            return Err(FlashStorageError::InvalidRegion);
+        }
+        let bank_size = flash.capacity() / 2 / F::ERASE_SIZE * F::ERASE_SIZE;
+        if bank_size < HEADER_SIZE + CRC_SIZE {
+            return Err(FlashStorageError::InvalidRegion);
+        }
+        Ok(Self {
+            flash: RefCell::new(flash),
+            bank_size: bank_size as u32,
+        })
+    }
+
+    /// Release the flash
+    pub fn into_inner(self) -> F {
+        self.flash.into_inner()
+    }
+
+    /// The largest serialized object data which can be stored
+    pub fn max_objects_len(&self) -> usize {
+        self.bank_size as usize - HEADER_SIZE - CRC_SIZE
+    }
+
+    /// Read the stored node ID
+    ///
+    /// Returns `NodeId::Unconfigured` if no node ID has been stored.
+    pub fn node_id(&self) -> Result<NodeId, FlashStorageError> {
+        let node_id = self.current()?.map(|h| h.node_id).unwrap_or(255);
+        Ok(NodeId::new(node_id).unwrap_or(NodeId::Unconfigured))
+    }
+
+    /// Get the size of the stored object data, or 0 if no objects have been stored
+    pub fn objects_len(&self) -> Result<usize, FlashStorageError> {
+        Ok(self.current()?.map(|h| h.objects_len).unwrap_or(0))
+    }
+
+    /// Read the stored object data into `buf`
+    ///
+    /// Returns the part of `buf` holding the data, which is empty if no objects have been stored.
+    pub fn read_objects<'b>(&self, buf: &'b mut [u8]) -> Result<&'b [u8], FlashStorageError> {
+        let Some(header) = self.current()? else {
+            return Ok(&buf[..0]);
+        };
+        if buf.len() < header.objects_len {
+            return Err(FlashStorageError::BufferTooSmall);
+        }
+        let data = &mut buf[..header.objects_len];
+        self.read(header.bank + HEADER_SIZE as u32, data)?;
+        Ok(data)
+    }
+
+    /// Restore all stored objects to the object dictionary
+    ///
+    /// `buf` is used to hold the stored data while it is restored, and must be at least
+    /// [`objects_len`](Self::objects_len) bytes.
+    pub fn restore_objects(&self, od: &[ODEntry], buf: &mut [u8]) -> Result<(), FlashStorageError> {
+        restore_stored_objects(od, self.read_objects(buf)?);
+        Ok(())
+    }
+
+    /// Restore the stored communication objects (0x1000-0x1fff) to the object dictionary
+    ///
+    /// `buf` is used to hold the stored data while it is restored, and must be at least
+    /// [`objects_len`](Self::objects_len) bytes.
+    pub fn restore_comm_objects(
+        &self,
+        od: &[ODEntry],
+        buf: &mut [u8],
+    ) -> Result<(), FlashStorageError> {
+        restore_stored_comm_objects(od, self.read_objects(buf)?);
+        Ok(())
+    }
+
+    /// Store a node ID, keeping the stored objects
+    pub fn store_node_config(&self, node_id: NodeId) -> Result<(), FlashStorageError> {
+        let current = self.current()?;
+        let objects_len = current.map(|h| h.objects_len).unwrap_or(0);
+        let mut flash = self.flash.borrow_mut();
+        let mut writer = self.begin_image(&mut flash, current, node_id.raw(), objects_len)?;
+        if let Some(current) = current {
+            let mut chunk = [0; CHUNK_SIZE];
+            let mut pos = 0;
+            while pos < objects_len {
+                let n = (objects_len - pos).min(CHUNK_SIZE);
+                Self::read_from(
+                    &mut flash,
+                    current.bank + (HEADER_SIZE + pos) as u32,
+                    &mut chunk[..n],
+                )?;
+                writer.write(&mut *flash, &chunk[..n])?;
+                pos += n;
+            }
+        }
+        writer.finish(&mut *flash)
+    }
+
+    /// Store serialized object data, keeping the stored node ID
+    ///
+    /// `len` bytes are read from `reader`, as provided to the `store_objects` callback.
+    pub fn store_objects(
+        &self,
+        reader: &mut dyn embedded_io::Read<Error = Infallible>,
+        len: usize,
+    ) -> Result<(), FlashStorageError> {
+        if len > self.max_objects_len() {
+            return Err(FlashStorageError::OutOfSpace);
+        }
+        let current = self.current()?;
+        let node_id = current.map(|h| h.node_id).unwrap_or(255);
+        let mut flash = self.flash.borrow_mut();
+        let mut writer = self.begin_image(&mut flash, current, node_id, len)?;
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut pos = 0;
+        while pos < len {
+            let n = (len - pos).min(CHUNK_SIZE);
+            let Ok(n) = reader.read(&mut chunk[..n]);
+            if n == 0 {
+                return Err(FlashStorageError::UnexpectedEof);
+            }
+            writer.write(&mut *flash, &chunk[..n])?;
+            pos += n;
+        }
+        writer.finish(&mut *flash)
+    }
+
+    /// Erase both banks, clearing the stored node ID and objects
+    pub fn clear(&self) -> Result<(), FlashStorageError> {
+        self.flash
+            .borrow_mut()
+            .erase(0, 2 * self.bank_size)
+            .map_err(flash_err)
+    }
+
+    /// Get a closure which stores the node ID, for use as [`Callbacks::store_node_config`]
+    ///
+    /// Errors are logged and dropped.
+    ///
+    /// [`Callbacks::store_node_config`]: crate::Callbacks::store_node_config
+    pub fn store_node_config_callback(&self) -> impl FnMut(NodeId) + '_ {
+        move |node_id| {
+            if let Err(e) = self.store_node_config(node_id) {
+                warn!("Failed to store node config: {:?}", e);
+            }
+        }
+    }
+
+    /// Get a closure which stores objects, for use as [`Callbacks::store_objects`]
+    ///
+    /// Errors are logged and dropped.
+    ///
+    /// [`Callbacks::store_objects`]: crate::Callbacks::store_objects
+    pub fn store_objects_callback(
+        &self,
+    ) -> impl Fn(&mut dyn embedded_io::Read<Error = Infallible>, usize) + '_ {
+        move |reader, len| {
+            if let Err(e) = self.store_objects(reader, len) {
+                warn!("Failed to store objects: {:?}", e);
+            }
+        }
+    }
+
+    /// Erase the bank which is not in use, and write the header of a new image to it
+    fn begin_image(
+        &self,
+        flash: &mut F,
+        current: Option<ImageHeader>,
+        node_id: u8,
+        objects_len: usize,
+    ) -> Result<BankWriter, FlashStorageError> {
+        let (bank, sequence) = match current {
+            Some(h) => (self.bank_size - h.bank, h.sequence.wrapping_add(1)),
+            None => (0, 0),
+        };
+        flash
+            .erase(bank, bank + self.bank_size)
+            .map_err(flash_err)?;
+        let mut header = [0xFF; HEADER_SIZE];
+        header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        header[8] = node_id;
+        header[12..16].copy_from_slice(&(objects_len as u32).to_le_bytes());
+        let mut writer = BankWriter::new(bank, bank + self.bank_size);
+        writer.write(flash, &header)?;
+        Ok(writer)
+    }
+
+    /// Find the newest valid image
+    fn current(&self) -> Result<Option<ImageHeader>, FlashStorageError> {
+        let a = self.check_bank(0)?;
+        let b = self.check_bank(self.bank_size)?;
+        Ok(match (a, b) {
+            (Some(a), Some(b)) => {
+                if (b.sequence.wrapping_sub(a.sequence) as i32) > 0 {
+                    Some(b)
+                } else {
+                    Some(a)
+                }
+            }
+            (a, b) => a.or(b),
+        })
+    }
+
+    /// Read the header of the image in a bank, and check its CRC
+    fn check_bank(&self, bank: u32) -> Result<Option<ImageHeader>, FlashStorageError> {
+        let mut header = [0; HEADER_SIZE];
+        self.read(bank, &mut header)?;
+        if u32::from_le_bytes(header[0..4].try_into().unwrap()) != MAGIC {
+            return Ok(None);
+        }
+        let objects_len = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+        if objects_len > self.max_objects_len() {
+            return Ok(None);
+        }
+
+        let mut crc = Crc32::new();
+        crc.update(&header);
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut pos = 0;
+        while pos < objects_len {
+            let n = (objects_len - pos).min(CHUNK_SIZE);
+            self.read(bank + (HEADER_SIZE + pos) as u32, &mut chunk[..n])?;
+            crc.update(&chunk[..n]);
+            pos += n;
+        }
+        let mut stored_crc = [0; CRC_SIZE];
+        self.read(bank + (HEADER_SIZE + objects_len) as u32, &mut stored_crc)?;
+        if u32::from_le_bytes(stored_crc) != crc.finish() {
+            return Ok(None);
+        }
+
+        Ok(Some(ImageHeader {
+            bank,
+            sequence: u32::from_le_bytes(header[4..8].try_into().unwrap()),
+            node_id: header[8],
+            objects_len,
+        }))
+    }
+
+    fn read(&self, offset: u32, out: &mut [u8]) -> Result<(), FlashStorageError> {
+        Self::read_from(&mut self.flash.borrow_mut(), offset, out)
+    }
+
+    /// Read from any offset, by reading aligned chunks of the flash
+    fn read_from(
+        flash: &mut F,
+        mut offset: u32,
+        mut out: &mut [u8],
+    ) -> Result<(), FlashStorageError> {
+        let mut chunk = [0; CHUNK_SIZE];
+        while !out.is_empty() {
+            let skip = offset as usize % F::READ_SIZE;
+            let aligned = offset - skip as u32;
+            let read_len = (skip + out.len())
+                .next_multiple_of(F::READ_SIZE)
+                .min(CHUNK_SIZE);
+            flash
+                .read(aligned, &mut chunk[..read_len])
+                .map_err(flash_err)?;
+            let n = (read_len - skip).min(out.len());
+            out[..n].copy_from_slice(&chunk[skip..skip + n]);
+            out = &mut out[n..];
+            offset += n as u32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_storage::nor_flash::{ErrorType, ReadNorFlash};
+
+    /// A RAM NOR flash, which checks alignment and that only erased bytes are written
+    struct RamFlash<const N: usize> {
+        data: [u8; N],
+        /// Fail writes after this many more bytes, to simulate an interrupted store
+        write_limit: Option<usize>,
+    }
+
+    impl<const N: usize> RamFlash<N> {
+        fn new() -> Self {
+            Self {
+                data: [0xFF; N],
+                write_limit: None,
+            }
+        }
+    }
+
+    impl<const N: usize> ErrorType for RamFlash<N> {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl<const N: usize> ReadNorFlash for RamFlash<N> {
+        const READ_SIZE: usize = 4;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::check_read(self, offset, bytes.len())?;
+            bytes.copy_from_slice(&self.data[offset as usize..offset as usize + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            N
+        }
+    }
+
+    impl<const N: usize> NorFlash for RamFlash<N> {
+        const WRITE_SIZE: usize = 8;
+        const ERASE_SIZE: usize = 64;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::check_erase(self, from, to)?;
+            self.data[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::check_write(self, offset, bytes.len())?;
+            if let Some(limit) = self.write_limit.as_mut() {
+                if *limit < bytes.len() {
+                    return Err(NorFlashErrorKind::Other);
+                }
+                *limit -= bytes.len();
+            }
+            let dest = &mut self.data[offset as usize..offset as usize + bytes.len()];
+            assert!(dest.iter().all(|b| *b == 0xFF), "write to unerased flash");
+            dest.copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    fn store(storage: &FlashStorage<impl NorFlash>, data: &[u8]) -> Result<(), FlashStorageError> {
+        let mut reader = data;
+        storage.store_objects(&mut reader, data.len())
+    }
+
+    fn stored(storage: &FlashStorage<impl NorFlash>) -> Vec<u8> {
+        let mut buf = [0; 256];
+        storage.read_objects(&mut buf).unwrap().to_vec()
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let storage = FlashStorage::new(RamFlash::<512>::new()).unwrap();
+        assert_eq!(NodeId::Unconfigured, storage.node_id().unwrap());
+        assert_eq!(0, storage.objects_len().unwrap());
+        assert!(stored(&storage).is_empty());
+
+        let data: Vec<u8> = (0..77).collect();
+        store(&storage, &data).unwrap();
+        assert_eq!(data, stored(&storage));
+        assert_eq!(NodeId::Unconfigured, storage.node_id().unwrap());
+
+        // Storing the node ID keeps the objects, and storing objects keeps the node ID
+        let node_id = NodeId::new(12).unwrap();
+        storage.store_node_config(node_id).unwrap();
+        assert_eq!(node_id, storage.node_id().unwrap());
+        assert_eq!(data, stored(&storage));
+        store(&storage, &[1, 2, 3]).unwrap();
+        assert_eq!(node_id, storage.node_id().unwrap());
+        assert_eq!(vec![1, 2, 3], stored(&storage));
+
+        // The data survives re-opening the flash
+        let storage = FlashStorage::new(storage.into_inner()).unwrap();
+        assert_eq!(node_id, storage.node_id().unwrap());
+        assert_eq!(vec![1, 2, 3], stored(&storage));
+
+        let mut buf = [0; 2];
+        assert_eq!(
+            Err(FlashStorageError::BufferTooSmall),
+            storage.read_objects(&mut buf).map(|_| ())
+        );
+
+        storage.clear().unwrap();
+        assert_eq!(NodeId::Unconfigured, storage.node_id().unwrap());
+        assert_eq!(0, storage.objects_len().unwrap());
+    }
+
+    #[test]
+    fn test_interrupted_store() {
+        let storage = FlashStorage::new(RamFlash::<512>::new()).unwrap();
+        store(&storage, &[1, 2, 3, 4]).unwrap();
+        store(&storage, &[5, 6, 7, 8]).unwrap();
+
+        // Fail part way through the next store
+        storage.flash.borrow_mut().write_limit = Some(40);
+        let data = [9; 100];
+        assert_eq!(
+            Err(FlashStorageError::Flash(NorFlashErrorKind::Other)),
+            store(&storage, &data)
+        );
+        storage.flash.borrow_mut().write_limit = None;
+        assert_eq!(vec![5, 6, 7, 8], stored(&storage));
+
+        // The next store succeeds, and takes over from the previous image
+        store(&storage, &data).unwrap();
+        assert_eq!(data.to_vec(), stored(&storage));
+    }
+
+    #[test]
+    fn test_region_size() {
+        assert_eq!(
+            Some(FlashStorageError::InvalidRegion),
+            FlashStorage::new(RamFlash::<100>::new()).err()
+        );
+        let storage = FlashStorage::new(RamFlash::<200>::new()).unwrap();
+        assert_eq!(64 - HEADER_SIZE - CRC_SIZE, storage.max_objects_len());
+        assert_eq!(
+            Err(FlashStorageError::OutOfSpace),
+            store(&storage, &[0; 45])
+        );
+        store(&storage, &[0; 44]).unwrap();
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let storage = FlashStorage::new(RamFlash::<512>::new()).unwrap();
+        let mut store_node_config = storage.store_node_config_callback();
+        let mut store_objects = storage.store_objects_callback();
+        let mut callbacks = crate::Callbacks {
+            store_node_config: Some(&mut store_node_config),
+            store_objects: Some(&mut store_objects),
+            ..Default::default()
+        };
+
+        (callbacks.store_node_config.as_mut().unwrap())(NodeId::new(5).unwrap());
+        let mut reader: &[u8] = &[1, 2];
+        (callbacks.store_objects.as_mut().unwrap())(&mut reader, 2);
+        assert_eq!(NodeId::new(5).unwrap(), storage.node_id().unwrap());
+        assert_eq!(vec![1, 2], stored(&storage));
+    }
+}
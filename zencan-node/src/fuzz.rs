@@ -0,0 +1,465 @@
+//! Deterministic protocol frontends for fuzzing
+//!
+//! Each harness wraps one of the node's protocol state machines with state which it owns, rather
+//! than the statics created by the generated code, so that arbitrary frames can be fed into a
+//! fresh instance on every fuzz iteration with no state carried between them. Frames are parsed
+//! the same way as by the [`NodeMbox`](crate::NodeMbox), and frames which it would count as
+//! malformed are rejected with a [`MalformedFrame`].
+//!
+//! - [`SdoServerHarness`] runs an SDO server against a caller provided object dictionary
+//! - [`LssSlaveHarness`] runs the LSS slave
+//! - [`NmtHarness`] applies NMT commands to a node's NMT state
+//!
+//! No time passes unless it is passed to [`SdoServerHarness::process`], so the result of a
+//! sequence of frames is always the same.
+//!
+//! The fuzz targets which use these are in the `fuzz` directory of the repository, and are run
+//! with `cargo fuzz`.
+
+use zencan_common::{
+    lss::{LssIdentity, LssRequest, LssResponse},
+    messages::{CanId, CanMessage, NmtCommand, NMT_CMD_ID},
+    nmt::NmtState,
+    objects::ObjectId,
+    NodeId,
+};
+
+use crate::{
+    diagnostics::MalformedFrame,
+    lss_slave::{self, LssConfig, LssContext, LssReceiver, LssSlave},
+    nmt_slave::{self, NmtContext},
+    object_dict::ODEntry,
+    SdoAccess, StandaloneSdoServer,
+};
+
+/// Runs an SDO server on frames fed to it
+///
+/// This is a [`StandaloneSdoServer`] which takes the source of each request as an argument, so
+/// that requests from several clients can be interleaved.
+#[allow(missing_debug_implementations)]
+pub struct SdoServerHarness<'a> {
+    server: StandaloneSdoServer<'a>,
+}
+
+impl<'a> SdoServerHarness<'a> {
+    /// Create a new harness
+    ///
+    /// `buffer` is used for segmented and block transfers, and should normally be
+    /// [`SDO_BUFFER_SIZE`](crate::SDO_BUFFER_SIZE) bytes. `od` is the object dictionary accessed
+    /// by the server.
+    pub fn new(buffer: &'a mut [u8], od: &'a [ODEntry<'a>]) -> Self {
+        // Requests are fed in with their source and responses are returned as data, so the COB
+        // IDs of the server are never used
+        Self {
+            server: StandaloneSdoServer::new(CanId::Std(0x600), CanId::Std(0x580), buffer, od),
+        }
+    }
+
+    /// Set the maximum number of queued requests handled in each call to
+    /// [`process`](Self::process)
+    pub fn set_request_budget(&mut self, budget: usize) {
+        self.server.set_request_budget(budget);
+    }
+
+    /// Receive an SDO request frame from the client with COB ID `source`
+    ///
+    /// Requests are queued until [`process`](Self::process) is called, except for block download
+    /// segments, which are written to the buffer as they are received.
+    pub fn feed(&mut self, source: CanId, data: &[u8]) -> Result<(), MalformedFrame> {
+        if data.len() != 8 {
+            return Err(MalformedFrame::Sdo);
+        }
+        self.server.receive_data(source, data);
+        Ok(())
+    }
+
+    /// Handle the queued requests, after `elapsed_us` microseconds have passed
+    ///
+    /// Returns the object updated by a completed download, if any. `on_access` is called for
+    /// each transfer which completes or is aborted.
+    pub fn process(
+        &mut self,
+        elapsed_us: u32,
+        on_access: &mut dyn FnMut(SdoAccess),
+    ) -> Option<ObjectId> {
+        self.server.process(elapsed_us, on_access)
+    }
+
+    /// Get the next response frame to be sent to the client
+    pub fn next_response(&mut self) -> Option<[u8; 8]> {
+        self.server.next_response_data()
+    }
+
+    /// Return to idle, abandoning any transfer in progress, as on an NMT reset
+    pub fn reset(&mut self) {
+        self.server.reset();
+    }
+}
+
+/// The node state acted on by the LSS and NMT harnesses
+///
+/// This stands in for the [`Node`](crate::Node), so that commands are handled by the same
+/// [`nmt_slave`] and [`lss_slave`] functions.
+struct HarnessNode {
+    node_id: NodeId,
+    reassigned_node_id: Option<NodeId>,
+    state: NmtState,
+    lss_slave: LssSlave,
+    identity: LssIdentity,
+    store_supported: bool,
+    stored: bool,
+    bootups: u32,
+}
+
+impl HarnessNode {
+    /// Create a node, and boot it
+    fn new(identity: LssIdentity, node_id: NodeId, store_supported: bool) -> Self {
+        let mut node = Self {
+            node_id,
+            reassigned_node_id: None,
+            state: NmtState::Bootup,
+            lss_slave: LssSlave::new(LssConfig {
+                identity,
+                node_id,
+                store_supported,
+            }),
+            identity,
+            store_supported,
+            stored: false,
+            bootups: 0,
+        };
+        nmt_slave::boot(&mut node, 0);
+        node
+    }
+}
+
+impl NmtContext for HarnessNode {
+    fn nmt_state(&self) -> NmtState {
+        self.state
+    }
+
+    fn enter_operational(&mut self) {
+        self.state = NmtState::Operational;
+    }
+
+    fn enter_stopped(&mut self) {
+        self.state = NmtState::Stopped;
+    }
+
+    fn enter_preoperational(&mut self) {
+        self.state = NmtState::PreOperational;
+    }
+
+    fn enter_bootup(&mut self) {
+        self.state = NmtState::Bootup;
+    }
+
+    // The harness has no parameters to reset
+    fn reset_app(&mut self) {}
+
+    fn reset_comm(&mut self) {}
+
+    fn apply_reassigned_node_id(&mut self) -> bool {
+        match self.reassigned_node_id.take() {
+            Some(node_id) => {
+                self.node_id = node_id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn boot_up(&mut self, _now_us: u64) {
+        self.lss_slave.update_config(LssConfig {
+            identity: self.identity,
+            node_id: self.node_id,
+            store_supported: self.store_supported,
+        });
+        if self.node_id.is_configured() {
+            self.bootups += 1;
+        }
+    }
+}
+
+impl LssContext for HarnessNode {
+    fn lss_slave(&mut self) -> &mut LssSlave {
+        &mut self.lss_slave
+    }
+
+    fn store_node_config(&mut self) {
+        self.stored = true;
+    }
+
+    fn reassign_node_id(&mut self, node_id: NodeId) {
+        self.reassigned_node_id = Some(node_id);
+    }
+}
+
+/// Runs an LSS slave on frames fed to it
+#[allow(missing_debug_implementations)]
+pub struct LssSlaveHarness {
+    receiver: LssReceiver,
+    node: HarnessNode,
+}
+
+impl LssSlaveHarness {
+    /// Create a new harness for a node with the given identity and node ID
+    ///
+    /// `store_supported` indicates whether the node accepts the store configuration command.
+    pub fn new(identity: LssIdentity, node_id: NodeId, store_supported: bool) -> Self {
+        Self {
+            receiver: LssReceiver::new(),
+            node: HarnessNode::new(identity, node_id, store_supported),
+        }
+    }
+
+    /// Receive an LSS request frame, and return the response to be sent, if any
+    ///
+    /// A node ID configured by the request is applied by booting with the new ID, as the node does
+    /// on its next process call.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Option<LssResponse>, MalformedFrame> {
+        let req: LssRequest = data.try_into().map_err(|_| MalformedFrame::Lss)?;
+        let resp = if self.receiver.handle_req(req) {
+            lss_slave::process_request(&mut self.node, &self.receiver)
+        } else {
+            None
+        };
+        nmt_slave::boot(&mut self.node, 0);
+        Ok(resp)
+    }
+
+    /// Get the node ID, as configured by LSS
+    pub fn node_id(&self) -> NodeId {
+        self.node.node_id
+    }
+
+    /// Returns true if a store configuration command has been accepted
+    pub fn stored(&self) -> bool {
+        self.node.stored
+    }
+}
+
+/// Applies NMT commands fed to it to a node's NMT state
+///
+/// A reset command puts the node in the Bootup state, and it then boots into PreOperational, as
+/// the node does on its next process call.
+#[allow(missing_debug_implementations)]
+pub struct NmtHarness {
+    node: HarnessNode,
+}
+
+impl NmtHarness {
+    /// Create a new harness for a node with the given ID, and boot it into PreOperational
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            node: HarnessNode::new(LssIdentity::default(), node_id, false),
+        }
+    }
+
+    /// Receive an NMT command frame
+    ///
+    /// Returns the command if it was addressed to the node. Commands are ignored by a node which
+    /// has no configured ID.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Option<NmtCommand>, MalformedFrame> {
+        let msg = CanMessage::new(NMT_CMD_ID, data);
+        let cmd = NmtCommand::try_from(msg).map_err(|_| MalformedFrame::Nmt)?;
+        let node_id = self.node.node_id;
+        let addressed = nmt_slave::handle_command(&mut self.node, node_id, cmd);
+        nmt_slave::boot(&mut self.node, 0);
+        Ok(addressed.then_some(cmd))
+    }
+
+    /// Get the current NMT state
+    pub fn state(&self) -> NmtState {
+        self.node.state
+    }
+
+    /// Get the number of boot-up messages the node has sent, including the one sent on creation
+    pub fn bootups(&self) -> u32 {
+        self.node.bootups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::{
+        lss::LssState,
+        messages::NmtCommandSpecifier,
+        objects::{AccessType, DataType, ObjectCode, SubInfo},
+        sdo::SdoRequest,
+    };
+
+    use crate::object_dict::{ByteField, ConstField, ProvidesSubObjects, SubObjectAccess};
+
+    use super::*;
+
+    struct Object2000 {
+        sub1: ByteField<64>,
+    }
+
+    impl ProvidesSubObjects for Object2000 {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                0 => Some((
+                    SubInfo::MAX_SUB_NUMBER,
+                    const { &ConstField::new(1u8.to_le_bytes()) },
+                )),
+                1 => Some((
+                    SubInfo {
+                        size: self.sub1.len(),
+                        data_type: DataType::OctetString,
+                        access_type: AccessType::Rw,
+                        ..Default::default()
+                    },
+                    &self.sub1,
+                )),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Record
+        }
+    }
+
+    /// A simple xorshift generator, so that the test is repeatable
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn test_sdo_harness() {
+        const CLIENT: CanId = CanId::Std(0x601);
+        let object = Object2000 {
+            sub1: ByteField::new([0; 64]),
+        };
+        let od = [ODEntry {
+            index: 0x2000,
+            data: &object,
+        }];
+        let mut buffer = [0; crate::SDO_BUFFER_SIZE];
+        let mut harness = SdoServerHarness::new(&mut buffer, &od);
+
+        assert_eq!(Err(MalformedFrame::Sdo), harness.feed(CLIENT, &[0; 4]));
+
+        let req = SdoRequest::expedited_download(0x2000, 1, &[1, 2, 3, 4]);
+        harness.feed(CLIENT, &req.to_bytes()).unwrap();
+        let updated = harness.process(0, &mut |_| {});
+        assert_eq!(
+            Some(ObjectId {
+                index: 0x2000,
+                sub: 1
+            }),
+            updated
+        );
+        assert!(harness.next_response().is_some());
+        assert_eq!(None, harness.next_response());
+
+        // Arbitrary frames must not panic, and the server must always be left in a state where
+        // it can complete a new transfer
+        let mut seed = 0x1234_5678;
+        for _ in 0..10000 {
+            let mut frame = [0u8; 8];
+            for b in frame.iter_mut() {
+                *b = next_random(&mut seed) as u8;
+            }
+            harness.feed(CLIENT, &frame).unwrap();
+            harness.process(next_random(&mut seed) % 1000, &mut |_| {});
+            while harness.next_response().is_some() {}
+        }
+        harness.reset();
+        harness.feed(CLIENT, &req.to_bytes()).unwrap();
+        assert_eq!(
+            Some(ObjectId {
+                index: 0x2000,
+                sub: 1
+            }),
+            harness.process(0, &mut |_| {})
+        );
+    }
+
+    #[test]
+    fn test_lss_harness() {
+        let identity = LssIdentity {
+            vendor_id: 1,
+            product_code: 2,
+            revision: 3,
+            serial: 4,
+        };
+        let mut harness = LssSlaveHarness::new(identity, NodeId::Unconfigured, true);
+
+        assert_eq!(Err(MalformedFrame::Lss), harness.feed(&[0xFF; 8]));
+
+        let requests = [
+            LssRequest::SwitchStateVendor { vendor_id: 1 },
+            LssRequest::SwitchStateProduct { product_code: 2 },
+            LssRequest::SwitchStateRevision { revision: 3 },
+        ];
+        for req in requests {
+            assert_eq!(Ok(None), harness.feed(CanMessage::from(req).data()));
+        }
+        assert_eq!(
+            Ok(Some(LssResponse::SwitchStateResponse)),
+            harness.feed(CanMessage::from(LssRequest::SwitchStateSerial { serial: 4 }).data())
+        );
+        assert_eq!(
+            Ok(Some(LssResponse::ConfigureNodeIdAck {
+                error: 0,
+                spec_error: 0
+            })),
+            harness.feed(CanMessage::from(LssRequest::ConfigureNodeId { node_id: 5 }).data())
+        );
+        assert_eq!(NodeId::new(5).unwrap(), harness.node_id());
+        harness
+            .feed(CanMessage::from(LssRequest::StoreConfiguration).data())
+            .unwrap();
+        assert!(harness.stored());
+        harness
+            .feed(
+                CanMessage::from(LssRequest::SwitchModeGlobal {
+                    mode: LssState::Waiting as u8,
+                })
+                .data(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_nmt_harness() {
+        let mut harness = NmtHarness::new(NodeId::new(3).unwrap());
+
+        assert_eq!(Err(MalformedFrame::Nmt), harness.feed(&[1]).map(|_| ()));
+        assert_eq!(
+            Err(MalformedFrame::Nmt),
+            harness.feed(&[0x55, 0]).map(|_| ())
+        );
+
+        let start = |node| {
+            CanMessage::from(NmtCommand {
+                cs: NmtCommandSpecifier::Start,
+                node,
+            })
+        };
+        assert!(harness.feed(start(4).data()).unwrap().is_none());
+        assert_eq!(NmtState::PreOperational, harness.state());
+        assert!(harness.feed(start(3).data()).unwrap().is_some());
+        assert_eq!(NmtState::Operational, harness.state());
+        assert_eq!(1, harness.bootups());
+        // A reset boots the node again, sending a new boot-up message
+        harness
+            .feed(&[NmtCommandSpecifier::ResetComm as u8, 0])
+            .unwrap();
+        assert_eq!(NmtState::PreOperational, harness.state());
+        assert_eq!(2, harness.bootups());
+        harness
+            .feed(&[NmtCommandSpecifier::ResetApp as u8, 3])
+            .unwrap();
+        assert_eq!(3, harness.bootups());
+
+        let mut unconfigured = NmtHarness::new(NodeId::Unconfigured);
+        assert!(unconfigured.feed(start(0).data()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,340 @@
+//! Heartbeat consumer for monitoring other nodes
+//!
+//! A node can monitor the heartbeats produced by other nodes on the bus, and be notified when one
+//! of them stops. The number of nodes which can be monitored is set at build time by the
+//! `[heartbeat_consumers]` section of the device config, and zencan-build allocates a static
+//! [`HeartbeatConsumer`] for each, along with the consumer heartbeat time object (0x1016) used to
+//! configure them.
+//!
+//! Each sub object of 0x1016 holds a u32, with the node ID to monitor in bits 16-23 and the
+//! heartbeat time in milliseconds in bits 0-15. An entry with a node ID or time of 0 is disabled.
+//! The same node ID may not be monitored by more than one enabled entry.
+//!
+//! Monitoring of a node begins when its first heartbeat is received. If no heartbeat follows
+//! within the configured time, the consumer enters the [`HeartbeatStatus::TimedOut`] state, and the
+//! [`heartbeat_timeout`](crate::Callbacks::heartbeat_timeout) callback is called. Monitoring resumes
+//! when the node's heartbeat is received again.
+//!
+//! Consumer entries are reset to their default configuration on an NMT reset.
+
+use zencan_common::{
+    nmt::NmtState,
+    objects::{ObjectCode, SubInfo},
+    sdo::AbortCode,
+    AtomicCell,
+};
+
+use crate::{abort_codes::check_write_len, object_dict::ObjectAccess};
+
+/// The monitoring state of a [`HeartbeatConsumer`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeartbeatStatus {
+    /// The consumer is disabled, or no heartbeat has been received since it was configured
+    Waiting,
+    /// A heartbeat has been received within the heartbeat time, reporting the given state
+    Alive(NmtState),
+    /// No heartbeat has been received within the heartbeat time
+    TimedOut,
+}
+
+const fn pack_config(node_id: u8, time_ms: u16) -> u32 {
+    ((node_id as u32) << 16) | time_ms as u32
+}
+
+const fn config_node_id(config: u32) -> u8 {
+    (config >> 16) as u8
+}
+
+const fn config_time_ms(config: u32) -> u16 {
+    config as u16
+}
+
+const fn config_enabled(config: u32) -> bool {
+    config_node_id(config) != 0 && config_time_ms(config) != 0
+}
+
+/// Monitoring state for a single heartbeat producer
+///
+/// These are created statically by zencan-build. Heartbeats are delivered by the
+/// [`NodeMbox`](crate::NodeMbox), and timeouts are detected by the [`Node`](crate::Node) when it is
+/// processed.
+#[allow(missing_debug_implementations)]
+pub struct HeartbeatConsumer {
+    /// The value of the 0x1016 sub object
+    config: AtomicCell<u32>,
+    /// The configuration restored on reset
+    default_config: u32,
+    /// The state reported by a heartbeat which has been received but not yet processed
+    received: AtomicCell<Option<NmtState>>,
+    /// The time at which the last heartbeat was processed, in microseconds
+    last_heartbeat_us: AtomicCell<u64>,
+    status: AtomicCell<HeartbeatStatus>,
+}
+
+impl HeartbeatConsumer {
+    /// Create a new consumer, with a default node ID and heartbeat time
+    ///
+    /// A `node_id` or `time_ms` of 0 creates a disabled consumer.
+    pub const fn new(node_id: u8, time_ms: u16) -> Self {
+        let default_config = pack_config(node_id, time_ms);
+        Self {
+            config: AtomicCell::new(default_config),
+            default_config,
+            received: AtomicCell::new(None),
+            last_heartbeat_us: AtomicCell::new(0),
+            status: AtomicCell::new(HeartbeatStatus::Waiting),
+        }
+    }
+
+    /// Get the ID of the monitored node, or 0 if none is configured
+    pub fn node_id(&self) -> u8 {
+        config_node_id(self.config.load())
+    }
+
+    /// Get the heartbeat time in milliseconds
+    pub fn time_ms(&self) -> u16 {
+        config_time_ms(self.config.load())
+    }
+
+    /// Get the current monitoring state
+    pub fn status(&self) -> HeartbeatStatus {
+        self.status.load()
+    }
+
+    /// Set the configuration, and restart monitoring
+    fn set_config(&self, config: u32) {
+        self.config.store(config);
+        self.received.store(None);
+        self.status.store(HeartbeatStatus::Waiting);
+    }
+
+    /// Restore the default configuration
+    pub(crate) fn reset(&self) {
+        self.set_config(self.default_config);
+    }
+
+    /// Returns true if this consumer monitors the heartbeat of `node`
+    pub(crate) fn monitors(&self, node: u8) -> bool {
+        let config = self.config.load();
+        config_enabled(config) && config_node_id(config) == node
+    }
+
+    /// Store a heartbeat received from `node`
+    ///
+    /// Returns true if the heartbeat is monitored by this consumer
+    pub(crate) fn store_heartbeat(&self, node: u8, state: NmtState) -> bool {
+        if self.monitors(node) {
+            self.received.store(Some(state));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Update the monitoring state
+    ///
+    /// Returns true when the heartbeat time has newly expired
+    pub(crate) fn update(&self, now_us: u64) -> bool {
+        if let Some(state) = self.received.take() {
+            self.last_heartbeat_us.store(now_us);
+            self.status.store(HeartbeatStatus::Alive(state));
+            return false;
+        }
+
+        let config = self.config.load();
+        if !config_enabled(config) || !matches!(self.status(), HeartbeatStatus::Alive(_)) {
+            return false;
+        }
+        let time_us = config_time_ms(config) as u64 * 1000;
+        if now_us.saturating_sub(self.last_heartbeat_us.load()) > time_us {
+            self.status.store(HeartbeatStatus::TimedOut);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Implements the consumer heartbeat time object (0x1016)
+///
+/// | Sub | Type | Description |
+/// | --- | ---- | ----------- |
+/// | 0   | u8   | Number of consumers |
+/// | 1-N | u32  | Node ID in bits 16-23, heartbeat time in ms in bits 0-15 |
+#[allow(missing_debug_implementations)]
+pub struct HeartbeatConsumerObject {
+    consumers: &'static [HeartbeatConsumer],
+}
+
+impl HeartbeatConsumerObject {
+    /// Create a new heartbeat consumer object
+    pub const fn new(consumers: &'static [HeartbeatConsumer]) -> Self {
+        Self { consumers }
+    }
+
+    fn consumer(&self, sub: u8) -> Result<&HeartbeatConsumer, AbortCode> {
+        if sub == 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        self.consumers
+            .get(sub as usize - 1)
+            .ok_or(AbortCode::NoSuchSubIndex)
+    }
+}
+
+impl ObjectAccess for HeartbeatConsumerObject {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub == 0 {
+            if offset != 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.consumers.len() as u8;
+            return Ok(1);
+        }
+
+        let value_bytes = self.consumer(sub)?.config.load().to_le_bytes();
+        if offset < value_bytes.len() {
+            let read_len = buf.len().min(value_bytes.len() - offset);
+            buf[..read_len].copy_from_slice(&value_bytes[offset..offset + read_len]);
+            Ok(read_len)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        Ok(self.sub_info(sub)?.size)
+    }
+
+    fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
+        if sub == 0 {
+            return Err(AbortCode::ReadOnly);
+        }
+        let consumer = self.consumer(sub)?;
+        check_write_len(data, 4)?;
+        let config = u32::from_le_bytes(data.try_into().unwrap());
+        if config >> 24 != 0 || config_node_id(config) > 127 {
+            return Err(AbortCode::InvalidValue);
+        }
+        // CiA 301 does not allow two entries to monitor the same node
+        if config_enabled(config) {
+            let duplicate = self.consumers.iter().enumerate().any(|(i, other)| {
+                i != sub as usize - 1
+                    && config_enabled(other.config.load())
+                    && other.node_id() == config_node_id(config)
+            });
+            if duplicate {
+                return Err(AbortCode::IncompatibleParameter);
+            }
+        }
+        consumer.set_config(config);
+        Ok(())
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Array
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        if sub == 0 {
+            return Ok(SubInfo::MAX_SUB_NUMBER);
+        }
+        self.consumer(sub)?;
+        Ok(SubInfo::new_u32().rw_access().persist(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_object(consumers: [HeartbeatConsumer; 2]) -> HeartbeatConsumerObject {
+        HeartbeatConsumerObject::new(Box::leak(Box::new(consumers)))
+    }
+
+    fn write_config(
+        object: &HeartbeatConsumerObject,
+        sub: u8,
+        node_id: u8,
+        time_ms: u16,
+    ) -> Result<(), AbortCode> {
+        object.write(sub, &pack_config(node_id, time_ms).to_le_bytes())
+    }
+
+    #[test]
+    fn test_object_access() {
+        let object = make_object([HeartbeatConsumer::new(5, 100), HeartbeatConsumer::new(0, 0)]);
+
+        let mut buf = [0; 4];
+        assert_eq!(Ok(1), object.read(0, 0, &mut buf));
+        assert_eq!(2, buf[0]);
+        assert_eq!(Ok(4), object.read(1, 0, &mut buf));
+        assert_eq!(0x0005_0064, u32::from_le_bytes(buf));
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.read(3, 0, &mut buf));
+
+        write_config(&object, 2, 6, 200).unwrap();
+        assert_eq!(6, object.consumers[1].node_id());
+        assert_eq!(200, object.consumers[1].time_ms());
+
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(0, &[1]));
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthLow),
+            object.write(1, &[0, 0])
+        );
+        assert_eq!(
+            Err(AbortCode::InvalidValue),
+            write_config(&object, 1, 128, 100)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_node_rejected() {
+        let object = make_object([HeartbeatConsumer::new(5, 100), HeartbeatConsumer::new(0, 0)]);
+
+        assert_eq!(
+            Err(AbortCode::IncompatibleParameter),
+            write_config(&object, 2, 5, 200)
+        );
+        // A disabled entry may share a node ID
+        write_config(&object, 2, 5, 0).unwrap();
+        // Rewriting the same entry is allowed
+        write_config(&object, 1, 5, 300).unwrap();
+    }
+
+    #[test]
+    fn test_timeout() {
+        let consumer = HeartbeatConsumer::new(5, 100);
+
+        // Monitoring does not start until the first heartbeat
+        assert!(!consumer.update(1_000_000));
+        assert_eq!(HeartbeatStatus::Waiting, consumer.status());
+
+        assert!(!consumer.store_heartbeat(6, NmtState::Operational));
+        assert!(consumer.store_heartbeat(5, NmtState::Operational));
+        assert!(!consumer.update(1_000_000));
+        assert_eq!(
+            HeartbeatStatus::Alive(NmtState::Operational),
+            consumer.status()
+        );
+
+        assert!(!consumer.update(1_100_000));
+        assert!(consumer.update(1_100_001));
+        assert_eq!(HeartbeatStatus::TimedOut, consumer.status());
+        // The timeout is only reported once
+        assert!(!consumer.update(1_200_000));
+
+        consumer.store_heartbeat(5, NmtState::PreOperational);
+        assert!(!consumer.update(1_300_000));
+        assert_eq!(
+            HeartbeatStatus::Alive(NmtState::PreOperational),
+            consumer.status()
+        );
+
+        consumer.set_config(pack_config(5, 0));
+        assert!(!consumer.store_heartbeat(5, NmtState::Operational));
+        consumer.reset();
+        assert_eq!(100, consumer.time_ms());
+        assert_eq!(HeartbeatStatus::Waiting, consumer.status());
+    }
+}
@@ -121,6 +121,15 @@
 //! );
 //! ```
 //!
+//! With the `embedded-storage` feature, `flash_storage::FlashStorage` provides ready-made
+//! `store_node_config` and `store_objects` callbacks, and the matching restore functions, for any
+//! `embedded_storage` NOR flash region.
+//!
+//! Alternatively, a [`NodeBuilder`] can be used to create the node. It checks that the callbacks
+//! provided by the application match the features enabled in the device config -- e.g. that a
+//! `store_objects` callback is provided when object storage is enabled -- and returns a
+//! [`NodeBuildError`] if they do not.
+//!
 //! ## Handling CAN messages
 //!
 //! The application has to handle sending and receiving CAN messages.
@@ -138,12 +147,24 @@
 //! zencan::NODE_MBOX.store_message(msg).ok();
 //! ```
 //!
+//! To see incoming messages alongside the stack, e.g. for diagnostics or a custom protocol, a
+//! [tap](NodeMbox::set_message_tap) can be set to receive a copy of each message stored in the
+//! mailbox.
+//!
+//! On a bus shared with J1939 devices, [J1939 coexistence](NodeMbox::set_j1939_coexistence) keeps
+//! the node from acting on 29-bit J1939 messages, and an [extended ID
+//! handler](NodeMbox::set_extended_id_handler) can receive them for a J1939 stack running alongside
+//! the node. [`J1939Id`](common::j1939::J1939Id) decodes and builds J1939 identifiers.
+//!
 //! Outgoing messages can be read from the mbox using the [`NodeMbox::next_transmit_message`]
 //! function. A callback can be registered (see [`NodeMbox::set_transmit_notify_callback`]) to be
 //! notified when new messages are queued for transmission -- this can be used to e.g. push the
 //! first message(s) to the CAN controller to initiate an IRQ driven transmit look, or to wake an
 //! async task which is responsible for moving messages from the node to the CAN controller.
 //!
+//! Applications which send their own messages on the same CAN controller can queue them with
+//! [`NodeMbox::send_message`], so that all messages are sent by the same transmit task.
+//!
 //! ```ignore
 //! #[embassy_executor::task]
 //! async fn twai_tx_task(mut twai_tx: TwaiTx<'static, Async>) {
@@ -170,6 +191,12 @@
 //! [callback](NodeMbox::set_process_notify_callback) which can be used to notify another task that
 //! process should be called when a message is received and requires processing.
 //!
+//! With the `embassy` feature, [`AsyncNode`](async_node::AsyncNode) implements this loop. For RTIC
+//! applications, which cannot easily provide static callbacks, the [`rtic`] module provides a queue
+//! for passing received messages from the CAN interrupt to the process task, which is generated as
+//! `RX_QUEUE` when `rx_queue_size` is set in the device config, and the mailbox can be polled with
+//! [`NodeMbox::take_process_pending`] and [`NodeMbox::take_transmit_pending`].
+//!
 //! Here's an example of a lilos task which executes process when either CAN_NOTIFY is signals, or
 //! 10ms has passed since the last notification.
 //!
@@ -186,22 +213,86 @@
 //! }
 //! ```
 //!
+//! # Features
+//!
+//! * `std`: Enables std support, and the critical-section std implementation. Enabled by default.
+//! * `log`: Log messages using the `log` crate. Enabled by default.
+//! * `defmt`: Log messages using `defmt`. Exactly one of `log` or `defmt` must be enabled.
+//! * `socketcan`: Enables socketcan support on linux. Implies `std`.
+//! * `embassy`: Provides [`AsyncNode`](async_node::AsyncNode), which runs the process loop on the
+//!   embassy executor.
+//! * `strict-abort-codes`: Respond with the specific SDO abort codes required by CiA 301 in cases
+//!   where an approximate code (usually a general error) is returned by default, and perform
+//!   additional CiA 301 checks on PDO mapping writes.
+//! * `log-verbose`: Log node events which are useful when debugging bus interactions, such as SDO
+//!   aborts, rejected PDO configuration writes, mailbox overruns, and LSS state changes. Disabled by
+//!   default, so that these messages take no flash unless they are requested.
+//! * `tracing`: Create `tracing` spans for each SDO transfer, NMT command, and LSS event handled by
+//!   the node, with the node ID, object index and sub, and the outcome. Implies `std`.
+//! * `unit-metadata`: Provides the [unit metadata object](unit_metadata), which is required by the
+//!   generated code when `unit_metadata` is enabled in the device config.
+//! * `fuzz`: Provides the [`fuzz`] module, for feeding arbitrary frames into the SDO server, LSS
+//!   slave, and NMT handling without statics.
+//! * `test-util`: Provides the [`test_util`] module, for testing a node on the host with a
+//!   simulated bus. Implies `std`.
+//! * `validate-strings`: Reject SDO writes to VisibleString objects which contain characters other
+//!   than visible ASCII, and writes to UnicodeString objects which are not valid UTF-8, with
+//!   [`AbortCode::InvalidValue`](common::sdo::AbortCode::InvalidValue).
+//!
+//! The crate is built under each supported combination of these features by
+//! `scripts/check-features.sh`, which also checks a no_std build for a thumbv7em target.
+//!
 #![cfg_attr(all(not(test), not(feature = "std")), no_std)]
 #![warn(missing_docs, missing_debug_implementations)]
 #![allow(clippy::comparison_chain)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+mod abort_codes;
+#[cfg(feature = "access-stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "access-stats")))]
+pub mod access_stats;
+#[cfg(feature = "embassy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embassy")))]
+pub mod async_node;
 mod bootloader;
+pub mod bus_state;
+pub mod change_counters;
+pub mod config_manager;
+pub mod diagnostics;
+#[cfg(feature = "embedded-storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-storage")))]
+pub mod flash_storage;
+#[cfg(feature = "fuzz")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fuzz")))]
+pub mod fuzz;
+pub mod heartbeat_consumer;
+pub mod log_ring;
 mod lss_slave;
+pub mod network_time;
+mod nmt_slave;
 mod node;
+mod node_builder;
+pub mod node_clock;
 mod node_mbox;
 mod node_state;
+pub mod notify;
 pub mod object_dict;
 pub mod pdo;
 mod persist;
 pub mod priority_queue;
+#[cfg(feature = "process-timing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "process-timing")))]
+pub mod process_timing;
+pub mod rtic;
 mod sdo_server;
 pub mod storage;
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod test_util;
+#[cfg(feature = "unit-metadata")]
+#[cfg_attr(docsrs, doc(cfg(feature = "unit-metadata")))]
+pub mod unit_metadata;
+mod verbose_log;
 
 // Re-export proc macros
 pub use zencan_macro::build_object_dict;
@@ -211,15 +302,21 @@ pub use critical_section;
 pub use embedded_io;
 pub use zencan_common as common;
 
-pub use bootloader::{BootloaderInfo, BootloaderSection, BootloaderSectionCallbacks};
+pub use bootloader::{
+    BootloaderBank, BootloaderInfo, BootloaderSection, BootloaderSectionCallbacks,
+};
 #[cfg(all(feature = "socketcan", target_os = "linux"))]
-#[cfg_attr(docsrs, doc(all(feature = "socketcan", target_os = "linux")))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "socketcan", target_os = "linux"))))]
 pub use common::open_socketcan;
 pub use node::{Callbacks, Node};
-pub use node_mbox::NodeMbox;
+pub use node_builder::{NodeBuildError, NodeBuilder};
+pub use node_mbox::{MessagePriority, NodeMbox, TxPriority};
 pub use node_state::NodeState;
+pub use notify::{MessageHandler, MessageTap, NotifyCallback};
 pub use persist::{restore_stored_comm_objects, restore_stored_objects};
-pub use sdo_server::SDO_BUFFER_SIZE;
+pub use sdo_server::{
+    SdoAccess, SdoAccessKind, StandaloneSdoServer, SDO_BUFFER_SIZE, SDO_QUEUE_DEPTH,
+};
 
 /// Include the code generated for the object dict in the build script.
 #[macro_export]
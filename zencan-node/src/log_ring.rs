@@ -0,0 +1,334 @@
+//! A RAM log buffer which can be read over SDO
+//!
+//! [`LogRing`] stores the most recent text written to it in a fixed size ring buffer, without any
+//! heap allocation. When the buffer is full, the oldest data is overwritten. It implements
+//! [`ObjectAccess`] as a read-only domain object, so that the log can be retrieved over the bus when
+//! no debugger is available.
+//!
+//! The log ring object (0x5F01) is created when `log_ring_size` is set to a non-zero value in the
+//! device config. The generated code creates a `LOG_RING` static, which the application can write
+//! to with the `write!` and `writeln!` macros:
+//!
+//! ```ignore
+//! writeln!(zencan::LOG_RING, "Sensor fault: {}", code).ok();
+//! ```
+//!
+//! Reading the object returns all of the lines currently in the ring, oldest first. When the buffer
+//! has wrapped, the partial line at the start of the buffer is skipped.
+//!
+//! A segmented or block upload of the log announces its size when it starts. If more text is
+//! logged during the upload than the buffer can hold while keeping the unread data, or the log is
+//! cleared, the data announced can no longer be sent, and the upload is aborted with
+//! [`AbortCode::GeneralError`].
+
+use core::{cell::RefCell, fmt};
+
+use critical_section::Mutex;
+use zencan_common::{
+    objects::{AccessType, DataType, ObjectCode, PdoMappable, SubInfo},
+    sdo::AbortCode,
+};
+
+use crate::object_dict::ObjectAccess;
+
+struct RingState<const N: usize> {
+    buf: [u8; N],
+    /// Total number of bytes ever written. The next byte is written at `head % N`.
+    head: u64,
+    /// Position of an in-progress partial read, and the end position captured when it began
+    read_cursor: Option<(u64, u64)>,
+}
+
+impl<const N: usize> RingState<N> {
+    fn push(&mut self, data: &[u8]) {
+        for &b in data {
+            self.buf[(self.head % N as u64) as usize] = b;
+            self.head += 1;
+        }
+    }
+
+    fn byte_at(&self, pos: u64) -> u8 {
+        self.buf[(pos % N as u64) as usize]
+    }
+
+    /// The oldest position still held in the buffer
+    fn oldest(&self) -> u64 {
+        self.head.saturating_sub(N as u64)
+    }
+
+    /// The position of the first complete line in the buffer
+    fn start(&self) -> u64 {
+        let oldest = self.oldest();
+        if oldest == 0 {
+            return 0;
+        }
+        // The buffer has wrapped, so skip the partial line at the start
+        (oldest..self.head)
+            .find(|&pos| self.byte_at(pos) == b'\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(oldest)
+    }
+
+    fn copy_out(&self, from: u64, to: u64, buf: &mut [u8]) -> usize {
+        let len = buf.len().min((to - from) as usize);
+        for (i, b) in buf[..len].iter_mut().enumerate() {
+            *b = self.byte_at(from + i as u64);
+        }
+        len
+    }
+}
+
+/// A fixed size ring buffer for storing log text
+///
+/// `N` is the size of the buffer in bytes.
+#[allow(missing_debug_implementations)]
+pub struct LogRing<const N: usize> {
+    state: Mutex<RefCell<RingState<N>>>,
+}
+
+impl<const N: usize> Default for LogRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LogRing<N> {
+    /// Create a new, empty LogRing
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(RingState {
+                buf: [0; N],
+                head: 0,
+                read_cursor: None,
+            })),
+        }
+    }
+
+    /// Append a string to the log
+    pub fn write_str(&self, s: &str) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).push(s.as_bytes()));
+    }
+
+    /// Append formatted text to the log
+    ///
+    /// This allows the `write!` and `writeln!` macros to be used on a shared reference to the ring.
+    pub fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        struct Writer<'a, const N: usize>(&'a LogRing<N>);
+        impl<const N: usize> fmt::Write for Writer<'_, N> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0.write_str(s);
+                Ok(())
+            }
+        }
+        fmt::write(&mut Writer(self), args)
+    }
+
+    /// Get the number of bytes which will be returned by a read of the log
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| {
+            let state = self.state.borrow_ref(cs);
+            (state.head - state.start()) as usize
+        })
+    }
+
+    /// Returns true if the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all text from the log
+    pub fn clear(&self) {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.head = 0;
+            // The data captured by an in-progress read is gone, so the read fails
+            state.read_cursor = None;
+        })
+    }
+
+    /// Copy log text into `buf`, starting `offset` bytes after the oldest complete line
+    ///
+    /// Returns the number of bytes copied
+    pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> usize {
+        critical_section::with(|cs| {
+            let state = self.state.borrow_ref(cs);
+            let from = state.start() + offset as u64;
+            if from >= state.head {
+                return 0;
+            }
+            state.copy_out(from, state.head, buf)
+        })
+    }
+}
+
+impl<const N: usize> ObjectAccess for LogRing<N> {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(self.read_bytes(offset, buf))
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(self.len())
+    }
+
+    fn upload_size(&self, sub: u8) -> Option<usize> {
+        if sub != 0 {
+            return None;
+        }
+        critical_section::with(|cs| {
+            let state = self.state.borrow_ref(cs);
+            // During a partial read, the extent of the log was captured when the read began
+            let (from, to) = state.read_cursor.unwrap_or((state.start(), state.head));
+            Some((to - from) as usize)
+        })
+    }
+
+    fn write(&self, sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn begin_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        // Capture the current extent of the log, so that text logged during the transfer does not
+        // shift the data being read
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.read_cursor = Some((state.start(), state.head));
+        });
+        Ok(())
+    }
+
+    fn read_partial(&self, sub: u8, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            let (pos, end) = state.read_cursor.ok_or(AbortCode::GeneralError)?;
+            // If the writer has overtaken the reader, the unread data is lost, and the size
+            // announced for the transfer can no longer be met
+            if pos < end && pos < state.oldest() {
+                state.read_cursor = None;
+                return Err(AbortCode::GeneralError);
+            }
+            let len = state.copy_out(pos, end, buf);
+            state.read_cursor = Some((pos + len as u64, end));
+            Ok(len)
+        })
+    }
+
+    fn end_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).read_cursor = None);
+        Ok(())
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Var
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(SubInfo {
+            size: N,
+            data_type: DataType::Domain,
+            access_type: AccessType::Ro,
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all<const N: usize>(ring: &LogRing<N>) -> String {
+        let mut buf = vec![0; N];
+        let len = ring.read(0, 0, &mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_log_ring_wraps() {
+        let ring = LogRing::<16>::new();
+        assert!(ring.is_empty());
+
+        writeln!(ring, "one").unwrap();
+        writeln!(ring, "two {}", 2).unwrap();
+        assert_eq!("one\ntwo 2\n", read_all(&ring));
+        assert_eq!(10, ring.read_size(0).unwrap());
+
+        // Overwrite the first line, and part of the second. The partial line is skipped.
+        writeln!(ring, "three").unwrap();
+        writeln!(ring, "four").unwrap();
+        assert_eq!("three\nfour\n", read_all(&ring));
+
+        // Offset reads are relative to the first complete line
+        let mut buf = [0; 4];
+        assert_eq!(4, ring.read(0, 6, &mut buf).unwrap());
+        assert_eq!(b"four", &buf);
+
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(Err(AbortCode::ReadOnly), ring.write(0, b"x"));
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), ring.read_size(1));
+    }
+
+    #[test]
+    fn test_log_ring_partial_read() {
+        let ring = LogRing::<32>::new();
+        ring.write_str("first\nsecond\n");
+
+        assert_eq!(Some(13), ring.upload_size(0));
+        ring.begin_partial_read(0).unwrap();
+        ring.write_str("x");
+        assert_eq!(Some(13), ring.upload_size(0));
+        let mut buf = [0; 7];
+        assert_eq!(7, ring.read_partial(0, &mut buf).unwrap());
+        assert_eq!(b"first\ns", &buf);
+
+        // Text written during the read is not included
+        ring.write_str("third\n");
+        assert_eq!(6, ring.read_partial(0, &mut buf).unwrap());
+        assert_eq!(b"econd\n", &buf[..6]);
+        assert_eq!(0, ring.read_partial(0, &mut buf).unwrap());
+        ring.end_partial_read(0).unwrap();
+
+        assert_eq!("first\nsecond\nxthird\n", read_all(&ring));
+    }
+
+    #[test]
+    fn test_log_ring_overrun_during_read() {
+        let ring = LogRing::<16>::new();
+        ring.write_str("first\nsecond\n");
+        ring.begin_partial_read(0).unwrap();
+        let mut buf = [0; 7];
+        assert_eq!(7, ring.read_partial(0, &mut buf).unwrap());
+
+        // Overwriting the unread text aborts the read
+        ring.write_str("overrun text\n");
+        assert_eq!(Err(AbortCode::GeneralError), ring.read_partial(0, &mut buf));
+        ring.end_partial_read(0).unwrap();
+
+        // Clearing the log also aborts a read in progress
+        ring.begin_partial_read(0).unwrap();
+        ring.clear();
+        assert_eq!(Err(AbortCode::GeneralError), ring.read_partial(0, &mut buf));
+        ring.end_partial_read(0).unwrap();
+    }
+}
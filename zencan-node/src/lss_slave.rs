@@ -9,6 +9,8 @@ use zencan_common::{
     NodeId,
 };
 
+use crate::verbose_log::{verbose_info, verbose_warn};
+
 /// Events which can be generated by the LSS slave to the higher level node
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -163,7 +165,11 @@ impl LssSlave {
         match request {
             LssRequest::SwitchModeGlobal { mode } => {
                 // TODO: Device should enter NMT reset communication state when node ID is configured
-                self.state = LssState::from_byte(mode)?;
+                let state = LssState::from_byte(mode)?;
+                if state != self.state {
+                    verbose_info!("LSS state changed from {:?} to {:?}", self.state, state);
+                }
+                self.state = state;
                 Ok(None)
             }
 
@@ -171,8 +177,10 @@ impl LssSlave {
                 if self.state == LssState::Configuring {
                     let mut error = 0;
                     if let Ok(node_id) = NodeId::try_from(node_id) {
+                        verbose_info!("LSS node ID set to {}", node_id.raw());
                         self.pending_node_id = node_id;
                     } else {
+                        verbose_warn!("LSS node ID {} rejected: out of range", node_id);
                         error = LssConfigureError::NodeIdOutOfRange as u8;
                     }
                     Ok(Some(LssResponse::ConfigureNodeIdAck {
@@ -187,6 +195,7 @@ impl LssSlave {
             LssRequest::ConfigureBitTiming { table: _, index: _ } => {
                 // Configuring bit timing is not supported
                 if self.state == LssState::Configuring {
+                    verbose_warn!("LSS bit timing configuration rejected: not supported");
                     Ok(Some(LssResponse::ConfigureBitTimingAck {
                         error: 1,
                         spec_error: 0,
@@ -205,6 +214,7 @@ impl LssSlave {
                             spec_error: 0,
                         }))
                     } else {
+                        verbose_warn!("LSS store configuration rejected: not supported");
                         Ok(Some(LssResponse::StoreConfigurationAck {
                             error: 1,
                             spec_error: 0,
@@ -223,6 +233,7 @@ impl LssSlave {
                     selected_identity.serial = serial;
                     if self.config.identity == selected_identity {
                         // If the identity matches, we are selected and enter the configuration state
+                        verbose_info!("LSS identity selected, entering configuration state");
                         self.state = LssState::Configuring;
                         Ok(Some(LssResponse::SwitchStateResponse))
                     } else {
@@ -314,6 +325,50 @@ impl LssSlave {
     }
 }
 
+/// The state acted on by LSS events
+///
+/// This is implemented by the [`Node`](crate::Node), and by the LSS harness in
+/// [`fuzz`](crate::fuzz), so that both handle LSS events the same way.
+pub(crate) trait LssContext {
+    /// Get the LSS slave
+    fn lss_slave(&mut self) -> &mut LssSlave;
+
+    /// Store the node configuration, on an LSS store configuration command
+    fn store_node_config(&mut self);
+
+    /// Change the node ID, on an LSS configure node ID command
+    ///
+    /// The new ID takes effect when the node next boots, see [`boot`](crate::nmt_slave::boot).
+    fn reassign_node_id(&mut self, node_id: NodeId);
+}
+
+/// Process the request received by the LSS slave, and handle any event it generates
+///
+/// Returns the response to be sent, if any.
+pub(crate) fn process_request(
+    ctx: &mut impl LssContext,
+    receiver: &LssReceiver,
+) -> Option<LssResponse> {
+    // Requests which the slave fails to handle, e.g. an invalid mode, are ignored
+    let resp = ctx.lss_slave().process(receiver).ok()??;
+
+    if let Some(event) = ctx.lss_slave().pending_event() {
+        info!("LSS Slave Event: {:?}", event);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("lss_event", event = ?event).entered();
+        match event {
+            LssEvent::StoreConfiguration => ctx.store_node_config(),
+            LssEvent::ActivateBitTiming {
+                table: _,
+                index: _,
+                delay: _,
+            } => (),
+            LssEvent::ConfigureNodeId { node_id } => ctx.reassign_node_id(node_id),
+        }
+    }
+    Some(resp)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
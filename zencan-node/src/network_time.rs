@@ -0,0 +1,151 @@
+//! Network time, disciplined from received TIME messages
+//!
+//! A TIME producer on the network broadcasts the time of day on COB ID 0x100. The node keeps the
+//! most recently received time along with the local time at which it was received, and
+//! extrapolates from it to provide the network time between TIME messages, so that timestamps
+//! taken by the applications on different nodes agree. The current network time is available from
+//! [`Node::network_time`](crate::Node::network_time).
+//!
+//! Local clocks run at slightly different rates, so the rate of the local clock relative to the
+//! network is estimated from TIME messages at least [`DRIFT_INTERVAL_US`] apart, and corrected
+//! for when extrapolating. Each received TIME message replaces the extrapolated time, so the error
+//! is bounded by the drift over the interval between TIME messages.
+//!
+//! # SYNC alignment
+//!
+//! The time is only as precise as the local time at which the TIME message is taken to be
+//! received. A producer can instead sample its time when it sends a SYNC, and send that time in
+//! the TIME message that follows. When [SYNC
+//! alignment](crate::Node::set_network_time_sync_alignment) is enabled, the node takes the time
+//! in each TIME message to refer to the most recently received SYNC.
+
+use zencan_common::TimeOfDay;
+
+/// The minimum interval over which the rate of the local clock is measured
+pub const DRIFT_INTERVAL_US: u64 = 10_000_000;
+
+/// The largest rate difference between the local and network clocks that is accepted
+///
+/// A larger difference means that the network time was changed, rather than that the clocks run at
+/// different rates, and it is not used to update the drift estimate.
+pub const MAX_DRIFT_PPM: i64 = 1000;
+
+/// A clock following the network time
+///
+/// The clock is updated with [`update`](Self::update) when a TIME message is received, and read
+/// with [`now`](Self::now). Both take the local time in microseconds, which must be from a
+/// monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetworkClock {
+    /// The local time in microseconds, and network time in milliseconds, at the last update
+    reference: Option<(u64, u64)>,
+    /// The local and network time at the start of the current drift measurement
+    drift_start: Option<(u64, u64)>,
+    /// The estimated rate of the network clock relative to the local clock, in parts per million
+    drift_ppm: i64,
+}
+
+impl NetworkClock {
+    /// Create a new clock, which has not yet received the network time
+    pub const fn new() -> Self {
+        Self {
+            reference: None,
+            drift_start: None,
+            drift_ppm: 0,
+        }
+    }
+
+    /// Update the clock with a network time received at local time `local_us`
+    pub fn update(&mut self, time: TimeOfDay, local_us: u64) {
+        let network_ms = time.total_millis();
+        match self.drift_start {
+            Some((start_local, start_network)) if local_us >= start_local => {
+                let local_elapsed = local_us - start_local;
+                if local_elapsed >= DRIFT_INTERVAL_US {
+                    let network_elapsed = (network_ms as i64 - start_network as i64) * 1000;
+                    let ppm =
+                        (network_elapsed - local_elapsed as i64) * 1_000_000 / local_elapsed as i64;
+                    if ppm.abs() <= MAX_DRIFT_PPM {
+                        // Low-pass filter the measurement, as it includes the jitter in the time
+                        // at which TIME messages are received
+                        self.drift_ppm += (ppm - self.drift_ppm) / 4;
+                    }
+                    self.drift_start = Some((local_us, network_ms));
+                }
+            }
+            _ => self.drift_start = Some((local_us, network_ms)),
+        }
+        self.reference = Some((local_us, network_ms));
+    }
+
+    /// Get the network time at local time `local_us`
+    ///
+    /// Returns None if no network time has been received, or the time is out of range
+    pub fn now(&self, local_us: u64) -> Option<TimeOfDay> {
+        let (ref_local, ref_network) = self.reference?;
+        let elapsed = local_us.saturating_sub(ref_local) as i64;
+        let elapsed = elapsed + elapsed * self.drift_ppm / 1_000_000;
+        let network_ms = ref_network.checked_add_signed(elapsed / 1000)?;
+        TimeOfDay::from_total_millis(network_ms).ok()
+    }
+
+    /// Get the estimated rate of the network clock relative to the local clock, in parts per
+    /// million
+    pub fn drift_ppm(&self) -> i64 {
+        self.drift_ppm
+    }
+
+    /// Forget the network time
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_MS: u64 = 86_400_000;
+
+    fn time(ms: u64) -> TimeOfDay {
+        TimeOfDay::from_total_millis(ms).unwrap()
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let mut clock = NetworkClock::new();
+        assert_eq!(None, clock.now(0));
+
+        clock.update(time(DAY_MS - 500), 1_000_000);
+        assert_eq!(Some(time(DAY_MS - 500)), clock.now(1_000_000));
+        assert_eq!(Some(time(DAY_MS + 250)), clock.now(1_750_000));
+        assert_eq!(1, clock.now(1_750_000).unwrap().days());
+        // Local time before the reference does not go back
+        assert_eq!(Some(time(DAY_MS - 500)), clock.now(0));
+
+        clock.reset();
+        assert_eq!(None, clock.now(1_000_000));
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut clock = NetworkClock::new();
+        // The network clock runs 100ppm faster than the local clock
+        for i in 0..40u64 {
+            let local_us = i * 5_000_000;
+            clock.update(time(1_000_000 + i * 50_005 / 10), local_us);
+        }
+        assert!((90..=100).contains(&clock.drift_ppm()));
+        // 10s after the last update, the extrapolated time is within 1ms of the network time
+        let last_local = 39 * 5_000_000;
+        let last_network = 1_000_000 + 39 * 50_005 / 10;
+        let now = clock.now(last_local + 10_000_000).unwrap().total_millis();
+        assert!(now.abs_diff(last_network + 10_001) <= 1);
+
+        // A step in the network time does not affect the drift estimate
+        let drift = clock.drift_ppm();
+        clock.update(time(DAY_MS * 100), 40 * 5_000_000);
+        assert_eq!(drift, clock.drift_ppm());
+        assert_eq!(Some(time(DAY_MS * 100)), clock.now(40 * 5_000_000));
+    }
+}
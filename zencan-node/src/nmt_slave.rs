@@ -0,0 +1,111 @@
+//! Handling of NMT commands and boot-up by the node
+//!
+//! The NMT state transitions are applied through [`NmtContext`], which is implemented by the
+//! [`Node`](crate::Node) for its own state, and by the harnesses in [`fuzz`](crate::fuzz) for state
+//! which they own, so that both run the same command handling.
+use defmt_or_log::debug;
+use zencan_common::{
+    messages::{NmtCommand, NmtCommandSpecifier},
+    nmt::NmtState,
+    NodeId,
+};
+
+/// The state acted on by NMT commands
+pub(crate) trait NmtContext {
+    /// Get the current NMT state
+    fn nmt_state(&self) -> NmtState;
+
+    /// Enter the Operational state
+    fn enter_operational(&mut self);
+
+    /// Enter the Stopped state
+    fn enter_stopped(&mut self);
+
+    /// Enter the PreOperational state
+    fn enter_preoperational(&mut self);
+
+    /// Enter the Bootup state, so that the node boots on the next call to [`boot`]
+    fn enter_bootup(&mut self);
+
+    /// Reset the application and communication parameters
+    fn reset_app(&mut self);
+
+    /// Reset the communication parameters
+    fn reset_comm(&mut self);
+
+    /// Apply a node ID reassigned since the last call, e.g. by LSS
+    ///
+    /// Returns true if the node ID changed, and the node must boot with the new ID
+    fn apply_reassigned_node_id(&mut self) -> bool;
+
+    /// Boot with the current node ID, and send the boot-up message if it is configured
+    fn boot_up(&mut self, now_us: u64);
+}
+
+/// Handle an NMT command received by the node with ID `node_id`
+///
+/// Returns true if the command addressed the node. Commands are ignored by a node which has no
+/// configured ID. Reset commands leave the node in the Bootup state, until the next call to
+/// [`boot`].
+pub(crate) fn handle_command(ctx: &mut impl NmtContext, node_id: NodeId, cmd: NmtCommand) -> bool {
+    let NodeId::Configured(node_id) = node_id else {
+        return false;
+    };
+    if !cmd.addresses(node_id.raw()) {
+        return false;
+    }
+    debug!("Received NMT command: {:?}", cmd.cs);
+
+    let prev_state = ctx.nmt_state();
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "nmt_command",
+        command = ?cmd.cs,
+        from = ?prev_state,
+        to = tracing::field::Empty,
+    )
+    .entered();
+
+    match cmd.cs {
+        NmtCommandSpecifier::Start => ctx.enter_operational(),
+        NmtCommandSpecifier::Stop => ctx.enter_stopped(),
+        NmtCommandSpecifier::EnterPreOp => ctx.enter_preoperational(),
+        NmtCommandSpecifier::ResetApp => {
+            ctx.reset_app();
+            ctx.enter_bootup();
+        }
+        NmtCommandSpecifier::ResetComm => {
+            ctx.reset_comm();
+            ctx.enter_bootup();
+        }
+    }
+
+    debug!(
+        "NMT state changed from {:?} to {:?}",
+        prev_state,
+        ctx.nmt_state()
+    );
+    #[cfg(feature = "tracing")]
+    span.record("to", tracing::field::debug(ctx.nmt_state()));
+    true
+}
+
+/// Boot the node if it has been reset or its node ID has been changed
+///
+/// The node passes through the Bootup state, and then enters PreOperational. Returns true if the
+/// node booted.
+pub(crate) fn boot(ctx: &mut impl NmtContext, now_us: u64) -> bool {
+    if ctx.apply_reassigned_node_id() {
+        ctx.enter_bootup();
+    }
+
+    if ctx.nmt_state() == NmtState::Bootup {
+        // The boot-up message is sent while still in the Bootup state, so that it carries the
+        // 0x00 state value required by CiA 301
+        ctx.boot_up(now_us);
+        ctx.enter_preoperational();
+        true
+    } else {
+        false
+    }
+}
@@ -7,27 +7,51 @@ use zencan_common::{
     constants::object_ids,
     lss::LssIdentity,
     messages::{
-        CanId, CanMessage, Heartbeat, NmtCommandSpecifier, SyncObject, ZencanMessage, LSS_RESP_ID,
+        CanId, CanMessage, ConnectionSet, Heartbeat, SyncObject, VendorBroadcast, ZencanMessage,
+        LSS_RESP_ID,
     },
     nmt::NmtState,
-    NodeId,
+    objects::PersistGroup,
+    NodeId, TimeOfDay,
 };
 
-use crate::sdo_server::SdoServer;
+#[cfg(feature = "access-stats")]
+use crate::access_stats::{AccessStats, ObjectAccessCounter};
+#[cfg(feature = "process-timing")]
+use crate::process_timing::{ProcessStage, ProcessTiming, TimingClockFn};
+use crate::sdo_server::{SdoAccess, SdoServer};
 use crate::{
-    lss_slave::{LssConfig, LssSlave},
+    bus_state::{BusErrorPolicy, BusState},
+    diagnostics::{DiagnosticsAutosave, DiagnosticsSnapshot, MalformedFrame, NodeDiagnostics},
+    lss_slave::{self, LssConfig, LssContext, LssSlave},
+    network_time::NetworkClock,
+    nmt_slave::{self, NmtContext},
+    node_clock::{NodeClock, NodeTime},
     node_mbox::NodeMbox,
     node_state::NmtStateAccess as _,
-    object_dict::{find_object, ODEntry},
+    object_dict::{find_object, tpdo_event_bit, ODEntry},
+    pdo::{InactiveEventPolicy, Pdo, PdoKind},
+    verbose_log::verbose_debug,
     NodeState,
 };
 
-use defmt_or_log::{debug, info};
+use defmt_or_log::{info, warn};
+
+/// The number of node generated messages which can wait for space in the transmit queue
+const DEFERRED_TX_SIZE: usize = 4;
 
 pub type StoreNodeConfigFn<'a> = dyn FnMut(NodeId) + 'a;
 pub type StoreObjectsFn<'a> = dyn Fn(&mut dyn embedded_io::Read<Error = Infallible>, usize) + 'a;
 pub type StateChangeFn<'a> = dyn FnMut(&'a [ODEntry<'a>]) + 'a;
 pub type SyncReceiveFn<'a> = dyn FnMut(SyncObject) + 'a;
+pub type TxOverflowFn<'a> = dyn FnMut(CanMessage) + 'a;
+pub type VendorBroadcastFn<'a> = dyn FnMut(VendorBroadcast) + 'a;
+pub type StoreDiagnosticsFn<'a> = dyn FnMut(&DiagnosticsSnapshot) + 'a;
+pub type BusOffRecoveredFn<'a> = dyn FnMut() + 'a;
+pub type HeartbeatTimeoutFn<'a> = dyn FnMut(u8) + 'a;
+pub type SdoAccessFn<'a> = dyn FnMut(&SdoAccess) + 'a;
+pub type PdoConfigChangedFn<'a> = dyn FnMut(PdoKind, usize) + 'a;
+pub type MalformedFrameFn<'a> = dyn FnMut(MalformedFrame, CanMessage) + 'a;
 
 /// Collection of callbacks events which Node object can call.
 ///
@@ -77,6 +101,73 @@ pub struct Callbacks<'a> {
 
     /// The node has received a SYNC object
     pub sync_received: Option<&'a mut SyncReceiveFn<'a>>,
+
+    /// A message generated by the node could not be queued because the transmit queue was full
+    ///
+    /// The message is passed to the callback. This indicates that messages are not being read from
+    /// the mailbox quickly enough; an application may wish to raise an EMCY, or reduce its own
+    /// traffic. These messages are also counted in
+    /// [`NodeDiagnostics::tx_overflows`](crate::diagnostics::NodeDiagnostics::tx_overflows).
+    ///
+    /// The message is not lost: the node keeps it, and queues it on a later call to
+    /// [`Node::process`] once there is space. See [`Node::pending_tx_count`].
+    pub tx_overflow: Option<&'a mut TxOverflowFn<'a>>,
+
+    /// A command was received on the vendor broadcast channel
+    ///
+    /// The channel is enabled by setting its CAN ID with
+    /// [`NodeMbox::set_vendor_broadcast_id`]. Commands are delivered in all NMT states, and
+    /// regardless of whether the node has a configured node ID.
+    pub vendor_broadcast: Option<&'a mut VendorBroadcastFn<'a>>,
+
+    /// Store a checkpoint of the diagnostics counters to persistent storage
+    ///
+    /// This is called according to the policy set with [`Node::set_diagnostics_autosave`], or when
+    /// [`Node::checkpoint_diagnostics`] is called. The snapshot should be stored, and restored with
+    /// [`NodeDiagnostics::restore`] on the next boot.
+    pub store_diagnostics: Option<&'a mut StoreDiagnosticsFn<'a>>,
+
+    /// The CAN controller has recovered from the bus-off state
+    ///
+    /// This is called by [`Node::report_bus_state`] when the reported state changes from
+    /// [`BusState::BusOff`] to any other state. See the [`bus_state`](crate::bus_state) module.
+    pub bus_off_recovered: Option<&'a mut BusOffRecoveredFn<'a>>,
+
+    /// A node monitored by a heartbeat consumer has not sent a heartbeat within its heartbeat time
+    ///
+    /// The ID of the node is passed to the callback. It is called once when the heartbeat time
+    /// expires, and again only after the node's heartbeat has resumed and then stopped again. See
+    /// the [`heartbeat_consumer`](crate::heartbeat_consumer) module.
+    pub heartbeat_timeout: Option<&'a mut HeartbeatTimeoutFn<'a>>,
+
+    /// An SDO upload or download has finished
+    ///
+    /// This is called once for every SDO transfer, whether it completed or was aborted, with the
+    /// object accessed, the number of bytes transferred, and the outcome. It can be used to keep
+    /// an audit log of configuration changes, or to update values derived from written objects,
+    /// without implementing a callback object for each of them. Transfers abandoned because of an
+    /// NMT reset are not reported.
+    pub sdo_access: Option<&'a mut SdoAccessFn<'a>>,
+
+    /// The communication or mapping parameters of a PDO have been written
+    ///
+    /// The kind of PDO and its number, i.e. its position in [`NodeState::rpdos`] or
+    /// [`NodeState::tpdos`], are passed to the callback. It is called from [`Node::process`] after
+    /// any write to the PDO's configuration objects, whether by SDO or when restoring stored
+    /// objects, so that an application which interprets PDO data according to its mappings can
+    /// read the new mappings with [`Pdo::mappings`]. Reconfiguring a PDO usually takes several
+    /// writes, so the callback may be called more than once for a single change; the PDO is
+    /// normally disabled until the change is complete (see [`Pdo::valid`]).
+    pub pdo_config_changed: Option<&'a mut PdoConfigChangedFn<'a>>,
+
+    /// A frame with a recognized COB ID could not be parsed, and was dropped
+    ///
+    /// The kind of frame and the message are passed to the callback, from [`Node::process`]. Only
+    /// the most recent malformed frame received since the previous call to process is reported,
+    /// but all of them are counted in
+    /// [`NodeDiagnostics::rx_malformed`](crate::diagnostics::NodeDiagnostics::rx_malformed). This
+    /// is useful for finding a misbehaving device on the bus.
+    pub malformed_frame: Option<&'a mut MalformedFrameFn<'a>>,
 }
 
 impl<'a> Callbacks<'a> {
@@ -91,6 +182,14 @@ impl<'a> Callbacks<'a> {
             enter_stopped: None,
             enter_preoperational: None,
             sync_received: None,
+            tx_overflow: None,
+            vendor_broadcast: None,
+            store_diagnostics: None,
+            bus_off_recovered: None,
+            heartbeat_timeout: None,
+            sdo_access: None,
+            pdo_config_changed: None,
+            malformed_frame: None,
         }
     }
 }
@@ -114,11 +213,36 @@ fn read_heartbeat_period(od: &[ODEntry]) -> Option<u16> {
     obj.read_u16(0).ok()
 }
 
+fn read_sync_window_length(od: &[ODEntry]) -> u32 {
+    find_object(od, object_ids::SYNC_WINDOW_LENGTH)
+        .and_then(|obj| obj.read_u32(0).ok())
+        .unwrap_or(0)
+}
+
 fn read_autostart(od: &[ODEntry]) -> Option<bool> {
     let obj = find_object(od, object_ids::AUTO_START)?;
     Some(obj.read_u8(0).unwrap() != 0)
 }
 
+/// Settings read from the startup config object (0x5001)
+#[derive(Clone, Copy, Debug, Default)]
+struct StartupSettings {
+    first_heartbeat_delay_ms: u16,
+    bootup_retransmit_count: u8,
+    bootup_retransmit_interval_ms: u16,
+}
+
+fn read_startup_settings(od: &[ODEntry]) -> StartupSettings {
+    let Some(obj) = find_object(od, object_ids::STARTUP_CONFIG) else {
+        return StartupSettings::default();
+    };
+    StartupSettings {
+        first_heartbeat_delay_ms: obj.read_u16(1).unwrap_or(0),
+        bootup_retransmit_count: obj.read_u8(2).unwrap_or(0),
+        bootup_retransmit_interval_ms: obj.read_u16(3).unwrap_or(0),
+    }
+}
+
 /// The main object representing a node
 ///
 /// # Operation
@@ -139,12 +263,50 @@ pub struct Node<'a> {
     mbox: &'static NodeMbox,
     state: &'static NodeState<'static>,
     reassigned_node_id: Option<NodeId>,
+    /// Update the default PDO COB IDs as soon as the node ID is reassigned
+    rebind_pdo_cob_ids: bool,
     next_heartbeat_time_us: u64,
     heartbeat_period_ms: u16,
+    /// An out-of-cycle heartbeat is to be sent on the next process call
+    heartbeat_requested: bool,
+    /// No heartbeats are sent before this time, so that the first one after boot-up can be delayed
+    heartbeat_hold_until_us: u64,
+    /// Number of boot-up message repetitions still to be sent
+    bootup_retransmits_remaining: u8,
+    next_bootup_time_us: u64,
+    bootup_retransmit_interval_ms: u16,
     auto_start: bool,
-    last_process_time_us: u64,
+    /// The node's time, measured from the timestamps passed to process
+    time: NodeTime,
+    /// The process time at which the most recent SYNC was handled
+    last_sync_time_us: Option<u64>,
+    /// The network time, disciplined from received TIME messages
+    network_clock: NetworkClock,
+    /// TIME messages carry the time of the preceding SYNC
+    network_time_sync_alignment: bool,
     callbacks: Callbacks<'a>,
     transmit_flag: bool,
+    /// Messages generated by the node which are waiting for space in the transmit queue
+    deferred_tx: heapless::Deque<CanMessage, DEFERRED_TX_SIZE>,
+    /// Elapsed time not yet added to the operating time counter
+    operating_time_remainder_us: u64,
+    diagnostics_autosave: Option<DiagnosticsAutosave>,
+    /// The time and value of the last diagnostics checkpoint
+    last_diagnostics_checkpoint: Option<(u64, DiagnosticsSnapshot)>,
+    /// The last CAN controller state reported by the application
+    bus_state: BusState,
+    bus_error_policy: BusErrorPolicy,
+    /// Use extended IDs for the default SDO server
+    sdo_extended_ids: bool,
+    /// Serve SDO requests in the Stopped state
+    sdo_in_stopped: bool,
+    inactive_event_policy: InactiveEventPolicy,
+    #[cfg(feature = "access-stats")]
+    access_stats: Option<AccessStats<'a>>,
+    #[cfg(feature = "process-timing")]
+    timing_clock: Option<TimingClockFn>,
+    #[cfg(feature = "process-timing")]
+    process_timing: ProcessTiming,
 }
 
 impl<'a> Node<'a> {
@@ -183,7 +345,7 @@ impl<'a> Node<'a> {
         let heartbeat_period_ms = read_heartbeat_period(od).unwrap_or(0);
         let next_heartbeat_time_us = 0;
         let auto_start = read_autostart(od).unwrap_or(false);
-        let last_process_time_us = 0;
+        let last_sync_time_us = None;
         let transmit_flag = false;
 
         let mut node = Self {
@@ -196,14 +358,39 @@ impl<'a> Node<'a> {
             mbox,
             state,
             reassigned_node_id,
+            rebind_pdo_cob_ids: true,
             next_heartbeat_time_us,
             heartbeat_period_ms,
+            heartbeat_requested: false,
+            heartbeat_hold_until_us: 0,
+            bootup_retransmits_remaining: 0,
+            next_bootup_time_us: 0,
+            bootup_retransmit_interval_ms: 0,
             auto_start,
-            last_process_time_us,
+            time: NodeTime::default(),
+            last_sync_time_us,
+            network_clock: NetworkClock::new(),
+            network_time_sync_alignment: false,
             transmit_flag,
+            deferred_tx: heapless::Deque::new(),
+            operating_time_remainder_us: 0,
+            diagnostics_autosave: None,
+            last_diagnostics_checkpoint: None,
+            bus_state: BusState::ErrorActive,
+            bus_error_policy: BusErrorPolicy::new(),
+            sdo_extended_ids: false,
+            sdo_in_stopped: false,
+            inactive_event_policy: InactiveEventPolicy::SendOnStart,
+            #[cfg(feature = "access-stats")]
+            access_stats: None,
+            #[cfg(feature = "process-timing")]
+            timing_clock: None,
+            #[cfg(feature = "process-timing")]
+            process_timing: ProcessTiming::default(),
         };
 
         node.reset_app();
+        node.enter_bootup();
         node
     }
 
@@ -214,6 +401,260 @@ impl<'a> Node<'a> {
         self.reassigned_node_id = Some(node_id);
     }
 
+    /// Select whether PDO COB IDs follow a node ID change immediately
+    ///
+    /// When enabled, a PDO whose COB ID is derived from the node ID (i.e. `add_node_id` is set in
+    /// its defaults, and no other COB ID has been written) switches to the new ID as soon as the
+    /// node ID is changed, e.g. by LSS. When disabled, PDOs keep their COB IDs until the next NMT
+    /// reset. Enabled by default.
+    pub fn set_rebind_pdo_cob_ids(&mut self, enabled: bool) {
+        self.rebind_pdo_cob_ids = enabled;
+    }
+
+    /// Set the maximum number of SDO requests which will be handled in each call to process
+    ///
+    /// By default, one SDO request is handled per call. When process is called at a fixed rate,
+    /// this limits segmented transfer throughput to one segment per call. Raising the budget allows
+    /// a fast client's queued requests to be handled together, at the cost of a longer worst-case
+    /// process call. A value of 0 is treated as 1.
+    ///
+    /// No more than [`SDO_QUEUE_DEPTH`](crate::SDO_QUEUE_DEPTH) requests can be queued between
+    /// process calls, so larger values are treated as `SDO_QUEUE_DEPTH`.
+    pub fn set_sdo_request_budget(&mut self, budget: usize) {
+        self.sdo_server.set_request_budget(budget);
+    }
+
+    /// Select whether the SDO server runs in the Stopped state
+    ///
+    /// Per CiA 301, SDO communication is disabled in the Stopped state: requests received while
+    /// stopped are dropped without a response, and any transfer in progress is abandoned when the
+    /// node stops. SDO resumes when the node leaves the Stopped state. Some masters expect to
+    /// configure stopped nodes, and this can be set to serve SDO requests in every state.
+    /// Disabled by default.
+    pub fn set_sdo_in_stopped(&mut self, allowed: bool) {
+        self.sdo_in_stopped = allowed;
+    }
+
+    /// Set the policy for TPDO events raised while the node is not Operational
+    ///
+    /// See [`InactiveEventPolicy`]. The default is [`InactiveEventPolicy::SendOnStart`].
+    pub fn set_inactive_event_policy(&mut self, policy: InactiveEventPolicy) {
+        self.inactive_event_policy = policy;
+    }
+
+    /// Get the network time, as of the last call to [`process`](Self::process)
+    ///
+    /// The time is kept from the TIME messages received from the network's TIME producer, and
+    /// extrapolated between them using the node's clock. Returns None until the first TIME
+    /// message has been received. See the [`network_time`](crate::network_time) module for
+    /// details.
+    pub fn network_time(&self) -> Option<TimeOfDay> {
+        self.network_clock.now(self.time.now_us())
+    }
+
+    /// Select whether received TIME messages refer to the time of the preceding SYNC
+    ///
+    /// When enabled, the time in each TIME message is taken to be the network time at which the
+    /// most recent SYNC was received, rather than the time at which the TIME message was
+    /// received. Disabled by default.
+    pub fn set_network_time_sync_alignment(&mut self, enabled: bool) {
+        self.network_time_sync_alignment = enabled;
+    }
+
+    /// Select whether the default SDO server uses extended (29-bit) CAN IDs
+    ///
+    /// The default SDO server receives requests on 0x600 + node ID and sends responses on 0x580 +
+    /// node ID, or the bases set by [`set_connection_set`](Self::set_connection_set). By default
+    /// these are standard IDs; when `extended` is true, the same values are used as extended IDs,
+    /// and requests received with standard IDs are ignored. Clients must be configured to match,
+    /// e.g. with `SdoClient::new_extended`.
+    pub fn set_sdo_extended_ids(&mut self, extended: bool) {
+        self.sdo_extended_ids = extended;
+        if let NodeId::Configured(_) = self.node_id {
+            self.mbox.set_sdo_rx_cob_id(Some(self.sdo_rx_cob_id()));
+            self.mbox.set_sdo_tx_cob_id(Some(self.sdo_tx_cob_id()));
+        }
+    }
+
+    /// Set the base IDs of the predefined connection set
+    ///
+    /// The COB IDs of the default SDO server and the heartbeat producer are found by adding the
+    /// node ID to these bases, and heartbeat consumers listen on the heartbeat base. The change
+    /// takes effect immediately. By default, the bases are those set by the device config, or
+    /// [`ConnectionSet::DEFAULT`] if it does not set them.
+    ///
+    /// The node does not produce EMCY messages, so `emcy_base` is not used.
+    pub fn set_connection_set(&mut self, connection_set: ConnectionSet) {
+        self.mbox.set_connection_set(connection_set);
+        if let NodeId::Configured(_) = self.node_id {
+            self.mbox.set_sdo_rx_cob_id(Some(self.sdo_rx_cob_id()));
+            self.mbox.set_sdo_tx_cob_id(Some(self.sdo_tx_cob_id()));
+        }
+    }
+
+    /// Get the base IDs of the predefined connection set
+    pub fn connection_set(&self) -> ConnectionSet {
+        self.mbox.connection_set()
+    }
+
+    /// Count the SDO reads and writes of each object
+    ///
+    /// `counters` holds one counter for each entry in the object dictionary, in the same order.
+    /// Pass `None` to stop counting. See the [access_stats](crate::access_stats) module for more
+    /// info.
+    #[cfg(feature = "access-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "access-stats")))]
+    pub fn set_access_counters(&mut self, counters: Option<&'a [ObjectAccessCounter]>) {
+        self.access_stats = counters.map(|counters| AccessStats::new(self.od, counters));
+    }
+
+    /// Get the SDO access counts of each object, if counting is enabled
+    #[cfg(feature = "access-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "access-stats")))]
+    pub fn access_stats(&self) -> Option<AccessStats<'a>> {
+        self.access_stats
+    }
+
+    /// Set the clock used to measure the execution time of [`process`](Self::process)
+    ///
+    /// The clock must be monotonic and return microseconds. Pass `None` to stop measuring. See the
+    /// [process_timing](crate::process_timing) module for more info.
+    #[cfg(feature = "process-timing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "process-timing")))]
+    pub fn set_timing_clock(&mut self, clock: Option<TimingClockFn>) {
+        self.timing_clock = clock;
+    }
+
+    /// Set a time budget for a call to [`process`](Self::process), in microseconds
+    ///
+    /// Calls taking longer than the budget are counted in
+    /// [`ProcessTiming::budget_overruns`]. Pass `None` to remove the budget.
+    #[cfg(feature = "process-timing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "process-timing")))]
+    pub fn set_process_budget_us(&mut self, budget_us: Option<u32>) {
+        self.process_timing.set_budget_us(budget_us);
+    }
+
+    /// Get the measured execution times of [`process`](Self::process)
+    #[cfg(feature = "process-timing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "process-timing")))]
+    pub fn process_timing(&self) -> &ProcessTiming {
+        &self.process_timing
+    }
+
+    /// Clear the worst case execution times and the budget overrun count
+    #[cfg(feature = "process-timing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "process-timing")))]
+    pub fn reset_process_timing(&mut self) {
+        self.process_timing.reset();
+    }
+
+    /// Enable or disable automatic checkpointing of the diagnostics counters
+    ///
+    /// Checkpoints are passed to the [`Callbacks::store_diagnostics`] callback. The first
+    /// checkpoint is written no sooner than one interval after the next call to
+    /// [`process`](Self::process); it is assumed that the counters were just restored from
+    /// storage, if they are stored. Pass `None` to disable autosave.
+    pub fn set_diagnostics_autosave(&mut self, policy: Option<DiagnosticsAutosave>) {
+        self.diagnostics_autosave = policy;
+        self.last_diagnostics_checkpoint = None;
+    }
+
+    /// Immediately store a checkpoint of the diagnostics counters
+    ///
+    /// This can be used, e.g., before a controlled shutdown to avoid losing counts accumulated
+    /// since the last automatic checkpoint. It has no effect if no
+    /// [`Callbacks::store_diagnostics`] callback is provided.
+    pub fn checkpoint_diagnostics(&mut self) {
+        let snapshot = self.diagnostics().snapshot();
+        if let Some(cb) = &mut self.callbacks.store_diagnostics {
+            cb(&snapshot);
+            self.last_diagnostics_checkpoint = Some((self.time.now_us(), snapshot));
+        }
+    }
+
+    /// Report a change in the error state of the CAN controller
+    ///
+    /// The node reacts to the state according to its [`BusErrorPolicy`]. See the
+    /// [`bus_state`](crate::bus_state) module.
+    pub fn report_bus_state(&mut self, state: BusState) {
+        let previous = core::mem::replace(&mut self.bus_state, state);
+        if previous == state {
+            return;
+        }
+        info!("CAN bus state changed from {:?} to {:?}", previous, state);
+
+        if self.tpdos_paused() {
+            // Values queued before the error occurred are stale by the time the bus recovers
+            for pdo in self.state.tpdos() {
+                pdo.buffered_value.take();
+            }
+        }
+
+        if previous == BusState::BusOff {
+            if let Some(cb) = &mut self.callbacks.bus_off_recovered {
+                cb();
+            }
+        }
+    }
+
+    /// Get the CAN controller state most recently reported with
+    /// [`report_bus_state`](Self::report_bus_state)
+    pub fn bus_state(&self) -> BusState {
+        self.bus_state
+    }
+
+    /// Set the policy for reacting to CAN controller error states
+    pub fn set_bus_error_policy(&mut self, policy: BusErrorPolicy) {
+        self.bus_error_policy = policy;
+    }
+
+    /// Call the pdo_config_changed callback for each PDO whose configuration has been written
+    fn notify_pdo_config_changes(&mut self) {
+        let pdos = [
+            (PdoKind::Rpdo, self.state.rpdos()),
+            (PdoKind::Tpdo, self.state.tpdos()),
+        ];
+        let mut tpdo_changed = false;
+        for (kind, pdos) in pdos {
+            for (num, pdo) in pdos.iter().enumerate() {
+                if pdo.take_config_changed() {
+                    tpdo_changed |= kind == PdoKind::Tpdo;
+                    if let Some(cb) = &mut self.callbacks.pdo_config_changed {
+                        (cb)(kind, num);
+                    }
+                }
+            }
+        }
+        if tpdo_changed {
+            self.update_tpdo_event_masks(false);
+        }
+    }
+
+    /// Record in the event flags of each object which TPDOs it is mapped into
+    ///
+    /// This allows process to check only the TPDOs mapping an object which has signaled an event,
+    /// instead of every mapping of every TPDO. When `rebuild` is false, bits are only added, so that
+    /// no event can be missed while the masks are updated; a stale bit only costs an unnecessary
+    /// check. The masks are rebuilt from scratch on reset.
+    fn update_tpdo_event_masks(&self, rebuild: bool) {
+        if rebuild {
+            for entry in self.od {
+                entry.data.set_tpdo_event_mask(0);
+            }
+        }
+        for (num, pdo) in self.state.tpdos().iter().enumerate() {
+            for object in pdo.mapped_objects() {
+                let mask = object.data.tpdo_event_mask();
+                object.data.set_tpdo_event_mask(mask | tpdo_event_bit(num));
+            }
+        }
+    }
+
+    fn tpdos_paused(&self) -> bool {
+        self.bus_error_policy.pause_tpdos(self.bus_state)
+    }
+
     /// Run periodic processing
     ///
     /// This should be called periodically by the application so that the node can update it's
@@ -224,31 +665,49 @@ impl<'a> Node<'a> {
     /// when an action is required.
     ///
     /// # Arguments
-    /// - `now_us`: A monotonic time in microseconds. This is used for measuring time and triggering
-    ///   time-based actions such as heartbeat transmission or SDO timeout
+    /// - `now_us`: The time in microseconds. This is used for measuring time and triggering
+    ///   time-based actions such as heartbeat transmission or SDO timeout. Only the time elapsed
+    ///   since the previous call is used; if the time goes backwards, no time is considered to have
+    ///   elapsed. See the [`node_clock`](crate::node_clock) module.
     ///
     /// # Returns
     ///
     /// A boolean indicating if objects were updated. This will be true when an SDO download has
     /// been completed, or when one or more RPDOs have been received.
     pub fn process(&mut self, now_us: u64) -> bool {
-        let elapsed = (now_us - self.last_process_time_us) as u32;
-        self.last_process_time_us = now_us;
+        self.process_inner(now_us, 64)
+    }
 
-        self.transmit_flag = false;
+    /// Run periodic processing, reading the time from a [`NodeClock`]
+    ///
+    /// This is the same as [`process`](Self::process), but supports clocks which wrap, e.g. a
+    /// 32-bit hardware timer, as described in the [`node_clock`](crate::node_clock) module.
+    pub fn process_clock<C: NodeClock>(&mut self, clock: &mut C) -> bool {
+        self.process_inner(clock.now_us(), C::BITS)
+    }
 
-        let mut update_flag = false;
-        if let Some(new_node_id) = self.reassigned_node_id.take() {
-            self.node_id = new_node_id;
-            self.state.set_nmt_state(NmtState::Bootup);
-        }
+    fn process_inner(&mut self, clock_us: u64, clock_bits: u32) -> bool {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("node_process", node_id = self.node_id()).entered();
+        #[cfg(feature = "process-timing")]
+        let process_start = self.timing_now();
 
-        if self.nmt_state() == NmtState::Bootup {
-            // Set state before calling boot_up, so the heartbeat state is correct
-            self.enter_preoperational();
-            self.boot_up();
+        let elapsed = self.time.update(clock_us, clock_bits);
+        let now_us = self.time.now_us();
+
+        self.operating_time_remainder_us += elapsed;
+        if self.operating_time_remainder_us >= 1_000_000 {
+            let seconds = self.operating_time_remainder_us / 1_000_000;
+            self.operating_time_remainder_us %= 1_000_000;
+            self.diagnostics().record_operating_time(seconds as u32);
         }
 
+        self.transmit_flag = false;
+        self.retry_deferred_messages();
+
+        let mut update_flag = false;
+        nmt_slave::boot(self, now_us);
+
         // If auto start is set on boot, and we already have an ID, we make the first transition to
         // Operational automatically
         if self.auto_start && self.node_id.is_configured() {
@@ -258,14 +717,51 @@ impl<'a> Node<'a> {
         }
 
         // Process SDO server
-        let (message_sent, updated_index) =
-            self.sdo_server
-                .process(self.mbox.sdo_comms(), elapsed, self.od);
-
-        self.transmit_flag |= message_sent;
-        if updated_index.is_some() {
-            update_flag = true;
+        #[cfg(feature = "process-timing")]
+        let sdo_start = self.timing_now();
+        if self.sdo_enabled() {
+            let sdo_access = &mut self.callbacks.sdo_access;
+            #[cfg(feature = "access-stats")]
+            let access_stats = self.access_stats;
+            let (message_sent, updated_index) = self.sdo_server.process(
+                self.mbox.sdo_comms(),
+                elapsed.min(u32::MAX as u64) as u32,
+                self.od,
+                &mut |access| {
+                    #[cfg(feature = "access-stats")]
+                    if let Some(stats) = &access_stats {
+                        stats.record(&access);
+                    }
+                    if let Some(cb) = sdo_access {
+                        (*cb)(&access);
+                    }
+                },
+            );
+
+            self.transmit_flag |= message_sent;
+            self.mbox
+                .diagnostics()
+                .record_sdo_aborts(self.sdo_server.take_aborts_sent());
+            if let Some(object) = updated_index {
+                self.state.change_counters().record_change(object.index);
+                update_flag = true;
+            }
+        } else {
+            // Drop the requests received while SDO is disabled
+            self.mbox.sdo_comms().reset();
         }
+        // Requests and responses dropped because the SDO queues were full
+        self.mbox
+            .diagnostics()
+            .record_rx_overruns(self.mbox.sdo_comms().take_requests_dropped());
+        self.mbox
+            .diagnostics()
+            .record_tx_overflows(self.mbox.sdo_comms().take_responses_dropped());
+
+        #[cfg(feature = "process-timing")]
+        self.record_timing(ProcessStage::Sdo, sdo_start);
+
+        self.notify_pdo_config_changes();
 
         // Read and clear the store command flag
         if self
@@ -280,45 +776,69 @@ impl<'a> Node<'a> {
             }
         }
 
+        self.autosave_diagnostics(now_us);
+
+        if let Some((kind, msg)) = self.mbox.read_malformed_mbox() {
+            if let Some(cb) = &mut self.callbacks.malformed_frame {
+                cb(kind, msg);
+            }
+        }
+
         // Process NMT
+        #[cfg(feature = "process-timing")]
+        let nmt_start = self.timing_now();
         if let Some(msg) = self.mbox.read_nmt_mbox() {
             if let Ok(ZencanMessage::NmtCommand(cmd)) = msg.try_into() {
                 self.message_count += 1;
-                // We cannot respond to NMT commands if we do not have a valid node ID
-
-                if let NodeId::Configured(node_id) = self.node_id {
-                    if cmd.node == 0 || cmd.node == node_id.raw() {
-                        debug!("Received NMT command: {:?}", cmd.cs);
-                        self.handle_nmt_command(cmd.cs);
-                    }
+                let node_id = self.node_id;
+                nmt_slave::handle_command(self, node_id, cmd);
+            }
+            // RPDOs received while the command was pending are only applied if it started the node
+            if self.nmt_state() != NmtState::Operational {
+                for pdo in self.state.rpdos() {
+                    pdo.buffered_value.take();
                 }
             }
         }
 
-        if let Ok(Some(resp)) = self.lss_slave.process(self.mbox.lss_receiver()) {
+        #[cfg(feature = "process-timing")]
+        self.record_timing(ProcessStage::Nmt, nmt_start);
+
+        #[cfg(feature = "process-timing")]
+        let lss_start = self.timing_now();
+        let mbox = self.mbox;
+        if let Some(resp) = lss_slave::process_request(self, mbox.lss_receiver()) {
             self.send_message(resp.to_can_message(LSS_RESP_ID));
+        }
 
-            if let Some(event) = self.lss_slave.pending_event() {
-                info!("LSS Slave Event: {:?}", event);
-                match event {
-                    crate::lss_slave::LssEvent::StoreConfiguration => {
-                        if let Some(cb) = &mut self.callbacks.store_node_config {
-                            (cb)(self.node_id)
-                        }
-                    }
-                    crate::lss_slave::LssEvent::ActivateBitTiming {
-                        table: _,
-                        index: _,
-                        delay: _,
-                    } => (),
-                    crate::lss_slave::LssEvent::ConfigureNodeId { node_id } => {
-                        self.set_node_id(node_id)
+        #[cfg(feature = "process-timing")]
+        self.record_timing(ProcessStage::Lss, lss_start);
+
+        if let Some(msg) = self.mbox.read_vendor_mbox() {
+            match VendorBroadcast::try_from(msg.data()) {
+                Ok(cmd) => {
+                    if let Some(cb) = &mut self.callbacks.vendor_broadcast {
+                        (cb)(cmd);
                     }
                 }
+                Err(_) => warn!("Invalid vendor broadcast message"),
             }
         }
 
-        if self.heartbeat_period_ms != 0 && now_us >= self.next_heartbeat_time_us {
+        if self.bootup_retransmits_remaining > 0 && now_us >= self.next_bootup_time_us {
+            self.bootup_retransmits_remaining -= 1;
+            self.next_bootup_time_us = now_us + (self.bootup_retransmit_interval_ms as u64) * 1000;
+            self.send_bootup();
+        }
+
+        if now_us < self.heartbeat_hold_until_us {
+            // Still waiting for the first heartbeat delay to expire after boot-up
+        } else if self.heartbeat_requested {
+            self.heartbeat_requested = false;
+            self.send_heartbeat();
+            // Restart the heartbeat period from the out-of-cycle heartbeat
+            self.next_heartbeat_time_us = now_us + (self.heartbeat_period_ms as u64) * 1000;
+        } else if self.heartbeat_period_ms != 0 && now_us >= self.next_heartbeat_time_us {
             self.send_heartbeat();
             // Perform catchup if we are behind, e.g. if we have not send a heartbeat in a long
             // time because we have not been configured
@@ -327,49 +847,129 @@ impl<'a> Node<'a> {
             }
         }
 
+        for consumer in self.mbox.heartbeat_consumers() {
+            if consumer.update(now_us) {
+                warn!("Heartbeat timeout for node {}", consumer.node_id());
+                if let Some(cb) = &mut self.callbacks.heartbeat_timeout {
+                    (cb)(consumer.node_id());
+                }
+            }
+        }
+
         // check if a sync has been received
         let sync = self.mbox.read_sync_flag();
+        if sync.is_some() {
+            self.last_sync_time_us = Some(now_us);
+        }
+        let sync_window_us = read_sync_window_length(self.od);
+
+        // TIME is consumed in the same states as SYNC
+        if let Some(time) = self.mbox.read_time_mbox() {
+            if matches!(
+                self.nmt_state(),
+                NmtState::Operational | NmtState::PreOperational
+            ) {
+                let local_us = if self.network_time_sync_alignment {
+                    self.last_sync_time_us.unwrap_or(now_us)
+                } else {
+                    now_us
+                };
+                self.network_clock.update(time.time, local_us);
+            }
+        }
 
+        #[cfg(feature = "process-timing")]
+        let pdo_start = self.timing_now();
         if self.nmt_state() == NmtState::Operational {
             // TODO Process RPDO when sync received
 
-            // Swap the active TPDO flag set. Returns true if any object flags were set since last
-            // toggle. Tracking the global trigger is a performance boost, at least in the frequent
-            // case when no events have been triggered. The goal is for `process` to be as fast as
-            // possible when it has nothing to do, so it can be called frequently with little cost.
-            let global_trigger = self.state.object_flag_sync().toggle();
+            // Synchronous TPDOs which were not transmitted before the sync window closed are
+            // dropped
+            if sync_window_us != 0 && !self.in_sync_window(None, now_us, sync_window_us) {
+                for pdo in self.state.tpdos() {
+                    if pdo.is_synchronous() && pdo.buffered_value.take().is_some() {
+                        verbose_debug!("Dropping TPDO not sent within sync window");
+                    }
+                }
+            }
 
+            // Swap the active TPDO flag set. Returns the event bits of the TPDOs mapping an object
+            // which has set a flag since the last toggle. Only those TPDOs need their mappings
+            // checked, so that `process` stays fast with many TPDOs, and is as fast as possible in
+            // the frequent case when no events have been triggered.
+            let dirty_tpdos = self.state.object_flag_sync().toggle_tpdo_events();
+
+            if dirty_tpdos != 0 {
+                let dirty = || {
+                    self.state
+                        .tpdos()
+                        .iter()
+                        .enumerate()
+                        .filter(move |(num, _)| dirty_tpdos & tpdo_event_bit(*num) != 0)
+                        .map(|(_, pdo)| pdo)
+                };
+                // Every TPDO latches its events before any object flags are cleared, so that an
+                // object mapped into multiple TPDOs triggers each of them
+                for pdo in dirty() {
+                    if pdo.valid() && pdo.transmission_type() >= 254 {
+                        pdo.latch_events();
+                    }
+                }
+                for pdo in dirty() {
+                    pdo.clear_events();
+                }
+            }
+
+            let tpdos_paused = self.tpdos_paused();
             for pdo in self.state.tpdos() {
                 if !(pdo.valid()) {
+                    pdo.take_event();
+                    continue;
+                }
+                // While paused, events remain pending so they are sent once transmission resumes
+                if tpdos_paused {
                     continue;
                 }
                 let transmission_type = pdo.transmission_type();
                 if transmission_type >= 254 {
-                    if global_trigger && pdo.read_events() {
-                        pdo.send_pdo();
-                        self.transmit_flag = true;
+                    // The timer is checked even when an event is pending, so that it is started
+                    let timer_expired = pdo.event_timer_expired(now_us);
+                    // Events raised during the inhibit time remain pending, so that they are
+                    // combined and sent when it expires
+                    if pdo.inhibited(now_us) {
+                        continue;
+                    }
+                    if pdo.take_event() || timer_expired {
+                        pdo.restart_event_timer(now_us);
+                        pdo.start_inhibit(now_us);
+                        self.send_pdo(pdo);
                     }
-                } else if sync.is_some() && pdo.sync_update() {
-                    pdo.send_pdo();
-                    self.transmit_flag = true;
+                } else if sync.is_some_and(|sync| pdo.sync_update(sync.count)) {
+                    self.send_pdo(pdo);
                 }
             }
 
-            for pdo in self.state.tpdos() {
-                pdo.clear_events();
-            }
-
             for rpdo in self.state.rpdos() {
                 if !rpdo.valid() {
                     continue;
                 }
                 if let Some(new_data) = rpdo.buffered_value.take() {
+                    if sync_window_us != 0
+                        && rpdo.is_synchronous()
+                        && !self.in_sync_window(rpdo.rx_timestamp(), now_us, sync_window_us)
+                    {
+                        verbose_debug!("Discarding RPDO received outside sync window");
+                        continue;
+                    }
                     rpdo.store_pdo_data(&new_data);
                     update_flag = true;
                 }
             }
         }
 
+        #[cfg(feature = "process-timing")]
+        self.record_timing(ProcessStage::Pdo, pdo_start);
+
         // Sync callback active when in operational or preop states. It is called after PDO
         // processing, so that any pending RPDOs which are transferred on SYNC are transferred
         // before the callback is run
@@ -388,25 +988,28 @@ impl<'a> Node<'a> {
             self.mbox.transmit_notify();
         }
 
+        #[cfg(feature = "process-timing")]
+        self.record_timing(ProcessStage::Total, process_start);
+
         update_flag
     }
 
-    fn handle_nmt_command(&mut self, cmd: NmtCommandSpecifier) {
-        let prev_state = self.nmt_state();
+    /// Read the timing clock, if one is set
+    #[cfg(feature = "process-timing")]
+    fn timing_now(&self) -> Option<u64> {
+        self.timing_clock.map(|clock| clock())
+    }
 
-        match cmd {
-            NmtCommandSpecifier::Start => self.enter_operational(),
-            NmtCommandSpecifier::Stop => self.enter_stopped(),
-            NmtCommandSpecifier::EnterPreOp => self.enter_preoperational(),
-            NmtCommandSpecifier::ResetApp => self.reset_app(),
-            NmtCommandSpecifier::ResetComm => self.reset_comm(),
+    /// Record the time elapsed since `start` for a stage of process
+    #[cfg(feature = "process-timing")]
+    fn record_timing(&mut self, stage: ProcessStage, start: Option<u64>) {
+        let (Some(clock), Some(start)) = (self.timing_clock, start) else {
+            return;
+        };
+        let elapsed_us = clock().saturating_sub(start).min(u32::MAX as u64) as u32;
+        if self.process_timing.record(stage, elapsed_us) {
+            verbose_debug!("Process took {}us, exceeding its budget", elapsed_us);
         }
-
-        debug!(
-            "NMT state changed from {:?} to {:?}",
-            prev_state,
-            self.nmt_state()
-        );
     }
 
     /// Get the current Node ID
@@ -424,66 +1027,390 @@ impl<'a> Node<'a> {
         self.message_count
     }
 
+    /// Get the number of messages which can currently be added to the transmit queue
+    ///
+    /// See [`NodeMbox::tx_queue_free`].
+    pub fn tx_queue_free(&self) -> usize {
+        self.mbox.tx_queue_free()
+    }
+
+    /// Get the communication statistics for the node
+    ///
+    /// The same counters can be read remotely from object 0x5F00, when it is enabled in the device
+    /// config.
+    pub fn diagnostics(&self) -> &NodeDiagnostics {
+        self.mbox.diagnostics()
+    }
+
+    /// Get the time until an event-driven TPDO is next due to be sent, relative to the last call to
+    /// [`process`](Self::process)
+    ///
+    /// This is the time until the inhibit time of a TPDO with a pending event expires, or until the
+    /// next event timer expiry, whichever is sooner. It is 0 if a TPDO is already due. Applications
+    /// can use it to schedule the next call to `process`, so that TPDOs held off by their inhibit
+    /// time are sent without delay. See the [pdo module docs](crate::pdo#inhibit-time).
+    ///
+    /// Returns None if no TPDO is waiting on a timer, or if TPDOs are not currently sent.
+    pub fn time_until_tpdo_us(&self) -> Option<u64> {
+        if self.nmt_state() != NmtState::Operational || self.tpdos_paused() {
+            return None;
+        }
+        let now_us = self.time.now_us();
+        self.state
+            .tpdos()
+            .iter()
+            .filter_map(|pdo| pdo.next_due_us())
+            .min()
+            .map(|due| due.saturating_sub(now_us))
+    }
+
+    /// Get the COB IDs used by the SDO server, as a (request, response) pair
+    ///
+    /// Returns None if the node does not have a configured node ID, in which case the SDO server is
+    /// not active.
+    pub fn sdo_cob_ids(&self) -> Option<(CanId, CanId)> {
+        Some((self.mbox.sdo_rx_cob_id()?, self.mbox.sdo_tx_cob_id()?))
+    }
+
+    /// Get the COB ID on which heartbeat messages are produced
+    ///
+    /// Returns None if the node does not have a configured node ID
+    pub fn heartbeat_cob_id(&self) -> Option<CanId> {
+        match self.node_id {
+            NodeId::Configured(node_id) => Some(CanId::Std(
+                self.mbox.connection_set().heartbeat_id(node_id.raw()),
+            )),
+            NodeId::Unconfigured => None,
+        }
+    }
+
+    /// Get the heartbeat producer period in milliseconds
+    ///
+    /// A value of 0 means heartbeat production is disabled
+    pub fn heartbeat_period_ms(&self) -> u16 {
+        self.heartbeat_period_ms
+    }
+
+    /// Send a heartbeat out of cycle
+    ///
+    /// The heartbeat is queued on the next call to [`process`](Self::process), and the heartbeat
+    /// period restarts from that time. It is sent even when periodic heartbeat production is
+    /// disabled, but not when the node does not have a configured node ID.
+    ///
+    /// The node already sends a heartbeat immediately when its NMT state changes, so this is only
+    /// needed when the application wants to announce itself at some other time.
+    pub fn force_heartbeat(&mut self) {
+        self.heartbeat_requested = true;
+    }
+
+    /// Check whether a message falls within the sync window following the most recent SYNC
+    ///
+    /// When both the message and the SYNC carry receive timestamps, they are compared directly.
+    /// Otherwise, the time at which process handled the SYNC is compared to `now_us`.
+    fn in_sync_window(&self, rx_timestamp: Option<u64>, now_us: u64, window_us: u32) -> bool {
+        if let (Some(rx), Some(sync)) = (rx_timestamp, self.mbox.last_sync_timestamp()) {
+            return rx >= sync && rx - sync <= window_us as u64;
+        }
+        self.last_sync_time_us
+            .is_some_and(|sync| now_us.saturating_sub(sync) <= window_us as u64)
+    }
+
     fn sdo_tx_cob_id(&self) -> CanId {
         let node_id: u8 = self.node_id.into();
-        CanId::Std(0x580 + node_id as u16)
+        self.sdo_cob_id(self.mbox.connection_set().sdo_response_id(node_id))
     }
 
     fn sdo_rx_cob_id(&self) -> CanId {
         let node_id: u8 = self.node_id.into();
-        CanId::Std(0x600 + node_id as u16)
+        self.sdo_cob_id(self.mbox.connection_set().sdo_request_id(node_id))
+    }
+
+    fn sdo_cob_id(&self, id: u16) -> CanId {
+        if self.sdo_extended_ids {
+            CanId::Extended(id as u32)
+        } else {
+            CanId::Std(id)
+        }
+    }
+
+    fn autosave_diagnostics(&mut self, now_us: u64) {
+        let Some(policy) = self.diagnostics_autosave else {
+            return;
+        };
+        let snapshot = self.diagnostics().snapshot();
+        match self.last_diagnostics_checkpoint {
+            None => self.last_diagnostics_checkpoint = Some((now_us, snapshot)),
+            Some((saved_us, saved)) => {
+                if policy.checkpoint_due(&saved, &snapshot, now_us.saturating_sub(saved_us)) {
+                    self.checkpoint_diagnostics();
+                }
+            }
+        }
+    }
+
+    /// Get the number of messages generated by the node which are waiting to be queued
+    ///
+    /// When the transmit queue is full, messages generated by the node -- heartbeats, including the
+    /// boot-up message, and LSS responses -- are held by the node, and queued on a later call to
+    /// [`Node::process`] once there is space. A non-zero count indicates that the application is
+    /// not taking messages from the mailbox fast enough.
+    ///
+    /// SDO server responses do not use the transmit queue, and are never held back by it.
+    pub fn pending_tx_count(&self) -> usize {
+        self.deferred_tx.len()
     }
 
     fn send_message(&mut self, msg: CanMessage) {
         self.transmit_flag = true;
-        // TODO: return  the error, and then handle it everywhere
-        self.mbox.queue_transmit_message(msg).ok();
+        // Messages which are already waiting go first, so that messages are sent in order
+        let result = if self.deferred_tx.is_empty() {
+            self.mbox.queue_transmit_message(msg)
+        } else {
+            self.mbox.diagnostics().record_tx_overflow();
+            Err(msg)
+        };
+        if let Err(msg) = result {
+            if let Some(cb) = &mut self.callbacks.tx_overflow {
+                cb(msg);
+            }
+            self.defer_message(msg);
+        }
+    }
+
+    fn defer_message(&mut self, msg: CanMessage) {
+        self.mbox.notify_on_tx_space();
+        // Only the latest heartbeat needs to be sent, but the boot-up message must not be replaced
+        let replace_last = self.deferred_tx.back().is_some_and(|last| {
+            self.is_heartbeat(&msg) && last.id() == msg.id() && !self.is_bootup(last)
+        });
+        if replace_last {
+            // Unwrap safety: replace_last is only set when there is a last message
+            *self.deferred_tx.back_mut().unwrap() = msg;
+            return;
+        }
+        if !self.deferred_tx.is_full() {
+            // Unwrap safety: checked for space above
+            self.deferred_tx.push_back(msg).unwrap();
+            return;
+        }
+
+        warn!("Too many messages waiting for the transmit queue; message dropped");
+        self.mbox.diagnostics().record_tx_dropped();
+        if !self.is_bootup(&msg) {
+            return;
+        }
+        // The boot-up message tells the network the node has (re)started, so make room for it by
+        // dropping the oldest message which is not a boot-up
+        let mut kept = heapless::Deque::<CanMessage, DEFERRED_TX_SIZE>::new();
+        let mut evicted = false;
+        while let Some(m) = self.deferred_tx.pop_front() {
+            if !evicted && !self.is_bootup(&m) {
+                evicted = true;
+                continue;
+            }
+            // Unwrap safety: kept holds no more messages than deferred_tx did
+            kept.push_back(m).unwrap();
+        }
+        if evicted {
+            // Unwrap safety: a message was removed above
+            kept.push_back(msg).unwrap();
+        }
+        self.deferred_tx = kept;
+    }
+
+    fn is_heartbeat(&self, msg: &CanMessage) -> bool {
+        let connection_set = self.mbox.connection_set();
+        matches!(msg.id(), CanId::Std(id) if connection_set.heartbeat_node(id).is_some())
+    }
+
+    fn is_bootup(&self, msg: &CanMessage) -> bool {
+        self.is_heartbeat(msg) && msg.data() == [NmtState::Bootup as u8]
+    }
+
+    fn retry_deferred_messages(&mut self) {
+        while let Some(msg) = self.deferred_tx.pop_front() {
+            if let Err(msg) = self.mbox.requeue_transmit_message(msg) {
+                // Unwrap safety: a slot was just freed by pop_front
+                self.deferred_tx.push_front(msg).unwrap();
+                break;
+            }
+            self.transmit_flag = true;
+        }
+    }
+
+    fn send_pdo(&mut self, pdo: &Pdo) {
+        if pdo.send_pdo() {
+            self.mbox.diagnostics().record_pdo_dropped();
+        }
+        self.transmit_flag = true;
+    }
+
+    /// Returns true if SDO requests are served in the current NMT state
+    fn sdo_enabled(&self) -> bool {
+        self.sdo_in_stopped || self.nmt_state() != NmtState::Stopped
+    }
+
+    /// Send the boot-up message, which is a heartbeat carrying the Bootup state
+    fn send_bootup(&mut self) {
+        if let NodeId::Configured(node_id) = self.node_id {
+            let bootup = Heartbeat {
+                node: node_id.raw(),
+                toggle: false,
+                state: NmtState::Bootup,
+            };
+            self.send_message(bootup.to_message(&self.mbox.connection_set()));
+        }
+    }
+
+    /// Update the NMT state, and request a heartbeat to announce it if it has changed
+    ///
+    /// CiA 301 expects a new state to be reported promptly, rather than on the next periodic
+    /// heartbeat. Nothing is sent when heartbeat production is disabled.
+    fn set_nmt_state(&mut self, state: NmtState) {
+        let prev_state = self.nmt_state();
+        if self.heartbeat_period_ms != 0 && prev_state != state {
+            self.heartbeat_requested = true;
+        }
+        self.state.set_nmt_state(state);
+
+        let send_on_start = self.inactive_event_policy == InactiveEventPolicy::SendOnStart;
+        if prev_state == NmtState::Operational && state != NmtState::Operational {
+            // PDOs are only exchanged while Operational, so drop any waiting to be sent or applied
+            for pdo in self.state.tpdos() {
+                pdo.drop_buffered_value(send_on_start);
+                pdo.reset_event_timer();
+                pdo.reset_inhibit();
+            }
+            for pdo in self.state.rpdos() {
+                pdo.buffered_value.take();
+            }
+        } else if prev_state != NmtState::Operational
+            && state == NmtState::Operational
+            && !send_on_start
+        {
+            self.discard_tpdo_events();
+        }
+    }
+
+    /// Discard all pending TPDO events, and any event flags set on mapped objects
+    fn discard_tpdo_events(&self) {
+        let dirty_tpdos = self.state.object_flag_sync().toggle_tpdo_events();
+        for (num, pdo) in self.state.tpdos().iter().enumerate() {
+            if dirty_tpdos & tpdo_event_bit(num) != 0 {
+                pdo.clear_events();
+            }
+            pdo.take_event();
+        }
+    }
+
+    fn send_heartbeat(&mut self) {
+        if let NodeId::Configured(node_id) = self.node_id {
+            let heartbeat = Heartbeat {
+                node: node_id.raw(),
+                toggle: false,
+                state: self.nmt_state(),
+            };
+            self.send_message(heartbeat.to_message(&self.mbox.connection_set()));
+            self.next_heartbeat_time_us += (self.heartbeat_period_ms as u64) * 1000;
+        }
+    }
+}
+
+impl NmtContext for Node<'_> {
+    fn nmt_state(&self) -> NmtState {
+        self.state.nmt_state()
     }
 
     fn enter_operational(&mut self) {
-        self.state.set_nmt_state(NmtState::Operational);
+        self.set_nmt_state(NmtState::Operational);
         if let Some(cb) = &mut self.callbacks.enter_operational {
             (*cb)(self.od);
         }
     }
 
     fn enter_stopped(&mut self) {
-        self.state.set_nmt_state(NmtState::Stopped);
+        self.set_nmt_state(NmtState::Stopped);
+        if !self.sdo_enabled() {
+            // Abandon any SDO transfer in progress
+            self.sdo_server.reset();
+            self.mbox.sdo_comms().reset();
+        }
         if let Some(cb) = &mut self.callbacks.enter_stopped {
             (*cb)(self.od);
         }
     }
 
     fn enter_preoperational(&mut self) {
-        self.state.set_nmt_state(NmtState::PreOperational);
+        self.set_nmt_state(NmtState::PreOperational);
         if let Some(cb) = &mut self.callbacks.enter_preoperational {
             (*cb)(self.od);
         }
     }
 
+    fn enter_bootup(&mut self) {
+        self.state.set_nmt_state(NmtState::Bootup);
+    }
+
     fn reset_app(&mut self) {
+        self.last_sync_time_us = None;
+        self.network_clock.reset();
+        for consumer in self.mbox.heartbeat_consumers() {
+            consumer.reset();
+        }
         // TODO: All objects should get reset to their defaults, but that isn't yet supported
+        // Drop any SDO transfer in progress
+        self.sdo_server.reset();
+        self.mbox.sdo_comms().reset();
         for pdo in self.state.rpdos().iter().chain(self.state.tpdos()) {
+            pdo.buffered_value.take();
             pdo.init_defaults(self.node_id);
         }
+        self.update_tpdo_event_masks(true);
 
         if let Some(reset_app_cb) = &mut self.callbacks.reset_app {
             (*reset_app_cb)(self.od);
         }
-        self.state.set_nmt_state(NmtState::Bootup);
+        // Any object may have been restored from storage
+        for group in PersistGroup::ALL {
+            self.state.change_counters().record_group_change(group);
+        }
     }
 
     fn reset_comm(&mut self) {
+        self.last_sync_time_us = None;
+        for consumer in self.mbox.heartbeat_consumers() {
+            consumer.reset();
+        }
+        // Drop any SDO transfer in progress
+        self.sdo_server.reset();
+        self.mbox.sdo_comms().reset();
         for pdo in self.state.rpdos().iter().chain(self.state.tpdos()) {
+            pdo.buffered_value.take();
             pdo.init_defaults(self.node_id);
         }
+        self.update_tpdo_event_masks(true);
         if let Some(reset_comms_cb) = &mut self.callbacks.reset_comms {
             (*reset_comms_cb)(self.od);
         }
-        self.state.set_nmt_state(NmtState::Bootup);
+        self.state
+            .change_counters()
+            .record_group_change(PersistGroup::Communication);
+    }
+
+    fn apply_reassigned_node_id(&mut self) -> bool {
+        let Some(new_node_id) = self.reassigned_node_id.take() else {
+            return false;
+        };
+        self.node_id = new_node_id;
+        if self.rebind_pdo_cob_ids {
+            for pdo in self.state.rpdos().iter().chain(self.state.tpdos()) {
+                pdo.set_node_id(new_node_id);
+            }
+        }
+        true
     }
 
-    fn boot_up(&mut self) {
+    fn boot_up(&mut self, now_us: u64) {
         // Reset the LSS slave with the new ID
         self.lss_slave.update_config(LssConfig {
             identity: read_identity(self.od).unwrap_or_default(),
@@ -495,37 +1422,73 @@ impl<'a> Node<'a> {
             info!("Booting node with ID {}", node_id.raw());
             self.mbox.set_sdo_rx_cob_id(Some(self.sdo_rx_cob_id()));
             self.mbox.set_sdo_tx_cob_id(Some(self.sdo_tx_cob_id()));
-            self.send_heartbeat();
+            self.send_bootup();
+
+            // The startup config may have been changed since the last reset
+            let startup = read_startup_settings(self.od);
+            self.heartbeat_hold_until_us =
+                now_us + (startup.first_heartbeat_delay_ms as u64) * 1000;
+            self.bootup_retransmits_remaining = startup.bootup_retransmit_count;
+            self.bootup_retransmit_interval_ms = startup.bootup_retransmit_interval_ms;
+            self.next_bootup_time_us =
+                now_us + (startup.bootup_retransmit_interval_ms as u64) * 1000;
+        } else {
+            self.bootup_retransmits_remaining = 0;
         }
     }
+}
 
-    fn send_heartbeat(&mut self) {
-        if let NodeId::Configured(node_id) = self.node_id {
-            let heartbeat = Heartbeat {
-                node: node_id.raw(),
-                toggle: false,
-                state: self.nmt_state(),
-            };
-            self.send_message(heartbeat.into());
-            self.next_heartbeat_time_us += (self.heartbeat_period_ms as u64) * 1000;
+impl LssContext for Node<'_> {
+    fn lss_slave(&mut self) -> &mut LssSlave {
+        &mut self.lss_slave
+    }
+
+    fn store_node_config(&mut self) {
+        if let Some(cb) = &mut self.callbacks.store_node_config {
+            (cb)(self.node_id)
         }
     }
+
+    fn reassign_node_id(&mut self, node_id: NodeId) {
+        self.set_node_id(node_id)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use zencan_common::{
+        messages::{NmtCommand, NmtCommandSpecifier, SyncObject},
         nmt::NmtState,
         objects::{ObjectCode, SubInfo},
-        CanMessage, NodeId,
+        sdo::{SdoRequest, SdoResponse},
+        AtomicCell, CanId, CanMessage, NodeId,
     };
 
+    use super::{DEFERRED_TX_SIZE, LSS_RESP_ID};
     use crate::{
+        bus_state::{BusErrorPolicy, BusState},
+        diagnostics::{DiagnosticsAutosave, DiagnosticsSnapshot, InternalError},
+        node_clock::Counter32,
         object_dict::{ODEntry, ProvidesSubObjects, ScalarField, SubObjectAccess},
+        pdo::Pdo,
         priority_queue::PriorityQueue,
         Callbacks, Node, NodeMbox, NodeState,
     };
 
+    /// Create node 1, with a transmit queue of `N` messages, and return it along with its mailbox
+    fn test_node<'a, const N: usize>(
+        od_table: &'static [ODEntry<'static>],
+        tpdos: &'static [Pdo<'static>],
+        callbacks: Callbacks<'a>,
+    ) -> (Node<'a>, &'static NodeMbox) {
+        let tx_queue = Box::leak(Box::new(PriorityQueue::<N, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
+        let mbox = Box::leak(Box::new(NodeMbox::new(&[], tpdos, tx_queue, sdo_buffer)));
+        let state = Box::leak(Box::new(NodeState::new(&[], tpdos)));
+        let node = Node::new(NodeId::new(1).unwrap(), callbacks, mbox, state, od_table);
+        (node, mbox)
+    }
+
     struct AutoStartObject {
         value: ScalarField<u8>,
     }
@@ -558,18 +1521,7 @@ mod tests {
             data: object5000,
         }]));
 
-        let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
-        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
-        let mbox = Box::leak(Box::new(NodeMbox::new(&[], &[], tx_queue, sdo_buffer)));
-        let state = Box::leak(Box::new(NodeState::new(&[], &[])));
-
-        let mut node = Node::new(
-            NodeId::new(1).unwrap(),
-            Callbacks::default(),
-            mbox,
-            state,
-            od_table,
-        );
+        let (mut node, _) = test_node::<4>(od_table, &[], Callbacks::new());
 
         node.process(0);
         assert_eq!(NmtState::Operational, node.nmt_state());
@@ -583,20 +1535,395 @@ mod tests {
             data: object5000,
         }]));
 
-        let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
-        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
-        let mbox = Box::leak(Box::new(NodeMbox::new(&[], &[], tx_queue, sdo_buffer)));
-        let state = Box::leak(Box::new(NodeState::new(&[], &[])));
+        let (mut node, _) = test_node::<4>(od_table, &[], Callbacks::new());
 
-        let mut node = Node::new(
-            NodeId::new(1).unwrap(),
-            Callbacks::default(),
-            mbox,
-            state,
-            od_table,
+        node.process(0);
+        assert_eq!(NmtState::PreOperational, node.nmt_state());
+    }
+
+    struct StartupConfigObject {
+        max_sub: ScalarField<u8>,
+        first_heartbeat_delay: ScalarField<u16>,
+        bootup_retransmit_count: ScalarField<u8>,
+        bootup_retransmit_interval: ScalarField<u16>,
+    }
+
+    impl ProvidesSubObjects for StartupConfigObject {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                0 => Some((SubInfo::MAX_SUB_NUMBER, &self.max_sub)),
+                1 => Some((SubInfo::new_u16().rw_access(), &self.first_heartbeat_delay)),
+                2 => Some((SubInfo::new_u8().rw_access(), &self.bootup_retransmit_count)),
+                3 => Some((
+                    SubInfo::new_u16().rw_access(),
+                    &self.bootup_retransmit_interval,
+                )),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Record
+        }
+    }
+
+    #[test]
+    fn test_startup_config() {
+        let object5001 = Box::leak(Box::new(StartupConfigObject {
+            max_sub: ScalarField::<u8>::new(3),
+            first_heartbeat_delay: ScalarField::<u16>::new(50),
+            bootup_retransmit_count: ScalarField::<u8>::new(2),
+            bootup_retransmit_interval: ScalarField::<u16>::new(20),
+        }));
+        let od_table = Box::leak(Box::new([ODEntry {
+            index: 0x5001,
+            data: object5001,
+        }]));
+        let (mut node, mbox) = test_node::<4>(od_table, &[], Callbacks::new());
+        node.heartbeat_period_ms = 1000;
+
+        let sent_states = |node: &mut Node, now_us| {
+            node.process(now_us);
+            core::iter::from_fn(|| mbox.next_transmit_message())
+                .map(|msg| {
+                    assert_eq!(CanId::std(0x701), msg.id());
+                    msg.data()[0]
+                })
+                .collect::<Vec<_>>()
+        };
+        let bootup = NmtState::Bootup as u8;
+        let preop = NmtState::PreOperational as u8;
+
+        // The boot-up message is repeated twice, and the heartbeat is held for 50ms
+        assert_eq!(vec![bootup], sent_states(&mut node, 0));
+        assert_eq!(Vec::<u8>::new(), sent_states(&mut node, 19_999));
+        assert_eq!(vec![bootup], sent_states(&mut node, 20_000));
+        assert_eq!(vec![bootup], sent_states(&mut node, 40_000));
+        assert_eq!(Vec::<u8>::new(), sent_states(&mut node, 49_999));
+        assert_eq!(vec![preop], sent_states(&mut node, 50_000));
+        assert_eq!(Vec::<u8>::new(), sent_states(&mut node, 1_000_000));
+        assert_eq!(vec![preop], sent_states(&mut node, 1_050_000));
+
+        // Changes take effect on the next reset
+        object5001.bootup_retransmit_count.store(0);
+        object5001.first_heartbeat_delay.store(0);
+        mbox.store_message(
+            NmtCommand {
+                cs: NmtCommandSpecifier::ResetComm,
+                node: 0,
+            }
+            .into(),
+        )
+        .unwrap();
+        assert_eq!(Vec::<u8>::new(), sent_states(&mut node, 1_100_000));
+        assert_eq!(vec![bootup, preop], sent_states(&mut node, 1_100_001));
+        assert_eq!(Vec::<u8>::new(), sent_states(&mut node, 1_200_000));
+    }
+
+    #[cfg(feature = "process-timing")]
+    #[test]
+    fn test_process_timing() {
+        use crate::process_timing::ProcessStage;
+        use core::sync::atomic::{AtomicU64, Ordering};
+
+        // A clock which advances 10us on every read
+        static CLOCK: AtomicU64 = AtomicU64::new(0);
+        fn clock() -> u64 {
+            CLOCK.fetch_add(10, Ordering::Relaxed)
+        }
+
+        let (mut node, _) = test_node::<4>(&[], &[], Callbacks::new());
+
+        // Nothing is measured without a clock
+        node.process(0);
+        assert_eq!(0, node.process_timing().stage(ProcessStage::Total).worst_us);
+
+        node.set_timing_clock(Some(clock));
+        node.set_process_budget_us(Some(50));
+        node.process(1000);
+        let timing = *node.process_timing();
+        for stage in [
+            ProcessStage::Sdo,
+            ProcessStage::Nmt,
+            ProcessStage::Lss,
+            ProcessStage::Pdo,
+        ] {
+            assert_eq!(10, timing.stage(stage).last_us);
+        }
+        // Total covers the four stages, plus the reads of the clock between them
+        assert_eq!(90, timing.stage(ProcessStage::Total).worst_us);
+        assert_eq!(1, timing.budget_overruns());
+
+        node.reset_process_timing();
+        assert_eq!(0, node.process_timing().budget_overruns());
+        assert_eq!(0, node.process_timing().stage(ProcessStage::Total).worst_us);
+    }
+
+    #[test]
+    fn test_tx_overflow_callback() {
+        let mut dropped = Vec::new();
+        let mut tx_overflow = |msg: CanMessage| dropped.push(msg);
+        let mut callbacks = Callbacks::new();
+        callbacks.tx_overflow = Some(&mut tx_overflow);
+        let (mut node, mbox) = test_node::<2>(&[], &[], callbacks);
+
+        // Fill the transmit queue so that the boot-up message cannot be queued
+        let filler = CanMessage::new(CanId::std(0x100), &[]);
+        mbox.queue_transmit_message(filler).unwrap();
+        assert_eq!(1, mbox.tx_queue_free());
+        mbox.queue_transmit_message(filler).unwrap();
+        assert_eq!(0, mbox.tx_queue_free());
+        assert_eq!(Err(filler), mbox.queue_transmit_message(filler));
+        assert_eq!(1, mbox.diagnostics().tx_overflows());
+
+        node.process(0);
+        assert_eq!(0, node.tx_queue_free());
+        assert_eq!(2, node.diagnostics().tx_overflows());
+        assert_eq!(InternalError::TxQueueFull, node.diagnostics().last_error());
+        // The node keeps the message to retry it
+        assert_eq!(1, node.pending_tx_count());
+        drop(node);
+
+        assert_eq!(1, dropped.len());
+        assert_eq!(CanId::std(0x701), dropped[0].id());
+    }
+
+    #[test]
+    fn test_deferred_tx_retry() {
+        let (mut node, mbox) = test_node::<2>(&[], &[], Callbacks::new());
+        let filler = CanMessage::new(CanId::std(0x100), &[]);
+        mbox.queue_transmit_message(filler).unwrap();
+        mbox.queue_transmit_message(filler).unwrap();
+        node.process(0);
+        assert_eq!(1, node.pending_tx_count());
+
+        // Taking a message from the queue requests a process call to retry the boot-up message
+        assert!(!mbox.take_process_pending());
+        assert_eq!(Some(filler), mbox.next_transmit_message());
+        assert!(mbox.take_process_pending());
+        node.process(1);
+        assert_eq!(0, node.pending_tx_count());
+        assert_eq!(1, mbox.diagnostics().tx_overflows());
+
+        // The boot-up message is sent after the message ahead of it
+        assert_eq!(Some(filler), mbox.next_transmit_message());
+        let bootup = mbox.next_transmit_message().unwrap();
+        assert_eq!(CanId::std(0x701), bootup.id());
+        assert_eq!(&[NmtState::Bootup as u8], bootup.data());
+        assert_eq!(None, mbox.next_transmit_message());
+    }
+
+    #[test]
+    fn test_deferred_tx_full() {
+        let (mut node, mbox) = test_node::<1>(&[], &[], Callbacks::new());
+        let filler = CanMessage::new(CanId::std(0x100), &[]);
+        mbox.queue_transmit_message(filler).unwrap();
+        // The boot-up message waits for space
+        node.process(0);
+        assert_eq!(1, node.pending_tx_count());
+
+        let lss_resp = |n| CanMessage::new(LSS_RESP_ID, &[n]);
+        for n in 0..DEFERRED_TX_SIZE as u8 - 1 {
+            node.defer_message(lss_resp(n));
+        }
+        assert_eq!(DEFERRED_TX_SIZE, node.pending_tx_count());
+        assert_eq!(0, mbox.diagnostics().tx_dropped());
+
+        // Once full, new messages are dropped and counted
+        node.defer_message(lss_resp(0xFF));
+        assert_eq!(DEFERRED_TX_SIZE, node.pending_tx_count());
+        assert_eq!(1, mbox.diagnostics().tx_dropped());
+
+        // A boot-up message replaces the oldest message which is not a boot-up
+        let bootup = CanMessage::new(CanId::std(0x701), &[NmtState::Bootup as u8]);
+        node.defer_message(bootup);
+        assert_eq!(2, mbox.diagnostics().tx_dropped());
+        assert_eq!(InternalError::TxDropped, mbox.diagnostics().last_error());
+        let pending: Vec<_> = node.deferred_tx.iter().copied().collect();
+        assert_eq!(vec![bootup, lss_resp(1), lss_resp(2), bootup], pending);
+    }
+
+    #[test]
+    fn test_sdo_response_with_full_tx_queue() {
+        let (mut node, mbox) = test_node::<1>(&[], &[], Callbacks::new());
+        // The boot-up message fills the queue
+        node.process(0);
+        assert_eq!(0, node.tx_queue_free());
+
+        // A periodic heartbeat cannot be queued, but SDO responses do not need the queue
+        node.heartbeat_period_ms = 100;
+        let req = SdoRequest::initiate_upload(0x1000, 0);
+        mbox.store_message(CanMessage::new(CanId::std(0x601), &req.to_bytes()))
+            .unwrap();
+        node.process(100_000);
+        assert_eq!(1, node.pending_tx_count());
+
+        let mut received = Vec::new();
+        while let Some(msg) = mbox.next_transmit_message() {
+            received.push(msg.id());
+            node.process(100_001);
+        }
+        assert_eq!(
+            vec![CanId::std(0x581), CanId::std(0x701), CanId::std(0x701)],
+            received
         );
+        assert_eq!(0, node.pending_tx_count());
+    }
 
+    #[test]
+    fn test_sdo_queue_overrun() {
+        let (mut node, mbox) = test_node::<4>(&[], &[], Callbacks::new());
         node.process(0);
-        assert_eq!(NmtState::PreOperational, node.nmt_state());
+
+        // Only four requests can be queued between process calls
+        let req = SdoRequest::initiate_upload(0x1000, 0);
+        for _ in 0..5 {
+            mbox.store_message(CanMessage::new(CanId::std(0x601), &req.to_bytes()))
+                .unwrap();
+        }
+        node.process(0);
+        assert_eq!(1, node.diagnostics().rx_overruns());
+        assert_eq!(InternalError::RxOverrun, node.diagnostics().last_error());
+
+        // Responses which do not fit in the queue are counted as transmit overflows
+        mbox.sdo_comms().reset();
+        for _ in 0..5 {
+            mbox.sdo_comms()
+                .store_response(SdoResponse::download_acknowledge(0x1000, 0));
+        }
+        node.process(0);
+        assert_eq!(1, node.diagnostics().rx_overruns());
+        assert_eq!(1, node.diagnostics().tx_overflows());
+    }
+
+    #[test]
+    fn test_diagnostics_autosave() {
+        let mut saved = Vec::new();
+        let mut store = |snapshot: &DiagnosticsSnapshot| saved.push(*snapshot);
+        let mut callbacks = Callbacks::new();
+        callbacks.store_diagnostics = Some(&mut store);
+        let (mut node, mbox) = test_node::<4>(&[], &[], callbacks);
+
+        // Counters restored from a previous boot
+        mbox.diagnostics().restore(&DiagnosticsSnapshot {
+            rx_messages: 10,
+            operating_time_s: 1000,
+            ..Default::default()
+        });
+        node.set_diagnostics_autosave(Some(DiagnosticsAutosave::new(10, 100)));
+
+        // The first process only establishes the baseline
+        node.process(0);
+        mbox.diagnostics().record_rx();
+        mbox.diagnostics().record_sdo_aborts(1);
+        // Counter changes are coalesced until the interval elapses
+        node.process(5_000_000);
+        node.process(9_999_999);
+        node.process(10_000_000);
+        // Only operating time and message traffic change now, so no checkpoint until the idle
+        // interval
+        mbox.diagnostics().record_rx();
+        node.process(50_000_000);
+        node.process(110_000_000);
+        node.checkpoint_diagnostics();
+
+        assert_eq!(3, saved.len());
+        assert_eq!(11, saved[0].rx_messages);
+        assert_eq!(1, saved[0].sdo_aborts);
+        assert_eq!(1010, saved[0].operating_time_s);
+        assert_eq!(12, saved[1].rx_messages);
+        assert_eq!(1110, saved[1].operating_time_s);
+        assert_eq!(saved[1], saved[2]);
+    }
+
+    #[test]
+    fn test_heartbeat_with_wrapping_clock() {
+        let (mut node, mbox) = test_node::<4>(&[], &[], Callbacks::new());
+        node.heartbeat_period_ms = 100;
+
+        let count_heartbeats = || {
+            core::iter::from_fn(|| mbox.next_transmit_message())
+                .filter(|msg| msg.id() == CanId::std(0x701))
+                .count()
+        };
+
+        // A 32-bit counter, starting shortly before it wraps
+        let counter = core::cell::Cell::new(u32::MAX - 150_000);
+        let mut clock = Counter32(|| counter.get());
+        node.process_clock(&mut clock);
+        // The boot-up message, and the first heartbeat
+        assert_eq!(2, count_heartbeats());
+        let mut heartbeats = 0;
+        for _ in 0..30 {
+            counter.set(counter.get().wrapping_add(10_000));
+            node.process_clock(&mut clock);
+            heartbeats += count_heartbeats();
+        }
+        assert_eq!(3, heartbeats);
+
+        // Time going backwards does not send extra heartbeats, or stop them
+        node.process(0);
+        node.process(50_000);
+        assert_eq!(0, count_heartbeats());
+        node.process(100_000);
+        assert_eq!(1, count_heartbeats());
+    }
+
+    #[test]
+    fn test_tpdos_paused_on_bus_off() {
+        let od_table = Box::leak(Box::new([]));
+        let pdo_nmt_state = Box::leak(Box::new(AtomicCell::new(NmtState::Operational)));
+        let tpdos = Box::leak(Box::new([Pdo::new(od_table, pdo_nmt_state)]));
+
+        let mut recoveries = 0;
+        let mut bus_off_recovered = || recoveries += 1;
+        let mut callbacks = Callbacks::new();
+        callbacks.bus_off_recovered = Some(&mut bus_off_recovered);
+        let (mut node, mbox) = test_node::<4>(od_table, tpdos, callbacks);
+
+        // A TPDO sent on every SYNC
+        tpdos[0].set_valid(true);
+        tpdos[0].set_transmission_type(1);
+
+        let start = NmtCommand {
+            cs: NmtCommandSpecifier::Start,
+            node: 0,
+        };
+        mbox.store_message(start.into()).unwrap();
+        node.process(0);
+        assert_eq!(NmtState::Operational, node.nmt_state());
+        while mbox.next_transmit_message().is_some() {}
+
+        let sync_and_count_tpdos = |node: &mut Node, now_us| {
+            mbox.store_message(SyncObject::new(None).into()).unwrap();
+            node.process(now_us);
+            core::iter::from_fn(|| mbox.next_transmit_message())
+                .filter(|msg| msg.id() == tpdos[0].cob_id())
+                .count()
+        };
+
+        assert_eq!(1, sync_and_count_tpdos(&mut node, 1000));
+
+        // Error passive does not pause TPDOs by default
+        node.report_bus_state(BusState::ErrorPassive);
+        assert_eq!(1, sync_and_count_tpdos(&mut node, 2000));
+
+        node.report_bus_state(BusState::BusOff);
+        assert_eq!(BusState::BusOff, node.bus_state());
+        assert_eq!(0, sync_and_count_tpdos(&mut node, 3000));
+
+        node.report_bus_state(BusState::ErrorPassive);
+        assert_eq!(1, sync_and_count_tpdos(&mut node, 4000));
+
+        node.set_bus_error_policy(BusErrorPolicy {
+            pause_tpdos_when_error_passive: true,
+            ..Default::default()
+        });
+        assert_eq!(0, sync_and_count_tpdos(&mut node, 5000));
+
+        node.report_bus_state(BusState::ErrorActive);
+        assert_eq!(1, sync_and_count_tpdos(&mut node, 6000));
+
+        assert_eq!(1, recoveries);
     }
 }
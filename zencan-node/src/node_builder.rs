@@ -0,0 +1,398 @@
+//! A builder for [`Node`] which checks that the application wiring matches the device config
+//!
+//! Some features of a node are enabled by the device config, but also require the application to
+//! provide something -- e.g. the storage command object (0x1010) is only useful if the application
+//! provides a [`Callbacks::store_objects`] callback. When these disagree, [`Node::new`] accepts the
+//! mismatch silently, and the result is a node which, e.g., reports that it cannot save objects.
+//! [`NodeBuilder::build`] instead checks for these mismatches and returns a [`NodeBuildError`]
+//! describing the problem.
+
+use core::fmt;
+
+use zencan_common::{constants::object_ids, messages::ConnectionSet, NodeId};
+
+use crate::{
+    bus_state::BusErrorPolicy,
+    diagnostics::DiagnosticsAutosave,
+    object_dict::{find_object, ODEntry},
+    pdo::InactiveEventPolicy,
+    BootloaderSection, BootloaderSectionCallbacks, Callbacks, Node, NodeMbox, NodeState,
+    TxPriority,
+};
+
+/// The range of object indices used for bootloader section objects
+const BOOTLOADER_SECTION_INDICES: core::ops::RangeInclusive<u16> = 0x5510..=0x551F;
+
+/// Errors returned by [`NodeBuilder::build`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NodeBuildError {
+    /// The object dictionary contains the storage command object (0x1010), but no
+    /// [`Callbacks::store_objects`] callback was provided
+    StoreObjectsCallbackMissing,
+    /// A [`Callbacks::store_objects`] callback was provided, but the object dictionary has no
+    /// storage command object (0x1010), so the callback can never be called
+    StorageObjectMissing,
+    /// The object dictionary contains bootloader sections which have no registered callbacks
+    BootloaderSectionsUnregistered {
+        /// The number of bootloader section objects in the object dictionary
+        expected: usize,
+        /// The number of sections registered with [`NodeBuilder::bootloader_section`]
+        registered: usize,
+    },
+    /// Diagnostics autosave was enabled, but no [`Callbacks::store_diagnostics`] callback was
+    /// provided
+    StoreDiagnosticsCallbackMissing,
+}
+
+impl fmt::Display for NodeBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StoreObjectsCallbackMissing => write!(
+                f,
+                "object 0x1010 exists, but no store_objects callback was provided"
+            ),
+            Self::StorageObjectMissing => write!(
+                f,
+                "a store_objects callback was provided, but storage is not enabled in the device config"
+            ),
+            Self::BootloaderSectionsUnregistered {
+                expected,
+                registered,
+            } => write!(
+                f,
+                "the device config has {expected} bootloader sections, but {registered} were registered"
+            ),
+            Self::StoreDiagnosticsCallbackMissing => write!(
+                f,
+                "diagnostics autosave is enabled, but no store_diagnostics callback was provided"
+            ),
+        }
+    }
+}
+
+/// Builds a [`Node`], checking that the application wiring is consistent with the generated
+/// object dictionary
+///
+/// ```ignore
+/// let node = NodeBuilder::new(node_id, &zencan::NODE_MBOX, &zencan::NODE_STATE, &zencan::OD_TABLE)
+///     .callbacks(callbacks)
+///     .bootloader_section(&zencan::BOOTLOADER_SECTION0, &FLASH_CALLBACKS)
+///     .build()
+///     .unwrap();
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct NodeBuilder<'a> {
+    node_id: NodeId,
+    mbox: &'static NodeMbox,
+    state: &'static NodeState<'static>,
+    od: &'static [ODEntry<'static>],
+    callbacks: Callbacks<'a>,
+    bootloader_sections: usize,
+    sdo_request_budget: Option<usize>,
+    diagnostics_autosave: Option<DiagnosticsAutosave>,
+    bus_error_policy: BusErrorPolicy,
+    inactive_event_policy: InactiveEventPolicy,
+    sdo_extended_ids: bool,
+    tx_priority: Option<TxPriority>,
+    j1939_coexistence: bool,
+    rebind_pdo_cob_ids: bool,
+    sdo_in_stopped: bool,
+    connection_set: Option<ConnectionSet>,
+}
+
+impl<'a> NodeBuilder<'a> {
+    /// Create a new builder from the statics generated by `zencan-build`
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - Initial node ID assignment
+    /// * `mbox` - The `NODE_MBOX` object created by `zencan-build`
+    /// * `state` - The `NODE_STATE` state object created by `zencan-build`
+    /// * `od` - The `OD_TABLE` object containing the object dictionary created by `zencan-build`
+    pub fn new(
+        node_id: NodeId,
+        mbox: &'static NodeMbox,
+        state: &'static NodeState<'static>,
+        od: &'static [ODEntry<'static>],
+    ) -> Self {
+        Self {
+            node_id,
+            mbox,
+            state,
+            od,
+            callbacks: Callbacks::new(),
+            bootloader_sections: 0,
+            sdo_request_budget: None,
+            diagnostics_autosave: None,
+            bus_error_policy: BusErrorPolicy::new(),
+            inactive_event_policy: InactiveEventPolicy::SendOnStart,
+            sdo_extended_ids: false,
+            tx_priority: None,
+            j1939_coexistence: false,
+            rebind_pdo_cob_ids: true,
+            sdo_in_stopped: false,
+            connection_set: None,
+        }
+    }
+
+    /// Set the application callbacks
+    pub fn callbacks(mut self, callbacks: Callbacks<'a>) -> Self {
+        self.callbacks = callbacks;
+        self
+    }
+
+    /// Register the storage callbacks for a bootloader section
+    ///
+    /// Every bootloader section in the object dictionary must be registered.
+    pub fn bootloader_section(
+        mut self,
+        section: &'static BootloaderSection,
+        callbacks: &'static dyn BootloaderSectionCallbacks,
+    ) -> Self {
+        section.register_callbacks(callbacks);
+        self.bootloader_sections += 1;
+        self
+    }
+
+    /// Set the SDO request budget
+    ///
+    /// See [`Node::set_sdo_request_budget`]
+    pub fn sdo_request_budget(mut self, budget: usize) -> Self {
+        self.sdo_request_budget = Some(budget);
+        self
+    }
+
+    /// Enable diagnostics autosave
+    ///
+    /// See [`Node::set_diagnostics_autosave`]. Requires a [`Callbacks::store_diagnostics`]
+    /// callback.
+    pub fn diagnostics_autosave(mut self, policy: DiagnosticsAutosave) -> Self {
+        self.diagnostics_autosave = Some(policy);
+        self
+    }
+
+    /// Set the policy for reacting to CAN controller error states
+    ///
+    /// See [`Node::set_bus_error_policy`]
+    pub fn bus_error_policy(mut self, policy: BusErrorPolicy) -> Self {
+        self.bus_error_policy = policy;
+        self
+    }
+
+    /// Set the policy for TPDO events raised while the node is not Operational
+    ///
+    /// See [`Node::set_inactive_event_policy`]
+    pub fn inactive_event_policy(mut self, policy: InactiveEventPolicy) -> Self {
+        self.inactive_event_policy = policy;
+        self
+    }
+
+    /// Use extended (29-bit) CAN IDs for the default SDO server
+    ///
+    /// See [`Node::set_sdo_extended_ids`]
+    pub fn sdo_extended_ids(mut self, extended: bool) -> Self {
+        self.sdo_extended_ids = extended;
+        self
+    }
+
+    /// Set the policy for ordering SDO server responses relative to other transmitted messages
+    ///
+    /// See [`NodeMbox::set_tx_priority`]
+    pub fn tx_priority(mut self, priority: TxPriority) -> Self {
+        self.tx_priority = Some(priority);
+        self
+    }
+
+    /// Ignore extended ID messages, leaving them to J1939 devices sharing the bus
+    ///
+    /// See [`NodeMbox::set_j1939_coexistence`]
+    pub fn j1939_coexistence(mut self) -> Self {
+        self.j1939_coexistence = true;
+        self
+    }
+
+    /// Select whether PDO COB IDs follow a node ID change immediately
+    ///
+    /// See [`Node::set_rebind_pdo_cob_ids`]
+    pub fn rebind_pdo_cob_ids(mut self, enabled: bool) -> Self {
+        self.rebind_pdo_cob_ids = enabled;
+        self
+    }
+
+    /// Select whether the SDO server runs in the Stopped state
+    ///
+    /// See [`Node::set_sdo_in_stopped`]
+    pub fn sdo_in_stopped(mut self, allowed: bool) -> Self {
+        self.sdo_in_stopped = allowed;
+        self
+    }
+
+    /// Set the base IDs of the predefined connection set
+    ///
+    /// This overrides the bases set by the device config. The bases are applied before the node is
+    /// created, so that the boot-up message is sent with the new heartbeat base. See
+    /// [`Node::set_connection_set`]
+    pub fn connection_set(mut self, connection_set: ConnectionSet) -> Self {
+        self.connection_set = Some(connection_set);
+        self
+    }
+
+    /// Check the configuration, and create the node
+    pub fn build(self) -> Result<Node<'a>, NodeBuildError> {
+        let has_storage = find_object(self.od, object_ids::SAVE_OBJECTS).is_some();
+        match (has_storage, self.callbacks.store_objects.is_some()) {
+            (true, false) => return Err(NodeBuildError::StoreObjectsCallbackMissing),
+            (false, true) => return Err(NodeBuildError::StorageObjectMissing),
+            _ => (),
+        }
+
+        let expected = self
+            .od
+            .iter()
+            .filter(|entry| BOOTLOADER_SECTION_INDICES.contains(&entry.index))
+            .count();
+        if self.bootloader_sections < expected {
+            return Err(NodeBuildError::BootloaderSectionsUnregistered {
+                expected,
+                registered: self.bootloader_sections,
+            });
+        }
+
+        if self.diagnostics_autosave.is_some() && self.callbacks.store_diagnostics.is_none() {
+            return Err(NodeBuildError::StoreDiagnosticsCallbackMissing);
+        }
+
+        if let Some(connection_set) = self.connection_set {
+            self.mbox.set_connection_set(connection_set);
+        }
+        let mut node = Node::new(self.node_id, self.callbacks, self.mbox, self.state, self.od);
+        if let Some(budget) = self.sdo_request_budget {
+            node.set_sdo_request_budget(budget);
+        }
+        node.set_diagnostics_autosave(self.diagnostics_autosave);
+        node.set_bus_error_policy(self.bus_error_policy);
+        node.set_inactive_event_policy(self.inactive_event_policy);
+        node.set_sdo_extended_ids(self.sdo_extended_ids);
+        node.set_rebind_pdo_cob_ids(self.rebind_pdo_cob_ids);
+        node.set_sdo_in_stopped(self.sdo_in_stopped);
+        if let Some(priority) = self.tx_priority {
+            self.mbox.set_tx_priority(priority);
+        }
+        if self.j1939_coexistence {
+            self.mbox.set_j1939_coexistence(true);
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use zencan_common::CanMessage;
+
+    use super::*;
+    use crate::{
+        diagnostics::DiagnosticsSnapshot,
+        priority_queue::PriorityQueue,
+        storage::{StorageCommandObject, StorageContext},
+    };
+
+    struct NullSection;
+
+    impl BootloaderSectionCallbacks for NullSection {
+        fn erase(&self) -> bool {
+            true
+        }
+
+        fn write(&self, _data: &[u8]) {}
+
+        fn finalize(&self) -> bool {
+            true
+        }
+    }
+
+    fn builder(od: &'static [ODEntry<'static>]) -> NodeBuilder<'static> {
+        let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
+        let mbox = Box::leak(Box::new(NodeMbox::new(&[], &[], tx_queue, sdo_buffer)));
+        let state = Box::leak(Box::new(NodeState::new(&[], &[])));
+        NodeBuilder::new(NodeId::new(1).unwrap(), mbox, state, od)
+    }
+
+    fn store_objects_callbacks() -> Callbacks<'static> {
+        let store = Box::leak(Box::new(
+            |_: &mut dyn embedded_io::Read<Error = Infallible>, _: usize| {},
+        ));
+        let mut callbacks = Callbacks::new();
+        callbacks.store_objects = Some(store);
+        callbacks
+    }
+
+    #[test]
+    fn test_storage_checks() {
+        let context = Box::leak(Box::new(StorageContext::new()));
+        let object = Box::leak(Box::new(StorageCommandObject::new(context)));
+        let od = Box::leak(Box::new([ODEntry {
+            index: 0x1010,
+            data: object,
+        }]));
+
+        assert_eq!(
+            Some(NodeBuildError::StoreObjectsCallbackMissing),
+            builder(od).build().err()
+        );
+        assert!(builder(od)
+            .callbacks(store_objects_callbacks())
+            .build()
+            .is_ok());
+        assert_eq!(
+            Some(NodeBuildError::StorageObjectMissing),
+            builder(&[])
+                .callbacks(store_objects_callbacks())
+                .build()
+                .err()
+        );
+    }
+
+    #[test]
+    fn test_bootloader_checks() {
+        static SECTION: BootloaderSection = BootloaderSection::new("app", 1024);
+        static CALLBACKS: NullSection = NullSection;
+        let od = Box::leak(Box::new([ODEntry {
+            index: 0x5510,
+            data: &SECTION,
+        }]));
+
+        assert_eq!(
+            Some(NodeBuildError::BootloaderSectionsUnregistered {
+                expected: 1,
+                registered: 0
+            }),
+            builder(od).build().err()
+        );
+        assert!(builder(od)
+            .bootloader_section(&SECTION, &CALLBACKS)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_diagnostics_autosave_check() {
+        let policy = DiagnosticsAutosave::new(60, 3600);
+        assert_eq!(
+            Some(NodeBuildError::StoreDiagnosticsCallbackMissing),
+            builder(&[]).diagnostics_autosave(policy).build().err()
+        );
+
+        let store = Box::leak(Box::new(|_: &DiagnosticsSnapshot| {}));
+        let mut callbacks = Callbacks::new();
+        callbacks.store_diagnostics = Some(store);
+        assert!(builder(&[])
+            .callbacks(callbacks)
+            .diagnostics_autosave(policy)
+            .build()
+            .is_ok());
+    }
+}
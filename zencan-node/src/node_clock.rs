@@ -0,0 +1,138 @@
+//! Time sources for [`Node::process`](crate::Node::process)
+//!
+//! The node measures time from the timestamps passed to process. It only uses the difference
+//! between successive timestamps, and keeps its own time from these, so the timestamps do not have
+//! to come from a 64-bit monotonic clock:
+//!
+//! - If the time goes backwards, no time is considered to have elapsed, and the node's time
+//!   continues from the new value on the next call.
+//! - A clock which wraps can be used with [`Node::process_clock`](crate::Node::process_clock), by
+//!   implementing [`NodeClock`] with the width of the counter. [`Counter32`] wraps a free running
+//!   32-bit microsecond counter, e.g. a hardware timer. The time between calls must be less than
+//!   half of the counter range, which is about 35 minutes for a 32-bit counter.
+//!
+//! ```ignore
+//! let mut clock = Counter32(|| TIMER.counter());
+//! loop {
+//!     node.process_clock(&mut clock);
+//!     // ...
+//! }
+//! ```
+
+/// A source of time for the node, in microseconds
+pub trait NodeClock {
+    /// The number of bits in the counter
+    ///
+    /// The value returned by [`now_us`](Self::now_us) wraps to 0 after `2^BITS - 1`.
+    const BITS: u32 = 64;
+
+    /// Read the current time in microseconds
+    fn now_us(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> NodeClock for F {
+    fn now_us(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// A [`NodeClock`] for a 32-bit microsecond counter, which wraps after about 71 minutes
+#[derive(Debug, Clone, Copy)]
+pub struct Counter32<F>(pub F);
+
+impl<F: FnMut() -> u32> NodeClock for Counter32<F> {
+    const BITS: u32 = 32;
+
+    fn now_us(&mut self) -> u64 {
+        (self.0)() as u64
+    }
+}
+
+/// Tracks the node's time from successive clock readings
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct NodeTime {
+    /// The clock reading passed to the last update
+    last_reading: u64,
+    /// The node's time, which never goes backwards
+    now_us: u64,
+}
+
+impl NodeTime {
+    /// Update the time from a clock reading, with a counter width of `bits`, and return the time
+    /// elapsed since the last update
+    pub fn update(&mut self, reading: u64, bits: u32) -> u64 {
+        let mask = if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        };
+        let reading = reading & mask;
+        let delta = reading.wrapping_sub(self.last_reading) & mask;
+        self.last_reading = reading;
+        // A step of more than half the range can't be told apart from the clock going backwards
+        let elapsed = if delta > mask / 2 { 0 } else { delta };
+        self.now_us = self.now_us.saturating_add(elapsed);
+        elapsed
+    }
+
+    /// The node's current time, in microseconds
+    pub fn now_us(&self) -> u64 {
+        self.now_us
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic() {
+        let mut time = NodeTime::default();
+        assert_eq!(1000, time.update(1000, 64));
+        assert_eq!(500, time.update(1500, 64));
+        assert_eq!(0, time.update(1500, 64));
+        assert_eq!(1500, time.now_us());
+    }
+
+    #[test]
+    fn test_backwards() {
+        let mut time = NodeTime::default();
+        time.update(10_000, 64);
+        // Time goes backwards, e.g. because a timer was reset, so nothing has elapsed
+        assert_eq!(0, time.update(2_000, 64));
+        assert_eq!(10_000, time.now_us());
+        // And time continues from the new reading
+        assert_eq!(1_000, time.update(3_000, 64));
+        assert_eq!(11_000, time.now_us());
+
+        assert_eq!(0, time.update(2_000, 32));
+        assert_eq!(11_000, time.now_us());
+    }
+
+    #[test]
+    fn test_wrap() {
+        let mut time = NodeTime::default();
+        // Count up to just before the wrap, in steps of less than half the range
+        time.update(0x6000_0000, 32);
+        time.update(0xc000_0000, 32);
+        time.update(u32::MAX as u64 - 99, 32);
+        assert_eq!(200, time.update(100, 32));
+        assert_eq!(u32::MAX as u64 + 101, time.now_us());
+
+        let mut time = NodeTime::default();
+        time.update(u16::MAX as u64, 16);
+        // Bits above the counter width are ignored
+        assert_eq!(1, time.update(0x1_0000, 16));
+    }
+
+    #[test]
+    fn test_counter32() {
+        let mut count = u32::MAX;
+        let mut clock = Counter32(|| {
+            count = count.wrapping_add(1);
+            count
+        });
+        assert_eq!(0, clock.now_us());
+        assert_eq!(32, <Counter32<fn() -> u32> as NodeClock>::BITS);
+    }
+}
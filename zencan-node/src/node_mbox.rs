@@ -1,29 +1,114 @@
 //! Implements mailbox for receiving CAN messages
-use defmt_or_log::warn;
 use zencan_common::{
-    messages::{CanId, CanMessage, SyncObject},
+    messages::{CanId, CanMessage, ConnectionSet, NmtCommand, SyncObject, TimeObject},
+    nmt::NmtState,
     AtomicCell,
 };
 
 use crate::{
-    lss_slave::LssReceiver, pdo::Pdo, priority_queue::PriorityQueue, sdo_server::SdoComms,
+    diagnostics::{MalformedFrame, NodeDiagnostics},
+    heartbeat_consumer::HeartbeatConsumer,
+    lss_slave::LssReceiver,
+    notify::{HandlerCell, MessageHandler, MessageTap, NotifyCallback, NotifyCell, TapCell},
+    pdo::Pdo,
+    priority_queue::PriorityQueue,
+    sdo_server::SdoComms,
+    verbose_log::verbose_warn,
 };
 
 pub trait CanMessageQueue: Send + Sync {
-    fn push(&self, msg: CanMessage) -> Result<(), CanMessage>;
+    /// Queue a message, to be sent in order of `prio`, lowest first
+    fn push(&self, prio: u32, msg: CanMessage) -> Result<(), CanMessage>;
 
     fn pop(&self) -> Option<CanMessage>;
+
+    /// Get the priority of the message which will be returned by the next call to `pop`
+    fn peek_prio(&self) -> Option<u32>;
+
+    /// The number of messages which can be pushed before the queue is full
+    fn free_slots(&self) -> usize;
 }
 
 impl<const N: usize> CanMessageQueue for PriorityQueue<N, CanMessage> {
-    fn push(&self, msg: CanMessage) -> Result<(), CanMessage> {
-        let prio = msg.id().raw();
+    fn push(&self, prio: u32, msg: CanMessage) -> Result<(), CanMessage> {
         self.push(prio, msg)
     }
 
     fn pop(&self) -> Option<CanMessage> {
         self.pop()
     }
+
+    fn peek_prio(&self) -> Option<u32> {
+        self.peek_prio()
+    }
+
+    fn free_slots(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+
+/// The number of consecutive SDO messages which may be sent while lower priority messages are
+/// waiting
+const MAX_SDO_BURST: u8 = 8;
+
+/// Controls when SDO server responses are transmitted relative to other messages
+///
+/// TPDOs and messages from the general transmit queue (heartbeats, LSS responses, etc) are always
+/// sent in CAN arbitration order among themselves; the policy only decides where SDO responses fit
+/// in. See [`NodeMbox::set_tx_priority`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxPriority {
+    /// Send SDO responses in CAN arbitration order with other messages
+    ///
+    /// To keep a long SDO block upload from starving lower priority messages, after 8
+    /// consecutive SDO messages have been sent while another message was waiting, the waiting
+    /// message is sent next.
+    #[default]
+    Arbitration,
+    /// Send SDO responses only when no other message is waiting
+    ///
+    /// SDO transfers use only the bus time left over by other traffic, so bulk transfers never
+    /// delay control PDOs.
+    SdoLast,
+    /// Send SDO responses before any other message
+    ///
+    /// This makes configuration over SDO as fast as possible, at the cost of delaying TPDOs and
+    /// heartbeats while a transfer is in progress.
+    SdoFirst,
+}
+
+/// The place of an application message in the node's transmit schedule
+///
+/// See [`NodeMbox::send_message`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MessagePriority {
+    /// Send in CAN arbitration order of the message ID, like the node's own messages
+    #[default]
+    Arbitration,
+    /// Send as if the message had the given ID
+    ///
+    /// E.g. a debug message with a high priority ID can be sent after the node's PDOs by giving it
+    /// a lower priority ID here.
+    As(CanId),
+    /// Send before any other waiting TPDO or queued message
+    Highest,
+    /// Send only when no other TPDO or queued message is waiting
+    Lowest,
+}
+
+impl MessagePriority {
+    /// Get the key used to order the message in the transmit queue, lowest first
+    fn key(&self, msg: &CanMessage) -> u32 {
+        match self {
+            MessagePriority::Arbitration => msg.id().arbitration_key(),
+            MessagePriority::As(id) => id.arbitration_key(),
+            MessagePriority::Highest => 0,
+            // Bit 31 is reserved by the priority queue
+            MessagePriority::Lowest => 0x7FFF_FFFF,
+        }
+    }
 }
 
 /// A data structure to be shared between a receiving thread (e.g. a CAN controller IRQ) and the
@@ -38,13 +123,33 @@ pub struct NodeMbox {
     sdo_tx_cob_id: AtomicCell<Option<CanId>>,
     /// ID used for receiving SDO server requests
     sdo_rx_cob_id: AtomicCell<Option<CanId>>,
-    sdo_comms: SdoComms,
+    sdo_comms: SdoComms<'static>,
     nmt_mbox: AtomicCell<Option<CanMessage>>,
     lss_receiver: LssReceiver,
     sync_flag: AtomicCell<Option<SyncObject>>,
-    process_notify_cb: AtomicCell<Option<&'static (dyn Fn() + Sync)>>,
-    transmit_notify_cb: AtomicCell<Option<&'static (dyn Fn() + Sync)>>,
+    sync_timestamp: AtomicCell<Option<u64>>,
+    time_mbox: AtomicCell<Option<TimeObject>>,
+    /// The most recent malformed frame, which has not yet been passed to the application
+    malformed_mbox: AtomicCell<Option<(MalformedFrame, CanMessage)>>,
+    vendor_broadcast_id: AtomicCell<Option<CanId>>,
+    vendor_mbox: AtomicCell<Option<CanMessage>>,
+    heartbeat_consumers: &'static [HeartbeatConsumer],
+    connection_set: AtomicCell<ConnectionSet>,
+    process_notify_cb: NotifyCell,
+    transmit_notify_cb: NotifyCell,
+    message_tap: TapCell,
+    extended_id_handler: HandlerCell,
+    /// Extended ID messages are left to other protocols sharing the bus
+    j1939_coexistence: AtomicCell<bool>,
+    process_pending: AtomicCell<bool>,
+    transmit_pending: AtomicCell<bool>,
     tx_queue: &'static dyn CanMessageQueue,
+    /// The node has messages waiting for space in the transmit queue
+    tx_blocked: AtomicCell<bool>,
+    /// The number of SDO messages sent in a row while other messages were waiting
+    sdo_burst: AtomicCell<u8>,
+    tx_priority: AtomicCell<TxPriority>,
+    diagnostics: NodeDiagnostics,
 }
 
 impl NodeMbox {
@@ -65,8 +170,15 @@ impl NodeMbox {
         let nmt_mbox = AtomicCell::new(None);
         let lss_receiver = LssReceiver::new();
         let sync_flag = AtomicCell::new(None);
-        let process_notify_cb = AtomicCell::new(None);
-        let transmit_notify_cb = AtomicCell::new(None);
+        let sync_timestamp = AtomicCell::new(None);
+        let time_mbox = AtomicCell::new(None);
+        let vendor_broadcast_id = AtomicCell::new(None);
+        let vendor_mbox = AtomicCell::new(None);
+        let process_notify_cb = NotifyCell::new();
+        let transmit_notify_cb = NotifyCell::new();
+        let process_pending = AtomicCell::new(false);
+        let transmit_pending = AtomicCell::new(false);
+        let diagnostics = NodeDiagnostics::new();
         Self {
             rx_pdos,
             tx_pdos,
@@ -76,37 +188,189 @@ impl NodeMbox {
             nmt_mbox,
             lss_receiver,
             sync_flag,
+            sync_timestamp,
+            time_mbox,
+            malformed_mbox: AtomicCell::new(None),
+            vendor_broadcast_id,
+            vendor_mbox,
+            heartbeat_consumers: &[],
+            connection_set: AtomicCell::new(ConnectionSet::DEFAULT),
             process_notify_cb,
             transmit_notify_cb,
+            message_tap: TapCell::new(),
+            extended_id_handler: HandlerCell::new(),
+            j1939_coexistence: AtomicCell::new(false),
+            process_pending,
+            transmit_pending,
             tx_queue,
+            tx_blocked: AtomicCell::new(false),
+            sdo_burst: AtomicCell::new(0),
+            tx_priority: AtomicCell::new(TxPriority::Arbitration),
+            diagnostics,
         }
     }
 
+    /// Provide the consumers used to monitor the heartbeats of other nodes
+    ///
+    /// This is called by the code generated by zencan-build when the device config includes
+    /// heartbeat consumers. See the [`heartbeat_consumer`](crate::heartbeat_consumer) module.
+    pub const fn with_heartbeat_consumers(
+        mut self,
+        consumers: &'static [HeartbeatConsumer],
+    ) -> Self {
+        self.heartbeat_consumers = consumers;
+        self
+    }
+
+    pub(crate) fn heartbeat_consumers(&self) -> &'static [HeartbeatConsumer] {
+        self.heartbeat_consumers
+    }
+
+    /// Set the initial base IDs of the predefined connection set
+    ///
+    /// This is called by the code generated by zencan-build when the device config includes a
+    /// `connection_set` section. It can be changed at runtime with
+    /// [`Node::set_connection_set`](crate::Node::set_connection_set).
+    pub const fn with_connection_set(mut self, connection_set: ConnectionSet) -> Self {
+        self.connection_set = AtomicCell::new(connection_set);
+        self
+    }
+
+    pub(crate) fn connection_set(&self) -> ConnectionSet {
+        self.connection_set.load()
+    }
+
+    pub(crate) fn set_connection_set(&self, connection_set: ConnectionSet) {
+        self.connection_set.store(connection_set);
+    }
+
+    /// Access the communication statistics for the node
+    pub const fn diagnostics(&self) -> &NodeDiagnostics {
+        &self.diagnostics
+    }
+
     /// Set a callback for notification when a message is received and requires processing.
     ///
-    /// It must be static. Usually this will be a static fn, but in some circumstances, it may be
-    /// desirable to use Box::leak to pass a heap allocated closure instead.
-    pub fn set_process_notify_callback(&self, callback: &'static (dyn Fn() + Sync)) {
-        self.process_notify_cb.store(Some(callback));
+    /// Usually this will be a static fn, e.g. `&notify_task`, but see [`NotifyCallback`] for other
+    /// options, including owned closures when the `std` feature is enabled.
+    pub fn set_process_notify_callback(&self, callback: impl Into<NotifyCallback>) {
+        self.process_notify_cb.set(Some(callback.into()));
+    }
+
+    /// Remove the process notify callback
+    pub fn clear_process_notify_callback(&self) {
+        self.process_notify_cb.set(None);
     }
 
     fn process_notify(&self) {
-        if let Some(notify_cb) = self.process_notify_cb.load() {
-            notify_cb();
-        }
+        self.process_pending.store(true);
+        self.process_notify_cb.notify();
     }
 
     /// Set a callback for when new transmit messages are queued
     ///
-    /// This will be called during process anytime new messages are ready to be queued
-    pub fn set_transmit_notify_callback(&self, callback: &'static (dyn Fn() + Sync)) {
-        self.transmit_notify_cb.store(Some(callback));
+    /// This will be called during process anytime new messages are ready to be queued. See
+    /// [`NotifyCallback`] for the types of callback which can be provided.
+    pub fn set_transmit_notify_callback(&self, callback: impl Into<NotifyCallback>) {
+        self.transmit_notify_cb.set(Some(callback.into()));
+    }
+
+    /// Remove the transmit notify callback
+    pub fn clear_transmit_notify_callback(&self) {
+        self.transmit_notify_cb.set(None);
     }
 
     pub(crate) fn transmit_notify(&self) {
-        if let Some(notify_cb) = self.transmit_notify_cb.load() {
-            notify_cb();
-        }
+        self.transmit_pending.store(true);
+        self.transmit_notify_cb.notify();
+    }
+
+    /// Set a tap which receives a copy of each message stored in the mailbox
+    ///
+    /// The tap is called from [`store_message`](Self::store_message) with each message accepted by
+    /// the mailbox, and, if `include_rejected` is true, with each message which is not recognized,
+    /// along with a flag indicating whether it was accepted. This allows an application to
+    /// implement diagnostics, or a custom protocol alongside the CANopen stack, without filtering
+    /// messages before they reach the mailbox. Rejected messages are still returned by
+    /// `store_message`.
+    ///
+    /// The tap is called in the context which stores messages, which is often an interrupt
+    /// handler, so it should return quickly. See [`MessageTap`] for the types of callback which can
+    /// be provided.
+    pub fn set_message_tap(&self, tap: impl Into<MessageTap>, include_rejected: bool) {
+        self.message_tap.set(Some((tap.into(), include_rejected)));
+    }
+
+    /// Remove the message tap
+    pub fn clear_message_tap(&self) {
+        self.message_tap.set(None);
+    }
+
+    /// Set a handler for extended ID messages which are not used by the node
+    ///
+    /// Extended ID messages which are not recognized by the node -- or all extended ID messages,
+    /// when [J1939 coexistence](Self::set_j1939_coexistence) is enabled -- are passed to the
+    /// handler by [`store_message`](Self::store_message). If the handler returns true, the message
+    /// is accepted, and `store_message` returns `Ok(())`; otherwise it is returned as an `Err` as
+    /// usual. This allows e.g. a J1939 stack to receive its messages from the same mailbox.
+    ///
+    /// Like the [message tap](Self::set_message_tap), the handler is called in the context which
+    /// stores messages, so it should return quickly. See [`MessageHandler`] for the types of
+    /// callback which can be provided.
+    pub fn set_extended_id_handler(&self, handler: impl Into<MessageHandler>) {
+        self.extended_id_handler.set(Some(handler.into()));
+    }
+
+    /// Remove the extended ID handler
+    pub fn clear_extended_id_handler(&self) {
+        self.extended_id_handler.set(None);
+    }
+
+    /// Enable or disable J1939 coexistence
+    ///
+    /// On a bus shared with J1939 devices, the 29-bit ID space belongs to J1939, and an RPDO or
+    /// SDO configured with an extended COB ID could be triggered by J1939 traffic. When coexistence
+    /// is enabled, the node ignores all extended ID messages, and passes them only to the
+    /// [extended ID handler](Self::set_extended_id_handler), if one is set. Ignored messages are not
+    /// counted as received in the [diagnostics](Self::diagnostics).
+    ///
+    /// Disabled by default.
+    pub fn set_j1939_coexistence(&self, enabled: bool) {
+        self.j1939_coexistence.store(enabled);
+    }
+
+    /// Returns true if J1939 coexistence is enabled
+    pub fn j1939_coexistence(&self) -> bool {
+        self.j1939_coexistence.load()
+    }
+
+    /// Set the policy for ordering SDO server responses relative to other transmitted messages
+    ///
+    /// The default is [`TxPriority::Arbitration`].
+    pub fn set_tx_priority(&self, priority: TxPriority) {
+        self.tx_priority.store(priority);
+    }
+
+    /// Get the policy for ordering SDO server responses relative to other transmitted messages
+    pub fn tx_priority(&self) -> TxPriority {
+        self.tx_priority.load()
+    }
+
+    /// Check if a message requiring processing has been received, and clear the flag
+    ///
+    /// This is an alternative to [`set_process_notify_callback`](Self::set_process_notify_callback)
+    /// for applications which cannot easily provide a static callback, and instead poll the
+    /// mailbox, e.g. after forwarding messages from an [`RxQueue`](crate::rtic::RxQueue).
+    pub fn take_process_pending(&self) -> bool {
+        self.process_pending.replace(false)
+    }
+
+    /// Check if new messages have been queued for transmit, and clear the flag
+    ///
+    /// This is the polling alternative to
+    /// [`set_transmit_notify_callback`](Self::set_transmit_notify_callback).
+    pub fn take_transmit_pending(&self) -> bool {
+        self.transmit_pending.replace(false)
     }
 
     pub(crate) fn set_sdo_rx_cob_id(&self, cob_id: Option<CanId>) {
@@ -117,7 +381,21 @@ impl NodeMbox {
         self.sdo_tx_cob_id.store(cob_id);
     }
 
-    pub(crate) fn sdo_comms(&self) -> &SdoComms {
+    /// Get the COB ID on which the SDO server receives requests
+    ///
+    /// Returns None until the node has booted with a configured node ID
+    pub fn sdo_rx_cob_id(&self) -> Option<CanId> {
+        self.sdo_rx_cob_id.load()
+    }
+
+    /// Get the COB ID on which the SDO server sends responses
+    ///
+    /// Returns None until the node has booted with a configured node ID
+    pub fn sdo_tx_cob_id(&self) -> Option<CanId> {
+        self.sdo_tx_cob_id.load()
+    }
+
+    pub(crate) fn sdo_comms(&self) -> &SdoComms<'static> {
         &self.sdo_comms
     }
 
@@ -133,21 +411,119 @@ impl NodeMbox {
         self.sync_flag.take()
     }
 
+    pub(crate) fn read_time_mbox(&self) -> Option<TimeObject> {
+        self.time_mbox.take()
+    }
+
+    pub(crate) fn read_malformed_mbox(&self) -> Option<(MalformedFrame, CanMessage)> {
+        self.malformed_mbox.take()
+    }
+
+    /// Set the extended CAN ID used as the vendor broadcast channel
+    ///
+    /// Messages received with this 29-bit ID are decoded as
+    /// [`VendorBroadcast`](zencan_common::messages::VendorBroadcast) commands, and passed to the
+    /// [`vendor_broadcast`](crate::Callbacks::vendor_broadcast) callback. Setting `None` disables
+    /// the channel, which is the default.
+    pub fn set_vendor_broadcast_id(&self, id: Option<u32>) {
+        self.vendor_broadcast_id.store(id.map(CanId::extended));
+    }
+
+    /// Get the ID of the vendor broadcast channel, if enabled
+    pub fn vendor_broadcast_id(&self) -> Option<CanId> {
+        self.vendor_broadcast_id.load()
+    }
+
+    pub(crate) fn read_vendor_mbox(&self) -> Option<CanMessage> {
+        self.vendor_mbox.take()
+    }
+
+    /// Get the receive timestamp of the most recent SYNC message
+    ///
+    /// This is None if no SYNC has been received, or if the receiver did not provide a timestamp
+    /// (see [`CanMessage::timestamp`]). Together with [`Pdo::rx_timestamp`], it can be used to
+    /// check whether RPDOs arrived within the synchronous window following a SYNC.
+    pub fn last_sync_timestamp(&self) -> Option<u64> {
+        self.sync_timestamp.load()
+    }
+
     /// Store a received CAN message
     ///
     /// If the message is recognized and handled, `Ok(())` is returned. Otherwise, the message is
     /// returned inside an Err.
+    ///
+    /// If the receiver is able to capture receive times, it should set the message timestamp (see
+    /// [`CanMessage::with_timestamp`]) before storing it.
+    ///
+    /// Extended ID messages which the node does not use are passed to the [extended ID
+    /// handler](Self::set_extended_id_handler), if one is set. If a [message
+    /// tap](Self::set_message_tap) is set, it receives a copy of the message.
     pub fn store_message(&self, msg: CanMessage) -> Result<(), CanMessage> {
+        let extended = msg.id().is_extended();
+        let result = if extended && self.j1939_coexistence.load() {
+            Err(msg)
+        } else {
+            self.handle_message(msg)
+        };
+        if result.is_ok() {
+            self.diagnostics.record_rx();
+        }
+        let result = match result {
+            Err(msg) if extended && self.extended_id_handler.handle(&msg) => Ok(()),
+            result => result,
+        };
+        self.message_tap.tap(&msg, result.is_ok());
+        result
+    }
+
+    /// Count a received message which replaced an unprocessed message in a mailbox
+    fn record_overrun(&self, mbox: &str) {
+        self.diagnostics.record_rx_overrun();
+        verbose_warn!("{} mailbox overrun", mbox);
+    }
+
+    /// Count a received message which has a recognized COB ID, but could not be parsed
+    ///
+    /// Only the most recent malformed frame is kept for the application, as the count is what
+    /// matters when diagnosing a misbehaving device. The message is returned as rejected.
+    fn reject_malformed(&self, kind: MalformedFrame, msg: CanMessage) -> Result<(), CanMessage> {
+        self.diagnostics.record_rx_malformed();
+        verbose_warn!("Malformed {:?} frame on {:?}", kind, msg.id());
+        self.malformed_mbox.store(Some((kind, msg)));
+        self.process_notify();
+        Err(msg)
+    }
+
+    fn handle_message(&self, msg: CanMessage) -> Result<(), CanMessage> {
         let id = msg.id();
         if id == zencan_common::messages::NMT_CMD_ID {
-            self.nmt_mbox.store(Some(msg));
+            if NmtCommand::try_from(msg).is_err() {
+                return self.reject_malformed(MalformedFrame::Nmt, msg);
+            }
+            if self.nmt_mbox.replace(Some(msg)).is_some() {
+                self.record_overrun("NMT");
+            }
             self.process_notify();
             return Ok(());
         }
 
         if id == zencan_common::messages::SYNC_ID {
             let sync_object = SyncObject::from(msg);
-            self.sync_flag.store(Some(sync_object));
+            self.sync_timestamp.store(msg.timestamp());
+            if self.sync_flag.replace(Some(sync_object)).is_some() {
+                self.record_overrun("SYNC");
+            }
+            self.process_notify();
+            return Ok(());
+        }
+
+        if id == zencan_common::messages::TIME_ID {
+            let Ok(time) = TimeObject::try_from(msg) else {
+                return self.reject_malformed(MalformedFrame::Time, msg);
+            };
+            if self.time_mbox.replace(Some(time)).is_some() {
+                self.record_overrun("TIME");
+            }
             self.process_notify();
             return Ok(());
         }
@@ -158,27 +534,67 @@ impl NodeMbox {
                     self.process_notify();
                 }
             } else {
-                warn!("Invalid LSS request");
-                return Err(msg);
+                return self.reject_malformed(MalformedFrame::Lss, msg);
+            }
+            return Ok(());
+        }
+
+        if Some(id) == self.vendor_broadcast_id.load() {
+            if self.vendor_mbox.replace(Some(msg)).is_some() {
+                self.record_overrun("vendor broadcast");
             }
+            self.process_notify();
             return Ok(());
         }
 
+        if let CanId::Std(raw_id) = id {
+            let heartbeat_node = self.connection_set.load().heartbeat_node(raw_id);
+            if let Some(node) = heartbeat_node.filter(|&node| {
+                self.heartbeat_consumers
+                    .iter()
+                    .any(|consumer| consumer.monitors(node))
+            }) {
+                let state = msg
+                    .data()
+                    .first()
+                    .and_then(|data| NmtState::try_from(data & 0x7F).ok());
+                let Some(state) = state else {
+                    return self.reject_malformed(MalformedFrame::Heartbeat, msg);
+                };
+                self.heartbeat_consumers
+                    .iter()
+                    .any(|consumer| consumer.store_heartbeat(node, state));
+                return Ok(());
+            }
+        }
+
         for rpdo in self.rx_pdos {
             if !rpdo.valid() {
                 continue;
             }
             if id == rpdo.cob_id() {
+                // PDOs are only exchanged in the Operational state. The frame is kept while an NMT
+                // command is pending, because the command may start the node before the frame is
+                // processed.
+                if rpdo.nmt_state() != NmtState::Operational && self.nmt_mbox.load().is_none() {
+                    return Ok(());
+                }
                 // Unwrap safety: msg data cannot be longer than 8 byte size of the Vec
                 let data = heapless::Vec::from_slice(msg.data()).unwrap();
-                rpdo.buffered_value.store(Some(data));
+                rpdo.set_rx_timestamp(msg.timestamp());
+                if rpdo.buffered_value.replace(Some(data)).is_some() {
+                    self.record_overrun("RPDO");
+                }
                 return Ok(());
             }
         }
 
         if let Some(cob_id) = self.sdo_rx_cob_id.load() {
             if id == cob_id {
-                if self.sdo_comms.handle_req(msg.data()) {
+                if msg.data().len() != 8 {
+                    return self.reject_malformed(MalformedFrame::Sdo, msg);
+                }
+                if self.sdo_comms.handle_req(id, msg.data()) {
                     self.process_notify();
                 }
                 return Ok(());
@@ -190,34 +606,152 @@ impl NodeMbox {
 
     /// Get the next message ready for transmit
     ///
-    /// Messages are prioritized as follows:
+    /// Messages are dispensed in CAN arbitration order, i.e. the waiting message with the highest
+    /// priority COB ID is returned first, whether it is a TPDO, a message from the general transmit
+    /// queue (NMT, heartbeat, LSS, etc), or an SDO server response.
+    ///
+    /// An SDO block upload can produce a long stream of segments, which would otherwise prevent
+    /// any lower priority message, such as a heartbeat, from being sent until the stream ends. To
+    /// keep the stream from starving other traffic, after 8 consecutive SDO messages have been sent
+    /// while another message was waiting, the waiting message is sent next.
     ///
-    /// - TPDOs first, if available, starting with TPDO0
-    /// - Other non-SDO messages (SYNC, LSS, NMT)
-    /// - SDO server responses    
+    /// The placement of SDO responses can be changed with
+    /// [`set_tx_priority`](Self::set_tx_priority).
+    ///
+    /// If the node is waiting to queue messages of its own, taking a message from the transmit
+    /// queue triggers the process notify callback, so that the node can retry them.
     pub fn next_transmit_message(&self) -> Option<CanMessage> {
-        for pdo in self.tx_pdos.iter() {
-            if let Some(buf) = pdo.buffered_value.take() {
-                return Some(CanMessage::new(pdo.cob_id(), &buf));
+        let msg = self.next_queued_message();
+        if msg.is_some() {
+            self.diagnostics.record_tx();
+            if self.tx_blocked.load() && self.tx_queue.free_slots() > 0 {
+                self.tx_blocked.store(false);
+                self.process_notify();
             }
         }
+        msg
+    }
 
-        if let Some(msg) = self.tx_queue.pop() {
-            return Some(msg);
-        }
+    fn next_queued_message(&self) -> Option<CanMessage> {
+        critical_section::with(|_| {
+            let tpdo = self
+                .tx_pdos
+                .iter()
+                .filter(|pdo| pdo.has_buffered_value())
+                .min_by_key(|pdo| pdo.cob_id().arbitration_key());
+            let tpdo_key = tpdo.map(|pdo| pdo.cob_id().arbitration_key());
+            let queue_key = self.tx_queue.peek_prio();
+            let sdo_key = self
+                .sdo_tx_cob_id
+                .load()
+                .filter(|_| self.sdo_comms.transmit_pending())
+                .map(|id| id.arbitration_key());
+
+            let other_key = match (tpdo_key, queue_key) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
 
-        if let Some(msg) = self.sdo_comms.next_transmit_message() {
-            if let Some(id) = self.sdo_tx_cob_id.load() {
+            let send_sdo = match (sdo_key, other_key) {
+                (None, _) => false,
+                (Some(_), None) => {
+                    self.sdo_burst.store(0);
+                    true
+                }
+                (Some(_), Some(_)) if self.tx_priority.load() == TxPriority::SdoFirst => true,
+                (Some(_), Some(_)) if self.tx_priority.load() == TxPriority::SdoLast => false,
+                (Some(sdo), Some(other)) => {
+                    if sdo < other && self.sdo_burst.load() < MAX_SDO_BURST {
+                        self.sdo_burst.fetch_add(1);
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+
+            if send_sdo {
+                let id = self.sdo_tx_cob_id.load()?;
+                let msg = self.sdo_comms.next_transmit_message()?;
                 return Some(CanMessage::new(id, &msg));
             }
-        }
+            self.sdo_burst.store(0);
 
-        None
+            match (tpdo, queue_key) {
+                (Some(pdo), Some(queue_key)) if queue_key < pdo.cob_id().arbitration_key() => {
+                    self.tx_queue.pop()
+                }
+                (Some(pdo), _) => {
+                    let buf = pdo.buffered_value.take()?;
+                    Some(CanMessage::new(pdo.cob_id(), &buf))
+                }
+                (None, _) => self.tx_queue.pop(),
+            }
+        })
     }
 
     /// Store a message for transmission in the general transmit queue
+    ///
+    /// If the queue is full, the message is returned in an Err, and counted in
+    /// [`NodeDiagnostics::tx_overflows`].
     pub fn queue_transmit_message(&self, msg: CanMessage) -> Result<(), CanMessage> {
-        self.tx_queue.push(msg)
+        self.push_transmit_message(msg.id().arbitration_key(), msg)
+    }
+
+    fn push_transmit_message(&self, prio: u32, msg: CanMessage) -> Result<(), CanMessage> {
+        self.tx_queue.push(prio, msg).inspect_err(|_| {
+            self.diagnostics.record_tx_overflow();
+            verbose_warn!("Transmit queue overflow");
+        })
+    }
+
+    /// Queue an application message for transmission
+    ///
+    /// Applications which share the CAN controller with the node, e.g. to run a custom protocol
+    /// or send debug data, can send their messages through the node's transmit queue, so that
+    /// [`next_transmit_message`](Self::next_transmit_message) remains the only transmit path. The
+    /// message is scheduled among the node's own messages according to `priority`; SDO responses
+    /// are placed relative to it as set by [`set_tx_priority`](Self::set_tx_priority).
+    ///
+    /// The transmit notify callback is triggered, so the message is sent without waiting for the
+    /// next call to [`Node::process`](crate::Node::process). The queue is shared with the node's
+    /// messages, such as heartbeats, so an application sending many messages should check
+    /// [`tx_queue_free`](Self::tx_queue_free) to leave room for them.
+    ///
+    /// If the queue is full, the message is returned in an Err, and counted in
+    /// [`NodeDiagnostics::tx_overflows`].
+    pub fn send_message(
+        &self,
+        msg: CanMessage,
+        priority: MessagePriority,
+    ) -> Result<(), CanMessage> {
+        self.push_transmit_message(priority.key(&msg), msg)?;
+        self.transmit_notify();
+        Ok(())
+    }
+
+    /// Queue a message which the node previously failed to queue
+    ///
+    /// Unlike [`queue_transmit_message`](Self::queue_transmit_message), a full queue is not counted
+    /// as another overflow. On failure, the process notify callback is triggered once space becomes
+    /// available.
+    pub(crate) fn requeue_transmit_message(&self, msg: CanMessage) -> Result<(), CanMessage> {
+        self.tx_queue
+            .push(msg.id().arbitration_key(), msg)
+            .inspect_err(|_| self.tx_blocked.store(true))
+    }
+
+    /// Request a process notification once there is space in the transmit queue
+    pub(crate) fn notify_on_tx_space(&self) {
+        self.tx_blocked.store(true);
+    }
+
+    /// Get the number of messages which can currently be added to the general transmit queue
+    ///
+    /// Applications which queue their own messages can use this to pace their traffic, rather than
+    /// waiting for [`send_message`](Self::send_message) to fail.
+    pub fn tx_queue_free(&self) -> usize {
+        self.tx_queue.free_slots()
     }
 }
 
@@ -227,11 +761,13 @@ mod tests {
     use std::sync::Arc;
 
     use zencan_common::{
+        j1939::J1939Id,
         messages::SDO_REQ_BASE,
-        sdo::{BlockSegment, SdoRequest},
+        sdo::{BlockSegment, SdoRequest, SdoResponse},
+        NodeId,
     };
 
-    use crate::object_dict::ODEntry;
+    use crate::{object_dict::ODEntry, pdo::PdoDefaults};
 
     use super::*;
 
@@ -276,6 +812,376 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_message_tap() {
+        static TAPPED: std::sync::Mutex<Vec<(CanId, bool)>> = std::sync::Mutex::new(Vec::new());
+        fn tap(msg: &CanMessage, accepted: bool) {
+            TAPPED.lock().unwrap().push((msg.id(), accepted));
+        }
+
+        let obj = create_test_objects();
+        let nmt = CanMessage::new(zencan_common::messages::NMT_CMD_ID, &[1, 0]);
+        let unknown = CanMessage::new(CanId::std(0x123), &[]);
+
+        obj.mbox.set_message_tap(&tap, false);
+        obj.mbox.store_message(nmt).unwrap();
+        assert!(obj.mbox.store_message(unknown).is_err());
+        assert_eq!(
+            vec![(zencan_common::messages::NMT_CMD_ID, true)],
+            *TAPPED.lock().unwrap()
+        );
+
+        obj.mbox.set_message_tap(&tap, true);
+        // Rejected messages are still returned to the caller
+        assert_eq!(Err(unknown), obj.mbox.store_message(unknown));
+        assert_eq!((CanId::std(0x123), false), TAPPED.lock().unwrap()[1]);
+
+        obj.mbox.clear_message_tap();
+        obj.mbox.store_message(nmt).unwrap();
+        assert_eq!(2, TAPPED.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        use zencan_common::messages::{LSS_REQ_ID, NMT_CMD_ID, TIME_ID};
+
+        let consumers = Box::leak(Box::new([HeartbeatConsumer::new(5, 100)]));
+        consumers[0].reset();
+        let obj = create_test_objects();
+        let mbox = obj.mbox.with_heartbeat_consumers(consumers);
+
+        let frames = [
+            (MalformedFrame::Nmt, CanMessage::new(NMT_CMD_ID, &[1])),
+            (MalformedFrame::Nmt, CanMessage::new(NMT_CMD_ID, &[0x55, 0])),
+            (MalformedFrame::Lss, CanMessage::new(LSS_REQ_ID, &[])),
+            (MalformedFrame::Time, CanMessage::new(TIME_ID, &[0; 4])),
+            (MalformedFrame::Sdo, CanMessage::new(SDO_RX_COB_ID, &[0x40])),
+            (
+                MalformedFrame::Heartbeat,
+                CanMessage::new(CanId::std(0x705), &[]),
+            ),
+            (
+                MalformedFrame::Heartbeat,
+                CanMessage::new(CanId::std(0x705), &[0x42]),
+            ),
+        ];
+        for (i, (kind, msg)) in frames.into_iter().enumerate() {
+            assert_eq!(Err(msg), mbox.store_message(msg));
+            assert_eq!(i as u32 + 1, mbox.diagnostics.rx_malformed());
+            assert_eq!(Some((kind, msg)), mbox.read_malformed_mbox());
+        }
+        assert_eq!(0, mbox.diagnostics.rx_messages());
+        assert_eq!(
+            crate::diagnostics::InternalError::RxMalformed,
+            mbox.diagnostics.last_error()
+        );
+
+        // Only the most recent malformed frame is kept
+        let short_nmt = CanMessage::new(NMT_CMD_ID, &[]);
+        let short_time = CanMessage::new(TIME_ID, &[]);
+        mbox.store_message(short_nmt).unwrap_err();
+        mbox.store_message(short_time).unwrap_err();
+        assert_eq!(
+            Some((MalformedFrame::Time, short_time)),
+            mbox.read_malformed_mbox()
+        );
+        assert_eq!(None, mbox.read_malformed_mbox());
+
+        // A heartbeat from a node which is not monitored is not recognized, so is not malformed
+        let count = mbox.diagnostics.rx_malformed();
+        assert!(mbox
+            .store_message(CanMessage::new(CanId::std(0x706), &[]))
+            .is_err());
+        assert_eq!(count, mbox.diagnostics.rx_malformed());
+    }
+
+    /// Heartbeat consumers listen on the heartbeat base of the connection set
+    #[test]
+    fn test_heartbeat_connection_set() {
+        let consumers = Box::leak(Box::new([HeartbeatConsumer::new(5, 100)]));
+        consumers[0].reset();
+        let connection_set = ConnectionSet {
+            heartbeat_base: 0x500,
+            ..ConnectionSet::DEFAULT
+        };
+        let obj = create_test_objects();
+        let mbox = obj
+            .mbox
+            .with_heartbeat_consumers(consumers)
+            .with_connection_set(connection_set);
+        let heartbeat = |id: u16| CanMessage::new(CanId::std(id), &[NmtState::Operational as u8]);
+
+        assert!(mbox.store_message(heartbeat(0x705)).is_err());
+        assert!(mbox.store_message(heartbeat(0x505)).is_ok());
+        assert!(mbox.store_message(heartbeat(0x506)).is_err());
+
+        mbox.set_connection_set(ConnectionSet::DEFAULT);
+        assert!(mbox.store_message(heartbeat(0x705)).is_ok());
+        assert!(mbox.store_message(heartbeat(0x505)).is_err());
+    }
+
+    /// Extended IDs are distinct from standard IDs with the same raw value
+    #[test]
+    fn test_extended_ids() {
+        let obj = create_test_objects();
+        let data = [0; 8];
+        for raw in [0x000, 0x080, 0x7E5, SDO_RX_COB_ID.raw()] {
+            assert!(obj
+                .mbox
+                .store_message(CanMessage::new(CanId::extended(raw), &data))
+                .is_err());
+        }
+
+        let extended_sdo_cob_id = CanId::extended(SDO_RX_COB_ID.raw());
+        obj.mbox.set_sdo_rx_cob_id(Some(extended_sdo_cob_id));
+        let req = SdoRequest::initiate_upload(0x1000, 0);
+        assert!(obj
+            .mbox
+            .store_message(req.to_can_message(SDO_RX_COB_ID))
+            .is_err());
+        assert!(obj
+            .mbox
+            .store_message(req.to_can_message(extended_sdo_cob_id))
+            .is_ok());
+        assert_eq!(Some(req), obj.mbox.sdo_comms().take_request());
+    }
+
+    #[test]
+    fn test_j1939_coexistence() {
+        static HANDLED: std::sync::Mutex<Vec<CanId>> = std::sync::Mutex::new(Vec::new());
+        fn handle(msg: &CanMessage) -> bool {
+            HANDLED.lock().unwrap().push(msg.id());
+            J1939Id::try_from(msg.id()).is_ok_and(|id| id.pgn() == 0xFEF1)
+        }
+
+        let obj = create_test_objects();
+        let extended_sdo_cob_id = CanId::extended(0x18DA_0117);
+        obj.mbox.set_sdo_rx_cob_id(Some(extended_sdo_cob_id));
+        let req = SdoRequest::initiate_upload(0x1000, 0).to_can_message(extended_sdo_cob_id);
+        let ccvs = CanMessage::new(J1939Id::new(6, 0xFEF1, 0xFF, 0x80).into(), &[0; 8]);
+        let unknown = CanMessage::new(J1939Id::new(6, 0xFEF2, 0xFF, 0x80).into(), &[0; 8]);
+
+        // Without a handler, unused extended ID messages are rejected
+        assert_eq!(Err(ccvs), obj.mbox.store_message(ccvs));
+
+        // Messages used by the node are not passed to the handler
+        obj.mbox.set_extended_id_handler(&handle);
+        obj.mbox.store_message(req).unwrap();
+        assert!(obj.mbox.sdo_comms().take_request().is_some());
+        obj.mbox.store_message(ccvs).unwrap();
+        assert_eq!(Err(unknown), obj.mbox.store_message(unknown));
+        assert_eq!(vec![ccvs.id(), unknown.id()], *HANDLED.lock().unwrap());
+        // Standard ID messages are never passed to the handler
+        assert!(obj
+            .mbox
+            .store_message(CanMessage::new(CanId::std(0x123), &[]))
+            .is_err());
+        assert_eq!(2, HANDLED.lock().unwrap().len());
+
+        // With coexistence enabled, all extended ID messages go to the handler, even those
+        // matching a COB ID used by the node
+        let rx_count = obj.mbox.diagnostics().snapshot().rx_messages;
+        obj.mbox.set_j1939_coexistence(true);
+        assert_eq!(Err(req), obj.mbox.store_message(req));
+        assert!(obj.mbox.sdo_comms().take_request().is_none());
+        obj.mbox.store_message(ccvs).unwrap();
+        assert_eq!(vec![req.id(), ccvs.id()], HANDLED.lock().unwrap()[2..]);
+        assert_eq!(rx_count, obj.mbox.diagnostics().snapshot().rx_messages);
+
+        obj.mbox.clear_extended_id_handler();
+        assert_eq!(Err(ccvs), obj.mbox.store_message(ccvs));
+    }
+
+    #[test]
+    fn test_transmit_priority() {
+        let od = Box::leak(Box::new([]));
+        let nmt_state = Box::leak(Box::new(AtomicCell::new(
+            zencan_common::nmt::NmtState::Operational,
+        )));
+        let defaults = Box::leak(Box::new(PdoDefaults::new(
+            0x180,
+            false,
+            true,
+            true,
+            false,
+            254,
+            &[],
+        )));
+        let tpdos = Box::leak(Box::new([Pdo::new_with_defaults(od, nmt_state, defaults)]));
+        tpdos[0].init_defaults(NodeId::new(1).unwrap());
+        let txq = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0; 128]));
+        let mbox = NodeMbox::new(&[], tpdos, txq, sdo_buffer);
+        mbox.set_sdo_tx_cob_id(Some(CanId::std(0x581)));
+
+        let data = heapless::Vec::from_slice(&[1]).unwrap();
+        let heartbeat = CanMessage::new(CanId::std(0x701), &[5]);
+        let emcy = CanMessage::new(CanId::std(0x081), &[]);
+        let extended = CanMessage::new(CanId::extended(0x701 << 18), &[]);
+
+        // The TPDO goes before the heartbeat, but after the higher priority EMCY
+        mbox.queue_transmit_message(heartbeat).unwrap();
+        mbox.queue_transmit_message(emcy).unwrap();
+        // An extended ID loses to a standard ID with the same base ID
+        mbox.queue_transmit_message(extended).unwrap();
+        tpdos[0].buffered_value.store(Some(data.clone()));
+        mbox.sdo_comms()
+            .store_response(SdoResponse::download_acknowledge(0x1000, 0));
+        assert_eq!(
+            CanId::std(0x081),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert_eq!(
+            CanId::std(0x181),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert_eq!(
+            CanId::std(0x581),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert_eq!(
+            CanId::std(0x701),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert_eq!(
+            CanId::extended(0x701 << 18),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert!(mbox.next_transmit_message().is_none());
+
+        // A block upload stream yields to a waiting heartbeat after a burst of segments
+        mbox.sdo_comms().begin_block_upload(7 * 18, true);
+        mbox.queue_transmit_message(heartbeat).unwrap();
+        for _ in 0..MAX_SDO_BURST {
+            assert_eq!(
+                CanId::std(0x581),
+                mbox.next_transmit_message().unwrap().id()
+            );
+        }
+        assert_eq!(
+            CanId::std(0x701),
+            mbox.next_transmit_message().unwrap().id()
+        );
+        assert_eq!(
+            CanId::std(0x581),
+            mbox.next_transmit_message().unwrap().id()
+        );
+    }
+
+    #[test]
+    fn test_send_message() {
+        let od = Box::leak(Box::new([]));
+        let nmt_state = Box::leak(Box::new(AtomicCell::new(
+            zencan_common::nmt::NmtState::Operational,
+        )));
+        let defaults = Box::leak(Box::new(PdoDefaults::new(
+            0x180,
+            false,
+            true,
+            true,
+            false,
+            254,
+            &[],
+        )));
+        let tpdos = Box::leak(Box::new([Pdo::new_with_defaults(od, nmt_state, defaults)]));
+        tpdos[0].init_defaults(NodeId::new(1).unwrap());
+        let txq = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0; 128]));
+        let mbox = NodeMbox::new(&[], tpdos, txq, sdo_buffer);
+
+        let data: heapless::Vec<u8, 8> = heapless::Vec::from_slice(&[1]).unwrap();
+        let msg = |id| CanMessage::new(CanId::std(id), &[]);
+        let next_ids = || {
+            core::iter::from_fn(|| mbox.next_transmit_message().map(|msg| msg.id()))
+                .collect::<Vec<_>>()
+        };
+
+        tpdos[0].buffered_value.store(Some(data));
+        mbox.send_message(msg(0x001), MessagePriority::Lowest)
+            .unwrap();
+        assert!(mbox.take_transmit_pending());
+        mbox.send_message(msg(0x7F0), MessagePriority::Highest)
+            .unwrap();
+        mbox.send_message(msg(0x100), MessagePriority::Arbitration)
+            .unwrap();
+        // Sent as if it had a lower priority than the TPDO
+        mbox.send_message(msg(0x080), MessagePriority::As(CanId::std(0x200)))
+            .unwrap();
+        assert_eq!(
+            vec![
+                CanId::std(0x7F0),
+                CanId::std(0x100),
+                CanId::std(0x181),
+                CanId::std(0x080),
+                CanId::std(0x001),
+            ],
+            next_ids()
+        );
+
+        for _ in 0..4 {
+            mbox.send_message(msg(0x100), MessagePriority::Arbitration)
+                .unwrap();
+        }
+        assert_eq!(
+            Err(msg(0x101)),
+            mbox.send_message(msg(0x101), MessagePriority::Highest)
+        );
+        assert_eq!(1, mbox.diagnostics.tx_overflows());
+    }
+
+    #[test]
+    fn test_tx_priority_policy() {
+        let od = Box::leak(Box::new([]));
+        let nmt_state = Box::leak(Box::new(AtomicCell::new(
+            zencan_common::nmt::NmtState::Operational,
+        )));
+        // A TPDO with a lower priority COB ID than the SDO server
+        let defaults = Box::leak(Box::new(PdoDefaults::new(
+            0x600,
+            false,
+            false,
+            true,
+            false,
+            254,
+            &[],
+        )));
+        let tpdos = Box::leak(Box::new([Pdo::new_with_defaults(od, nmt_state, defaults)]));
+        tpdos[0].init_defaults(NodeId::new(1).unwrap());
+        let txq = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0; 128]));
+        let mbox = NodeMbox::new(&[], tpdos, txq, sdo_buffer);
+        mbox.set_sdo_tx_cob_id(Some(CanId::std(0x581)));
+        assert_eq!(TxPriority::Arbitration, mbox.tx_priority());
+
+        let data: heapless::Vec<u8, 8> = heapless::Vec::from_slice(&[1]).unwrap();
+        let emcy = CanMessage::new(CanId::std(0x081), &[]);
+        let next_ids = |n: usize| {
+            (0..n)
+                .map(|_| mbox.next_transmit_message().unwrap().id())
+                .collect::<Vec<_>>()
+        };
+
+        // SDO responses wait for all other messages, even lower priority ones
+        mbox.set_tx_priority(TxPriority::SdoLast);
+        mbox.sdo_comms().begin_block_upload(7 * 3, true);
+        mbox.queue_transmit_message(emcy).unwrap();
+        tpdos[0].buffered_value.store(Some(data.clone()));
+        assert_eq!(
+            vec![CanId::std(0x081), CanId::std(0x600), CanId::std(0x581)],
+            next_ids(3)
+        );
+        while mbox.next_transmit_message().is_some() {}
+
+        // SDO responses go ahead of all other messages, for the whole transfer
+        mbox.set_tx_priority(TxPriority::SdoFirst);
+        mbox.sdo_comms().begin_block_upload(7 * 18, true);
+        mbox.queue_transmit_message(emcy).unwrap();
+        tpdos[0].buffered_value.store(Some(data.clone()));
+        let ids = next_ids(20);
+        assert!(ids[..18].iter().all(|id| *id == CanId::std(0x581)));
+        assert_eq!(vec![CanId::std(0x081), CanId::std(0x600)], ids[18..]);
+    }
+
     #[test]
     /// Test response to SDO requests
     fn test_sdo_requests() {
@@ -310,7 +1216,7 @@ mod tests {
             .mbox
             .store_message(req.to_can_message(SDO_RX_COB_ID))
             .is_ok());
-        assert_eq!(false, process_flag.swap(false, Ordering::Relaxed));
+        assert!(!process_flag.swap(false, Ordering::Relaxed));
         assert_eq!(None, obj.mbox.sdo_comms().take_request());
         let buf = obj.mbox.sdo_comms().borrow_buffer();
         assert_eq!([1, 2, 3, 4, 5, 6, 7], buf[0..7]);
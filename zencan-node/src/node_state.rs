@@ -1,7 +1,8 @@
 //! Implements node state struct
 use zencan_common::nmt::NmtState;
-use zencan_common::AtomicCell;
+use zencan_common::{AtomicCell, CanId};
 
+use crate::change_counters::ChangeCounters;
 use crate::object_dict::ObjectFlagSync;
 
 use crate::pdo::Pdo;
@@ -33,6 +34,8 @@ pub struct NodeState<'a> {
     /// State shared between the [`StorageCommandObject`](crate::storage::StorageCommandObject) and
     /// [`Node`](crate::node::Node) for indicating when a store objects command has been recieved.
     storage_context: StorageContext,
+    /// Counters of modifications to each persist group
+    change_counters: ChangeCounters,
     /// Global storage for the NMT state
     nmt_state: AtomicCell<NmtState>,
 }
@@ -54,6 +57,7 @@ impl<'a> NodeState<'a> {
             tpdos,
             object_flag_sync,
             storage_context,
+            change_counters: ChangeCounters::new(),
             nmt_state: AtomicCell::new(NmtState::Bootup),
         }
     }
@@ -68,6 +72,22 @@ impl<'a> NodeState<'a> {
         self.tpdos
     }
 
+    /// Get the COB IDs of all enabled RPDOs
+    pub fn enabled_rpdo_cob_ids(&self) -> impl Iterator<Item = CanId> + 'a {
+        self.rpdos
+            .iter()
+            .filter(|pdo| pdo.valid())
+            .map(|pdo| pdo.cob_id())
+    }
+
+    /// Get the COB IDs of all enabled TPDOs
+    pub fn enabled_tpdo_cob_ids(&self) -> impl Iterator<Item = CanId> + 'a {
+        self.tpdos
+            .iter()
+            .filter(|pdo| pdo.valid())
+            .map(|pdo| pdo.cob_id())
+    }
+
     /// Access the pdo_sync as a const function
     ///
     /// This is required so that it can be shared with the objects in generated code
@@ -80,6 +100,11 @@ impl<'a> NodeState<'a> {
         &self.storage_context
     }
 
+    /// Access the change_counters as a const function
+    pub const fn change_counters(&'a self) -> &'a ChangeCounters {
+        &self.change_counters
+    }
+
     /// Set the NMT state
     ///
     /// This method is intended only for the `Node` object to update the global node nmt state
@@ -0,0 +1,557 @@
+//! Notification callbacks used by the [`NodeMbox`](crate::NodeMbox)
+//!
+//! A [`NotifyCallback`] can be created from:
+//!
+//! - A static reference to a function or closure, e.g. `&notify_task`
+//! - A function pointer and a static context reference, with [`NotifyCallback::with_context`]. This
+//!   allows the same function to be used for several contexts without writing a wrapper function
+//!   for each, e.g. `NotifyCallback::with_context(Notify::notify, &CAN_NOTIFY)`.
+//! - With the `std` feature, an owned closure, with [`NotifyCallback::from_fn`], so that closures
+//!   capturing runtime values do not need to be leaked to obtain a static reference.
+//!
+//! A [`MessageTap`], which receives a copy of incoming messages, and a [`MessageHandler`], which
+//! receives extended ID messages not used by the node, can be created in the same ways.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use zencan_common::CanMessage;
+
+mod sealed {
+    /// Restricts [`Signature`](super::Signature) and [`FnPointer`](super::FnPointer) to the types
+    /// in this module
+    pub trait Sealed {}
+}
+
+/// A function pointer type, which can be erased to a `*const ()`
+///
+/// This is sealed, and only implemented for the function pointer types used by the signatures in
+/// this module.
+trait FnPointer: Copy + sealed::Sealed {}
+
+impl<T> sealed::Sealed for fn(&T) {}
+impl<T> FnPointer for fn(&T) {}
+impl<T> sealed::Sealed for fn(&T, &CanMessage, bool) {}
+impl<T> FnPointer for fn(&T, &CanMessage, bool) {}
+impl<T> sealed::Sealed for fn(&T, &CanMessage) -> bool {}
+impl<T> FnPointer for fn(&T, &CanMessage) -> bool {}
+
+/// The signature of a callback
+///
+/// This is sealed, and only implemented by the marker types in this module.
+trait Signature: sealed::Sealed {
+    /// The arguments passed to the callback, after the context for a [`ContextFn`]
+    type Args<'a>;
+    /// The value returned by the callback
+    type Output;
+    /// The function pointer type, which takes a `&T` context as its first argument
+    type Fn<T>: FnPointer;
+    /// The static function or closure type
+    type StaticFn: ?Sized + Sync + 'static;
+    /// The owned closure type
+    #[cfg(feature = "std")]
+    type OwnedFn: ?Sized + Send + Sync + 'static;
+
+    /// Call `func` with the context and arguments
+    fn invoke<T>(func: Self::Fn<T>, ctx: &T, args: Self::Args<'_>) -> Self::Output;
+
+    /// Call a static function or closure
+    fn invoke_static(func: &Self::StaticFn, args: Self::Args<'_>) -> Self::Output;
+
+    /// Call an owned closure
+    #[cfg(feature = "std")]
+    fn invoke_owned(func: &Self::OwnedFn, args: Self::Args<'_>) -> Self::Output;
+}
+
+/// A callback with signature `S`, created in one of the ways described in the
+/// [module documentation](self)
+enum Callback<S: Signature> {
+    Static(&'static S::StaticFn),
+    Context(ContextFn<S>),
+    #[cfg(feature = "std")]
+    Owned(std::sync::Arc<S::OwnedFn>),
+}
+
+impl<S: Signature> Clone for Callback<S> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Static(func) => Self::Static(*func),
+            Self::Context(f) => Self::Context(*f),
+            #[cfg(feature = "std")]
+            Self::Owned(func) => Self::Owned(func.clone()),
+        }
+    }
+}
+
+impl<S: Signature> Callback<S> {
+    fn call(&self, args: S::Args<'_>) -> S::Output {
+        match self {
+            Self::Static(func) => S::invoke_static(*func, args),
+            Self::Context(f) => f.call(args),
+            #[cfg(feature = "std")]
+            Self::Owned(func) => S::invoke_owned(func, args),
+        }
+    }
+}
+
+/// A function pointer and a static context reference, with the type of the context erased
+struct ContextFn<S: Signature> {
+    func: *const (),
+    ctx: *const (),
+    call: for<'a> unsafe fn(*const (), *const (), S::Args<'a>) -> S::Output,
+}
+
+impl<S: Signature> Clone for ContextFn<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: Signature> Copy for ContextFn<S> {}
+
+// Safety: ContextFn is only created by `ContextFn::new`, from a `&'static T` where `T: Sync`, so
+// the context may be shared with any thread
+unsafe impl<S: Signature> Send for ContextFn<S> {}
+unsafe impl<S: Signature> Sync for ContextFn<S> {}
+
+impl<S: Signature> ContextFn<S> {
+    /// Erase the types of `func` and `ctx`
+    ///
+    /// This relies on `S::Fn<T>` being a function pointer, so that it can be converted to a
+    /// `*const ()` and back. That is guaranteed by the [`FnPointer`] bound, which is sealed and
+    /// only implemented for function pointer types; the size assertion checks it again at compile
+    /// time. `call` is instantiated for the same `S` and `T`, so it is always given back the types
+    /// which were erased.
+    fn new<T: Sync + 'static>(func: S::Fn<T>, ctx: &'static T) -> Self {
+        const { assert!(size_of::<S::Fn<T>>() == size_of::<*const ()>()) };
+        Self {
+            // Safety: `S::Fn<T>` is a function pointer, and is the same size as `*const ()`
+            func: unsafe { core::mem::transmute_copy::<S::Fn<T>, *const ()>(&func) },
+            ctx: ctx as *const T as *const (),
+            call: call_with_context::<S, T>,
+        }
+    }
+
+    fn call(&self, args: S::Args<'_>) -> S::Output {
+        // Safety: `func`, `ctx` and `call` were all created by `new` for the same type `T`
+        unsafe { (self.call)(self.func, self.ctx, args) }
+    }
+}
+
+/// Call a function pointer created from `S::Fn<T>` with a context created from `&'static T`
+///
+/// # Safety
+///
+/// `func` and `ctx` must have been created from a `S::Fn<T>` and a `&'static T` of the same type
+/// `T`
+unsafe fn call_with_context<S: Signature, T>(
+    func: *const (),
+    ctx: *const (),
+    args: S::Args<'_>,
+) -> S::Output {
+    let func = core::mem::transmute_copy::<*const (), S::Fn<T>>(&func);
+    S::invoke(func, &*(ctx as *const T), args)
+}
+
+/// Storage for an optional callback which can be replaced from any context
+pub(crate) struct CallbackCell<C: Clone> {
+    callback: Mutex<RefCell<Option<C>>>,
+}
+
+impl<C: Clone> CallbackCell<C> {
+    pub const fn new() -> Self {
+        Self {
+            callback: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn set(&self, callback: Option<C>) {
+        // The previous callback is dropped outside of the critical section
+        let _old = critical_section::with(|cs| self.callback.borrow(cs).replace(callback));
+    }
+
+    /// Get a copy of the callback, so that it can be called outside of the critical section
+    fn get(&self) -> Option<C> {
+        critical_section::with(|cs| self.callback.borrow(cs).borrow().clone())
+    }
+}
+
+/// A callback used to notify the application of mailbox events
+///
+/// See the [module documentation](self) for the ways in which a callback can be created.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct NotifyCallback(Callback<NotifySignature>);
+
+/// The signature of a [`NotifyCallback`]
+enum NotifySignature {}
+
+impl sealed::Sealed for NotifySignature {}
+
+impl Signature for NotifySignature {
+    type Args<'a> = ();
+    type Output = ();
+    type Fn<T> = fn(&T);
+    type StaticFn = dyn Fn() + Sync;
+    #[cfg(feature = "std")]
+    type OwnedFn = dyn Fn() + Send + Sync;
+
+    fn invoke<T>(func: Self::Fn<T>, ctx: &T, _args: ()) {
+        func(ctx)
+    }
+
+    fn invoke_static(func: &Self::StaticFn, _args: ()) {
+        func()
+    }
+
+    #[cfg(feature = "std")]
+    fn invoke_owned(func: &Self::OwnedFn, _args: ()) {
+        func()
+    }
+}
+
+impl NotifyCallback {
+    /// Create a callback from a static function or closure
+    pub const fn from_static(func: &'static (dyn Fn() + Sync)) -> Self {
+        Self(Callback::Static(func))
+    }
+
+    /// Create a callback which calls `func(ctx)`
+    pub fn with_context<T: Sync + 'static>(func: fn(&T), ctx: &'static T) -> Self {
+        Self(Callback::Context(ContextFn::new(func, ctx)))
+    }
+
+    /// Create a callback from an owned closure
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn from_fn(func: impl Fn() + Send + Sync + 'static) -> Self {
+        let func: std::sync::Arc<dyn Fn() + Send + Sync> = std::sync::Arc::new(func);
+        Self(Callback::Owned(func))
+    }
+
+    /// Call the callback
+    pub fn call(&self) {
+        self.0.call(())
+    }
+}
+
+impl<F: Fn() + Sync> From<&'static F> for NotifyCallback {
+    fn from(func: &'static F) -> Self {
+        Self::from_static(func)
+    }
+}
+
+impl<F: Fn() + Sync> From<&'static mut F> for NotifyCallback {
+    fn from(func: &'static mut F) -> Self {
+        Self::from_static(func)
+    }
+}
+
+impl From<&'static (dyn Fn() + Sync)> for NotifyCallback {
+    fn from(func: &'static (dyn Fn() + Sync)) -> Self {
+        Self(Callback::Static(func))
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl From<Box<dyn Fn() + Send + Sync>> for NotifyCallback {
+    fn from(func: Box<dyn Fn() + Send + Sync>) -> Self {
+        Self(Callback::Owned(func.into()))
+    }
+}
+
+/// Storage for an optional [`NotifyCallback`] which can be replaced from any context
+pub(crate) type NotifyCell = CallbackCell<NotifyCallback>;
+
+impl NotifyCell {
+    /// Call the callback, if one is set
+    ///
+    /// The callback is called outside of the critical section.
+    pub fn notify(&self) {
+        if let Some(callback) = self.get() {
+            callback.call();
+        }
+    }
+}
+
+/// A callback which receives a copy of messages stored in the [`NodeMbox`](crate::NodeMbox)
+///
+/// The callback is called with each message, and a flag which is true if the message was accepted
+/// by the mailbox. See [`NodeMbox::set_message_tap`](crate::NodeMbox::set_message_tap).
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct MessageTap(Callback<TapSignature>);
+
+/// An owned [`MessageTap`] closure
+#[cfg(feature = "std")]
+type OwnedTapFn = std::sync::Arc<dyn Fn(&CanMessage, bool) + Send + Sync>;
+
+/// The signature of a [`MessageTap`]
+enum TapSignature {}
+
+impl sealed::Sealed for TapSignature {}
+
+impl Signature for TapSignature {
+    type Args<'a> = (&'a CanMessage, bool);
+    type Output = ();
+    type Fn<T> = fn(&T, &CanMessage, bool);
+    type StaticFn = dyn Fn(&CanMessage, bool) + Sync;
+    #[cfg(feature = "std")]
+    type OwnedFn = dyn Fn(&CanMessage, bool) + Send + Sync;
+
+    fn invoke<T>(func: Self::Fn<T>, ctx: &T, (msg, accepted): (&CanMessage, bool)) {
+        func(ctx, msg, accepted)
+    }
+
+    fn invoke_static(func: &Self::StaticFn, (msg, accepted): (&CanMessage, bool)) {
+        func(msg, accepted)
+    }
+
+    #[cfg(feature = "std")]
+    fn invoke_owned(func: &Self::OwnedFn, (msg, accepted): (&CanMessage, bool)) {
+        func(msg, accepted)
+    }
+}
+
+impl MessageTap {
+    /// Create a tap from a static function or closure
+    pub const fn from_static(func: &'static (dyn Fn(&CanMessage, bool) + Sync)) -> Self {
+        Self(Callback::Static(func))
+    }
+
+    /// Create a tap which calls `func(ctx, msg, accepted)`
+    pub fn with_context<T: Sync + 'static>(
+        func: fn(&T, &CanMessage, bool),
+        ctx: &'static T,
+    ) -> Self {
+        Self(Callback::Context(ContextFn::new(func, ctx)))
+    }
+
+    /// Create a tap from an owned closure
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn from_fn(func: impl Fn(&CanMessage, bool) + Send + Sync + 'static) -> Self {
+        let func: OwnedTapFn = std::sync::Arc::new(func);
+        Self(Callback::Owned(func))
+    }
+
+    /// Call the tap
+    pub fn call(&self, msg: &CanMessage, accepted: bool) {
+        self.0.call((msg, accepted))
+    }
+}
+
+impl<F: Fn(&CanMessage, bool) + Sync> From<&'static F> for MessageTap {
+    fn from(func: &'static F) -> Self {
+        Self::from_static(func)
+    }
+}
+
+/// Storage for an optional [`MessageTap`], and whether it receives rejected messages
+pub(crate) type TapCell = CallbackCell<(MessageTap, bool)>;
+
+impl TapCell {
+    /// Pass a message to the tap, if one is set
+    ///
+    /// Rejected messages are only passed to a tap which requested them. The tap is called outside
+    /// of the critical section.
+    pub fn tap(&self, msg: &CanMessage, accepted: bool) {
+        match self.get() {
+            Some((tap, include_rejected)) if accepted || include_rejected => {
+                tap.call(msg, accepted)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A callback which receives extended ID messages which are not used by the node
+///
+/// The callback returns true if it accepted the message. See
+/// [`NodeMbox::set_extended_id_handler`](crate::NodeMbox::set_extended_id_handler).
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct MessageHandler(Callback<HandlerSignature>);
+
+/// An owned [`MessageHandler`] closure
+#[cfg(feature = "std")]
+type OwnedHandlerFn = std::sync::Arc<dyn Fn(&CanMessage) -> bool + Send + Sync>;
+
+/// The signature of a [`MessageHandler`]
+enum HandlerSignature {}
+
+impl sealed::Sealed for HandlerSignature {}
+
+impl Signature for HandlerSignature {
+    type Args<'a> = &'a CanMessage;
+    type Output = bool;
+    type Fn<T> = fn(&T, &CanMessage) -> bool;
+    type StaticFn = dyn Fn(&CanMessage) -> bool + Sync;
+    #[cfg(feature = "std")]
+    type OwnedFn = dyn Fn(&CanMessage) -> bool + Send + Sync;
+
+    fn invoke<T>(func: Self::Fn<T>, ctx: &T, msg: &CanMessage) -> bool {
+        func(ctx, msg)
+    }
+
+    fn invoke_static(func: &Self::StaticFn, msg: &CanMessage) -> bool {
+        func(msg)
+    }
+
+    #[cfg(feature = "std")]
+    fn invoke_owned(func: &Self::OwnedFn, msg: &CanMessage) -> bool {
+        func(msg)
+    }
+}
+
+impl MessageHandler {
+    /// Create a handler from a static function or closure
+    pub const fn from_static(func: &'static (dyn Fn(&CanMessage) -> bool + Sync)) -> Self {
+        Self(Callback::Static(func))
+    }
+
+    /// Create a handler which calls `func(ctx, msg)`
+    pub fn with_context<T: Sync + 'static>(
+        func: fn(&T, &CanMessage) -> bool,
+        ctx: &'static T,
+    ) -> Self {
+        Self(Callback::Context(ContextFn::new(func, ctx)))
+    }
+
+    /// Create a handler from an owned closure
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn from_fn(func: impl Fn(&CanMessage) -> bool + Send + Sync + 'static) -> Self {
+        let func: OwnedHandlerFn = std::sync::Arc::new(func);
+        Self(Callback::Owned(func))
+    }
+
+    /// Call the handler
+    pub fn call(&self, msg: &CanMessage) -> bool {
+        self.0.call(msg)
+    }
+}
+
+impl<F: Fn(&CanMessage) -> bool + Sync> From<&'static F> for MessageHandler {
+    fn from(func: &'static F) -> Self {
+        Self::from_static(func)
+    }
+}
+
+/// Storage for an optional [`MessageHandler`] which can be replaced from any context
+pub(crate) type HandlerCell = CallbackCell<MessageHandler>;
+
+impl HandlerCell {
+    /// Pass a message to the handler, and return true if it accepted it
+    ///
+    /// Returns false if no handler is set. The handler is called outside of the critical section.
+    pub fn handle(&self, msg: &CanMessage) -> bool {
+        self.get().is_some_and(|handler| handler.call(msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn increment(counter: &AtomicU32) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_static_callback() {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        fn notify() {
+            increment(&COUNT);
+        }
+        let cell = NotifyCell::new();
+        cell.notify();
+        cell.set(Some((&notify).into()));
+        cell.notify();
+        cell.notify();
+        assert_eq!(2, COUNT.load(Ordering::Relaxed));
+        cell.set(None);
+        cell.notify();
+        assert_eq!(2, COUNT.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_context_callback() {
+        static FIRST: AtomicU32 = AtomicU32::new(0);
+        static SECOND: AtomicU32 = AtomicU32::new(0);
+        NotifyCallback::with_context(increment, &FIRST).call();
+        let second = NotifyCallback::with_context(increment, &SECOND);
+        second.call();
+        second.clone().call();
+        assert_eq!(1, FIRST.load(Ordering::Relaxed));
+        assert_eq!(2, SECOND.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_owned_callback() {
+        let count = std::sync::Arc::new(AtomicU32::new(0));
+        let cell = NotifyCell::new();
+        let count_clone = count.clone();
+        cell.set(Some(NotifyCallback::from_fn(move || {
+            increment(&count_clone)
+        })));
+        cell.notify();
+        assert_eq!(1, count.load(Ordering::Relaxed));
+
+        // Replacing the callback drops the closure
+        cell.set(None);
+        assert_eq!(1, std::sync::Arc::strong_count(&count));
+    }
+
+    #[test]
+    fn test_message_tap() {
+        static ACCEPTED: AtomicU32 = AtomicU32::new(0);
+        static REJECTED: AtomicU32 = AtomicU32::new(0);
+        fn count(_: &(), _msg: &CanMessage, accepted: bool) {
+            if accepted {
+                increment(&ACCEPTED);
+            } else {
+                increment(&REJECTED);
+            }
+        }
+        let msg = CanMessage::new(zencan_common::messages::CanId::std(0x123), &[1]);
+        let cell = TapCell::new();
+        cell.tap(&msg, true);
+
+        cell.set(Some((MessageTap::with_context(count, &()), false)));
+        cell.tap(&msg, true);
+        cell.tap(&msg, false);
+        assert_eq!(1, ACCEPTED.load(Ordering::Relaxed));
+        assert_eq!(0, REJECTED.load(Ordering::Relaxed));
+
+        cell.set(Some((MessageTap::with_context(count, &()), true)));
+        cell.tap(&msg, false);
+        assert_eq!(1, REJECTED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_message_handler() {
+        static HANDLED: AtomicU32 = AtomicU32::new(0);
+        fn handle(_: &(), msg: &CanMessage) -> bool {
+            increment(&HANDLED);
+            msg.id().is_extended()
+        }
+        let ext = CanMessage::new(zencan_common::messages::CanId::extended(0x18FEF180), &[1]);
+        let std = CanMessage::new(zencan_common::messages::CanId::std(0x123), &[1]);
+        let cell = HandlerCell::new();
+        assert!(!cell.handle(&ext));
+
+        cell.set(Some(MessageHandler::with_context(handle, &())));
+        assert!(cell.handle(&ext));
+        assert!(!cell.handle(&std));
+        assert_eq!(2, HANDLED.load(Ordering::Relaxed));
+
+        cell.set(None);
+        assert!(!cell.handle(&ext));
+        assert_eq!(2, HANDLED.load(Ordering::Relaxed));
+    }
+}
@@ -148,9 +148,26 @@
 //!
 //! # Object threading support
 //!
-//! All object must be `Sync` and `Send`, to allow for access from any thread. This is implemented
-//! using the `critical_section` crate. All objects support [`ObjectAccess::read`] and
-//! [`ObjectAccess::write`], which allow for atomic access of objects. For small objects, the SDO
+//! All object must be `Sync` and `Send`, to allow for access from any thread, core, or interrupt
+//! priority. All objects support [`ObjectAccess::read`] and [`ObjectAccess::write`], which allow
+//! for atomic access of objects. The storage types provided here implement this as follows:
+//!
+//! - [`ScalarField`] values of 8, 16, or 32 bits (including `bool` and `f32`) are stored in native
+//!   atomics, and are accessed lock-free. This includes the elements of generated array objects.
+//! - All other scalar values (24 and 64-bit values, and time types), and [`ByteField`] /
+//!   [`NullTermByteField`] values, are accessed inside a critical section.
+//! - Object event flags are updated inside a critical section.
+//!
+//! Critical sections are provided by the `critical_section` crate, and it is up to the application
+//! to provide an implementation which is correct for its platform. On multi-core MCUs (e.g. RP2040
+//! or ESP32-S3), it must be one which excludes the other cores -- such as the implementations
+//! provided by the HAL crates for those chips -- and not one which only disables interrupts on the
+//! current core.
+//!
+//! Each object access is atomic, but there is no atomicity across sub objects. A reader may see a
+//! new value in one sub object and an old value in another while a writer updates both.
+//!
+//! For small objects, the SDO
 //! server will access objects using a single read or write call, buffering the data for segmented
 //! or block transfers, ensuring atomic access. However, if the size of the transfer is larger than
 //! the SDO buffer (currently fixed at 889 bytes, but likely to become adjustable in the future)
@@ -164,6 +181,10 @@
 //! Some objects support event flags, which can be set via [`ObjectAccess::set_event_flag`]. These
 //! are used to trigger TPDO transmission.
 //!
+//! For TPDO mappable objects, `zencan-build` also generates setters which store a value and set its
+//! event flag in one call: `set_and_notify(idx, value)` for arrays, and
+//! `set_<field>_and_notify(value)` for vars and record fields.
+//!
 
 mod object_flags;
 mod objects;
@@ -1,7 +1,7 @@
-use core::cell::UnsafeCell;
+use core::{cell::UnsafeCell, sync::atomic::Ordering};
 
 use critical_section::Mutex;
-use zencan_common::AtomicCell;
+use portable_atomic::AtomicU32;
 
 /// A struct used for synchronizing the A/B event flags of all objects, which are used for
 /// triggering PDO events
@@ -22,6 +22,16 @@ struct ObjectFlagsInner {
     toggle: bool,
     /// A global flag that should be set by any object which has set a flag
     global_flag: bool,
+    /// The TPDO event bits of all objects which have set a flag, see [`tpdo_event_bit`]
+    dirty_tpdos: u32,
+}
+
+/// Get the bit representing a TPDO in the event masks of objects
+///
+/// With more than 32 TPDOs, several share each bit. A set bit then only means that one of them may
+/// have an event, which is resolved by checking their mapped objects.
+pub(crate) const fn tpdo_event_bit(tpdo: usize) -> u32 {
+    1 << (tpdo % 32)
 }
 
 impl ObjectFlagSync {
@@ -31,20 +41,34 @@ impl ObjectFlagSync {
             inner: Mutex::new(UnsafeCell::new(ObjectFlagsInner {
                 toggle: false,
                 global_flag: false,
+                dirty_tpdos: 0,
             })),
         }
     }
 
     /// Toggle the flag and return the global flag
     pub fn toggle(&self) -> bool {
+        self.toggle_inner().0
+    }
+
+    /// Toggle the flag and return the event bits of the TPDOs which may have events
+    ///
+    /// Only TPDOs mapping an object which has set a flag since the last toggle are included.
+    pub fn toggle_tpdo_events(&self) -> u32 {
+        self.toggle_inner().1
+    }
+
+    fn toggle_inner(&self) -> (bool, u32) {
         critical_section::with(|cs| {
             let inner = self.inner.borrow(cs).get();
-            // Safety: This is the only place inner is accessed, and it is in a critical section
+            // Safety: inner is only accessed in critical sections
             unsafe {
                 let global = (*inner).global_flag;
+                let dirty_tpdos = (*inner).dirty_tpdos;
                 (*inner).global_flag = false;
+                (*inner).dirty_tpdos = 0;
                 (*inner).toggle = !(*inner).toggle;
-                global
+                (global, dirty_tpdos)
             }
         })
     }
@@ -59,6 +83,21 @@ impl ObjectFlagSync {
             inner.toggle
         })
     }
+
+    /// Get the current value of the flag for setting, and mark the given TPDOs as dirty
+    fn get_flag_for_set(&self, tpdo_mask: u32) -> bool {
+        critical_section::with(|cs| {
+            let inner = unsafe { &mut (*self.inner.borrow(cs).get()) };
+            inner.global_flag = true;
+            inner.dirty_tpdos |= tpdo_mask;
+            inner.toggle
+        })
+    }
+}
+
+/// Get the number of words an [`ObjectFlags`] needs to store `count` flags
+pub const fn object_flag_words(count: usize) -> usize {
+    count.div_ceil(32)
 }
 
 /// Stores an event flag for each sub object in an object
@@ -70,11 +109,19 @@ impl ObjectFlagSync {
 /// In order to achieve this in a synchronized way without long critical sections, each object
 /// holds two sets of flags, and they are swapped atomically using a global `ObjectFlagSync` shared by
 /// all `ObjectFlags` instances.
+///
+/// Flags are stored in `N` 32-bit atomic words, so an object has flags for sub indices `0..N * 32`.
+/// Setting a flag outside of this range has no effect. `N` must be at least 1; see
+/// [`object_flag_words`].
+///
+/// Each object also records which TPDOs it is mapped into. The node updates this whenever the TPDO
+/// mappings change, and setting a flag marks those TPDOs as dirty, so that the node only has to
+/// check the mappings of TPDOs which may have an event.
 #[allow(missing_debug_implementations)]
 pub struct ObjectFlags<const N: usize> {
     sync: &'static ObjectFlagSync,
-    flags0: AtomicCell<[u8; N]>,
-    flags1: AtomicCell<[u8; N]>,
+    banks: [[AtomicU32; N]; 2],
+    tpdo_mask: AtomicU32,
 }
 
 /// Trait for accessing object flags
@@ -88,56 +135,149 @@ pub trait ObjectFlagAccess {
     /// The flag is read from the currently inactive flag set, i.e. the flag value from before the
     /// last sync toggle is returned
     fn get_flag(&self, sub: u8) -> bool;
-    /// Clear all flags in the currently active flag set
+    /// Find the lowest sub index at or above `from` with its flag set
+    ///
+    /// Like `get_flag`, this reads the currently inactive flag set
+    fn next_flag(&self, from: u8) -> Option<u8>;
+    /// Clear all flags in the currently inactive flag set, i.e. the set read by `get_flag`
     fn clear(&self);
+    /// Get the event bits of the TPDOs this object is mapped into
+    fn tpdo_mask(&self) -> u32;
+    /// Set the event bits of the TPDOs this object is mapped into
+    fn set_tpdo_mask(&self, mask: u32);
 }
 
 impl<const N: usize> ObjectFlags<N> {
     /// Create a new ObjectFlags
     pub const fn new(sync: &'static ObjectFlagSync) -> Self {
+        const { assert!(N > 0, "ObjectFlags must have at least one word") };
         Self {
             sync,
-            flags0: AtomicCell::new([0; N]),
-            flags1: AtomicCell::new([0; N]),
+            banks: [const { [const { AtomicU32::new(0) }; N] }; 2],
+            tpdo_mask: AtomicU32::new(0),
         }
     }
+
+    /// Get the inactive flag set, which is read
+    fn read_bank(&self) -> &[AtomicU32; N] {
+        let toggle = self.sync.get_flag(false);
+        &self.banks[toggle as usize]
+    }
 }
 
 impl<const N: usize> ObjectFlagAccess for ObjectFlags<N> {
     fn set_flag(&self, sub: u8) {
-        if sub as usize >= N * 8 {
+        let word = sub as usize / 32;
+        if word >= N {
             return;
         }
-        let flags = if self.sync.get_flag(true) {
-            &self.flags0
-        } else {
-            &self.flags1
-        };
-        flags
-            .fetch_update(|mut f| {
-                f[sub as usize / 8] |= 1 << (sub & 7);
-                Some(f)
-            })
-            .unwrap();
+        let toggle = self
+            .sync
+            .get_flag_for_set(self.tpdo_mask.load(Ordering::Acquire));
+        self.banks[!toggle as usize][word].fetch_or(1 << (sub % 32), Ordering::AcqRel);
     }
 
     fn get_flag(&self, sub: u8) -> bool {
-        if sub as usize >= N * 8 {
+        let word = sub as usize / 32;
+        if word >= N {
             return false;
         }
-        let flags = if self.sync.get_flag(false) {
-            &self.flags1.load()
-        } else {
-            &self.flags0.load()
-        };
-        flags[(sub / 8) as usize] & (1 << (sub & 7)) != 0
+        self.read_bank()[word].load(Ordering::Acquire) & (1 << (sub % 32)) != 0
+    }
+
+    fn next_flag(&self, from: u8) -> Option<u8> {
+        let bank = self.read_bank();
+        let mut word = from as usize / 32;
+        // Ignore flags below `from` in the first word
+        let mut mask = u32::MAX << (from % 32);
+        while word < N {
+            let flags = bank[word].load(Ordering::Acquire) & mask;
+            if flags != 0 {
+                return Some((word * 32) as u8 + flags.trailing_zeros() as u8);
+            }
+            word += 1;
+            mask = u32::MAX;
+        }
+        None
     }
 
     fn clear(&self) {
-        if self.sync.get_flag(false) {
-            self.flags1.store([0; N]);
-        } else {
-            self.flags0.store([0; N]);
+        for word in self.read_bank() {
+            word.store(0, Ordering::Release);
         }
     }
+
+    fn tpdo_mask(&self) -> u32 {
+        self.tpdo_mask.load(Ordering::Acquire)
+    }
+
+    fn set_tpdo_mask(&self, mask: u32) {
+        self.tpdo_mask.store(mask, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_flags() {
+        let sync = Box::leak(Box::new(ObjectFlagSync::new()));
+        let flags = ObjectFlags::<{ object_flag_words(255) }>::new(sync);
+
+        flags.set_flag(0);
+        flags.set_flag(40);
+        flags.set_flag(254);
+        // Flags are not visible until the sync is toggled
+        assert!(!flags.get_flag(40));
+        assert_eq!(None, flags.next_flag(0));
+        assert!(sync.toggle());
+
+        assert!(flags.get_flag(0));
+        assert!(flags.get_flag(40));
+        assert!(!flags.get_flag(41));
+        assert!(flags.get_flag(254));
+        assert_eq!(Some(0), flags.next_flag(0));
+        assert_eq!(Some(40), flags.next_flag(1));
+        assert_eq!(Some(254), flags.next_flag(41));
+        assert_eq!(None, flags.next_flag(255));
+
+        // Flags set now go to the other set, and clear only affects the set being read
+        flags.set_flag(3);
+        flags.clear();
+        assert_eq!(None, flags.next_flag(0));
+        assert!(sync.toggle());
+        assert!(flags.get_flag(3));
+        assert!(!flags.get_flag(40));
+    }
+
+    #[test]
+    fn test_dirty_tpdos() {
+        let sync = Box::leak(Box::new(ObjectFlagSync::new()));
+        let mapped = ObjectFlags::<1>::new(sync);
+        let unmapped = ObjectFlags::<1>::new(sync);
+        mapped.set_tpdo_mask(tpdo_event_bit(1) | tpdo_event_bit(34));
+        assert_eq!(0b110, mapped.tpdo_mask());
+
+        unmapped.set_flag(0);
+        assert_eq!(0, sync.toggle_tpdo_events());
+        mapped.set_flag(0);
+        assert_eq!(0b110, sync.toggle_tpdo_events());
+        assert_eq!(0, sync.toggle_tpdo_events());
+        // The global flag is still set for objects not mapped to a TPDO
+        unmapped.set_flag(0);
+        assert!(sync.toggle());
+    }
+
+    #[test]
+    fn test_object_flags_out_of_range() {
+        let sync = Box::leak(Box::new(ObjectFlagSync::new()));
+        let flags = ObjectFlags::<1>::new(sync);
+        flags.set_flag(31);
+        flags.set_flag(32);
+        sync.toggle();
+        assert!(flags.get_flag(31));
+        assert!(!flags.get_flag(32));
+        assert_eq!(Some(31), flags.next_flag(0));
+    }
 }
@@ -8,6 +8,11 @@ use zencan_common::{
 
 use super::{ObjectFlagAccess, SubObjectAccess};
 
+/// The largest value which [`ObjectAccess::write_from`] gathers into a single buffer by default
+///
+/// Larger values are written with partial writes.
+pub const WRITE_GATHER_SIZE: usize = 64;
+
 /// A trait for accessing objects
 ///
 /// Any struct which implements an object in the object dictionary must implement this trait
@@ -22,6 +27,15 @@ pub trait ObjectAccess: Sync + Send {
     /// Get the number of bytes available for a read
     fn read_size(&self, sub: u8) -> Result<usize, AbortCode>;
 
+    /// Get the exact number of bytes an upload of the sub object will return, if it is known
+    ///
+    /// This is the object level counterpart of [`SubObjectAccess::current_size`], and is used by
+    /// the SDO server to report the size of uploads which do not fit in its buffer. The default
+    /// implementation returns None.
+    fn upload_size(&self, _sub: u8) -> Option<usize> {
+        None
+    }
+
     /// Write raw bytes to a subobject
     ///
     /// The length of `data` must match the size of the object, or else it will fail with either
@@ -32,6 +46,67 @@ pub trait ObjectAccess: Sync + Send {
     /// If the sub exists but is not writeable, it shall fail with [`AbortCode::ReadOnly`].
     fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode>;
 
+    /// Read raw bytes from a subobject into several buffers
+    ///
+    /// The buffers are filled in order, as if they were a single buffer, starting `offset` bytes
+    /// into the sub object, so that a caller can assemble output from separate pieces of memory
+    /// without an intermediate copy. Returns the total number of bytes read, which is less than
+    /// the total length of `bufs` if the end of the sub object is reached.
+    ///
+    /// The default implementation calls [`read`](Self::read) for each buffer.
+    fn read_into(
+        &self,
+        sub: u8,
+        offset: usize,
+        bufs: &mut [&mut [u8]],
+    ) -> Result<usize, AbortCode> {
+        let mut total = 0;
+        for buf in bufs.iter_mut() {
+            let len = self.read(sub, offset + total, buf)?;
+            total += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Write raw bytes to a subobject from several buffers
+    ///
+    /// The value written is the concatenation of `data`, and is checked as by
+    /// [`write`](Self::write).
+    ///
+    /// The default implementation writes a single buffer directly. Values of up to
+    /// [`WRITE_GATHER_SIZE`] bytes in several buffers are gathered on the stack and written with
+    /// `write`, and larger ones are written with a partial write, so they fail with
+    /// [`AbortCode::UnsupportedAccess`] if the object does not support partial writes.
+    fn write_from(&self, sub: u8, data: &[&[u8]]) -> Result<(), AbortCode> {
+        match data {
+            [] => self.write(sub, &[]),
+            [data] => self.write(sub, data),
+            _ => {
+                let len: usize = data.iter().map(|d| d.len()).sum();
+                if len <= WRITE_GATHER_SIZE {
+                    let mut buf = [0; WRITE_GATHER_SIZE];
+                    let mut pos = 0;
+                    for d in data {
+                        buf[pos..pos + d.len()].copy_from_slice(d);
+                        pos += d.len();
+                    }
+                    return self.write(sub, &buf[..len]);
+                }
+                self.begin_partial(sub)?;
+                for d in data {
+                    if let Err(abort_code) = self.write_partial(sub, d) {
+                        self.end_partial(sub).ok();
+                        return Err(abort_code);
+                    }
+                }
+                self.end_partial(sub)
+            }
+        }
+    }
+
     /// Initialize a new partial write
     ///
     /// This must be called before performing calls to `partial_write`.
@@ -75,6 +150,46 @@ pub trait ObjectAccess: Sync + Send {
         Err(AbortCode::GeneralError)
     }
 
+    /// Initialize a new partial read
+    ///
+    /// This must be called before performing calls to `read_partial`. See
+    /// [`SubObjectAccess::begin_partial_read`].
+    ///
+    /// A default implementation is provided which returns an appropriate error:
+    /// - [`AbortCode::NoSuchSubIndex`] if the sub object does not exist
+    /// - [`AbortCode::WriteOnly`] if the sub object is write-only
+    /// - [`AbortCode::UnsupportedAccess`] if the sub object does not support partial reads
+    ///
+    /// Objects which support partial reading must override the default implementation.
+    fn begin_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Ok(sub_info) = self.sub_info(sub) {
+            if sub_info.access_type.is_readable() {
+                Err(AbortCode::UnsupportedAccess)
+            } else {
+                Err(AbortCode::WriteOnly)
+            }
+        } else {
+            Err(AbortCode::NoSuchSubIndex)
+        }
+    }
+
+    /// Read the next bytes of a partial read
+    ///
+    /// Returns the number of bytes read. A value less than `buf.len()` indicates the end of the
+    /// data.
+    fn read_partial(&self, _sub: u8, _buf: &mut [u8]) -> Result<usize, AbortCode> {
+        // All callers should have failed at begin_partial_read, so this should never be called
+        Err(AbortCode::GeneralError)
+    }
+
+    /// Finalize a previous partial read
+    ///
+    /// This must always be called after a successful `begin_partial_read`, including when the
+    /// read is not completed.
+    fn end_partial_read(&self, _sub: u8) -> Result<(), AbortCode> {
+        Err(AbortCode::GeneralError)
+    }
+
     /// Get the type of this object
     fn object_code(&self) -> ObjectCode;
 
@@ -94,6 +209,30 @@ pub trait ObjectAccess: Sync + Send {
         }
     }
 
+    /// Get the value reported for the object structure sub index (0xFF)
+    ///
+    /// The object code is in bits 0-7, and the data type of the sub objects is in bits 8-15. Records
+    /// have no single data type, and report a data type of 0.
+    fn object_structure(&self) -> u32 {
+        let object_code = self.object_code();
+        let data_type = match object_code {
+            ObjectCode::Var => self.data_type(0).ok(),
+            ObjectCode::Array => self.data_type(1).ok(),
+            _ => None,
+        };
+        let data_type = data_type.map(u16::from).unwrap_or(0) as u32;
+        (data_type << 8) | object_code as u32
+    }
+
+    /// Returns true if the object may be accessed with SDO complete access
+    ///
+    /// Complete access reads or writes all sub objects of a record or array in a single SDO
+    /// transfer. The SDO server implements it using the per sub object methods, so objects only
+    /// have to opt in. The default implementation returns false.
+    fn supports_complete_access(&self) -> bool {
+        false
+    }
+
     /// Set an event flag for the specified sub object on this object
     ///
     /// Event flags are used for triggering PDOs. This is optional, as not all objects support PDOs
@@ -109,11 +248,30 @@ pub trait ObjectAccess: Sync + Send {
         false
     }
 
+    /// Find the lowest sub object at or above `from` with its event flag set
+    ///
+    /// This is optional as not all objects support events
+    fn next_event_flag(&self, _from: u8) -> Option<u8> {
+        None
+    }
+
     /// Clear event flags for all sub objects
     ///
     /// This is optional as not all objects support events
     fn clear_events(&self) {}
 
+    /// Get the event bits of the TPDOs this object is mapped into
+    ///
+    /// This is optional as not all objects support events
+    fn tpdo_event_mask(&self) -> u32 {
+        0
+    }
+
+    /// Set the event bits of the TPDOs this object is mapped into
+    ///
+    /// This is optional as not all objects support events
+    fn set_tpdo_event_mask(&self, _mask: u32) {}
+
     /// Get the access type of a specific sub object
     fn access_type(&self, sub: u8) -> Result<AccessType, AbortCode> {
         Ok(self.sub_info(sub)?.access_type)
@@ -139,26 +297,7 @@ pub trait ObjectAccess: Sync + Send {
     /// number of bytes which may be read, but not necessarily the number of bytes which may be
     /// written.
     fn current_size(&self, sub: u8) -> Result<usize, AbortCode> {
-        const CHUNK_SIZE: usize = 8;
-
-        let size = self.size(sub)?;
-        if self.data_type(sub)?.is_str() {
-            // Look for first 0
-            let mut chunk = 0;
-            let mut buf = [0; CHUNK_SIZE];
-            while chunk < size / CHUNK_SIZE + 1 {
-                let offset = chunk * CHUNK_SIZE;
-                let bytes_to_read = (size - offset).min(CHUNK_SIZE);
-                self.read(sub, offset, &mut buf[0..bytes_to_read])?;
-
-                if let Some(zero_pos) = buf[0..bytes_to_read].iter().position(|b| *b == 0) {
-                    return Ok(zero_pos + chunk * CHUNK_SIZE);
-                }
-                chunk += 1;
-            }
-        }
-        // not a string type or no null-terminator was found
-        Ok(size)
+        stored_size(self, sub)
     }
 
     /// Read a sub object as a u32
@@ -230,6 +369,39 @@ pub trait ProvidesSubObjects {
 
     /// What type of object is this
     fn object_code(&self) -> ObjectCode;
+
+    /// Returns true if the object may be accessed with SDO complete access
+    ///
+    /// See [`ObjectAccess::supports_complete_access`]. The default implementation returns false.
+    fn supports_complete_access(&self) -> bool {
+        false
+    }
+}
+
+/// Get the size of the value stored in a sub object, from its size and data type
+///
+/// This is the default implementation of [`ObjectAccess::current_size`]
+fn stored_size<T: ObjectAccess + ?Sized>(obj: &T, sub: u8) -> Result<usize, AbortCode> {
+    const CHUNK_SIZE: usize = 8;
+
+    let size = obj.size(sub)?;
+    if obj.data_type(sub)?.is_str() {
+        // Look for first 0
+        let mut chunk = 0;
+        let mut buf = [0; CHUNK_SIZE];
+        while chunk < size / CHUNK_SIZE + 1 {
+            let offset = chunk * CHUNK_SIZE;
+            let bytes_to_read = (size - offset).min(CHUNK_SIZE);
+            obj.read(sub, offset, &mut buf[0..bytes_to_read])?;
+
+            if let Some(zero_pos) = buf[0..bytes_to_read].iter().position(|b| *b == 0) {
+                return Ok(zero_pos + chunk * CHUNK_SIZE);
+            }
+            chunk += 1;
+        }
+    }
+    // not a string type or no null-terminator was found
+    Ok(size)
 }
 
 // Implement ObjectAccess for any type that implements ProvidesSubObjects
@@ -254,6 +426,42 @@ impl<T: ProvidesSubObjects + Sync + Send> ObjectAccess for T {
         }
     }
 
+    fn read_into(
+        &self,
+        sub: u8,
+        offset: usize,
+        bufs: &mut [&mut [u8]],
+    ) -> Result<usize, AbortCode> {
+        // Look up the sub object once for all of the buffers
+        let Some((info, access)) = self.get_sub_object(sub) else {
+            return Err(AbortCode::NoSuchSubIndex);
+        };
+        if !info.access_type.is_readable() {
+            return Err(AbortCode::WriteOnly);
+        }
+        let mut total = 0;
+        for buf in bufs.iter_mut() {
+            let len = access.read(offset + total, buf)?;
+            total += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    fn upload_size(&self, sub: u8) -> Option<usize> {
+        self.get_sub_object(sub)
+            .and_then(|(_info, access)| access.current_size())
+    }
+
+    fn current_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        match self.upload_size(sub) {
+            Some(size) => Ok(size),
+            None => stored_size(self, sub),
+        }
+    }
+
     fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
         if let Some((info, access)) = self.get_sub_object(sub) {
             if info.access_type.is_writable() {
@@ -294,6 +502,34 @@ impl<T: ProvidesSubObjects + Sync + Send> ObjectAccess for T {
         }
     }
 
+    fn begin_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Some((info, access)) = self.get_sub_object(sub) {
+            if info.access_type.is_readable() {
+                access.begin_partial_read()
+            } else {
+                Err(AbortCode::WriteOnly)
+            }
+        } else {
+            Err(AbortCode::NoSuchSubIndex)
+        }
+    }
+
+    fn read_partial(&self, sub: u8, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if let Some((_, access)) = self.get_sub_object(sub) {
+            access.read_partial(buf)
+        } else {
+            Err(AbortCode::NoSuchSubIndex)
+        }
+    }
+
+    fn end_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Some((_, access)) = self.get_sub_object(sub) {
+            access.end_partial_read()
+        } else {
+            Err(AbortCode::NoSuchSubIndex)
+        }
+    }
+
     fn set_event_flag(&self, sub: u8) -> Result<(), AbortCode> {
         if let Some(flags) = self.flags() {
             flags.set_flag(sub);
@@ -311,12 +547,30 @@ impl<T: ProvidesSubObjects + Sync + Send> ObjectAccess for T {
         }
     }
 
+    fn next_event_flag(&self, from: u8) -> Option<u8> {
+        self.flags().and_then(|flags| flags.next_flag(from))
+    }
+
     fn clear_events(&self) {
         if let Some(flags) = self.flags() {
             flags.clear();
         }
     }
 
+    fn tpdo_event_mask(&self) -> u32 {
+        self.flags().map(|flags| flags.tpdo_mask()).unwrap_or(0)
+    }
+
+    fn supports_complete_access(&self) -> bool {
+        ProvidesSubObjects::supports_complete_access(self)
+    }
+
+    fn set_tpdo_event_mask(&self, mask: u32) {
+        if let Some(flags) = self.flags() {
+            flags.set_tpdo_mask(mask);
+        }
+    }
+
     fn object_code(&self) -> ObjectCode {
         self.object_code()
     }
@@ -330,6 +584,41 @@ impl<T: ProvidesSubObjects + Sync + Send> ObjectAccess for T {
     }
 }
 
+/// Provides [`ObjectAccess`] for a [`ProvidesSubObjects`] through dynamic dispatch
+///
+/// The `ObjectAccess` implementation for `ProvidesSubObjects` is generic, so a copy of it is compiled
+/// for every object type in the object dictionary. Objects placed in the object dictionary via a
+/// `SharedObjectAccess` all share a single copy instead, at the cost of an indirect call to
+/// [`get_sub_object`](ProvidesSubObjects::get_sub_object). This is used by the compact mode of
+/// `zencan-build`.
+#[allow(missing_debug_implementations)]
+pub struct SharedObjectAccess<'a>(&'a (dyn ProvidesSubObjects + Sync + Send));
+
+impl<'a> SharedObjectAccess<'a> {
+    /// Create a SharedObjectAccess for an object
+    pub const fn new(obj: &'a (dyn ProvidesSubObjects + Sync + Send)) -> Self {
+        Self(obj)
+    }
+}
+
+impl ProvidesSubObjects for SharedObjectAccess<'_> {
+    fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+        self.0.get_sub_object(sub)
+    }
+
+    fn flags(&self) -> Option<&dyn ObjectFlagAccess> {
+        self.0.flags()
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        self.0.object_code()
+    }
+
+    fn supports_complete_access(&self) -> bool {
+        self.0.supports_complete_access()
+    }
+}
+
 /// OD placeholder for an object which will have a handler registered at runtime
 #[allow(missing_debug_implementations)]
 pub struct CallbackObject<'a> {
@@ -369,6 +658,10 @@ impl ObjectAccess for CallbackObject<'_> {
         }
     }
 
+    fn upload_size(&self, sub: u8) -> Option<usize> {
+        self.obj.load().and_then(|obj| obj.upload_size(sub))
+    }
+
     fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
         if let Some(obj) = self.obj.load() {
             obj.write(sub, data)
@@ -377,6 +670,35 @@ impl ObjectAccess for CallbackObject<'_> {
         }
     }
 
+    fn read_into(
+        &self,
+        sub: u8,
+        offset: usize,
+        bufs: &mut [&mut [u8]],
+    ) -> Result<usize, AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.read_into(sub, offset, bufs)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn write_from(&self, sub: u8, data: &[&[u8]]) -> Result<(), AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.write_from(sub, data)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn begin_partial(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.begin_partial(sub)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
     fn write_partial(&self, sub: u8, buf: &[u8]) -> Result<(), AbortCode> {
         if let Some(obj) = self.obj.load() {
             obj.write_partial(sub, buf)
@@ -393,6 +715,30 @@ impl ObjectAccess for CallbackObject<'_> {
         }
     }
 
+    fn begin_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.begin_partial_read(sub)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn read_partial(&self, sub: u8, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.read_partial(sub, buf)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn end_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if let Some(obj) = self.obj.load() {
+            obj.end_partial_read(sub)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
     fn object_code(&self) -> ObjectCode {
         self.object_code
     }
@@ -434,3 +780,85 @@ pub fn find_object_entry<'a, 'b>(table: &'b [ODEntry<'a>], index: u16) -> Option
         .ok()
         .map(|i| &table[i])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_dict::ByteField;
+
+    struct TestObject {
+        field: ByteField<128>,
+    }
+
+    impl ProvidesSubObjects for TestObject {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                1 => Some((SubInfo::new_visible_str(128).rw_access(), &self.field)),
+                2 => Some((SubInfo::new_visible_str(128).wo_access(), &self.field)),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Record
+        }
+    }
+
+    fn make_object() -> TestObject {
+        let mut value = [0; 128];
+        value.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        TestObject {
+            field: ByteField::new(value),
+        }
+    }
+
+    #[test]
+    fn test_read_into() {
+        let object = make_object();
+        let mut a = [0; 3];
+        let mut b = [0; 4];
+        assert_eq!(7, object.read_into(1, 10, &mut [&mut a, &mut b]).unwrap());
+        assert_eq!([10, 11, 12], a);
+        assert_eq!([13, 14, 15, 16], b);
+
+        // Reading past the end returns a short count
+        let mut a = [0; 4];
+        let mut b = [0; 4];
+        assert_eq!(6, object.read_into(1, 122, &mut [&mut a, &mut b]).unwrap());
+        assert_eq!([122, 123, 124, 125], a);
+        assert_eq!([126, 127, 0, 0], b);
+
+        assert_eq!(
+            Err(AbortCode::WriteOnly),
+            object.read_into(2, 0, &mut [&mut a])
+        );
+        assert_eq!(
+            Err(AbortCode::NoSuchSubIndex),
+            object.read_into(3, 0, &mut [&mut a])
+        );
+    }
+
+    #[test]
+    fn test_write_from() {
+        let object = make_object();
+        object.write_from(1, &[&[1, 2], &[3]]).unwrap();
+        assert_eq!([1, 2, 3, 3], object.field.load()[..4]);
+
+        // Large values are written with a partial write
+        let a = [0xaa; 100];
+        let b = [0xbb; 28];
+        object.write_from(1, &[&a, &b]).unwrap();
+        let value = object.field.load();
+        assert_eq!(a, value[..100]);
+        assert_eq!(b, value[100..]);
+
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            object.write_from(1, &[&a, &a])
+        );
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            object.write_from(1, &[&[0; 129]])
+        );
+    }
+}
@@ -1,9 +1,15 @@
 //! Collection of generic fields which implement a sub-object
 
-use core::cell::UnsafeCell;
+use core::{cell::UnsafeCell, sync::atomic::Ordering};
+
+use portable_atomic::{AtomicBool, AtomicI16, AtomicI32, AtomicI8, AtomicU16, AtomicU32, AtomicU8};
 
 use zencan_common::{
-    i24, sdo::AbortCode, traits::ReadSize, u24, AtomicCell, TimeDifference, TimeOfDay,
+    i24,
+    sdo::AbortCode,
+    strings::{truncate_utf8, StringEncoding},
+    traits::ReadSize,
+    u24, AtomicCell, TimeDifference, TimeOfDay,
 };
 
 /// Allow transparent byte level access to a sub object
@@ -41,6 +47,20 @@ pub trait SubObjectAccess: Sync + Send {
     /// read_size and a subsequent call to read.
     fn read_size(&self) -> usize;
 
+    /// Return the exact number of bytes an upload of the sub object will return, if it is known
+    ///
+    /// This is called by the SDO server after `begin_partial_read`, if the object supports partial
+    /// reads, and before any data is read. Objects whose size changes at runtime, such as log
+    /// buffers, can implement it so that the size is reported to the client at the start of an
+    /// upload which is too large to be buffered. The upload then transfers exactly this many bytes,
+    /// and is aborted if fewer bytes can be read.
+    ///
+    /// The default implementation returns None, in which case the size is only reported for
+    /// uploads which fit in the SDO buffer.
+    fn current_size(&self) -> Option<usize> {
+        None
+    }
+
     /// Write data to the sub object
     ///
     /// For most objects, the length of data must match the size of the object exactly. However, for
@@ -102,47 +122,198 @@ pub trait SubObjectAccess: Sync + Send {
     fn end_partial(&self) -> Result<(), AbortCode> {
         Err(AbortCode::UnsupportedAccess)
     }
+
+    /// Begin a multi-part read from the object
+    ///
+    /// This is the counterpart to `begin_partial` for reads. Objects which support it can stream
+    /// their data sequentially, e.g. from external storage, instead of supporting random access
+    /// via `read`. When supported, SDO uploads use partial reads in preference to `read`.
+    ///
+    /// Partial reads are always performed according to the following sequence:
+    /// - One call to `begin_partial_read`
+    /// - Zero or more calls to `read_partial`
+    /// - One call to `end_partial_read`
+    ///
+    /// `end_partial_read` is called even when the transfer is aborted before all data is read.
+    ///
+    /// # Errors
+    ///
+    /// - [`AbortCode::UnsupportedAccess`] if the object does not support partial reads
+    /// - [`AbortCode::ResourceNotAvailable`] if the object cannot be read because of the
+    ///   application state.
+    fn begin_partial_read(&self) -> Result<(), AbortCode> {
+        Err(AbortCode::UnsupportedAccess)
+    }
+
+    /// Read the next part of multi-part data from the object
+    ///
+    /// The buffer should be filled completely, unless the end of the object data has been reached.
+    /// Returning fewer bytes than `buf.len()` indicates that no more data is available.
+    fn read_partial(&self, _buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Err(AbortCode::UnsupportedAccess)
+    }
+
+    /// Finish a multi-part read
+    fn end_partial_read(&self) -> Result<(), AbortCode> {
+        Err(AbortCode::UnsupportedAccess)
+    }
+}
+
+/// Storage for the value of a [`ScalarField`]
+///
+/// Types which fit in a native atomic (8, 16 and 32-bit values, including `f32` and `bool`) are
+/// stored in one, so that they can be accessed lock-free from any core or interrupt priority.
+/// Other types are stored in an [`AtomicCell`], which uses a critical section.
+pub trait ScalarStorage: Copy + Send {
+    /// The container which stores the value
+    type Cell: Send + Sync;
+
+    /// Create a new container holding `value`
+    fn new_cell(value: Self) -> Self::Cell;
+
+    /// Read the value from the container
+    fn load(cell: &Self::Cell) -> Self;
+
+    /// Write a new value into the container
+    fn store(cell: &Self::Cell, value: Self);
+}
+
+macro_rules! impl_atomic_storage {
+    ($rust_type: ty, $atomic: ty) => {
+        impl ScalarStorage for $rust_type {
+            type Cell = $atomic;
+
+            fn new_cell(value: Self) -> Self::Cell {
+                <$atomic>::new(value)
+            }
+
+            fn load(cell: &Self::Cell) -> Self {
+                cell.load(Ordering::Acquire)
+            }
+
+            fn store(cell: &Self::Cell, value: Self) {
+                cell.store(value, Ordering::Release)
+            }
+        }
+
+        impl ScalarField<$rust_type> {
+            /// Create a new ScalarField with the given value
+            pub const fn new(value: $rust_type) -> Self {
+                Self {
+                    value: <$atomic>::new(value),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_cell_storage {
+    ($rust_type: ty) => {
+        impl ScalarStorage for $rust_type {
+            type Cell = AtomicCell<$rust_type>;
+
+            fn new_cell(value: Self) -> Self::Cell {
+                AtomicCell::new(value)
+            }
+
+            fn load(cell: &Self::Cell) -> Self {
+                cell.load()
+            }
+
+            fn store(cell: &Self::Cell, value: Self) {
+                cell.store(value)
+            }
+        }
+
+        impl ScalarField<$rust_type> {
+            /// Create a new ScalarField with the given value
+            pub const fn new(value: $rust_type) -> Self {
+                Self {
+                    value: AtomicCell::new(value),
+                }
+            }
+        }
+    };
+}
+
+impl_atomic_storage!(u8, AtomicU8);
+impl_atomic_storage!(u16, AtomicU16);
+impl_atomic_storage!(u32, AtomicU32);
+impl_atomic_storage!(i8, AtomicI8);
+impl_atomic_storage!(i16, AtomicI16);
+impl_atomic_storage!(i32, AtomicI32);
+impl_atomic_storage!(bool, AtomicBool);
+impl_cell_storage!(u24);
+impl_cell_storage!(u64);
+impl_cell_storage!(i24);
+impl_cell_storage!(i64);
+impl_cell_storage!(f64);
+impl_cell_storage!(TimeOfDay);
+impl_cell_storage!(TimeDifference);
+
+// f32 is stored as its bit pattern
+impl ScalarStorage for f32 {
+    type Cell = AtomicU32;
+
+    fn new_cell(value: Self) -> Self::Cell {
+        AtomicU32::new(value.to_bits())
+    }
+
+    fn load(cell: &Self::Cell) -> Self {
+        f32::from_bits(cell.load(Ordering::Acquire))
+    }
+
+    fn store(cell: &Self::Cell, value: Self) {
+        cell.store(value.to_bits(), Ordering::Release)
+    }
+}
+
+impl ScalarField<f32> {
+    /// Create a new ScalarField with the given value
+    #[allow(unknown_lints, unnecessary_transmutes)]
+    pub const fn new(value: f32) -> Self {
+        // `f32::to_bits` is not const until rust 1.83
+        // SAFETY: f32 and u32 have the same size, and any bit pattern is a valid u32
+        let bits = unsafe { core::mem::transmute::<f32, u32>(value) };
+        Self {
+            value: AtomicU32::new(bits),
+        }
+    }
 }
 
 /// A sub object which contains a single scalar value of type T, which is a standard rust type
+///
+/// See [`ScalarStorage`] for how the value is stored.
 #[allow(missing_debug_implementations)]
-pub struct ScalarField<T: Copy> {
-    value: AtomicCell<T>,
+pub struct ScalarField<T: ScalarStorage> {
+    value: T::Cell,
 }
 
-impl<T: Send + Copy + PartialEq> ScalarField<T> {
+impl<T: ScalarStorage> ScalarField<T> {
     /// Atomically read the value of the field
     pub fn load(&self) -> T {
-        self.value.load()
+        T::load(&self.value)
     }
 
     /// Atomically store a new value into the field
     pub fn store(&self, value: T) {
-        self.value.store(value);
+        T::store(&self.value, value)
     }
 }
 
-impl<T: Copy + Default> Default for ScalarField<T> {
+impl<T: ScalarStorage + Default> Default for ScalarField<T> {
     fn default() -> Self {
         Self {
-            value: AtomicCell::default(),
+            value: T::new_cell(T::default()),
         }
     }
 }
 
 macro_rules! impl_scalar_field {
     ($rust_type: ty) => {
-        impl ScalarField<$rust_type> {
-            /// Create a new ScalarField with the given value
-            pub const fn new(value: $rust_type) -> Self {
-                Self {
-                    value: AtomicCell::new(value),
-                }
-            }
-        }
         impl SubObjectAccess for ScalarField<$rust_type> {
             fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-                let bytes = self.value.load().to_le_bytes();
+                let bytes = self.load().to_le_bytes();
                 if offset < bytes.len() {
                     let read_len = buf.len().min(bytes.len() - offset);
                     buf[0..read_len].copy_from_slice(&bytes[offset..offset + read_len]);
@@ -164,7 +335,7 @@ macro_rules! impl_scalar_field {
                         AbortCode::DataTypeMismatchLengthHigh
                     }
                 })?);
-                self.value.store(value);
+                self.store(value);
                 Ok(())
             }
         }
@@ -184,19 +355,10 @@ impl_scalar_field!(i64);
 impl_scalar_field!(f32);
 impl_scalar_field!(f64);
 
-impl ScalarField<bool> {
-    /// Create a new field
-    pub const fn new(value: bool) -> Self {
-        Self {
-            value: AtomicCell::new(value),
-        }
-    }
-}
-
 // bool doesn't support from_le_bytes so it needs a special implementation
 impl SubObjectAccess for ScalarField<bool> {
     fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-        let value = self.value.load();
+        let value = self.load();
         if offset != 0 || buf.len() > 1 {
             return Err(AbortCode::DataTypeMismatchLengthHigh);
         }
@@ -213,14 +375,14 @@ impl SubObjectAccess for ScalarField<bool> {
             return Err(AbortCode::DataTypeMismatchLengthHigh);
         }
         let value = data[0] != 0;
-        self.value.store(value);
+        self.store(value);
         Ok(())
     }
 }
 
 impl SubObjectAccess for ScalarField<TimeDifference> {
     fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-        let value = self.value.load();
+        let value = self.load();
         let bytes = value.to_le_bytes();
         if offset < bytes.len() {
             let read_len = buf.len().min(bytes.len() - offset);
@@ -243,23 +405,14 @@ impl SubObjectAccess for ScalarField<TimeDifference> {
                 AbortCode::DataTypeMismatchLengthHigh
             }
         })?);
-        self.value.store(value);
+        self.store(value);
         Ok(())
     }
 }
 
-impl ScalarField<TimeDifference> {
-    /// Create a new ScalarField with the given value
-    pub const fn new(value: TimeDifference) -> Self {
-        Self {
-            value: AtomicCell::new(value),
-        }
-    }
-}
-
 impl SubObjectAccess for ScalarField<TimeOfDay> {
     fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-        let value = self.value.load();
+        let value = self.load();
         let bytes = value.to_le_bytes();
         if offset < bytes.len() {
             let read_len = buf.len().min(bytes.len() - offset);
@@ -282,20 +435,11 @@ impl SubObjectAccess for ScalarField<TimeOfDay> {
                 AbortCode::DataTypeMismatchLengthHigh
             }
         })?);
-        self.value.store(value);
+        self.store(value);
         Ok(())
     }
 }
 
-impl ScalarField<TimeOfDay> {
-    /// Create a new ScalarField with the given value
-    pub const fn new(value: TimeOfDay) -> Self {
-        Self {
-            value: AtomicCell::new(value),
-        }
-    }
-}
-
 /// A sub object which contains a fixed-size byte array
 ///
 /// This is the data storage backing for all string types
@@ -410,13 +554,35 @@ impl<const N: usize> SubObjectAccess for ByteField<N> {
 /// A byte field which supports storing short values using null termination to indicate size
 ///
 /// This is here to support VisibleString and UnicodeString types.
+///
+/// When the `validate-strings` feature is enabled, values written over SDO are checked against the
+/// field's [`StringEncoding`], if it has one, and rejected with [`AbortCode::InvalidValue`] if they
+/// are not valid. The value is checked as it is written, so a rejected segmented write may leave
+/// part of the new value stored.
 #[allow(clippy::len_without_is_empty, missing_debug_implementations)]
-pub struct NullTermByteField<const N: usize>(ByteField<N>);
+pub struct NullTermByteField<const N: usize> {
+    field: ByteField<N>,
+    encoding: Option<StringEncoding>,
+}
 
 impl<const N: usize> NullTermByteField<N> {
     /// Create a new NullTermByteField with the provided value
     pub const fn new(value: [u8; N]) -> Self {
-        Self(ByteField::new(value))
+        Self {
+            field: ByteField::new(value),
+            encoding: None,
+        }
+    }
+
+    /// Set the encoding that values written to the field must use
+    pub const fn with_encoding(mut self, encoding: StringEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Get the encoding of the field, if it has one
+    pub fn encoding(&self) -> Option<StringEncoding> {
+        self.encoding
     }
 
     /// Return the size of the sub object
@@ -429,12 +595,12 @@ impl<const N: usize> NullTermByteField<N> {
     /// Note that this will return the entire array, including any invalid bytes after the null
     /// terminator.
     pub fn load(&self) -> [u8; N] {
-        self.0.load()
+        self.field.load()
     }
 
     /// Atomically store a new value to the object
     pub fn store(&self, value: [u8; N]) {
-        self.0.store(value);
+        self.field.store(value);
     }
 
     /// Store a str to the object
@@ -442,61 +608,110 @@ impl<const N: usize> NullTermByteField<N> {
     /// If the string is shorter than the object size, it will be stored with a null terminator
     /// If longer, an error will be returned.
     pub fn set_str(&self, value: &[u8]) -> Result<(), AbortCode> {
-        self.0.begin_partial()?;
-        self.0.write_partial(value)?;
+        self.field.begin_partial()?;
+        self.field.write_partial(value)?;
         if value.len() < N {
-            self.0.write_partial(&[0])?;
+            self.field.write_partial(&[0])?;
         }
         self.end_partial()?;
         Ok(())
     }
+
+    /// Store a str to the object, truncating it to fit if necessary
+    ///
+    /// Strings longer than the object are cut short at the last character boundary which fits, so
+    /// that a UTF-8 value is never split part way through a character. Returns the number of bytes
+    /// stored, excluding any null terminator.
+    ///
+    /// Like [`set_str`](Self::set_str), this returns [`AbortCode::InvalidValue`] if string
+    /// validation is enabled and the value is not valid for the field's encoding.
+    pub fn set_str_truncated(&self, value: &str) -> Result<usize, AbortCode> {
+        let value = truncate_utf8(value, N);
+        self.set_str(value.as_bytes())?;
+        Ok(value.len())
+    }
+
+    /// Check the value being written to the field
+    ///
+    /// `data` is the value written so far, and `complete` indicates whether it is the full value
+    fn check_encoding(&self, data: &[u8], complete: bool) -> Result<(), AbortCode> {
+        if !cfg!(feature = "validate-strings") {
+            return Ok(());
+        }
+        match self.encoding {
+            Some(encoding) if !encoding.is_valid(data, complete) => Err(AbortCode::InvalidValue),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the value written by a partial write in progress
+    fn check_partial_encoding(&self, complete: bool) -> Result<(), AbortCode> {
+        let written = self.field.write_offset.load().unwrap_or(0).min(N);
+        critical_section::with(|_| {
+            let bytes = unsafe { &*self.field.value.get() };
+            self.check_encoding(&bytes[..written], complete)
+        })
+    }
 }
 
 impl<const N: usize> Default for NullTermByteField<N> {
     fn default() -> Self {
-        Self(ByteField::default())
+        Self::new([0; N])
     }
 }
 
 impl<const N: usize> SubObjectAccess for NullTermByteField<N> {
     fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-        let size = self.0.read(offset, buf)?;
+        let size = self.field.read(offset, buf)?;
         let size = buf[0..size].iter().position(|b| *b == 0).unwrap_or(size);
         Ok(size)
     }
 
     fn read_size(&self) -> usize {
         critical_section::with(|_| {
-            let bytes = unsafe { &*self.0.value.get() };
+            let bytes = unsafe { &*self.field.value.get() };
             // Find the first 0, or if there are none the length is the full array
             bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len())
         })
     }
 
     fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
-        self.0.begin_partial()?;
-        self.0.write_partial(data)?;
+        if data.len() <= N {
+            self.check_encoding(data, true)?;
+        }
+        self.field.begin_partial()?;
+        self.field.write_partial(data)?;
         if data.len() < N {
-            self.0.write_partial(&[0])?;
+            self.field.write_partial(&[0])?;
         }
-        self.0.end_partial()?;
+        self.field.end_partial()?;
         Ok(())
     }
 
     fn begin_partial(&self) -> Result<(), AbortCode> {
-        self.0.begin_partial()
+        self.field.begin_partial()
     }
 
     fn write_partial(&self, data: &[u8]) -> Result<(), AbortCode> {
-        self.0.write_partial(data)
+        self.field.write_partial(data)?;
+        if self.encoding.is_some() {
+            self.check_partial_encoding(false)?;
+        }
+        Ok(())
     }
 
     fn end_partial(&self) -> Result<(), AbortCode> {
+        let result = if self.encoding.is_some() {
+            self.check_partial_encoding(true)
+        } else {
+            Ok(())
+        };
         // Null terminate if the length of data written is less than the sub object size
-        if self.0.write_offset.load().unwrap_or(0) < N {
-            self.0.write_partial(&[0])?;
+        if self.field.write_offset.load().unwrap_or(0) < N {
+            self.field.write_partial(&[0])?;
         }
-        self.0.end_partial()
+        self.field.end_partial()?;
+        result
     }
 }
 
@@ -565,6 +780,68 @@ impl<const N: usize> SubObjectAccess for ConstField<N> {
     }
 }
 
+/// Sub 0 of a resizable array, which holds the number of elements currently in use
+///
+/// Unlike the constant sub 0 of a fixed size array, the length can be written, to any value up to
+/// the capacity of the array. Larger values are rejected with [`AbortCode::ValueTooHigh`].
+#[allow(missing_debug_implementations)]
+pub struct ArrayLengthField {
+    len: ScalarField<u8>,
+    capacity: u8,
+}
+
+impl ArrayLengthField {
+    /// Create a new length field
+    ///
+    /// `len` is the initial length, and must be no larger than `capacity`
+    pub const fn new(len: u8, capacity: u8) -> Self {
+        assert!(len <= capacity);
+        Self {
+            len: ScalarField::<u8>::new(len),
+            capacity,
+        }
+    }
+
+    /// Get the current length of the array
+    pub fn load(&self) -> u8 {
+        self.len.load()
+    }
+
+    /// Set the current length of the array
+    ///
+    /// Returns [`AbortCode::ValueTooHigh`] if `len` is larger than the capacity
+    pub fn store(&self, len: u8) -> Result<(), AbortCode> {
+        if len > self.capacity {
+            return Err(AbortCode::ValueTooHigh);
+        }
+        self.len.store(len);
+        Ok(())
+    }
+
+    /// Get the capacity of the array, i.e. the largest allowed length
+    pub fn capacity(&self) -> u8 {
+        self.capacity
+    }
+}
+
+impl SubObjectAccess for ArrayLengthField {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        self.len.read(offset, buf)
+    }
+
+    fn read_size(&self) -> usize {
+        1
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
+        match data.len() {
+            0 => Err(AbortCode::DataTypeMismatchLengthLow),
+            1 => self.store(data[0]),
+            _ => Err(AbortCode::DataTypeMismatchLengthHigh),
+        }
+    }
+}
+
 /// A handler-backed sub-object for runtime registered implementation
 #[allow(missing_debug_implementations)]
 pub struct CallbackSubObject {
@@ -608,6 +885,12 @@ impl SubObjectAccess for CallbackSubObject {
         }
     }
 
+    fn current_size(&self) -> Option<usize> {
+        self.handler
+            .load()
+            .and_then(|handler| handler.current_size())
+    }
+
     fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
         if let Some(handler) = self.handler.load() {
             handler.write(data)
@@ -639,6 +922,30 @@ impl SubObjectAccess for CallbackSubObject {
             Err(AbortCode::ResourceNotAvailable)
         }
     }
+
+    fn begin_partial_read(&self) -> Result<(), AbortCode> {
+        if let Some(handler) = self.handler.load() {
+            handler.begin_partial_read()
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn read_partial(&self, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if let Some(handler) = self.handler.load() {
+            handler.read_partial(buf)
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
+
+    fn end_partial_read(&self) -> Result<(), AbortCode> {
+        if let Some(handler) = self.handler.load() {
+            handler.end_partial_read()
+        } else {
+            Err(AbortCode::ResourceNotAvailable)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -704,6 +1011,26 @@ mod tests {
         assert_eq!([0u8, 1, 2, 3, 4, 5, 6, 7, 0], buf)
     }
 
+    #[test]
+    fn test_array_length_field() {
+        let field = ArrayLengthField::new(4, 8);
+        assert_eq!(4, field.load());
+        assert_eq!(8, field.capacity());
+
+        field.write(&[8]).unwrap();
+        assert_eq!(8, field.load());
+        field.write(&[0]).unwrap();
+        assert_eq!(0, field.load());
+        assert_eq!(Err(AbortCode::ValueTooHigh), field.write(&[9]));
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            field.write(&[1, 0])
+        );
+        assert_eq!(Err(AbortCode::ValueTooHigh), field.store(9));
+        assert_eq!(0, field.load());
+        sub_read_test_helper(&field, &[0]);
+    }
+
     fn sub_read_test_helper(field: &dyn SubObjectAccess, expected_bytes: &[u8]) {
         let n = expected_bytes.len();
 
@@ -886,4 +1213,78 @@ mod tests {
         let field = ConstByteRefField::new(&[1, 2, 3, 4, 5]);
         sub_read_test_helper(&field, &[1, 2, 3, 4, 5]);
     }
+
+    #[test]
+    fn test_set_str_truncated() {
+        let field = NullTermByteField::new([0; 4]).with_encoding(StringEncoding::Unicode);
+        assert_eq!(Ok(4), field.set_str_truncated("ab°"));
+        assert_eq!(Ok(3), field.set_str_truncated("a°°"));
+        sub_read_test_helper(&field, "a°".as_bytes());
+        assert_eq!(Ok(4), field.set_str_truncated("abcde"));
+        sub_read_test_helper(&field, b"abcd");
+    }
+
+    #[cfg(feature = "validate-strings")]
+    #[test]
+    fn test_string_validation() {
+        let field = NullTermByteField::new([0; 8]).with_encoding(StringEncoding::Visible);
+        field.write(b"abc").unwrap();
+        assert_eq!(Err(AbortCode::InvalidValue), field.write(b"a\tb"));
+        sub_read_test_helper(&field, b"abc");
+
+        let field = NullTermByteField::new([0; 8]).with_encoding(StringEncoding::Unicode);
+        let value = "°C°C".as_bytes();
+        field.begin_partial().unwrap();
+        // A character may be split between partial writes
+        field.write_partial(&value[..1]).unwrap();
+        field.write_partial(&value[1..]).unwrap();
+        field.end_partial().unwrap();
+        sub_read_test_helper(&field, value);
+
+        field.begin_partial().unwrap();
+        field.write_partial(&value[..1]).unwrap();
+        assert_eq!(Err(AbortCode::InvalidValue), field.end_partial());
+        field.begin_partial().unwrap();
+        assert_eq!(Err(AbortCode::InvalidValue), field.write_partial(&[0xff]));
+        field.end_partial().ok();
+
+        // Fields without an encoding accept any value
+        NullTermByteField::new([0; 2]).write(&[0xff]).unwrap();
+    }
+
+    /// Write alternating values from one thread while others read, and check that every read
+    /// returns one of the written values in full
+    fn stress_test_helper<const N: usize>(field: &dyn SubObjectAccess, values: [[u8; N]; 2]) {
+        const ITERATIONS: usize = 20000;
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..ITERATIONS {
+                    field.write(&values[i % 2]).unwrap();
+                }
+            });
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    let mut buf = [0; N];
+                    for _ in 0..ITERATIONS {
+                        assert_eq!(N, field.read(0, &mut buf).unwrap());
+                        assert!(values.contains(&buf), "Torn read: {buf:?}");
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        stress_test_helper(&ScalarField::<u8>::new(0), [[0], [0xff]]);
+        stress_test_helper(&ScalarField::<u16>::new(0), [[0; 2], [0xff; 2]]);
+        stress_test_helper(&ScalarField::<u32>::new(0), [[0; 4], [0xff; 4]]);
+        stress_test_helper(&ScalarField::<u64>::new(0), [[0; 8], [0xff; 8]]);
+        // Fields start with one of the written values, as a reader may run before the writer
+        stress_test_helper(
+            &ScalarField::<f32>::new(1.5),
+            [1.5f32.to_le_bytes(), (-2.25f32).to_le_bytes()],
+        );
+        stress_test_helper(&ByteField::new([0x55; 32]), [[0x55; 32], [0xaa; 32]]);
+    }
 }
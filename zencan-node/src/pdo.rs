@@ -27,6 +27,17 @@
 //!     { index=0x2000, sub=1, size=32 },
 //! ]
 //!
+//! # Send TPDO2 on every 4th SYNC, starting with the SYNC whose counter is 3
+//! [pdos.tpdo.2]
+//! enabled = true
+//! cob_id = 0x300
+//! add_node_id = true
+//! transmission_type = 4
+//! sync_start = 3
+//! mappings = [
+//!     { index=0x2000, sub=2, size=32 },
+//! ]
+//!
 //! # Configure RPDO0 to receive on extended ID 0x5000
 //! [pdos.rpdo.0]
 //! enabled = true
@@ -39,12 +50,64 @@
 //!     { index = 0x2000, sub=2, size=32 },
 //! ]
 //! ```
+//!
+//! ## SYNC Start Value
+//!
+//! A TPDO with transmission type N (1 - 240) is sent on every Nth SYNC. When the SYNC producer
+//! includes a counter in its SYNC messages, the TPDO's SYNC start value (sub 6 of its communication
+//! object) selects the SYNC on which counting begins: the TPDO is first sent on the SYNC whose
+//! counter equals the start value, and then on every Nth SYNC after that. Giving nodes different
+//! start values staggers their transmissions, so that they do not all send on the same SYNC.
+//!
+//! A start value of 0 disables this, and the TPDO is first sent on the Nth SYNC after it becomes
+//! valid. If the counter wraps around without reaching the start value, e.g. because the start
+//! value is larger than the producer's counter overflow value, counting begins on the SYNC
+//! following the wraparound.
+//!
+//! ## Event Timer
+//!
+//! An event-driven TPDO (transmission type 254 or 255) with a non-zero event timer (sub 5 of its
+//! communication object, in milliseconds) is also sent whenever the timer expires. The timer
+//! restarts each time the TPDO is sent, so it is sent at least once per period, with or without
+//! events. The first period starts when the node enters the Operational state.
+//!
+//! ## Inhibit Time
+//!
+//! An event-driven TPDO with a non-zero inhibit time (sub 3 of its communication object, in units of
+//! 100us) is sent at most once per inhibit time. Events raised while the inhibit time is running are
+//! not dropped: they are combined, and the TPDO is sent once with the latest values of its mapped
+//! objects as soon as the inhibit time expires. The event timer is also held off until then.
+//!
+//! This gives objects which change faster than the inhibit time a fixed maximum update latency,
+//! provided the node is processed when the inhibit time expires. The application can use
+//! [`Node::time_until_tpdo_us`](crate::Node::time_until_tpdo_us) to schedule its next call to
+//! `process`.
+//!
+//! ## Configuration Changes
+//!
+//! PDOs may be reconfigured at runtime by writing their communication and mapping objects, e.g. by
+//! a master over SDO. Applications which interpret the data of a PDO based on its mappings can
+//! register the [`pdo_config_changed`](crate::Callbacks::pdo_config_changed) callback to be told
+//! when this happens, and read the new configuration with [`Pdo::mappings`].
+//!
+//! ## NMT States
+//!
+//! PDOs are only exchanged in the Operational state. RPDOs received in any other state are ignored,
+//! and when the node leaves the Operational state, TPDOs queued for transmission and RPDOs not yet
+//! applied are dropped.
+//!
+//! Objects may still signal events for event-driven TPDOs while the node is not operational. The
+//! node's [`InactiveEventPolicy`], set with
+//! [`Node::set_inactive_event_policy`](crate::Node::set_inactive_event_policy), selects whether
+//! these events are discarded, or sent with the latest object values when the node is started.
 
 use crate::{
+    abort_codes::{self, check_legacy_write_len, check_write_len},
     node_state::NmtStateAccess,
     object_dict::{
         find_object_entry, ConstField, ODEntry, ObjectAccess, ProvidesSubObjects, SubObjectAccess,
     },
+    verbose_log::verbose_info,
 };
 use zencan_common::{
     nmt::NmtState,
@@ -60,6 +123,40 @@ use zencan_common::{
 /// objects to a single PDO
 const N_MAPPING_PARAMS: usize = 8;
 
+/// Identifies whether a PDO is received or transmitted by the node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PdoKind {
+    /// A receive PDO
+    Rpdo,
+    /// A transmit PDO
+    Tpdo,
+}
+
+/// Selects what happens to TPDO events raised while the node is not Operational
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InactiveEventPolicy {
+    /// Events are kept, and each TPDO with a pending event is sent once when the node is started
+    #[default]
+    SendOnStart,
+    /// Events are discarded when the node is started, so only events raised while Operational are
+    /// sent
+    Discard,
+}
+
+/// The progress of a synchronous TPDO towards its first transmission
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SyncPhase {
+    /// Waiting for the SYNC start value
+    Waiting {
+        /// The counter of the last SYNC received while waiting, used to detect wraparound
+        last_count: Option<u8>,
+    },
+    /// Counting SYNCs since the last transmission
+    Started,
+}
+
 #[derive(Clone, Copy)]
 /// Data structure for storing a PDO object mapping
 struct MappingEntry<'a> {
@@ -78,6 +175,9 @@ pub struct PdoDefaults<'a> {
     cob_id: u32,
     flags: u8,
     transmission_type: u8,
+    sync_start: u8,
+    event_timer: u16,
+    inhibit_time: u16,
     mappings: &'a [u32],
 }
 
@@ -99,6 +199,9 @@ impl<'a> PdoDefaults<'a> {
         cob_id: 0,
         flags: 0,
         transmission_type: 0,
+        sync_start: 0,
+        event_timer: 0,
+        inhibit_time: 0,
         mappings: &[],
     };
 
@@ -131,10 +234,31 @@ impl<'a> PdoDefaults<'a> {
             cob_id,
             flags,
             transmission_type,
+            sync_start: 0,
+            event_timer: 0,
+            inhibit_time: 0,
             mappings,
         }
     }
 
+    /// Set the default SYNC start value
+    pub const fn with_sync_start(mut self, sync_start: u8) -> Self {
+        self.sync_start = sync_start;
+        self
+    }
+
+    /// Set the default event timer period, in milliseconds
+    pub const fn with_event_timer(mut self, event_timer: u16) -> Self {
+        self.event_timer = event_timer;
+        self
+    }
+
+    /// Set the default inhibit time, in units of 100us
+    pub const fn with_inhibit_time(mut self, inhibit_time: u16) -> Self {
+        self.inhibit_time = inhibit_time;
+        self
+    }
+
     pub const fn valid(&self) -> bool {
         self.flags & (1 << Self::VALID_FLAG) != 0
     }
@@ -191,8 +315,28 @@ pub struct Pdo<'a> {
     transmission_type: AtomicCell<u8>,
     /// Tracks the number of sync signals since this was last sent or received
     sync_counter: AtomicCell<u8>,
+    /// The SYNC counter value on which a synchronous TPDO begins counting, or 0 if unused
+    sync_start: AtomicCell<u8>,
+    /// Whether a synchronous TPDO is still waiting for the SYNC start value
+    sync_phase: AtomicCell<SyncPhase>,
+    /// The event timer period in ms, or 0 if unused
+    event_timer: AtomicCell<u16>,
+    /// The time at which the event timer next expires, or None if it has not been started
+    event_deadline_us: AtomicCell<Option<u64>>,
+    /// The inhibit time in units of 100us, or 0 if unused
+    inhibit_time: AtomicCell<u16>,
+    /// The time at which the inhibit time started by the last transmission expires
+    inhibit_end_us: AtomicCell<Option<u64>>,
+    /// Set when an event has been latched from the mapped objects, and cleared when the PDO is
+    /// sent
+    ///
+    /// Each PDO latches its own events, so that an object mapped into several TPDOs triggers all of
+    /// them, independent of when the object flags are cleared.
+    event_pending: AtomicCell<bool>,
     /// The last received data value for an RPDO, or ready to transmit data for a TPDO
     pub buffered_value: AtomicCell<Option<heapless::Vec<u8, 8>>>,
+    /// The receive timestamp of the last received message for an RPDO
+    rx_timestamp: AtomicCell<Option<u64>>,
     /// Indicates how many of the values in mapping_params are valid
     ///
     /// This represents sub0 for the mapping object
@@ -201,6 +345,9 @@ pub struct Pdo<'a> {
     ///
     /// These specify which objects are
     mapping_params: [AtomicCell<Option<MappingEntry<'a>>>; N_MAPPING_PARAMS],
+    /// Set when the communication or mapping parameters are written, and cleared when the change
+    /// is reported to the application
+    config_changed: AtomicCell<bool>,
     /// System default values for this PDO
     defaults: Option<&'a PdoDefaults<'a>>,
 }
@@ -214,9 +361,18 @@ impl<'a> Pdo<'a> {
         let rtr_disabled = AtomicCell::new(false);
         let transmission_type = AtomicCell::new(0);
         let sync_counter = AtomicCell::new(0);
+        let sync_start = AtomicCell::new(0);
+        let sync_phase = AtomicCell::new(SyncPhase::Waiting { last_count: None });
+        let event_timer = AtomicCell::new(0);
+        let event_deadline_us = AtomicCell::new(None);
+        let inhibit_time = AtomicCell::new(0);
+        let inhibit_end_us = AtomicCell::new(None);
+        let event_pending = AtomicCell::new(false);
         let buffered_value = AtomicCell::new(None);
+        let rx_timestamp = AtomicCell::new(None);
         let valid_maps = AtomicCell::new(0);
         let mapping_params = [const { AtomicCell::new(None) }; N_MAPPING_PARAMS];
+        let config_changed = AtomicCell::new(false);
         let defaults = None;
         Self {
             od,
@@ -227,9 +383,18 @@ impl<'a> Pdo<'a> {
             rtr_disabled,
             transmission_type,
             sync_counter,
+            sync_start,
+            sync_phase,
+            event_timer,
+            event_deadline_us,
+            inhibit_time,
+            inhibit_end_us,
+            event_pending,
             buffered_value,
+            rx_timestamp,
             valid_maps,
             mapping_params,
+            config_changed,
             defaults,
         }
     }
@@ -245,6 +410,18 @@ impl<'a> Pdo<'a> {
         pdo
     }
 
+    /// Get the receive timestamp of the most recently received message for this RPDO
+    ///
+    /// This is None if no message has been received, or if the receiver did not provide a
+    /// timestamp (see [`CanMessage::timestamp`](zencan_common::messages::CanMessage::timestamp)).
+    pub fn rx_timestamp(&self) -> Option<u64> {
+        self.rx_timestamp.load()
+    }
+
+    pub(crate) fn set_rx_timestamp(&self, timestamp: Option<u64>) {
+        self.rx_timestamp.store(timestamp);
+    }
+
     /// Set the valid bit
     pub fn set_valid(&self, value: bool) {
         self.valid.store(value);
@@ -265,6 +442,11 @@ impl<'a> Pdo<'a> {
         self.transmission_type.load()
     }
 
+    /// Returns true if the PDO uses a synchronous transmission type (0-240)
+    pub fn is_synchronous(&self) -> bool {
+        self.transmission_type() <= 240
+    }
+
     /// Get the COB ID used for transmission of this PDO
     pub fn cob_id(&self) -> CanId {
         self.cob_id.load().unwrap_or(self.default_cob_id())
@@ -283,10 +465,114 @@ impl<'a> Pdo<'a> {
         defaults.can_id(node_id)
     }
 
+    /// Get the SYNC start value
+    pub fn sync_start(&self) -> u8 {
+        self.sync_start.load()
+    }
+
+    /// Set the SYNC start value
+    ///
+    /// See the [module docs](self#sync-start-value)
+    pub fn set_sync_start(&self, value: u8) {
+        self.sync_start.store(value);
+        self.reset_sync();
+    }
+
+    /// Get the event timer period in milliseconds
+    pub fn event_timer(&self) -> u16 {
+        self.event_timer.load()
+    }
+
+    /// Set the event timer period in milliseconds, or 0 to disable it
+    ///
+    /// See the [module docs](self#event-timer)
+    pub fn set_event_timer(&self, value: u16) {
+        self.event_timer.store(value);
+        self.reset_event_timer();
+    }
+
+    /// Check if the event timer has expired
+    ///
+    /// The timer is started on the first call after it is reset
+    pub(crate) fn event_timer_expired(&self, now_us: u64) -> bool {
+        if self.event_timer.load() == 0 {
+            return false;
+        }
+        match self.event_deadline_us.load() {
+            Some(deadline) => now_us >= deadline,
+            None => {
+                self.restart_event_timer(now_us);
+                false
+            }
+        }
+    }
+
+    /// Start a new event timer period, e.g. after the PDO is sent
+    pub(crate) fn restart_event_timer(&self, now_us: u64) {
+        let period_us = self.event_timer.load() as u64 * 1000;
+        self.event_deadline_us.store(Some(now_us + period_us));
+    }
+
+    /// Stop the event timer, so that it starts again on the next check
+    pub(crate) fn reset_event_timer(&self) {
+        self.event_deadline_us.store(None);
+    }
+
+    /// Get the inhibit time in units of 100us
+    pub fn inhibit_time(&self) -> u16 {
+        self.inhibit_time.load()
+    }
+
+    /// Set the inhibit time in units of 100us, or 0 to disable it
+    ///
+    /// See the [module docs](self#inhibit-time)
+    pub fn set_inhibit_time(&self, value: u16) {
+        self.inhibit_time.store(value);
+        self.reset_inhibit();
+    }
+
+    /// Check if transmission is held off by the inhibit time
+    pub(crate) fn inhibited(&self, now_us: u64) -> bool {
+        self.inhibit_end_us.load().is_some_and(|end| now_us < end)
+    }
+
+    /// Start the inhibit time, after the PDO is sent
+    pub(crate) fn start_inhibit(&self, now_us: u64) {
+        let inhibit_us = self.inhibit_time.load() as u64 * 100;
+        self.inhibit_end_us
+            .store((inhibit_us != 0).then_some(now_us + inhibit_us));
+    }
+
+    /// Cancel a running inhibit time
+    pub(crate) fn reset_inhibit(&self) {
+        self.inhibit_end_us.store(None);
+    }
+
+    /// Get the time at which this TPDO is next due to be sent, if it is known
+    ///
+    /// This is the end of the inhibit time if an event is pending, or else the event timer
+    /// expiry. The time may be in the past, if the PDO is already due.
+    pub(crate) fn next_due_us(&self) -> Option<u64> {
+        if !self.valid() || self.transmission_type() < 254 {
+            return None;
+        }
+        let inhibit_end = self.inhibit_end_us.load().unwrap_or(0);
+        if self.event_pending.load() {
+            return Some(inhibit_end);
+        }
+        if self.event_timer.load() == 0 {
+            return None;
+        }
+        self.event_deadline_us
+            .load()
+            .map(|deadline| deadline.max(inhibit_end))
+    }
+
     /// This function should be called when a SYNC event occurs
     ///
-    /// It will return true if the PDO should be sent in response to the SYNC event
-    pub fn sync_update(&self) -> bool {
+    /// `count` is the counter value carried by the SYNC message, if any. It will return true if the
+    /// PDO should be sent in response to the SYNC event
+    pub fn sync_update(&self, count: Option<u8>) -> bool {
         if !self.valid.load() {
             return false;
         }
@@ -297,6 +583,27 @@ impl<'a> Pdo<'a> {
             // For now, send every sync
             true
         } else if transmission_type <= 240 {
+            if let SyncPhase::Waiting { last_count } = self.sync_phase.load() {
+                let start = self.sync_start.load();
+                match count {
+                    Some(count) if start != 0 => {
+                        let wrapped = last_count.is_some_and(|last| count <= last);
+                        if count != start && !wrapped {
+                            self.sync_phase.store(SyncPhase::Waiting {
+                                last_count: Some(count),
+                            });
+                            return false;
+                        }
+                        // This is the first SYNC, so the PDO is sent immediately
+                        self.sync_phase.store(SyncPhase::Started);
+                        self.sync_counter.store(0);
+                        return true;
+                    }
+                    // No start value is in use, so counting begins now
+                    _ => self.sync_phase.store(SyncPhase::Started),
+                }
+            }
+
             // Atomically update this PDO's sync counter. If it has
             // reached the transmit threshold ("transmission_type"),
             // then reset it to zero.
@@ -323,29 +630,156 @@ impl<'a> Pdo<'a> {
         }
     }
 
+    /// Get the objects currently mapped to the PDO, in the order they appear in its data
+    pub fn mappings(&self) -> heapless::Vec<PdoMapping, N_MAPPING_PARAMS> {
+        let valid_maps = (self.valid_maps.load() as usize).min(self.mapping_params.len());
+        self.mapping_params[..valid_maps]
+            .iter()
+            .map_while(|param| param.load())
+            .map(|param| PdoMapping {
+                index: param.object.index,
+                sub: param.sub,
+                size: param.length * 8,
+            })
+            .collect()
+    }
+
+    /// Record that the configuration was written, so that the application can be notified
+    fn mark_config_changed(&self) {
+        self.config_changed.store(true);
+    }
+
+    /// Read and clear the configuration changed flag
+    pub(crate) fn take_config_changed(&self) -> bool {
+        self.config_changed.take()
+    }
+
     /// Check mapped objects for TPDO event flag
+    ///
+    /// Each mapped object is scanned once, visiting only the sub objects which have their flag set,
+    /// so the cost depends on the number of events rather than on the size of the mapped objects.
     pub fn read_events(&self) -> bool {
         if !self.valid.load() {
             return false;
         }
 
-        for i in 0..self.mapping_params.len() {
-            let param = self.mapping_params[i].load();
-            if param.is_none() {
+        let valid_maps = (self.valid_maps.load() as usize).min(self.mapping_params.len());
+        let mut mappings = [None; N_MAPPING_PARAMS];
+        for (i, param) in self.mapping_params[..valid_maps].iter().enumerate() {
+            mappings[i] = param.load();
+            if mappings[i].is_none() {
                 break;
             }
-            let param = param.unwrap();
-            if param.object.data.read_event_flag(param.sub) {
-                return true;
+        }
+        let mappings = &mappings[..];
+        let is_mapped = |index: u16, sub: u8| {
+            mappings
+                .iter()
+                .flatten()
+                .any(|m| m.object.index == index && m.sub == sub)
+        };
+
+        for (i, param) in mappings.iter().enumerate() {
+            let Some(param) = param else {
+                break;
+            };
+            let index = param.object.index;
+            // Objects mapped more than once are only scanned at their first mapping
+            if mappings[..i]
+                .iter()
+                .flatten()
+                .any(|m| m.object.index == index)
+            {
+                continue;
+            }
+            let mut from = 0;
+            while let Some(sub) = param.object.data.next_event_flag(from) {
+                if is_mapped(index, sub) {
+                    return true;
+                }
+                match sub.checked_add(1) {
+                    Some(next) => from = next,
+                    None => break,
+                }
             }
         }
         false
     }
 
-    fn nmt_state(&self) -> NmtState {
+    pub(crate) fn nmt_state(&self) -> NmtState {
         self.nmt_state.nmt_state()
     }
 
+    /// Check if the transmission type or mapping of the PDO may be changed
+    ///
+    /// They can always be changed while the PDO is invalid. While it is valid, they can only be
+    /// changed in the PreOperational state, or in Bootup when the defaults are loaded (Bootup is
+    /// always a short-lived state).
+    fn check_config_writable(&self) -> Result<(), AbortCode> {
+        let nmt_state = self.nmt_state();
+        if self.valid() && nmt_state != NmtState::PreOperational && nmt_state != NmtState::Bootup {
+            verbose_info!(
+                "Rejected config change to valid PDO {:?} in state {:?}",
+                self.cob_id(),
+                nmt_state
+            );
+            return Err(abort_codes::DEVICE_STATE);
+        }
+        Ok(())
+    }
+
+    /// Change the valid bit, resetting the transmission state when it changes
+    ///
+    /// This allows a PDO to be disabled and re-enabled with a new configuration while the node is
+    /// operational, without a stale value or event from the old configuration being sent.
+    fn update_valid(&self, valid: bool) {
+        if self.valid.replace(valid) != valid {
+            self.reset_sync();
+            self.reset_event_timer();
+            self.reset_inhibit();
+            self.event_pending.store(false);
+            self.buffered_value.store(None);
+        }
+    }
+
+    /// Restart SYNC counting, so that the PDO waits for its SYNC start value again
+    fn reset_sync(&self) {
+        self.sync_counter.store(0);
+        self.sync_phase
+            .store(SyncPhase::Waiting { last_count: None });
+    }
+
+    /// Latch any events set on the mapped objects into this PDO's pending event flag
+    pub(crate) fn latch_events(&self) {
+        if self.read_events() {
+            self.event_pending.store(true);
+        }
+    }
+
+    /// Read and clear the pending event flag
+    pub(crate) fn take_event(&self) -> bool {
+        self.event_pending.take()
+    }
+
+    /// Drop a queued message, e.g. when PDO transmission stops
+    ///
+    /// If `keep_event` is set and an event-driven TPDO is dropped, its event is kept pending, so
+    /// that it is sent again with the latest values
+    pub(crate) fn drop_buffered_value(&self, keep_event: bool) {
+        if self.buffered_value.take().is_some() && keep_event && self.transmission_type() >= 254 {
+            self.event_pending.store(true);
+        }
+    }
+
+    /// Get the objects referenced by the valid mappings
+    pub(crate) fn mapped_objects(&self) -> impl Iterator<Item = &'a ODEntry<'a>> + '_ {
+        let valid_maps = (self.valid_maps.load() as usize).min(self.mapping_params.len());
+        self.mapping_params[..valid_maps]
+            .iter()
+            .map_while(|param| param.load())
+            .map(|param| param.object)
+    }
+
     pub(crate) fn clear_events(&self) {
         for i in 0..self.mapping_params.len() {
             let param = self.mapping_params[i].load();
@@ -376,13 +810,21 @@ impl<'a> Pdo<'a> {
             let data_to_write = &data[offset..offset + length];
             // validity of the mappings must be validated during write, so that error here is not
             // possible
-            param.object.data.write(param.sub, data_to_write).ok();
+            param
+                .object
+                .data
+                .write_from(param.sub, &[data_to_write])
+                .ok();
             offset += length;
         }
     }
 
-    pub(crate) fn send_pdo(&self) {
-        let mut data = [0u8; 8];
+    /// Queue the current value of the mapped objects for transmission
+    ///
+    /// Returns true if a previously queued value, which had not yet been transmitted, was dropped
+    pub(crate) fn send_pdo(&self) -> bool {
+        // Mapped values are read straight into the queued frame
+        let mut data = heapless::Vec::<u8, 8>::new();
         let mut offset = 0;
         let valid_maps = self.valid_maps.load() as usize;
         for (i, param) in self.mapping_params.iter().enumerate() {
@@ -397,24 +839,32 @@ impl<'a> Pdo<'a> {
             }
             let param = param.unwrap();
             let length = param.length as usize;
-            if offset + length > data.len() {
+            if data.resize(offset + length, 0).is_err() {
                 break;
             }
             // validity of the mappings must be validated during write, so that error here is not
             // possible
-
             param
                 .object
                 .data
-                .read(param.sub, 0, &mut data[offset..offset + length])
+                .read_into(param.sub, 0, &mut [&mut data[offset..]])
                 .ok();
             offset += length;
         }
         // If there is an old value here which has not been sent yet, replace it with the latest
         // Data will be sent by mbox in message handling thread.
-        // Unwrap safety: ensured above that data cannot be longer than 8 bytes
-        self.buffered_value
-            .store(Some(heapless::Vec::from_slice(&data[0..offset]).unwrap()));
+        self.buffered_value.replace(Some(data)).is_some()
+    }
+
+    /// Returns true if a value is waiting to be transmitted
+    pub(crate) fn has_buffered_value(&self) -> bool {
+        critical_section::with(|cs| {
+            let cell = self.buffered_value.borrow(cs);
+            let value = cell.take();
+            let pending = value.is_some();
+            cell.set(value);
+            pending
+        })
     }
 
     /// Lookup a PDO mapped object and create a MappingEntry if it is valid
@@ -423,9 +873,14 @@ impl<'a> Pdo<'a> {
     /// a reference to the mapped object for faster access when
     /// sending/receiving PDOs.
     ///
-    /// This function may fail if the mapped object doesn't exist, or if it is
-    /// too short.
-    fn try_create_mapping_entry(&self, mapping: PdoMapping) -> Result<MappingEntry<'a>, AbortCode> {
+    /// This function may fail if the mapped object doesn't exist, if it is
+    /// too short, or if its access type does not allow it to be used by a PDO of
+    /// the given direction.
+    fn try_create_mapping_entry(
+        &self,
+        mapping: PdoMapping,
+        direction: MappingDirection,
+    ) -> Result<MappingEntry<'a>, AbortCode> {
         let PdoMapping {
             index,
             sub,
@@ -438,6 +893,25 @@ impl<'a> Pdo<'a> {
         }
         let entry = find_object_entry(self.od, index).ok_or(AbortCode::NoSuchObject)?;
         let sub_info = entry.data.sub_info(sub)?;
+        // A TPDO reads its mapped objects, and an RPDO writes them
+        let access_ok = match direction {
+            MappingDirection::Any => true,
+            MappingDirection::Tpdo => sub_info.access_type.is_readable(),
+            MappingDirection::Rpdo => sub_info.access_type.is_writable(),
+        };
+        if !access_ok {
+            return Err(AbortCode::UnnallowedPdo);
+        }
+        if cfg!(feature = "strict-abort-codes") {
+            let mappable = match direction {
+                MappingDirection::Any => sub_info.pdo_mapping != PdoMappable::None,
+                MappingDirection::Tpdo => sub_info.pdo_mapping.supports_tpdo(),
+                MappingDirection::Rpdo => sub_info.pdo_mapping.supports_rpdo(),
+            };
+            if !mappable {
+                return Err(AbortCode::UnnallowedPdo);
+            }
+        }
         if sub_info.size < length as usize / 8 {
             return Err(AbortCode::IncompatibleParameter);
         }
@@ -448,6 +922,33 @@ impl<'a> Pdo<'a> {
         })
     }
 
+    /// Check that `count` mappings can be enabled
+    ///
+    /// The mappings must exist, and their total length must fit in a single CAN message.
+    fn check_mapping_count(&self, count: u8) -> Result<(), AbortCode> {
+        if count as usize > self.mapping_params.len() {
+            return Err(AbortCode::ValueTooHigh);
+        }
+        let mut total_len = 0;
+        for param in &self.mapping_params[..count as usize] {
+            match param.load() {
+                Some(entry) => total_len += entry.length as usize,
+                None => return Err(AbortCode::InvalidValue),
+            }
+        }
+        if total_len > 8 {
+            return Err(AbortCode::PdoTooLong);
+        }
+        Ok(())
+    }
+
+    /// Set the node ID used to compute the default COB ID
+    ///
+    /// A COB ID which has been written to the PDO is not changed, nor is any other configuration.
+    pub(crate) fn set_node_id(&self, node_id: NodeId) {
+        self.node_id.store(node_id);
+    }
+
     /// Initialize the PDO configuration with its default value
     pub fn init_defaults(&'a self, node_id: NodeId) {
         if self.defaults.is_none() {
@@ -456,11 +957,14 @@ impl<'a> Pdo<'a> {
         let defaults = self.defaults.unwrap();
 
         self.node_id.store(node_id);
+        self.event_pending.store(false);
         for (i, m) in defaults.mappings.iter().enumerate() {
             if i >= self.mapping_params.len() {
                 return;
             }
-            if let Ok(entry) = self.try_create_mapping_entry(PdoMapping::from_object_value(*m)) {
+            if let Ok(entry) = self
+                .try_create_mapping_entry(PdoMapping::from_object_value(*m), MappingDirection::Any)
+            {
                 self.mapping_params[i].store(Some(entry));
             }
         }
@@ -471,9 +975,27 @@ impl<'a> Pdo<'a> {
         self.cob_id.store(None);
         self.rtr_disabled.store(defaults.rtr_disabled());
         self.transmission_type.store(defaults.transmission_type);
+        self.sync_start.store(defaults.sync_start);
+        self.reset_sync();
+        self.event_timer.store(defaults.event_timer);
+        self.reset_event_timer();
+        self.inhibit_time.store(defaults.inhibit_time);
+        self.reset_inhibit();
     }
 }
 
+/// Copy the bytes of a PDO config value starting at `offset` into `buf`
+///
+/// Returns the number of bytes copied, which is 0 if offset is at or past the end of the value
+fn read_value_bytes(bytes: &[u8], offset: usize, buf: &mut [u8]) -> usize {
+    if offset >= bytes.len() {
+        return 0;
+    }
+    let read_len = buf.len().min(bytes.len() - offset);
+    buf[..read_len].copy_from_slice(&bytes[offset..offset + read_len]);
+    read_len
+}
+
 struct PdoCobSubObject<'a> {
     pdo: &'a Pdo<'a>,
 }
@@ -505,14 +1027,7 @@ impl SubObjectAccess for PdoCobSubObject<'_> {
             value |= 1 << 31;
         }
 
-        let bytes = value.to_le_bytes();
-        if offset < bytes.len() {
-            let read_len = buf.len().min(bytes.len() - offset);
-            buf[0..read_len].copy_from_slice(&bytes[offset..offset + read_len]);
-            Ok(read_len)
-        } else {
-            Ok(0)
-        }
+        Ok(read_value_bytes(&value.to_le_bytes(), offset, buf))
     }
 
     fn read_size(&self) -> usize {
@@ -520,32 +1035,40 @@ impl SubObjectAccess for PdoCobSubObject<'_> {
     }
 
     fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
-        // Changing PDO config is only allowed during PreOperational state, or Bootup when the
-        // defaults are loaded (Bootup is a always a short-lived state).
-        let nmt_state = self.pdo.nmt_state();
-        if nmt_state != NmtState::PreOperational && nmt_state != NmtState::Bootup {
-            return Err(AbortCode::GeneralError);
-        }
-        if data.len() < 4 {
-            Err(AbortCode::DataTypeMismatchLengthLow)
-        } else if data.len() > 4 {
-            Err(AbortCode::DataTypeMismatchLengthHigh)
+        // The COB-ID may be written in any NMT state, but while the PDO is valid, only the valid
+        // bit may be changed (CiA 301). Changes take effect immediately, so a PDO can be
+        // reconfigured while operational by invalidating it, changing the config, and then setting
+        // it valid again.
+        check_write_len(data, 4)?;
+        let value = u32::from_le_bytes(data.try_into().unwrap());
+        let not_valid = (value & (1 << 31)) != 0;
+        let no_rtr = (value & (1 << 30)) != 0;
+        let extended_id = (value & (1 << 29)) != 0;
+
+        let can_id = if extended_id {
+            CanId::Extended(value & 0x1FFFFFFF)
         } else {
-            let value = u32::from_le_bytes(data.try_into().unwrap());
-            let not_valid = (value & (1 << 31)) != 0;
-            let no_rtr = (value & (1 << 30)) != 0;
-            let extended_id = (value & (1 << 29)) != 0;
-
-            let can_id = if extended_id {
-                CanId::Extended(value & 0x1FFFFFFF)
-            } else {
-                CanId::Std((value & 0x7FF) as u16)
-            };
-            self.pdo.cob_id.store(Some(can_id));
-            self.pdo.valid.store(!not_valid);
-            self.pdo.rtr_disabled.store(no_rtr);
-            Ok(())
+            CanId::Std((value & 0x7FF) as u16)
+        };
+        if self.pdo.valid() && !not_valid {
+            if can_id != self.pdo.cob_id() {
+                verbose_info!(
+                    "Rejected COB ID change to {:?} for valid PDO {:?}",
+                    can_id,
+                    self.pdo.cob_id()
+                );
+                return Err(AbortCode::InvalidValue);
+            }
+            if no_rtr != self.pdo.rtr_disabled.load() {
+                verbose_info!("Rejected RTR change for valid PDO {:?}", can_id);
+                return Err(AbortCode::InvalidValue);
+            }
         }
+        self.pdo.cob_id.store(Some(can_id));
+        self.pdo.rtr_disabled.store(no_rtr);
+        self.pdo.update_valid(!not_valid);
+        self.pdo.mark_config_changed();
+        Ok(())
     }
 }
 
@@ -561,11 +1084,11 @@ impl<'a> PdoTransmissionTypeSubObject<'a> {
 
 impl SubObjectAccess for PdoTransmissionTypeSubObject<'_> {
     fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
-        if offset > 1 {
-            return Ok(0);
-        }
-        buf[0] = self.pdo.transmission_type();
-        Ok(1)
+        Ok(read_value_bytes(
+            &[self.pdo.transmission_type()],
+            offset,
+            buf,
+        ))
     }
 
     fn read_size(&self) -> usize {
@@ -573,18 +1096,108 @@ impl SubObjectAccess for PdoTransmissionTypeSubObject<'_> {
     }
 
     fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
-        // Changing PDO config is only allowed during PreOperational state, or Bootup when the
-        // defaults are loaded (Bootup is a always a short-lived state).
-        let nmt_state = self.pdo.nmt_state();
-        if nmt_state != NmtState::PreOperational && nmt_state != NmtState::Bootup {
-            return Err(AbortCode::GeneralError);
-        }
-        if data.is_empty() {
-            Err(AbortCode::DataTypeMismatchLengthLow)
-        } else {
-            self.pdo.set_transmission_type(data[0]);
-            Ok(())
+        self.pdo.check_config_writable()?;
+        check_write_len(data, 1)?;
+        self.pdo.set_transmission_type(data[0]);
+        self.pdo.mark_config_changed();
+        Ok(())
+    }
+}
+
+struct PdoSyncStartSubObject<'a> {
+    pdo: &'a Pdo<'a>,
+}
+
+impl<'a> PdoSyncStartSubObject<'a> {
+    pub const fn new(pdo: &'a Pdo<'a>) -> Self {
+        Self { pdo }
+    }
+}
+
+impl SubObjectAccess for PdoSyncStartSubObject<'_> {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Ok(read_value_bytes(&[self.pdo.sync_start()], offset, buf))
+    }
+
+    fn read_size(&self) -> usize {
+        1
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
+        self.pdo.check_config_writable()?;
+        check_write_len(data, 1)?;
+        if data[0] > 240 {
+            return Err(AbortCode::ValueTooHigh);
         }
+        self.pdo.set_sync_start(data[0]);
+        self.pdo.mark_config_changed();
+        Ok(())
+    }
+}
+
+struct PdoEventTimerSubObject<'a> {
+    pdo: &'a Pdo<'a>,
+}
+
+impl<'a> PdoEventTimerSubObject<'a> {
+    pub const fn new(pdo: &'a Pdo<'a>) -> Self {
+        Self { pdo }
+    }
+}
+
+impl SubObjectAccess for PdoEventTimerSubObject<'_> {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Ok(read_value_bytes(
+            &self.pdo.event_timer().to_le_bytes(),
+            offset,
+            buf,
+        ))
+    }
+
+    fn read_size(&self) -> usize {
+        2
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
+        // The event timer may be changed at any time, including while the PDO is valid
+        check_write_len(data, 2)?;
+        self.pdo
+            .set_event_timer(u16::from_le_bytes([data[0], data[1]]));
+        self.pdo.mark_config_changed();
+        Ok(())
+    }
+}
+
+struct PdoInhibitTimeSubObject<'a> {
+    pdo: &'a Pdo<'a>,
+}
+
+impl<'a> PdoInhibitTimeSubObject<'a> {
+    pub const fn new(pdo: &'a Pdo<'a>) -> Self {
+        Self { pdo }
+    }
+}
+
+impl SubObjectAccess for PdoInhibitTimeSubObject<'_> {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        Ok(read_value_bytes(
+            &self.pdo.inhibit_time().to_le_bytes(),
+            offset,
+            buf,
+        ))
+    }
+
+    fn read_size(&self) -> usize {
+        2
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), AbortCode> {
+        self.pdo.check_config_writable()?;
+        check_write_len(data, 2)?;
+        self.pdo
+            .set_inhibit_time(u16::from_le_bytes([data[0], data[1]]));
+        self.pdo.mark_config_changed();
+        Ok(())
     }
 }
 
@@ -593,23 +1206,48 @@ impl SubObjectAccess for PdoTransmissionTypeSubObject<'_> {
 pub struct PdoCommObject<'a> {
     cob: PdoCobSubObject<'a>,
     transmission_type: PdoTransmissionTypeSubObject<'a>,
+    /// The SYNC start value sub object, which only TPDOs have
+    sync_start: Option<PdoSyncStartSubObject<'a>>,
+    /// The inhibit time sub object, which only TPDOs have
+    inhibit_time: Option<PdoInhibitTimeSubObject<'a>>,
+    /// The event timer sub object, which only TPDOs have
+    event_timer: Option<PdoEventTimerSubObject<'a>>,
 }
 
 impl<'a> PdoCommObject<'a> {
-    /// Create a new PdoCommObject
+    /// Create a new PdoCommObject for an RPDO
     pub const fn new(pdo: &'a Pdo<'a>) -> Self {
         let cob = PdoCobSubObject::new(pdo);
         let transmission_type = PdoTransmissionTypeSubObject::new(pdo);
         Self {
             cob,
             transmission_type,
+            sync_start: None,
+            inhibit_time: None,
+            event_timer: None,
         }
     }
+
+    /// Create a new PdoCommObject for a TPDO
+    ///
+    /// This includes the inhibit time at sub 3, the event timer at sub 5, and the SYNC start value
+    /// at sub 6
+    pub const fn new_tpdo(pdo: &'a Pdo<'a>) -> Self {
+        let mut obj = Self::new(pdo);
+        obj.inhibit_time = Some(PdoInhibitTimeSubObject::new(pdo));
+        obj.event_timer = Some(PdoEventTimerSubObject::new(pdo));
+        obj.sync_start = Some(PdoSyncStartSubObject::new(pdo));
+        obj
+    }
 }
 
 impl ProvidesSubObjects for PdoCommObject<'_> {
     fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
         match sub {
+            0 if self.sync_start.is_some() => Some((
+                SubInfo::MAX_SUB_NUMBER,
+                const { &ConstField::new(6u8.to_le_bytes()) },
+            )),
             0 => Some((
                 SubInfo::MAX_SUB_NUMBER,
                 const { &ConstField::new(2u8.to_le_bytes()) },
@@ -624,6 +1262,24 @@ impl ProvidesSubObjects for PdoCommObject<'_> {
                 SubInfo::new_u8().rw_access().persist(true),
                 &self.transmission_type,
             )),
+            3 => self.inhibit_time.as_ref().map(|inhibit_time| {
+                (
+                    SubInfo::new_u16().rw_access().persist(true),
+                    inhibit_time as &dyn SubObjectAccess,
+                )
+            }),
+            5 => self.event_timer.as_ref().map(|event_timer| {
+                (
+                    SubInfo::new_u16().rw_access().persist(true),
+                    event_timer as &dyn SubObjectAccess,
+                )
+            }),
+            6 => self.sync_start.as_ref().map(|sync_start| {
+                (
+                    SubInfo::new_u8().rw_access().persist(true),
+                    sync_start as &dyn SubObjectAccess,
+                )
+            }),
             _ => None,
         }
     }
@@ -633,16 +1289,56 @@ impl ProvidesSubObjects for PdoCommObject<'_> {
     }
 }
 
+/// The type of PDO which a [`PdoMappingObject`] configures
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MappingDirection {
+    /// Mappings are not checked against the access type of the mapped object
+    Any,
+    /// Mapped objects must be readable
+    Tpdo,
+    /// Mapped objects must be writable
+    Rpdo,
+}
+
 /// Implements a PDO mapping config object for both TPDOs and RPDOs
 #[allow(missing_debug_implementations)]
 pub struct PdoMappingObject<'a> {
     pdo: &'a Pdo<'a>,
+    direction: MappingDirection,
 }
 
 impl<'a> PdoMappingObject<'a> {
     /// Create a new PdoMappingObject
+    ///
+    /// Mappings written to this object are not checked against the access type of the mapped
+    /// object. Use [`new_tpdo`](Self::new_tpdo) or [`new_rpdo`](Self::new_rpdo) to have them
+    /// checked.
     pub const fn new(pdo: &'a Pdo<'a>) -> Self {
-        Self { pdo }
+        Self {
+            pdo,
+            direction: MappingDirection::Any,
+        }
+    }
+
+    /// Create a new PdoMappingObject for a TPDO
+    ///
+    /// Write-only objects cannot be mapped, and are rejected with [`AbortCode::UnnallowedPdo`].
+    pub const fn new_tpdo(pdo: &'a Pdo<'a>) -> Self {
+        Self {
+            pdo,
+            direction: MappingDirection::Tpdo,
+        }
+    }
+
+    /// Create a new PdoMappingObject for an RPDO
+    ///
+    /// Read-only and const objects cannot be mapped, and are rejected with
+    /// [`AbortCode::UnnallowedPdo`].
+    pub const fn new_rpdo(pdo: &'a Pdo<'a>) -> Self {
+        Self {
+            pdo,
+            direction: MappingDirection::Rpdo,
+        }
     }
 }
 
@@ -683,25 +1379,37 @@ impl ObjectAccess for PdoMappingObject<'_> {
     }
 
     fn write(&self, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
-        // Changing PDO config is only allowed during PreOperational state, or Bootup when the
-        // defaults are loaded (Bootup is a always a short-lived state).
-        let nmt_state = self.pdo.nmt_state();
-        if nmt_state != NmtState::PreOperational && nmt_state != NmtState::Bootup {
-            return Err(AbortCode::GeneralError);
-        }
+        self.pdo.check_config_writable()?;
         if sub == 0 {
+            check_write_len(data, 1)?;
+            if cfg!(feature = "strict-abort-codes") {
+                self.pdo.check_mapping_count(data[0]).inspect_err(|&e| {
+                    verbose_info!("Rejected PDO mapping count {}: {:?}", data[0], e)
+                })?;
+            }
             self.pdo.valid_maps.store(data[0]);
+            self.pdo.mark_config_changed();
             Ok(())
         } else if sub <= self.pdo.mapping_params.len() as u8 {
-            if data.len() != 4 {
-                return Err(AbortCode::DataTypeMismatch);
-            }
+            check_legacy_write_len(data, 4)?;
             let value = u32::from_le_bytes(data.try_into().unwrap());
 
             let mapping = PdoMapping::from_object_value(value);
 
-            self.pdo.mapping_params[(sub - 1) as usize]
-                .store(Some(self.pdo.try_create_mapping_entry(mapping)?));
+            let entry = self
+                .pdo
+                .try_create_mapping_entry(mapping, self.direction)
+                .inspect_err(|&e| {
+                    verbose_info!(
+                        "Rejected PDO mapping of 0x{:x}sub{} ({} bits): {:?}",
+                        mapping.index,
+                        mapping.sub,
+                        mapping.size,
+                        e
+                    )
+                })?;
+            self.pdo.mapping_params[(sub - 1) as usize].store(Some(entry));
+            self.pdo.mark_config_changed();
             Ok(())
         } else {
             Err(AbortCode::NoSuchSubIndex)
@@ -748,7 +1456,10 @@ mod tests {
     impl ProvidesSubObjects for TestObject {
         fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
             match sub {
-                0 => Some((SubInfo::new_u32(), &self.value)),
+                0 => Some((
+                    SubInfo::new_u32().pdo_mapping(PdoMappable::Both),
+                    &self.value,
+                )),
                 _ => None,
             }
         }
@@ -759,8 +1470,9 @@ mod tests {
     }
 
     #[test]
-    /// Assert that attempts to update PDO comms or mapping parameters fail when in operational mode
-    pub fn test_changes_denied_while_operational() {
+    /// Assert that a valid PDO cannot be reconfigured while operational, but that it can be
+    /// invalidated, reconfigured, and re-enabled
+    pub fn test_changes_while_operational() {
         let object1000 = TestObject::default();
         let od = &[ODEntry {
             index: 0x1000,
@@ -775,19 +1487,398 @@ mod tests {
 
         // Setup initially
         mapping_obj
-            .write(1, &((0x1000 << 16) | 32 as u32).to_le_bytes())
+            .write(1, &((0x1000 << 16) | 32u32).to_le_bytes())
             .unwrap();
         mapping_obj.write(0, &[1]).unwrap();
-        comm_obj.write(1, &(1u32 << 31).to_le_bytes()).unwrap();
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+        assert!(pdo.valid());
 
         nmt_state.store(NmtState::Operational);
 
-        // Changing now should error
+        // Changing the config of the valid PDO should error
         let result = mapping_obj.write(1, &0u32.to_le_bytes());
-        assert_eq!(Err(AbortCode::GeneralError), result);
-        let result = comm_obj.write(1, &0u32.to_le_bytes());
-        assert_eq!(Err(AbortCode::GeneralError), result);
-        let result = comm_obj.write(2, &0u32.to_le_bytes());
-        assert_eq!(Err(AbortCode::GeneralError), result);
+        assert_eq!(Err(abort_codes::DEVICE_STATE), result);
+        let result = comm_obj.write(2, &[0]);
+        assert_eq!(Err(abort_codes::DEVICE_STATE), result);
+        let result = comm_obj.write(1, &0x182u32.to_le_bytes());
+        assert_eq!(Err(AbortCode::InvalidValue), result);
+        // The RTR bit can't be changed either
+        let result = comm_obj.write(1, &(0x181u32 | 1 << 30).to_le_bytes());
+        assert_eq!(Err(AbortCode::InvalidValue), result);
+        assert!(!pdo.rtr_disabled.load());
+        // Re-writing the same COB-ID is allowed
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+
+        // Invalidate the PDO, and queue a value which should be dropped
+        pdo.send_pdo();
+        comm_obj
+            .write(1, &(0x181u32 | 1 << 31).to_le_bytes())
+            .unwrap();
+        assert!(!pdo.valid());
+        assert!(pdo.buffered_value.take().is_none());
+
+        // Now it can be reconfigured and re-enabled
+        mapping_obj.write(0, &[0]).unwrap();
+        comm_obj.write(2, &[1]).unwrap();
+        comm_obj.write(1, &0x182u32.to_le_bytes()).unwrap();
+        assert!(pdo.valid());
+        assert_eq!(CanId::std(0x182), pdo.cob_id());
+        assert_eq!(1, pdo.transmission_type());
+    }
+
+    #[test]
+    fn test_mappings_and_config_changed() {
+        let object1000 = TestObject::default();
+        let od = &[ODEntry {
+            index: 0x1000,
+            data: &object1000,
+        }];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let pdo = Pdo::new(od, &nmt_state);
+        let comm_obj = PdoCommObject::new(&pdo);
+        let mapping_obj = PdoMappingObject::new(&pdo);
+        assert!(!pdo.take_config_changed());
+
+        mapping_obj
+            .write(1, &((0x1000 << 16) | 16u32).to_le_bytes())
+            .unwrap();
+        assert!(pdo.take_config_changed());
+        assert!(!pdo.take_config_changed());
+        // Mappings past the count are not in use
+        assert!(pdo.mappings().is_empty());
+        mapping_obj.write(0, &[1]).unwrap();
+        assert!(pdo.take_config_changed());
+        assert_eq!(
+            [PdoMapping {
+                index: 0x1000,
+                sub: 0,
+                size: 16
+            }],
+            pdo.mappings().as_slice()
+        );
+
+        // Rejected writes are not changes
+        assert!(mapping_obj.write(2, &0x20000020u32.to_le_bytes()).is_err());
+        assert!(!pdo.take_config_changed());
+        comm_obj.write(2, &[1]).unwrap();
+        assert!(pdo.take_config_changed());
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+        assert!(pdo.take_config_changed());
+    }
+
+    #[test]
+    fn test_sync_start_value() {
+        let od = &[];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let pdo = Pdo::new(od, &nmt_state);
+        let comm_obj = PdoCommObject::new_tpdo(&pdo);
+        // RPDOs have no SYNC start value
+        assert_eq!(
+            Err(AbortCode::NoSuchSubIndex),
+            PdoCommObject::new(&pdo).write(6, &[1])
+        );
+
+        comm_obj.write(2, &[3]).unwrap();
+        assert_eq!(Err(AbortCode::ValueTooHigh), comm_obj.write(6, &[241]));
+        comm_obj.write(6, &[2]).unwrap();
+        assert_eq!(2, comm_obj.read_u8(6).unwrap());
+        // Reads at an offset past the one byte value return no data
+        let mut buf = [0xFF; 2];
+        assert_eq!(Ok(0), comm_obj.read(6, 1, &mut buf));
+        assert_eq!(Ok(0), comm_obj.read(6, 1, &mut []));
+        assert_eq!([0xFF; 2], buf);
+        // Multi-byte values can be read from an offset
+        comm_obj.write(5, &0x1234u16.to_le_bytes()).unwrap();
+        assert_eq!(Ok(1), comm_obj.read(5, 1, &mut buf));
+        assert_eq!(0x12, buf[0]);
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+
+        // Sent on the SYNC with counter 2, and then every 3rd SYNC
+        let sent: Vec<bool> = (1..=8).map(|count| pdo.sync_update(Some(count))).collect();
+        assert_eq!(
+            vec![false, true, false, false, true, false, false, true],
+            sent
+        );
+
+        // Without a counter in the SYNC, counting starts immediately
+        comm_obj
+            .write(1, &(0x181u32 | 1 << 31).to_le_bytes())
+            .unwrap();
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+        let sent: Vec<bool> = (0..3).map(|_| pdo.sync_update(None)).collect();
+        assert_eq!(vec![false, false, true], sent);
+
+        // A start value which is never reached starts counting after the counter wraps around
+        comm_obj
+            .write(1, &(0x181u32 | 1 << 31).to_le_bytes())
+            .unwrap();
+        comm_obj.write(6, &[10]).unwrap();
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+        let sent: Vec<bool> = [3, 4, 5, 1, 2, 3, 4]
+            .into_iter()
+            .map(|count| pdo.sync_update(Some(count)))
+            .collect();
+        assert_eq!(vec![false, false, false, true, false, false, true], sent);
+    }
+
+    #[test]
+    /// Assert that writes with the wrong length are rejected with a length specific abort code
+    pub fn test_config_write_lengths() {
+        let od = &[];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let pdo = Pdo::new(od, &nmt_state);
+
+        let comm_obj = PdoCommObject::new(&pdo);
+        let mapping_obj = PdoMappingObject::new(&pdo);
+
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthLow),
+            mapping_obj.write(0, &[])
+        );
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            mapping_obj.write(0, &[0, 0])
+        );
+        // Mapping entries report a generic mismatch, unless strict abort codes are enabled
+        let mapping_too_short = if cfg!(feature = "strict-abort-codes") {
+            AbortCode::DataTypeMismatchLengthLow
+        } else {
+            AbortCode::DataTypeMismatch
+        };
+        assert_eq!(Err(mapping_too_short), mapping_obj.write(1, &[0; 3]));
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            comm_obj.write(2, &[0; 2])
+        );
+    }
+
+    #[cfg(feature = "strict-abort-codes")]
+    #[test]
+    /// Assert that invalid mappings are rejected with the CiA 301 abort codes
+    pub fn test_strict_mapping_checks() {
+        #[derive(Default)]
+        struct UnmappableObject {
+            value: ScalarField<u32>,
+        }
+
+        impl ProvidesSubObjects for UnmappableObject {
+            fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+                match sub {
+                    0 => Some((SubInfo::new_u32(), &self.value)),
+                    _ => None,
+                }
+            }
+
+            fn object_code(&self) -> ObjectCode {
+                ObjectCode::Var
+            }
+        }
+
+        let object1000 = TestObject::default();
+        let object1001 = UnmappableObject::default();
+        let od = &[
+            ODEntry {
+                index: 0x1000,
+                data: &object1000,
+            },
+            ODEntry {
+                index: 0x1001,
+                data: &object1001,
+            },
+        ];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let pdo = Pdo::new(od, &nmt_state);
+        let mapping_obj = PdoMappingObject::new(&pdo);
+
+        assert_eq!(
+            Err(AbortCode::UnnallowedPdo),
+            mapping_obj.write(1, &((0x1001 << 16) | 32u32).to_le_bytes())
+        );
+
+        for sub in 1..=3 {
+            mapping_obj
+                .write(sub, &((0x1000 << 16) | 32u32).to_le_bytes())
+                .unwrap();
+        }
+        mapping_obj.write(0, &[2]).unwrap();
+        assert_eq!(Err(AbortCode::PdoTooLong), mapping_obj.write(0, &[3]));
+        assert_eq!(Err(AbortCode::InvalidValue), mapping_obj.write(0, &[4]));
+        assert_eq!(
+            Err(AbortCode::ValueTooHigh),
+            mapping_obj.write(0, &[N_MAPPING_PARAMS as u8 + 1])
+        );
+    }
+
+    #[test]
+    /// Assert that TPDOs reject write-only objects, and RPDOs reject read-only and const objects
+    pub fn test_mapping_access_types() {
+        #[derive(Default)]
+        struct AccessObject {
+            wo: ScalarField<u32>,
+            ro: ScalarField<u32>,
+            constant: ScalarField<u32>,
+            rw: ScalarField<u32>,
+        }
+
+        impl ProvidesSubObjects for AccessObject {
+            fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+                let info = SubInfo::new_u32().pdo_mapping(PdoMappable::Both);
+                match sub {
+                    1 => Some((info.wo_access(), &self.wo)),
+                    2 => Some((info.ro_access(), &self.ro)),
+                    3 => Some((info.const_access(), &self.constant)),
+                    4 => Some((info.rw_access(), &self.rw)),
+                    _ => None,
+                }
+            }
+
+            fn object_code(&self) -> ObjectCode {
+                ObjectCode::Record
+            }
+        }
+
+        let object1000 = AccessObject::default();
+        let od = &[ODEntry {
+            index: 0x1000,
+            data: &object1000,
+        }];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let tpdo = Pdo::new(od, &nmt_state);
+        let rpdo = Pdo::new(od, &nmt_state);
+        let tpdo_mapping = PdoMappingObject::new_tpdo(&tpdo);
+        let rpdo_mapping = PdoMappingObject::new_rpdo(&rpdo);
+        let mapping = |sub: u32| ((0x1000 << 16) | (sub << 8) | 32u32).to_le_bytes();
+
+        assert_eq!(
+            Err(AbortCode::UnnallowedPdo),
+            tpdo_mapping.write(1, &mapping(1))
+        );
+        for sub in 2..=4 {
+            tpdo_mapping.write(1, &mapping(sub)).unwrap();
+        }
+
+        for sub in 2..=3 {
+            assert_eq!(
+                Err(AbortCode::UnnallowedPdo),
+                rpdo_mapping.write(1, &mapping(sub))
+            );
+        }
+        rpdo_mapping.write(1, &mapping(1)).unwrap();
+        rpdo_mapping.write(1, &mapping(4)).unwrap();
+    }
+
+    #[test]
+    /// Assert that the TPDO event scan only visits the sub objects with their flag set
+    fn test_read_events_visits_flagged_subs() {
+        use crate::object_dict::ObjectFlagAccess;
+        use core::sync::atomic::Ordering;
+        use portable_atomic::AtomicU32;
+
+        /// Event flags which record the sub objects returned by `next_flag`
+        #[derive(Default)]
+        struct RecordingFlags {
+            flags: AtomicU32,
+            visited: AtomicU32,
+            get_flag_calls: AtomicU32,
+        }
+
+        impl ObjectFlagAccess for RecordingFlags {
+            fn set_flag(&self, sub: u8) {
+                self.flags.fetch_or(1 << sub, Ordering::Relaxed);
+            }
+
+            fn get_flag(&self, sub: u8) -> bool {
+                self.get_flag_calls.fetch_add(1, Ordering::Relaxed);
+                self.flags.load(Ordering::Relaxed) & (1 << sub) != 0
+            }
+
+            fn next_flag(&self, from: u8) -> Option<u8> {
+                let flags = self.flags.load(Ordering::Relaxed) & (u32::MAX << from);
+                if flags == 0 {
+                    return None;
+                }
+                let sub = flags.trailing_zeros() as u8;
+                self.visited.fetch_or(1 << sub, Ordering::Relaxed);
+                Some(sub)
+            }
+
+            fn clear(&self) {
+                self.flags.store(0, Ordering::Relaxed);
+                self.visited.store(0, Ordering::Relaxed);
+            }
+
+            fn tpdo_mask(&self) -> u32 {
+                0
+            }
+
+            fn set_tpdo_mask(&self, _mask: u32) {}
+        }
+
+        #[derive(Default)]
+        struct FlaggedObject {
+            values: [ScalarField<u32>; 8],
+            flags: RecordingFlags,
+        }
+
+        impl ProvidesSubObjects for FlaggedObject {
+            fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+                let info = SubInfo::new_u32().pdo_mapping(PdoMappable::Both);
+                self.values
+                    .get(sub.checked_sub(1)? as usize)
+                    .map(|value| (info, value as &dyn SubObjectAccess))
+            }
+
+            fn flags(&self) -> Option<&dyn ObjectFlagAccess> {
+                Some(&self.flags)
+            }
+
+            fn object_code(&self) -> ObjectCode {
+                ObjectCode::Record
+            }
+        }
+
+        let object2000 = FlaggedObject::default();
+        let od = &[ODEntry {
+            index: 0x2000,
+            data: &object2000,
+        }];
+        let nmt_state = AtomicCell::new(NmtState::PreOperational);
+        let pdo = Pdo::new(od, &nmt_state);
+        let comm_obj = PdoCommObject::new(&pdo);
+        let mapping_obj = PdoMappingObject::new_tpdo(&pdo);
+        let mapping = |sub: u32| ((0x2000 << 16) | (sub << 8) | 32u32).to_le_bytes();
+
+        // Map subs 2 and 5 of the object
+        mapping_obj.write(1, &mapping(2)).unwrap();
+        mapping_obj.write(2, &mapping(5)).unwrap();
+        mapping_obj.write(0, &[2]).unwrap();
+        comm_obj.write(1, &0x181u32.to_le_bytes()).unwrap();
+
+        // No flags set: nothing is visited
+        assert!(!pdo.read_events());
+        assert_eq!(0, object2000.flags.visited.load(Ordering::Relaxed));
+
+        // Only unmapped subs are flagged: those are visited, and nothing else
+        object2000.flags.set_flag(3);
+        object2000.flags.set_flag(7);
+        assert!(!pdo.read_events());
+        assert_eq!(
+            (1 << 3) | (1 << 7),
+            object2000.flags.visited.load(Ordering::Relaxed)
+        );
+
+        // A mapped sub is flagged: the scan stops there
+        object2000.flags.clear();
+        object2000.flags.set_flag(1);
+        object2000.flags.set_flag(5);
+        object2000.flags.set_flag(8);
+        assert!(pdo.read_events());
+        assert_eq!(
+            (1 << 1) | (1 << 5),
+            object2000.flags.visited.load(Ordering::Relaxed)
+        );
+
+        // The flags are never checked one sub at a time
+        assert_eq!(0, object2000.flags.get_flag_calls.load(Ordering::Relaxed));
     }
 }
@@ -62,7 +62,7 @@ async fn serialize_object(obj: &ODEntry<'_>, sub: u8, reg: &RefCell<u8>) {
         // can be modified on a different thread than `Node::process()` is called. Fixing it
         // requires an object locking mechanism, which may be worth considering in the future.
         obj.data.read(sub, read_pos, &mut buf).unwrap();
-        let copy_len = data_size as usize - read_pos;
+        let copy_len = (data_size as usize - read_pos).min(CHUNK_SIZE);
         read_pos += copy_len;
         write_bytes(&buf[0..copy_len], reg).await;
         if read_pos >= data_size as usize {
@@ -206,7 +206,8 @@ impl<'a> PersistNodeRef<'a> {
 
         match NodeType::from_byte(data[0]) {
             NodeType::ObjectValue => {
-                if data.len() < 5 {
+                // The value may be empty, e.g. for an empty domain
+                if data.len() < 4 {
                     return Err(PersistReadError::NodeLengthShort);
                 }
                 Ok(Self::ObjectValue(ObjectValue {
@@ -438,4 +439,41 @@ mod tests {
         );
         assert_eq!(deser.next(), None);
     }
+
+    #[test]
+    fn test_serialize_large_value() {
+        use crate::config_manager::{DcfStore, DcfStoreObject};
+
+        fn make_od() -> &'static [ODEntry<'static>] {
+            let stores = Box::leak(Box::new([DcfStore::<64>::new(2), DcfStore::<64>::new(3)]));
+            let object = Box::leak(Box::new(DcfStoreObject::new(stores)));
+            Box::leak(Box::new([ODEntry {
+                index: 0x1F22,
+                data: object,
+            }]))
+        }
+
+        // Values longer than the serializer's internal chunk size are written in several chunks,
+        // and empty values do not end the restore
+        let value: Vec<u8> = (0..50).collect();
+        let od = make_od();
+        od[0].data.write(3, &value).unwrap();
+
+        let data = RefCell::new(Vec::new());
+        serialize(od, &|reader, size| {
+            let mut buf = vec![0; size];
+            assert_eq!(size, reader.read(&mut buf).unwrap());
+            data.borrow_mut().extend_from_slice(&buf);
+        });
+        let data = data.take();
+        assert_eq!(62, data.len());
+
+        let restored_od = make_od();
+        restored_od[0].data.write(2, &[1]).unwrap();
+        restore_stored_objects(restored_od, &data);
+        assert_eq!(0, restored_od[0].data.read_size(2).unwrap());
+        let mut buf = [0; 64];
+        assert_eq!(50, restored_od[0].data.read(3, 0, &mut buf).unwrap());
+        assert_eq!(value, buf[..50]);
+    }
 }
@@ -84,27 +84,64 @@ where
         })
     }
 
+    /// Get the number of items currently in the queue
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| {
+            let buffer = self.buffer.borrow_ref(cs);
+            buffer.iter().filter(|loc| !loc.is_empty()).count()
+        })
+    }
+
+    /// Returns true if there are no items in the queue
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the maximum number of items the queue can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
     /// Remove the queue item with the lowest priority value
     ///
     /// Returns: The item with the lowest priority value in the queue, or None if the queue is empty
     pub fn pop(&self) -> Option<T> {
         critical_section::with(|cs| {
-            let mut min_prio = u32::MAX;
-            let mut selected_index = None;
             let mut buffer = self.buffer.borrow_ref_mut(cs);
-            // Traverse the list and find the lowest priority
-            for (i, loc) in buffer.iter().enumerate() {
-                if let Some(prio) = loc.prio() {
-                    if prio < min_prio {
-                        min_prio = prio;
-                        selected_index = Some(i);
-                    }
-                }
-            }
+            Self::min_index(&buffer[..]).map(|i| buffer[i].take())?
+        })
+    }
+
+    /// Get the queue item with the lowest priority value without removing it
+    pub fn peek(&self) -> Option<T> {
+        critical_section::with(|cs| {
+            let buffer = self.buffer.borrow_ref(cs);
+            Self::min_index(&buffer[..]).map(|i| buffer[i].value())?
+        })
+    }
 
-            selected_index.map(|i| buffer[i].take())?
+    /// Get the priority value of the item which will be returned by the next call to `pop`
+    pub fn peek_prio(&self) -> Option<u32> {
+        critical_section::with(|cs| {
+            let buffer = self.buffer.borrow_ref(cs);
+            Self::min_index(&buffer[..]).and_then(|i| buffer[i].prio())
         })
     }
+
+    fn min_index(buffer: &[Prio<T>]) -> Option<usize> {
+        let mut min_prio = u32::MAX;
+        let mut selected_index = None;
+        // Traverse the list and find the lowest priority
+        for (i, loc) in buffer.iter().enumerate() {
+            if let Some(prio) = loc.prio() {
+                if prio < min_prio {
+                    min_prio = prio;
+                    selected_index = Some(i);
+                }
+            }
+        }
+        selected_index
+    }
 }
 
 #[cfg(test)]
@@ -120,11 +157,17 @@ mod test {
         queue.push(10, 1).unwrap();
 
         // Now the queue is full
+        assert_eq!(4, queue.len());
         assert_eq!(Err(12), queue.push(100, 12));
 
+        assert_eq!(Some(0), queue.peek());
+        assert_eq!(4, queue.len());
+
         assert_eq!(Some(0), queue.pop());
         assert_eq!(Some(1), queue.pop());
         assert_eq!(Some(2), queue.pop());
         assert_eq!(Some(3), queue.pop());
+        assert!(queue.is_empty());
+        assert_eq!(4, queue.capacity());
     }
 }
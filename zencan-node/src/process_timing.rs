@@ -0,0 +1,148 @@
+//! Execution time measurement for [`Node::process`](crate::Node::process)
+//!
+//! On a slow MCU, it can be important to know how long a call to process may take, e.g. to verify
+//! that it fits in the time slot it is given. When the `process-timing` feature is enabled, the
+//! node can measure the time spent in each of its subsystems, and track the worst case seen for
+//! each.
+//!
+//! The node has no way to read the time on its own, so measurement starts once the application
+//! provides a clock with [`Node::set_timing_clock`](crate::Node::set_timing_clock). The clock must
+//! be monotonic and count microseconds; it is read a few times on every call to process, so it
+//! should be cheap, e.g. a free running hardware timer. The
+//! [`AsyncNode`](crate::async_node::AsyncNode) sets it up automatically using `embassy-time`.
+//!
+//! ```ignore
+//! node.set_timing_clock(Some(|| TIMER.now_us()));
+//! // Count calls which take longer than 200us
+//! node.set_process_budget_us(Some(200));
+//!
+//! // Later...
+//! let timing = node.process_timing();
+//! info!("SDO worst case: {}us", timing.stage(ProcessStage::Sdo).worst_us);
+//! info!("Budget overruns: {}", timing.budget_overruns());
+//! ```
+
+/// A monotonic clock, returning the time in microseconds
+pub type TimingClockFn = fn() -> u64;
+
+/// The parts of [`Node::process`](crate::Node::process) which are measured separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProcessStage {
+    /// The SDO server
+    Sdo,
+    /// Handling of NMT commands
+    Nmt,
+    /// The LSS slave, including the node config storage callback
+    Lss,
+    /// Scanning TPDOs for transmission, and storing received RPDOs
+    Pdo,
+    /// The whole call to process
+    Total,
+}
+
+impl ProcessStage {
+    /// All stages
+    pub const ALL: [ProcessStage; 5] = [
+        ProcessStage::Sdo,
+        ProcessStage::Nmt,
+        ProcessStage::Lss,
+        ProcessStage::Pdo,
+        ProcessStage::Total,
+    ];
+}
+
+/// The measured execution time of a single stage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StageTiming {
+    /// The time taken by the most recent call, in microseconds
+    pub last_us: u32,
+    /// The longest time taken since the last reset, in microseconds
+    pub worst_us: u32,
+}
+
+/// Execution times of [`Node::process`](crate::Node::process)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessTiming {
+    stages: [StageTiming; ProcessStage::ALL.len()],
+    budget_us: Option<u32>,
+    budget_overruns: u32,
+}
+
+impl ProcessTiming {
+    /// Get the execution time of a stage
+    pub fn stage(&self, stage: ProcessStage) -> StageTiming {
+        self.stages[stage as usize]
+    }
+
+    /// Get the time budget for a call to process, if one is set
+    pub fn budget_us(&self) -> Option<u32> {
+        self.budget_us
+    }
+
+    /// Get the number of calls to process which took longer than the budget
+    pub fn budget_overruns(&self) -> u32 {
+        self.budget_overruns
+    }
+
+    /// Clear the worst case times and the budget overrun count
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.worst_us = 0;
+        }
+        self.budget_overruns = 0;
+    }
+
+    pub(crate) fn set_budget_us(&mut self, budget_us: Option<u32>) {
+        self.budget_us = budget_us;
+    }
+
+    /// Record the time taken by a stage
+    ///
+    /// Returns true if the stage is [`ProcessStage::Total`] and it has exceeded the budget
+    pub(crate) fn record(&mut self, stage: ProcessStage, elapsed_us: u32) -> bool {
+        let timing = &mut self.stages[stage as usize];
+        timing.last_us = elapsed_us;
+        timing.worst_us = timing.worst_us.max(elapsed_us);
+        let overrun =
+            stage == ProcessStage::Total && self.budget_us.is_some_and(|b| elapsed_us > b);
+        if overrun {
+            self.budget_overruns = self.budget_overruns.saturating_add(1);
+        }
+        overrun
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut timing = ProcessTiming::default();
+        assert!(!timing.record(ProcessStage::Sdo, 30));
+        assert!(!timing.record(ProcessStage::Sdo, 10));
+        assert_eq!(
+            StageTiming {
+                last_us: 10,
+                worst_us: 30
+            },
+            timing.stage(ProcessStage::Sdo)
+        );
+        assert_eq!(StageTiming::default(), timing.stage(ProcessStage::Pdo));
+
+        // Only the total is compared to the budget
+        timing.set_budget_us(Some(20));
+        assert!(!timing.record(ProcessStage::Pdo, 25));
+        assert!(!timing.record(ProcessStage::Total, 20));
+        assert!(timing.record(ProcessStage::Total, 21));
+        assert_eq!(1, timing.budget_overruns());
+
+        timing.reset();
+        assert_eq!(0, timing.budget_overruns());
+        assert_eq!(0, timing.stage(ProcessStage::Sdo).worst_us);
+        assert_eq!(10, timing.stage(ProcessStage::Sdo).last_us);
+        assert_eq!(Some(20), timing.budget_us());
+    }
+}
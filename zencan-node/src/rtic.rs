@@ -0,0 +1,246 @@
+//! Helpers for integrating a [`Node`](crate::Node) into an RTIC application
+//!
+//! The [`NodeMbox`] notification callbacks must be `&'static dyn Fn`, which does not fit well with
+//! RTIC's resource model. Instead, received messages can be passed from the CAN receive interrupt to
+//! the task which owns the node through an [`RxQueue`], and the mailbox flags can be polled with
+//! [`NodeMbox::take_process_pending`] and [`NodeMbox::take_transmit_pending`].
+//!
+//! The queue is split into an [`RxProducer`], which is a local resource of the CAN receive task,
+//! and an [`RxConsumer`], which is a local resource of the process task. Neither requires a lock.
+//!
+//! Setting `rx_queue_size` in the device config generates a [`StaticRxQueue`] named `RX_QUEUE`
+//! alongside the `NODE_MBOX`, which can be split without a `&'static mut`:
+//!
+//! ```
+//! # use zencan_node::{common::CanMessage, priority_queue::PriorityQueue, NodeMbox};
+//! use zencan_node::common::messages::{NmtCommand, NmtCommandSpecifier};
+//! use zencan_node::rtic::StaticRxQueue;
+//!
+//! // Generated when `rx_queue_size = 8` is set in the device config
+//! static RX_QUEUE: StaticRxQueue<9> = StaticRxQueue::new();
+//! # static TX_QUEUE: PriorityQueue<4, CanMessage> = PriorityQueue::new();
+//! # let sdo_buffer = Box::leak(Box::new([0; 64]));
+//! # let node_mbox = NodeMbox::new(&[], &[], &TX_QUEUE, sdo_buffer);
+//!
+//! let (mut rx_producer, mut rx_consumer) = RX_QUEUE.split().unwrap();
+//! // The queue can only be split once
+//! assert!(RX_QUEUE.split().is_none());
+//!
+//! // In the CAN receive interrupt
+//! let msg: CanMessage = NmtCommand { cs: NmtCommandSpecifier::Start, node: 0 }.into();
+//! rx_producer.enqueue(msg).ok();
+//!
+//! // In the process task
+//! if rx_consumer.forward(&node_mbox) {
+//!     // node.process(now_us);
+//! }
+//! ```
+//!
+//! In an RTIC application, the producer and consumer are created in `init`:
+//!
+//! ```ignore
+//! #[rtic::app(device = stm32g4xx_hal::stm32, dispatchers = [USART1])]
+//! mod app {
+//!     use zencan_node::rtic::{RxConsumer, RxProducer};
+//!
+//!     #[shared]
+//!     struct Shared {}
+//!
+//!     #[local]
+//!     struct Local {
+//!         node: Node<'static>,
+//!         can: Can,
+//!         rx_producer: RxProducer<'static>,
+//!         rx_consumer: RxConsumer<'static>,
+//!     }
+//!
+//!     #[init]
+//!     fn init(cx: init::Context) -> (Shared, Local) {
+//!         let node = Node::new(
+//!             NodeId::Unconfigured,
+//!             Callbacks::default(),
+//!             &zencan::NODE_MBOX,
+//!             &zencan::NODE_STATE,
+//!             &zencan::OD_TABLE,
+//!         );
+//!         let (rx_producer, rx_consumer) = zencan::RX_QUEUE.split().unwrap();
+//!         // ...
+//!     }
+//!
+//!     #[task(binds = FDCAN1_INTR0_IT, local = [can, rx_producer], priority = 3)]
+//!     fn can_rx(cx: can_rx::Context) {
+//!         while let Some(msg) = cx.local.can.receive() {
+//!             cx.local.rx_producer.enqueue(msg).ok();
+//!         }
+//!         // Run the process task immediately, rather than waiting for the next period
+//!         rtic::pend(Interrupt::SPI1);
+//!     }
+//!
+//!     #[task(binds = SPI1, local = [node, rx_consumer], priority = 2)]
+//!     fn process(cx: process::Context) {
+//!         cx.local.rx_consumer.forward(&zencan::NODE_MBOX);
+//!         cx.local.node.process(Mono::now().ticks());
+//!         if zencan::NODE_MBOX.take_transmit_pending() {
+//!             can_tx::spawn().ok();
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Without a generated queue, an [`RxQueue`] can be declared as a local resource of `init`, e.g.
+//! `#[init(local = [rx_queue: RxQueue<16> = RxQueue::new()])]`, and split with [`RxQueue::split`].
+//!
+//! The process task should also be pended periodically, e.g. from a timer interrupt, so that
+//! time-based actions such as heartbeat transmission are performed.
+
+use core::{cell::UnsafeCell, sync::atomic::Ordering};
+
+use heapless::spsc::{Consumer, Producer, Queue};
+use portable_atomic::AtomicBool;
+use zencan_common::CanMessage;
+
+use crate::NodeMbox;
+
+/// A queue for passing received messages to the [`NodeMbox`]
+///
+/// The queue can hold up to `N - 1` messages.
+#[allow(missing_debug_implementations)]
+pub struct RxQueue<const N: usize> {
+    queue: Queue<CanMessage, N>,
+}
+
+impl<const N: usize> Default for RxQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RxQueue<N> {
+    /// Create a new, empty queue
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+        }
+    }
+
+    /// Split the queue into a producer for the receive interrupt, and a consumer for the process task
+    pub fn split(&mut self) -> (RxProducer<'_>, RxConsumer<'_>) {
+        let (producer, consumer) = self.queue.split();
+        (RxProducer { producer }, RxConsumer { consumer })
+    }
+}
+
+/// An [`RxQueue`] which can be stored in a static, and split once
+///
+/// This is generated as `RX_QUEUE` when `rx_queue_size` is set in the device config. Unlike
+/// [`RxQueue::split`], [`split`](Self::split) only requires a shared reference, so the halves can be
+/// created wherever they are needed, e.g. in RTIC's `init`. The queue can hold up to `N - 1`
+/// messages.
+#[allow(missing_debug_implementations)]
+pub struct StaticRxQueue<const N: usize> {
+    queue: UnsafeCell<RxQueue<N>>,
+    split: AtomicBool,
+}
+
+// Safety: The queue is only accessed through the producer and consumer, which are created at most
+// once
+unsafe impl<const N: usize> Sync for StaticRxQueue<N> {}
+
+impl<const N: usize> Default for StaticRxQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> StaticRxQueue<N> {
+    /// Create a new, empty queue
+    pub const fn new() -> Self {
+        Self {
+            queue: UnsafeCell::new(RxQueue::new()),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Split the queue into a producer for the receive interrupt, and a consumer for the process task
+    ///
+    /// Returns None if the queue has already been split.
+    pub fn split(&'static self) -> Option<(RxProducer<'static>, RxConsumer<'static>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        // Safety: The flag ensures that this is the only reference to the queue ever created
+        let queue = unsafe { &mut *self.queue.get() };
+        Some(queue.split())
+    }
+}
+
+/// The receiving half of an [`RxQueue`]
+#[allow(missing_debug_implementations)]
+pub struct RxProducer<'a> {
+    producer: Producer<'a, CanMessage>,
+}
+
+impl RxProducer<'_> {
+    /// Add a received message to the queue
+    ///
+    /// If the queue is full, the message is returned in an Err.
+    pub fn enqueue(&mut self, msg: CanMessage) -> Result<(), CanMessage> {
+        self.producer.enqueue(msg)
+    }
+}
+
+/// The forwarding half of an [`RxQueue`]
+#[allow(missing_debug_implementations)]
+pub struct RxConsumer<'a> {
+    consumer: Consumer<'a, CanMessage>,
+}
+
+impl RxConsumer<'_> {
+    /// Store all queued messages in the mailbox
+    ///
+    /// Messages which are not handled by the node are dropped. Returns true if any of the messages
+    /// require a call to [`Node::process`](crate::Node::process).
+    pub fn forward(&mut self, mbox: &NodeMbox) -> bool {
+        while let Some(msg) = self.consumer.dequeue() {
+            mbox.store_message(msg).ok();
+        }
+        mbox.take_process_pending()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::{
+        messages::{NmtCommand, NmtCommandSpecifier},
+        CanId,
+    };
+
+    use super::*;
+    use crate::priority_queue::PriorityQueue;
+
+    #[test]
+    fn test_rx_queue_forward() {
+        let tx_queue = Box::leak(Box::new(PriorityQueue::<4, CanMessage>::new()));
+        let sdo_buffer = Box::leak(Box::new([0u8; 100]));
+        let mbox = NodeMbox::new(&[], &[], tx_queue, sdo_buffer);
+
+        let mut queue = RxQueue::<3>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert!(!consumer.forward(&mbox));
+
+        let nmt: CanMessage = NmtCommand {
+            cs: NmtCommandSpecifier::Start,
+            node: 0,
+        }
+        .into();
+        let other = CanMessage::new(CanId::std(0x123), &[1, 2]);
+        producer.enqueue(other).unwrap();
+        producer.enqueue(nmt).unwrap();
+        // Capacity is N - 1
+        assert_eq!(Err(other), producer.enqueue(other));
+
+        assert!(consumer.forward(&mbox));
+        assert_eq!(1, mbox.diagnostics().rx_messages());
+        assert!(!consumer.forward(&mbox));
+    }
+}
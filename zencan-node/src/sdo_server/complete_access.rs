@@ -0,0 +1,240 @@
+//! Serialization of whole objects for SDO complete access
+//!
+//! A complete access transfer carries the values of all sub objects of a record or array,
+//! concatenated in sub index order, starting at sub 0 or sub 1. As in CANopen over EtherCAT, sub 0
+//! is padded to 16 bits. Sub indices which are not defined on a record are skipped, and every
+//! other sub object takes its full size, with strings padded with zeros.
+//!
+//! Write-only sub objects are read as zeros, and the data for read-only sub objects is ignored
+//! when writing, so that the data read from an object can be modified and written back.
+use zencan_common::{objects::SubInfo, sdo::AbortCode};
+
+use crate::object_dict::ObjectAccess;
+
+/// The size of sub 0 in complete access data
+const SUB0_SIZE: usize = 2;
+
+/// Check that an object may be accessed with complete access, starting at `start_sub`
+fn check_access(obj: &dyn ObjectAccess, start_sub: u8) -> Result<(), AbortCode> {
+    if !obj.supports_complete_access() {
+        return Err(AbortCode::UnsupportedAccess);
+    }
+    if start_sub > 1 {
+        return Err(AbortCode::NoSuchSubIndex);
+    }
+    Ok(())
+}
+
+/// Get the sub objects included in complete access data, for an object with `max_sub` subs
+fn sub_infos(
+    obj: &dyn ObjectAccess,
+    max_sub: u8,
+) -> impl Iterator<Item = Result<(u8, SubInfo), AbortCode>> + '_ {
+    (1..=max_sub).filter_map(|sub| match obj.sub_info(sub) {
+        // Domains have no fixed size, so can't be included
+        Ok(info) if info.size == 0 => Some(Err(AbortCode::UnsupportedAccess)),
+        Ok(info) => Some(Ok((sub, info))),
+        Err(AbortCode::NoSuchSubIndex) => None,
+        Err(abort_code) => Some(Err(abort_code)),
+    })
+}
+
+/// Read the sub objects of an object, starting at `start_sub`, into `buf`
+///
+/// Returns the number of bytes read, or [`AbortCode::OutOfMemory`] if they do not fit.
+pub(crate) fn read_complete(
+    obj: &dyn ObjectAccess,
+    start_sub: u8,
+    buf: &mut [u8],
+) -> Result<usize, AbortCode> {
+    check_access(obj, start_sub)?;
+    let max_sub = obj.read_u8(0)?;
+    let mut pos = 0;
+    if start_sub == 0 {
+        if buf.len() < SUB0_SIZE {
+            return Err(AbortCode::OutOfMemory);
+        }
+        buf[..SUB0_SIZE].copy_from_slice(&(max_sub as u16).to_le_bytes());
+        pos = SUB0_SIZE;
+    }
+    for info in sub_infos(obj, max_sub) {
+        let (sub, info) = info?;
+        let Some(dest) = buf.get_mut(pos..pos + info.size) else {
+            return Err(AbortCode::OutOfMemory);
+        };
+        dest.fill(0);
+        if info.access_type.is_readable() {
+            obj.read(sub, 0, dest)?;
+        }
+        pos += info.size;
+    }
+    Ok(pos)
+}
+
+/// Write the sub objects of an object, starting at `start_sub`, from `data`
+///
+/// The data is checked against the object before any sub object is written. If it starts at sub 0,
+/// the number of sub objects is written first, if it differs from the current value.
+pub(crate) fn write_complete(
+    obj: &dyn ObjectAccess,
+    start_sub: u8,
+    data: &[u8],
+) -> Result<(), AbortCode> {
+    check_access(obj, start_sub)?;
+    let current_max_sub = obj.read_u8(0)?;
+    let (max_sub, mut data) = if start_sub == 0 {
+        if data.len() < SUB0_SIZE {
+            return Err(AbortCode::DataTypeMismatchLengthLow);
+        }
+        let max_sub = u16::from_le_bytes([data[0], data[1]]);
+        let max_sub = u8::try_from(max_sub).map_err(|_| AbortCode::ValueTooHigh)?;
+        (max_sub, &data[SUB0_SIZE..])
+    } else {
+        (current_max_sub, data)
+    };
+
+    let mut size = 0;
+    for info in sub_infos(obj, max_sub) {
+        size += info?.1.size;
+    }
+    if data.len() < size {
+        return Err(AbortCode::DataTypeMismatchLengthLow);
+    } else if data.len() > size {
+        return Err(AbortCode::DataTypeMismatchLengthHigh);
+    }
+
+    if max_sub != current_max_sub {
+        obj.write(0, &[max_sub])?;
+    }
+    for info in sub_infos(obj, max_sub) {
+        let (sub, info) = info?;
+        let (value, rest) = data.split_at(info.size);
+        if info.access_type.is_writable() {
+            obj.write(sub, value)?;
+        }
+        data = rest;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::objects::{AccessType, DataType, ObjectCode, PdoMappable};
+
+    use super::*;
+    use crate::object_dict::{
+        ConstField, NullTermByteField, ProvidesSubObjects, ScalarField, SubObjectAccess,
+    };
+
+    struct TestRecord {
+        complete_access: bool,
+        value: ScalarField<u32>,
+        read_only: ScalarField<u16>,
+        write_only: ScalarField<u8>,
+        name: NullTermByteField<4>,
+    }
+
+    impl TestRecord {
+        fn new(complete_access: bool) -> Self {
+            Self {
+                complete_access,
+                value: ScalarField::<u32>::new(0x12345678),
+                read_only: ScalarField::<u16>::new(0xabcd),
+                write_only: ScalarField::<u8>::new(0x55),
+                name: NullTermByteField::new(*b"ab\0\0"),
+            }
+        }
+    }
+
+    fn info(data_type: DataType, access_type: AccessType, size: usize) -> SubInfo {
+        SubInfo {
+            size,
+            data_type,
+            access_type,
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+        }
+    }
+
+    impl ProvidesSubObjects for TestRecord {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            // Sub 3 is not defined
+            match sub {
+                0 => Some((SubInfo::MAX_SUB_NUMBER, const { &ConstField::new([5]) })),
+                1 => Some((info(DataType::UInt32, AccessType::Rw, 4), &self.value)),
+                2 => Some((info(DataType::UInt16, AccessType::Ro, 2), &self.read_only)),
+                4 => Some((info(DataType::UInt8, AccessType::Wo, 1), &self.write_only)),
+                5 => Some((info(DataType::VisibleString, AccessType::Rw, 4), &self.name)),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Record
+        }
+
+        fn supports_complete_access(&self) -> bool {
+            self.complete_access
+        }
+    }
+
+    #[test]
+    fn test_read_complete() {
+        let record = TestRecord::new(true);
+        let mut buf = [0xff; 16];
+        assert_eq!(Ok(13), read_complete(&record, 0, &mut buf));
+        assert_eq!(
+            [5, 0, 0x78, 0x56, 0x34, 0x12, 0xcd, 0xab, 0, b'a', b'b', 0, 0],
+            buf[..13]
+        );
+        assert_eq!(Ok(11), read_complete(&record, 1, &mut buf));
+        assert_eq!(
+            [0x78, 0x56, 0x34, 0x12, 0xcd, 0xab, 0, b'a', b'b', 0, 0],
+            buf[..11]
+        );
+
+        assert_eq!(
+            Err(AbortCode::OutOfMemory),
+            read_complete(&record, 0, &mut buf[..12])
+        );
+        assert_eq!(
+            Err(AbortCode::NoSuchSubIndex),
+            read_complete(&record, 2, &mut buf)
+        );
+        assert_eq!(
+            Err(AbortCode::UnsupportedAccess),
+            read_complete(&TestRecord::new(false), 0, &mut buf)
+        );
+    }
+
+    #[test]
+    fn test_write_complete() {
+        let record = TestRecord::new(true);
+        let data = [5, 0, 1, 2, 3, 4, 0, 0, 0x11, b'x', b'y', b'z', 0];
+        assert_eq!(Ok(()), write_complete(&record, 0, &data));
+        assert_eq!(0x04030201, record.value.load());
+        // Read-only data is ignored
+        assert_eq!(0xabcd, record.read_only.load());
+        assert_eq!(0x11, record.write_only.load());
+        let mut name = [0; 4];
+        record.name.read(0, &mut name).unwrap();
+        assert_eq!(*b"xyz\0", name);
+
+        assert_eq!(Ok(()), write_complete(&record, 1, &data[2..]));
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthLow),
+            write_complete(&record, 1, &data[3..])
+        );
+        assert_eq!(
+            Err(AbortCode::DataTypeMismatchLengthHigh),
+            write_complete(&record, 0, &[data.as_slice(), &[0]].concat())
+        );
+        // Nothing is written if the data doesn't match the object
+        assert_eq!(0x04030201, record.value.load());
+        // The number of sub objects of a record can't be changed
+        assert_eq!(
+            Err(AbortCode::ReadOnly),
+            write_complete(&record, 0, &[2, 0, 1, 2, 3, 4, 0, 0])
+        );
+    }
+}
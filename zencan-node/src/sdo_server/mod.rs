@@ -1,10 +1,20 @@
+mod complete_access;
 mod sdo_comms;
 mod sdo_server;
+mod standalone;
 
 pub(crate) use sdo_comms::SdoComms;
 pub(crate) use sdo_server::SdoServer;
+pub use sdo_server::{SdoAccess, SdoAccessKind};
+pub use standalone::StandaloneSdoServer;
 
 /// Default size for SDO data buffer
 ///
 /// Enough for 127 segments of 7 bytes each, which is the maximum size of a block transfer
 pub const SDO_BUFFER_SIZE: usize = 889;
+
+/// The number of SDO requests, and responses, which can be queued between process calls
+///
+/// This also caps the request budget set with
+/// [`Node::set_sdo_request_budget`](crate::Node::set_sdo_request_budget).
+pub const SDO_QUEUE_DEPTH: usize = 4;
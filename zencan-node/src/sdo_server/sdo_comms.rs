@@ -1,14 +1,21 @@
 use core::{
+    cell::RefCell,
     ops::{Deref, DerefMut},
     sync::atomic::Ordering,
 };
 
+use critical_section::Mutex;
+use heapless::Deque;
 use portable_atomic::{AtomicU32, AtomicU8};
 use zencan_common::{
+    messages::CanId,
     sdo::{BlockSegment, SdoRequest, SdoResponse},
     AtomicCell,
 };
 
+use super::SDO_QUEUE_DEPTH;
+use crate::verbose_log::verbose_warn;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ReceiverState {
     Normal,
@@ -27,18 +34,18 @@ pub enum ReceiverState {
     BlockSendAborted,
 }
 
-pub struct BufferGuard<'a> {
-    buf: Option<&'static mut [u8]>,
-    home: &'a AtomicCell<Option<&'static mut [u8]>>,
+pub struct BufferGuard<'a, 'b> {
+    buf: Option<&'b mut [u8]>,
+    home: &'a AtomicCell<Option<&'b mut [u8]>>,
 }
 
-impl Drop for BufferGuard<'_> {
+impl Drop for BufferGuard<'_, '_> {
     fn drop(&mut self) {
         self.home.store(Some(self.buf.take().unwrap()));
     }
 }
 
-impl Deref for BufferGuard<'_> {
+impl Deref for BufferGuard<'_, '_> {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
@@ -46,7 +53,7 @@ impl Deref for BufferGuard<'_> {
     }
 }
 
-impl DerefMut for BufferGuard<'_> {
+impl DerefMut for BufferGuard<'_, '_> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.buf.as_mut().unwrap()
     }
@@ -59,35 +66,60 @@ impl DerefMut for BufferGuard<'_> {
 /// segments until they are all received, they may come in faster than process is executed to handle
 /// them.
 ///
+/// Requests and responses are queued, so that a client which sends requests faster than process is
+/// called does not lose them, and so that the server can handle more than one request in a process
+/// call.
+///
+/// A request or response which arrives when its queue is full is dropped, and counted so that the
+/// node can record it in its [`NodeDiagnostics`](crate::diagnostics::NodeDiagnostics).
+///
 /// A timer is also reset to 0 on each message received, and this can be used in `process()` to
 /// implement a timeout in case an expected message is never received.
-pub(crate) struct SdoComms {
-    request: AtomicCell<Option<SdoRequest>>,
-    response: AtomicCell<Option<SdoResponse>>,
+///
+/// Each request is stored along with the COB ID it was received on, and the COB ID of the client
+/// which owns the transfer in progress is tracked, so that the server can detect requests from
+/// another client interleaved with a transfer. The [`NodeMbox`](crate::NodeMbox) only delivers
+/// requests received on the SDO server's RX COB ID, so a node's server sees a single source and
+/// cannot tell clients apart. Only a server which is fed requests with their source, such as
+/// [`fuzz::SdoServerHarness`](crate::fuzz::SdoServerHarness), can receive from more than one.
+///
+/// The buffer is usually a static owned by the [`NodeMbox`](crate::NodeMbox), but any buffer which
+/// outlives the comms may be used, so that an SDO server can be run without statics.
+pub(crate) struct SdoComms<'b> {
+    requests: Mutex<RefCell<Deque<(CanId, SdoRequest), SDO_QUEUE_DEPTH>>>,
+    responses: Mutex<RefCell<Deque<SdoResponse, SDO_QUEUE_DEPTH>>>,
     state: AtomicCell<ReceiverState>,
-    buffer: AtomicCell<Option<&'static mut [u8]>>,
+    buffer: AtomicCell<Option<&'b mut [u8]>>,
     timer: AtomicU32,
     last_seqnum: AtomicU8,
     blksize: AtomicU8,
+    requests_dropped: AtomicU32,
+    responses_dropped: AtomicU32,
+    /// The source of the transfer in progress, if any
+    client: AtomicCell<Option<CanId>>,
 }
 
-impl SdoComms {
-    pub const fn new(sdo_buffer: &'static mut [u8]) -> Self {
+impl<'b> SdoComms<'b> {
+    pub const fn new(sdo_buffer: &'b mut [u8]) -> Self {
         Self {
-            request: AtomicCell::new(None),
-            response: AtomicCell::new(None),
+            requests: Mutex::new(RefCell::new(Deque::new())),
+            responses: Mutex::new(RefCell::new(Deque::new())),
             state: AtomicCell::new(ReceiverState::Normal),
             buffer: AtomicCell::new(Some(sdo_buffer)),
             timer: AtomicU32::new(0),
             last_seqnum: AtomicU8::new(0),
             blksize: AtomicU8::new(0),
+            requests_dropped: AtomicU32::new(0),
+            responses_dropped: AtomicU32::new(0),
+            client: AtomicCell::new(None),
         }
     }
 
     pub fn next_transmit_message(&self) -> Option<[u8; 8]> {
         // Always send a queued response if avaliable
-        if let Some(resp) = self.response.take().map(|resp| resp.to_bytes()) {
-            return Some(resp);
+        let resp = critical_section::with(|cs| self.responses.borrow_ref_mut(cs).pop_front());
+        if let Some(resp) = resp {
+            return Some(resp.to_bytes());
         }
         critical_section::with(|_| {
             match self.state.load() {
@@ -132,17 +164,31 @@ impl SdoComms {
 
     /// Handle received request from client
     ///
+    /// `source` is the COB ID the request was received on.
+    ///
     /// Returns true if the received message demands further handling in a process call
-    pub fn handle_req(&self, msg_data: &[u8]) -> bool {
+    pub fn handle_req(&self, source: CanId, msg_data: &[u8]) -> bool {
         // Ignore invalid lengths
         if msg_data.len() != 8 {
             return false;
         }
 
+        // A message from a client other than the one which owns the transfer can never be part of
+        // that transfer, and is queued as a request so the server can see the interleaving
+        if self.client.load().is_some_and(|client| client != source) {
+            return match msg_data.try_into() {
+                Ok(req) => {
+                    self.push_request(source, req);
+                    true
+                }
+                Err(_) => false,
+            };
+        }
+
         match self.state.load() {
             ReceiverState::Normal => match msg_data.try_into() {
                 Ok(req) => {
-                    self.request.store(Some(req));
+                    self.push_request(source, req);
                     self.timer.store(0, Ordering::Relaxed);
                     true
                 }
@@ -155,7 +201,7 @@ impl SdoComms {
                 // byte, which would correspond to seqnum = 0 if it was a block segment.
                 if msg_data[0] == 0x80 {
                     if let Ok(req) = SdoRequest::try_from(msg_data) {
-                        self.request.store(Some(req));
+                        self.push_request(source, req);
                         self.set_state(ReceiverState::Normal);
                         return true;
                     }
@@ -213,14 +259,14 @@ impl SdoComms {
             }
             ReceiverState::BlockSendCompleted => {
                 if let Ok(req) = msg_data.try_into() {
-                    self.request.store(Some(req));
+                    self.push_request(source, req);
                     self.timer.store(0, Ordering::Relaxed);
                 }
                 true
             }
             ReceiverState::BlockSendAborted => {
                 if let Ok(req) = msg_data.try_into() {
-                    self.request.store(Some(req));
+                    self.push_request(source, req);
                     self.timer.store(0, Ordering::Relaxed);
                 }
                 true
@@ -228,8 +274,68 @@ impl SdoComms {
         }
     }
 
+    /// Clear all queued requests and responses, and return to normal receive state
+    pub(crate) fn reset(&self) {
+        critical_section::with(|cs| {
+            self.requests.borrow_ref_mut(cs).clear();
+            self.responses.borrow_ref_mut(cs).clear();
+            self.timer.store(0, Ordering::Relaxed);
+            self.client.store(None);
+            self.set_state(ReceiverState::Normal);
+        });
+    }
+
+    /// Queue a received request. If the queue is full, the request is dropped and counted.
+    fn push_request(&self, source: CanId, req: SdoRequest) {
+        let result = critical_section::with(|cs| {
+            // Any requests not yet handled and responses not yet sent belong to the aborted
+            // transfer, and the client is no longer expecting them. An abort from another client
+            // does not affect the transfer.
+            let owner = !matches!(self.client.load(), Some(client) if client != source);
+            if owner && matches!(req, SdoRequest::Abort { .. }) {
+                self.requests.borrow_ref_mut(cs).clear();
+                self.responses.borrow_ref_mut(cs).clear();
+            }
+            self.requests.borrow_ref_mut(cs).push_back((source, req))
+        });
+        if result.is_err() {
+            self.requests_dropped.add(1, Ordering::Relaxed);
+            verbose_warn!("SDO request queue full; request dropped");
+        }
+    }
+
+    /// Queue a response for transmission. If the queue is full, the response is dropped and
+    /// counted.
     pub(crate) fn store_response(&self, resp: SdoResponse) {
-        self.response.store(Some(resp));
+        let result = critical_section::with(|cs| self.responses.borrow_ref_mut(cs).push_back(resp));
+        if result.is_err() {
+            self.responses_dropped.add(1, Ordering::Relaxed);
+            verbose_warn!("SDO response queue full; response dropped");
+        }
+    }
+
+    /// Return the number of requests dropped because the queue was full since the last call, and
+    /// reset the count
+    pub(crate) fn take_requests_dropped(&self) -> u32 {
+        self.requests_dropped.swap(0, Ordering::Relaxed)
+    }
+
+    /// Return the number of responses dropped because the queue was full since the last call, and
+    /// reset the count
+    pub(crate) fn take_responses_dropped(&self) -> u32 {
+        self.responses_dropped.swap(0, Ordering::Relaxed)
+    }
+
+    /// Returns true if there is a message waiting to be sent by
+    /// [`next_transmit_message`](Self::next_transmit_message)
+    pub(crate) fn transmit_pending(&self) -> bool {
+        critical_section::with(|cs| !self.responses.borrow_ref(cs).is_empty())
+            || matches!(self.state.load(), ReceiverState::BlockSend { .. })
+    }
+
+    /// Returns true if there is room to queue another response
+    pub(crate) fn response_space_available(&self) -> bool {
+        critical_section::with(|cs| !self.responses.borrow_ref(cs).is_full())
     }
 
     pub(crate) fn set_state(&self, state: ReceiverState) {
@@ -246,7 +352,7 @@ impl SdoComms {
     ///
     /// This function will panic if the buffer has already been borrowed, or if the buffer was never
     /// set via `store_buffer`.
-    pub(crate) fn borrow_buffer(&self) -> BufferGuard<'_> {
+    pub(crate) fn borrow_buffer(&self) -> BufferGuard<'_, 'b> {
         let buf = self.buffer.take();
 
         BufferGuard {
@@ -256,7 +362,42 @@ impl SdoComms {
     }
 
     pub(crate) fn take_request(&self) -> Option<SdoRequest> {
-        self.request.take()
+        critical_section::with(|cs| self.requests.borrow_ref_mut(cs).pop_front())
+            .map(|(_, req)| req)
+    }
+
+    /// Get the source and content of the next pending request without removing it
+    pub(crate) fn peek_request(&self) -> Option<(CanId, SdoRequest)> {
+        critical_section::with(|cs| self.requests.borrow_ref(cs).front().copied())
+    }
+
+    /// Set the client which owns the transfer in progress
+    pub(crate) fn set_client(&self, client: Option<CanId>) {
+        self.client.store(client);
+    }
+
+    /// Get the client which owns the transfer in progress
+    pub(crate) fn client(&self) -> Option<CanId> {
+        self.client.load()
+    }
+
+    /// Returns true if there are received requests waiting to be processed
+    pub(crate) fn request_pending(&self) -> bool {
+        critical_section::with(|cs| !self.requests.borrow_ref(cs).is_empty())
+    }
+
+    /// Returns true if the next pending request is the start of a new transfer
+    pub(crate) fn initiate_request_pending(&self) -> bool {
+        let next = self.peek_request().map(|(_, req)| req);
+        matches!(
+            next,
+            Some(
+                SdoRequest::InitiateDownload { .. }
+                    | SdoRequest::InitiateUpload { .. }
+                    | SdoRequest::InitiateBlockDownload { .. }
+                    | SdoRequest::InitiateBlockUpload { .. }
+            )
+        )
     }
 
     pub(crate) fn begin_block_download(&self, blksize: u8) {
@@ -1,11 +1,12 @@
 use zencan_common::{
-    objects::{DataType, ObjectId, SubInfo},
+    objects::{DataType, ObjectId, SubInfo, OBJECT_STRUCTURE_SUB},
     sdo::{AbortCode, SdoRequest, SdoResponse},
 };
 
 use crate::object_dict::{find_object_entry, ODEntry};
 
-use crate::sdo_server::{sdo_comms::ReceiverState, SdoComms};
+use crate::sdo_server::{complete_access, sdo_comms::ReceiverState, SdoComms, SDO_QUEUE_DEPTH};
+use crate::verbose_log::verbose_info;
 
 /// Size of block transfers Always support max of 127 segments in block transfers. This may have to
 /// adjust to support configurable buffer size
@@ -14,6 +15,63 @@ const BLKSIZE: u8 = 127;
 /// Number of microseconds to wait for a message before timing out an SDO transaction
 const SDO_TIMEOUT_US: u32 = 25000;
 
+/// The direction of an [`SdoAccess`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SdoAccessKind {
+    /// The client read the sub object with an upload
+    Read,
+    /// The client wrote the sub object with a download
+    Write,
+}
+
+/// A record of an SDO transfer, passed to the [`sdo_access`](crate::Callbacks::sdo_access)
+/// callback when the transfer finishes
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SdoAccess {
+    /// Whether the sub object was read or written
+    pub kind: SdoAccessKind,
+    /// The object index
+    pub index: u16,
+    /// The sub index
+    pub sub: u8,
+    /// The number of bytes of object data transferred
+    ///
+    /// For an aborted transfer, this is the number of bytes transferred before the abort.
+    pub len: usize,
+    /// The outcome of the transfer
+    ///
+    /// An abort sent by the client is reported with the client's abort code, or
+    /// [`AbortCode::GeneralError`] if the code is not recognized. A transfer which the client
+    /// abandons by starting another is reported as [`AbortCode::GeneralError`].
+    pub result: Result<(), AbortCode>,
+}
+
+impl SdoAccess {
+    /// Create the record for a transfer started by `req`, if it is an initiate request
+    fn start(req: &SdoRequest) -> Option<Self> {
+        let (kind, index, sub) = match *req {
+            SdoRequest::InitiateDownload { index, sub, .. }
+            | SdoRequest::InitiateBlockDownload { index, sub, .. } => {
+                (SdoAccessKind::Write, index, sub)
+            }
+            SdoRequest::InitiateUpload { index, sub, .. }
+            | SdoRequest::InitiateBlockUpload { index, sub, .. } => {
+                (SdoAccessKind::Read, index, sub)
+            }
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            index,
+            sub,
+            len: 0,
+            result: Ok(()),
+        })
+    }
+}
+
 fn validate_download_size(dl_size: usize, subobj: &SubInfo) -> Result<(), AbortCode> {
     if subobj.size == 0 {
         // Some objects (e.g. domains) do not provide a size, and we simply must write to them and
@@ -41,6 +99,8 @@ struct SdoResult<'a> {
     response: Option<SdoResponse>,
     updated_object: Option<ObjectId>,
     new_state: SdoState<'a>,
+    /// The number of bytes of new object data read or written
+    transferred: usize,
 }
 
 impl<'a> SdoResult<'a> {
@@ -50,6 +110,7 @@ impl<'a> SdoResult<'a> {
             response: None,
             updated_object: None,
             new_state,
+            transferred: 0,
         }
     }
 
@@ -59,6 +120,7 @@ impl<'a> SdoResult<'a> {
             response: None,
             updated_object: None,
             new_state,
+            transferred: 0,
         }
     }
 
@@ -68,6 +130,7 @@ impl<'a> SdoResult<'a> {
             response: Some(SdoResponse::abort(index, sub, abort_code)),
             updated_object: None,
             new_state: SdoState::Idle,
+            transferred: 0,
         }
     }
 
@@ -77,6 +140,7 @@ impl<'a> SdoResult<'a> {
             response: Some(response),
             updated_object: None,
             new_state,
+            transferred: 0,
         }
     }
 
@@ -91,8 +155,14 @@ impl<'a> SdoResult<'a> {
             response: Some(response),
             updated_object: Some(ObjectId { index, sub }),
             new_state,
+            transferred: 0,
         }
     }
+
+    fn with_transferred(mut self, len: usize) -> Self {
+        self.transferred = len;
+        self
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -102,6 +172,12 @@ struct Segmented<'a> {
     toggle_state: bool,
     segment_counter: u32,
     bytes_in_buffer: Option<u32>,
+    /// The total size of an upload, when it was reported to the client before the data was read
+    size: Option<u32>,
+    /// Upload data is being streamed from the object with partial reads
+    partial_read: bool,
+    /// The transfer uses complete access, so all data is buffered and written at once
+    complete_access: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -121,8 +197,11 @@ struct UploadBlock<'a> {
     crc: Option<crc16::State<crc16::XMODEM>>,
     sub: u8,
     blksize: u8,
+    /// Upload data is being streamed from the object with partial reads
+    partial_read: bool,
 }
 
+#[derive(Clone, Copy)]
 enum SdoState<'a> {
     Idle,
     DownloadSegmented(Segmented<'a>),
@@ -133,6 +212,57 @@ enum SdoState<'a> {
     UploadBlock(UploadBlock<'a>),
 }
 
+/// Check that the sub object can be read, and start a partial read on it if it supports it
+///
+/// Returns true if a partial read was started, or false if the object must be read with `read`
+fn begin_upload(obj: &ODEntry, sub: u8) -> Result<bool, AbortCode> {
+    if sub == OBJECT_STRUCTURE_SUB {
+        return Ok(false);
+    }
+    // Checked here for all objects, so that an object implementing `read` without checking its
+    // access type cannot leak write-only data
+    if !obj.data.sub_info(sub)?.access_type.is_readable() {
+        return Err(AbortCode::WriteOnly);
+    }
+    match obj.data.begin_partial_read(sub) {
+        Ok(()) => Ok(true),
+        Err(AbortCode::UnsupportedAccess) => Ok(false),
+        Err(abort_code) => Err(abort_code),
+    }
+}
+
+/// Get the sub info for a download target
+///
+/// The object structure sub is handled by the server for all objects, and is never writable
+fn download_sub_info(obj: &ODEntry, sub: u8) -> Result<SubInfo, AbortCode> {
+    if sub == OBJECT_STRUCTURE_SUB {
+        Ok(SubInfo::OBJECT_STRUCTURE)
+    } else {
+        obj.data.sub_info(sub)
+    }
+}
+
+/// Read upload data from an object, using either partial reads or offset reads
+fn read_upload_data(
+    obj: &ODEntry,
+    sub: u8,
+    partial_read: bool,
+    offset: usize,
+    buf: &mut [u8],
+) -> Result<usize, AbortCode> {
+    if sub == OBJECT_STRUCTURE_SUB {
+        let value = obj.data.object_structure().to_le_bytes();
+        let read_size = buf.len().min(value.len().saturating_sub(offset));
+        buf[..read_size].copy_from_slice(&value[offset..offset + read_size]);
+        return Ok(read_size);
+    }
+    if partial_read {
+        obj.data.read_partial(sub, buf)
+    } else {
+        obj.data.read(sub, offset, buf)
+    }
+}
+
 fn copy_upload_sublock(
     rx: &SdoComms,
     obj: &ODEntry,
@@ -140,6 +270,7 @@ fn copy_upload_sublock(
     sub: u8,
     blksize: u8,
     offset: usize,
+    partial_read: bool,
 ) -> Result<(usize, bool), AbortCode> {
     let mut full_buf = rx.borrow_buffer();
     let len = full_buf.len();
@@ -150,7 +281,7 @@ fn copy_upload_sublock(
         return Err(AbortCode::InvalidBlockSize);
     }
     let buf = &mut full_buf[0..blksize as usize * 7];
-    let read_size = obj.data.read(sub, offset, buf)?;
+    let read_size = read_upload_data(obj, sub, partial_read, offset, buf)?;
 
     // Start the CRC calculations
     if let Some(crc) = crc {
@@ -170,6 +301,37 @@ fn copy_upload_sublock(
 }
 
 impl<'a> SdoState<'a> {
+    /// Get the index and sub of the object being transferred, if any
+    fn object_id(&self) -> Option<(u16, u8)> {
+        match self {
+            SdoState::Idle => None,
+            SdoState::DownloadSegmented(state) | SdoState::UploadSegmented(state) => {
+                Some((state.object.index, state.sub))
+            }
+            SdoState::DownloadBlock(state) | SdoState::EndDownloadBlock(state) => {
+                Some((state.object.index, state.sub))
+            }
+            SdoState::InitiateUploadBlock(state) | SdoState::UploadBlock(state) => {
+                Some((state.object.index, state.sub))
+            }
+        }
+    }
+
+    /// Get the object and sub of a partial read in progress, if any
+    fn partial_read(&self) -> Option<(&'a ODEntry<'a>, u8)> {
+        match self {
+            SdoState::UploadSegmented(state) if state.partial_read => {
+                Some((state.object, state.sub))
+            }
+            SdoState::InitiateUploadBlock(state) | SdoState::UploadBlock(state)
+                if state.partial_read =>
+            {
+                Some((state.object, state.sub))
+            }
+            _ => None,
+        }
+    }
+
     pub fn update(&self, rx: &SdoComms, elapsed_us: u32, od: &'a [ODEntry<'a>]) -> SdoResult<'a> {
         match self {
             SdoState::Idle => Self::idle(od, rx),
@@ -197,6 +359,21 @@ impl<'a> SdoState<'a> {
                 s,
                 index,
                 sub,
+                ca: true,
+                data,
+            } => Self::initiate_complete_download(od, n, e, s, index, sub, data, rx),
+            SdoRequest::InitiateUpload {
+                index,
+                sub,
+                ca: true,
+            } => Self::initiate_complete_upload(od, index, sub, rx),
+            SdoRequest::InitiateDownload {
+                n,
+                e,
+                s,
+                index,
+                sub,
+                ca: false,
                 data,
             } => {
                 let od_entry = match find_object_entry(od, index) {
@@ -205,18 +382,18 @@ impl<'a> SdoState<'a> {
                 };
                 let obj = &od_entry.data;
 
-                let subinfo = match obj.sub_info(sub) {
+                let subinfo = match download_sub_info(od_entry, sub) {
                     Ok(s) => s,
                     Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
                 };
 
+                // Verify that the requested object is writable before accepting any data
+                if !subinfo.access_type.is_writable() {
+                    return SdoResult::abort(index, sub, AbortCode::ReadOnly);
+                }
+
                 if e {
                     // Doing an expedited download
-                    // Verify that the requested object is writable
-                    if !subinfo.access_type.is_writable() {
-                        return SdoResult::abort(index, sub, AbortCode::ReadOnly);
-                    }
-
                     // Verify data size requested by client fits object, and abort if not
                     let dl_size = 4 - n as usize;
                     if let Err(abort_code) = validate_download_size(dl_size, &subinfo) {
@@ -233,6 +410,7 @@ impl<'a> SdoState<'a> {
                         sub,
                         SdoState::Idle,
                     )
+                    .with_transferred(dl_size)
                 } else {
                     // starting a segmented download
                     // If size is provided, verify data size requested by client fits object, and
@@ -250,32 +428,71 @@ impl<'a> SdoState<'a> {
                         toggle_state: false,
                         segment_counter: 0,
                         bytes_in_buffer: Some(0),
+                        size: None,
+                        partial_read: false,
+                        complete_access: false,
                     });
                     SdoResult::response(SdoResponse::download_acknowledge(index, sub), new_state)
                 }
             }
-            SdoRequest::InitiateUpload { index, sub } => {
+            SdoRequest::InitiateUpload {
+                index,
+                sub,
+                ca: false,
+            } => {
                 let od_entry = match find_object_entry(od, index) {
                     Some(x) => x,
                     None => return SdoResult::abort(index, sub, AbortCode::NoSuchObject),
                 };
                 let obj = od_entry.data;
 
+                let partial_read = match begin_upload(od_entry, sub) {
+                    Ok(partial_read) => partial_read,
+                    Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
+                };
+
+                // Objects with a dynamic size may report it before any data is read
+                let known_size = if sub == OBJECT_STRUCTURE_SUB {
+                    None
+                } else {
+                    obj.upload_size(sub)
+                };
+
                 let mut full_buf = rx.borrow_buffer();
                 let len = full_buf.len();
                 // Limit buffer to be a multiple of segment size
                 let buf = &mut full_buf[0..len - (len % 7)];
-                let read_size = match obj.read(sub, 0, buf) {
+                let read_size = match read_upload_data(od_entry, sub, partial_read, 0, buf) {
                     Ok(s) => s,
-                    Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
+                    Err(abort_code) => {
+                        if partial_read {
+                            obj.end_partial_read(sub).ok();
+                        }
+                        return SdoResult::abort(index, sub, abort_code);
+                    }
+                };
+                let read_size = match known_size {
+                    Some(known_size) if read_size > known_size => known_size,
+                    // The object ended before the size it reported
+                    Some(known_size) if read_size < known_size && read_size < buf.len() => {
+                        if partial_read {
+                            obj.end_partial_read(sub).ok();
+                        }
+                        return SdoResult::abort(index, sub, AbortCode::GeneralError);
+                    }
+                    _ => read_size,
                 };
 
                 if read_size <= 4 {
+                    if partial_read {
+                        obj.end_partial_read(sub).ok();
+                    }
                     // Do expedited upload
                     SdoResult::response(
                         SdoResponse::expedited_upload(index, sub, &buf[..read_size]),
                         SdoState::Idle,
                     )
+                    .with_transferred(read_size)
                 } else {
                     // Start a segmented upload
 
@@ -286,19 +503,26 @@ impl<'a> SdoState<'a> {
                     // larger than the buffer, and so the read size may change. If large objects are
                     // written during an SDO transfer, it is possible for the client to receive a
                     // torn read, which is some combination of multiple values.
-                    let ack_size = if read_size == buf.len() {
+                    //
+                    // Objects which report their size up front are the exception, and transfer
+                    // exactly the reported number of bytes.
+                    let bytes_in_buffer = if read_size == buf.len() {
                         None
                     } else {
                         Some(read_size as u32)
                     };
+                    let size = known_size.map(|size| size as u32);
                     SdoResult::response(
-                        SdoResponse::upload_acknowledge(index, sub, ack_size),
+                        SdoResponse::upload_acknowledge(index, sub, bytes_in_buffer.or(size)),
                         SdoState::UploadSegmented(Segmented {
                             object: od_entry,
                             sub,
                             toggle_state: false,
                             segment_counter: 0,
-                            bytes_in_buffer: ack_size,
+                            bytes_in_buffer,
+                            size,
+                            partial_read,
+                            complete_access: false,
                         }),
                     )
                 }
@@ -316,11 +540,15 @@ impl<'a> SdoState<'a> {
                     None => return SdoResult::abort(index, sub, AbortCode::NoSuchObject),
                 };
 
-                let subinfo = match od_entry.data.sub_info(sub) {
+                let subinfo = match download_sub_info(od_entry, sub) {
                     Ok(s) => s,
                     Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
                 };
 
+                if !subinfo.access_type.is_writable() {
+                    return SdoResult::abort(index, sub, AbortCode::ReadOnly);
+                }
+
                 // If size is provided, verify data size requested by client fits object, and
                 // abort if not
                 if s {
@@ -365,6 +593,11 @@ impl<'a> SdoState<'a> {
                     None
                 };
 
+                let partial_read = match begin_upload(od_entry, sub) {
+                    Ok(partial_read) => partial_read,
+                    Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
+                };
+
                 SdoResult::response(
                     SdoResponse::block_upload_acknowledge(index, true, sub, None),
                     SdoState::InitiateUploadBlock(UploadBlock {
@@ -374,13 +607,119 @@ impl<'a> SdoState<'a> {
                         sent_counter: 0,
                         object: od_entry,
                         blksize,
+                        partial_read,
                     }),
                 )
             }
+            // An abort may arrive for a transfer which the server has already finished or timed
+            // out on. Aborts never get a response.
+            SdoRequest::Abort { .. } => SdoResult::no_response(SdoState::Idle),
+            // The client confirms the end of a block upload, which the server has already
+            // completed. No response is sent.
+            SdoRequest::EndBlockUpload => SdoResult::no_response(SdoState::Idle),
             _ => SdoResult::abort(0, 0, AbortCode::InvalidCommandSpecifier),
         }
     }
 
+    /// Handle an initiate download request using complete access
+    #[allow(clippy::too_many_arguments)]
+    fn initiate_complete_download(
+        od: &'a [ODEntry<'a>],
+        n: u8,
+        e: bool,
+        s: bool,
+        index: u16,
+        sub: u8,
+        data: [u8; 4],
+        rx: &SdoComms,
+    ) -> SdoResult<'a> {
+        let od_entry = match find_object_entry(od, index) {
+            Some(x) => x,
+            None => return SdoResult::abort(index, sub, AbortCode::NoSuchObject),
+        };
+
+        if e {
+            let dl_size = 4 - n as usize;
+            if let Err(abort_code) =
+                complete_access::write_complete(od_entry.data, sub, &data[0..dl_size])
+            {
+                return SdoResult::abort(index, sub, abort_code);
+            }
+            SdoResult::response_with_update(
+                SdoResponse::download_acknowledge(index, sub),
+                index,
+                sub,
+                SdoState::Idle,
+            )
+            .with_transferred(dl_size)
+        } else {
+            if !od_entry.data.supports_complete_access() {
+                return SdoResult::abort(index, sub, AbortCode::UnsupportedAccess);
+            }
+            // The data is buffered until the transfer completes, so it has to fit
+            if s && u32::from_le_bytes(data) as usize > rx.borrow_buffer().len() {
+                return SdoResult::abort(index, sub, AbortCode::OutOfMemory);
+            }
+            let new_state = SdoState::DownloadSegmented(Segmented {
+                object: od_entry,
+                sub,
+                toggle_state: false,
+                segment_counter: 0,
+                bytes_in_buffer: Some(0),
+                size: None,
+                partial_read: false,
+                complete_access: true,
+            });
+            SdoResult::response(SdoResponse::download_acknowledge(index, sub), new_state)
+        }
+    }
+
+    /// Handle an initiate upload request using complete access
+    fn initiate_complete_upload(
+        od: &'a [ODEntry<'a>],
+        index: u16,
+        sub: u8,
+        rx: &SdoComms,
+    ) -> SdoResult<'a> {
+        let od_entry = match find_object_entry(od, index) {
+            Some(x) => x,
+            None => return SdoResult::abort(index, sub, AbortCode::NoSuchObject),
+        };
+
+        let mut full_buf = rx.borrow_buffer();
+        let len = full_buf.len();
+        // Limit buffer to be a multiple of segment size
+        let buf = &mut full_buf[0..len - (len % 7)];
+        let read_size = match complete_access::read_complete(od_entry.data, sub, buf) {
+            Ok(s) => s,
+            Err(abort_code) => return SdoResult::abort(index, sub, abort_code),
+        };
+
+        if read_size <= 4 {
+            SdoResult::response(
+                SdoResponse::expedited_upload(index, sub, &buf[..read_size]),
+                SdoState::Idle,
+            )
+            .with_transferred(read_size)
+        } else {
+            // All of the data has been read into the buffer, so the size is known
+            let size = Some(read_size as u32);
+            SdoResult::response(
+                SdoResponse::upload_acknowledge(index, sub, size),
+                SdoState::UploadSegmented(Segmented {
+                    object: od_entry,
+                    sub,
+                    toggle_state: false,
+                    segment_counter: 0,
+                    bytes_in_buffer: size,
+                    size,
+                    partial_read: false,
+                    complete_access: true,
+                }),
+            )
+        }
+    }
+
     fn download_segmented(state: &Segmented<'a>, rx: &SdoComms, elapsed_us: u32) -> SdoResult<'a> {
         let req = match rx.take_request() {
             Some(req) => req,
@@ -424,6 +763,14 @@ impl<'a> SdoState<'a> {
 
                 // See if we need to make this a partial write
                 if buffer_full && (!c || more_bytes_in_message) {
+                    // Complete access data is written in one go, so it must fit in the buffer
+                    if state.complete_access {
+                        return SdoResult::abort(
+                            state.object.index,
+                            state.sub,
+                            AbortCode::OutOfMemory,
+                        );
+                    }
                     if on_first_buffer {
                         if let Err(abort_code) = obj.begin_partial(state.sub) {
                             return SdoResult::abort(state.object.index, state.sub, abort_code);
@@ -457,6 +804,14 @@ impl<'a> SdoState<'a> {
                         if let Err(abort_code) = obj.end_partial(state.sub) {
                             return SdoResult::abort(state.object.index, state.sub, abort_code);
                         }
+                    } else if state.complete_access {
+                        if let Err(abort_code) = complete_access::write_complete(
+                            state.object.data,
+                            state.sub,
+                            &buf[0..buffer_offset + segment_size],
+                        ) {
+                            return SdoResult::abort(state.object.index, state.sub, abort_code);
+                        }
                     } else if let Err(abort_code) =
                         obj.write(state.sub, &buf[0..buffer_offset + segment_size])
                     {
@@ -469,6 +824,7 @@ impl<'a> SdoState<'a> {
                         state.sub,
                         SdoState::Idle,
                     )
+                    .with_transferred(segment_size)
                 } else {
                     // Segments that didn't fit in the buffer get stored to beginning of new buffer
                     if copy_len < segment_size {
@@ -485,6 +841,7 @@ impl<'a> SdoState<'a> {
                         SdoResponse::download_segment_acknowledge(state.toggle_state),
                         new_state,
                     )
+                    .with_transferred(segment_size)
                 }
             }
             SdoRequest::Abort {
@@ -532,27 +889,40 @@ impl<'a> SdoState<'a> {
                 // How far into the current buffer we are
                 let buf_read_offset = total_read_offset % buf.len();
 
-                let segment_size = if let Some(bytes_in_buffer) = state.bytes_in_buffer {
+                let mut segment_size = if let Some(bytes_in_buffer) = state.bytes_in_buffer {
                     bytes_in_buffer as usize - buf_read_offset
                 } else {
                     buf.len() - buf_read_offset
                 }
                 .min(7);
+                if let Some(size) = state.size {
+                    segment_size = segment_size.min(size as usize - total_read_offset);
+                }
                 let mut msg_buf = [0; 7];
                 msg_buf[..segment_size]
                     .copy_from_slice(&buf[buf_read_offset..buf_read_offset + segment_size]);
 
                 let mut c = false;
                 let mut bytes_in_buffer = state.bytes_in_buffer;
-                if state.bytes_in_buffer.is_none() {
+                if state.size == Some((total_read_offset + segment_size) as u32) {
+                    // All of the reported size has been sent
+                    c = true;
+                } else if state.bytes_in_buffer.is_none() {
                     if buf_read_offset + segment_size == buf.len() {
                         // We completed the buffered data. Read again to see if there is more data
                         // to send
-                        let read_size = state
-                            .object
-                            .data
-                            .read(state.sub, total_read_offset + segment_size, buf)
-                            .unwrap();
+                        let read_size = match read_upload_data(
+                            state.object,
+                            state.sub,
+                            state.partial_read,
+                            total_read_offset + segment_size,
+                            buf,
+                        ) {
+                            Ok(read_size) => read_size,
+                            Err(abort_code) => {
+                                return SdoResult::abort(state.object.index, state.sub, abort_code)
+                            }
+                        };
                         if read_size == 0 {
                             // No further data in object, this is the last segment
                             c = true;
@@ -571,15 +941,26 @@ impl<'a> SdoState<'a> {
                     }
                 }
 
+                if c && state
+                    .size
+                    .is_some_and(|size| size as usize != total_read_offset + segment_size)
+                {
+                    // The object ended before the size reported to the client
+                    return SdoResult::abort(
+                        state.object.index,
+                        state.sub,
+                        AbortCode::GeneralError,
+                    );
+                }
+
                 let new_state = if c {
                     SdoState::Idle
                 } else {
                     SdoState::UploadSegmented(Segmented {
-                        object: state.object,
-                        sub: state.sub,
                         toggle_state: !state.toggle_state,
                         segment_counter: state.segment_counter + 1,
                         bytes_in_buffer,
+                        ..*state
                     })
                 };
 
@@ -587,6 +968,7 @@ impl<'a> SdoState<'a> {
                     SdoResponse::upload_segment(state.toggle_state, c, &msg_buf[0..segment_size]),
                     new_state,
                 )
+                .with_transferred(segment_size)
             }
             SdoRequest::Abort {
                 index: _,
@@ -638,6 +1020,7 @@ impl<'a> SdoState<'a> {
                         SdoState::DownloadBlock(*state),
                     )
                 } else {
+                    let mut transferred = 0;
                     let new_state = if complete {
                         // This is the last block, but we can't do anything with it until we get the
                         // end block transfer request because we don't know how many bytes are
@@ -681,6 +1064,7 @@ impl<'a> SdoState<'a> {
                             return SdoResult::abort(state.object.index, state.sub, abort_code);
                         }
 
+                        transferred = write_length;
                         // Prepare to download a new block
                         rx.begin_block_download(BLKSIZE);
                         SdoState::DownloadBlock(DownloadBlock {
@@ -696,6 +1080,7 @@ impl<'a> SdoState<'a> {
                         },
                         new_state,
                     )
+                    .with_transferred(transferred)
                 }
             }
             _ => SdoResult::no_response(SdoState::Idle),
@@ -766,6 +1151,7 @@ impl<'a> SdoState<'a> {
                     state.sub,
                     SdoState::Idle,
                 )
+                .with_transferred(write_len)
             }
             SdoRequest::Abort {
                 index: _,
@@ -797,6 +1183,7 @@ impl<'a> SdoState<'a> {
                         state.sub,
                         state.blksize,
                         offset,
+                        state.partial_read,
                     ) {
                         Ok(result) => result,
                         Err(abort_code) => {
@@ -811,6 +1198,7 @@ impl<'a> SdoState<'a> {
                         last_subblock_size: read_size,
                         ..state
                     }))
+                    .with_transferred(read_size)
                 }
                 SdoRequest::Abort {
                     index: _,
@@ -848,7 +1236,22 @@ impl<'a> SdoState<'a> {
                         SdoRequest::ConfirmBlock { ackseq, blksize } => {
                             let expected_ackseq = state.last_subblock_size.div_ceil(7);
 
-                            if ackseq != expected_ackseq as u8 {
+                            if ackseq != expected_ackseq as u8 && state.partial_read {
+                                // Streamed data cannot be read again, but the last sub block is
+                                // still in the buffer, so it can be re-sent as long as it fits in
+                                // the new block size
+                                if state.last_subblock_size > blksize as usize * 7 {
+                                    return SdoResult::abort(
+                                        state.object.index,
+                                        state.sub,
+                                        AbortCode::InvalidBlockSize,
+                                    );
+                                }
+                                let send_complete =
+                                    state.last_subblock_size != state.blksize as usize * 7;
+                                rx.begin_block_upload(state.last_subblock_size, send_complete);
+                                SdoResult::block_segments_queued(SdoState::UploadBlock(state))
+                            } else if ackseq != expected_ackseq as u8 {
                                 let offset = state.sent_counter - state.last_subblock_size;
 
                                 // Failed to receive all blocks. Re-send subblock. Don't recalc CRC.
@@ -859,6 +1262,7 @@ impl<'a> SdoState<'a> {
                                     state.sub,
                                     blksize,
                                     offset,
+                                    state.partial_read,
                                 ) {
                                     Ok(result) => result,
                                     Err(abort_code) => {
@@ -888,6 +1292,7 @@ impl<'a> SdoState<'a> {
                                     state.sub,
                                     blksize,
                                     offset,
+                                    state.partial_read,
                                 ) {
                                     Ok(result) => result,
                                     Err(abort_code) => {
@@ -909,6 +1314,7 @@ impl<'a> SdoState<'a> {
                                             ..state
                                         },
                                     ))
+                                    .with_transferred(read_size)
                                 } else {
                                     rx.set_state(ReceiverState::Normal);
                                     let n = 7 - (state.last_subblock_size % 7) as u8;
@@ -942,7 +1348,12 @@ impl<'a> SdoState<'a> {
                     SdoResult::no_response(SdoState::UploadBlock(state))
                 }
             }
-            ReceiverState::BlockSendAborted => todo!(),
+            ReceiverState::BlockSendAborted => {
+                // The client sent an abort while segments were being sent
+                rx.take_request();
+                rx.set_state(ReceiverState::Normal);
+                SdoResult::no_response(SdoState::Idle)
+            }
             _ => SdoResult::abort(
                 state.object.index,
                 state.sub,
@@ -959,6 +1370,13 @@ impl<'a> SdoState<'a> {
 /// instantiate multiple instances of `SdoServer` to track each.
 pub(crate) struct SdoServer<'a> {
     state: SdoState<'a>,
+    request_budget: usize,
+    aborts_sent: u32,
+    /// The transfer in progress, reported when it finishes
+    access: Option<SdoAccess>,
+    /// Span covering the transfer in progress
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
 }
 
 impl<'a> SdoServer<'a> {
@@ -966,34 +1384,206 @@ impl<'a> SdoServer<'a> {
     pub fn new() -> Self {
         Self {
             state: SdoState::Idle,
+            request_budget: 1,
+            aborts_sent: 0,
+            access: None,
+            #[cfg(feature = "tracing")]
+            span: None,
         }
     }
 
+    /// Return to idle, abandoning any transfer in progress
+    ///
+    /// The abandoned transfer is not reported.
+    pub fn reset(&mut self) {
+        if let Some((entry, sub)) = self.state.partial_read() {
+            entry.data.end_partial_read(sub).ok();
+        }
+        self.state = SdoState::Idle;
+        self.access = None;
+        #[cfg(feature = "tracing")]
+        if let Some(span) = self.span.take() {
+            span.record("outcome", "reset");
+        }
+    }
+
+    /// Set the maximum number of queued requests which will be handled in a single process call
+    ///
+    /// A value of 0 is treated as 1, and values above [`SDO_QUEUE_DEPTH`] are treated as
+    /// `SDO_QUEUE_DEPTH`.
+    pub fn set_request_budget(&mut self, budget: usize) {
+        self.request_budget = budget.clamp(1, SDO_QUEUE_DEPTH);
+    }
+
+    /// Return the number of abort responses sent since the last call, and reset the count
+    pub fn take_aborts_sent(&mut self) -> u32 {
+        core::mem::take(&mut self.aborts_sent)
+    }
+
     /// Handle incoming SDO requests
     ///
-    /// This will process the request, update server state and the object dictionary accordingly,
-    /// and return a response to be transmitted back to the client, as well the index of the updated
-    /// object when a download is completed.
+    /// This will process pending requests, up to the request budget, update server state and the
+    /// object dictionary accordingly, and queue responses to be transmitted back to the client. It
+    /// returns a flag indicating if messages are pending for transmit, as well the index of the
+    /// last updated object when a download is completed.
+    ///
+    /// `on_access` is called for each upload or download which completes or is aborted.
     pub fn process(
         &mut self,
         comms: &SdoComms,
         elapsed_us: u32,
         od: &'a [ODEntry<'a>],
+        on_access: &mut dyn FnMut(SdoAccess),
     ) -> (bool, Option<ObjectId>) {
-        let result = self.state.update(comms, elapsed_us, od);
-        self.state = result.new_state;
-        if let Some(resp) = result.response {
-            comms.store_response(resp);
+        let mut tx_pending = false;
+        let mut updated_object = None;
+        let mut elapsed_us = elapsed_us;
+        for _ in 0..self.request_budget {
+            if let Some((index, sub)) = self.state.object_id() {
+                let next = comms.peek_request();
+                let source = next.map(|(source, _)| source);
+                if matches!(next, Some((_, SdoRequest::Abort { .. }))) && source != comms.client() {
+                    // An abort from another client cannot refer to this transfer
+                    comms.take_request();
+                } else if source.is_some() && source != comms.client() {
+                    // Another client has interleaved a request with the transfer in progress. The
+                    // transfer is aborted, and the new request is handled from idle.
+                    verbose_info!(
+                        "Aborting SDO transfer of 0x{:x}sub{}: request from another client",
+                        index,
+                        sub
+                    );
+                    comms.store_response(SdoResponse::abort(index, sub, AbortCode::GeneralError));
+                    self.aborts_sent = self.aborts_sent.wrapping_add(1);
+                    tx_pending = true;
+                    comms.set_state(ReceiverState::Normal);
+                    self.finish_access(Err(AbortCode::GeneralError), on_access);
+                    self.reset();
+                } else if comms.initiate_request_pending() {
+                    // A client which gives up on a transfer may send its abort and immediately
+                    // start a new transfer, in which case the abort can be lost if the request
+                    // queue overflows. A new initiate request is taken as an implicit abort of the
+                    // transfer in progress.
+                    comms.set_state(ReceiverState::Normal);
+                    self.finish_access(Err(AbortCode::GeneralError), on_access);
+                    self.reset();
+                }
+            }
+            let request = comms.peek_request().map(|(_, req)| req);
+            if matches!(self.state, SdoState::Idle) {
+                // Any transfer started by the next request belongs to the client which sent it
+                comms.set_client(comms.peek_request().map(|(source, _)| source));
+                self.access = request.as_ref().and_then(SdoAccess::start);
+            }
+            let partial_read = self.state.partial_read();
+            let result = self.state.update(comms, elapsed_us, od);
+            if let Some(access) = self.access.as_mut() {
+                access.len += result.transferred;
+            }
+            // Finish a partial read once the transfer is complete or aborted
+            if let Some((entry, sub)) = partial_read {
+                if result.new_state.partial_read().is_none() {
+                    entry.data.end_partial_read(sub).ok();
+                }
+            }
+            // Time only passes once per process call
+            elapsed_us = 0;
+            self.state = result.new_state;
+            #[cfg(feature = "tracing")]
+            self.trace_transfer(result.response.as_ref());
+            if matches!(self.state, SdoState::Idle) {
+                comms.set_client(None);
+                let outcome = match (&result.response, &request) {
+                    (Some(SdoResponse::Abort { abort_code, .. }), _) => {
+                        Err(AbortCode::try_from(*abort_code).unwrap_or(AbortCode::GeneralError))
+                    }
+                    (_, Some(SdoRequest::Abort { abort_code, .. })) => {
+                        Err(AbortCode::try_from(*abort_code).unwrap_or(AbortCode::GeneralError))
+                    }
+                    _ => Ok(()),
+                };
+                self.finish_access(outcome, on_access);
+            }
+            if let Some(resp) = result.response {
+                if let SdoResponse::Abort {
+                    index,
+                    sub,
+                    abort_code,
+                } = resp
+                {
+                    verbose_info!(
+                        "SDO abort 0x{:x} sent for 0x{:x}sub{}",
+                        abort_code,
+                        index,
+                        sub
+                    );
+                    self.aborts_sent = self.aborts_sent.wrapping_add(1);
+                }
+                comms.store_response(resp);
+            }
+            tx_pending |= result.tx_pending;
+            if result.updated_object.is_some() {
+                updated_object = result.updated_object;
+            }
+            if !comms.request_pending() || !comms.response_space_available() {
+                break;
+            }
+        }
+        (tx_pending, updated_object)
+    }
+
+    /// Report the transfer in progress, if any, with the given outcome
+    fn finish_access(
+        &mut self,
+        result: Result<(), AbortCode>,
+        on_access: &mut dyn FnMut(SdoAccess),
+    ) {
+        if let Some(access) = self.access.take() {
+            on_access(SdoAccess { result, ..access });
+        }
+    }
+
+    /// Open a span when a transfer starts, and close it with the outcome when it returns to idle
+    #[cfg(feature = "tracing")]
+    fn trace_transfer(&mut self, response: Option<&SdoResponse>) {
+        if self.span.is_none() {
+            let object_id = match response {
+                Some(
+                    SdoResponse::ConfirmUpload { index, sub, .. }
+                    | SdoResponse::ConfirmDownload { index, sub }
+                    | SdoResponse::ConfirmBlockDownload { index, sub, .. }
+                    | SdoResponse::ConfirmBlockUpload { index, sub, .. }
+                    | SdoResponse::Abort { index, sub, .. },
+                ) => Some((*index, *sub)),
+                _ => None,
+            };
+            if let Some((index, sub)) = object_id {
+                self.span = Some(tracing::debug_span!(
+                    "sdo_server_transfer",
+                    index = %format_args!("{index:#06x}"),
+                    sub,
+                    outcome = tracing::field::Empty,
+                ));
+            }
+        }
+        if matches!(self.state, SdoState::Idle) {
+            if let Some(span) = self.span.take() {
+                match response {
+                    Some(SdoResponse::Abort { abort_code, .. }) => {
+                        span.record("outcome", format_args!("abort {abort_code:#010x}"))
+                    }
+                    _ => span.record("outcome", "complete"),
+                };
+            }
         }
-        (result.tx_pending, result.updated_object)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::object_dict::{
-        find_object, ByteField, ConstField, NullTermByteField, ObjectAccess as _,
-        ProvidesSubObjects, SubObjectAccess,
+        find_object, ByteField, ConstField, NullTermByteField, ObjectAccess, ProvidesSubObjects,
+        SubObjectAccess,
     };
     use zencan_common::{
         objects::{AccessType, DataType, ObjectCode},
@@ -1001,9 +1591,13 @@ mod tests {
     };
 
     use crate::SDO_BUFFER_SIZE;
+    use zencan_common::messages::CanId;
 
     use super::*;
 
+    /// The COB ID requests are received on
+    const CLIENT: CanId = CanId::Std(0x601);
+
     const SUB1_SIZE: usize = 1200;
     const SUB2_SIZE: usize = 78;
     struct Object1000 {
@@ -1072,8 +1666,8 @@ mod tests {
         const INDEX: u16 = 0x1000;
         const SUB: u8 = 1;
         let mut round_trip = |msg_data: [u8; 8], elapsed| {
-            rx.handle_req(&msg_data);
-            let (_, update_index) = server.process(rx, elapsed, od);
+            rx.handle_req(CLIENT, &msg_data);
+            let (_, update_index) = server.process(rx, elapsed, od, &mut |_| {});
             let resp: Option<SdoResponse> = rx
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
@@ -1111,7 +1705,7 @@ mod tests {
             let msg = BlockSegment {
                 c,
                 seqnum,
-                data: chunk.try_into().unwrap(),
+                data: chunk,
             }
             .to_bytes();
 
@@ -1182,18 +1776,15 @@ mod tests {
         const SUB: u8 = 1;
         const DATA_SIZE: usize = 7 * 3;
         let mut round_trip = |msg_data: [u8; 8], elapsed| {
-            comms.handle_req(&msg_data);
-            let (_, update_index) = server.process(&comms, elapsed, od.table);
+            comms.handle_req(CLIENT, &msg_data);
+            let (_, update_index) = server.process(&comms, elapsed, od.table, &mut |_| {});
             let resp: Option<SdoResponse> = comms
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
             (resp, update_index)
         };
 
-        let mut data = [0; DATA_SIZE];
-        for i in 0..DATA_SIZE {
-            data[i] = i as u8;
-        }
+        let data: [u8; DATA_SIZE] = core::array::from_fn(|i| i as u8);
 
         // Initiate the transfer
         let msg =
@@ -1304,20 +1895,15 @@ mod tests {
 
         let mut round_trip = |msg_data: Option<[u8; 8]>, elapsed| {
             if let Some(msg_data) = msg_data {
-                comms.handle_req(&msg_data);
+                comms.handle_req(CLIENT, &msg_data);
             }
-            let (_, update_index) = server.process(&comms, elapsed, od.table);
+            let (_, update_index) = server.process(&comms, elapsed, od.table, &mut |_| {});
             let resp: Option<SdoResponse> = comms
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
             (resp, update_index)
         };
 
-        let mut data = [0; DATA_SIZE];
-        for i in 0..DATA_SIZE {
-            data[i] = i as u8;
-        }
-
         // Start transfer
         let (resp, index) = round_trip(
             Some(
@@ -1370,9 +1956,9 @@ mod tests {
 
         let mut round_trip = |msg_data: Option<[u8; 8]>, elapsed| {
             if let Some(msg_data) = msg_data {
-                comms.handle_req(&msg_data);
+                comms.handle_req(CLIENT, &msg_data);
             }
-            let (_, update_index) = server.process(&comms, elapsed, od.table);
+            let (_, update_index) = server.process(&comms, elapsed, od.table, &mut |_| {});
             let resp: Option<SdoResponse> = comms
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
@@ -1405,8 +1991,8 @@ mod tests {
         );
 
         // Send the start block command -- no response is expected other than sending block data
-        comms.handle_req(&SdoRequest::StartBlockUpload.to_bytes());
-        server.process(&comms, 0, od.table);
+        comms.handle_req(CLIENT, &SdoRequest::StartBlockUpload.to_bytes());
+        server.process(&comms, 0, od.table, &mut |_| {});
 
         let mut receive_a_block = |size: usize, last_block: bool, block_expect_data: &[u8]| {
             let num_segments = ((size as f64) / 7.0).ceil() as usize;
@@ -1428,16 +2014,17 @@ mod tests {
                 );
             }
             comms.handle_req(
+                CLIENT,
                 &SdoRequest::ConfirmBlock {
                     ackseq: num_segments as u8,
                     blksize: BLKSIZE,
                 }
                 .to_bytes(),
             );
-            server.process(&comms, 0, od.table);
+            server.process(&comms, 0, od.table, &mut |_| {});
         };
 
-        let num_blocks = (write_data.len() + BLKSIZE as usize * 7 - 1) / (BLKSIZE as usize * 7);
+        let num_blocks = write_data.len().div_ceil(BLKSIZE as usize * 7);
         for i in 0..num_blocks {
             let start_idx = i * BLKSIZE as usize * 7;
             let block_size = (write_data.len() - start_idx).min(BLKSIZE as usize * 7);
@@ -1449,7 +2036,7 @@ mod tests {
             );
         }
 
-        server.process(&comms, 0, od.table);
+        server.process(&comms, 0, od.table, &mut |_| {});
 
         let expect_n = 7 - (write_data.len() % 7) as u8;
         let expect_crc = crc16::State::<crc16::XMODEM>::calculate(&write_data);
@@ -1464,6 +2051,11 @@ mod tests {
             ),
             msg
         );
+
+        // The client confirms the end of the transfer, which gets no response
+        comms.handle_req(CLIENT, &SdoRequest::EndBlockUpload.to_bytes());
+        server.process(&comms, 0, od.table, &mut |_| {});
+        assert_eq!(None, comms.next_transmit_message());
     }
 
     /// Test uploading a value with a length of 7
@@ -1480,9 +2072,9 @@ mod tests {
 
         let mut round_trip = |msg_data: Option<[u8; 8]>, elapsed| {
             if let Some(msg_data) = msg_data {
-                comms.handle_req(&msg_data);
+                comms.handle_req(CLIENT, &msg_data);
             }
-            let (_, update_index) = server.process(&comms, elapsed, od.table);
+            let (_, update_index) = server.process(&comms, elapsed, od.table, &mut |_| {});
             let resp: Option<SdoResponse> = comms
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
@@ -1581,9 +2173,9 @@ mod tests {
 
         let mut round_trip = |msg_data: Option<[u8; 8]>, elapsed| {
             if let Some(msg_data) = msg_data {
-                comms.handle_req(&msg_data);
+                comms.handle_req(CLIENT, &msg_data);
             }
-            let (_, update_index) = server.process(&comms, elapsed, od.table);
+            let (_, update_index) = server.process(&comms, elapsed, od.table, &mut |_| {});
             let resp: Option<SdoResponse> = comms
                 .next_transmit_message()
                 .map(|data| data.try_into().unwrap());
@@ -1674,4 +2266,400 @@ mod tests {
         // Test doing a length just larger than the buffer
         do_segmented_upload(SDO_BUFFER_SIZE + 1);
     }
+
+    #[test]
+    fn test_abandoned_transfer() {
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+
+        const INDEX: u16 = 0x1000;
+        const SUB: u8 = 1;
+
+        od.object1000.write(SUB, &[1; 20]).unwrap();
+
+        let mut round_trip = |msg_data: [u8; 8]| {
+            comms.handle_req(CLIENT, &msg_data);
+            server.process(&comms, 0, od.table, &mut |_| {});
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        };
+
+        // An abort received while idle gets no response
+        let resp = round_trip(SdoRequest::abort(INDEX, SUB, AbortCode::GeneralError).to_bytes());
+        assert_eq!(None, resp);
+
+        // Start a segmented upload, and abandon it after the first segment
+        let resp = round_trip(SdoRequest::initiate_upload(INDEX, SUB).to_bytes());
+        assert!(matches!(
+            resp,
+            Some(SdoResponse::ConfirmUpload { e: false, .. })
+        ));
+        let resp = round_trip(SdoRequest::upload_segment_request(false).to_bytes());
+        assert!(matches!(resp, Some(SdoResponse::UploadSegment { .. })));
+
+        // A new upload should start a new transfer from the beginning
+        let resp = round_trip(SdoRequest::initiate_upload(INDEX, SUB).to_bytes());
+        assert!(matches!(
+            resp,
+            Some(SdoResponse::ConfirmUpload { e: false, .. })
+        ));
+        let resp = round_trip(SdoRequest::upload_segment_request(false).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::UploadSegment {
+                t: false,
+                n: 0,
+                c: false,
+                data: [1; 7]
+            }),
+            resp
+        );
+    }
+
+    #[test]
+    fn test_access_aborts() {
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+
+        const INDEX: u16 = 0x1000;
+
+        let mut round_trip = |msg_data: [u8; 8]| {
+            comms.handle_req(CLIENT, &msg_data);
+            server.process(&comms, 0, od.table, &mut |_| {});
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        };
+
+        // Downloads to a read-only sub must be refused at initiation, for all transfer types
+        let resp = round_trip(SdoRequest::initiate_download(INDEX, 0, Some(1)).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, 0, AbortCode::ReadOnly)),
+            resp
+        );
+        let resp = round_trip(SdoRequest::initiate_block_download(INDEX, 0, true, 1).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, 0, AbortCode::ReadOnly)),
+            resp
+        );
+
+        // Accessing a sub which does not exist
+        let resp = round_trip(SdoRequest::initiate_upload(INDEX, 3).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, 3, AbortCode::NoSuchSubIndex)),
+            resp
+        );
+        let resp = round_trip(SdoRequest::initiate_download(INDEX, 3, Some(1)).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, 3, AbortCode::NoSuchSubIndex)),
+            resp
+        );
+
+        // Accessing an object which does not exist
+        let resp = round_trip(SdoRequest::initiate_upload(0x1001, 0).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(0x1001, 0, AbortCode::NoSuchObject)),
+            resp
+        );
+    }
+
+    #[test]
+    fn test_object_structure_sub() {
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+
+        const INDEX: u16 = 0x1000;
+
+        let mut round_trip = |msg_data: [u8; 8]| {
+            comms.handle_req(CLIENT, &msg_data);
+            server.process(&comms, 0, od.table, &mut |_| {});
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        };
+
+        // Sub 0xFF reports the object code, even though the object does not implement it
+        let resp = round_trip(SdoRequest::initiate_upload(INDEX, 0xFF).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::expedited_upload(
+                INDEX,
+                0xFF,
+                &(ObjectCode::Record as u32).to_le_bytes()
+            )),
+            resp
+        );
+
+        let resp = round_trip(SdoRequest::initiate_download(INDEX, 0xFF, Some(4)).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, 0xFF, AbortCode::ReadOnly)),
+            resp
+        );
+    }
+
+    #[test]
+    fn test_write_only_upload() {
+        /// An object which does not check its access type on read
+        struct WriteOnlyObject;
+
+        impl ObjectAccess for WriteOnlyObject {
+            fn read(&self, _sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+                let data = 0x1234u32.to_le_bytes();
+                let len = buf.len().min(data.len().saturating_sub(offset));
+                buf[..len].copy_from_slice(&data[offset..offset + len]);
+                Ok(len)
+            }
+
+            fn read_size(&self, _sub: u8) -> Result<usize, AbortCode> {
+                Ok(4)
+            }
+
+            fn write(&self, _sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+                Ok(())
+            }
+
+            fn object_code(&self) -> ObjectCode {
+                ObjectCode::Var
+            }
+
+            fn sub_info(&self, _sub: u8) -> Result<SubInfo, AbortCode> {
+                Ok(SubInfo::new_u32().wo_access())
+            }
+        }
+
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od: &'static [ODEntry<'static>] = Box::leak(Box::new([ODEntry {
+            index: 0x2000,
+            data: &WriteOnlyObject,
+        }]));
+
+        let mut round_trip = |msg_data: [u8; 8]| {
+            comms.handle_req(CLIENT, &msg_data);
+            server.process(&comms, 0, od, &mut |_| {});
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        };
+
+        let resp = round_trip(SdoRequest::initiate_upload(0x2000, 0).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(0x2000, 0, AbortCode::WriteOnly)),
+            resp
+        );
+        let resp =
+            round_trip(SdoRequest::initiate_block_upload(0x2000, 0, true, 127, 0).to_bytes());
+        assert_eq!(
+            Some(SdoResponse::abort(0x2000, 0, AbortCode::WriteOnly)),
+            resp
+        );
+        let resp = round_trip(SdoRequest::expedited_download(0x2000, 0, &[1, 2, 3, 4]).to_bytes());
+        assert_eq!(Some(SdoResponse::download_acknowledge(0x2000, 0)), resp);
+    }
+
+    #[test]
+    fn test_request_budget() {
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+
+        const INDEX: u16 = 0x1000;
+        const SUB: u8 = 2;
+
+        // The budget is capped at the queue depth
+        server.set_request_budget(100);
+        assert_eq!(SDO_QUEUE_DEPTH, server.request_budget);
+        server.set_request_budget(0);
+        assert_eq!(1, server.request_budget);
+        server.set_request_budget(4);
+
+        // A fast client queues up an entire segmented download before process is called
+        let data = [5u8; 14];
+        comms.handle_req(
+            CLIENT,
+            &SdoRequest::initiate_download(INDEX, SUB, Some(14)).to_bytes(),
+        );
+        comms.handle_req(
+            CLIENT,
+            &SdoRequest::download_segment(false, false, &data[0..7]).to_bytes(),
+        );
+        comms.handle_req(
+            CLIENT,
+            &SdoRequest::download_segment(true, true, &data[7..14]).to_bytes(),
+        );
+
+        let (tx_pending, index) = server.process(&comms, 0, od.table, &mut |_| {});
+        assert!(tx_pending);
+        assert_eq!(Some(INDEX), index.map(|id| id.index));
+
+        let responses: Vec<SdoResponse> = core::iter::from_fn(|| comms.next_transmit_message())
+            .map(|data| data.try_into().unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                SdoResponse::download_acknowledge(INDEX, SUB),
+                SdoResponse::download_segment_acknowledge(false),
+                SdoResponse::download_segment_acknowledge(true),
+            ],
+            responses
+        );
+        assert_eq!(data, od.object1000.sub2.load()[0..14]);
+    }
+
+    #[test]
+    fn test_interleaved_clients() {
+        // A node's NodeMbox only delivers requests from a single COB ID, so this is only reachable
+        // when requests are fed to the server with their source, e.g. by the fuzz harness
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+
+        const OTHER_CLIENT: CanId = CanId::Std(0x602);
+        const INDEX: u16 = 0x1000;
+        const SUB: u8 = 2;
+
+        let mut round_trip = |source: CanId, msg_data: [u8; 8]| {
+            comms.handle_req(source, &msg_data);
+            server.process(&comms, 0, od.table, &mut |_| {});
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        };
+
+        let data = [7u8; 14];
+        let resp = round_trip(
+            CLIENT,
+            SdoRequest::initiate_download(INDEX, SUB, Some(14)).to_bytes(),
+        );
+        assert_eq!(Some(SdoResponse::download_acknowledge(INDEX, SUB)), resp);
+        let resp = round_trip(
+            CLIENT,
+            SdoRequest::download_segment(false, false, &data[0..7]).to_bytes(),
+        );
+        assert_eq!(Some(SdoResponse::download_segment_acknowledge(false)), resp);
+
+        // An abort from another client does not affect the transfer
+        let resp = round_trip(
+            OTHER_CLIENT,
+            SdoRequest::abort(INDEX, SUB, AbortCode::GeneralError).to_bytes(),
+        );
+        assert_eq!(None, resp);
+
+        // Another client starting a transfer aborts the transfer in progress, and is then served
+        let resp = round_trip(
+            OTHER_CLIENT,
+            SdoRequest::initiate_upload(INDEX, 0).to_bytes(),
+        );
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, SUB, AbortCode::GeneralError)),
+            resp
+        );
+        assert_eq!(
+            Some(SdoResponse::expedited_upload(INDEX, 0, &[1])),
+            comms
+                .next_transmit_message()
+                .map(|data| SdoResponse::try_from(data).unwrap())
+        );
+
+        // The rest of the pre-empted transfer is rejected, and the object is not written
+        let resp = round_trip(
+            CLIENT,
+            SdoRequest::download_segment(true, true, &data[7..14]).to_bytes(),
+        );
+        assert!(matches!(
+            resp,
+            Some(SdoResponse::Abort {
+                abort_code,
+                ..
+            }) if abort_code == AbortCode::InvalidCommandSpecifier as u32
+        ));
+        assert_eq!([0; 14], od.object1000.sub2.load()[0..14]);
+
+        // A block download is also pre-empted, and segments from the other client are not written
+        // into the block buffer
+        let resp = round_trip(
+            CLIENT,
+            SdoRequest::initiate_block_download(INDEX, SUB, true, 14).to_bytes(),
+        );
+        assert!(matches!(
+            resp,
+            Some(SdoResponse::ConfirmBlockDownload { .. })
+        ));
+        let resp = round_trip(
+            OTHER_CLIENT,
+            SdoRequest::initiate_upload(INDEX, 0).to_bytes(),
+        );
+        assert_eq!(
+            Some(SdoResponse::abort(INDEX, SUB, AbortCode::GeneralError)),
+            resp
+        );
+        assert_eq!(ReceiverState::Normal, comms.state());
+    }
+
+    #[test]
+    fn test_access_hook() {
+        let buffer = Box::leak(Box::new([0; SDO_BUFFER_SIZE]));
+        let mut server = SdoServer::new();
+        let comms = SdoComms::new(buffer);
+        let od = test_od();
+        let mut accesses = Vec::new();
+
+        const INDEX: u16 = 0x1000;
+
+        let mut round_trip = |msg_data: [u8; 8]| {
+            comms.handle_req(CLIENT, &msg_data);
+            server.process(&comms, 0, od.table, &mut |access| accesses.push(access));
+            comms.next_transmit_message();
+        };
+
+        // A segmented download of 10 bytes
+        round_trip(SdoRequest::initiate_download(INDEX, 1, Some(10)).to_bytes());
+        round_trip(SdoRequest::download_segment(false, false, &[1; 7]).to_bytes());
+        round_trip(SdoRequest::download_segment(true, true, &[2; 3]).to_bytes());
+        // Read it back with a segmented upload
+        round_trip(SdoRequest::initiate_upload(INDEX, 1).to_bytes());
+        round_trip(SdoRequest::upload_segment_request(false).to_bytes());
+        round_trip(SdoRequest::upload_segment_request(true).to_bytes());
+        // Aborted by the server
+        round_trip(SdoRequest::initiate_upload(0x2000, 1).to_bytes());
+        round_trip(SdoRequest::expedited_download(INDEX, 0, &[1]).to_bytes());
+        // Aborted by the client after one segment
+        round_trip(SdoRequest::initiate_download(INDEX, 1, None).to_bytes());
+        round_trip(SdoRequest::download_segment(false, false, &[1; 7]).to_bytes());
+        round_trip(SdoRequest::abort(INDEX, 1, AbortCode::NoData).to_bytes());
+        // Aborts when idle are not transfers
+        round_trip(SdoRequest::abort(INDEX, 1, AbortCode::NoData).to_bytes());
+
+        let access = |kind, index, sub, len, result| SdoAccess {
+            kind,
+            index,
+            sub,
+            len,
+            result,
+        };
+        assert_eq!(
+            vec![
+                access(SdoAccessKind::Write, INDEX, 1, 10, Ok(())),
+                access(SdoAccessKind::Read, INDEX, 1, 10, Ok(())),
+                access(
+                    SdoAccessKind::Read,
+                    0x2000,
+                    1,
+                    0,
+                    Err(AbortCode::NoSuchObject)
+                ),
+                access(SdoAccessKind::Write, INDEX, 0, 0, Err(AbortCode::ReadOnly)),
+                access(SdoAccessKind::Write, INDEX, 1, 7, Err(AbortCode::NoData)),
+            ],
+            accesses
+        );
+    }
 }
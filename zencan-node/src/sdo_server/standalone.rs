@@ -0,0 +1,244 @@
+//! An SDO server which is not attached to a node
+use zencan_common::{
+    messages::{CanId, CanMessage},
+    objects::ObjectId,
+};
+
+use crate::object_dict::ODEntry;
+
+use super::{SdoAccess, SdoComms, SdoServer};
+
+/// An SDO server which owns its state, and can be instantiated without statics
+///
+/// The SDO server run by a [`Node`](crate::Node) receives requests through the generated
+/// [`NodeMbox`](crate::NodeMbox). This runs the same server on frames passed to it directly, with
+/// a buffer provided by the caller, e.g. to serve an object dictionary on a second pair of COB IDs,
+/// or to test an object dictionary without a node.
+///
+/// The simplest use is to call [`handle_frame`](Self::handle_frame) for each received frame. Block
+/// uploads send more than one frame in response to a request, and the remaining frames are read
+/// with [`next_frame`](Self::next_frame).
+#[allow(missing_debug_implementations)]
+pub struct StandaloneSdoServer<'a> {
+    server: SdoServer<'a>,
+    comms: SdoComms<'a>,
+    od: &'a [ODEntry<'a>],
+    rx_id: CanId,
+    tx_id: CanId,
+}
+
+impl<'a> StandaloneSdoServer<'a> {
+    /// Create a new server
+    ///
+    /// # Arguments
+    /// - `rx_id`: The COB ID requests are received on
+    /// - `tx_id`: The COB ID responses are sent on
+    /// - `buffer`: The buffer used for segmented and block transfers. It should normally be
+    ///   [`SDO_BUFFER_SIZE`](crate::SDO_BUFFER_SIZE) bytes.
+    /// - `od`: The object dictionary served
+    pub fn new(rx_id: CanId, tx_id: CanId, buffer: &'a mut [u8], od: &'a [ODEntry<'a>]) -> Self {
+        Self {
+            server: SdoServer::new(),
+            comms: SdoComms::new(buffer),
+            od,
+            rx_id,
+            tx_id,
+        }
+    }
+
+    /// Set the maximum number of queued requests which will be handled in a single process call
+    ///
+    /// A value of 0 is treated as 1, and values above [`SDO_QUEUE_DEPTH`](crate::SDO_QUEUE_DEPTH)
+    /// are treated as `SDO_QUEUE_DEPTH`. The default is 1.
+    pub fn set_request_budget(&mut self, budget: usize) {
+        self.server.set_request_budget(budget);
+    }
+
+    /// Receive a frame, and handle it with no time elapsed
+    ///
+    /// Returns the first response frame to be sent, if any. Frames which are not SDO requests for
+    /// this server are ignored.
+    pub fn handle_frame(&mut self, frame: CanMessage) -> Option<CanMessage> {
+        self.receive(frame).ok()?;
+        self.process(0, &mut |_| {});
+        self.next_frame()
+    }
+
+    /// Queue a received frame, to be handled by the next call to [`process`](Self::process)
+    ///
+    /// Returns the frame as an error if it is not a valid request on the server's receive COB ID.
+    pub fn receive(&mut self, frame: CanMessage) -> Result<(), CanMessage> {
+        if frame.id() != self.rx_id || frame.data().len() != 8 {
+            return Err(frame);
+        }
+        self.receive_data(frame.id(), frame.data());
+        Ok(())
+    }
+
+    /// Queue the data of a request from the client with COB ID `source`, without checking the ID
+    pub(crate) fn receive_data(&mut self, source: CanId, data: &[u8]) {
+        self.comms.handle_req(source, data);
+    }
+
+    /// Handle the queued requests, after `elapsed_us` microseconds have passed since the last call
+    ///
+    /// Returns the object updated by a completed download, if any. `on_access` is called for each
+    /// transfer which completes or is aborted.
+    pub fn process(
+        &mut self,
+        elapsed_us: u32,
+        on_access: &mut dyn FnMut(SdoAccess),
+    ) -> Option<ObjectId> {
+        let (_, updated_object) = self
+            .server
+            .process(&self.comms, elapsed_us, self.od, on_access);
+        updated_object
+    }
+
+    /// Get the next response frame to be sent
+    pub fn next_frame(&mut self) -> Option<CanMessage> {
+        self.next_response_data()
+            .map(|data| CanMessage::new(self.tx_id, &data))
+    }
+
+    /// Get the data of the next response frame to be sent
+    pub(crate) fn next_response_data(&mut self) -> Option<[u8; 8]> {
+        self.comms.next_transmit_message()
+    }
+
+    /// Return the number of abort responses sent since the last call, and reset the count
+    pub fn take_aborts_sent(&mut self) -> u32 {
+        self.server.take_aborts_sent()
+    }
+
+    /// Return to idle, abandoning any transfer in progress
+    pub fn reset(&mut self) {
+        self.server.reset();
+        self.comms.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zencan_common::{
+        objects::{AccessType, DataType, ObjectCode, SubInfo},
+        sdo::{SdoRequest, SdoResponse},
+    };
+
+    use crate::object_dict::{ConstField, NullTermByteField, ProvidesSubObjects, SubObjectAccess};
+    use crate::SDO_BUFFER_SIZE;
+
+    use super::*;
+
+    const RX_ID: CanId = CanId::Std(0x643);
+    const TX_ID: CanId = CanId::Std(0x5C3);
+
+    struct Object2000 {
+        name: NullTermByteField<32>,
+    }
+
+    impl ProvidesSubObjects for Object2000 {
+        fn get_sub_object(&self, sub: u8) -> Option<(SubInfo, &dyn SubObjectAccess)> {
+            match sub {
+                0 => Some((
+                    SubInfo::MAX_SUB_NUMBER,
+                    const { &ConstField::new(1u8.to_le_bytes()) },
+                )),
+                1 => Some((
+                    SubInfo {
+                        size: self.name.len(),
+                        data_type: DataType::VisibleString,
+                        access_type: AccessType::Rw,
+                        ..Default::default()
+                    },
+                    &self.name,
+                )),
+                _ => None,
+            }
+        }
+
+        fn object_code(&self) -> ObjectCode {
+            ObjectCode::Record
+        }
+    }
+
+    fn request(req: SdoRequest) -> CanMessage {
+        CanMessage::new(RX_ID, &req.to_bytes())
+    }
+
+    fn response(frame: Option<CanMessage>) -> SdoResponse {
+        let frame = frame.unwrap();
+        assert_eq!(TX_ID, frame.id());
+        SdoResponse::try_from(frame).unwrap()
+    }
+
+    #[test]
+    fn test_segmented_transfers() {
+        let object = Object2000 {
+            name: NullTermByteField::new([0; 32]),
+        };
+        let od = [ODEntry {
+            index: 0x2000,
+            data: &object,
+        }];
+        let mut buffer = [0; SDO_BUFFER_SIZE];
+        let mut server = StandaloneSdoServer::new(RX_ID, TX_ID, &mut buffer, &od);
+
+        // Frames for other servers are ignored
+        let other = CanMessage::new(
+            CanId::Std(0x601),
+            &SdoRequest::initiate_upload(0x2000, 1).to_bytes(),
+        );
+        assert_eq!(None, server.handle_frame(other));
+        assert_eq!(Err(other), server.receive(other));
+
+        let name = b"standalone";
+        let resp = server.handle_frame(request(SdoRequest::initiate_download(
+            0x2000,
+            1,
+            Some(name.len() as u32),
+        )));
+        assert_eq!(
+            SdoResponse::ConfirmDownload {
+                index: 0x2000,
+                sub: 1
+            },
+            response(resp)
+        );
+        let mut toggle = false;
+        for (i, chunk) in name.chunks(7).enumerate() {
+            let last = (i + 1) * 7 >= name.len();
+            let resp =
+                server.handle_frame(request(SdoRequest::download_segment(toggle, last, chunk)));
+            assert_eq!(
+                SdoResponse::ConfirmDownloadSegment { t: toggle },
+                response(resp)
+            );
+            toggle = !toggle;
+        }
+        assert_eq!(None, server.next_frame());
+
+        let mut read = [0; 10];
+        object.name.read(0, &mut read).unwrap();
+        assert_eq!(name, &read);
+
+        let resp = server.handle_frame(request(SdoRequest::initiate_upload(0x2000, 1)));
+        assert!(matches!(
+            response(resp),
+            SdoResponse::ConfirmUpload { n: 0, e: false, s: true, index: 0x2000, sub: 1, data }
+                if data == (name.len() as u32).to_le_bytes()
+        ));
+    }
+
+    #[test]
+    fn test_abort_count() {
+        let od = [];
+        let mut buffer = [0; SDO_BUFFER_SIZE];
+        let mut server = StandaloneSdoServer::new(RX_ID, TX_ID, &mut buffer, &od);
+
+        let resp = server.handle_frame(request(SdoRequest::initiate_upload(0x2000, 1)));
+        assert!(matches!(response(resp), SdoResponse::Abort { .. }));
+        assert_eq!(1, server.take_aborts_sent());
+        assert_eq!(0, server.take_aborts_sent());
+    }
+}
@@ -11,7 +11,10 @@ use zencan_common::{
     sdo::AbortCode,
 };
 
-use crate::object_dict::ObjectAccess;
+use crate::{
+    abort_codes::{self, check_legacy_write_len},
+    object_dict::ObjectAccess,
+};
 
 /// A callback function type for handling a store objects event
 pub type StoreObjectsCallback =
@@ -54,11 +57,11 @@ impl ObjectAccess for StorageCommandObject {
     fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
         match sub {
             0 => {
-                if offset != 0 || buf.len() != 1 {
-                    Err(AbortCode::DataTypeMismatch)
-                } else {
+                if offset == 0 && !buf.is_empty() {
                     buf[0] = 1;
                     Ok(1)
+                } else {
+                    Ok(0)
                 }
             }
             1 => {
@@ -93,23 +96,19 @@ impl ObjectAccess for StorageCommandObject {
         match sub {
             0 => Err(AbortCode::ReadOnly),
             1 => {
-                if data.len() != 4 {
-                    Err(AbortCode::DataTypeMismatch)
+                check_legacy_write_len(data, 4)?;
+                let value = u32::from_le_bytes(data[0..4].try_into().unwrap());
+                // Magic value ('save') triggering a save
+                if value != SAVE_CMD {
+                    return Err(abort_codes::WRONG_SIGNATURE);
+                }
+                if self.storage_context.store_supported.load(Ordering::Relaxed) {
+                    self.storage_context
+                        .store_flag
+                        .store(true, Ordering::Relaxed);
+                    Ok(())
                 } else {
-                    let value = u32::from_le_bytes(data[0..4].try_into().unwrap());
-                    // Magic value ('save') triggering a save
-                    if value == SAVE_CMD {
-                        if self.storage_context.store_supported.load(Ordering::Relaxed) {
-                            self.storage_context
-                                .store_flag
-                                .store(true, Ordering::Relaxed);
-                            Ok(())
-                        } else {
-                            Err(AbortCode::ResourceNotAvailable)
-                        }
-                    } else {
-                        Err(AbortCode::IncompatibleParameter)
-                    }
+                    Err(AbortCode::ResourceNotAvailable)
                 }
             }
             _ => Err(AbortCode::NoSuchSubIndex),
@@ -117,7 +116,7 @@ impl ObjectAccess for StorageCommandObject {
     }
 
     fn object_code(&self) -> ObjectCode {
-        ObjectCode::Record
+        ObjectCode::Array
     }
 
     fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
@@ -0,0 +1,334 @@
+//! Utilities for testing nodes on a simulated bus
+//!
+//! These allow the firmware for a device to be tested on the host, with its generated object
+//! dictionary, by connecting the node to clients on a [`SimBus`] rather than a CAN controller. They
+//! are the same utilities used by zencan's own integration tests.
+//!
+//! A [`SimBus`] passes messages between the mailboxes of the nodes added to it, and any number of
+//! senders and receivers, which implement the [`AsyncCanSender`] and [`AsyncCanReceiver`] traits
+//! so that they can be used with the zencan-client types. Messages are delivered to nodes as soon
+//! as they are sent, but the messages transmitted by nodes are only delivered when
+//! [`SimBus::flush_mailboxes`] is called.
+//!
+//! [`test_with_background_process`] runs a test task while calling [`Node::process`] and flushing
+//! the bus in the background, which is usually the easiest way to write a test:
+//!
+//! ```ignore
+//! use zencan_client::SdoClient;
+//! use zencan_node::test_util::{test_with_background_process, BusLogger, SimBus};
+//!
+//! #[tokio::test]
+//! async fn test_device_name() {
+//!     let mut bus = SimBus::new();
+//!     bus.add_node(&zencan::NODE_MBOX);
+//!     let mut node = Node::new(
+//!         NodeId::new(1).unwrap(),
+//!         Callbacks::new(),
+//!         &zencan::NODE_MBOX,
+//!         &zencan::NODE_STATE,
+//!         &zencan::OD_TABLE,
+//!     );
+//!     let mut client = SdoClient::new_std(1, bus.new_sender(), bus.new_receiver());
+//!     // Print all messages on the bus at the end of the test
+//!     let _logger = BusLogger::new(bus.new_receiver());
+//!
+//!     let test_task = move |_ctx| async move {
+//!         assert_eq!("My Device", client.read_utf8(0x1008, 0).await.unwrap());
+//!     };
+//!     test_with_background_process(&mut [&mut node], &mut bus, test_task).await;
+//! }
+//! ```
+//!
+//! The node's statics are shared by every test in the binary, so tests which use the same node
+//! should not run in parallel, e.g. by using the `serial_test` crate.
+//!
+//! This module requires the `test-util` feature, and a tokio runtime with the time driver enabled.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use zencan_common::{
+    messages::{CanMessage, ZencanMessage},
+    traits::{AsyncCanReceiver, AsyncCanSender, CanSendError},
+};
+
+use crate::{Node, NodeMbox};
+
+/// A simulated CAN bus connecting node mailboxes with senders and receivers
+///
+/// Cloning the bus creates another handle to the same bus.
+#[derive(Clone, Default)]
+#[allow(missing_debug_implementations)]
+pub struct SimBus<'a> {
+    mailboxes: Arc<Mutex<Vec<&'a NodeMbox>>>,
+    // None node external channels for sending messages to, e.g. test listeners
+    external_channels: Arc<Mutex<Vec<UnboundedSender<CanMessage>>>>,
+}
+
+impl<'a> SimBus<'a> {
+    /// Create a new bus, with no nodes
+    pub fn new() -> Self {
+        Self {
+            mailboxes: Arc::new(Mutex::new(Vec::new())),
+            external_channels: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Deliver all messages waiting in the node mailboxes for transmission
+    ///
+    /// Each message is delivered to every other node on the bus, and to every receiver.
+    pub fn flush_mailboxes(&self) {
+        let mailboxes = self.mailboxes.lock().unwrap();
+        let external_channels = self.external_channels.lock().unwrap();
+
+        for (i, sending_mbox) in mailboxes.iter().enumerate() {
+            while let Some(sent_frame) = sending_mbox.next_transmit_message() {
+                for (j, receiving_mbox) in mailboxes.iter().enumerate() {
+                    if i == j {
+                        // Don't send the message back to the node that sent it
+                        continue;
+                    }
+                    receiving_mbox.store_message(sent_frame).ok();
+                }
+
+                // Send to all non-node listeners
+                for ext in external_channels.iter() {
+                    ext.send(sent_frame).unwrap()
+                }
+            }
+        }
+    }
+
+    /// Connect a node to the bus
+    pub fn add_node(&mut self, mbox: &'a NodeMbox) {
+        let mut mailboxes = self.mailboxes.lock().unwrap();
+        mailboxes.push(mbox);
+    }
+
+    /// Create a receiver for all messages sent on the bus
+    ///
+    /// The receiver must be kept until the bus is no longer used, as sending panics when a
+    /// receiver has been dropped.
+    pub fn new_receiver(&mut self) -> SimBusReceiver {
+        let (tx, rx) = unbounded_channel();
+        self.external_channels.lock().unwrap().push(tx);
+        SimBusReceiver { channel_rx: rx }
+    }
+
+    /// Create a sender for sending messages on the bus
+    pub fn new_sender(&mut self) -> SimBusSender<'a> {
+        SimBusSender {
+            node_states: self.mailboxes.clone(),
+            external_channels: self.external_channels.clone(),
+        }
+    }
+}
+
+/// Sends messages on a [`SimBus`]
+///
+/// Messages are delivered immediately to all nodes and receivers on the bus.
+#[allow(missing_debug_implementations)]
+pub struct SimBusSender<'a> {
+    node_states: Arc<Mutex<Vec<&'a NodeMbox>>>,
+    external_channels: Arc<Mutex<Vec<UnboundedSender<CanMessage>>>>,
+}
+
+/// Create an error type for sim bus sender.
+///
+/// The sender can't fail, so this type is never instantiated
+#[derive(Debug)]
+pub struct SimBusSendError(());
+
+impl CanSendError for SimBusSendError {
+    fn into_can_message(self) -> CanMessage {
+        panic!("uninstantiable")
+    }
+
+    fn message(&self) -> String {
+        String::new()
+    }
+}
+
+impl AsyncCanSender for SimBusSender<'_> {
+    type Error = SimBusSendError;
+    async fn send(&mut self, msg: CanMessage) -> Result<(), SimBusSendError> {
+        // Send to nodes on the bus
+        for ns in self.node_states.lock().unwrap().iter() {
+            // It doesn't matter if store message fails; that just means the node did not
+            // recognize/accept the message
+            ns.store_message(msg).ok();
+        }
+        // Send to external listeners on the bus (those created by `new_receiver()``)
+        for rx in self.external_channels.lock().unwrap().iter() {
+            rx.send(msg).unwrap();
+        }
+
+        Ok(())
+    }
+}
+
+/// Receives all messages sent on a [`SimBus`]
+#[allow(missing_debug_implementations)]
+pub struct SimBusReceiver {
+    channel_rx: UnboundedReceiver<CanMessage>,
+}
+
+impl SimBusReceiver {
+    /// Discard all received messages
+    pub fn flush(&mut self) {
+        while self.channel_rx.try_recv().is_ok() {}
+    }
+}
+
+impl AsyncCanReceiver for SimBusReceiver {
+    type Error = ();
+
+    async fn recv(&mut self) -> Result<CanMessage, Self::Error> {
+        self.channel_rx.recv().await.ok_or(())
+    }
+
+    fn try_recv(&mut self) -> Option<CanMessage> {
+        self.channel_rx.try_recv().ok()
+    }
+
+    fn flush(&mut self) {
+        while self.channel_rx.try_recv().is_ok() {}
+    }
+}
+
+/// Passed to the test task, to synchronize with the background process loop
+#[allow(missing_debug_implementations)]
+pub struct TestContext {
+    channel_rx: tokio::sync::mpsc::Receiver<()>,
+}
+
+impl TestContext {
+    /// Wait for node process to be called n times
+    pub async fn wait_for_process(&mut self, n: usize) {
+        // Flush the channel
+        while self.channel_rx.try_recv().is_ok() {}
+        // Wait for n cycle notices
+        for _ in 0..n {
+            self.channel_rx.recv().await;
+        }
+    }
+}
+
+/// Run a test task, while processing nodes on a bus in the background
+///
+/// Every 50us, [`Node::process`] is called on each node with the time since the test started, and
+/// the bus mailboxes are flushed. Returns the output of the test task once it completes.
+pub async fn test_with_background_process<F, T, Fut>(
+    nodes: &mut [&mut Node<'_>],
+    bus: &mut SimBus<'_>,
+    test_task: F,
+) -> T
+where
+    F: (FnOnce(TestContext) -> Fut) + 'static,
+    Fut: Future<Output = T>,
+{
+    // Call process once, to make sure the node is initialized before SDO requests come in
+    for node in nodes.iter_mut() {
+        node.process(0);
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+
+    let epoch = Instant::now();
+    let node_process_task = async move {
+        loop {
+            let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
+            tokio::time::sleep(tokio::time::Duration::from_micros(50)).await;
+            for node in nodes.iter_mut() {
+                node.process(now_us);
+                // Service tx mailboxes
+                bus.flush_mailboxes();
+                // Send notice to the TestContext that the process cycle has been executed
+                tx.try_send(()).ok();
+            }
+        }
+    };
+
+    let ctx = TestContext { channel_rx: rx };
+    tokio::select! {
+        _ = node_process_task => panic!("Node process task exited"),
+        test_result = test_task(ctx) => test_result
+    }
+}
+
+/// Like [`test_with_background_process`], but for nodes which are each on their own bus
+pub async fn test_with_background_buses<F, T, Fut>(
+    channels: &mut [(&mut Node<'_>, &mut SimBus<'_>)],
+    test_task: F,
+) -> T
+where
+    F: (FnOnce(TestContext) -> Fut) + 'static,
+    Fut: Future<Output = T>,
+{
+    for (node, bus) in channels.iter_mut() {
+        node.process(0);
+        bus.flush_mailboxes();
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+
+    let epoch = Instant::now();
+    let node_process_task = async move {
+        loop {
+            let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
+            tokio::time::sleep(tokio::time::Duration::from_micros(50)).await;
+            for (node, bus) in channels.iter_mut() {
+                node.process(now_us);
+                bus.flush_mailboxes();
+            }
+            tx.try_send(()).ok();
+        }
+    };
+
+    let ctx = TestContext { channel_rx: rx };
+    tokio::select! {
+        _ = node_process_task => panic!("Node process task exited"),
+        test_result = test_task(ctx) => test_result
+    }
+}
+
+/// Prints the messages sent on a bus when it is dropped
+///
+/// Creating a logger at the start of a test prints the bus history when the test ends, including
+/// when it fails, which helps to find the cause of the failure.
+#[allow(missing_debug_implementations)]
+pub struct BusLogger {
+    rx: SimBusReceiver,
+}
+
+impl BusLogger {
+    /// Create a logger from a receiver on the bus
+    pub fn new(rx: SimBusReceiver) -> Self {
+        Self { rx }
+    }
+
+    /// Print the messages received since the last call
+    pub fn print(&mut self) {
+        println!("Bus message history");
+        println!("-------------------");
+        while let Some(msg) = self.rx.try_recv() {
+            let parsed_msg: Result<ZencanMessage, _> = msg.try_into();
+
+            if let Ok(msg) = parsed_msg {
+                println!("{:?}", msg);
+            } else {
+                println!("{:?}", msg);
+            }
+        }
+    }
+}
+
+impl Drop for BusLogger {
+    fn drop(&mut self) {
+        self.print();
+    }
+}
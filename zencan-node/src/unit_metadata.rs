@@ -0,0 +1,140 @@
+//! Engineering unit metadata object
+//!
+//! When `unit_metadata = true` is set in the device config, zencan-build encodes the `unit`,
+//! `scale` and `offset` declared on objects in the device config into a table, and creates a
+//! [`UnitMetadataObject`] to serve it as the read-only domain object 0x5F03. A client can read the
+//! table to convert raw object values to engineering values without access to the device config.
+//!
+//! The encoding of the table is defined in [`zencan_common::unit_metadata`]. This module requires
+//! the `unit-metadata` feature.
+
+use zencan_common::{
+    objects::{AccessType, DataType, ObjectCode, PdoMappable, SubInfo},
+    sdo::AbortCode,
+    AtomicCell,
+};
+
+use crate::object_dict::ObjectAccess;
+
+/// Implements the unit metadata object (0x5F03)
+///
+/// A read-only domain object containing a constant, pre-encoded table
+#[allow(missing_debug_implementations)]
+pub struct UnitMetadataObject {
+    table: &'static [u8],
+    /// Position of an in-progress partial read
+    read_cursor: AtomicCell<Option<usize>>,
+}
+
+impl UnitMetadataObject {
+    /// Create a new unit metadata object serving `table`
+    pub const fn new(table: &'static [u8]) -> Self {
+        Self {
+            table,
+            read_cursor: AtomicCell::new(None),
+        }
+    }
+
+    fn copy_out(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let remaining = self.table.get(offset..).unwrap_or(&[]);
+        let len = buf.len().min(remaining.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        len
+    }
+}
+
+impl ObjectAccess for UnitMetadataObject {
+    fn read(&self, sub: u8, offset: usize, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(self.copy_out(offset, buf))
+    }
+
+    fn read_size(&self, sub: u8) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(self.table.len())
+    }
+
+    fn upload_size(&self, sub: u8) -> Option<usize> {
+        (sub == 0).then_some(self.table.len())
+    }
+
+    fn write(&self, sub: u8, _data: &[u8]) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Err(AbortCode::ReadOnly)
+    }
+
+    fn begin_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        self.read_cursor.store(Some(0));
+        Ok(())
+    }
+
+    fn read_partial(&self, sub: u8, buf: &mut [u8]) -> Result<usize, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        let pos = self.read_cursor.load().ok_or(AbortCode::GeneralError)?;
+        let len = self.copy_out(pos, buf);
+        self.read_cursor.store(Some(pos + len));
+        Ok(len)
+    }
+
+    fn end_partial_read(&self, sub: u8) -> Result<(), AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        self.read_cursor.store(None);
+        Ok(())
+    }
+
+    fn object_code(&self) -> ObjectCode {
+        ObjectCode::Var
+    }
+
+    fn sub_info(&self, sub: u8) -> Result<SubInfo, AbortCode> {
+        if sub != 0 {
+            return Err(AbortCode::NoSuchSubIndex);
+        }
+        Ok(SubInfo {
+            size: self.table.len(),
+            data_type: DataType::Domain,
+            access_type: AccessType::Ro,
+            pdo_mapping: PdoMappable::None,
+            persist: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_read() {
+        static TABLE: [u8; 5] = [1, 2, 3, 4, 5];
+        let object = UnitMetadataObject::new(&TABLE);
+
+        let mut buf = [0; 3];
+        assert_eq!(Ok(5), object.read_size(0));
+        assert_eq!(Ok(2), object.read(0, 3, &mut buf));
+        assert_eq!([4, 5], buf[..2]);
+
+        object.begin_partial_read(0).unwrap();
+        assert_eq!(Ok(3), object.read_partial(0, &mut buf));
+        assert_eq!([1, 2, 3], buf);
+        assert_eq!(Ok(2), object.read_partial(0, &mut buf));
+        assert_eq!(Ok(0), object.read_partial(0, &mut buf));
+        object.end_partial_read(0).unwrap();
+
+        assert_eq!(Err(AbortCode::ReadOnly), object.write(0, &[0]));
+        assert_eq!(Err(AbortCode::NoSuchSubIndex), object.read(1, 0, &mut buf));
+    }
+}
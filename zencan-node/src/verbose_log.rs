@@ -0,0 +1,43 @@
+//! Logging macros for node events which are only compiled in with the `log-verbose` feature
+//!
+//! These are used for events which are useful when debugging a node's interaction with the bus,
+//! such as SDO aborts and mailbox overruns, but which flash-constrained builds may want to leave
+//! out. Without the feature, the arguments are still borrowed, so that variables used only for
+//! logging do not cause unused warnings, but no format strings are included in the build.
+
+macro_rules! verbose_log {
+    ($level:ident, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "log-verbose")]
+        defmt_or_log::$level!($fmt $(, $arg)*);
+        #[cfg(not(feature = "log-verbose"))]
+        {
+            $(let _ = &$arg;)*
+        }
+    }};
+}
+
+/// Log a debug message when the `log-verbose` feature is enabled
+macro_rules! verbose_debug {
+    ($($tt:tt)*) => {
+        $crate::verbose_log::verbose_log!(debug, $($tt)*)
+    };
+}
+
+/// Log an info message when the `log-verbose` feature is enabled
+macro_rules! verbose_info {
+    ($($tt:tt)*) => {
+        $crate::verbose_log::verbose_log!(info, $($tt)*)
+    };
+}
+
+/// Log a warning when the `log-verbose` feature is enabled
+macro_rules! verbose_warn {
+    ($($tt:tt)*) => {
+        $crate::verbose_log::verbose_log!(warn, $($tt)*)
+    };
+}
+
+pub(crate) use verbose_debug;
+pub(crate) use verbose_info;
+pub(crate) use verbose_log;
+pub(crate) use verbose_warn;